@@ -14,4 +14,8 @@ pub struct Cli {
     /// Output directory
     #[arg(long, short = 'O')]
     pub out_dir: PathBuf,
+
+    /// Number of blocks kept in the LRU block cache while packing
+    #[arg(long, default_value_t = 64)]
+    pub cache_blocks: usize,
 }
@@ -10,13 +10,14 @@ use std::sync::Mutex;
 
 use clap::Parser;
 use cli::Cli;
-use easy_fs::EasyFileSystem;
+use easy_fs::{block_cache, EasyFileSystem, LruBlockCache};
 use easy_fs_fuse::BlockFile;
 
 fn main() -> io::Result<()> {
     env_logger::init();
     let cli = Cli::parse();
     println!("source={:?}\ntarget={:?}", cli.source, cli.target);
+    easy_fs::set_block_cache(Arc::new(LruBlockCache::new(cli.cache_blocks)));
 
     let block_file = Arc::new(BlockFile(Mutex::new({
         let fd = OpenOptions::new()
@@ -30,7 +31,7 @@ fn main() -> io::Result<()> {
         fd
     })));
 
-    let efs = EasyFileSystem::new(block_file, 16 * 2048, 1);
+    let efs = EasyFileSystem::new(block_file, 1);
     let root_inode = Arc::new(EasyFileSystem::root_inode(&efs));
 
     let apps = fs::read_dir(&cli.source)?
@@ -56,5 +57,14 @@ fn main() -> io::Result<()> {
         inode.write_at(0, &elf_data);
     }
 
+    let stats = block_cache::stats();
+    println!(
+        "block cache: {} blocks, {} hits, {} misses ({}% hit)",
+        cli.cache_blocks,
+        stats.hits,
+        stats.misses,
+        stats.hit_percent()
+    );
+
     Ok(())
 }
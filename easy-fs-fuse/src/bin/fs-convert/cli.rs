@@ -0,0 +1,43 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// FAT directory that holds the files of the flat easy-fs root
+    #[arg(long, short, global = true, default_value = "usr/bin")]
+    pub dir: String,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Convert an easy-fs image into a FAT32 image
+    ToFat {
+        /// easy-fs image to read
+        input: PathBuf,
+
+        /// FAT32 image to write
+        output: PathBuf,
+    },
+
+    /// Convert a FAT32 image into an easy-fs image
+    ToEasyFs {
+        /// FAT32 image to read
+        input: PathBuf,
+
+        /// easy-fs image to write
+        output: PathBuf,
+    },
+
+    /// Check an easy-fs image for corrupt entries, orphan inodes and leaked blocks
+    Check {
+        /// easy-fs image to check
+        image: PathBuf,
+
+        /// Drop corrupt entries and free orphan inodes and leaked blocks
+        #[arg(long)]
+        repair: bool,
+    },
+}
@@ -0,0 +1,89 @@
+//! Convert between easy-fs and FAT32 images.
+//!
+//! easy-fs has a single flat root directory, which maps to one FAT directory
+//! (`/usr/bin` by default), see [`easy_fs_fuse::convert`].
+
+mod cli;
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use block_dev::BlockDevice;
+use clap::Parser;
+use cli::{Cli, Command};
+use easy_fs::EasyFileSystem;
+use easy_fs_fuse::convert::{efs_to_fat, fat_to_efs, invalid_image};
+use easy_fs_fuse::BlockFile;
+
+/// Blocks of a new easy-fs image, the same as easy-fs-packer
+const EFS_BLOCKS: u32 = 16 * 2048;
+/// Size of a new FAT32 image, the same as fat-fuse
+const FAT_DISK_SIZE: u64 = 4 << 30;
+
+fn main() -> io::Result<()> {
+    env_logger::init();
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::ToFat { input, output } => to_fat(&input, &output, &cli.dir),
+        Command::ToEasyFs { input, output } => to_easy_fs(&input, &output, &cli.dir),
+        Command::Check { image, repair } => check(&image, repair),
+    }
+}
+
+fn to_fat(input: &Path, output: &Path, dir: &str) -> io::Result<()> {
+    efs_to_fat(
+        open_image(input)?,
+        &create_image(output, FAT_DISK_SIZE)?,
+        dir,
+    )
+}
+
+fn to_easy_fs(input: &Path, output: &Path, dir: &str) -> io::Result<()> {
+    let fat_dev = open_image(input)?;
+    let efs_dev = create_image(output, EFS_BLOCKS as u64 * easy_fs::BLOCK_SIZE as u64)?;
+    fat_to_efs(&fat_dev, efs_dev, dir)
+}
+
+fn check(image: &Path, repair: bool) -> io::Result<()> {
+    let block_dev = Arc::new(BlockFile(Mutex::new(
+        OpenOptions::new().read(true).write(repair).open(image)?,
+    )));
+    let efs = EasyFileSystem::open(block_dev).map_err(invalid_image)?;
+    let report = EasyFileSystem::check(&efs, repair);
+
+    for (dir, offset) in &report.corrupt_entries {
+        println!("corrupt entry at offset {offset} of directory inode {dir}");
+    }
+    for inode in &report.orphan_inodes {
+        println!("orphan inode {inode}");
+    }
+    if !report.leaked_blocks.is_empty() {
+        println!("{} leaked blocks", report.leaked_blocks.len());
+    }
+
+    match (report.is_clean(), repair) {
+        (true, _) => println!("clean"),
+        (false, true) => println!("repaired"),
+        (false, false) => return Err(io::Error::other("image is inconsistent")),
+    }
+    Ok(())
+}
+
+fn open_image(path: &Path) -> io::Result<Arc<dyn BlockDevice>> {
+    Ok(Arc::new(BlockFile(Mutex::new(File::open(path)?))))
+}
+
+fn create_image(path: &Path, size: u64) -> io::Result<Arc<dyn BlockDevice>> {
+    let fd = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    fd.set_len(size)?;
+
+    Ok(Arc::new(BlockFile(Mutex::new(fd))))
+}
@@ -0,0 +1,18 @@
+use clap::Parser;
+use std::path::PathBuf;
+
+/// Pack the user applications into a compressed read-only squash-fs image
+#[derive(Parser)]
+pub struct Cli {
+    /// Executable source directory
+    #[arg(long, short)]
+    pub source: PathBuf,
+
+    /// Executable target directory
+    #[arg(long, short)]
+    pub target: PathBuf,
+
+    /// Output directory, the image is written to `apps.img`
+    #[arg(long, short = 'O')]
+    pub out_dir: PathBuf,
+}
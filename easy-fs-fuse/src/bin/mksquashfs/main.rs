@@ -0,0 +1,42 @@
+mod cli;
+
+use std::fs;
+use std::io;
+
+use clap::Parser;
+use cli::Cli;
+use squash_fs::Builder;
+
+fn main() -> io::Result<()> {
+    env_logger::init();
+    let cli = Cli::parse();
+    println!("source={:?}\ntarget={:?}", cli.source, cli.target);
+
+    let mut apps = fs::read_dir(&cli.source)?
+        .map(|app| {
+            app.map(|app| {
+                app.file_name()
+                    .to_str()
+                    .and_then(|fname| fname.split_once('.'))
+                    .expect("source file name doesn't match `*.rs`")
+                    .0
+                    .to_owned()
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    // 按名字排序，相同的输入总是生成相同的镜像
+    apps.sort();
+
+    let mut builder = Builder::new();
+    let mut raw_size = 0;
+    for app in apps {
+        println!("program: {app:?}");
+        let elf_data = fs::read(cli.target.join(&app))?;
+        raw_size += elf_data.len();
+        builder.add_file(builder.root(), &app, elf_data);
+    }
+
+    let image = builder.build();
+    println!("packed {raw_size} bytes into {} bytes", image.len());
+    fs::write(cli.out_dir.join("apps.img"), image)
+}
@@ -0,0 +1,111 @@
+//! Copying files between easy-fs and FAT, the core of fs-convert.
+//!
+//! easy-fs has a single flat root directory, which maps to one FAT directory.
+//! Subdirectories of that FAT directory are skipped.
+
+use std::io;
+use std::sync::Arc;
+
+use block_dev::BlockDevice;
+use easy_fs::{EasyFileSystem, EfsError};
+use fat::{FatFileSystem, Inode, ROOT};
+use vfs::DirEntryType;
+
+/// Formats `fat_dev` as FAT32 and copies every file of the easy-fs image on
+/// `efs_dev` into the FAT directory `dir`
+pub fn efs_to_fat(
+    efs_dev: Arc<dyn BlockDevice>,
+    fat_dev: &Arc<dyn BlockDevice>,
+    dir: &str,
+) -> io::Result<()> {
+    let efs = EasyFileSystem::open(efs_dev).map_err(invalid_image)?;
+    let efs_root = EasyFileSystem::root_inode(&efs);
+
+    let mut fs = FatFileSystem::foramt(fat_dev);
+    let fat_dir = dir
+        .split('/')
+        .filter(|cmp| !cmp.is_empty())
+        .try_fold(ROOT.clone(), |parent, cmp| parent.mkdir(cmp, &mut fs))
+        .map_err(|err| io::Error::other(format!("mkdir {dir}: {err:?}")))?;
+
+    for name in efs_root.ls() {
+        let inode = efs_root.find(&name).unwrap();
+        let mut data = Vec::new();
+        let mut buf = [0; easy_fs::BLOCK_SIZE];
+        loop {
+            let read = inode.read_at(data.len(), &mut buf);
+            if read == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..read]);
+        }
+
+        println!("{name}: {} bytes", data.len());
+        let mut file = fat_dir
+            .create_file(&name, &mut fs)
+            .map_err(|err| io::Error::other(format!("create {name}: {err:?}")))?;
+        file.write_at(0, &data, &mut fs)
+            .map_err(|err| io::Error::other(format!("write {name}: {err}")))?;
+    }
+
+    // FAT delays write-back, sectors still dirty in the cache would be lost
+    fs.sync();
+    Ok(())
+}
+
+/// Copies every regular file in the FAT directory `dir` of the volume on
+/// `fat_dev` into a new easy-fs image on `efs_dev`
+pub fn fat_to_efs(
+    fat_dev: &Arc<dyn BlockDevice>,
+    efs_dev: Arc<dyn BlockDevice>,
+    dir: &str,
+) -> io::Result<()> {
+    let fs = FatFileSystem::load(fat_dev)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid FAT image"))?;
+    let fat_dir = ROOT
+        .find(dir.trim_matches('/'), &fs)
+        .filter(|inode| inode.kind() == DirEntryType::Directory)
+        .ok_or_else(|| io::Error::other(format!("{dir} is not a directory")))?;
+
+    let efs = EasyFileSystem::new(efs_dev, 1);
+    let efs_root = EasyFileSystem::root_inode(&efs);
+
+    for dirent in list(&fat_dir, &fs) {
+        if dirent.ty != DirEntryType::Regular {
+            log::warn!("skipping non-regular entry {}", dirent.name);
+            continue;
+        }
+
+        let file = fat_dir.find(&dirent.name, &fs).unwrap();
+        let mut data = vec![0; file.stat(&fs).size as usize];
+        let read = file
+            .read_at(0, &mut data, &fs)
+            .map_err(|err| io::Error::other(format!("read {}: {err}", dirent.name)))?;
+        assert_eq!(read, data.len(), "short read");
+
+        println!("{}: {} bytes", dirent.name, data.len());
+        let inode = efs_root.create(&dirent.name).ok_or_else(|| {
+            io::Error::other(format!("duplicated or too long name {}", dirent.name))
+        })?;
+        if inode.write_at(0, &data) < data.len() {
+            return Err(io::Error::other(format!(
+                "{} exceeds the easy-fs file size limit",
+                dirent.name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn invalid_image(err: EfsError) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid easy-fs image: {err:?}"),
+    )
+}
+
+/// All entries of a FAT directory except `.` and `..`
+fn list(dir: &Inode, fs: &FatFileSystem) -> Vec<vfs::DirEntry> {
+    dir.dir_iter(0, fs).collect()
+}
@@ -1,3 +1,4 @@
+pub mod convert;
 #[cfg(test)]
 mod tests;
 
@@ -31,7 +32,17 @@ impl BlockDevice for BlockFile {
         );
     }
 
+    fn flush(&self) {
+        self.0.lock().unwrap().sync_data().expect("flushing image");
+    }
+
     fn handle_irq(&self) {
         unimplemented!()
     }
+
+    fn num_blocks(&self) -> usize {
+        let file = self.0.lock().unwrap();
+        let len = file.metadata().expect("querying image size").len();
+        len as usize / BLOCK_SIZE
+    }
 }
@@ -0,0 +1,400 @@
+use std::sync::Arc;
+
+use block_dev::mem::MemDisk;
+use block_dev::BlockDevice;
+use easy_fs::{EasyFileSystem, EasyVfs, EfsError, Inode, BLOCK_SIZE, MAX_FILE_SIZE, NAME_MAX_LEN};
+
+use crate::convert::{efs_to_fat, fat_to_efs};
+
+/// 间接索引块的编号容量
+const INDIRECT_COUNT: usize = BLOCK_SIZE / 4;
+const DIRECT_CAP: usize = 26;
+const INDIRECT1_CAP: usize = DIRECT_CAP + INDIRECT_COUNT;
+const INDIRECT2_CAP: usize = INDIRECT1_CAP + INDIRECT_COUNT.pow(2);
+
+const TOTAL_BLOCKS: usize = 64 * 1024;
+
+fn new_fs() -> (Arc<spin::Mutex<EasyFileSystem>>, Arc<Inode>) {
+    let device: Arc<dyn BlockDevice> = Arc::new(MemDisk::new(TOTAL_BLOCKS));
+    let efs = EasyFileSystem::new(device, 1);
+    let file = EasyFileSystem::root_inode(&efs).create("file").unwrap();
+    (efs, file)
+}
+
+/// 每个块以其序号填充，便于发现错位的块
+fn block_of(index: usize) -> [u8; BLOCK_SIZE] {
+    let words = index * INDIRECT_COUNT..(index + 1) * INDIRECT_COUNT;
+    let bytes: Vec<u8> = words.flat_map(|w| (w as u32).to_le_bytes()).collect();
+    bytes.try_into().unwrap()
+}
+
+/// 追加写入直至文件共有`blocks`个数据块
+fn grow_to(file: &Inode, written: &mut usize, blocks: usize) {
+    const CHUNK: usize = 64;
+
+    while *written < blocks {
+        let n = CHUNK.min(blocks - *written);
+        let buf: Vec<u8> = (*written..*written + n).flat_map(block_of).collect();
+        assert_eq!(file.write_at(*written * BLOCK_SIZE, &buf), buf.len());
+        *written += n;
+    }
+}
+
+fn verify(file: &Inode, blocks: usize) {
+    let mut buf = [0; BLOCK_SIZE];
+    for index in 0..blocks {
+        assert_eq!(file.read_at(index * BLOCK_SIZE, &mut buf), BLOCK_SIZE);
+        assert_eq!(buf, block_of(index), "block {index} mismatched");
+    }
+}
+
+/// 目录文件的字节数
+fn dir_size(dir: &Inode) -> usize {
+    let mut buf = [0; BLOCK_SIZE];
+    let mut size = 0;
+    while let n @ 1.. = dir.read_at(size, &mut buf) {
+        size += n;
+    }
+    size
+}
+
+/// 下一个将被分配的数据块
+fn next_free(efs: &spin::Mutex<EasyFileSystem>) -> u32 {
+    let mut efs = efs.lock();
+    let block = efs.alloc_data();
+    efs.dealloc_data(block);
+    block
+}
+
+#[test]
+fn expand_across_indirect_boundaries() {
+    let (_efs, file) = new_fs();
+    let mut written = 0;
+
+    for blocks in [
+        DIRECT_CAP,
+        DIRECT_CAP + 1,
+        INDIRECT1_CAP,
+        INDIRECT1_CAP + 1,
+        INDIRECT1_CAP + INDIRECT_COUNT,
+        INDIRECT1_CAP + INDIRECT_COUNT + 1,
+        INDIRECT2_CAP,
+        INDIRECT2_CAP + 1,
+        INDIRECT2_CAP + INDIRECT_COUNT + 1,
+        INDIRECT2_CAP + INDIRECT_COUNT.pow(2) + 1,
+    ] {
+        grow_to(&file, &mut written, blocks);
+        assert_eq!(file.read_at(blocks * BLOCK_SIZE, &mut [0; 1]), 0);
+    }
+
+    verify(&file, written);
+}
+
+#[test]
+fn write_beyond_max_size() {
+    let (efs, file) = new_fs();
+    let first = next_free(&efs);
+
+    // 三级索引也编不到的位置，一个块都不分配
+    assert_eq!(file.write_at(MAX_FILE_SIZE, &[1; BLOCK_SIZE]), 0);
+    assert_eq!(file.write_at(usize::MAX, &[1]), 0);
+    assert_eq!(next_free(&efs), first);
+    assert_eq!(file.read_at(0, &mut [0; 1]), 0);
+}
+
+#[test]
+fn clear_releases_every_block() {
+    for blocks in [
+        INDIRECT1_CAP + 1,
+        INDIRECT2_CAP,
+        INDIRECT2_CAP + 1,
+        INDIRECT2_CAP + INDIRECT_COUNT + 1,
+        INDIRECT2_CAP + INDIRECT_COUNT.pow(2) + INDIRECT_COUNT + 1,
+    ] {
+        let (efs, file) = new_fs();
+        let first = next_free(&efs);
+
+        let mut written = 0;
+        grow_to(&file, &mut written, blocks);
+        let used = (next_free(&efs) - first) as usize;
+        file.clear();
+
+        // 首次适配，全部释放后应当重新得到同一段连续的块
+        let mut efs = efs.lock();
+        for i in 0..used {
+            assert_eq!(efs.alloc_data(), first + i as u32, "{blocks} blocks leaked");
+        }
+    }
+}
+
+#[test]
+fn long_names_reuse_slots() {
+    let (efs, _file) = new_fs();
+    let root = EasyFileSystem::root_inode(&efs);
+    let name_of = |len: usize, c: char| -> String { std::iter::repeat_n(c, len).collect() };
+
+    let long = name_of(NAME_MAX_LEN, 'l');
+    assert!(root.create(&long).is_some());
+    assert!(root.create(&name_of(NAME_MAX_LEN + 1, 'x')).is_none());
+    assert!(root.find(&long[..NAME_MAX_LEN - 1]).is_none());
+    root.create(&name_of(40, 'm')).unwrap();
+
+    assert!(root.find(&long).is_some());
+    let mut names = root.ls();
+    names.sort();
+    assert_eq!(names, ["file".to_owned(), long.clone(), name_of(40, 'm')]);
+
+    // 删除长名字后，其槽位足以容纳若干较短的名字，目录不应增长
+    let before = dir_size(&root);
+    root.unlink_at(&long).unwrap();
+    for c in ['a', 'b', 'c'] {
+        root.create(&name_of(59, c)).unwrap();
+    }
+    assert_eq!(dir_size(&root), before);
+    assert!(root.find(&name_of(40, 'm')).is_some());
+    assert_eq!(root.ls().len(), 5);
+}
+
+#[test]
+fn check_finds_and_repairs_damage() {
+    let (efs, file) = new_fs();
+    let root = EasyFileSystem::root_inode(&efs);
+    let mut written = 0;
+    grow_to(&file, &mut written, DIRECT_CAP + 1);
+    root.create("kept").unwrap();
+    root.create("removed")
+        .unwrap()
+        .write_at(0, &[1; BLOCK_SIZE]);
+    root.unlink_at("removed").unwrap();
+    assert!(EasyFileSystem::check(&efs, false).is_clean());
+
+    // 损坏`file`的目录项，它与它的块随之无人引用
+    let mut dirent = [0; 32];
+    assert_eq!(root.read_at(0, &mut dirent), 32);
+    assert_eq!(&dirent[..5], b"file\0");
+    dirent[0] = b'F';
+    root.write_at(0, &dirent);
+    assert!(root.find("file").is_none());
+    assert_eq!(root.ls(), ["kept"]);
+
+    let report = EasyFileSystem::check(&efs, true);
+    assert_eq!(report.corrupt_entries, [(0, 0)]);
+    assert_eq!(report.orphan_inodes, [1]);
+    // 数据块与一级索引块
+    assert_eq!(report.leaked_blocks.len(), DIRECT_CAP + 2);
+
+    assert!(EasyFileSystem::check(&efs, false).is_clean());
+    let free = next_free(&efs);
+    assert_eq!(free, *report.leaked_blocks.iter().min().unwrap());
+    let reused = root.create("file").unwrap();
+    assert_eq!(reused.stat().inode, 1);
+}
+
+#[test]
+fn hard_links() {
+    let (efs, file) = new_fs();
+    let root = EasyFileSystem::root_inode(&efs);
+    file.write_at(0, b"shared");
+
+    root.link_at("file", "alias").unwrap();
+    assert_eq!(file.stat().links, 2);
+    let alias = root.find("alias").unwrap();
+    assert_eq!(alias.stat().inode, file.stat().inode);
+
+    // 已存在的名称不会被重复插入，不存在的文件无从链接
+    assert!(matches!(
+        root.link_at("file", "alias"),
+        Err(vfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root.link_at("missing", "other"),
+        Err(vfs::Error::NotFound)
+    ));
+    assert!(matches!(
+        root.link_at("file", &"l".repeat(NAME_MAX_LEN + 1)),
+        Err(vfs::Error::NameTooLong)
+    ));
+    assert_eq!(file.stat().links, 2);
+    assert_eq!(root.ls().len(), 2);
+
+    // 删除一个链接后内容仍在，最后一个链接被删除时才释放
+    root.unlink_at("file").unwrap();
+    let mut buf = [0; 6];
+    assert_eq!(alias.read_at(0, &mut buf), 6);
+    assert_eq!(&buf, b"shared");
+    assert_eq!(alias.stat().links, 1);
+    assert!(matches!(root.unlink_at("file"), Err(vfs::Error::NotFound)));
+    root.unlink_at("alias").unwrap();
+    assert!(root.ls().is_empty());
+    assert!(EasyFileSystem::check(&efs, false).is_clean());
+}
+
+#[test]
+fn open_validates_super_block() {
+    let blank: Arc<dyn BlockDevice> = Arc::new(MemDisk::new(16));
+    assert_eq!(EasyFileSystem::open(blank).unwrap_err(), EfsError::BadMagic);
+
+    let device = Arc::new(MemDisk::new(TOTAL_BLOCKS));
+    EasyFileSystem::new(device.clone(), 1);
+    assert!(EasyFileSystem::open(device.clone()).is_ok());
+
+    // 数据区的块数与总块数对不上
+    let mut image = device.bytes().clone();
+    image[20] ^= 1;
+    let corrupt: Arc<dyn BlockDevice> = Arc::new(MemDisk::from_image(image));
+    assert_eq!(
+        EasyFileSystem::open(corrupt).unwrap_err(),
+        EfsError::Corrupt
+    );
+
+    // 镜像被截短
+    let mut image = device.bytes().clone();
+    image.truncate(image.len() / 2);
+    let truncated: Arc<dyn BlockDevice> = Arc::new(MemDisk::from_image(image));
+    assert_eq!(
+        EasyFileSystem::open(truncated).unwrap_err(),
+        EfsError::Truncated
+    );
+}
+
+/// 经由特型对象访问，同内核
+#[test]
+fn vfs_trait_objects() {
+    use vfs::{DirEntryType, FileSystem, VfsInode};
+
+    let (efs, file) = new_fs();
+    let fs = EasyVfs::new(efs.clone());
+    let root = fs.root();
+    assert_eq!(root.kind(), DirEntryType::Directory);
+    file.write_at(0, b"through the trait");
+
+    let file: Arc<dyn VfsInode> = root.lookup("file").unwrap();
+    assert_eq!(file.kind(), DirEntryType::Regular);
+    assert_eq!(file.stat().size, 17);
+    let mut buf = [0; 32];
+    assert_eq!(file.read_at(8, &mut buf).unwrap(), 9);
+    assert_eq!(&buf[..9], b"the trait");
+    assert!(matches!(
+        file.lookup("child"),
+        Err(vfs::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        root.read_at(0, &mut buf),
+        Err(vfs::Error::IsADirectory)
+    ));
+    assert!(matches!(
+        file.write_at(MAX_FILE_SIZE, b"x"),
+        Err(vfs::Error::FileTooLarge)
+    ));
+
+    // 只能清空文件
+    assert!(matches!(file.truncate(3), Err(vfs::Error::Unsupported)));
+    file.truncate(0).unwrap();
+    assert_eq!(file.stat().size, 0);
+
+    let created = root.create("created").unwrap();
+    assert!(matches!(
+        root.create("created"),
+        Err(vfs::Error::AlreadyExists)
+    ));
+    assert!(matches!(
+        root.create(&"c".repeat(NAME_MAX_LEN + 1)),
+        Err(vfs::Error::NameTooLong)
+    ));
+
+    // 硬链接只能在同一镜像内建立
+    root.link("created", &*root, "alias").unwrap();
+    assert_eq!(root.lookup("alias").unwrap().id(), created.id());
+    let (other, _) = new_fs();
+    let other_root = EasyVfs::new(other).root();
+    assert!(matches!(
+        root.link("created", &*other_root, "elsewhere"),
+        Err(vfs::Error::CrossesDevices)
+    ));
+
+    // 游标为目录项序号，读到一半可以续读
+    let mut names = Vec::new();
+    let next = root
+        .read_dir(0, &mut |dirent| {
+            names.push(dirent.name.clone());
+            names.len() < 2
+        })
+        .unwrap();
+    assert_eq!(next, 1);
+    names.pop();
+    let end = root
+        .read_dir(next, &mut |dirent| {
+            names.push(dirent.name.clone());
+            true
+        })
+        .unwrap();
+    assert_eq!(end, 3);
+    assert_eq!(names, ["file", "created", "alias"]);
+
+    root.unlink("alias").unwrap();
+    root.unlink("created").unwrap();
+    root.unlink("file").unwrap();
+    assert!(EasyFileSystem::check(&efs, false).is_clean());
+}
+
+#[test]
+fn read_dir_plus_matches_stat() {
+    use vfs::FileSystem;
+
+    let (efs, file) = new_fs();
+    let root = EasyVfs::new(efs).root();
+    file.write_at(0, &[1; 3000]);
+    root.create("empty").unwrap();
+
+    let mut entries = Vec::new();
+    let end = root
+        .read_dir_plus(0, &mut |dirent, stat| {
+            entries.push((dirent.name.clone(), *stat));
+            true
+        })
+        .unwrap();
+    assert_eq!(end, 2);
+    assert_eq!(entries[0].0, "file");
+    assert_eq!(entries[0].1.size, 3000);
+    for (name, stat) in &entries {
+        assert_eq!(*stat, root.lookup(name).unwrap().stat());
+    }
+}
+
+/// 转为FAT再转回，内容不变。FAT的扇区缓存全进程只绑定一个设备，仅此测试使用
+#[test]
+fn convert_round_trip() {
+    let files: Vec<(String, Vec<u8>)> = (0..3)
+        .map(|i| {
+            (
+                format!("file{i}"),
+                (0..70000).map(|b| (b * 7 + i) as u8).collect(),
+            )
+        })
+        .collect();
+    let efs_dev: Arc<dyn BlockDevice> = Arc::new(MemDisk::new(TOTAL_BLOCKS));
+    let efs = EasyFileSystem::new(efs_dev.clone(), 1);
+    let root = EasyFileSystem::root_inode(&efs);
+    for (name, data) in &files {
+        assert_eq!(root.create(name).unwrap().write_at(0, data), data.len());
+    }
+
+    let fat_disk = Arc::new(MemDisk::new(64 * 2048));
+    let fat_dev: Arc<dyn BlockDevice> = fat_disk.clone();
+    efs_to_fat(efs_dev, &fat_dev, "usr/bin").unwrap();
+    // 返回时已全部写回，进程此时退出也不丢数据
+    let written = fat_disk.bytes().clone();
+    fat::FatFileSystem::load(&fat_dev).unwrap().sync();
+    assert!(*fat_disk.bytes() == written, "dirty sectors left");
+
+    let back_dev: Arc<dyn BlockDevice> = Arc::new(MemDisk::new(TOTAL_BLOCKS));
+    fat_to_efs(&fat_dev, back_dev.clone(), "/usr/bin/").unwrap();
+    let back = EasyFileSystem::open(back_dev).unwrap();
+    let root = EasyFileSystem::root_inode(&back);
+    for (name, data) in &files {
+        let inode = root.find(name).unwrap();
+        let mut buf = vec![0; data.len() + 1];
+        assert_eq!(inode.read_at(0, &mut buf), data.len(), "{name}");
+        assert!(buf[..data.len()] == data[..], "{name} mismatched");
+    }
+}
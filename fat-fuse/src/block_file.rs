@@ -44,5 +44,19 @@ impl BlockDevice for BlockFile {
         );
     }
 
+    fn flush(&self) {
+        self.inner.borrow().sync_data().expect("flushing image");
+    }
+
     fn handle_irq(&self) {}
+
+    fn num_blocks(&self) -> usize {
+        let len = self
+            .inner
+            .borrow()
+            .metadata()
+            .expect("querying image size")
+            .len();
+        len as usize / SECTOR_SIZE
+    }
 }
@@ -1,9 +1,36 @@
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
 
 #[derive(Parser)]
 pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Pack executables into a new FAT image
+    Pack(PackArgs),
+
+    /// Report files added, removed or changed between two FAT images
+    Diff {
+        /// The original image
+        old: PathBuf,
+
+        /// The updated image
+        new: PathBuf,
+    },
+
+    /// List the size, checksum and path of every regular file in a FAT image
+    Manifest {
+        /// The image to walk
+        image: PathBuf,
+    },
+}
+
+#[derive(Args)]
+pub struct PackArgs {
     /// Executable source directory
     #[arg(long, short)]
     pub source: PathBuf,
@@ -15,4 +42,12 @@ pub struct Cli {
     /// Output directory
     #[arg(long, short = 'O')]
     pub out_dir: PathBuf,
+
+    /// Configuration directory whose files are copied into `/etc`
+    #[arg(long, short)]
+    pub etc: Option<PathBuf>,
+
+    /// File holding a 64-digit hex key; the image is encrypted with XTS-AES-128
+    #[arg(long)]
+    pub key_file: Option<PathBuf>,
 }
@@ -0,0 +1,140 @@
+//! Compare the regular files of two FAT images.
+//!
+//! The sector cache of the `fat` crate is global and bound to the first device
+//! it is loaded from, so each image is walked by a `manifest` child process.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use block_dev::BlockDevice;
+use fat::{FatFileSystem, Inode, ROOT};
+use vfs::{DirEntry, DirEntryType};
+
+use crate::BlockFile;
+
+/// Size and checksum of a regular file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Digest {
+    size: u64,
+    checksum: u64,
+}
+
+/// Print the manifest of `image`, one `size\tchecksum\tpath` line per regular file.
+pub fn manifest(image: &Path) -> io::Result<()> {
+    let block_dev: Arc<dyn BlockDevice> = Arc::new(BlockFile::new(File::open(image)?));
+    let fs = FatFileSystem::load(&block_dev)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid FAT image"))?;
+
+    let mut stdout = io::stdout().lock();
+    let mut stack = vec![(String::new(), ROOT.clone())];
+    while let Some((dir_path, dir)) = stack.pop() {
+        for dirent in list(&dir, &fs) {
+            let path = format!("{dir_path}/{}", dirent.name);
+            let inode = dir
+                .find(&dirent.name, &fs)
+                .expect("listed entry is missing");
+            match dirent.ty {
+                DirEntryType::Directory => stack.push((path, inode)),
+                _ => {
+                    let digest = digest(&inode, &fs)?;
+                    writeln!(stdout, "{}\t{:016x}\t{path}", digest.size, digest.checksum)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Report the differences between `old` and `new`.
+///
+/// Returns whether the two images hold the same files.
+pub fn diff(old: &Path, new: &Path) -> io::Result<bool> {
+    let old = load_manifest(old)?;
+    let new = load_manifest(new)?;
+    let mut identical = true;
+
+    for (path, old_digest) in &old {
+        match new.get(path) {
+            None => {
+                println!("- {path}");
+                identical = false;
+            }
+            Some(new_digest) if new_digest != old_digest => {
+                println!(
+                    "~ {path} ({} -> {} bytes, {:016x} -> {:016x})",
+                    old_digest.size, new_digest.size, old_digest.checksum, new_digest.checksum
+                );
+                identical = false;
+            }
+            Some(_) => (),
+        }
+    }
+
+    for path in new.keys().filter(|path| !old.contains_key(*path)) {
+        println!("+ {path}");
+        identical = false;
+    }
+
+    Ok(identical)
+}
+
+fn load_manifest(image: &Path) -> io::Result<BTreeMap<String, Digest>> {
+    let output = Command::new(env::current_exe()?)
+        .arg("manifest")
+        .arg(image)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "failed to walk {}: {}",
+            image.display(),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|line| {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(size), Some(checksum), Some(path)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(io::Error::other(format!("malformed manifest line: {line}")));
+            };
+            let digest = Digest {
+                size: size.parse().map_err(io::Error::other)?,
+                checksum: u64::from_str_radix(checksum, 16).map_err(io::Error::other)?,
+            };
+            Ok((path.to_owned(), digest))
+        })
+        .collect()
+}
+
+/// All entries of `dir` except `.` and `..`
+fn list(dir: &Inode, fs: &FatFileSystem) -> Vec<DirEntry> {
+    dir.read_dir(fs).collect()
+}
+
+fn digest(inode: &Inode, fs: &FatFileSystem) -> io::Result<Digest> {
+    let size = inode.stat(fs).size;
+    let mut data = vec![0; size as usize];
+    let read = inode.read_at(0, &mut data, fs).map_err(io::Error::other)?;
+    assert_eq!(read, data.len(), "short read");
+
+    Ok(Digest {
+        size,
+        checksum: fnv1a(&data),
+    })
+}
+
+/// 64-bit FNV-1a hash
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
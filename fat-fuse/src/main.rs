@@ -1,21 +1,40 @@
 mod block_file;
 mod cli;
+mod diff;
 
 use std::fs::{self, File, OpenOptions};
 use std::io::{self, Read};
+use std::process::ExitCode;
 use std::sync::Arc;
 
+use block_dev::crypt::{self, XtsBlockDevice};
 use block_dev::BlockDevice;
 use clap::Parser;
 use fat::{FatFileSystem, ROOT};
 use typed_bytesize::ByteSizeIec;
 
-pub use self::{block_file::BlockFile, cli::Cli};
+pub use self::{
+    block_file::BlockFile,
+    cli::{Cli, Command, PackArgs},
+};
 
-fn main() -> io::Result<()> {
+fn main() -> io::Result<ExitCode> {
     env_logger::init();
 
-    let cli = Cli::parse();
+    match Cli::parse().command {
+        Command::Pack(args) => pack(&args)?,
+        Command::Diff { old, new } => {
+            if !diff::diff(&old, &new)? {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Command::Manifest { image } => diff::manifest(&image)?,
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn pack(cli: &PackArgs) -> io::Result<()> {
     println!("source={:?}\ntarget={:?}", cli.source, cli.target);
 
     let disk_size = ByteSizeIec::gib(4).0;
@@ -27,13 +46,21 @@ fn main() -> io::Result<()> {
         .open(cli.out_dir.join("fs.img"))?;
     fd.set_len(disk_size)?;
 
-    let block_dev: Arc<dyn BlockDevice> = Arc::new(BlockFile::new(fd));
-    let mut fs = FatFileSystem::foramt(disk_size as usize, &block_dev);
+    let mut block_dev: Arc<dyn BlockDevice> = Arc::new(BlockFile::new(fd));
+    if let Some(key_file) = &cli.key_file {
+        let key = crypt::parse_key(&fs::read_to_string(key_file)?).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "key must be 64 hex digits")
+        })?;
+        block_dev = Arc::new(XtsBlockDevice::new(block_dev, &key));
+    }
+    let fs = FatFileSystem::foramt(&block_dev);
 
     let usr_bin = ROOT
-        .mkdir("usr", &mut fs)
-        .and_then(|usr| usr.mkdir("bin", &mut fs))
+        .mkdir("usr", &fs)
+        .and_then(|usr| usr.mkdir("bin", &fs))
         .unwrap();
+    // 其它文件系统的挂载点
+    ROOT.mkdir("mnt", &fs).unwrap();
 
     let apps = fs::read_dir(&cli.source)?
         .map(|app| {
@@ -54,9 +81,27 @@ fn main() -> io::Result<()> {
         let mut elf_data: Vec<u8> = Vec::new();
         host_file.read_to_end(&mut elf_data)?;
 
-        let mut inode = usr_bin.create_file(&app, &mut fs).unwrap();
-        inode.write_at(0, &elf_data, &mut fs);
+        let mut inode = usr_bin.create_file(&app, &fs).unwrap();
+        inode
+            .write_at(0, &elf_data, &fs)
+            .map_err(io::Error::other)?;
+    }
+
+    if let Some(etc) = &cli.etc {
+        let etc_dir = ROOT.mkdir("etc", &fs).unwrap();
+        for entry in fs::read_dir(etc)? {
+            let entry = entry?;
+            let name = entry.file_name().into_string().unwrap();
+            log::info!("etc={name:?}");
+
+            let mut inode = etc_dir.create_file(&name, &fs).unwrap();
+            inode
+                .write_at(0, &fs::read(entry.path())?, &fs)
+                .map_err(io::Error::other)?;
+        }
     }
+    // 写入是延迟写回的
+    fs.sync();
 
     Ok(())
 }
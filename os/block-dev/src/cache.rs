@@ -0,0 +1,398 @@
+//! # 块缓存接口
+//!
+//! 文件系统通过 [`BlockCache`] 读写块设备上的块，
+//! 内核只需实现一次缓存策略并注入各个文件系统，
+//! 即可统一管理缓存占用的内存与写回时机。
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::mem;
+use core::slice;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use spin::Mutex;
+
+use crate::{BlockDevice, BlockOp, BlockRequest};
+
+/// 块缓存特质
+pub trait BlockCache: Debug + Send + Sync {
+    /// 获取`device`上从`block_id`起、长`size`字节的缓存块，未缓存则从设备读入
+    fn get(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        size: usize,
+    ) -> Arc<Mutex<CachedBlock>>;
+
+    /// 将所有脏块写回设备，并令写过的设备落盘
+    fn sync_all(&self);
+
+    /// 预读`device`上从`block_id`起的`count`块，已缓存的块不再读取。
+    /// 这只是提示，实现可以忽略，也可以不等读完就返回
+    #[allow(unused_variables)]
+    fn prefetch(&self, device: &Arc<dyn BlockDevice>, block_id: usize, count: usize, size: usize) {}
+
+    /// 写回并丢弃`device`上的`block_id`块，此块仍被引用时保留。
+    /// 这只是提示，实现可以忽略
+    #[allow(unused_variables)]
+    fn release(&self, device: &Arc<dyn BlockDevice>, block_id: usize) {}
+
+    /// [`get`](Self::get)的命中统计，不统计的实现返回全零
+    fn stats(&self) -> CacheStats {
+        CacheStats::default()
+    }
+}
+
+/// 块缓存的命中统计
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    /// 块已在缓存中，含预读来的块
+    pub hits: usize,
+    /// 须从设备读入
+    pub misses: usize,
+}
+
+impl CacheStats {
+    /// 命中率的百分比，尚无访问时为0
+    pub fn hit_percent(&self) -> usize {
+        (self.hits * 100)
+            .checked_div(self.hits + self.misses)
+            .unwrap_or(0)
+    }
+}
+
+/// 内存中的缓存块
+#[derive(Debug)]
+pub struct CachedBlock {
+    /// 缓存的数据
+    data: Box<[u8]>,
+    /// 对应的块ID
+    block_id: usize,
+    /// 底层块设备的引用
+    device: Arc<dyn BlockDevice>,
+    /// 是否为脏块
+    modified: bool,
+}
+
+impl CachedBlock {
+    pub fn new(device: &Arc<dyn BlockDevice>, block_id: usize, size: usize) -> Self {
+        let mut data = vec![0; size];
+        device.read_block(block_id, &mut data);
+        Self::with_data(device, block_id, data.into())
+    }
+
+    /// 以已读出的`data`构造缓存块
+    fn with_data(device: &Arc<dyn BlockDevice>, block_id: usize, data: Box<[u8]>) -> Self {
+        Self {
+            data,
+            block_id,
+            device: device.clone(),
+            modified: false,
+        }
+    }
+
+    /// 对应的块设备
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    /// 是否有尚未写回的修改
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    pub fn sync(&mut self) {
+        if self.modified {
+            self.modified = false;
+            self.device.write_block(self.block_id, &self.data);
+        }
+    }
+
+    pub fn get<T>(&self, offset: usize) -> &T {
+        let type_size = mem::size_of::<T>();
+        assert!(type_size + offset <= self.data.len());
+        unsafe { &*self.data.as_ptr().add(offset).cast() }
+    }
+
+    pub fn get_mut<T>(&mut self, offset: usize) -> &mut T {
+        let type_size = mem::size_of::<T>();
+        assert!(type_size + offset <= self.data.len());
+        self.modified = true;
+        unsafe { &mut *self.data.as_mut_ptr().add(offset).cast() }
+    }
+
+    pub fn as_slice<T>(&self) -> &[T] {
+        let type_size = mem::size_of::<T>();
+        let len = self.data.len() / type_size;
+        assert_eq!(0, self.data.len() % type_size);
+        unsafe { slice::from_raw_parts(self.data.as_ptr().cast(), len) }
+    }
+
+    pub fn as_mut_slice<T>(&mut self) -> &mut [T] {
+        let type_size = mem::size_of::<T>();
+        let len = self.data.len() / type_size;
+        assert_eq!(0, self.data.len() % type_size);
+        self.modified = true;
+        unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr().cast(), len) }
+    }
+
+    #[inline]
+    pub fn map<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
+        f(self.get(offset))
+    }
+
+    #[inline]
+    pub fn map_mut<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
+        f(self.get_mut(offset))
+    }
+
+    #[inline]
+    pub fn map_slice<T, V>(&self, f: impl FnOnce(&[T]) -> V) -> V {
+        f(self.as_slice())
+    }
+
+    #[inline]
+    pub fn map_mut_slice<T, V>(&mut self, f: impl FnOnce(&mut [T]) -> V) -> V {
+        f(self.as_mut_slice())
+    }
+
+    #[inline]
+    pub fn zeroize(&mut self) {
+        self.data.fill(0);
+        self.modified = true;
+    }
+}
+
+impl Drop for CachedBlock {
+    fn drop(&mut self) {
+        self.sync();
+    }
+}
+
+/// 有容量上限的块缓存，满时踢走闲置块
+///
+/// 预读以异步请求提交，读完的块在下次访问缓存时才移入队列；
+/// 访问尚在读取的块时，经设备的[`BlockDevice::wait_until`]等待其完成。
+#[derive(Debug)]
+pub struct FifoBlockCache {
+    capacity: usize,
+    queue: Mutex<Vec<(BlockKey, Arc<Mutex<CachedBlock>>)>>,
+    /// 已提交而尚未移入队列的预读，须在`queue`之后上锁
+    inflight: Mutex<Vec<InFlight>>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+/// 尚在读取的块
+#[derive(Debug)]
+struct InFlight {
+    key: BlockKey,
+    device: Arc<dyn BlockDevice>,
+    /// 读完时由完成回调填入
+    slot: Arc<ReadSlot>,
+}
+
+/// 预读的结果，等待者可能仍持有其引用，移入队列时取走数据而非独占
+#[derive(Debug, Default)]
+struct ReadSlot {
+    completed: AtomicBool,
+    data: Mutex<Option<Box<[u8]>>>,
+}
+
+impl ReadSlot {
+    fn complete(&self, data: Box<[u8]>) {
+        *self.data.lock() = Some(data);
+        self.completed.store(true, Ordering::Release);
+    }
+
+    fn is_completed(&self) -> bool {
+        self.completed.load(Ordering::Acquire)
+    }
+
+    fn take(&self) -> Option<Box<[u8]>> {
+        self.data.lock().take()
+    }
+}
+
+/// 块设备的地址与块ID，在块设备之间唯一地标识一个块。
+///
+/// 查找时不能锁住缓存块，调用者可能正持有其中某块的锁。
+type BlockKey = (usize, usize);
+
+impl FifoBlockCache {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            queue: Mutex::new(Vec::new()),
+            inflight: Mutex::new(Vec::new()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// 将读完的预读移入队列，没有空位时丢弃
+    fn reap(
+        &self,
+        queue: &mut Vec<(BlockKey, Arc<Mutex<CachedBlock>>)>,
+        inflight: &mut Vec<InFlight>,
+    ) {
+        for read in inflight.extract_if(.., |read| read.slot.is_completed()) {
+            if !self.make_room(queue) {
+                continue;
+            }
+            let Some(data) = read.slot.take() else {
+                continue;
+            };
+            let cache = CachedBlock::with_data(&read.device, read.key.1, data);
+            queue.push((read.key, Arc::new(Mutex::new(cache))));
+        }
+    }
+
+    /// 触及上限时写回并踢走最早缓存的闲置块，全部块都被引用时返回`false`
+    fn make_room(&self, queue: &mut Vec<(BlockKey, Arc<Mutex<CachedBlock>>)>) -> bool {
+        if queue.len() < self.capacity {
+            return true;
+        }
+        // 没有其它引用的才能写回
+        match queue
+            .iter()
+            .position(|(_, cache)| Arc::strong_count(cache) == 1)
+        {
+            Some(index) => {
+                queue.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl BlockCache for FifoBlockCache {
+    fn get(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        size: usize,
+    ) -> Arc<Mutex<CachedBlock>> {
+        let key = (device_addr(device), block_id);
+        let mut queue = loop {
+            let mut queue = self.queue.lock();
+            let mut inflight = self.inflight.lock();
+            self.reap(&mut queue, &mut inflight);
+
+            // 尝试从缓冲区中读取块
+            if let Some(cache) = queue
+                .iter()
+                .find_map(|(k, cache)| (key == *k).then_some(cache))
+            {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Arc::clone(cache);
+            }
+
+            // 块正在预读，放开锁等它读完，以免阻塞其它块的访问
+            let Some(read) = inflight.iter().find(|read| read.key == key) else {
+                break queue;
+            };
+            // 其它任务可能在此期间将其移入队列
+            let slot = read.slot.clone();
+            drop(inflight);
+            drop(queue);
+            device.wait_until(&|| slot.is_completed());
+        };
+
+        // 触及上限，写回一个块
+        assert!(self.make_room(&mut queue), "run out of block cache");
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        // 缓存新块
+        let cache = Arc::new(Mutex::new(CachedBlock::new(device, block_id, size)));
+        queue.push((key, cache.clone()));
+
+        cache
+    }
+
+    fn sync_all(&self) {
+        let mut written: Vec<Arc<dyn BlockDevice>> = Vec::new();
+        for (_, cache) in self.queue.lock().iter() {
+            let mut block = cache.lock();
+            if !block.modified {
+                continue;
+            }
+            block.sync();
+            let addr = device_addr(&block.device);
+            if written.iter().all(|device| device_addr(device) != addr) {
+                written.push(block.device.clone());
+            }
+        }
+
+        for device in written {
+            device.flush();
+        }
+    }
+
+    fn prefetch(&self, device: &Arc<dyn BlockDevice>, block_id: usize, count: usize, size: usize) {
+        let addr = device_addr(device);
+        let mut queue = self.queue.lock();
+        let mut inflight = self.inflight.lock();
+        self.reap(&mut queue, &mut inflight);
+
+        // 预读至多占用一半的容量，以免挤走刚读过的块
+        let limit = self.capacity / 2;
+        let missing: Vec<usize> = (block_id..block_id + count.min(limit))
+            .filter(|&id| {
+                queue.iter().all(|(k, _)| *k != (addr, id))
+                    && inflight.iter().all(|read| read.key != (addr, id))
+            })
+            .collect();
+
+        for id in missing {
+            if inflight.len() >= limit {
+                break;
+            }
+            let slot = Arc::new(ReadSlot::default());
+            let done = slot.clone();
+            let request = BlockRequest {
+                op: BlockOp::Read,
+                block_id: id,
+                buf: vec![0; size].into(),
+            };
+            inflight.push(InFlight {
+                key: (addr, id),
+                device: device.clone(),
+                slot,
+            });
+            device.submit(request, Box::new(move |request| done.complete(request.buf)));
+        }
+
+        // 同步完成的请求立即入队
+        self.reap(&mut queue, &mut inflight);
+    }
+
+    fn release(&self, device: &Arc<dyn BlockDevice>, block_id: usize) {
+        let key = (device_addr(device), block_id);
+        let mut queue = self.queue.lock();
+
+        // 丢弃时随之写回
+        if let Some(index) = queue
+            .iter()
+            .position(|(k, cache)| *k == key && Arc::strong_count(cache) == 1)
+        {
+            queue.remove(index);
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// 块设备的地址，忽略虚表指针
+fn device_addr(device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(device).cast::<()>() as usize
+}
@@ -0,0 +1,155 @@
+//! # 透明加密层
+//!
+//! [`XtsBlockDevice`] 包装另一个块设备，以 XTS-AES-128 逐块加解密：
+//! 写入时加密，读出时解密，上层的文件系统对此毫无察觉。
+//!
+//! 块号即 XTS 的数据单元号，以小端序编为128位后经第二把密钥加密得到初始调整值；
+//! 块内每16字节为一个密码块，调整值依次在 GF(2^128) 中乘以本原元。
+//! 块的大小须为16字节的整数倍，因此无需密文挪用。
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+
+use aes::cipher::consts::U16;
+use aes::cipher::generic_array::GenericArray;
+use aes::cipher::{BlockDecrypt, BlockEncrypt, KeyInit};
+use aes::Aes128;
+
+use crate::BlockDevice;
+
+/// 密钥长度：数据密钥与调整值密钥各16字节
+pub const KEY_SIZE: usize = 32;
+
+/// 密码块长度
+const UNIT: usize = 16;
+
+/// 加密存储的块设备
+pub struct XtsBlockDevice {
+    inner: Arc<dyn BlockDevice>,
+    data: Aes128,
+    tweak: Aes128,
+}
+
+impl XtsBlockDevice {
+    pub fn new(inner: Arc<dyn BlockDevice>, key: &[u8; KEY_SIZE]) -> Self {
+        let (data, tweak) = key.split_at(KEY_SIZE / 2);
+        Self {
+            inner,
+            data: Aes128::new(GenericArray::from_slice(data)),
+            tweak: Aes128::new(GenericArray::from_slice(tweak)),
+        }
+    }
+
+    /// 原地加密第`block_id`块的内容
+    pub fn encrypt(&self, block_id: usize, buf: &mut [u8]) {
+        self.xts(block_id, buf, |block| self.data.encrypt_block(block));
+    }
+
+    /// 原地解密第`block_id`块的内容
+    pub fn decrypt(&self, block_id: usize, buf: &mut [u8]) {
+        self.xts(block_id, buf, |block| self.data.decrypt_block(block));
+    }
+
+    fn xts(&self, block_id: usize, buf: &mut [u8], cipher: impl Fn(&mut GenericArray<u8, U16>)) {
+        assert_eq!(buf.len() % UNIT, 0, "block size must be a multiple of 16");
+
+        let mut tweak = GenericArray::from((block_id as u128).to_le_bytes());
+        self.tweak.encrypt_block(&mut tweak);
+        let mut tweak = u128::from_le_bytes(tweak.into());
+
+        for chunk in buf.as_chunks_mut::<UNIT>().0 {
+            let mask = tweak.to_le_bytes();
+            xor(chunk, &mask);
+            cipher(GenericArray::from_mut_slice(chunk));
+            xor(chunk, &mask);
+            tweak = mul_alpha(tweak);
+        }
+    }
+}
+
+impl Debug for XtsBlockDevice {
+    // 不可泄露密钥
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("XtsBlockDevice")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlockDevice for XtsBlockDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.inner.read_block(block_id, buf);
+        self.decrypt(block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut cipher_text = buf.to_vec();
+        self.encrypt(block_id, &mut cipher_text);
+        self.inner.write_block(block_id, &cipher_text);
+    }
+
+    fn handle_irq(&self) {
+        self.inner.handle_irq();
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.inner.num_blocks()
+    }
+
+    fn block_size(&self) -> usize {
+        self.inner.block_size()
+    }
+
+    fn suspend(&self) {
+        self.inner.suspend();
+    }
+
+    fn resume(&self) {
+        self.inner.resume();
+    }
+
+    fn read_blocks(&self, block_id: usize, bufs: &mut [&mut [u8]]) {
+        self.inner.read_blocks(block_id, bufs);
+        for (i, buf) in bufs.iter_mut().enumerate() {
+            self.decrypt(block_id + i, buf);
+        }
+    }
+
+    fn write_blocks(&self, block_id: usize, bufs: &[&[u8]]) {
+        let mut cipher_texts: Vec<_> = bufs.iter().map(|buf| buf.to_vec()).collect();
+        for (i, buf) in cipher_texts.iter_mut().enumerate() {
+            self.encrypt(block_id + i, buf);
+        }
+        let bufs: Vec<&[u8]> = cipher_texts.iter().map(|buf| &buf[..]).collect();
+        self.inner.write_blocks(block_id, &bufs);
+    }
+}
+
+/// 解析十六进制的密钥，忽略首尾空白
+pub fn parse_key(hex: &str) -> Option<[u8; KEY_SIZE]> {
+    let hex = hex.trim().as_bytes();
+    if hex.len() != KEY_SIZE * 2 {
+        return None;
+    }
+
+    let digit = |c: u8| char::from(c).to_digit(16).map(|d| d as u8);
+    let mut key = [0; KEY_SIZE];
+    for (byte, &[high, low]) in key.iter_mut().zip(hex.as_chunks().0) {
+        *byte = digit(high)? << 4 | digit(low)?;
+    }
+    Some(key)
+}
+
+fn xor(chunk: &mut [u8], mask: &[u8; UNIT]) {
+    chunk.iter_mut().zip(mask).for_each(|(b, m)| *b ^= m);
+}
+
+/// 在 GF(2^128) 中乘以本原元 x，既约多项式为 x^128 + x^7 + x^2 + x + 1
+fn mul_alpha(tweak: u128) -> u128 {
+    (tweak << 1) ^ ((tweak >> 127) * 0x87)
+}
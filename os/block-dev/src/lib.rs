@@ -5,14 +5,107 @@
 //! 实现了此特质的类型称为**块设备驱动**。
 //!
 //! 文件系统可以通过块设备驱动读写块设备。
+//!
+//! 除了同步读写，驱动还可以接受异步的请求：[`BlockDevice::submit`]提交请求后立即返回，
+//! 请求在设备中断到来时完成，由[`BlockDevice::handle_irq`]调用请求附带的回调。
+//! 调用者借此在等待磁盘期间做别的事，例如块缓存的预读不再阻塞读者。
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
 
-#![no_std]
+mod cache;
+#[cfg(feature = "crypt")]
+pub mod crypt;
+#[cfg(any(test, feature = "test-util"))]
+pub mod mem;
 
+#[cfg(test)]
+mod tests;
+
+use alloc::boxed::Box;
 use core::fmt::Debug;
+use core::hint;
+
+pub use self::cache::{BlockCache, CacheStats, CachedBlock, FifoBlockCache};
+
+/// 异步请求的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockOp {
+    Read,
+    Write,
+}
+
+/// 异步的块请求，缓冲区随请求移交给驱动，完成时交还
+#[derive(Debug)]
+pub struct BlockRequest {
+    pub op: BlockOp,
+    pub block_id: usize,
+    /// 读请求承接数据，写请求提供数据
+    pub buf: Box<[u8]>,
+}
+
+/// 请求完成时的回调，可能在中断上下文中执行，不得睡眠，也不得再访问设备
+pub type Completion = Box<dyn FnOnce(BlockRequest) + Send>;
 
 /// 块设备驱动特质
 pub trait BlockDevice: Debug + Send + Sync {
     fn read_block(&self, block_id: usize, buf: &mut [u8]);
     fn write_block(&self, block_id: usize, buf: &[u8]);
     fn handle_irq(&self);
+
+    /// 设备的容量，以块计
+    fn num_blocks(&self) -> usize;
+
+    /// 块的字节数，默认为扇区的大小
+    fn block_size(&self) -> usize {
+        512
+    }
+
+    /// 将设备自身缓冲的写入落盘，返回后此前写入的块不会因掉电而丢失。
+    /// 不缓冲写入的设备无需实现
+    fn flush(&self) {}
+
+    /// 挂起前使设备静默，例如确保已提交的请求全部完成
+    fn suspend(&self) {}
+
+    /// 唤醒后恢复设备
+    fn resume(&self) {}
+
+    /// 提交异步请求，请求完成后以之调用`done`。
+    ///
+    /// 默认同步地完成请求，返回前即已调用`done`
+    fn submit(&self, mut request: BlockRequest, done: Completion) {
+        match request.op {
+            BlockOp::Read => self.read_block(request.block_id, &mut request.buf),
+            BlockOp::Write => self.write_block(request.block_id, &request.buf),
+        }
+        done(request);
+    }
+
+    /// 等待此前提交的请求完成，直到`done`返回真。
+    ///
+    /// `done`通常检查请求的回调是否已执行。默认忙等，
+    /// 能够调度任务的驱动应让当前任务睡眠，由完成中断唤醒后再检查
+    fn wait_until(&self, done: &dyn Fn() -> bool) {
+        while !done() {
+            hint::spin_loop();
+        }
+    }
+
+    /// 从`block_id`起读取连续的块，第`i`个缓冲区承接第`block_id + i`块。
+    ///
+    /// 缓冲区在内存中无需连续，支持分散/聚集的驱动可以将其合并为一次请求
+    fn read_blocks(&self, block_id: usize, bufs: &mut [&mut [u8]]) {
+        for (i, buf) in bufs.iter_mut().enumerate() {
+            self.read_block(block_id + i, buf);
+        }
+    }
+
+    /// 从`block_id`起写入连续的块，第`i`个缓冲区写至第`block_id + i`块
+    fn write_blocks(&self, block_id: usize, bufs: &[&[u8]]) {
+        for (i, buf) in bufs.iter().enumerate() {
+            self.write_block(block_id + i, buf);
+        }
+    }
 }
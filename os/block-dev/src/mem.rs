@@ -0,0 +1,47 @@
+//! 内存中的块设备，供各文件系统的测试挂载镜像，仅在启用`test-util`特性时编译
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use spin::{Mutex, MutexGuard};
+
+use crate::BlockDevice;
+
+/// 内存中的块设备，块即512字节的扇区
+#[derive(Debug, Default)]
+pub struct MemDisk(Mutex<Vec<u8>>);
+
+impl MemDisk {
+    /// `num_blocks`个全零的块
+    pub fn new(num_blocks: usize) -> Self {
+        Self::from_image(vec![0; num_blocks * 512])
+    }
+
+    /// 以`image`为内容，末尾不足一块的部分读写不到
+    pub fn from_image(image: Vec<u8>) -> Self {
+        Self(Mutex::new(image))
+    }
+
+    /// 直接读写设备上的字节，用于检查或篡改镜像
+    pub fn bytes(&self) -> MutexGuard<'_, Vec<u8>> {
+        self.0.lock()
+    }
+}
+
+impl BlockDevice for MemDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let start = block_id * self.block_size();
+        buf.copy_from_slice(&self.0.lock()[start..start + self.block_size()]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let start = block_id * self.block_size();
+        self.0.lock()[start..start + self.block_size()].copy_from_slice(buf);
+    }
+
+    fn handle_irq(&self) {}
+
+    fn num_blocks(&self) -> usize {
+        self.0.lock().len() / self.block_size()
+    }
+}
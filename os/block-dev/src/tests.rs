@@ -0,0 +1,287 @@
+//! 块缓存与加密层的测试
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::sync::Mutex;
+
+#[cfg(feature = "crypt")]
+use crate::crypt::{parse_key, XtsBlockDevice, KEY_SIZE};
+use crate::mem::MemDisk;
+use crate::{BlockCache, BlockDevice, BlockOp, BlockRequest, Completion, FifoBlockCache};
+
+#[cfg(feature = "crypt")]
+#[derive(Debug)]
+struct Null;
+
+#[cfg(feature = "crypt")]
+impl BlockDevice for Null {
+    fn read_block(&self, _block_id: usize, _buf: &mut [u8]) {}
+    fn write_block(&self, _block_id: usize, _buf: &[u8]) {}
+    fn handle_irq(&self) {}
+    fn num_blocks(&self) -> usize {
+        0
+    }
+}
+
+#[cfg(feature = "crypt")]
+fn hex(s: &str) -> Vec<u8> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+/// IEEE 1619-2007 附录B的测试向量
+#[cfg(feature = "crypt")]
+#[test]
+fn ieee1619_vectors() {
+    let cases = [
+        (
+            [0; KEY_SIZE],
+            0,
+            "00000000000000000000000000000000\
+             00000000000000000000000000000000",
+            "917cf69ebd68b2ec9b9fe9a3eadda692\
+             cd43d2f59598ed858c02c2652fbf922e",
+        ),
+        (
+            *b"\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\x11\
+               \x22\x22\x22\x22\x22\x22\x22\x22\x22\x22\x22\x22\x22\x22\x22\x22",
+            0x3333333333,
+            "44444444444444444444444444444444\
+             44444444444444444444444444444444",
+            "c454185e6a16936e39334038acef838b\
+             fb186fff7480adc4289382ecd6d394f0",
+        ),
+    ];
+
+    for (key, unit, plain, cipher) in cases {
+        let dev = XtsBlockDevice::new(Arc::new(Null), &key);
+        let mut buf = hex(plain);
+        dev.encrypt(unit, &mut buf);
+        assert_eq!(buf, hex(cipher));
+        dev.decrypt(unit, &mut buf);
+        assert_eq!(buf, hex(plain));
+    }
+}
+
+#[cfg(feature = "crypt")]
+#[test]
+fn parse_hex_key() {
+    let text = "000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F\n";
+    let key = parse_key(text).unwrap();
+    assert!(key.iter().enumerate().all(|(i, &b)| b == i as u8));
+    assert!(parse_key(&text[2..]).is_none());
+    assert!(parse_key(&text.replace('0', "+")).is_none());
+}
+
+/// 记录读写次数的内存块设备
+#[derive(Debug, Default)]
+struct CountingDisk {
+    blocks: MemDisk,
+    reads: Mutex<Vec<usize>>,
+    writes: Mutex<Vec<usize>>,
+    flushes: Mutex<usize>,
+}
+
+impl BlockDevice for CountingDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.reads.lock().unwrap().push(block_id);
+        self.blocks.read_block(block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.writes.lock().unwrap().push(block_id);
+        self.blocks.write_block(block_id, buf);
+    }
+
+    fn flush(&self) {
+        *self.flushes.lock().unwrap() += 1;
+    }
+
+    fn handle_irq(&self) {}
+
+    fn num_blocks(&self) -> usize {
+        self.blocks.num_blocks()
+    }
+}
+
+fn counting_disk(num_blocks: usize) -> (Arc<CountingDisk>, Arc<dyn BlockDevice>) {
+    let disk = Arc::new(CountingDisk {
+        blocks: MemDisk::new(num_blocks),
+        ..Default::default()
+    });
+    let dev: Arc<dyn BlockDevice> = disk.clone();
+    (disk, dev)
+}
+
+/// 异步完成请求的磁盘，中断到来时才完成已提交的请求
+#[derive(Default)]
+struct DeferredDisk {
+    disk: CountingDisk,
+    pending: Mutex<Vec<(BlockRequest, Completion)>>,
+    /// 首次等待时执行，模拟等待期间运行的其它任务
+    on_wait: Mutex<Option<Box<dyn FnOnce() + Send>>>,
+}
+
+impl core::fmt::Debug for DeferredDisk {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DeferredDisk")
+            .field("disk", &self.disk)
+            .finish_non_exhaustive()
+    }
+}
+
+impl BlockDevice for DeferredDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.disk.read_block(block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.disk.write_block(block_id, buf);
+    }
+
+    fn submit(&self, request: BlockRequest, done: Completion) {
+        self.pending.lock().unwrap().push((request, done));
+    }
+
+    // 等待时中断随之到来
+    fn wait_until(&self, done: &dyn Fn() -> bool) {
+        let on_wait = self.on_wait.lock().unwrap().take();
+        if let Some(on_wait) = on_wait {
+            on_wait();
+        }
+        while !done() {
+            self.handle_irq();
+        }
+    }
+
+    fn handle_irq(&self) {
+        let pending = core::mem::take(&mut *self.pending.lock().unwrap());
+        for (mut request, done) in pending {
+            assert_eq!(request.op, BlockOp::Read);
+            self.disk.read_block(request.block_id, &mut request.buf);
+            done(request);
+        }
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.disk.num_blocks()
+    }
+}
+
+#[test]
+fn prefetch() {
+    let (disk, dev) = counting_disk(16);
+    let cache = FifoBlockCache::new(8);
+
+    cache.get(&dev, 1, 512);
+    // 已缓存的块不再读取，预读至多占用一半的容量
+    cache.prefetch(&dev, 0, 8, 512);
+    assert_eq!(*disk.reads.lock().unwrap(), [1, 0, 2, 3]);
+
+    disk.reads.lock().unwrap().clear();
+    for id in 0..4 {
+        cache.get(&dev, id, 512);
+    }
+    assert!(disk.reads.lock().unwrap().is_empty());
+}
+
+#[test]
+fn release() {
+    let (disk, dev) = counting_disk(4);
+    let cache = FifoBlockCache::new(4);
+
+    let block = cache.get(&dev, 2, 512);
+    block.lock().map_mut(0, |b: &mut u8| *b = 0xAA);
+
+    // 仍被引用的块保留
+    cache.release(&dev, 2);
+    assert!(disk.writes.lock().unwrap().is_empty());
+
+    // 丢弃时写回，再次访问时重新读取
+    drop(block);
+    cache.release(&dev, 2);
+    assert_eq!(*disk.writes.lock().unwrap(), [2]);
+    assert_eq!(disk.blocks.bytes()[2 * 512], 0xAA);
+    cache.get(&dev, 2, 512);
+    assert_eq!(*disk.reads.lock().unwrap(), [2, 2]);
+}
+
+#[test]
+fn sync_all_flushes_written_devices() {
+    let (disk, dev) = counting_disk(4);
+    let (idle, idle_dev) = counting_disk(4);
+    let cache = FifoBlockCache::new(4);
+
+    cache.get(&idle_dev, 0, 512);
+    cache
+        .get(&dev, 1, 512)
+        .lock()
+        .map_mut(0, |b: &mut u8| *b = 0xAA);
+    cache
+        .get(&dev, 3, 512)
+        .lock()
+        .map_mut(0, |b: &mut u8| *b = 0xBB);
+
+    // 写过的设备只落盘一次，没写过的不落盘
+    cache.sync_all();
+    assert_eq!(*disk.writes.lock().unwrap(), [1, 3]);
+    assert_eq!(*disk.flushes.lock().unwrap(), 1);
+    assert_eq!(*idle.flushes.lock().unwrap(), 0);
+
+    // 没有脏块时不再落盘
+    cache.sync_all();
+    assert_eq!(*disk.flushes.lock().unwrap(), 1);
+}
+
+#[test]
+fn async_prefetch() {
+    let disk = Arc::new(DeferredDisk {
+        disk: CountingDisk {
+            blocks: MemDisk::from_image((0..4).flat_map(|i| [i as u8; 512]).collect()),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let dev: Arc<dyn BlockDevice> = disk.clone();
+    let cache = FifoBlockCache::new(8);
+
+    // 预读只提交请求，不等读完
+    cache.prefetch(&dev, 0, 3, 512);
+    assert!(disk.disk.reads.lock().unwrap().is_empty());
+    assert_eq!(disk.pending.lock().unwrap().len(), 3);
+
+    // 访问尚在读取的块时等它完成，不再另行读取
+    assert_eq!(cache.get(&dev, 1, 512).lock().map(0, |b: &u8| *b), 1);
+    assert_eq!(*disk.disk.reads.lock().unwrap(), [0, 1, 2]);
+    for id in 0..3 {
+        cache.get(&dev, id, 512);
+    }
+    assert_eq!(disk.disk.reads.lock().unwrap().len(), 3);
+}
+
+#[test]
+fn reaped_while_waiting() {
+    let disk = Arc::new(DeferredDisk {
+        disk: CountingDisk {
+            blocks: MemDisk::from_image((0..4).flat_map(|i| [i as u8; 512]).collect()),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let dev: Arc<dyn BlockDevice> = disk.clone();
+    let cache = Arc::new(FifoBlockCache::new(8));
+    cache.prefetch(&dev, 0, 2, 512);
+
+    // 等待块1期间，另一任务在中断后访问块0，顺带将块1移入队列
+    let (other, other_dev) = (cache.clone(), dev.clone());
+    *disk.on_wait.lock().unwrap() = Some(Box::new(move || {
+        other_dev.handle_irq();
+        assert_eq!(other.get(&other_dev, 0, 512).lock().map(0, |b: &u8| *b), 0);
+    }));
+    assert_eq!(cache.get(&dev, 1, 512).lock().map(0, |b: &u8| *b), 1);
+    assert_eq!(*disk.disk.reads.lock().unwrap(), [0, 1]);
+    assert_eq!(cache.stats().misses, 0);
+}
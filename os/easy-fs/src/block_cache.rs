@@ -8,144 +8,184 @@
 //! 且**操作块时一定在缓冲区当中**。
 //!
 //! 缓存与块设备同步后并不会移除块缓存，该操作由缓存管理器调度执行。
+//! 未注入块缓存时使用[`LruBlockCache`]：easy-fs的位图与间接块会被反复访问，
+//! 按最近使用的先后淘汰比按缓存的先后淘汰更能留住它们。
 
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
-use block_dev::BlockDevice;
-use spin::Mutex;
+use block_dev::{BlockCache as Cache, BlockDevice, CacheStats, CachedBlock};
+use spin::{Mutex, Once};
 
 use crate::BLOCK_SIZE;
 
-static BLOCK_CACHE_MANAGER: Mutex<BlockCacheManager> = Mutex::new(BlockCacheManager::new());
+/// 未注入块缓存时，默认缓存的块个数上限
+const DEFAULT_CAPACITY: usize = 16;
 
-/// 块缓存全局管理，缓存、调度块缓存
-struct BlockCacheManager {
-    queue: Vec<(usize, Arc<Mutex<BlockCache>>)>,
+static BLOCK_CACHE: Once<Arc<dyn Cache>> = Once::new();
+
+/// 注入块缓存，须在打开或创建文件系统之前调用，否则使用默认的缓存
+pub fn set_block_cache(cache: Arc<dyn Cache>) {
+    BLOCK_CACHE.call_once(|| cache);
+}
+
+#[inline]
+fn cache() -> &'static Arc<dyn Cache> {
+    BLOCK_CACHE.call_once(|| Arc::new(LruBlockCache::new(DEFAULT_CAPACITY)))
 }
 
 #[inline]
 pub fn get(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<BlockCache>> {
-    BLOCK_CACHE_MANAGER.lock().get(block_id, block_device)
+    cache().get(&block_device, block_id, BLOCK_SIZE)
 }
 
 pub fn sync_all() {
-    BLOCK_CACHE_MANAGER
-        .lock()
-        .queue
-        .iter()
-        .for_each(|(_, cache)| cache.lock().sync());
+    cache().sync_all();
+}
+
+/// 块缓存的命中统计，注入的缓存不统计时为全零
+pub fn stats() -> CacheStats {
+    cache().stats()
 }
 
 /// 内存中的块缓存
-pub struct BlockCache {
-    /// 缓存的数据
-    data: [u8; BLOCK_SIZE],
-    /// 对应的块ID
-    block_id: usize,
-    /// 底层块设备的引用
-    block_device: Arc<dyn BlockDevice>,
-    /// 是否为脏块
-    modified: bool,
+pub type BlockCache = CachedBlock;
+
+/// 块设备的地址与块ID，在块设备之间唯一地标识一个块
+type BlockKey = (usize, usize);
+
+/// 有容量上限的块缓存，满时踢走最久未用的闲置块
+#[derive(Debug)]
+pub struct LruBlockCache {
+    capacity: usize,
+    inner: Mutex<LruInner>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
 }
 
-impl BlockCache {
-    pub fn new(block_id: usize, block_device: Arc<dyn BlockDevice>) -> Self {
-        let mut data = [0; BLOCK_SIZE];
-        block_device.read_block(block_id, &mut data);
+#[derive(Debug, Default)]
+struct LruInner {
+    /// 缓存的块及其最近一次使用的时间戳
+    blocks: BTreeMap<BlockKey, (usize, Arc<Mutex<CachedBlock>>)>,
+    /// 时间戳到块，按时间戳排序即按最近使用的先后排序
+    recency: BTreeMap<usize, BlockKey>,
+    /// 逻辑时钟，每次使用块时递增
+    clock: usize,
+}
 
+impl LruBlockCache {
+    /// 至多缓存`capacity`块
+    pub fn new(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "block cache needs room for at least one block"
+        );
         Self {
-            data,
-            block_id,
-            block_device,
-            modified: false,
+            capacity,
+            inner: Mutex::new(LruInner::default()),
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
         }
     }
+}
 
-    pub fn sync(&mut self) {
-        if self.modified {
-            self.modified = false;
-            self.block_device.write_block(self.block_id, &self.data);
-        }
+impl LruInner {
+    fn touch(&mut self, key: BlockKey) {
+        self.clock += 1;
+        let (stamp, _) = self.blocks.get_mut(&key).unwrap();
+        self.recency.remove(stamp);
+        *stamp = self.clock;
+        self.recency.insert(self.clock, key);
     }
 
-    pub fn get<T: Sized>(&self, offset: usize) -> &T {
-        let type_size = mem::size_of::<T>();
-        assert!(type_size + offset <= BLOCK_SIZE);
-        let addr = self.offset(offset).cast();
-        unsafe { &*addr }
+    /// 写回并踢走最久未用的闲置块，全部块都被引用时返回`false`
+    fn evict(&mut self) -> bool {
+        // 没有其它引用的才能写回
+        let Some((&stamp, &key)) = self
+            .recency
+            .iter()
+            .find(|(_, key)| Arc::strong_count(&self.blocks[key].1) == 1)
+        else {
+            return false;
+        };
+        self.recency.remove(&stamp);
+        self.blocks.remove(&key);
+        true
     }
+}
 
-    pub fn get_mut<T: Sized>(&mut self, offset: usize) -> &mut T {
-        let type_size = mem::size_of::<T>();
-        assert!(type_size + offset <= BLOCK_SIZE);
-        self.modified = true;
-        let addr = self.offset(offset).cast_mut().cast();
-        unsafe { &mut *addr }
-    }
+impl Cache for LruBlockCache {
+    fn get(
+        &self,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        size: usize,
+    ) -> Arc<Mutex<CachedBlock>> {
+        let key = (device_addr(device), block_id);
+        let mut inner = self.inner.lock();
+
+        if let Some((_, cache)) = inner.blocks.get(&key) {
+            let cache = cache.clone();
+            inner.touch(key);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return cache;
+        }
 
-    #[inline]
-    pub fn map<T: Sized, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
-        f(self.get(offset))
-    }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        if inner.blocks.len() >= self.capacity {
+            assert!(inner.evict(), "run out of block cache");
+        }
+        let cache = Arc::new(Mutex::new(CachedBlock::new(device, block_id, size)));
+        inner.blocks.insert(key, (0, cache.clone()));
+        inner.touch(key);
 
-    #[inline]
-    pub fn map_mut<T: Sized, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
-        f(self.get_mut(offset))
+        cache
     }
-}
 
-impl BlockCache {
-    #[inline]
-    fn offset(&self, count: usize) -> *const u8 {
-        &self.data[count]
-    }
-}
+    fn sync_all(&self) {
+        let mut written: Vec<Arc<dyn BlockDevice>> = Vec::new();
+        for (_, cache) in self.inner.lock().blocks.values() {
+            let mut block = cache.lock();
+            if !block.is_modified() {
+                continue;
+            }
+            block.sync();
+            let addr = device_addr(block.device());
+            if written.iter().all(|device| device_addr(device) != addr) {
+                written.push(block.device().clone());
+            }
+        }
 
-impl Drop for BlockCache {
-    fn drop(&mut self) {
-        self.sync();
+        for device in written {
+            device.flush();
+        }
     }
-}
-
-impl BlockCacheManager {
-    /// 块缓存个数的上限
-    const CAPACITY: usize = 16;
 
-    const fn new() -> Self {
-        Self { queue: Vec::new() }
-    }
+    fn release(&self, device: &Arc<dyn BlockDevice>, block_id: usize) {
+        let key = (device_addr(device), block_id);
+        let mut inner = self.inner.lock();
 
-    // 块缓存调度策略：踢走闲置块
-    fn get(
-        &mut self,
-        block_id: usize,
-        block_device: Arc<dyn BlockDevice>,
-    ) -> Arc<Mutex<BlockCache>> {
-        // 尝试从缓冲区中读取块
-        if let Some(cache) = self
-            .queue
-            .iter()
-            .find_map(|(id, cache)| (block_id == *id).then_some(cache))
-        {
-            return Arc::clone(cache);
+        // 丢弃时随之写回
+        let Some(&(stamp, ref cache)) = inner.blocks.get(&key) else {
+            return;
         };
-
-        // 触及上限，写回一个块
-        if self.queue.len() == Self::CAPACITY {
-            let index = self
-                .queue
-                .iter()
-                .position(|(_, cache)| Arc::strong_count(cache) == 1) // 没有其它引用的才能写回
-                .expect("run out of block cache");
-            self.queue.remove(index);
+        if Arc::strong_count(cache) == 1 {
+            inner.recency.remove(&stamp);
+            inner.blocks.remove(&key);
         }
+    }
 
-        // 缓存新块
-        let block_cache = Arc::new(Mutex::new(BlockCache::new(block_id, block_device)));
-        self.queue.push((block_id, block_cache.clone()));
-
-        block_cache
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
     }
 }
+
+/// 块设备的地址，忽略虚表指针
+fn device_addr(device: &Arc<dyn BlockDevice>) -> usize {
+    Arc::as_ptr(device).cast::<()>() as usize
+}
@@ -4,15 +4,18 @@
 
 use core::mem;
 
+use alloc::collections::BTreeSet;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use block_dev::BlockDevice;
+use enumflags2::BitFlags;
 use spin::Mutex;
 
 use crate::block_cache;
 use crate::layout::*;
 use crate::DataBlock;
 use crate::Inode;
-use crate::{BLOCK_BITS, BLOCK_SIZE};
+use crate::{EfsError, Feature, BLOCK_BITS, BLOCK_SIZE};
 
 const INODE_SIZE: usize = mem::size_of::<DiskInode>();
 const INODES_PER_BLOCK: usize = BLOCK_SIZE / INODE_SIZE;
@@ -24,16 +27,20 @@ pub struct EasyFileSystem {
     data_bitmap: Bitmap,
     inode_area_start_block: u32,
     data_area_start_block: u32,
+    /// 镜像用到的特性
+    features: BitFlags<Feature>,
 }
 
 impl EasyFileSystem {
-    pub fn new(
-        block_device: Arc<dyn BlockDevice>,
-        total_blocks: u32,
-        inode_bitmap_blocks: u32,
-    ) -> Arc<Mutex<Self>> {
+    /// 将整个设备格式化为文件系统
+    pub fn new(block_device: Arc<dyn BlockDevice>, inode_bitmap_blocks: u32) -> Arc<Mutex<Self>> {
+        let total_blocks = device_blocks(&block_device) as u32;
         let inode_bitmap = Bitmap::new(1, inode_bitmap_blocks as usize);
         let inode_area_cap = inode_bitmap.capacity();
+        assert!(
+            inode_area_cap <= CHECKED_INODE_ID_LIMIT as usize,
+            "too many inodes for checksummed directory entries"
+        );
         let inode_area_blocks =
             ((inode_area_cap * mem::size_of::<DiskInode>() + BLOCK_SIZE - 1) / BLOCK_SIZE) as u32;
         let inode_total_blocks = inode_bitmap_blocks + inode_area_blocks;
@@ -52,6 +59,7 @@ impl EasyFileSystem {
             data_bitmap,
             inode_area_start_block: 1 + inode_bitmap_blocks,
             data_area_start_block: 1 + inode_total_blocks + data_bitmap_blocks,
+            features: Feature::SUPPORTED,
         };
 
         for i in 0..total_blocks {
@@ -85,11 +93,15 @@ impl EasyFileSystem {
         Arc::new(Mutex::new(efs))
     }
 
-    pub fn open(block_device: Arc<dyn BlockDevice>) -> Arc<Mutex<Self>> {
+    /// 打开设备上的文件系统，超级块无效时报错
+    pub fn open(block_device: Arc<dyn BlockDevice>) -> Result<Arc<Mutex<Self>>, EfsError> {
         block_cache::get(0, block_device.clone())
             .lock()
             .map(0, |super_block: &SuperBlock| {
-                assert!(super_block.is_valid(), "error when loading EFS");
+                let features = super_block.validate()?;
+                if super_block.total_blocks as usize > device_blocks(&block_device) {
+                    return Err(EfsError::Truncated);
+                }
 
                 let inode_total_blocks =
                     super_block.inode_bitmap_blocks + super_block.inode_area_blocks;
@@ -102,9 +114,10 @@ impl EasyFileSystem {
                     ),
                     inode_area_start_block: 1 + super_block.inode_bitmap_blocks,
                     data_area_start_block: 1 + inode_total_blocks + super_block.data_bitmap_blocks,
+                    features,
                 };
 
-                Arc::new(Mutex::new(efs))
+                Ok(Arc::new(Mutex::new(efs)))
             })
     }
 
@@ -114,18 +127,46 @@ impl EasyFileSystem {
         self.inode_bitmap.alloc(&self.block_device).unwrap()
     }
 
+    /// 回收 inode，其数据块须已回收
+    #[inline]
+    pub fn dealloc_inode(&mut self, inode_id: u32) {
+        self.inode_bitmap
+            .dealloc_batch(&self.block_device, &[inode_id]);
+    }
+
     /// 在磁盘上分配新的数据块并返回其ID
     #[inline]
     pub fn alloc_data(&mut self) -> u32 {
         self.data_area_start_block + self.data_bitmap.alloc(&self.block_device).unwrap()
     }
 
+    /// 批量分配数据块，填满`block_ids`
+    pub fn alloc_data_batch(&mut self, block_ids: &mut [u32]) {
+        let n = self.data_bitmap.alloc_batch(&self.block_device, block_ids);
+        assert_eq!(n, block_ids.len(), "run out of data blocks");
+        block_ids
+            .iter_mut()
+            .for_each(|block_id| *block_id += self.data_area_start_block);
+    }
+
     pub fn dealloc_data(&mut self, block_id: u32) {
-        block_cache::get(block_id as usize, self.block_device.clone())
-            .lock()
-            .map_mut(0, |data_block: &mut DataBlock| data_block.fill(0));
+        self.dealloc_data_batch(&mut [block_id]);
+    }
+
+    /// 批量回收数据块，`block_ids`会被排序以便按位图块归并
+    pub fn dealloc_data_batch(&mut self, block_ids: &mut [u32]) {
+        block_ids.sort_unstable();
+        for &block_id in block_ids.iter() {
+            block_cache::get(block_id as usize, self.block_device.clone())
+                .lock()
+                .map_mut(0, |data_block: &mut DataBlock| data_block.fill(0));
+        }
+
+        block_ids
+            .iter_mut()
+            .for_each(|block_id| *block_id -= self.data_area_start_block);
         self.data_bitmap
-            .dealloc(&self.block_device, block_id - self.data_area_start_block)
+            .dealloc_batch(&self.block_device, block_ids);
     }
 
     /// 通过ID获取 inode 在磁盘上的位置：**块ID**以及**块内偏移**
@@ -136,9 +177,141 @@ impl EasyFileSystem {
         (block_id, block_inoffset)
     }
 
+    /// 镜像用到的特性
+    #[inline]
+    pub fn features(&self) -> BitFlags<Feature> {
+        self.features
+    }
+
+    /// 目录项是否带有校验和
+    #[inline]
+    pub fn dir_checksums(&self) -> bool {
+        self.features.contains(Feature::DirChecksums)
+    }
+
+    /// 文件名的最大长度
+    #[inline]
+    pub fn name_max(&self) -> usize {
+        if self.features.contains(Feature::LongNames) {
+            NAME_MAX_LEN
+        } else {
+            SHORT_NAME_MAX_LEN
+        }
+    }
+
     pub fn root_inode(efs: &Arc<Mutex<Self>>) -> Inode {
         let block_device = efs.lock().block_device.clone();
         let (block_id, block_offset) = efs.lock().disk_inode_pos(0);
         Inode::new(block_id, block_offset, efs.clone(), block_device)
     }
 }
+
+/// [`EasyFileSystem::check`]发现的问题
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CheckReport {
+    /// 校验和不符或指向不存在的 inode 的目录项：所在目录的 inode ID 及目录项的偏移
+    pub corrupt_entries: Vec<(u32, usize)>,
+    /// 已分配却不被任何目录引用的 inode
+    pub orphan_inodes: Vec<u32>,
+    /// 标记为已用却不属于任何可达文件的块，含孤儿 inode 的块
+    pub leaked_blocks: Vec<u32>,
+}
+
+impl CheckReport {
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        self.corrupt_entries.is_empty()
+            && self.orphan_inodes.is_empty()
+            && self.leaked_blocks.is_empty()
+    }
+}
+
+impl EasyFileSystem {
+    /// 一致性检查：从根目录出发找出全部可达的 inode 与块，再与两张位图对照。
+    /// `repair`为真时清除损坏的目录项，回收孤儿 inode 与泄漏的块。
+    pub fn check(efs: &Arc<Mutex<Self>>, repair: bool) -> CheckReport {
+        let mut fs = efs.lock();
+        let block_device = fs.block_device.clone();
+        let checksum = fs.dir_checksums();
+        let inode_cap = fs.inode_bitmap.capacity() as u32;
+
+        let mut report = CheckReport::default();
+        let mut reachable_inodes = BTreeSet::from([0]);
+        let mut reachable_blocks = BTreeSet::new();
+        let mut dirs = Vec::from([0]);
+
+        while let Some(inode_id) = dirs.pop() {
+            let (block_id, block_offset) = fs.disk_inode_pos(inode_id);
+            let inode = block_cache::get(block_id as usize, block_device.clone());
+            let mut inode = inode.lock();
+
+            let corrupt = inode.map(block_offset, |disk_inode: &DiskInode| {
+                reachable_blocks.extend(disk_inode.blocks(&block_device));
+                if !disk_inode.is_dir() {
+                    return Vec::new();
+                }
+
+                let mut corrupt = Vec::new();
+                for (offset, dir_entry) in disk_inode.dir_entries(&block_device, checksum) {
+                    if dir_entry.is_intact() && dir_entry.is_empty() {
+                        continue;
+                    }
+                    if !dir_entry.is_intact() || dir_entry.inode_id() >= inode_cap {
+                        corrupt.push((offset, dir_entry.slots()));
+                        continue;
+                    }
+                    // 硬链接使同一 inode 被多次引用
+                    if reachable_inodes.insert(dir_entry.inode_id()) {
+                        dirs.push(dir_entry.inode_id());
+                    }
+                }
+                corrupt
+            });
+
+            if repair && !corrupt.is_empty() {
+                inode.map_mut(block_offset, |disk_inode: &mut DiskInode| {
+                    let zeros = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+                    for &(offset, slots) in &corrupt {
+                        disk_inode.write_at(
+                            offset,
+                            &zeros[..slots * DirEntry::SIZE],
+                            &block_device,
+                        );
+                    }
+                });
+            }
+            report
+                .corrupt_entries
+                .extend(corrupt.into_iter().map(|(offset, _)| (inode_id, offset)));
+        }
+
+        report.orphan_inodes = fs
+            .inode_bitmap
+            .allocated(&block_device)
+            .into_iter()
+            .filter(|inode_id| !reachable_inodes.contains(inode_id))
+            .collect();
+        report.leaked_blocks = fs
+            .data_bitmap
+            .allocated(&block_device)
+            .into_iter()
+            .map(|block_id| block_id + fs.data_area_start_block)
+            .filter(|block_id| !reachable_blocks.contains(block_id))
+            .collect();
+
+        if repair {
+            for &inode_id in &report.orphan_inodes {
+                fs.dealloc_inode(inode_id);
+            }
+            fs.dealloc_data_batch(&mut report.leaked_blocks.clone());
+            block_cache::sync_all();
+        }
+
+        report
+    }
+}
+
+/// 设备能容纳多少个文件系统的块
+fn device_blocks(block_device: &Arc<dyn BlockDevice>) -> usize {
+    block_device.num_blocks() * block_device.block_size() / BLOCK_SIZE
+}
@@ -1,4 +1,5 @@
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use block_dev::BlockDevice;
 
 use crate::block_cache;
@@ -7,6 +8,9 @@ use crate::BLOCK_BITS;
 /// 位图区域内块的结构
 type BitmapBlock = [u64; BLOCK_BITS / 64];
 
+/// 位图块内的字数
+const BLOCK_WORDS: usize = BLOCK_BITS / 64;
+
 /// 位图区域，记录其指示区域的块分配情况
 #[derive(Debug)]
 pub struct Bitmap {
@@ -14,6 +18,8 @@ pub struct Bitmap {
     start_block_id: usize,
     /// 位图占用块数
     blocks: usize,
+    /// 空闲位搜索的起点(字序号)，其之前的字均已占满
+    next_word: usize,
 }
 
 /// 块编号
@@ -25,6 +31,7 @@ impl Bitmap {
         Self {
             start_block_id,
             blocks,
+            next_word: 0,
         }
     }
 
@@ -36,48 +43,89 @@ impl Bitmap {
 
     /// 在指示区域内分配新的块，返回其编号。
     /// 若位图的空间用尽，则返回空。
-    pub fn alloc(&self, block_device: &Arc<dyn BlockDevice>) -> Option<u32> {
-        // 遍历位图区域内所有的块，寻找块内还有剩余空间的bit组(即还有0)
-        // 起始块ID + 块索引 = 索引指向块的实际ID
-        for block_index in 0..self.blocks {
+    pub fn alloc(&mut self, block_device: &Arc<dyn BlockDevice>) -> Option<u32> {
+        let mut id = [0];
+        (self.alloc_batch(block_device, &mut id) == 1).then_some(id[0])
+    }
+
+    /// 分配至多`ids.len()`个块，编号依次写入`ids`，返回实际分配的个数。
+    ///
+    /// 以64位的字为单位扫描，每个位图块只加锁一次。
+    pub fn alloc_batch(&mut self, block_device: &Arc<dyn BlockDevice>, ids: &mut [u32]) -> usize {
+        let mut n = 0;
+        let mut word = self.next_word;
+
+        while n < ids.len() && word < self.blocks * BLOCK_WORDS {
+            let block_index = word / BLOCK_WORDS;
             let cache = block_cache::get(self.start_block_id + block_index, block_device.clone());
             let mut cache = cache.lock();
             let bitmap_block: &mut BitmapBlock = cache.get_mut(0);
 
-            let Some((group_index, ingroup_index)) =
-                bitmap_block
-                    .iter()
-                    .enumerate()
-                    .find_map(|(group_index, &bits)| {
-                        (bits != u64::MAX).then_some((group_index, bits.trailing_ones()))
-                    })
-            else {
-                continue;
-            };
-
-            // 追加新位
-            bitmap_block[group_index] |= 1 << ingroup_index;
-            // 计算位图所指示区域内块的编号
-            return Some(BlockID::encode(
-                block_index,
-                group_index,
-                ingroup_index as usize,
-            ));
+            while n < ids.len() && word / BLOCK_WORDS == block_index {
+                let bits = &mut bitmap_block[word % BLOCK_WORDS];
+                while n < ids.len() && *bits != u64::MAX {
+                    let ingroup_index = bits.trailing_ones();
+                    // 追加新位
+                    *bits |= 1 << ingroup_index;
+                    // 计算位图所指示区域内块的编号
+                    ids[n] =
+                        BlockID::encode(block_index, word % BLOCK_WORDS, ingroup_index as usize);
+                    n += 1;
+                }
+
+                if *bits == u64::MAX {
+                    word += 1;
+                }
+            }
         }
 
-        None
+        self.next_word = word;
+        n
     }
 
-    pub fn dealloc(&self, block_device: &Arc<dyn BlockDevice>, block_id: u32) {
-        let (block_index, group_index, ingroup_index) = BlockID(block_id).decode();
-        let cache = block_cache::get(self.start_block_id + block_index, block_device.clone());
-        let mut cache = cache.lock();
-        let bitmap_block: &mut BitmapBlock = cache.get_mut(0);
+    /// 已分配的全部编号，从小到大
+    pub fn allocated(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut ids = Vec::new();
+        for block_index in 0..self.blocks {
+            block_cache::get(self.start_block_id + block_index, block_device.clone())
+                .lock()
+                .map(0, |bitmap_block: &BitmapBlock| {
+                    for (group_index, &bits) in bitmap_block.iter().enumerate() {
+                        let mut bits = bits;
+                        while bits != 0 {
+                            let ingroup_index = bits.trailing_zeros() as usize;
+                            bits &= bits - 1;
+                            ids.push(BlockID::encode(block_index, group_index, ingroup_index));
+                        }
+                    }
+                });
+        }
+        ids
+    }
+
+    /// 回收一批块，同一位图块内的相邻编号只加锁一次
+    pub fn dealloc_batch(&mut self, block_device: &Arc<dyn BlockDevice>, block_ids: &[u32]) {
+        let mut block_ids = block_ids.iter().peekable();
 
-        // 编号一定得有对应的位
-        assert_ne!(bitmap_block[group_index] & (1 << ingroup_index), 0);
+        while let Some(&&first) = block_ids.peek() {
+            let (block_index, _, _) = BlockID(first).decode();
+            let cache = block_cache::get(self.start_block_id + block_index, block_device.clone());
+            let mut cache = cache.lock();
+            let bitmap_block: &mut BitmapBlock = cache.get_mut(0);
 
-        bitmap_block[group_index] -= 1 << ingroup_index;
+            while let Some(&&block_id) = block_ids.peek() {
+                let (bi, group_index, ingroup_index) = BlockID(block_id).decode();
+                if bi != block_index {
+                    break;
+                }
+                block_ids.next();
+
+                // 编号一定得有对应的位
+                assert_ne!(bitmap_block[group_index] & (1 << ingroup_index), 0);
+                bitmap_block[group_index] -= 1 << ingroup_index;
+                self.next_word = self.next_word.min(block_index * BLOCK_WORDS + group_index);
+            }
+        }
     }
 }
 
@@ -1,32 +1,84 @@
+use core::mem::{self, offset_of};
 use core::{ptr, slice};
 
-const NAME_MAX_LEN: usize = 27;
+/// 文件名的最大长度
+pub const NAME_MAX_LEN: usize = 255;
+/// 首个槽位可容纳的名字长度，也是旧版本镜像中文件名的最大长度
+pub const SHORT_NAME_MAX_LEN: usize = 27;
+/// 校验和在首个槽位中的位置，即inode ID的最高字节
+const CHECKSUM_OFFSET: usize = offset_of!(RawDirEntry, inode_id) + 3;
+/// 启用校验和时inode ID的上限
+pub const CHECKED_INODE_ID_LIMIT: u32 = 1 << 24;
+/// 续接槽位的最大数量
+const EXT_MAX: usize = (NAME_MAX_LEN - SHORT_NAME_MAX_LEN).div_ceil(DirEntry::SIZE);
 
-/// 文件系统项的元信息
-#[derive(Debug, Default, Clone)]
+/// 目录项在磁盘上的首个槽位
+///
+/// 名字超出首个槽位时，其余部分依次存于紧随其后的续接槽位，每个续接槽位全部用于存放名字。
+/// 旧版本中`ext`处恒为名字结尾的\0，因此旧镜像的目录项即是不带续接槽位的目录项。
+///
+/// 启用[`Feature::DirChecksums`]时，inode ID只有低24位，最高字节是整个目录项的校验和；
+/// 否则inode ID占满32位。
+///
+/// [`Feature::DirChecksums`]: crate::Feature::DirChecksums
+#[derive(Debug)]
 #[repr(C)]
+struct RawDirEntry {
+    name: [u8; SHORT_NAME_MAX_LEN],
+    /// 续接槽位的数量
+    ext: u8,
+    /// 小端序
+    inode_id: [u8; 4],
+}
+
+const _: () = {
+    assert!(mem::size_of::<RawDirEntry>() == DirEntry::SIZE);
+    assert!(offset_of!(RawDirEntry, ext) == SHORT_NAME_MAX_LEN);
+    assert!(offset_of!(RawDirEntry, inode_id) == SHORT_NAME_MAX_LEN + 1);
+    assert!(CHECKSUM_OFFSET == DirEntry::SIZE - 1);
+    assert!(SHORT_NAME_MAX_LEN + EXT_MAX * DirEntry::SIZE >= NAME_MAX_LEN);
+    assert!(EXT_MAX <= u8::MAX as usize);
+};
+
+/// 文件系统项的元信息，名字为空表示空槽位
+#[derive(Debug, Clone)]
 pub struct DirEntry {
-    // 最后一字节留给 \0
-    name: [u8; NAME_MAX_LEN + 1],
+    name: [u8; NAME_MAX_LEN],
+    len: usize,
     inode_id: u32,
+    /// 读自磁盘时是否完好，未启用校验和时总是完好
+    intact: bool,
 }
 
 impl DirEntry {
-    /// 元信息大小恒为32字节
+    /// 每个槽位的大小恒为32字节
     pub const SIZE: usize = 32;
+    /// 一个目录项最多占据的槽位数
+    pub const MAX_SLOTS: usize = 1 + EXT_MAX;
 
     #[inline]
     pub fn new(name: &str, inode_id: u32) -> Self {
         let bytes = name.as_bytes();
-        let mut name = [0; NAME_MAX_LEN + 1];
+        assert!(bytes.len() <= NAME_MAX_LEN, "file name too long");
+        let mut name = [0; NAME_MAX_LEN];
         name[..bytes.len()].copy_from_slice(bytes);
 
-        Self { name, inode_id }
+        Self {
+            name,
+            len: bytes.len(),
+            inode_id,
+            intact: true,
+        }
     }
 
     pub fn name(&self) -> &str {
-        let len = self.name.iter().position(|&c| c == 0).unwrap();
-        core::str::from_utf8(&self.name[..len]).unwrap()
+        core::str::from_utf8(&self.name[..self.len]).unwrap()
+    }
+
+    /// 名字为空，即空槽位
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
     #[inline]
@@ -34,13 +86,102 @@ impl DirEntry {
         self.inode_id
     }
 
+    /// 校验和是否相符
+    #[inline]
+    pub fn is_intact(&self) -> bool {
+        self.intact
+    }
+
+    /// 占据的槽位数
+    #[inline]
+    pub fn slots(&self) -> usize {
+        1 + self
+            .len
+            .saturating_sub(SHORT_NAME_MAX_LEN)
+            .div_ceil(Self::SIZE)
+    }
+
+    /// 由首个槽位得知整个目录项占据的槽位数
     #[inline]
-    pub fn as_bytes(&self) -> &[u8] {
-        unsafe { slice::from_raw_parts(ptr::from_ref(self).cast(), Self::SIZE) }
+    pub fn slots_of(head: &[u8]) -> usize {
+        1 + (head[offset_of!(RawDirEntry, ext)] as usize).min(EXT_MAX)
     }
 
+    /// 写出磁盘上的字节，长度为[`Self::slots`]个槽位，`checksum`为真时带上校验和
+    pub fn encode<'a>(
+        &self,
+        buf: &'a mut [u8; Self::SIZE * Self::MAX_SLOTS],
+        checksum: bool,
+    ) -> &'a [u8] {
+        let slots = self.slots();
+        let buf = &mut buf[..slots * Self::SIZE];
+        buf.fill(0);
+
+        let (short, rest) = self.name[..self.len].split_at(self.len.min(SHORT_NAME_MAX_LEN));
+        let mut head = RawDirEntry {
+            name: [0; SHORT_NAME_MAX_LEN],
+            ext: (slots - 1) as u8,
+            inode_id: self.inode_id.to_le_bytes(),
+        };
+        head.name[..short.len()].copy_from_slice(short);
+        buf[..Self::SIZE].copy_from_slice(head.as_bytes());
+        buf[Self::SIZE..Self::SIZE + rest.len()].copy_from_slice(rest);
+        if checksum {
+            assert!(self.inode_id < CHECKED_INODE_ID_LIMIT);
+            buf[CHECKSUM_OFFSET] = checksum_of(buf);
+        }
+
+        buf
+    }
+
+    /// 从磁盘上的字节读回，`bytes`须包含[`Self::slots_of`]个槽位，`checksum`为真时核对校验和
+    pub fn decode(bytes: &[u8], checksum: bool) -> Self {
+        let slots = Self::slots_of(bytes);
+        let bytes = &bytes[..slots * Self::SIZE];
+        let intact = !checksum || bytes[CHECKSUM_OFFSET] == checksum_of(bytes);
+        let (head, ext) = bytes.split_at(Self::SIZE);
+        let head = unsafe { ptr::read_unaligned(head.as_ptr().cast::<RawDirEntry>()) };
+
+        let mut name = [0; NAME_MAX_LEN];
+        let mut len = head
+            .name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(SHORT_NAME_MAX_LEN);
+        name[..len].copy_from_slice(&head.name[..len]);
+        if len == SHORT_NAME_MAX_LEN {
+            let rest = ext.iter().position(|&c| c == 0).unwrap_or(ext.len());
+            let rest = rest.min(NAME_MAX_LEN - len);
+            name[len..len + rest].copy_from_slice(&ext[..rest]);
+            len += rest;
+        }
+
+        let mut inode_id = u32::from_le_bytes(head.inode_id);
+        if checksum {
+            inode_id %= CHECKED_INODE_ID_LIMIT;
+        }
+
+        Self {
+            name,
+            len,
+            inode_id,
+            intact,
+        }
+    }
+}
+
+impl RawDirEntry {
     #[inline]
-    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
-        unsafe { slice::from_raw_parts_mut(ptr::from_mut(self).cast(), Self::SIZE) }
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(ptr::from_ref(self).cast(), DirEntry::SIZE) }
     }
 }
+
+/// 目录项各槽位的校验和，不含校验和本身。仿照FAT长文件名的算法，循环右移后累加
+fn checksum_of(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != CHECKSUM_OFFSET)
+        .fold(0u8, |sum, (_, &b)| sum.rotate_right(1).wrapping_add(b))
+}
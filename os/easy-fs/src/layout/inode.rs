@@ -13,8 +13,12 @@
 
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::iter;
+use core::mem::{self, offset_of};
+
 use block_dev::BlockDevice;
 
+use super::DirEntry;
 use crate::block_cache;
 use crate::DataBlock;
 use crate::BLOCK_SIZE;
@@ -30,7 +34,6 @@ const DIRECT_COUNT: usize = 26;
 const INDIRECT1_COUNT: usize = INDIRECT_COUNT;
 /// 二级索引块可编号数量
 const INDIRECT2_COUNT: usize = INDIRECT_COUNT.pow(2);
-#[allow(dead_code)]
 /// 三级索引块可编号数量
 const INDIRECT3_COUNT: usize = INDIRECT_COUNT.pow(3);
 /// 直接索引时的编号容量
@@ -39,10 +42,12 @@ const DIRECT_CAP: usize = DIRECT_COUNT;
 const INDIRECT1_CAP: usize = DIRECT_CAP + INDIRECT1_COUNT;
 /// 用上二级索引时的编号容量
 const INDIRECT2_CAP: usize = INDIRECT1_CAP + INDIRECT2_COUNT;
-#[allow(dead_code)]
 /// 用上三级索引时的编号容量
 const INDIRECT3_CAP: usize = INDIRECT2_CAP + INDIRECT3_COUNT;
 
+/// 文件的最大字节数，受限于三级索引的容量
+pub const MAX_FILE_SIZE: usize = INDIRECT3_CAP * BLOCK_SIZE;
+
 #[derive(Default)]
 #[repr(C)]
 pub struct DiskInode {
@@ -63,15 +68,30 @@ pub struct DiskInode {
     indirect2: u32,
     /// 指向一个三级索引块
     indirect3: u32,
+    /// 属主的用户ID
+    pub owner: u32,
 }
 
 #[derive(Default, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
 pub enum DiskInodeKind {
     #[default]
     File,
     Directory,
 }
 
+const _: () = {
+    assert!(mem::size_of::<DiskInode>() == 136);
+    assert!(offset_of!(DiskInode, size) == 4);
+    assert!(offset_of!(DiskInode, links) == 8);
+    assert!(offset_of!(DiskInode, kind) == 12);
+    assert!(offset_of!(DiskInode, direct) == 16);
+    assert!(offset_of!(DiskInode, indirect1) == 120);
+    assert!(offset_of!(DiskInode, indirect2) == 124);
+    assert!(offset_of!(DiskInode, indirect3) == 128);
+    assert!(offset_of!(DiskInode, owner) == 132);
+};
+
 impl DiskInode {
     #[inline]
     pub fn init(&mut self, id: u32, kind: DiskInodeKind) {
@@ -143,192 +163,125 @@ impl DiskInode {
         }
     }
 
-    pub fn expand_to(
+    /// 扩展至`larger_size`字节，新增的数据块与索引块均在需要时才经由`alloc`分配，
+    /// 其个数与[`DiskInode::count_total_block`]之差一致。
+    pub fn expand_to<F>(
         &mut self,
         larger_size: u32,
-        new_blocks: Vec<u32>,
+        mut alloc: F,
         block_device: &Arc<dyn BlockDevice>,
-    ) {
-        let mut block_index = Self::count_data_block(self.size);
+    ) where
+        F: FnMut() -> u32,
+    {
+        let old_blocks = Self::count_data_block(self.size);
         self.size = larger_size;
-        let mut new_total_blocks = Self::count_data_block(self.size);
-        let mut new_blocks = new_blocks.into_iter();
-
-        /******************** 直接索引 ********************/
-        // 填充直接索引
-        while block_index < new_total_blocks.min(DIRECT_COUNT) {
-            self.direct[block_index] = new_blocks.next().unwrap();
-            block_index += 1;
-        }
-        /******************** END ********************/
-
-        if new_total_blocks <= DIRECT_COUNT {
-            return;
-        }
-
-        /******************** 一级索引 ********************/
-        // 这次size的增加经过了DIRECT_CAP，创建一级索引
-        if block_index == DIRECT_COUNT {
-            self.indirect1 = new_blocks.next().unwrap();
-        }
-
-        block_index -= DIRECT_COUNT;
-        new_total_blocks -= DIRECT_COUNT;
-
-        // 填充一级索引
-        block_cache::get(self.indirect1 as usize, block_device.clone())
-            .lock()
-            .map_mut(0, |indirect1: &mut IndirectBlock| {
-                while block_index < new_total_blocks.min(INDIRECT1_COUNT) {
-                    indirect1[block_index] = new_blocks.next().unwrap();
-                    block_index += 1;
+        let new_blocks = Self::count_data_block(self.size);
+
+        for block_index in old_blocks..new_blocks {
+            if block_index < DIRECT_CAP {
+                /******************** 直接索引 ********************/
+                self.direct[block_index] = alloc();
+            } else if block_index < INDIRECT1_CAP {
+                /******************** 一级索引 ********************/
+                let index = block_index - DIRECT_CAP;
+                // 首次越过DIRECT_CAP，创建一级索引
+                if index == 0 {
+                    self.indirect1 = alloc();
                 }
-            });
-        /******************** END ********************/
 
-        if new_total_blocks <= INDIRECT1_COUNT {
-            return;
-        }
-
-        /******************** 二级索引 ********************/
-        // 这次size的增加经过了INDIRECT1_CAP，创建二级索引
-        if block_index == INDIRECT1_COUNT {
-            self.indirect2 = new_blocks.next().unwrap();
-        }
-
-        block_index -= INDIRECT1_COUNT;
-        new_total_blocks -= INDIRECT1_COUNT;
-
-        // 填充二级索引
-        let mut index2 = block_index / INDIRECT1_COUNT;
-        let mut index1 = block_index % INDIRECT1_COUNT;
-        let new_end2 = new_total_blocks / INDIRECT1_COUNT;
-        let new_end1 = new_total_blocks % INDIRECT1_COUNT;
-        block_cache::get(self.indirect2 as usize, block_device.clone())
-            .lock()
-            .map_mut(0, |indirect2: &mut IndirectBlock| {
-                // 索引一旦呈树状，就无法用 `new_total_blocks.min(COUNT)` 做限制了；
-                // new_total_blocks 的限制通过 new_end 达成
-                while block_index < INDIRECT2_COUNT
-                    && ((index2 < new_end2) || (index2 == new_end2 && index1 < new_end1))
-                {
-                    // 子块索引为0表示进入新块
-                    if index1 == 0 {
-                        indirect2[index2] = new_blocks.next().unwrap();
-                        block_index += 1;
-                    }
-
-                    block_cache::get(indirect2[index2] as usize, block_device.clone())
-                        .lock()
-                        .map_mut(0, |indirect1: &mut IndirectBlock| {
-                            indirect1[index1] = new_blocks.next().unwrap();
-                            block_index += 1;
-                        });
-
-                    index1 += 1;
-                    if index1 == INDIRECT1_COUNT {
-                        index1 = 0;
-                        index2 += 1;
-                    }
+                let data = alloc();
+                block_cache::get(self.indirect1 as usize, block_device.clone())
+                    .lock()
+                    .map_mut(0, |indirect1: &mut IndirectBlock| indirect1[index] = data);
+            } else if block_index < INDIRECT2_CAP {
+                /******************** 二级索引 ********************/
+                let index = block_index - INDIRECT1_CAP;
+                if index == 0 {
+                    self.indirect2 = alloc();
                 }
-            });
-        /******************** END ********************/
-
-        if new_total_blocks <= INDIRECT2_COUNT {
-            return;
-        }
 
-        /******************** 三级索引 ********************/
-        // 这次size的增加经过了INDIRECT2_CAP，创建三级索引
-        if block_index == INDIRECT2_COUNT {
-            self.indirect3 = new_blocks.next().unwrap();
-        }
-
-        block_index -= INDIRECT2_COUNT;
-        new_total_blocks -= INDIRECT2_COUNT;
-
-        // 填充三级索引
-        let mut index3 = block_index / INDIRECT2_COUNT;
-        let mut index2 = block_index % INDIRECT2_COUNT / INDIRECT1_COUNT;
-        let mut index1 = block_index % INDIRECT1_COUNT;
-        let new_end3 = new_total_blocks / INDIRECT2_COUNT;
-        let new_end2 = new_total_blocks % INDIRECT2_COUNT / INDIRECT1_COUNT;
-        let new_end1 = new_total_blocks % INDIRECT1_COUNT;
-        block_cache::get(self.indirect3 as usize, block_device.clone())
-            .lock()
-            .map_mut(0, |indirect3: &mut IndirectBlock| {
-                while (index3 < new_end3)
-                    || (index3 == new_end3 && index2 < new_end2)
-                    || (index3 == new_end3 && index2 == new_end2 && index1 < new_end1)
-                {
-                    if index2 == 0 {
-                        // 未初始化块该怎么用由我安排
-                        indirect3[index3] = new_blocks.next().unwrap();
-                        block_index += 1;
-                    }
-
-                    block_cache::get(indirect3[index3] as usize, block_device.clone())
-                        .lock()
-                        .map_mut(0, |indirect2: &mut IndirectBlock| {
-                            if index1 == 0 {
-                                indirect2[index2] = new_blocks.next().unwrap();
-                                block_index += 1;
-                            }
-
-                            block_cache::get(indirect2[index2] as usize, block_device.clone())
-                                .lock()
-                                .map_mut(0, |indirect1: &mut IndirectBlock| {
-                                    indirect1[index1] = new_blocks.next().unwrap();
-                                    block_index += 1;
-                                });
-                        });
-
-                    index1 += 1;
-                    if index1 == INDIRECT1_COUNT {
-                        index1 = 0;
-                        index2 += 1;
-                        if index2 == INDIRECT2_COUNT {
-                            index2 = 0;
-                            index3 += 1;
+                let indirect1 = block_cache::get(self.indirect2 as usize, block_device.clone())
+                    .lock()
+                    .map_mut(0, |indirect2: &mut IndirectBlock| {
+                        // 子块索引为0表示进入新块
+                        if index.is_multiple_of(INDIRECT1_COUNT) {
+                            indirect2[index / INDIRECT1_COUNT] = alloc();
                         }
-                    }
+                        indirect2[index / INDIRECT1_COUNT]
+                    });
+
+                let data = alloc();
+                block_cache::get(indirect1 as usize, block_device.clone())
+                    .lock()
+                    .map_mut(0, |indirect1: &mut IndirectBlock| {
+                        indirect1[index % INDIRECT1_COUNT] = data
+                    });
+            } else {
+                /******************** 三级索引 ********************/
+                let index = block_index - INDIRECT2_CAP;
+                if index == 0 {
+                    self.indirect3 = alloc();
                 }
-            });
-        /******************** END ********************/
+
+                let indirect2 = block_cache::get(self.indirect3 as usize, block_device.clone())
+                    .lock()
+                    .map_mut(0, |indirect3: &mut IndirectBlock| {
+                        if index.is_multiple_of(INDIRECT2_COUNT) {
+                            indirect3[index / INDIRECT2_COUNT] = alloc();
+                        }
+                        indirect3[index / INDIRECT2_COUNT]
+                    });
+
+                let indirect1 = block_cache::get(indirect2 as usize, block_device.clone())
+                    .lock()
+                    .map_mut(0, |indirect2: &mut IndirectBlock| {
+                        let index2 = index % INDIRECT2_COUNT / INDIRECT1_COUNT;
+                        if index.is_multiple_of(INDIRECT1_COUNT) {
+                            indirect2[index2] = alloc();
+                        }
+                        indirect2[index2]
+                    });
+
+                let data = alloc();
+                block_cache::get(indirect1 as usize, block_device.clone())
+                    .lock()
+                    .map_mut(0, |indirect1: &mut IndirectBlock| {
+                        indirect1[index % INDIRECT1_COUNT] = data
+                    });
+            }
+        }
     }
 
-    pub fn clear(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
-        let mut drop_data_blocks: Vec<u32> = Vec::with_capacity(Self::count_total_block(self.size));
+    /// 文件占据的全部块，包括各级索引块
+    pub fn blocks(&self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let mut blocks: Vec<u32> = Vec::with_capacity(Self::count_total_block(self.size));
         let mut data_blocks = Self::count_data_block(self.size);
-        self.size = 0;
 
         /******************** 直接索引 ********************/
-        drop_data_blocks.extend_from_slice(&self.direct[..data_blocks.min(DIRECT_CAP)]);
-        self.direct.fill(0);
+        blocks.extend_from_slice(&self.direct[..data_blocks.min(DIRECT_CAP)]);
         /******************** END ********************/
 
         if data_blocks <= DIRECT_COUNT {
-            return drop_data_blocks;
+            return blocks;
         }
 
         /******************** 一级索引 ********************/
-        drop_data_blocks.push(self.indirect1);
+        blocks.push(self.indirect1);
         data_blocks -= DIRECT_COUNT;
 
         block_cache::get(self.indirect1 as usize, block_device.clone())
             .lock()
-            .map_mut(0, |indirect1: &mut IndirectBlock| {
-                drop_data_blocks.extend_from_slice(&indirect1[..data_blocks.min(INDIRECT1_COUNT)]);
+            .map(0, |indirect1: &IndirectBlock| {
+                blocks.extend_from_slice(&indirect1[..data_blocks.min(INDIRECT1_COUNT)]);
             });
-        self.indirect1 = 0;
         /******************** END ********************/
 
         if data_blocks <= INDIRECT1_COUNT {
-            return drop_data_blocks;
+            return blocks;
         }
 
         /******************** 二级索引 ********************/
-        drop_data_blocks.push(self.indirect2);
+        blocks.push(self.indirect2);
         data_blocks -= INDIRECT1_COUNT;
 
         let index2 = if data_blocks <= INDIRECT2_COUNT {
@@ -342,11 +295,11 @@ impl DiskInode {
             .map(0, |indirect2: &IndirectBlock| {
                 // 遍历 index2 之前的所有ID
                 for &block in indirect2.iter().take(index2) {
-                    drop_data_blocks.push(block);
+                    blocks.push(block);
                     block_cache::get(block as usize, block_device.clone())
                         .lock()
                         .map(0, |indirect1: &IndirectBlock| {
-                            drop_data_blocks.extend_from_slice(indirect1);
+                            blocks.extend_from_slice(indirect1);
                         });
                 }
 
@@ -354,25 +307,24 @@ impl DiskInode {
                 // 一级索引在 index1 之前的全部ID
                 let index1 = data_blocks % INDIRECT1_COUNT;
                 if index1 > 0 && index2 != INDIRECT_COUNT {
-                    drop_data_blocks.push(indirect2[index2]);
+                    blocks.push(indirect2[index2]);
                     block_cache::get(indirect2[index2] as usize, block_device.clone())
                         .lock()
                         .map(0, |indirect1: &IndirectBlock| {
-                            drop_data_blocks.extend_from_slice(&indirect1[..index1]);
+                            blocks.extend_from_slice(&indirect1[..index1]);
                         });
                 }
             });
-        self.indirect2 = 0;
         /******************** END ********************/
 
         if data_blocks <= INDIRECT2_COUNT {
-            return drop_data_blocks;
+            return blocks;
         }
 
         /******************** 三级索引 ********************/
         // NOTE: 索引最深为三级时才需要写
         assert!(data_blocks <= INDIRECT3_COUNT);
-        drop_data_blocks.push(self.indirect3);
+        blocks.push(self.indirect3);
         data_blocks -= INDIRECT2_COUNT;
 
         let index3 = data_blocks / INDIRECT2_COUNT;
@@ -381,53 +333,96 @@ impl DiskInode {
             .lock()
             .map(0, |indirect3: &IndirectBlock| {
                 for &block in indirect3.iter().take(index3) {
-                    drop_data_blocks.push(block);
+                    blocks.push(block);
                     block_cache::get(block as usize, block_device.clone())
                         .lock()
                         .map(0, |indirect2: &IndirectBlock| {
                             for &block in indirect2 {
-                                drop_data_blocks.push(block);
+                                blocks.push(block);
                                 block_cache::get(block as usize, block_device.clone())
                                     .lock()
                                     .map(0, |indirect1: &IndirectBlock| {
-                                        drop_data_blocks.extend_from_slice(indirect1);
+                                        blocks.extend_from_slice(indirect1);
                                     });
                             }
                         });
                 }
 
                 let index2 = data_blocks % INDIRECT2_COUNT / INDIRECT1_COUNT;
-                if index2 > 0 {
-                    drop_data_blocks.push(indirect3[index3]);
+                // 末个二级索引块可能只用到了其首个一级索引块的一部分
+                if data_blocks % INDIRECT2_COUNT > 0 {
+                    blocks.push(indirect3[index3]);
                     block_cache::get(indirect3[index3] as usize, block_device.clone())
                         .lock()
                         .map(0, |indirect2: &IndirectBlock| {
                             for &block in indirect2.iter().take(index2) {
-                                drop_data_blocks.push(block);
+                                blocks.push(block);
                                 block_cache::get(block as usize, block_device.clone())
                                     .lock()
                                     .map(0, |indirect1: &IndirectBlock| {
-                                        drop_data_blocks.extend_from_slice(indirect1);
+                                        blocks.extend_from_slice(indirect1);
                                     });
                             }
 
                             let index1 = data_blocks % INDIRECT1_COUNT;
                             if index1 > 0 {
-                                drop_data_blocks.push(indirect2[index2]);
+                                blocks.push(indirect2[index2]);
                                 block_cache::get(indirect2[index2] as usize, block_device.clone())
                                     .lock()
                                     .map(0, |indirect1: &IndirectBlock| {
-                                        drop_data_blocks.extend_from_slice(&indirect1[..index1]);
+                                        blocks.extend_from_slice(&indirect1[..index1]);
                                     });
                             }
                         });
                 }
             });
+        /******************** END ********************/
+
+        blocks
+    }
 
+    /// 清空文件，返回其占据的全部块以便回收
+    pub fn clear(&mut self, block_device: &Arc<dyn BlockDevice>) -> Vec<u32> {
+        let blocks = self.blocks(block_device);
+        self.size = 0;
+        self.direct.fill(0);
+        self.indirect1 = 0;
+        self.indirect2 = 0;
         self.indirect3 = 0;
-        /******************** END ********************/
+        blocks
+    }
 
-        drop_data_blocks
+    /// 依次读出目录下的各项及其偏移，空槽位的名字为空
+    pub fn dir_entries<'a>(
+        &'a self,
+        block_device: &'a Arc<dyn BlockDevice>,
+        checksum: bool,
+    ) -> impl Iterator<Item = (usize, DirEntry)> + 'a {
+        assert!(self.is_dir());
+        let size = self.size as usize;
+        let mut buf = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+        let mut offset = 0;
+
+        iter::from_fn(move || {
+            if offset >= size {
+                return None;
+            }
+            let head = &mut buf[..DirEntry::SIZE];
+            assert_eq!(self.read_at(offset, head, block_device), DirEntry::SIZE);
+            let len = DirEntry::slots_of(head) * DirEntry::SIZE;
+            assert_eq!(
+                self.read_at(
+                    offset + DirEntry::SIZE,
+                    &mut buf[DirEntry::SIZE..len],
+                    block_device
+                ),
+                len - DirEntry::SIZE
+            );
+
+            let item = (offset, DirEntry::decode(&buf[..len], checksum));
+            offset += len;
+            Some(item)
+        })
     }
 
     /// 从指定位置(字节偏移)读出数据填充`buf`
@@ -440,7 +435,7 @@ impl DiskInode {
         let mut start = offset;
         let end = (start + buf.len()).min(self.size as usize);
 
-        if start > end {
+        if start >= end {
             return 0;
         }
 
@@ -536,12 +531,13 @@ impl DiskInode {
 
         // 超出一级索引，使用二级索引块
         if data_blocks > INDIRECT1_CAP {
-            total += 1 + (data_blocks - INDIRECT1_CAP).div_ceil(INDIRECT_COUNT);
+            total += 1 + (data_blocks.min(INDIRECT2_CAP) - INDIRECT1_CAP).div_ceil(INDIRECT_COUNT);
         }
 
-        // 超出二级索引，使用三级索引块
+        // 超出二级索引，使用三级索引块及其下的二级、一级索引块
         if data_blocks > INDIRECT2_CAP {
-            total += 1 + (data_blocks - INDIRECT2_CAP).div_ceil(INDIRECT_COUNT);
+            let rest = data_blocks - INDIRECT2_CAP;
+            total += 1 + rest.div_ceil(INDIRECT2_COUNT) + rest.div_ceil(INDIRECT_COUNT);
         }
 
         total
@@ -2,6 +2,8 @@
 //!
 //! easy-fs 的磁盘布局：
 //! 超级块 | 索引节点位图 | 索引节点区域 | 数据块位图 | 数据块区域
+//!
+//! 各结构的布局即镜像的格式，其定义处以编译期断言核对大小与字段偏移
 
 mod super_block;
 pub use super_block::SuperBlock;
@@ -10,8 +12,8 @@ mod bitmap;
 pub use bitmap::Bitmap;
 
 mod inode;
-pub use inode::{DiskInode, DiskInodeKind};
+pub use inode::{DiskInode, DiskInodeKind, MAX_FILE_SIZE};
 
 /// 文件项，也属于磁盘文件系统数据结构
 mod dir_entry;
-pub use dir_entry::DirEntry;
+pub use dir_entry::{DirEntry, CHECKED_INODE_ID_LIMIT, NAME_MAX_LEN, SHORT_NAME_MAX_LEN};
@@ -1,4 +1,9 @@
-use crate::MAGIC;
+use core::mem::{self, offset_of};
+
+use enumflags2::{BitFlags, FromBitsError};
+
+use super::{DiskInode, CHECKED_INODE_ID_LIMIT};
+use crate::{EfsError, Feature, BLOCK_BITS, BLOCK_SIZE, MAGIC, VERSION};
 
 /// 超级块：
 /// - 提供文件系统合法性校验；
@@ -14,8 +19,24 @@ pub struct SuperBlock {
     pub inode_area_blocks: u32,
     pub data_bitmap_blocks: u32,
     pub data_area_blocks: u32,
+    /// 格式版本，旧镜像中此处为零，见[`VERSION`]
+    version: u32,
+    /// 镜像用到的[`Feature`]，旧镜像中此处为零
+    features: u32,
 }
 
+const _: () = {
+    assert!(mem::size_of::<SuperBlock>() == 32);
+    assert!(offset_of!(SuperBlock, magic) == 0);
+    assert!(offset_of!(SuperBlock, total_blocks) == 4);
+    assert!(offset_of!(SuperBlock, inode_bitmap_blocks) == 8);
+    assert!(offset_of!(SuperBlock, inode_area_blocks) == 12);
+    assert!(offset_of!(SuperBlock, data_bitmap_blocks) == 16);
+    assert!(offset_of!(SuperBlock, data_area_blocks) == 20);
+    assert!(offset_of!(SuperBlock, version) == 24);
+    assert!(offset_of!(SuperBlock, features) == 28);
+};
+
 impl SuperBlock {
     #[inline]
     pub fn init(
@@ -33,6 +54,8 @@ impl SuperBlock {
             inode_area_blocks,
             data_bitmap_blocks,
             data_area_blocks,
+            version: VERSION,
+            features: Feature::SUPPORTED.bits(),
         };
     }
 
@@ -40,4 +63,48 @@ impl SuperBlock {
     pub fn is_valid(&self) -> bool {
         self.magic == MAGIC
     }
+
+    #[inline]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// 镜像用到的特性，含有不认识的特性时返回错误
+    #[inline]
+    pub fn features(&self) -> Result<BitFlags<Feature>, FromBitsError<Feature>> {
+        BitFlags::from_bits(self.features)
+    }
+
+    /// 校验魔数、版本、特性以及各区域的大小，返回镜像用到的特性
+    pub fn validate(&self) -> Result<BitFlags<Feature>, EfsError> {
+        if !self.is_valid() {
+            return Err(EfsError::BadMagic);
+        }
+        if self.version() != VERSION {
+            return Err(EfsError::UnsupportedVersion(self.version()));
+        }
+        let features = self
+            .features()
+            .map_err(|e| EfsError::UnsupportedFeatures(e.invalid_bits()))?;
+
+        // 超级块之后依次是inode位图、inode区、数据位图、数据区，恰好占满整个文件系统
+        let used = 1
+            + self.inode_bitmap_blocks as u64
+            + self.inode_area_blocks as u64
+            + self.data_bitmap_blocks as u64
+            + self.data_area_blocks as u64;
+        let inodes = self.inode_bitmap_blocks as usize * BLOCK_BITS;
+        let inode_area = self.inode_area_blocks as usize * BLOCK_SIZE;
+        if used != self.total_blocks as u64
+            || self.inode_bitmap_blocks == 0
+            || inode_area < inodes * mem::size_of::<DiskInode>()
+            || (self.data_bitmap_blocks as usize * BLOCK_BITS) < self.data_area_blocks as usize
+            || (features.contains(Feature::DirChecksums)
+                && inodes > CHECKED_INODE_ID_LIMIT as usize)
+        {
+            return Err(EfsError::Corrupt);
+        }
+
+        Ok(features)
+    }
 }
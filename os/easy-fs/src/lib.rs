@@ -1,12 +1,15 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(int_roundings)]
 
 extern crate alloc;
 
+use enumflags2::{bitflags, make_bitflags, BitFlags};
+
 /* easyfs 的整体架构，自上而下 */
 
 // 索引节点层：实现文件创建、打开、读写等操作
 mod vfs;
+mod vfs_impl;
 
 // 磁盘块管理器层
 mod efs;
@@ -15,16 +18,59 @@ mod efs;
 mod layout;
 
 // 块缓存层：内存上的磁盘块数据缓存
-mod block_cache;
+pub mod block_cache;
+
+#[cfg(test)]
+mod tests;
 
 pub use self::{
-    efs::EasyFileSystem,
-    layout::DirEntry,
+    block_cache::{set_block_cache, LruBlockCache},
+    efs::{CheckReport, EasyFileSystem},
+    layout::{DirEntry, MAX_FILE_SIZE, NAME_MAX_LEN},
     vfs::{Inode, Stat, StatKind},
+    vfs_impl::EasyVfs,
 };
 
 pub const MAGIC: u32 = 0x3b800001;
+/// 超级块格式的版本：
+/// - 0：最初的格式，超级块中没有版本号与特性；
+/// - 1：超级块带有版本号与特性，见[`Feature`]；
+/// - 2：索引节点带有属主，由132字节增至136字节。
+///
+/// 各版本的索引节点布局不同，只能打开当前版本的镜像
+pub const VERSION: u32 = 2;
 pub const BLOCK_SIZE: usize = 512;
 pub const BLOCK_BITS: usize = BLOCK_SIZE * 8;
 
 type DataBlock = [u8; BLOCK_SIZE];
+
+/// 打开镜像时的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EfsError {
+    /// 不是easy-fs镜像
+    BadMagic,
+    /// 由其它版本的工具生成
+    UnsupportedVersion(u32),
+    /// 用到了本实现不认识的特性，附带这些特性的位
+    UnsupportedFeatures(u32),
+    /// 超级块记录的各区域大小相互矛盾
+    Corrupt,
+    /// 文件系统比所在的设备还大
+    Truncated,
+}
+
+/// 改变了磁盘格式的特性，镜像用到了本实现不认识的特性时拒绝打开
+#[bitflags]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// 目录项可带续接槽位，文件名最长255字节；否则最长27字节
+    LongNames = 1 << 0,
+    /// 目录项带有校验和，inode ID只有24位
+    DirChecksums = 1 << 1,
+}
+
+impl Feature {
+    /// 本实现支持，也是新建的镜像所用的全部特性
+    pub const SUPPORTED: BitFlags<Self> = make_bitflags!(Self::{LongNames | DirChecksums});
+}
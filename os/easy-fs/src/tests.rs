@@ -0,0 +1,209 @@
+//! 磁盘数据结构的往返测试：按磁盘上的字节写出，检查关键字段的位置，再读回比较；
+//! 以及块缓存的淘汰顺序
+
+use alloc::sync::Arc;
+use core::{mem, ptr, slice};
+
+use block_dev::mem::MemDisk;
+use block_dev::{BlockCache, BlockDevice, CacheStats};
+use enumflags2::BitFlags;
+
+use crate::layout::{DirEntry, DiskInode, DiskInodeKind, SuperBlock, NAME_MAX_LEN};
+use crate::{EfsError, Feature, LruBlockCache, BLOCK_SIZE, MAGIC, VERSION};
+
+/// 按磁盘上的字节看待`value`
+fn to_bytes<T>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(ptr::from_ref(value).cast(), mem::size_of::<T>()) }
+}
+
+/// 从磁盘上的字节读回，如同从块缓存中映射
+fn from_bytes<T>(bytes: &[u8]) -> T {
+    assert_eq!(bytes.len(), mem::size_of::<T>());
+    unsafe { ptr::read_unaligned(bytes.as_ptr().cast()) }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn super_block_round_trip() {
+    let mut super_block: SuperBlock = unsafe { mem::zeroed() };
+    super_block.init(8192, 1, 512, 2, 7677);
+    let bytes = to_bytes(&super_block);
+
+    assert_eq!(u32_at(bytes, 0), MAGIC);
+    assert_eq!(u32_at(bytes, 4), 8192);
+    assert_eq!(u32_at(bytes, 8), 1);
+    assert_eq!(u32_at(bytes, 12), 512);
+    assert_eq!(u32_at(bytes, 16), 2);
+    assert_eq!(u32_at(bytes, 20), 7677);
+    assert_eq!(u32_at(bytes, 24), VERSION);
+
+    let decoded: SuperBlock = from_bytes(bytes);
+    assert!(decoded.is_valid());
+    assert_eq!(decoded.total_blocks, 8192);
+    assert_eq!(decoded.data_area_blocks, 7677);
+}
+
+#[test]
+fn disk_inode_round_trip() {
+    let mut inode = DiskInode::default();
+    inode.init(7, DiskInodeKind::Directory);
+    inode.size = 1000;
+    inode.owner = 1000;
+    let bytes = to_bytes(&inode);
+
+    assert_eq!(u32_at(bytes, 0), 7);
+    assert_eq!(u32_at(bytes, 4), 1000);
+    assert_eq!(u32_at(bytes, 8), 1);
+    assert_eq!(bytes[12], DiskInodeKind::Directory as u8);
+    assert_eq!(u32_at(bytes, 132), 1000);
+
+    let decoded: DiskInode = from_bytes(bytes);
+    assert!(decoded.is_dir());
+    assert_eq!((decoded.id, decoded.size, decoded.links), (7, 1000, 1));
+    assert_eq!(decoded.owner, 1000);
+}
+
+#[test]
+fn super_block_versions_and_features() {
+    let mut super_block: SuperBlock = unsafe { mem::zeroed() };
+    super_block.init(8192, 1, 512, 2, 7677);
+    let mut bytes = to_bytes(&super_block).to_vec();
+    assert_eq!(u32_at(&bytes, 28), Feature::SUPPORTED.bits());
+
+    // 旧镜像的超级块只有24字节，其后为零
+    bytes[24..].fill(0);
+    let decoded: SuperBlock = from_bytes(&bytes);
+    assert!(decoded.is_valid());
+    assert_eq!(decoded.version(), 0);
+    assert_eq!(decoded.features().unwrap(), BitFlags::empty());
+
+    let unknown = 1 << 31;
+    bytes[28..].copy_from_slice(&(Feature::SUPPORTED.bits() | unknown).to_le_bytes());
+    let decoded: SuperBlock = from_bytes(&bytes);
+    assert_eq!(decoded.features().unwrap_err().invalid_bits(), unknown);
+}
+
+#[test]
+fn super_block_validation() {
+    let mut super_block: SuperBlock = unsafe { mem::zeroed() };
+    // 与`EasyFileSystem::new(_, 8192, 1)`的布局一致
+    super_block.init(8192, 1, 1088, 2, 7100);
+    assert_eq!(super_block.validate(), Ok(Feature::SUPPORTED));
+    let bytes = to_bytes(&super_block).to_vec();
+
+    let corrupt = |offset: usize, value: u32| {
+        let mut bytes = bytes.clone();
+        bytes[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+        from_bytes::<SuperBlock>(&bytes).validate()
+    };
+    assert_eq!(corrupt(0, 0), Err(EfsError::BadMagic));
+    assert_eq!(
+        corrupt(24, VERSION + 1),
+        Err(EfsError::UnsupportedVersion(VERSION + 1))
+    );
+    // 旧版本的索引节点布局不同
+    assert_eq!(
+        corrupt(24, VERSION - 1),
+        Err(EfsError::UnsupportedVersion(VERSION - 1))
+    );
+    assert_eq!(
+        corrupt(28, 1 << 31),
+        Err(EfsError::UnsupportedFeatures(1 << 31))
+    );
+    // 各区域之和与总块数不符
+    assert_eq!(corrupt(4, 16384), Err(EfsError::Corrupt));
+    // 总块数相符，但inode区容不下位图所能分配的inode
+    let mut shrunk: SuperBlock = from_bytes(&bytes);
+    shrunk.inode_area_blocks -= 1;
+    shrunk.data_area_blocks += 1;
+    assert_eq!(shrunk.validate(), Err(EfsError::Corrupt));
+}
+
+#[test]
+fn dir_entry_round_trip() {
+    let dirent = DirEntry::new("hello.txt", 42);
+    let mut buf = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+    let bytes = dirent.encode(&mut buf, false);
+
+    assert_eq!(bytes.len(), DirEntry::SIZE);
+    assert_eq!(&bytes[..10], b"hello.txt\0");
+    assert_eq!(bytes[27], 0);
+    assert_eq!(u32_at(bytes, 28), 42);
+
+    let decoded = DirEntry::decode(bytes, false);
+    assert_eq!(decoded.name(), "hello.txt");
+    assert_eq!(decoded.inode_id(), 42);
+}
+
+#[test]
+fn long_dir_entry_round_trip() {
+    for len in [27, 28, 59, 60, NAME_MAX_LEN] {
+        let name: String = (0..len).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let dirent = DirEntry::new(&name, 7);
+        let mut buf = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+        let bytes = dirent.encode(&mut buf, false);
+
+        let slots = 1 + len.saturating_sub(27).div_ceil(DirEntry::SIZE);
+        assert_eq!(bytes.len(), slots * DirEntry::SIZE, "{len} bytes");
+        assert_eq!(DirEntry::slots_of(bytes), slots);
+        assert_eq!(&bytes[..27], &name.as_bytes()[..27]);
+        assert_eq!(u32_at(bytes, 28), 7);
+
+        let decoded = DirEntry::decode(bytes, false);
+        assert_eq!(decoded.name(), name);
+        assert_eq!(decoded.inode_id(), 7);
+    }
+}
+
+#[test]
+fn dir_entry_checksum() {
+    let name = "a name long enough to need a continuation slot";
+    let mut buf = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+    let len = DirEntry::new(name, 0x12_3456).encode(&mut buf, true).len();
+    let bytes = &mut buf[..len];
+    assert_ne!(bytes[31], 0);
+
+    let decoded = DirEntry::decode(bytes, true);
+    assert!(decoded.is_intact());
+    assert_eq!(decoded.inode_id(), 0x12_3456);
+    // 空槽位的校验和为零
+    assert!(DirEntry::decode(&[0; DirEntry::SIZE], true).is_intact());
+
+    // 续接槽位中的名字同样受保护
+    bytes[40] ^= 1;
+    assert!(!DirEntry::decode(bytes, true).is_intact());
+    assert!(DirEntry::decode(bytes, false).is_intact());
+}
+
+#[test]
+fn lru_block_cache() {
+    let dev: Arc<dyn BlockDevice> = Arc::new(MemDisk::new(4));
+    let cache = LruBlockCache::new(2);
+
+    cache
+        .get(&dev, 0, BLOCK_SIZE)
+        .lock()
+        .map_mut(0, |b: &mut u8| *b = 0xAA);
+    cache.get(&dev, 1, BLOCK_SIZE);
+    // 块0刚被用过，满时踢走的是块1
+    cache.get(&dev, 0, BLOCK_SIZE);
+    cache.get(&dev, 2, BLOCK_SIZE);
+    assert_eq!(cache.stats(), CacheStats { hits: 1, misses: 3 });
+    cache.get(&dev, 0, BLOCK_SIZE);
+    assert_eq!(cache.stats().hits, 2);
+
+    // 被引用的块不会被踢走，踢走时写回
+    let pinned = cache.get(&dev, 0, BLOCK_SIZE);
+    cache.get(&dev, 3, BLOCK_SIZE);
+    cache.get(&dev, 1, BLOCK_SIZE);
+    assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 5 });
+    drop(pinned);
+    cache.get(&dev, 2, BLOCK_SIZE);
+    cache.sync_all();
+    let mut buf = [0; BLOCK_SIZE];
+    dev.read_block(0, &mut buf);
+    assert_eq!(buf[0], 0xAA);
+}
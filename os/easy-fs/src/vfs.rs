@@ -3,6 +3,7 @@
 //! 位于内存的虚拟文件系统，确立了文件系统的操作逻辑：
 //! 通过多个 [`Inode`] 形成文件树。
 
+use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
 
@@ -12,7 +13,7 @@ use spin::Mutex;
 
 use crate::block_cache;
 use crate::layout::DirEntry;
-use crate::layout::{DiskInode, DiskInodeKind};
+use crate::layout::{DiskInode, DiskInodeKind, MAX_FILE_SIZE};
 use crate::EasyFileSystem;
 
 #[derive(Debug)]
@@ -21,7 +22,7 @@ pub struct Inode {
     block_id: usize,
     /// inode的块内偏移
     block_offset: usize,
-    fs: Arc<Mutex<EasyFileSystem>>,
+    pub(crate) fs: Arc<Mutex<EasyFileSystem>>,
     block_device: Arc<dyn BlockDevice>,
 }
 
@@ -64,8 +65,11 @@ impl Inode {
     /// 在当前 inode 下创建子 inode
     pub fn create(&self, name: &str) -> Option<Arc<Self>> {
         let mut fs = self.fs.lock();
+        if name.len() > fs.name_max() {
+            return None;
+        }
 
-        let inode_id = self.on_disk(|root_inode: &DiskInode| self.get(root_inode, name));
+        let inode_id = self.on_disk(|root_inode: &DiskInode| self.get(root_inode, name, &fs));
         // 确认没有已创建的同名项
         if inode_id.is_some() {
             return None;
@@ -81,9 +85,7 @@ impl Inode {
             });
 
         self.on_disk_mut(|root_inode| {
-            let slot = self.find_or_new_slot(root_inode, &mut fs);
-            let dir_entry = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(slot, dir_entry.as_bytes(), &self.block_device);
+            self.insert(root_inode, DirEntry::new(name, new_inode_id), &mut fs)
         });
 
         block_cache::sync_all();
@@ -101,7 +103,13 @@ impl Inode {
         self.on_disk(|disk_inode| disk_inode.read_at(offset, buf, &self.block_device))
     }
 
+    /// 越过[`MAX_FILE_SIZE`]的部分不写入，返回实际写入的字节数
     pub fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        if offset >= MAX_FILE_SIZE {
+            return 0;
+        }
+        let buf = &buf[..buf.len().min(MAX_FILE_SIZE - offset)];
+
         let mut fs = self.fs.lock();
         let size = self.on_disk_mut(|disk_inode| {
             self.expand_to((offset + buf.len()) as u32, disk_inode, &mut fs);
@@ -121,39 +129,61 @@ impl Inode {
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
         let fs = self.fs.lock();
         self.on_disk(|disk_inode| {
-            self.get(disk_inode, name)
+            self.get(disk_inode, name, &fs)
                 .map(|inode_id| Arc::new(self.inode(&fs, inode_id)))
         })
     }
 
-    pub fn link_at(&self, name: &str, new_path: &str) -> Option<()> {
+    /// 列出目录下所有文件的名字
+    pub fn ls(&self) -> Vec<String> {
+        let fs = self.fs.lock();
+        self.on_disk(|disk_inode| {
+            self.live_entries(disk_inode, &fs)
+                .map(|(_, dir_entry)| String::from(dir_entry.name()))
+                .collect()
+        })
+    }
+
+    /// 为当前目录下的`name`建立硬链接`new_path`
+    pub fn link_at(&self, name: &str, new_path: &str) -> Result<(), vfs::Error> {
         let mut fs = self.fs.lock();
+        if new_path.len() > fs.name_max() {
+            return Err(vfs::Error::NameTooLong);
+        }
 
-        let inode_id = self.on_disk(|root_inode: &DiskInode| {
+        let (inode_id, existing) = self.on_disk(|root_inode: &DiskInode| {
             assert!(root_inode.is_dir());
-            self.get(root_inode, name)
-        })?;
+            (
+                self.get(root_inode, name, &fs),
+                self.get(root_inode, new_path, &fs),
+            )
+        });
+        let inode_id = inode_id.ok_or(vfs::Error::NotFound)?;
+        if existing.is_some() {
+            return Err(vfs::Error::AlreadyExists);
+        }
         self.inode(&fs, inode_id).on_disk_mut(|disk_inode| {
             disk_inode.links += 1;
         });
 
         self.on_disk_mut(|root_inode| {
-            let slot = self.find_or_new_slot(root_inode, &mut fs);
-            let dir_entry = DirEntry::new(new_path, inode_id);
-            root_inode.write_at(slot, dir_entry.as_bytes(), &self.block_device);
+            self.insert(root_inode, DirEntry::new(new_path, inode_id), &mut fs)
         });
 
         block_cache::sync_all();
-        Some(())
+        Ok(())
     }
 
-    pub fn unlink_at(&self, name: &str) -> Option<()> {
+    /// 删除当前目录下的`name`，最后一个链接被删除时释放其索引节点
+    pub fn unlink_at(&self, name: &str) -> Result<(), vfs::Error> {
         let mut fs = self.fs.lock();
 
-        let inode_id = self.on_disk_mut(|root_inode| {
-            assert!(root_inode.is_dir());
-            self.remove(root_inode, name)
-        })?;
+        let inode_id = self
+            .on_disk_mut(|root_inode| {
+                assert!(root_inode.is_dir());
+                self.remove(root_inode, name, &fs)
+            })
+            .ok_or(vfs::Error::NotFound)?;
         let inode = self.inode(&fs, inode_id);
 
         let links = inode.on_disk_mut(|disk_inode| {
@@ -162,10 +192,29 @@ impl Inode {
         });
         if links == 0 {
             inode.internal_clear(&mut fs);
+            fs.dealloc_inode(inode_id);
         }
 
         block_cache::sync_all();
-        Some(())
+        Ok(())
+    }
+
+    /// 文件的字节数
+    pub fn size(&self) -> usize {
+        let _fs = self.fs.lock();
+        self.on_disk(|disk_inode| disk_inode.size as usize)
+    }
+
+    /// 属主的用户ID
+    pub fn owner(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.on_disk(|disk_inode| disk_inode.owner)
+    }
+
+    pub fn set_owner(&self, uid: u32) {
+        let _fs = self.fs.lock();
+        self.on_disk_mut(|disk_inode| disk_inode.owner = uid);
+        block_cache::sync_all();
     }
 
     pub fn stat(&self) -> Stat {
@@ -195,64 +244,73 @@ impl Inode {
             .map_mut(self.block_offset, f)
     }
 
-    /// 在 DiskInode 下通过名字获取目录项的inode ID
-    fn get(&self, disk_inode: &DiskInode, name: &str) -> Option<u32> {
-        assert!(disk_inode.is_dir());
-        // 目录下存放的是文件系统项元信息
-        let size = disk_inode.size as usize;
-        let mut dir_entry = DirEntry::default();
-
-        for offset in (0..size).step_by(DirEntry::SIZE) {
-            assert_eq!(
-                disk_inode.read_at(offset, dir_entry.as_bytes_mut(), &self.block_device),
-                DirEntry::SIZE
-            );
-            if dir_entry.name() == name {
-                return Some(dir_entry.inode_id());
-            }
-        }
+    /// 目录下完好且非空的各项及其偏移，校验和不符的项视而不见
+    fn live_entries<'a>(
+        &'a self,
+        disk_inode: &'a DiskInode,
+        fs: &EasyFileSystem,
+    ) -> impl Iterator<Item = (usize, DirEntry)> + 'a {
+        disk_inode
+            .dir_entries(&self.block_device, fs.dir_checksums())
+            .filter(|(_, dir_entry)| dir_entry.is_intact() && !dir_entry.is_empty())
+    }
 
-        None
+    /// 在 DiskInode 下通过名字获取目录项的inode ID
+    fn get(&self, disk_inode: &DiskInode, name: &str, fs: &EasyFileSystem) -> Option<u32> {
+        self.live_entries(disk_inode, fs)
+            .find(|(_, dir_entry)| dir_entry.name() == name)
+            .map(|(_, dir_entry)| dir_entry.inode_id())
     }
 
-    /// 在 DiskInode 下通过名字删除目录项并返回其inode ID
-    fn remove(&self, disk_inode: &mut DiskInode, name: &str) -> Option<u32> {
-        assert!(disk_inode.is_dir());
-        let size = disk_inode.size as usize;
-        let mut dir_entry = DirEntry::default();
-
-        for offset in (0..size).step_by(DirEntry::SIZE) {
-            assert_eq!(
-                disk_inode.read_at(offset, dir_entry.as_bytes_mut(), &self.block_device),
-                DirEntry::SIZE
-            );
-            if dir_entry.name() == name {
-                disk_inode.write_at(offset, &[0; DirEntry::SIZE], &self.block_device);
-                return Some(dir_entry.inode_id());
-            }
-        }
+    /// 在 DiskInode 下通过名字删除目录项并返回其inode ID，续接槽位一并清空
+    fn remove(&self, disk_inode: &mut DiskInode, name: &str, fs: &EasyFileSystem) -> Option<u32> {
+        let (offset, dir_entry) = self
+            .live_entries(disk_inode, fs)
+            .find(|(_, dir_entry)| dir_entry.name() == name)?;
 
-        None
+        let zeros = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+        let len = dir_entry.slots() * DirEntry::SIZE;
+        disk_inode.write_at(offset, &zeros[..len], &self.block_device);
+        Some(dir_entry.inode_id())
     }
 
-    /// 在当前目录的数据当中，寻找空槽位；找不到就分配新槽位
-    fn find_or_new_slot(&self, disk_inode: &mut DiskInode, fs: &mut EasyFileSystem) -> usize {
-        assert!(disk_inode.is_dir());
+    /// 将目录项写入当前目录的数据当中
+    fn insert(&self, disk_inode: &mut DiskInode, dir_entry: DirEntry, fs: &mut EasyFileSystem) {
+        let slot = self.find_or_new_slots(disk_inode, dir_entry.slots(), fs);
+        let mut buf = [0; DirEntry::SIZE * DirEntry::MAX_SLOTS];
+        let bytes = dir_entry.encode(&mut buf, fs.dir_checksums());
+        disk_inode.write_at(slot, bytes, &self.block_device);
+    }
+
+    /// 在当前目录的数据当中，寻找`count`个连续的空槽位；找不到就在末尾分配新槽位
+    fn find_or_new_slots(
+        &self,
+        disk_inode: &mut DiskInode,
+        count: usize,
+        fs: &mut EasyFileSystem,
+    ) -> usize {
         let size = disk_inode.size as usize;
-        let mut dir_entry = DirEntry::default();
-
-        for offset in (0..size).step_by(DirEntry::SIZE) {
-            assert_eq!(
-                disk_inode.read_at(offset, dir_entry.as_bytes_mut(), &self.block_device),
-                DirEntry::SIZE
-            );
-            if dir_entry.name().is_empty() {
-                return offset;
+        let wanted = count * DirEntry::SIZE;
+        // 连续空槽位的起点，空槽位总是单独占据一个槽位
+        let mut run = None;
+
+        let entries = disk_inode.dir_entries(&self.block_device, fs.dir_checksums());
+        for (offset, dir_entry) in entries {
+            // 损坏的项留待检查时处理，不可覆盖
+            if !dir_entry.is_intact() || !dir_entry.is_empty() {
+                run = None;
+                continue;
+            }
+            let start = *run.get_or_insert(offset);
+            if offset + DirEntry::SIZE - start >= wanted {
+                return start;
             }
         }
 
-        self.expand_to((size + DirEntry::SIZE) as u32, disk_inode, fs);
-        size
+        // 末尾的空槽位不够，接着它们扩充
+        let start = run.unwrap_or(size);
+        self.expand_to((start + wanted) as u32, disk_inode, fs);
+        start
     }
 
     /// 凭借ID获取Inode
@@ -267,27 +325,42 @@ impl Inode {
         )
     }
 
+    /// 扩充至`larger_size`字节，不大于当前大小时什么也不做，如覆写文件中间
     fn expand_to(&self, larger_size: u32, disk_inode: &mut DiskInode, fs: &mut EasyFileSystem) {
-        assert!(larger_size > disk_inode.size);
+        if larger_size <= disk_inode.size {
+            return;
+        }
 
-        let new_blocks = DiskInode::count_total_block(larger_size)
-            - DiskInode::count_total_block(disk_inode.size);
-        let new_blocks: Vec<u32> = (0..new_blocks).map(|_| fs.alloc_data()).collect();
+        const BATCH: usize = 64;
 
-        // 传进去的是一批未初始化块的ID
-        disk_inode.expand_to(larger_size, new_blocks, &self.block_device);
+        let mut remaining = DiskInode::count_total_block(larger_size)
+            - DiskInode::count_total_block(disk_inode.size);
+        let mut batch = [0; BATCH];
+        let (mut next, mut len) = (0, 0);
+
+        // 按需分配未初始化块，每次向位图批量索取
+        disk_inode.expand_to(
+            larger_size,
+            || {
+                if next == len {
+                    len = remaining.min(BATCH);
+                    fs.alloc_data_batch(&mut batch[..len]);
+                    remaining -= len;
+                    next = 0;
+                }
+                next += 1;
+                batch[next - 1]
+            },
+            &self.block_device,
+        );
     }
 
     fn internal_clear(&self, fs: &mut EasyFileSystem) {
         self.on_disk_mut(|disk_inode| {
-            let data_blocks = disk_inode.clear(&self.block_device);
-            assert_eq!(
-                data_blocks.len(),
-                DiskInode::count_total_block(disk_inode.size)
-            );
-            for data_block in data_blocks {
-                fs.dealloc_data(data_block);
-            }
+            let total_blocks = DiskInode::count_total_block(disk_inode.size);
+            let mut data_blocks = disk_inode.clear(&self.block_device);
+            assert_eq!(data_blocks.len(), total_blocks);
+            fs.dealloc_data_batch(&mut data_blocks);
         });
     }
 }
@@ -0,0 +1,195 @@
+//! 以[`vfs::FileSystem`]与[`vfs::VfsInode`]特型对象暴露easy-fs。
+//! easy-fs只有根目录一层，其下都是普通文件
+
+use alloc::sync::Arc;
+use core::any::Any;
+
+use spin::Mutex;
+use vfs::{DirEntry, DirEntryType, VfsInode};
+
+use crate::layout::DiskInode;
+use crate::{EasyFileSystem, Inode, StatKind, BLOCK_SIZE, MAX_FILE_SIZE};
+
+/// 打开的easy-fs镜像
+pub struct EasyVfs(Arc<Mutex<EasyFileSystem>>);
+
+impl EasyVfs {
+    pub fn new(efs: Arc<Mutex<EasyFileSystem>>) -> Self {
+        Self(efs)
+    }
+}
+
+impl vfs::FileSystem for EasyVfs {
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(EasyFileSystem::root_inode(&self.0))
+    }
+}
+
+impl Inode {
+    fn is_dir(&self) -> bool {
+        self.stat().kind == StatKind::DIR
+    }
+
+    fn dir(&self) -> Result<&Self, vfs::Error> {
+        if self.is_dir() {
+            Ok(self)
+        } else {
+            Err(vfs::Error::NotADirectory)
+        }
+    }
+
+    fn file(&self) -> Result<&Self, vfs::Error> {
+        if self.is_dir() {
+            Err(vfs::Error::IsADirectory)
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+impl VfsInode for Inode {
+    fn id(&self) -> u64 {
+        self.stat().inode
+    }
+
+    fn kind(&self) -> DirEntryType {
+        dirent_type(self.stat().kind)
+    }
+
+    /// easy-fs不记录时间，均为0
+    fn stat(&self) -> vfs::Stat {
+        let size = self.size() as u64;
+        vfs::Stat {
+            mode: self.kind(),
+            block_size: BLOCK_SIZE as u64,
+            blocks: size.div_ceil(BLOCK_SIZE as u64),
+            size,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+
+    fn size(&self) -> usize {
+        Inode::size(self)
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, vfs::Error> {
+        Ok(Inode::read_at(self.file()?, offset, buf))
+    }
+
+    /// 越过[`MAX_FILE_SIZE`]的部分不写入，一个字节也写不了时报错
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, vfs::Error> {
+        match Inode::write_at(self.file()?, offset, buf) {
+            0 if !buf.is_empty() => Err(vfs::Error::FileTooLarge),
+            size => Ok(size),
+        }
+    }
+
+    /// 只能清空文件
+    fn truncate(&self, size: usize) -> Result<(), vfs::Error> {
+        let file = self.file()?;
+        if size == 0 {
+            file.clear();
+            Ok(())
+        } else if size == file.size() {
+            Ok(())
+        } else {
+            Err(vfs::Error::Unsupported)
+        }
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_FILE_SIZE
+    }
+
+    fn allocated(&self, size: usize) -> usize {
+        DiskInode::count_total_block(size.min(MAX_FILE_SIZE) as u32) * BLOCK_SIZE
+    }
+
+    fn owner(&self) -> u32 {
+        Inode::owner(self)
+    }
+
+    fn set_owner(&self, uid: u32) -> Result<(), vfs::Error> {
+        Inode::set_owner(self, uid);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn VfsInode>, vfs::Error> {
+        let inode = self.dir()?.find(name).ok_or(vfs::Error::NotFound)?;
+        Ok(inode)
+    }
+
+    fn create(&self, name: &str) -> Result<Arc<dyn VfsInode>, vfs::Error> {
+        let dir = self.dir()?;
+        if name.len() > self.fs.lock().name_max() {
+            return Err(vfs::Error::NameTooLong);
+        }
+        let inode = Inode::create(dir, name).ok_or(vfs::Error::AlreadyExists)?;
+        Ok(inode)
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), vfs::Error> {
+        self.dir()?.unlink_at(name)
+    }
+
+    /// 只有根目录，`new_dir`须是同一镜像的根目录
+    fn link(&self, name: &str, new_dir: &dyn VfsInode, new_name: &str) -> Result<(), vfs::Error> {
+        let dir = self.dir()?;
+        let new_dir = new_dir
+            .as_any()
+            .downcast_ref::<Self>()
+            .filter(|new_dir| Arc::ptr_eq(&new_dir.fs, &self.fs))
+            .ok_or(vfs::Error::CrossesDevices)?;
+        if new_dir.id() != self.id() {
+            return Err(vfs::Error::NotADirectory);
+        }
+        dir.link_at(name, new_name)
+    }
+
+    /// 偏移量是目录项的序号
+    fn read_dir(
+        &self,
+        offset: usize,
+        sink: &mut dyn FnMut(&DirEntry) -> bool,
+    ) -> Result<usize, vfs::Error> {
+        self.read_dir_plus(offset, &mut |dirent, _| sink(dirent))
+    }
+
+    /// 列出名字时本就要找到各项的索引节点，状态顺带得出
+    fn read_dir_plus(
+        &self,
+        offset: usize,
+        sink: &mut dyn FnMut(&DirEntry, &vfs::Stat) -> bool,
+    ) -> Result<usize, vfs::Error> {
+        let dir = self.dir()?;
+        let mut offset = offset;
+        for name in dir.ls().into_iter().skip(offset) {
+            // 目录项指向的索引节点总在
+            let inode = dir.find(&name).expect("listed entry");
+            let stat = VfsInode::stat(&*inode);
+            let dirent = DirEntry {
+                inode: VfsInode::id(&*inode),
+                ty: stat.mode,
+                name,
+            };
+            if !sink(&dirent, &stat) {
+                break;
+            }
+            offset += 1;
+        }
+        Ok(offset)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn dirent_type(kind: StatKind) -> DirEntryType {
+    match kind {
+        StatKind::DIR => DirEntryType::Directory,
+        StatKind::FILE => DirEntryType::Regular,
+    }
+}
@@ -4,14 +4,33 @@ use core::ops::Sub;
 #[repr(transparent)]
 pub struct ClusterId<T = u32>(T);
 
-#[derive(Debug, PartialEq, Eq)]
+/// 簇编号或FAT表项不指向可用的簇
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClusterError {
+    /// 未分配的簇
     Free,
+    /// 坏簇，不可再分配
     Defective,
+    /// 保留的编号，或超出了FAT表
     Reserved,
+    /// 链表的末尾
     Eof,
 }
 
+impl core::fmt::Display for ClusterError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let msg = match self {
+            Self::Free => "cluster is free",
+            Self::Defective => "cluster is defective",
+            Self::Reserved => "cluster id is reserved",
+            Self::Eof => "end of cluster chain",
+        };
+        f.write_str(msg)
+    }
+}
+
+impl core::error::Error for ClusterError {}
+
 impl Sub for ClusterId<u32> {
     type Output = Self;
 
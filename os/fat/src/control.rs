@@ -1,45 +1,105 @@
 use alloc::sync::Arc;
-use alloc::vec;
 use alloc::vec::Vec;
 use core::iter::Iterator;
 use core::mem;
 use core::ops::Range;
 
 use block_dev::BlockDevice;
+use spin::{RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use crate::volume::{
     data::DataArea,
     fat::Fat,
-    reserved::{Bpb, FsInfo},
+    reserved::{self, Bpb, FatType, FsInfo},
 };
-use crate::{sector, ClusterId, SectorId};
-
+use crate::{sector, ClusterError, ClusterId, SectorId};
+
+/// FAT文件系统，支持FAT12/16/32卷，格式化时只生成FAT32卷
+///
+/// 卷的几何信息与配置在加载后只读；会变的只有FAT表，由读写锁保护，
+/// 单个扇区的读写则由扇区缓存各自的锁保护。因此所有操作都只需`&self`，
+/// 查找与读取不必等待进行中的修改，只在沿簇链表前进时短暂地共享FAT表。
+///
+/// 修改文件或目录涉及多个扇区，调用者须将各修改彼此串行化。
 #[derive(Debug)]
 pub struct FatFileSystem {
-    /// FAT
-    fat: Fat,
+    /// FAT，分配与回收簇时独占
+    fat: RwLock<Fat>,
     /// 数据区
     data_area: DataArea,
+    root: RootDir,
+    writeback: Writeback,
+    /// 延迟写回时，每次修改后调用，供调用者安排后台写回
+    flush_hook: Option<fn()>,
+    /// 自UNIX纪元的秒数，用作目录项的时间戳
+    clock: Option<fn() -> u64>,
+}
+
+/// 根目录的位置。[`ROOT`](crate::ROOT)的首簇记为[`ClusterId::FREE`]，
+/// 与指向根目录的`..`目录项一致，访问时再换成此处的位置
+#[derive(Debug, Clone)]
+enum RootDir {
+    /// FAT32：与其它目录一样是簇链表
+    Chain(ClusterId<u32>),
+    /// FAT12/16：FAT区与数据区之间的定长区域，不能扩展
+    Fixed(Range<SectorId>),
+}
+
+/// 修改文件或目录后何时写回扇区
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Writeback {
+    /// 每次修改后立即写回所有脏扇区
+    Immediate,
+    /// 脏扇区留在缓存中，直到被踢出或调用[`FatFileSystem::sync`]，
+    /// 连续的小写入因此合并为一次写回
+    #[default]
+    Delayed,
 }
 
 impl FatFileSystem {
-    pub fn load(dev: &Arc<dyn BlockDevice>) -> Self {
+    /// 加载设备上的卷，卷比设备还大时返回`None`
+    pub fn load(dev: &Arc<dyn BlockDevice>) -> Option<Self> {
         let bpb: Bpb = {
             let mut buf = [0u8; mem::size_of::<Bpb>()];
             dev.read_block(0, &mut buf);
             unsafe { mem::transmute(buf) }
         };
 
+        if bpb.total_sectors() * bpb.sector_bytes() > device_size(dev) {
+            log::error!(
+                "FAT volume of {} sectors exceeds the device of {} blocks",
+                bpb.total_sectors(),
+                dev.num_blocks()
+            );
+            return None;
+        }
+
         sector::init_cache(&bpb, dev);
 
-        FatFileSystem {
-            fat: Fat::new(&bpb),
+        let fat = Fat::new(&bpb);
+        let root = match fat.fat_type() {
+            FatType::T32 => RootDir::Chain(bpb.root_cluster()),
+            FatType::T12 | FatType::T16 => RootDir::Fixed(bpb.root_dir_area()),
+        };
+        log::info!(
+            "{:?} volume of {} clusters",
+            fat.fat_type(),
+            bpb.total_clusters()
+        );
+
+        Some(FatFileSystem {
+            fat: RwLock::new(fat),
             data_area: DataArea::new(&bpb),
-        }
+            root,
+            writeback: Writeback::default(),
+            flush_hook: None,
+            clock: None,
+        })
     }
 
-    pub fn foramt(disk_size: usize, dev: &Arc<dyn BlockDevice>) -> Self {
-        let bpb = Bpb::new(disk_size);
+    /// 将整个设备格式化为FAT32卷
+    pub fn foramt(dev: &Arc<dyn BlockDevice>) -> Self {
+        let bpb = Bpb::new(device_size(dev));
         let mut fat = Fat::new(&bpb);
         let data_area = DataArea::new(&bpb);
 
@@ -75,23 +135,119 @@ impl FatFileSystem {
 
         sector::sync_all();
 
-        Self { fat, data_area }
+        Self {
+            fat: RwLock::new(fat),
+            data_area,
+            root: RootDir::Chain(ClusterId::MIN),
+            writeback: Writeback::default(),
+            flush_hook: None,
+            clock: None,
+        }
     }
 
-    pub const fn fat(&self) -> &Fat {
-        &self.fat
+    /// 卷上次是否正常卸载
+    pub fn is_clean(&self) -> bool {
+        self.fat().is_clean()
     }
 
-    pub fn fat_mut(&mut self) -> &mut Fat {
-        &mut self.fat
+    /// 挂载后调用，卸载前的崩溃会在卷上留下未正常卸载的标记
+    pub fn mark_dirty(&self) {
+        self.fat_mut().set_clean(false);
+        sector::sync_all();
+    }
+
+    /// 写回FSInfo的备份(仅FAT32)，标记卷为正常卸载，并写回所有扇区
+    pub fn unmount(&self) {
+        let mut fat = self.fat_mut();
+        if fat.fat_type() == FatType::T32 {
+            reserved::write_backup();
+        }
+        fat.set_clean(true);
+        sector::sync_all();
+    }
+
+    pub fn set_writeback(&mut self, writeback: Writeback) {
+        self.writeback = writeback;
+    }
+
+    /// 登记延迟写回时每次修改后调用的钩子，调用者可借此安排后台写回。
+    /// 钩子在修改结束前调用，其中可以查找与读取，但不能再修改
+    pub fn set_flush_hook(&mut self, hook: fn()) {
+        self.flush_hook = Some(hook);
+    }
+
+    /// 登记时钟，此后创建与写入文件时记录时间。未登记时目录项的时间戳保持不变
+    pub fn set_clock(&mut self, clock: fn() -> u64) {
+        self.clock = Some(clock);
+    }
+
+    /// 当前时间，未登记时钟时为`None`
+    pub(crate) fn now(&self) -> Option<u64> {
+        self.clock.map(|clock| clock())
+    }
+
+    /// 写回所有脏扇区，并令设备落盘
+    pub fn sync(&self) {
+        sector::sync_all();
+    }
+
+    /// 修改了文件或目录，按写回策略立即写回，或通知钩子
+    pub(crate) fn written(&self) {
+        match self.writeback {
+            Writeback::Immediate => self.sync(),
+            Writeback::Delayed => {
+                if let Some(hook) = self.flush_hook {
+                    hook();
+                }
+            }
+        }
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.fat().fat_type()
+    }
+
+    /// 共享FAT表，持有期间不能再调用[`Self::fat_mut`]或分配簇
+    pub fn fat(&self) -> RwLockReadGuard<'_, Fat> {
+        self.fat.read()
+    }
+
+    pub fn fat_mut(&self) -> RwLockWriteGuard<'_, Fat> {
+        self.fat.write()
     }
 
     pub const fn data(&self) -> &DataArea {
         &self.data_area
     }
 
-    pub fn alloc_cluster(&mut self) -> (ClusterId<u32>, Range<SectorId>) {
-        let id = self.fat.alloc().unwrap();
+    /// 表面扫描：向每个未分配簇的扇区写入测试图样并读回比对，有差错的簇标为坏簇。
+    ///
+    /// 未分配簇中的数据会被覆盖。返回新标出的坏簇个数。
+    pub fn scan_surface(&self) -> usize {
+        let mut marked = 0;
+
+        for id in (usize::from(ClusterId::MIN)..).map(ClusterId::from) {
+            let Ok(mut sectors) = self.data_area.cluster(id) else {
+                break;
+            };
+            if sectors.is_empty() {
+                break;
+            }
+            if self.fat().next(id) != Err(ClusterError::Free) {
+                continue;
+            }
+            if !sectors.all(sector::probe) && self.fat_mut().mark_bad(id) {
+                log::warn!("Cluster {id} is defective");
+                marked += 1;
+            }
+        }
+        sector::sync_all();
+
+        marked
+    }
+
+    pub fn alloc_cluster(&self) -> (ClusterId<u32>, Range<SectorId>) {
+        let id = self.fat_mut().alloc().unwrap();
         let sectors = self
             .data_area
             .cluster(id)
@@ -104,25 +260,89 @@ impl FatFileSystem {
         (id, sectors)
     }
 
+    /// 分配`n`个已清零的簇并链接成链表，返回首簇。
+    ///
+    /// 优先分配编号连续的一段，使大文件的簇链表只占少数几段；找不到时退而逐个分配再链接。
+    pub fn alloc_cluster_run(&self, n: usize) -> ClusterId<u32> {
+        let head = self.fat_mut().alloc_run(n);
+        let Some(head) = head else {
+            let (head, _) = self.alloc_cluster();
+            let mut last = head;
+            for _ in 1..n {
+                let (next, _) = self.alloc_cluster();
+                unsafe { self.fat_mut().couple(last, next) };
+                last = next;
+            }
+            return head;
+        };
+
+        let tail = ClusterId::from(usize::from(head) + n - 1);
+        let start = self.data_area.cluster(head).unwrap().start;
+        let end = self.data_area.cluster(tail).unwrap().end;
+        for sid in start..end {
+            sector::get(sid).lock().zeroize();
+        }
+        head
+    }
+
     pub fn data_sectors(
         &self,
         start_cluster: ClusterId<u32>,
     ) -> impl Iterator<Item = SectorId> + '_ {
         DataSectors {
             id: Some(start_cluster),
+            fixed: None,
             control: self,
         }
         .flatten()
     }
 
-    pub fn data_sector_cursor(&self, start_cluster: ClusterId<u32>) -> SectorCursor {
+    /// 首簇为`start_cluster`的目录的所有扇区，根目录的首簇为[`ClusterId::FREE`]
+    pub fn dir_sectors(
+        &self,
+        start_cluster: ClusterId<u32>,
+    ) -> impl Iterator<Item = SectorId> + '_ {
+        let (id, fixed) = match self.dir_head(start_cluster) {
+            (Some(id), _) => (Some(id), None),
+            (None, sectors) => (None, Some(sectors)),
+        };
+        DataSectors {
+            id,
+            fixed,
+            control: self,
+        }
+        .flatten()
+    }
+
+    pub fn dir_sector_cursor(&self, start_cluster: ClusterId<u32>) -> SectorCursor<'_> {
         SectorCursor::new(start_cluster, self)
     }
+
+    /// 目录真正的首簇及其扇区；FAT12/16的根目录不在簇链表上，首簇为`None`，
+    /// 扇区为整个根目录区
+    pub(crate) fn dir_head(
+        &self,
+        start_cluster: ClusterId<u32>,
+    ) -> (Option<ClusterId<u32>>, Range<SectorId>) {
+        let id = match (&self.root, start_cluster) {
+            (RootDir::Fixed(sectors), ClusterId::FREE) => return (None, sectors.clone()),
+            (&RootDir::Chain(root), ClusterId::FREE) => root,
+            (_, id) => id,
+        };
+        (Some(id), self.data_area.cluster(id).unwrap())
+    }
+}
+
+/// 设备的字节数
+fn device_size(dev: &Arc<dyn BlockDevice>) -> usize {
+    dev.num_blocks() * dev.block_size()
 }
 
 #[derive(Debug)]
 struct DataSectors<'a> {
     id: Option<ClusterId<u32>>,
+    /// 不在簇链表上的扇区，即FAT12/16的根目录区
+    fixed: Option<Range<SectorId>>,
     control: &'a FatFileSystem,
 }
 
@@ -130,28 +350,40 @@ impl Iterator for DataSectors<'_> {
     type Item = Range<SectorId>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(sectors) = self.fixed.take() {
+            return Some(sectors);
+        }
         let id = self.id.take()?;
-        let sectors = self.control.data_area.cluster(id).unwrap();
-        self.id = self.control.fat.next(id).unwrap();
+        let sectors = self.control.data_area.cluster(id).ok()?;
+        self.id = self
+            .control
+            .fat()
+            .next(id)
+            .inspect_err(|e| log::error!("Cluster chain broken after {id}: {e}"))
+            .ok()
+            .flatten();
         Some(sectors)
     }
 }
 
+/// 目录扇区的双向游标
 #[derive(Debug)]
 pub struct SectorCursor<'a> {
     current: (usize, Range<SectorId>, usize, SectorId),
+    /// 已走过的簇，FAT12/16的根目录为空
     clusters: Vec<ClusterId>,
     control: &'a FatFileSystem,
 }
 
 impl<'a> SectorCursor<'a> {
+    /// 根目录的首簇为[`ClusterId::FREE`]
     pub fn new(start_cluster: ClusterId<u32>, control: &'a FatFileSystem) -> Self {
-        let sids = control.data_area.cluster(start_cluster).unwrap();
+        let (head, sids) = control.dir_head(start_cluster);
         let start_sector = sids.start;
 
         Self {
             current: (0, sids, 0, start_sector),
-            clusters: vec![start_cluster],
+            clusters: head.into_iter().collect(),
             control,
         }
     }
@@ -228,7 +460,14 @@ impl<'a> SectorCursor<'a> {
             let next_cid = match self.clusters.get(next_ci) {
                 Some(&next_cid) => next_cid,
                 None => {
-                    let next_cid = self.control.fat.next(self.clusters[*cindex]).unwrap()?;
+                    // 定长的根目录区没有下一个簇
+                    let cid = *self.clusters.get(*cindex)?;
+                    let next_cid = self
+                        .control
+                        .fat()
+                        .next(cid)
+                        .inspect_err(|e| log::error!("Cluster chain broken after {cid}: {e}"))
+                        .ok()??;
                     self.clusters.push(next_cid);
                     next_cid
                 }
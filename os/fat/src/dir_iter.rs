@@ -0,0 +1,218 @@
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::Range;
+
+use vfs::{DirEntryType, Stat};
+
+use crate::inode::entry_stat;
+use crate::volume::data::*;
+use crate::{sector, ClusterId, FatFileSystem, SectorId};
+
+/// 目录项的流式迭代器
+///
+/// 迭代器持有(簇, 扇区, 项)三元组所表示的位置，可随时由[`DirIter::offset`]
+/// 导出为偏移量，再由[`Inode::dir_iter`]从该偏移量处继续，无需从头遍历目录。
+///
+/// 偏移量是目录项槽位在簇链表中的序号，而非已读出的目录项个数，
+/// 因此在目录中增删其它项时依然稳定。
+///
+/// [`Inode::dir_iter`]: crate::Inode::dir_iter
+#[derive(Debug)]
+pub struct DirIter<'a> {
+    /// FAT12/16的根目录不在簇链表上，为`None`
+    cluster: Option<ClusterId<u32>>,
+    sectors: Range<SectorId>,
+    sector: SectorId,
+    nth: usize,
+    /// 上一个扇区，用于拼接跨扇区的长目录项
+    prev_sector: Option<SectorId>,
+    offset: usize,
+    done: bool,
+    sb: &'a FatFileSystem,
+}
+
+impl<'a> DirIter<'a> {
+    /// 从`start_cluster`起始的目录中，定位到偏移量`offset`处。根目录的首簇为[`ClusterId::FREE`]
+    pub(crate) fn new(start_cluster: ClusterId<u32>, offset: usize, sb: &'a FatFileSystem) -> Self {
+        let per_sector = sector::size() / mem::size_of::<DirEntry>();
+        let (mut cluster, mut sectors) = sb.dir_head(start_cluster);
+        // 定长的根目录区视为一个大簇
+        let per_cluster = match cluster {
+            Some(_) => per_sector * sb.data().cluster_sectors(),
+            None => per_sector * sectors.clone().count(),
+        };
+
+        // 仅沿FAT表跳过整簇，不读取目录扇区
+        let mut prev_cluster = None;
+        let mut done = false;
+        for _ in 0..offset / per_cluster {
+            match cluster.and_then(|id| sb.fat().next(id).unwrap()) {
+                Some(next) => {
+                    prev_cluster = cluster;
+                    cluster = Some(next);
+                    sectors = sb.data().cluster(next).unwrap();
+                }
+                None => {
+                    done = true;
+                    break;
+                }
+            }
+        }
+
+        let sindex = offset % per_cluster / per_sector;
+        let sector = sectors.start + sindex;
+        let prev_sector = if sindex > 0 {
+            Some(sectors.start + (sindex - 1))
+        } else {
+            prev_cluster.and_then(|prev| sb.data().cluster(prev).unwrap().next_back())
+        };
+
+        Self {
+            cluster,
+            sector,
+            sectors,
+            nth: offset % per_sector,
+            prev_sector,
+            offset,
+            done,
+            sb,
+        }
+    }
+
+    /// 下一个待检视的目录项槽位的偏移量
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// 移动到下一个扇区，目录结束时返回`false`。
+    fn advance_sector(&mut self) -> bool {
+        self.prev_sector = Some(self.sector);
+        self.nth = 0;
+
+        if self.sector + 1 < self.sectors.end {
+            self.sector = self.sector + 1;
+            return true;
+        }
+
+        // 定长的根目录区到此为止
+        let Some(cluster) = self.cluster else {
+            return false;
+        };
+        match self.sb.fat().next(cluster) {
+            Ok(Some(next)) => {
+                self.cluster = Some(next);
+                self.sectors = self.sb.data().cluster(next).unwrap();
+                self.sector = self.sectors.start;
+                true
+            }
+            Ok(None) => false,
+            // 损坏的目录只列出链表断开前的部分
+            Err(e) => {
+                log::error!("Directory chain broken after {cluster}: {e}");
+                false
+            }
+        }
+    }
+}
+
+impl DirIter<'_> {
+    /// 同[`Iterator::next`]，并由同一短目录项得出其状态，无需再逐项查找
+    pub fn next_plus(&mut self) -> Option<(vfs::DirEntry, Stat)> {
+        let (entry, short) = self.next_short()?;
+        let stat = entry_stat(entry.ty, short.cluster_id(), &short, self.sb);
+        Some((entry, stat))
+    }
+
+    /// 下一个目录项及其短目录项
+    fn next_short(&mut self) -> Option<(vfs::DirEntry, ShortDirEntry)> {
+        while !self.done {
+            let dirents = sector::get(self.sector);
+            let dirents = dirents.lock();
+            let dirents: &[DirEntry] = dirents.as_slice();
+
+            while let Some(dirent) = dirents.get(self.nth) {
+                if unsafe { dirent.short.status() == DirEntryStatus::TailFree } {
+                    self.done = true;
+                    return None;
+                }
+
+                let i = self.nth;
+                self.nth += 1;
+                self.offset += 1;
+
+                if unsafe {
+                    dirent.short.status() == DirEntryStatus::Occupied
+                        && dirent.attr() != LongDirEntry::attr()
+                        && !dirent.short.is_relative()
+                } {
+                    let longs = collect_longs(dirents, i, self.prev_sector);
+                    let (short, attr) = unsafe { (dirent.short, dirent.attr()) };
+                    let entry = vfs::DirEntry {
+                        inode: short.cluster_id().into(),
+                        ty: if attr.contains(AttrFlag::Directory) {
+                            DirEntryType::Directory
+                        } else {
+                            DirEntryType::Regular
+                        },
+                        name: dirents2name(&longs),
+                    };
+                    return Some((entry, short));
+                }
+            }
+
+            if !self.advance_sector() {
+                self.done = true;
+            }
+        }
+
+        None
+    }
+}
+
+impl Iterator for DirIter<'_> {
+    type Item = vfs::DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_short().map(|(entry, _)| entry)
+    }
+}
+
+/// 逆序收集`dirents[i]`所属的长目录项，必要时延伸至上一个扇区。
+fn collect_longs(
+    dirents: &[DirEntry],
+    i: usize,
+    prev_sector: Option<SectorId>,
+) -> Vec<LongDirEntry> {
+    let checksum = unsafe { dirents[i].short.checksum() };
+    let mut longs = Vec::with_capacity(10);
+
+    for dirent in dirents[..i].iter().rev().take_while(|dirent| unsafe {
+        dirent.attr() == LongDirEntry::attr() && dirent.long.chksum == checksum
+    }) {
+        longs.push(unsafe { LongDirEntry::clone(&dirent.long) });
+        if unsafe { dirent.long.ord & LongDirEntry::LAST_MASK == LongDirEntry::LAST_MASK } {
+            return longs;
+        }
+    }
+
+    // 长目录项被扇区边界截断
+    let prev = prev_sector.expect("The last long entry was lost");
+    sector::get(prev).lock().map_slice(|dirents: &[DirEntry]| {
+        let end = dirents
+            .iter()
+            .rposition(|dirent| unsafe {
+                dirent.attr() == LongDirEntry::attr()
+                    && dirent.long.chksum == checksum
+                    && (dirent.long.ord & LongDirEntry::LAST_MASK == LongDirEntry::LAST_MASK)
+            })
+            .expect("The last long entry was lost");
+        longs.extend(
+            dirents[end..]
+                .iter()
+                .rev()
+                .map(|dirent| unsafe { LongDirEntry::clone(&dirent.long) }),
+        );
+    });
+
+    longs
+}
@@ -0,0 +1,111 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use spin::Mutex;
+
+use crate::{ClusterError, ClusterId, FatFileSystem, SectorId};
+
+/// 簇链表中一段编号连续的簇
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    /// 首簇在文件内的序号
+    index: usize,
+    start: ClusterId<u32>,
+    len: usize,
+}
+
+impl Extent {
+    fn end(&self) -> usize {
+        self.index + self.len
+    }
+}
+
+/// 簇链表的映射缓存
+///
+/// 首次访问时沿FAT表解析整条簇链表，合并为若干段连续的簇，
+/// 此后文件内的随机访问只需二分查找，而不必每次都从首簇出发。
+/// 簇链表发生变化(扩展或截断)后须调用[`ExtentCache::invalidate`]。
+#[derive(Default)]
+pub(crate) struct ExtentCache(Mutex<Option<Vec<Extent>>>);
+
+impl ExtentCache {
+    pub const fn new() -> Self {
+        Self(Mutex::new(None))
+    }
+
+    pub fn invalidate(&self) {
+        *self.0.lock() = None;
+    }
+
+    /// 求以`start_id`为首簇的文件中，第`nth`个扇区的编号。超出簇链表时返回`Ok(None)`，
+    /// 簇链表损坏时报错。
+    pub fn sector(
+        &self,
+        start_id: ClusterId<u32>,
+        nth: usize,
+        sb: &FatFileSystem,
+    ) -> Result<Option<SectorId>, ClusterError> {
+        if start_id == ClusterId::FREE {
+            return Ok(None);
+        }
+
+        let cluster_sectors = sb.data().cluster_sectors();
+        let cindex = nth / cluster_sectors;
+
+        let mut extents = self.0.lock();
+        // 越界时可能是其它打开者扩展了文件，重新解析一次
+        if extents
+            .as_ref()
+            .and_then(|extents| extents.last())
+            .is_none_or(|last| cindex >= last.end())
+        {
+            *extents = Some(resolve(start_id, sb)?);
+        }
+        let extents = extents.as_ref().unwrap();
+
+        let i = extents.partition_point(|extent| extent.end() <= cindex);
+        let Some(extent) = extents.get(i) else {
+            return Ok(None);
+        };
+        let cid = ClusterId::from(usize::from(extent.start) + (cindex - extent.index));
+
+        Ok(Some(sb.data().cluster(cid)?.start + nth % cluster_sectors))
+    }
+}
+
+impl Clone for ExtentCache {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.0.lock().clone()))
+    }
+}
+
+impl fmt::Debug for ExtentCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.0.lock() {
+            Some(extents) => write!(f, "ExtentCache({} extents)", extents.len()),
+            None => f.write_str("ExtentCache(invalid)"),
+        }
+    }
+}
+
+/// 沿FAT表解析整条簇链表
+fn resolve(start_id: ClusterId<u32>, sb: &FatFileSystem) -> Result<Vec<Extent>, ClusterError> {
+    let mut extents: Vec<Extent> = Vec::new();
+    let mut id = Some(start_id);
+    let mut index = 0;
+
+    while let Some(cid) = id {
+        match extents.last_mut() {
+            Some(last) if usize::from(last.start) + last.len == usize::from(cid) => last.len += 1,
+            _ => extents.push(Extent {
+                index,
+                start: cid,
+                len: 1,
+            }),
+        }
+        index += 1;
+        id = sb.fat().next(cid)?;
+    }
+
+    Ok(extents)
+}
@@ -1,15 +1,48 @@
 use alloc::vec::Vec;
 use core::mem;
+use core::ops::Range;
 
-use vfs::{DirEntryType, Stat};
+use vfs::{DirEntryType, Stat, NAME_MAX};
 
+use crate::dir_iter::DirIter;
+use crate::extent::ExtentCache;
 use crate::volume::data::*;
-use crate::{sector, ClusterId, FatFileSystem, SectorId};
+use crate::{sector, ClusterError, ClusterId, FatFileSystem, SectorId};
 
+/// 文件的最大字节数，目录项只以32位记录大小
+pub const MAX_FILE_SIZE: usize = u32::MAX as usize;
+
+/// 写入或扩展文件时的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteError {
+    Cluster(ClusterError),
+    /// 文件将超过[`MAX_FILE_SIZE`]
+    FileTooLarge,
+}
+
+impl From<ClusterError> for WriteError {
+    fn from(e: ClusterError) -> Self {
+        Self::Cluster(e)
+    }
+}
+
+impl core::fmt::Display for WriteError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Cluster(e) => e.fmt(f),
+            Self::FileTooLarge => f.write_str("file would exceed the maximum size"),
+        }
+    }
+}
+
+impl core::error::Error for WriteError {}
+
+/// 根目录，其首簇记为[`ClusterId::FREE`]，由[`FatFileSystem`]换成真正的位置
 pub static ROOT: Inode = Inode {
-    start_id: ClusterId::MIN,
+    start_id: ClusterId::FREE,
     range: DirEntryRange::ROOT,
     ty: DirEntryType::Directory,
+    extents: ExtentCache::new(),
 };
 
 /// 目录项会指向一个簇链表，这就是FAT文件系统中的inode。
@@ -21,6 +54,8 @@ pub struct Inode {
     start_id: ClusterId<u32>,
     range: DirEntryRange,
     ty: DirEntryType,
+    /// 仅文件使用
+    extents: ExtentCache,
 }
 
 impl Inode {
@@ -55,7 +90,14 @@ impl Inode {
     }
 
     /// 文件
-    pub fn read_at(&self, offset: usize, buf: &mut [u8], sb: &FatFileSystem) -> usize {
+    ///
+    /// 簇链表损坏时报错，此前读到的数据仍在`buf`中。
+    pub fn read_at(
+        &self,
+        offset: usize,
+        buf: &mut [u8],
+        sb: &FatFileSystem,
+    ) -> Result<usize, ClusterError> {
         debug_assert_eq!(self.ty, DirEntryType::Regular);
 
         let file_size = self.range.short.access(ShortDirEntry::size);
@@ -65,92 +107,113 @@ impl Inode {
         let end = (start + buf.len()).min(file_size); // exclusive
 
         if start >= end {
-            return 0;
+            return Ok(0);
         }
 
         let mut read_size = 0;
 
-        let n_skip = start / sector_size;
-        let n_take = end.div_ceil(sector_size);
-        for sid in sb.data_sectors(self.start_id).take(n_take).skip(n_skip) {
-            let block_read_size = (end - read_size).min(sector_size);
+        let nths = start / sector_size..end.div_ceil(sector_size);
+        for (nth, sid) in nths.clone().zip(self.sectors(nths, sb)) {
+            let sid = sid?;
+            let (from, to) = sector_span(nth, start..end);
+            let block_read_size = to - from;
             sector::get(sid).lock().map_slice(|data: &[u8]| {
-                buf[read_size..read_size + block_read_size]
-                    .copy_from_slice(&data[..block_read_size])
+                buf[read_size..read_size + block_read_size].copy_from_slice(&data[from..to])
             });
             read_size += block_read_size;
         }
 
-        read_size
+        Ok(read_size)
+    }
+
+    /// 文件
+    ///
+    /// 预读`[offset, offset + len)`所在的扇区，超出文件末尾的部分被忽略。
+    /// 这只是提示，簇链表损坏时读到损坏处为止。
+    pub fn prefetch(&self, offset: usize, len: usize, sb: &FatFileSystem) {
+        // 同一簇内的扇区是连续的，合并为一次预读
+        let mut run: Option<(SectorId, usize)> = None;
+        for sid in self.file_sectors(offset, len, sb) {
+            match &mut run {
+                Some((start, count)) if *start + *count == sid => *count += 1,
+                _ => {
+                    if let Some((start, count)) = run.replace((sid, 1)) {
+                        sector::prefetch(start, count);
+                    }
+                }
+            }
+        }
+        if let Some((start, count)) = run {
+            sector::prefetch(start, count);
+        }
+    }
+
+    /// 文件
+    ///
+    /// 写回并丢弃`[offset, offset + len)`所在扇区的缓存，仍被引用的扇区保留。
+    pub fn release(&self, offset: usize, len: usize, sb: &FatFileSystem) {
+        for sid in self.file_sectors(offset, len, sb) {
+            sector::release(sid);
+        }
     }
 
     /// 目录
     ///
     /// 在当前目录下创建文件。
-    pub fn create_file(&self, name: &str, sb: &mut FatFileSystem) -> Result<Self, vfs::Error> {
+    pub fn create_file(&self, name: &str, sb: &FatFileSystem) -> Result<Self, vfs::Error> {
         debug_assert_eq!(self.ty, DirEntryType::Directory);
 
         // NOTE: 出来的是默认值，不需要赋予[`ClusterId::FREE`]了
-        let (short, longs) = name2dirents(name);
+        let (mut short, longs) = name2dirents(name);
+        if let Some(now) = sb.now() {
+            short.set_created(now);
+        }
         let range = self.create(name, short, longs, sb)?;
-        sector::sync_all();
+        sb.written();
 
         Ok(Self {
             start_id: ClusterId::FREE,
             range,
             ty: DirEntryType::Regular,
+            extents: ExtentCache::new(),
         })
     }
 
     /// 文件
     ///
     /// 随机写入，对于空文件会分配有效的起始簇编号再写入。
-    pub fn write_at(&mut self, offset: usize, buf: &[u8], sb: &mut FatFileSystem) -> usize {
+    /// 簇链表损坏，或写入后会超过[`MAX_FILE_SIZE`]时报错，后者不写入任何字节。
+    pub fn write_at(
+        &mut self,
+        offset: usize,
+        buf: &[u8],
+        sb: &FatFileSystem,
+    ) -> Result<usize, WriteError> {
         debug_assert_eq!(self.ty, DirEntryType::Regular);
 
         let file_size = self.range.short.access(ShortDirEntry::size);
         let sector_size = sector::size();
 
         let start = offset;
-        let end = start + buf.len(); // exclusive
+        let end = start
+            .checked_add(buf.len())
+            .filter(|&end| end <= MAX_FILE_SIZE)
+            .ok_or(WriteError::FileTooLarge)?; // exclusive
 
-        // Expand
+        // Expand，`offset`越过末尾时中间的空洞读出为0
         if end > file_size {
-            let added_sectors = (end - file_size).div_ceil(sector_size);
-            debug_assert!(added_sectors > 0);
-
-            let mut added_clusters = added_sectors.div_ceil(sb.data().cluster_sectors());
-
-            let mut current = if self.start_id == ClusterId::FREE {
-                /* 空文件 */
-                added_clusters -= 1;
-                self.start_id = sb.alloc_cluster().0;
-                self.range
-                    .short
-                    .access_mut(|dirent| dirent.set_cluster_id(self.start_id));
-                self.start_id
-            } else {
-                sb.fat().last(self.start_id).unwrap()
-            };
-
-            for _ in 0..added_clusters {
-                let next = sb.alloc_cluster().0;
-                unsafe {
-                    sb.fat_mut().couple(current, next);
-                }
-                current = next;
-            }
+            self.grow(file_size, end, sb)?;
         }
 
         let mut wrote_size = 0;
 
-        let n_skip = start / sector_size;
-        let n_take = end.div_ceil(sector_size);
-        for sid in sb.data_sectors(self.start_id).take(n_take).skip(n_skip) {
-            let block_write_size = (end - wrote_size).min(sector_size);
+        let nths = start / sector_size..end.div_ceil(sector_size);
+        for (nth, sid) in nths.clone().zip(self.sectors(nths, sb)) {
+            let sid = sid?;
+            let (from, to) = sector_span(nth, start..end);
+            let block_write_size = to - from;
             sector::get(sid).lock().map_mut_slice(|data: &mut [u8]| {
-                data[..block_write_size]
-                    .copy_from_slice(&buf[wrote_size..wrote_size + block_write_size])
+                data[from..to].copy_from_slice(&buf[wrote_size..wrote_size + block_write_size])
             });
             wrote_size += block_write_size;
         }
@@ -158,152 +221,139 @@ impl Inode {
         if end > file_size {
             self.range.short.access_mut(|dirent| dirent.resize(end));
         }
-        sector::sync_all();
+        self.touch(sb);
+        sb.written();
 
-        wrote_size
+        Ok(wrote_size)
     }
 
     /// 文件
-    pub fn clear(&mut self, sb: &mut FatFileSystem) {
+    ///
+    /// 将文件截断或扩展至`size`字节。截断时释放多余的簇，扩展出的部分读出为0。
+    /// 簇链表损坏，或`size`超过[`MAX_FILE_SIZE`]时报错。
+    pub fn truncate(&mut self, size: usize, sb: &FatFileSystem) -> Result<(), WriteError> {
+        debug_assert_eq!(self.ty, DirEntryType::Regular);
+        if size > MAX_FILE_SIZE {
+            return Err(WriteError::FileTooLarge);
+        }
+
+        let file_size = self.range.short.access(ShortDirEntry::size);
+        let cluster_bytes = sb.data().cluster_sectors() * sector::size();
+
+        if size == 0 {
+            self.clear(sb);
+        } else if size < file_size {
+            let mut last = self.start_id;
+            for _ in 1..size.div_ceil(cluster_bytes) {
+                last = sb.fat().next(last)?.ok_or(ClusterError::Eof)?;
+            }
+            let rest = sb.fat().next(last)?;
+            if let Some(rest) = rest {
+                unsafe { sb.fat_mut().couple(last, ClusterId::EOF) };
+                dealloc_chain(rest, sb);
+                self.extents.invalidate();
+            }
+        } else if size > file_size {
+            self.grow(file_size, size, sb)?;
+        }
+
+        self.range.short.access_mut(|dirent| dirent.resize(size));
+        self.touch(sb);
+        sb.written();
+        Ok(())
+    }
+
+    /// 文件
+    pub fn clear(&mut self, sb: &FatFileSystem) {
         debug_assert_eq!(self.ty, DirEntryType::Regular);
 
         // 跳过空文件
         if self.start_id != ClusterId::FREE {
-            sb.fat_mut().dealloc(self.start_id).unwrap();
+            dealloc_chain(self.start_id, sb);
             self.start_id = ClusterId::FREE;
+            self.extents.invalidate();
             self.range.short.access_mut(|dirent| dirent.resize(0));
         }
+        self.touch(sb);
     }
 
     /// 目录
     ///
     /// 在当前目录下创建目录。
-    pub fn mkdir(&self, name: &str, sb: &mut FatFileSystem) -> Result<Self, vfs::Error> {
+    pub fn mkdir(&self, name: &str, sb: &FatFileSystem) -> Result<Self, vfs::Error> {
         debug_assert_eq!(self.ty, DirEntryType::Directory);
 
         let (mut short, longs) = name2dirents(name);
+        if let Some(now) = sb.now() {
+            short.set_created(now);
+        }
         let start_id = self.alloc_dir(&mut short, sb);
-        let range = self.create(name, short, longs, sb)?;
-        sector::sync_all();
+        let range = self
+            .create(name, short, longs, sb)
+            .inspect_err(|_| dealloc_chain(start_id, sb))?;
+        sb.written();
 
         Ok(Self {
             start_id,
             range,
             ty: DirEntryType::Directory,
+            extents: ExtentCache::new(),
         })
     }
 
     /// 目录
     ///
-    /// 读取at之后的目录项，最多为count个。
-    pub fn ls_at(&self, at: usize, count: usize, sb: &FatFileSystem) -> Vec<vfs::DirEntry> {
-        debug_assert_eq!(self.ty, DirEntryType::Directory);
-
-        let mut buf = Vec::with_capacity(count);
-        let mut skipped = 0;
-        let sectors = sb.data_sectors(self.start_id);
-        let mut read = 0;
-
-        let mut prev_sector = None;
-        for sid in sectors {
-            let dirents = sector::get(sid);
-            let dirents = dirents.lock();
-            let dirents: &[DirEntry] = dirents.as_slice();
-
-            for (i, dirent) in dirents
-                .iter()
-                .take_while(|dirent| unsafe { dirent.short.status() != DirEntryStatus::TailFree })
-                .enumerate()
-            {
-                if read == count {
-                    return buf;
-                }
-
-                if unsafe {
-                    dirent.short.status() == DirEntryStatus::Occupied
-                        && dirent.attr() != LongDirEntry::attr()
-                        && !dirent.short.is_relative()
-                } {
-                    if skipped < at {
-                        skipped += 1;
-                        continue;
-                    }
-
-                    let checksum = unsafe { dirent.short.checksum() };
-                    log::debug!(
-                        "parent={} pos=({sid}, {i}) checksum={checksum:#x}",
-                        self.start_id
-                    );
-                    let mut longs = Vec::with_capacity(10);
+    /// 从头遍历目录项。迭代器记着所在的扇区与项，中途可由[`DirIter::offset`]
+    /// 取出偏移量，之后交给[`Inode::dir_iter`]续读。
+    pub fn read_dir<'a>(&self, sb: &'a FatFileSystem) -> DirIter<'a> {
+        self.dir_iter(0, sb)
+    }
 
-                    let mut discrete = true;
+    /// 目录
+    ///
+    /// 从偏移量`offset`处开始遍历目录项，偏移量见[`DirIter::offset`]。
+    pub fn dir_iter<'a>(&self, offset: usize, sb: &'a FatFileSystem) -> DirIter<'a> {
+        debug_assert_eq!(self.ty, DirEntryType::Directory);
 
-                    for dirent in dirents[..i].iter().rev().take_while(|dirent| unsafe {
-                        dirent.attr() == LongDirEntry::attr() && dirent.long.chksum == checksum
-                    }) {
-                        longs.push(unsafe { LongDirEntry::clone(&dirent.long) });
-                        if unsafe {
-                            dirent.long.ord & LongDirEntry::LAST_MASK == LongDirEntry::LAST_MASK
-                        } {
-                            discrete = false;
-                            break;
-                        }
-                    }
+        DirIter::new(self.start_id, offset, sb)
+    }
 
-                    if discrete {
-                        let prev = prev_sector.unwrap();
-                        sector::get(prev).lock().map_slice(|dirents: &[DirEntry]| {
-                            let end = dirents
-                                .iter()
-                                .rposition(|dirent| unsafe {
-                                    dirent.attr() == LongDirEntry::attr()
-                                        && dirent.long.chksum == checksum
-                                        && (dirent.long.ord & LongDirEntry::LAST_MASK
-                                            == LongDirEntry::LAST_MASK)
-                                })
-                                .expect("The last long entry was lost");
-                            longs.extend(
-                                dirents[end..]
-                                    .iter()
-                                    .rev()
-                                    .map(|dirent| unsafe { LongDirEntry::clone(&dirent.long) }),
-                            );
-                        });
-                    }
+    /// 目录
+    ///
+    /// 读取at之后的目录项，最多为count个。
+    ///
+    /// 每次调用都会从头遍历目录，连续分页读取请使用[`Inode::read_dir`]。
+    pub fn ls_at(&self, at: usize, count: usize, sb: &FatFileSystem) -> Vec<vfs::DirEntry> {
+        self.read_dir(sb).skip(at).take(count).collect()
+    }
 
-                    let dname = dirents2name(&longs);
-                    buf.push(unsafe {
-                        vfs::DirEntry {
-                            inode: dirent.short.cluster_id().into(),
-                            ty: if dirent.attr().contains(AttrFlag::Directory) {
-                                DirEntryType::Directory
-                            } else {
-                                DirEntryType::Regular
-                            },
-                            name: dname,
-                        }
-                    });
-                    read += 1;
-                }
-            }
+    /// 文件的字节数，目录为0
+    pub fn size(&self) -> usize {
+        self.range.short.access(ShortDirEntry::size)
+    }
 
-            prev_sector = Some(sid);
+    /// FAT不记录状态改变的时间，`ctime`为创建时间。根目录没有目录项，时间均为0
+    pub fn stat(&self, sb: &FatFileSystem) -> Stat {
+        let dirent = self.range.short.access(|dirent: &ShortDirEntry| *dirent);
+        let mut stat = entry_stat(self.ty, self.start_id, &dirent, sb);
+        if self.is_root() {
+            [stat.atime, stat.mtime, stat.ctime] = [0; 3];
         }
-
-        buf
+        stat
     }
 
-    pub fn stat(&self, sb: &FatFileSystem) -> Stat {
-        Stat {
-            mode: self.ty,
-            block_size: sector::size() as u64,
-            blocks: sb.data_sectors(self.start_id).count() as u64,
-            size: self.range.short.access(ShortDirEntry::size) as u64,
+    /// 设置最后访问与最后修改时间，自UNIX纪元的秒数，为`None`的保持不变。
+    /// 根目录没有目录项，无从设置
+    pub fn set_times(&self, atime: Option<u64>, mtime: Option<u64>, sb: &FatFileSystem) {
+        if self.is_root() {
+            return;
         }
+        self.write_times(atime, mtime);
+        sb.written();
     }
 
     /// 目录
-    pub fn unlink(&mut self, name: &str, sb: &mut FatFileSystem) -> Result<(), vfs::Error> {
+    pub fn unlink(&mut self, name: &str, sb: &FatFileSystem) -> Result<(), vfs::Error> {
         debug_assert_eq!(self.ty, DirEntryType::Directory);
 
         let inode = self.find_cwd(name, sb).ok_or(vfs::Error::NotFound)?;
@@ -311,11 +361,11 @@ impl Inode {
             return Err(vfs::Error::IsADirectory);
         }
         if inode.start_id != ClusterId::FREE {
-            sb.fat_mut().dealloc(inode.start_id).unwrap();
+            dealloc_chain(inode.start_id, sb);
         }
         self.remove(inode.range, sb);
 
-        sector::sync_all();
+        sb.written();
 
         Ok(())
     }
@@ -323,7 +373,7 @@ impl Inode {
     /// 目录
     ///
     /// 删除空目录。
-    pub fn rmdir(&mut self, name: &str, sb: &mut FatFileSystem) -> Result<(), vfs::Error> {
+    pub fn rmdir(&mut self, name: &str, sb: &FatFileSystem) -> Result<(), vfs::Error> {
         debug_assert_eq!(self.ty, DirEntryType::Directory);
 
         let inode = self.find_cwd(name, sb).ok_or(vfs::Error::NotFound)?;
@@ -333,70 +383,236 @@ impl Inode {
             return Err(vfs::Error::DirectoryNotEmpty);
         }
 
-        sb.fat_mut().dealloc(inode.start_id).unwrap();
+        dealloc_chain(inode.start_id, sb);
         self.remove(inode.range, sb);
 
-        sector::sync_all();
+        sb.written();
 
         Ok(())
     }
 
     /// 目录
     ///
-    /// 当`new_parent`为`None`时，`old_name`和`new_name`必须不同。
+    /// 将`old_name`移至`new_parent`(为`None`时即当前目录)下的`new_name`，簇链表不变。
+    ///
+    /// 同POSIX，`new_name`已存在时原子地替换之：文件只能替换文件，目录只能替换空目录。
+    /// 目录不能移到其自身之下。
     pub fn rename(
         &mut self,
         old_name: &str,
-        mut new_parent: Option<&mut Self>,
+        new_parent: Option<&mut Self>,
         new_name: &str,
-        sb: &mut FatFileSystem,
+        sb: &FatFileSystem,
     ) -> Result<(), vfs::Error> {
         debug_assert_eq!(self.ty, DirEntryType::Directory);
 
+        // 须在移除原目录项之前检查
+        if new_name.len() > NAME_MAX {
+            return Err(vfs::Error::NameTooLong);
+        }
         let src = self.find_cwd(old_name, sb).ok_or(vfs::Error::NotFound)?;
         let (short, new_longs) = src
             .range
             .short
             .access(|short| rename_dirents(short, new_name));
 
-        {
-            let dest_parent = new_parent
-                .as_deref_mut()
-                .inspect(|p| debug_assert_eq!(p.ty, DirEntryType::Directory))
-                .unwrap_or_else(|| {
-                    debug_assert_ne!(old_name, new_name);
-                    self
-                });
+        let dest_parent: &Self = match new_parent {
+            Some(parent) => {
+                debug_assert_eq!(parent.ty, DirEntryType::Directory);
+                parent
+            }
+            None => self,
+        };
+        let moved_dir = src.ty == DirEntryType::Directory && dest_parent.start_id != self.start_id;
+        if moved_dir && dest_parent.is_within(src.start_id, sb) {
+            return Err(vfs::Error::InvalidInput);
+        }
 
-            if let Some(dest) = dest_parent.find_cwd(new_name, sb) {
-                // new_name的文件已存在
+        match dest_parent.find_cwd(new_name, sb) {
+            // 同一个目录项，什么也不用做
+            Some(dest) if dest.range.short == src.range.short => return Ok(()),
+            Some(dest) => {
                 match (src.ty, dest.ty) {
                     (DirEntryType::Directory, DirEntryType::Directory) => {
-                        // 对于非空的目录，删除失败就退出
-                        dest_parent.rmdir(new_name, sb)?;
+                        if !dest.is_empty_dir(sb) {
+                            return Err(vfs::Error::DirectoryNotEmpty);
+                        }
                     }
                     (_, DirEntryType::Directory) => return Err(vfs::Error::IsADirectory),
                     (DirEntryType::Directory, _) => return Err(vfs::Error::NotADirectory),
-                    _ => {
-                        // 普通文件的覆盖
-                        dest_parent.unlink(new_name, sb)?;
-                    }
+                    _ => {}
+                }
+                // 短名只由名称决定，与原有的长目录项相符，覆写短目录项即完成替换
+                dest.range.short.access_mut(|dirent| *dirent = short);
+                if dest.start_id != ClusterId::FREE {
+                    dealloc_chain(dest.start_id, sb);
                 }
             }
+            // 先建新目录项，失败时原目录项还在
+            None => {
+                dest_parent.create(new_name, short, new_longs, sb)?;
+            }
         }
-
         self.remove(src.range, sb);
-        new_parent
-            .unwrap_or(self)
-            .create(new_name, short, new_longs, sb)?;
 
-        sector::sync_all();
+        if moved_dir {
+            // 更新被移动目录的`..`，指向根目录时为[`ClusterId::FREE`]
+            let first = sb.data().cluster(src.start_id).unwrap().start;
+            sector::get(first).lock().map_mut(
+                mem::size_of::<ShortDirEntry>(),
+                |parent: &mut ShortDirEntry| parent.set_cluster_id(dest_parent.start_id),
+            );
+        }
+
+        sb.written();
 
         Ok(())
     }
 }
 
 impl Inode {
+    /// 根目录，其目录项的位置只是占位
+    fn is_root(&self) -> bool {
+        self.ty == DirEntryType::Directory && self.start_id == ClusterId::FREE
+    }
+
+    /// 目录
+    ///
+    /// 自身是否为首簇为`ancestor`的目录或在其之下，沿`..`目录项向上查找。
+    fn is_within(&self, ancestor: ClusterId<u32>, sb: &FatFileSystem) -> bool {
+        let mut id = self.start_id;
+        while id != ancestor {
+            if id == ClusterId::FREE {
+                return false;
+            }
+            let first = sb.data().cluster(id).unwrap().start;
+            id = sector::get(first)
+                .lock()
+                .map(mem::size_of::<ShortDirEntry>(), ShortDirEntry::cluster_id);
+        }
+        true
+    }
+
+    /// 内容改变后更新最后修改与访问时间，由调用者通知写回
+    fn touch(&self, sb: &FatFileSystem) {
+        if let Some(now) = sb.now().filter(|_| !self.is_root()) {
+            self.write_times(Some(now), Some(now));
+        }
+    }
+
+    fn write_times(&self, atime: Option<u64>, mtime: Option<u64>) {
+        self.range.short.access_mut(|dirent| {
+            if let Some(atime) = atime {
+                dirent.set_accessed(atime);
+            }
+            if let Some(mtime) = mtime {
+                dirent.set_modified(mtime);
+            }
+        });
+    }
+
+    /// 文件
+    ///
+    /// 文件内序号位于`range`的扇区，经由簇链表的映射缓存求得。
+    fn sectors<'a>(
+        &'a self,
+        range: Range<usize>,
+        sb: &'a FatFileSystem,
+    ) -> impl Iterator<Item = Result<SectorId, ClusterError>> + 'a {
+        range.map_while(|nth| self.extents.sector(self.start_id, nth, sb).transpose())
+    }
+
+    /// 文件
+    ///
+    /// 令簇链表至少容纳`size`字节，不足时分配已清零的簇接在末尾。
+    fn reserve(&mut self, size: usize, sb: &FatFileSystem) -> Result<(), ClusterError> {
+        let needed = size.div_ceil(sb.data().cluster_sectors() * sector::size());
+        if needed == 0 {
+            return Ok(());
+        }
+
+        let (last, count) = if self.start_id == ClusterId::FREE {
+            /* 空文件 */
+            (None, 0)
+        } else {
+            let mut last = self.start_id;
+            let mut count = 1;
+            while let Some(next) = sb.fat().next(last)? {
+                last = next;
+                count += 1;
+            }
+            (Some(last), count)
+        };
+        if count >= needed {
+            return Ok(());
+        }
+
+        // 一次分配所缺的全部簇，尽量编号连续
+        let head = sb.alloc_cluster_run(needed - count);
+        match last {
+            Some(last) => unsafe { sb.fat_mut().couple(last, head) },
+            None => {
+                self.start_id = head;
+                self.range
+                    .short
+                    .access_mut(|dirent| dirent.set_cluster_id(head));
+            }
+        }
+
+        self.extents.invalidate();
+        Ok(())
+    }
+
+    /// 文件
+    ///
+    /// 为自`file_size`扩展至`size`字节接上所需的簇，扩展出的部分读出为0，但不修改目录项中的大小。
+    fn grow(
+        &mut self,
+        file_size: usize,
+        size: usize,
+        sb: &FatFileSystem,
+    ) -> Result<(), ClusterError> {
+        self.reserve(size, sb)?;
+        // 新分配的簇已清零，原末尾所在的簇中可能残留截断前的数据
+        let cluster_bytes = sb.data().cluster_sectors() * sector::size();
+        let tail_end = size.min(file_size.next_multiple_of(cluster_bytes));
+        self.zero(file_size..tail_end, sb)
+    }
+
+    /// 文件
+    ///
+    /// 将文件中`range`内的字节清零，超出簇链表的部分被忽略。
+    fn zero(&self, range: Range<usize>, sb: &FatFileSystem) -> Result<(), ClusterError> {
+        let sector_size = sector::size();
+        let nths = range.start / sector_size..range.end.div_ceil(sector_size);
+
+        for (nth, sid) in nths.clone().zip(self.sectors(nths, sb)) {
+            let (from, to) = sector_span(nth, range.clone());
+            sector::get(sid?)
+                .lock()
+                .map_mut_slice(|data: &mut [u8]| data[from..to].fill(0));
+        }
+
+        Ok(())
+    }
+
+    /// 文件
+    ///
+    /// `[offset, offset + len)`中位于文件内的扇区，簇链表损坏时提前结束。
+    fn file_sectors<'a>(
+        &'a self,
+        offset: usize,
+        len: usize,
+        sb: &'a FatFileSystem,
+    ) -> impl Iterator<Item = SectorId> + 'a {
+        let file_size = self.range.short.access(ShortDirEntry::size);
+        let end = offset.saturating_add(len).min(file_size);
+        let sector_size = sector::size();
+
+        self.sectors(offset / sector_size..end.div_ceil(sector_size), sb)
+            .map_while(Result::ok)
+    }
+
     /// 目录
     ///
     /// 搜索当前目录下指定名称的项。
@@ -405,7 +621,7 @@ impl Inode {
         log::debug!("Checksum of {name}: {checksum:#x}");
 
         let mut prev_sector = None;
-        for sid in sb.data_sectors(self.start_id) {
+        for sid in sb.dir_sectors(self.start_id) {
             let dirents = sector::get(sid);
             let dirents = dirents.lock();
             let dirents: &[DirEntry] = dirents.as_slice();
@@ -494,8 +710,11 @@ impl Inode {
         name: &str,
         short: ShortDirEntry,
         longs: Vec<LongDirEntry>,
-        sb: &mut FatFileSystem,
+        sb: &FatFileSystem,
     ) -> Result<DirEntryRange, vfs::Error> {
+        if name.len() > NAME_MAX {
+            return Err(vfs::Error::NameTooLong);
+        }
         if self.find_cwd(name, sb).is_some() {
             return Err(vfs::Error::AlreadyExists);
         }
@@ -504,7 +723,7 @@ impl Inode {
 
         let n_long = longs.len();
 
-        let mut sectors = sb.data_sectors(self.start_id);
+        let mut sectors = sb.dir_sectors(self.start_id);
         let mut prev_sector = None;
 
         /* 尝试收集足够的连续中间槽 */
@@ -591,8 +810,7 @@ impl Inode {
                 let start_sector = if let Some(sc) = sectors.next() {
                     sc
                 } else {
-                    drop(sectors);
-                    let last_cid = sb.fat().last(self.start_id).unwrap();
+                    let last_cid = self.last_cluster(sb)?;
                     let (ncid, new_sectors) = sb.alloc_cluster();
                     unsafe {
                         sb.fat_mut().couple(last_cid, ncid);
@@ -621,8 +839,7 @@ impl Inode {
         }
 
         /* 尝试分配新块 */
-        drop(sectors);
-        let last = sb.fat().last(self.start_id).unwrap();
+        let last = self.last_cluster(sb)?;
         let (ncid, sectors) = sb.alloc_cluster();
         unsafe {
             sb.fat_mut().couple(last, ncid);
@@ -641,7 +858,16 @@ impl Inode {
         Ok(DirEntryRange::new(end, start))
     }
 
-    fn alloc_dir(&self, dir: &mut ShortDirEntry, sb: &mut FatFileSystem) -> ClusterId<u32> {
+    /// 目录
+    ///
+    /// 簇链表的最后一个簇，目录须扩展时使用。FAT12/16的根目录区是定长的，无法扩展
+    fn last_cluster(&self, sb: &FatFileSystem) -> Result<ClusterId<u32>, vfs::Error> {
+        let (head, _) = sb.dir_head(self.start_id);
+        let head = head.ok_or(vfs::Error::NoSpace)?;
+        Ok(sb.fat().last(head).unwrap())
+    }
+
+    fn alloc_dir(&self, dir: &mut ShortDirEntry, sb: &FatFileSystem) -> ClusterId<u32> {
         let (ncid, sectors) = sb.alloc_cluster();
         dir.set_cluster_id(ncid);
         dir.attr |= AttrFlag::Directory;
@@ -654,10 +880,10 @@ impl Inode {
         ncid
     }
 
-    fn remove(&self, range: DirEntryRange, sb: &mut FatFileSystem) {
+    fn remove(&self, range: DirEntryRange, sb: &FatFileSystem) {
         let sector_dirents = sector_dirents();
 
-        let mut cursor = sb.data_sector_cursor(self.start_id);
+        let mut cursor = sb.dir_sector_cursor(self.start_id);
 
         cursor
             .find(range.short.sector)
@@ -693,13 +919,8 @@ impl Inode {
                     head_pos = Some(pos);
                     pos.access(|dirent| dirent.status())
                 })
-                .unwrap_or_else(|| {
-                    if tail_status == DirEntryStatus::TailFree {
-                        DirEntryStatus::TailFree
-                    } else {
-                        DirEntryStatus::Free
-                    }
-                })
+                // 根目录的首项之前没有相对目录项，视同被占用
+                .unwrap_or(DirEntryStatus::Occupied)
         } else {
             // 判断依据在当前扇区
             let mut pos = range.last_long;
@@ -757,7 +978,7 @@ impl Inode {
                     end = sector_dirents;
                     if cursor.prev().is_none() {
                         free_as = &TAIL_FREE;
-                        break if self.start_id == ClusterId::MIN {
+                        break if self.start_id == ClusterId::FREE {
                             0
                         } else {
                             2
@@ -786,8 +1007,8 @@ impl Inode {
     }
 
     fn is_empty_dir(&self, sb: &FatFileSystem) -> bool {
-        let mut sectors = sb.data_sectors(self.start_id);
-        let i = if self.start_id == ClusterId::MIN {
+        let mut sectors = sb.dir_sectors(self.start_id);
+        let i = if self.start_id == ClusterId::FREE {
             0
         } else {
             2
@@ -904,7 +1125,7 @@ impl DirEntryRange {
     } */
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct DirEntryPos {
     sector: SectorId,
     nth: usize,
@@ -953,6 +1174,47 @@ impl From<(DirEntryRange, &ShortDirEntry)> for Inode {
             } else {
                 DirEntryType::Regular
             },
+            extents: ExtentCache::new(),
         }
     }
 }
+
+/// 释放簇链表。链表损坏时，损坏处之后的簇无从找回，只能泄漏。
+/// 文件中第`nth`个扇区与字节区间`range`的交集，以扇区内的偏移表示
+fn sector_span(nth: usize, range: Range<usize>) -> (usize, usize) {
+    let sector_size = sector::size();
+    let base = nth * sector_size;
+    (
+        range.start.max(base) - base,
+        range.end.min(base + sector_size) - base,
+    )
+}
+
+fn dealloc_chain(start_id: ClusterId<u32>, sb: &FatFileSystem) {
+    if let Err(e) = sb.fat_mut().dealloc(start_id) {
+        log::warn!("Cluster chain from {start_id} is broken ({e}), the rest is leaked");
+    }
+}
+
+/// 由短目录项`dirent`得出其所指文件或目录的状态，首簇为`start_id`
+pub(crate) fn entry_stat(
+    ty: DirEntryType,
+    start_id: ClusterId<u32>,
+    dirent: &ShortDirEntry,
+    sb: &FatFileSystem,
+) -> Stat {
+    let blocks = if ty == DirEntryType::Directory {
+        sb.dir_sectors(start_id).count()
+    } else {
+        sb.data_sectors(start_id).count()
+    };
+    Stat {
+        mode: ty,
+        block_size: sector::size() as u64,
+        blocks: blocks as u64,
+        size: dirent.size() as u64,
+        atime: dirent.accessed(),
+        mtime: dirent.modified(),
+        ctime: dirent.created(),
+    }
+}
@@ -1,17 +1,25 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 #![feature(step_trait)]
 
 extern crate alloc;
 
 mod cluster;
 mod control;
+mod dir_iter;
+mod extent;
 mod inode;
 mod sector;
+#[cfg(test)]
+mod tests;
+mod vfs_impl;
 mod volume;
 
 pub use self::{
     cluster::{ClusterError, ClusterId},
-    control::FatFileSystem,
-    inode::{Inode, ROOT},
-    sector::SectorId,
+    control::{FatFileSystem, Writeback},
+    dir_iter::DirIter,
+    inode::{Inode, WriteError, MAX_FILE_SIZE, ROOT},
+    sector::{set_block_cache, SectorId},
+    vfs_impl::FatVfs,
+    volume::reserved::FatType,
 };
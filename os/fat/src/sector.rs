@@ -1,14 +1,11 @@
 //! 扇区的抽象
 
-use alloc::boxed::Box;
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 use core::iter::Step;
-use core::mem;
-use core::slice;
 
-use block_dev::BlockDevice;
+use block_dev::{BlockCache, BlockDevice, CachedBlock, FifoBlockCache};
 use derive_more::{Add, From, Into};
 use spin::Mutex;
 use spin::Once;
@@ -17,13 +14,25 @@ use crate::volume::reserved::Bpb;
 
 const BLOCK_SIZE: usize = 512;
 
+/// 未注入块缓存时，默认缓存的扇区个数上限
+const DEFAULT_CACHE_CAPACITY: usize = 16;
+
+static BLOCK_CACHE: Once<Arc<dyn BlockCache>> = Once::new();
 static CACHE_MANAGER: Once<CacheManager> = Once::new();
 
+/// 注入块缓存，须在加载或格式化文件系统之前调用，否则使用默认的缓存
+pub fn set_block_cache(cache: Arc<dyn BlockCache>) {
+    BLOCK_CACHE.call_once(|| cache);
+}
+
 pub fn init_cache(bpb: &Bpb, dev: &Arc<dyn BlockDevice>) {
+    let cache = BLOCK_CACHE
+        .call_once(|| Arc::new(FifoBlockCache::new(DEFAULT_CACHE_CAPACITY)))
+        .clone();
     CACHE_MANAGER.call_once(|| CacheManager {
         sector_bytes: bpb.sector_bytes(),
         dev: dev.clone(),
-        queue: Mutex::default(),
+        cache,
     });
 }
 
@@ -32,7 +41,7 @@ struct CacheManager {
     sector_bytes: usize,
     /// 底层块设备的引用
     dev: Arc<dyn BlockDevice>,
-    queue: Mutex<Vec<(SectorId, Arc<Mutex<Sector>>)>>,
+    cache: Arc<dyn BlockCache>,
 }
 
 #[inline]
@@ -42,7 +51,8 @@ fn manager() -> &'static CacheManager {
 
 #[inline]
 pub fn get(id: SectorId) -> Arc<Mutex<Sector>> {
-    manager().get(id)
+    let mgr = manager();
+    mgr.cache.get(&mgr.dev, id.block(), mgr.sector_bytes)
 }
 
 #[inline]
@@ -52,24 +62,41 @@ pub fn size() -> usize {
 
 #[inline]
 pub fn sync_all() {
-    manager()
-        .queue
-        .lock()
-        .iter()
-        .for_each(|(_, sector)| sector.lock().sync())
+    manager().cache.sync_all()
 }
 
-/// 内存中的扇区
-#[derive(Debug)]
-pub struct Sector {
-    /// 缓存的数据
-    data: Box<[u8]>,
-    /// 对应的块ID
-    id: SectorId,
-    /// 是否为脏块
-    modified: bool,
+/// 预读从`id`起的`count`个连续扇区。
+///
+/// 设备支持异步请求时不等读完就返回，此后访问尚未读完的扇区会睡眠至读完
+#[inline]
+pub fn prefetch(id: SectorId, count: usize) {
+    let mgr = manager();
+    mgr.cache
+        .prefetch(&mgr.dev, id.block(), count, mgr.sector_bytes)
+}
+
+/// 写回并丢弃扇区的缓存，扇区仍被引用时保留
+#[inline]
+pub fn release(id: SectorId) {
+    let mgr = manager();
+    mgr.cache.release(&mgr.dev, id.block())
+}
+
+/// 绕过缓存向扇区写入测试图样再读回，不一致即为坏扇区。扇区原有的数据会被覆盖。
+pub fn probe(id: SectorId) -> bool {
+    let mgr = manager();
+    let pattern: Vec<u8> = (0..mgr.sector_bytes).map(|i| i as u8 ^ 0xA5).collect();
+    let mut readback = vec![0; mgr.sector_bytes];
+
+    mgr.dev.write_block(id.block(), &pattern);
+    mgr.dev.read_block(id.block(), &mut readback);
+
+    readback == pattern
 }
 
+/// 内存中的扇区
+pub type Sector = CachedBlock;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Add, From, Into)]
 #[repr(transparent)]
 pub struct SectorId(usize);
@@ -112,119 +139,3 @@ impl SectorId {
         self.0 * (size() / BLOCK_SIZE)
     }
 }
-
-impl Sector {
-    pub fn new(id: SectorId) -> Self {
-        let mgr = manager();
-        let mut data = vec![0; size()];
-        mgr.dev.read_block(id.block(), &mut data);
-
-        Self {
-            data: data.into(),
-            id,
-            modified: false,
-        }
-    }
-
-    pub fn sync(&mut self) {
-        if self.modified {
-            self.modified = false;
-            manager().dev.write_block(self.id.block(), &self.data);
-        }
-    }
-
-    pub fn get<T>(&self, offset: usize) -> &T {
-        let type_size = mem::size_of::<T>();
-        assert!(type_size + offset <= self.data.len());
-        let addr = &self.data[offset];
-        unsafe { mem::transmute(addr) }
-    }
-
-    pub fn get_mut<T>(&mut self, offset: usize) -> &mut T {
-        let type_size = mem::size_of::<T>();
-        assert!(type_size + offset <= self.data.len());
-        self.modified = true;
-        let addr = &mut self.data[offset];
-        unsafe { mem::transmute(addr) }
-    }
-
-    pub fn as_slice<T>(&self) -> &[T] {
-        let type_size = mem::size_of::<T>();
-        let len = self.data.len() / type_size;
-        assert_eq!(0, self.data.len() % type_size);
-        unsafe { slice::from_raw_parts(self.data.as_ptr().cast(), len) }
-    }
-
-    pub fn as_mut_slice<T>(&mut self) -> &mut [T] {
-        let type_size = mem::size_of::<T>();
-        let len = self.data.len() / type_size;
-        assert_eq!(0, self.data.len() % type_size);
-        self.modified = true;
-        unsafe { slice::from_raw_parts_mut(self.data.as_mut_ptr().cast(), len) }
-    }
-
-    #[inline]
-    pub fn map<T, V>(&self, offset: usize, f: impl FnOnce(&T) -> V) -> V {
-        f(self.get(offset))
-    }
-
-    #[inline]
-    pub fn map_mut<T, V>(&mut self, offset: usize, f: impl FnOnce(&mut T) -> V) -> V {
-        f(self.get_mut(offset))
-    }
-
-    #[inline]
-    pub fn map_slice<T, V>(&self, f: impl FnOnce(&[T]) -> V) -> V {
-        f(self.as_slice())
-    }
-
-    #[inline]
-    pub fn map_mut_slice<T, V>(&mut self, f: impl FnOnce(&mut [T]) -> V) -> V {
-        f(self.as_mut_slice())
-    }
-
-    #[inline]
-    pub fn zeroize(&mut self) {
-        self.data.fill(0);
-        self.modified = true;
-    }
-}
-
-impl Drop for Sector {
-    fn drop(&mut self) {
-        self.sync();
-    }
-}
-
-impl CacheManager {
-    /// 块缓存个数的上限
-    const CAPACITY: usize = 16;
-
-    // 块缓存调度策略：踢走闲置块
-    fn get(&self, id: SectorId) -> Arc<Mutex<Sector>> {
-        let mut queue = self.queue.lock();
-
-        // 尝试从缓冲区中读取块
-        if let Some(cache) = queue
-            .iter()
-            .find_map(|(sid, cache)| (id == *sid).then_some(cache))
-        {
-            return Arc::clone(cache);
-        };
-
-        // 触及上限，写回一个块
-        if queue.len() == Self::CAPACITY {
-            let index = queue
-                .iter()
-                .position(|(_, cache)| Arc::strong_count(cache) == 1) // 没有其它引用的才能写回
-                .expect("run out of block cache");
-            queue.remove(index);
-        }
-
-        // 缓存新块
-        let block_cache = Arc::new(Mutex::new(Sector::new(id)));
-        queue.push((id, block_cache.clone()));
-
-        block_cache
-    }
-}
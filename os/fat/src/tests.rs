@@ -0,0 +1,1005 @@
+//! 磁盘数据结构的往返测试：按磁盘上的字节写出，检查关键字段的位置，再读回比较。
+//! 簇链表的测试则在内存中的磁盘上格式化一个卷，直接操作FAT。
+
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::iter;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::{mem, ptr, slice};
+use std::sync::{LazyLock, Mutex, MutexGuard, PoisonError};
+
+use block_dev::mem::MemDisk;
+use block_dev::BlockDevice;
+use vfs::{DirEntryType, FileSystem, VfsInode};
+
+use crate::volume::data::{dirents2name, name2dirents, AttrFlag, LongDirEntry, ShortDirEntry};
+use crate::volume::fat::Fat;
+use crate::volume::reserved::{Bpb, FatType, FsInfo};
+use crate::{
+    sector, ClusterError, ClusterId, FatFileSystem, FatVfs, Inode, WriteError, Writeback,
+    MAX_FILE_SIZE, ROOT,
+};
+
+const DISK_SIZE: usize = 64 * 1024 * 1024;
+
+/// 按磁盘上的字节看待`value`
+fn to_bytes<T>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(ptr::from_ref(value).cast(), mem::size_of::<T>()) }
+}
+
+/// 从磁盘上的字节读回，如同从扇区缓存中映射
+fn from_bytes<T>(bytes: &[u8]) -> T {
+    assert_eq!(bytes.len(), mem::size_of::<T>());
+    unsafe { ptr::read_unaligned(bytes.as_ptr().cast()) }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn sizes() {
+    assert_eq!(512, mem::size_of::<Bpb>());
+    assert_eq!(512, mem::size_of::<FsInfo>());
+    assert_eq!(32, mem::size_of::<ShortDirEntry>());
+    assert_eq!(32, mem::size_of::<LongDirEntry>());
+}
+
+#[test]
+fn bpb_round_trip() {
+    let bpb = Bpb::new(DISK_SIZE);
+    let bytes = to_bytes(&bpb);
+    // 格式化出的卷按簇数判定也须是FAT32
+    assert_eq!(bpb.fat_type(), FatType::T32);
+
+    assert_eq!(u16_at(bytes, 11), 512);
+    assert_eq!(bytes[13] as usize, bpb.cluster_sectors());
+    assert_eq!(u16_at(bytes, 14), 8);
+    assert_eq!(bytes[16], 2);
+    assert_eq!(u32_at(bytes, 32) as usize, DISK_SIZE / 512);
+    assert_eq!(u32_at(bytes, 36) as usize, bpb.fat_sectors());
+    assert_eq!(u32_at(bytes, 44), 2);
+    assert_eq!(u16_at(bytes, 48), 1);
+    assert_eq!(u16_at(bytes, 50), 6);
+    assert_eq!(&bytes[82..90], b"FAT32   ");
+    assert_eq!(&bytes[510..], [0x55, 0xAA]);
+
+    let decoded: Bpb = from_bytes(bytes);
+    assert_eq!(decoded.sector_bytes(), bpb.sector_bytes());
+    assert_eq!(decoded.total_clusters(), bpb.total_clusters());
+    assert_eq!(decoded.data_area(), bpb.data_area());
+    assert_eq!(to_bytes(&decoded), bytes);
+}
+
+#[test]
+fn fs_info_round_trip() {
+    let bpb = Bpb::new(DISK_SIZE);
+    let fs_info = FsInfo::new(&bpb);
+    let bytes = to_bytes(&fs_info);
+
+    assert_eq!(u32_at(bytes, 0), 0x41615252);
+    assert_eq!(u32_at(bytes, 484), 0x61417272);
+    assert_eq!(u32_at(bytes, 488) as usize, bpb.total_clusters());
+    assert_eq!(u32_at(bytes, 492), 0xFFFFFFFF);
+    assert_eq!(u32_at(bytes, 508), 0xAA550000);
+
+    let decoded: FsInfo = from_bytes(bytes);
+    assert_eq!(decoded.free_count(), bpb.total_clusters());
+    assert_eq!(to_bytes(&decoded), bytes);
+}
+
+#[test]
+fn short_dir_entry_round_trip() {
+    let id = ClusterId::new(0x0012_3456);
+    let mut dirent = ShortDirEntry::new_directory("HELLO", id);
+    dirent.resize(1234);
+    let bytes = to_bytes(&dirent);
+
+    assert_eq!(&bytes[..5], b"HELLO");
+    assert_eq!(bytes[11], AttrFlag::Archive as u8);
+    assert_eq!(u16_at(bytes, 20), 0x0012);
+    assert_eq!(u16_at(bytes, 26), 0x3456);
+    assert_eq!(u32_at(bytes, 28), 1234);
+
+    let decoded: ShortDirEntry = from_bytes(bytes);
+    assert_eq!(decoded.cluster_id(), id);
+    assert_eq!(decoded.size(), 1234);
+    assert_eq!(decoded.checksum(), dirent.checksum());
+}
+
+#[test]
+fn long_dir_entry_round_trip() {
+    let name = "a rather long file name.txt";
+    let (short, longs) = name2dirents(name);
+    let bytes: Vec<u8> = longs
+        .iter()
+        .flat_map(|long| to_bytes(long).to_vec())
+        .collect();
+
+    // 反序排列，首项为最后一个长目录项
+    assert_eq!(bytes[0], 2 | LongDirEntry::LAST_MASK);
+    assert_eq!(bytes[11], LongDirEntry::attr().bits());
+    assert_eq!(bytes[13], short.checksum());
+    assert_eq!(bytes[32], 1);
+    assert_eq!(&bytes[33..43], b"a rather l");
+
+    let mut decoded: Vec<LongDirEntry> = bytes.chunks(32).map(from_bytes).collect();
+    decoded.reverse();
+    assert_eq!(dirents2name(&decoded), name);
+}
+
+static DISK: LazyLock<Arc<MemDisk>> = LazyLock::new(|| Arc::new(MemDisk::new(DISK_SIZE / 512)));
+
+/// 扇区缓存是全局的，所有测试共用一个卷，逐个独占
+static VOLUME: LazyLock<Mutex<FatFileSystem>> = LazyLock::new(|| {
+    let dev: Arc<dyn BlockDevice> = DISK.clone();
+    Mutex::new(FatFileSystem::foramt(&dev))
+});
+
+fn volume() -> MutexGuard<'static, FatFileSystem> {
+    // 某个测试失败不应牵连其它测试
+    VOLUME.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 分配`len`个簇并依次链接
+fn alloc_chain(fat: &mut Fat, len: usize) -> Vec<ClusterId> {
+    let ids: Vec<_> = (0..len).map(|_| fat.alloc().unwrap()).collect();
+    for pair in ids.windows(2) {
+        unsafe { fat.couple(pair[0], pair[1]) };
+    }
+    ids
+}
+
+/// 沿链表收集自`head`起的所有簇
+fn collect_chain(fat: &Fat, head: ClusterId) -> Vec<ClusterId> {
+    iter::successors(Some(head), |&id| fat.next(id).unwrap()).collect()
+}
+
+#[test]
+fn cluster_id_validation() {
+    assert_eq!(ClusterId::FREE.validate(), Err(ClusterError::Free));
+    assert_eq!(ClusterId::BAD.validate(), Err(ClusterError::Defective));
+    assert_eq!(ClusterId::EOF.validate(), Err(ClusterError::Eof));
+    assert_eq!(ClusterId::new(1).validate(), Err(ClusterError::Reserved));
+    assert_eq!(
+        ClusterId::new(0x0FFF_FFF8).validate(),
+        Err(ClusterError::Reserved)
+    );
+    assert_eq!(ClusterId::MIN.validate(), Ok(ClusterId::MIN));
+
+    // 高4位保留，不属于簇号
+    assert_eq!(ClusterId::from(0xF000_0002_u32), ClusterId::MIN);
+    let id = ClusterId::new(0x0123_4567);
+    assert_eq!(ClusterId::from(id.split()), id);
+}
+
+#[test]
+fn chain_round_trip() {
+    let fs = volume();
+    let mut fat = fs.fat_mut();
+    let ids = alloc_chain(&mut fat, 5);
+
+    assert_eq!(collect_chain(&fat, ids[0]), ids);
+    assert_eq!(fat.last(ids[0]), Ok(ids[4]));
+    assert_eq!(fat.last(ids[2]), Ok(ids[4]));
+    assert_eq!(fat.next(ids[4]), Ok(None));
+
+    fat.dealloc(ids[0]).unwrap();
+    for &id in &ids {
+        assert_eq!(fat.next(id), Err(ClusterError::Free));
+    }
+    assert_eq!(fat.dealloc(ids[0]), Err(ClusterError::Free));
+}
+
+#[test]
+fn chain_truncation() {
+    let fs = volume();
+    let mut fat = fs.fat_mut();
+    let ids = alloc_chain(&mut fat, 5);
+
+    // 保留前两个簇，释放其后的部分
+    let rest = fat.next(ids[1]).unwrap().unwrap();
+    unsafe { fat.couple(ids[1], ClusterId::EOF) };
+    fat.dealloc(rest).unwrap();
+
+    assert_eq!(collect_chain(&fat, ids[0]), ids[..2]);
+    for &id in &ids[2..] {
+        assert_eq!(fat.next(id), Err(ClusterError::Free));
+    }
+
+    // 截断后仍能接长
+    let tail = alloc_chain(&mut fat, 2);
+    unsafe { fat.couple(ids[1], tail[0]) };
+    assert_eq!(fat.last(ids[0]), Ok(tail[1]));
+    fat.dealloc(ids[0]).unwrap();
+}
+
+#[test]
+fn cluster_run() {
+    let fs = volume();
+    let mut fat = fs.fat_mut();
+
+    // 间隔释放，留下一串长度为1的空洞
+    let ids = alloc_chain(&mut fat, 8);
+    for &id in &ids {
+        unsafe { fat.couple(id, ClusterId::EOF) };
+    }
+    for &id in ids.iter().step_by(2) {
+        fat.dealloc(id).unwrap();
+    }
+
+    // 连续的一段越过空洞，链表按编号排列
+    let head = fat.alloc_run(4).unwrap();
+    assert!(head > ids[7]);
+    let run = collect_chain(&fat, head);
+    assert_eq!(run.len(), 4);
+    assert!(run
+        .windows(2)
+        .all(|w| usize::from(w[1]) == usize::from(w[0]) + 1));
+
+    // 单个簇仍先填空洞
+    let single = fat.alloc_run(1).unwrap();
+    assert_eq!(single, ids[0]);
+
+    fat.dealloc(head).unwrap();
+    fat.dealloc(single).unwrap();
+    for &id in ids.iter().skip(1).step_by(2) {
+        fat.dealloc(id).unwrap();
+    }
+    drop(fat);
+
+    // 大文件一次分配所需的全部簇，数据读回无误
+    let mut file = ROOT.create_file("run", &fs).unwrap();
+    let data: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    assert_eq!(file.write_at(0, &data, &fs), Ok(data.len()));
+    let chain = collect_chain(&fs.fat(), ClusterId::from(file.id() as u32));
+    assert!(chain
+        .windows(2)
+        .all(|w| usize::from(w[1]) == usize::from(w[0]) + 1));
+    let mut buf = vec![0; data.len()];
+    assert_eq!(file.read_at(0, &mut buf, &fs), Ok(data.len()));
+    assert_eq!(buf, data);
+
+    file.clear(&fs);
+    ROOT.clone().unlink("run", &fs).unwrap();
+}
+
+#[test]
+fn bad_cluster() {
+    let fs = volume();
+    let mut fat = fs.fat_mut();
+    let ids = alloc_chain(&mut fat, 2);
+
+    // 链接到坏簇的链表无法走下去
+    unsafe { fat.couple(ids[0], ClusterId::BAD) };
+    assert_eq!(fat.next(ids[0]), Err(ClusterError::Defective));
+    assert_eq!(fat.last(ids[0]), Err(ClusterError::Defective));
+    assert_eq!(fat.next(ClusterId::BAD), Err(ClusterError::Defective));
+
+    // 标为坏簇的簇不会被分配
+    unsafe { fat.couple(ids[1], ClusterId::BAD) };
+    let fresh = fat.alloc().unwrap();
+    assert!(!ids.contains(&fresh));
+
+    unsafe {
+        fat.couple(ids[0], ClusterId::EOF);
+        fat.couple(ids[1], ClusterId::EOF);
+    }
+    for id in [ids[0], ids[1], fresh] {
+        fat.dealloc(id).unwrap();
+    }
+}
+
+#[test]
+fn mark_bad() {
+    let fs = volume();
+    let mut fat = fs.fat_mut();
+    let ids = alloc_chain(&mut fat, 2);
+
+    // 已分配的簇不能标为坏簇
+    assert!(!fat.mark_bad(ids[1]));
+
+    // 链表的后半截被释放后标为坏簇，再释放整条链表时在坏簇处报错
+    fat.dealloc(ids[1]).unwrap();
+    assert!(fat.mark_bad(ids[1]));
+    assert!(!fat.mark_bad(ids[1]));
+    assert_eq!(fat.dealloc(ids[0]), Err(ClusterError::Defective));
+    assert_eq!(fat.next(ids[0]), Err(ClusterError::Free));
+}
+
+#[test]
+fn scan_surface() {
+    let fs = volume();
+    // 内存中的磁盘没有坏扇区
+    assert_eq!(fs.scan_surface(), 0);
+}
+
+#[test]
+fn cluster_out_of_fat() {
+    let fs = volume();
+    let fat = fs.fat();
+
+    assert_eq!(fat.next(ClusterId::FREE), Err(ClusterError::Free));
+    assert_eq!(
+        fat.next(ClusterId::new(0x0FFF_FFF0)),
+        Err(ClusterError::Reserved)
+    );
+}
+
+/// 磁盘上是否有以`bytes`开头的扇区
+fn on_disk(bytes: &[u8]) -> bool {
+    DISK.bytes()
+        .chunks(512)
+        .any(|sector| sector.starts_with(bytes))
+}
+
+static FLUSH_REQUESTS: AtomicUsize = AtomicUsize::new(0);
+
+fn request_flush() {
+    FLUSH_REQUESTS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[test]
+fn delayed_writeback() {
+    let mut fs = volume();
+    fs.set_flush_hook(request_flush);
+
+    // 写入只留在缓存中，写回前每次修改都通知钩子
+    let mut file = ROOT.create_file("delayed", &fs).unwrap();
+    let data = b"written back only on sync";
+    file.write_at(0, data, &fs).unwrap();
+    assert_eq!(FLUSH_REQUESTS.load(Ordering::Relaxed), 2);
+    assert!(!on_disk(data));
+    fs.sync();
+    assert!(on_disk(data));
+
+    // 立即写回时不再通知钩子
+    fs.set_writeback(Writeback::Immediate);
+    let data = b"written back immediately";
+    file.write_at(0, data, &fs).unwrap();
+    assert!(on_disk(data));
+    assert_eq!(FLUSH_REQUESTS.load(Ordering::Relaxed), 2);
+    fs.set_writeback(Writeback::Delayed);
+}
+
+/// 以`boot`为0号扇区，其余扇区即[`DISK`]。
+/// 扇区缓存只绑定首个加载的设备，FAT12/16卷只能放在测试卷用不到的末尾，与之共用一块磁盘
+#[derive(Debug)]
+struct Overlay {
+    boot: [u8; 512],
+}
+
+impl BlockDevice for Overlay {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        match block_id {
+            0 => buf.copy_from_slice(&self.boot),
+            _ => DISK.read_block(block_id, buf),
+        }
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        DISK.write_block(block_id, buf);
+    }
+
+    fn handle_irq(&self) {}
+
+    fn num_blocks(&self) -> usize {
+        DISK.num_blocks()
+    }
+}
+
+/// 像常见的格式化工具那样，在[`DISK`]上自`reserved`号扇区起放置有512个根目录项的FAT12/16卷，
+/// 返回其启动扇区所在的设备。须持有[`volume`]时调用
+fn small_volume(
+    ty: FatType,
+    sec_per_clus: u8,
+    reserved: usize,
+    fat_sectors: usize,
+    total_sectors: usize,
+) -> Arc<dyn BlockDevice> {
+    const ROOT_SECTORS: usize = 32;
+
+    let mut boot = [0; 512];
+    boot[..3].copy_from_slice(&[0xEB, 0x3C, 0x90]);
+    boot[3..11].copy_from_slice(b"mkfs.fat");
+    boot[11..13].copy_from_slice(&512u16.to_le_bytes());
+    boot[13] = sec_per_clus;
+    boot[14..16].copy_from_slice(&u16::try_from(reserved).unwrap().to_le_bytes());
+    boot[16] = 2;
+    boot[17..19].copy_from_slice(&512u16.to_le_bytes());
+    match u16::try_from(total_sectors) {
+        Ok(total) => boot[19..21].copy_from_slice(&total.to_le_bytes()),
+        Err(_) => boot[32..36].copy_from_slice(&(total_sectors as u32).to_le_bytes()),
+    }
+    boot[21] = 0xF8;
+    boot[22..24].copy_from_slice(&u16::try_from(fat_sectors).unwrap().to_le_bytes());
+    boot[38] = 0x29;
+    boot[43..54].copy_from_slice(b"NO NAME    ");
+    boot[510..].copy_from_slice(&[0x55, 0xAA]);
+
+    let mut disk = DISK.bytes();
+    let start = reserved * 512;
+    let fats = 2 * fat_sectors * 512;
+    disk[start..start + fats + ROOT_SECTORS * 512].fill(0);
+    for fat in [start, start + fats / 2] {
+        // FAT[0]为介质描述符，FAT[1]全为1，FAT12的两项共占3个字节
+        disk[fat..fat + 3].copy_from_slice(&[0xF8, 0xFF, 0xFF]);
+        if ty == FatType::T16 {
+            disk[fat + 3] = 0xFF;
+        }
+    }
+
+    Arc::new(Overlay { boot })
+}
+
+#[test]
+fn fat16_volume() {
+    const RESERVED: usize = 60000;
+    const FAT_SECTORS: usize = 72;
+
+    let _guard = volume();
+    let dev = small_volume(FatType::T16, 4, RESERVED, FAT_SECTORS, DISK_SIZE / 512);
+    let fs = FatFileSystem::load(&dev).unwrap();
+    assert_eq!(fs.fat_type(), FatType::T16);
+    assert!(fs.fat().is_clean());
+
+    // 2号簇是数据区的第一个簇，跟在定长的根目录区后面
+    let data: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+    let mut file = ROOT.create_file("hello.txt", &fs).unwrap();
+    assert_eq!(file.write_at(0, &data, &fs), Ok(data.len()));
+    let dir = ROOT.mkdir("dir", &fs).unwrap();
+    dir.create_file("inner", &fs).unwrap();
+    fs.sync();
+
+    let fat = RESERVED * 512;
+    let data_start = (RESERVED + 2 * FAT_SECTORS + 32) * 512;
+    let disk = DISK.bytes().clone();
+    assert_eq!(
+        [2, 3, 4, 5].map(|id| u16_at(&disk, fat + id * 2)),
+        [3, 4, 0xFFFF, 0xFFFF]
+    );
+    assert_eq!(disk[data_start..data_start + data.len()], data);
+
+    let mut buf = vec![0; data.len()];
+    let file = ROOT.find("hello.txt", &fs).unwrap();
+    assert_eq!(file.read_at(0, &mut buf, &fs), Ok(data.len()));
+    assert_eq!(buf, data);
+    assert!(ROOT.find("dir/inner", &fs).is_some());
+    let names: Vec<_> = ROOT
+        .ls_at(0, usize::MAX, &fs)
+        .into_iter()
+        .map(|dirent| dirent.name)
+        .collect();
+    assert_eq!(names, ["hello.txt", "dir"]);
+
+    // 根目录不能增长，填满后报错
+    let full = (0..512)
+        .map(|i| ROOT.create_file(&format!("f{i}"), &fs))
+        .find_map(Result::err);
+    assert!(matches!(full, Some(vfs::Error::NoSpace)));
+}
+
+#[test]
+fn fat12_volume() {
+    const RESERVED: usize = 40000;
+
+    let _guard = volume();
+    // 2000个簇
+    let dev = small_volume(FatType::T12, 8, RESERVED, 6, RESERVED + 12 + 32 + 2000 * 8);
+    let fs = FatFileSystem::load(&dev).unwrap();
+    assert_eq!(fs.fat_type(), FatType::T12);
+
+    let mut file = ROOT.create_file("twelve", &fs).unwrap();
+    let data = vec![0x12; 3 * 8 * 512];
+    assert_eq!(file.write_at(0, &data, &fs), Ok(data.len()));
+    fs.sync();
+
+    // 两个表项共用3个字节：2->3、3->4，4为末尾
+    let fat = RESERVED * 512;
+    let entries = DISK.bytes()[fat + 3..fat + 9].to_vec();
+    assert_eq!(entries, [0x03, 0x40, 0x00, 0xFF, 0x0F, 0x00]);
+
+    // 341号表项横跨FAT的前两个扇区
+    let mut fat = fs.fat_mut();
+    let [prev, id, next] = [340, 341, 342].map(ClusterId::new);
+    for cid in [prev, id, next] {
+        unsafe { fat.couple(cid, ClusterId::EOF) };
+    }
+    unsafe { fat.couple(id, next) };
+    assert_eq!(fat.next(prev), Ok(None));
+    assert_eq!(fat.next(id), Ok(Some(next)));
+    assert_eq!(fat.next(next), Ok(None));
+    fat.dealloc(id).unwrap();
+    fat.dealloc(prev).unwrap();
+    assert_eq!(fat.next(next), Err(ClusterError::Free));
+}
+
+#[test]
+fn truncate() {
+    let fs = volume();
+    let cluster_bytes = fs.data().cluster_sectors() * 512;
+    let mut file = ROOT.create_file("truncated", &fs).unwrap();
+    let data = vec![0xAB; 3 * cluster_bytes];
+    file.write_at(0, &data, &fs).unwrap();
+    let head = ClusterId::new(file.id() as u32);
+
+    // 截断时释放多余的簇
+    let chain = collect_chain(&fs.fat(), head);
+    file.truncate(cluster_bytes + 1, &fs).unwrap();
+    assert_eq!(collect_chain(&fs.fat(), head), chain[..2]);
+    assert_eq!(fs.fat().next(chain[2]), Err(ClusterError::Free));
+
+    // 扩展出的部分读出为0，包括原末尾所在簇中截断前的数据
+    file.truncate(2 * cluster_bytes + 10, &fs).unwrap();
+    let mut buf = vec![0xFF; data.len()];
+    assert_eq!(file.read_at(0, &mut buf, &fs), Ok(2 * cluster_bytes + 10));
+    assert!(buf[..=cluster_bytes].iter().all(|&b| b == 0xAB));
+    assert!(buf[cluster_bytes + 1..2 * cluster_bytes + 10]
+        .iter()
+        .all(|&b| b == 0));
+    assert_eq!(collect_chain(&fs.fat(), head).len(), 3);
+
+    file.truncate(0, &fs).unwrap();
+    assert_eq!(file.id(), 0);
+    assert_eq!(fs.fat().next(head), Err(ClusterError::Free));
+}
+
+#[test]
+fn file_size_limit() {
+    let fs = volume();
+    let mut file = ROOT.create_file("huge", &fs).unwrap();
+
+    // 越过上限的写入与扩展一个字节也不做，更不会分配簇
+    assert_eq!(
+        file.write_at(MAX_FILE_SIZE, b"x", &fs),
+        Err(WriteError::FileTooLarge)
+    );
+    assert_eq!(
+        file.write_at(usize::MAX, b"x", &fs),
+        Err(WriteError::FileTooLarge)
+    );
+    assert_eq!(
+        file.truncate(MAX_FILE_SIZE + 1, &fs),
+        Err(WriteError::FileTooLarge)
+    );
+    assert_eq!(file.stat(&fs).size, 0);
+    assert_eq!(file.id(), 0);
+
+    ROOT.clone().unlink("huge", &fs).unwrap();
+}
+
+#[test]
+fn unaligned_io() {
+    let fs = volume();
+    let mut file = ROOT.create_file("unaligned", &fs).unwrap();
+    let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+    file.write_at(0, &data, &fs).unwrap();
+
+    // 跨越扇区边界的读写只触及给定的区间
+    assert_eq!(file.write_at(500, &[0xAA; 20], &fs), Ok(20));
+    let mut buf = [0; 30];
+    assert_eq!(file.read_at(495, &mut buf, &fs), Ok(30));
+    assert_eq!(buf[..5], data[495..500]);
+    assert_eq!(buf[5..25], [0xAA; 20]);
+    assert_eq!(buf[25..], data[520..525]);
+
+    // 越过末尾写入，中间的空洞读出为0
+    assert_eq!(file.write_at(1500, b"tail", &fs), Ok(4));
+    let mut buf = vec![0xFF; 510];
+    assert_eq!(file.read_at(994, &mut buf, &fs), Ok(510));
+    assert_eq!(buf[..6], data[994..]);
+    assert!(buf[6..506].iter().all(|&b| b == 0));
+    assert_eq!(&buf[506..], b"tail");
+
+    file.clear(&fs);
+}
+
+static NOW: AtomicU64 = AtomicU64::new(0);
+
+fn clock() -> u64 {
+    NOW.load(Ordering::Relaxed)
+}
+
+#[test]
+fn timestamps() {
+    let mut fs = volume();
+    fs.set_clock(clock);
+
+    // 2024-02-29T12:34:57Z，修改时间只精确到2秒，访问时间只精确到日
+    NOW.store(1_709_210_097, Ordering::Relaxed);
+    let mut file = ROOT.create_file("stamped", &fs).unwrap();
+    let stat = file.stat(&fs);
+    assert_eq!(stat.ctime, 1_709_210_097);
+    assert_eq!(stat.mtime, 1_709_210_096);
+    assert_eq!(stat.atime, 1_709_164_800);
+
+    // 写入更新修改时间，创建时间不变
+    NOW.store(1_709_300_000, Ordering::Relaxed);
+    file.write_at(0, b"stamped", &fs).unwrap();
+    let stat = ROOT.find("stamped", &fs).unwrap().stat(&fs);
+    assert_eq!(stat.ctime, 1_709_210_097);
+    assert_eq!(stat.mtime, 1_709_300_000);
+
+    // 超出FAT能表示的范围时取最近的一端
+    file.set_times(Some(0), Some(u64::MAX), &fs);
+    let stat = file.stat(&fs);
+    assert_eq!(stat.atime, 315_532_800);
+    assert_eq!(stat.mtime, 4_354_819_198);
+
+    let root = ROOT.stat(&fs);
+    assert_eq!([root.atime, root.mtime, root.ctime], [0; 3]);
+
+    file.clear(&fs);
+}
+
+#[test]
+fn dir_cursor() {
+    let fs = volume();
+    let mut dir = ROOT.mkdir("cursor", &fs).unwrap();
+    let names: Vec<_> = (0..40).map(|i| format!("entry {i:0>20}")).collect();
+    for name in &names {
+        dir.create_file(name, &fs).unwrap();
+    }
+
+    // 逐页读取，页间删去已读过的项，续读时既不重复也不遗漏
+    let mut iter = dir.read_dir(&fs);
+    let mut listed: Vec<_> = iter.by_ref().take(15).map(|dirent| dirent.name).collect();
+    let offset = iter.offset();
+    for name in &listed[..10] {
+        dir.unlink(name, &fs).unwrap();
+    }
+    listed.extend(dir.dir_iter(offset, &fs).map(|dirent| dirent.name));
+    assert_eq!(listed, names);
+
+    for name in &names[10..] {
+        dir.unlink(name, &fs).unwrap();
+    }
+    assert_eq!(dir.read_dir(&fs).count(), 0);
+    ROOT.clone().rmdir("cursor", &fs).unwrap();
+}
+
+#[test]
+fn name_too_long() {
+    let fs = volume();
+    let longest = "n".repeat(vfs::NAME_MAX);
+    let too_long = "n".repeat(vfs::NAME_MAX + 1);
+
+    assert!(matches!(
+        ROOT.create_file(&too_long, &fs),
+        Err(vfs::Error::NameTooLong)
+    ));
+    assert!(matches!(
+        ROOT.mkdir(&too_long, &fs),
+        Err(vfs::Error::NameTooLong)
+    ));
+
+    // 改名失败时原文件保留
+    let mut file = ROOT.create_file(&longest, &fs).unwrap();
+    assert!(ROOT.find(&longest, &fs).is_some());
+    let mut root = ROOT.clone();
+    assert!(matches!(
+        root.rename(&longest, None, &too_long, &fs),
+        Err(vfs::Error::NameTooLong)
+    ));
+    assert!(ROOT.find(&longest, &fs).is_some());
+
+    file.clear(&fs);
+    root.unlink(&longest, &fs).unwrap();
+}
+
+#[test]
+fn root_growth() {
+    let fs = volume();
+    let (head, _) = fs.dir_head(ClusterId::FREE);
+    let head = head.unwrap();
+    let before = collect_chain(&fs.fat(), head).len();
+
+    // 名称长短不一，长目录项会跨越扇区与簇的边界
+    let names: Vec<_> = (0..400)
+        .map(|i| match i % 4 {
+            0 => format!("r{i}"),
+            1 => format!("root entry {i:0>40}"),
+            2 => format!("{i:x>200}"),
+            _ => format!("{i}.txt"),
+        })
+        .collect();
+    for name in &names {
+        let mut file = ROOT.create_file(name, &fs).unwrap();
+        file.write_at(0, name.as_bytes(), &fs).unwrap();
+    }
+    assert!(collect_chain(&fs.fat(), head).len() > before);
+    fs.sync();
+
+    let listed: Vec<_> = ROOT
+        .ls_at(0, usize::MAX, &fs)
+        .into_iter()
+        .map(|dirent| dirent.name)
+        .collect();
+    for name in &names {
+        assert!(listed.contains(name), "{name} isn't listed");
+        let file = ROOT.find(name, &fs).unwrap();
+        let mut buf = vec![0; name.len()];
+        assert_eq!(file.read_at(0, &mut buf, &fs), Ok(name.len()));
+        assert_eq!(buf, name.as_bytes());
+    }
+
+    let mut root = ROOT.clone();
+    for name in &names {
+        ROOT.find(name, &fs).unwrap().clear(&fs);
+        root.unlink(name, &fs).unwrap();
+    }
+    assert!(names.iter().all(|name| ROOT.find(name, &fs).is_none()));
+    assert!(!ROOT
+        .ls_at(0, usize::MAX, &fs)
+        .iter()
+        .any(|dirent| names.contains(&dirent.name)));
+}
+
+/// 目录的`..`所指的首簇
+fn parent_id(dir: &Inode, fs: &FatFileSystem) -> u64 {
+    let first = fs
+        .data()
+        .cluster(ClusterId::new(dir.id() as u32))
+        .unwrap()
+        .start;
+    let dotdot = sector::get(first)
+        .lock()
+        .map(mem::size_of::<ShortDirEntry>(), ShortDirEntry::cluster_id);
+    dotdot.into()
+}
+
+#[test]
+fn rename_across_dirs() {
+    let fs = volume();
+    let mut src_dir = ROOT.mkdir("rename src", &fs).unwrap();
+    let mut dest_dir = ROOT.mkdir("rename dest", &fs).unwrap();
+
+    // 替换另一目录下已有的文件：簇链表随文件移动，被替换者的簇链表释放
+    let mut moved = src_dir.create_file("moved file", &fs).unwrap();
+    moved.write_at(0, b"moved", &fs).unwrap();
+    let mut replaced = dest_dir.create_file("replaced file", &fs).unwrap();
+    replaced.write_at(0, b"replaced", &fs).unwrap();
+    let (moved_head, replaced_head) = (moved.id(), replaced.id());
+    src_dir
+        .rename("moved file", Some(&mut dest_dir), "replaced file", &fs)
+        .unwrap();
+    assert!(src_dir.find("moved file", &fs).is_none());
+    let file = dest_dir.find("replaced file", &fs).unwrap();
+    assert_eq!(file.id(), moved_head);
+    let mut buf = [0; 8];
+    assert_eq!(file.read_at(0, &mut buf, &fs), Ok(5));
+    assert_eq!(&buf[..5], b"moved");
+    assert_eq!(
+        fs.fat().next(ClusterId::new(replaced_head as u32)),
+        Err(ClusterError::Free)
+    );
+
+    // 移到另一目录下不存在的名称
+    dest_dir
+        .rename("replaced file", Some(&mut src_dir), "back", &fs)
+        .unwrap();
+    assert!(dest_dir.find("replaced file", &fs).is_none());
+    assert_eq!(src_dir.find("back", &fs).unwrap().id(), moved_head);
+
+    // 改名为自身什么也不做
+    let mut same = src_dir.clone();
+    src_dir
+        .rename("back", Some(&mut same), "back", &fs)
+        .unwrap();
+    assert_eq!(src_dir.find("back", &fs).unwrap().id(), moved_head);
+
+    // 文件与目录不能互相替换，目录只能替换空目录
+    src_dir.mkdir("sub", &fs).unwrap();
+    let sub_full = dest_dir.mkdir("full", &fs).unwrap();
+    sub_full.create_file("child", &fs).unwrap();
+    assert!(matches!(
+        src_dir.rename("back", Some(&mut dest_dir), "full", &fs),
+        Err(vfs::Error::IsADirectory)
+    ));
+    assert!(matches!(
+        src_dir.rename("sub", Some(&mut dest_dir), "full", &fs),
+        Err(vfs::Error::DirectoryNotEmpty)
+    ));
+    assert!(src_dir.find("sub", &fs).is_some());
+
+    // 移动目录时更新其`..`
+    let sub_head = src_dir.find("sub", &fs).unwrap().id();
+    src_dir
+        .rename("sub", Some(&mut dest_dir), "sub", &fs)
+        .unwrap();
+    let sub = dest_dir.find("sub", &fs).unwrap();
+    assert_eq!(sub.id(), sub_head);
+    assert_eq!(parent_id(&sub, &fs), dest_dir.id());
+
+    // 目录不能移到其自身之下
+    let mut nested = sub.mkdir("nested", &fs).unwrap();
+    assert!(matches!(
+        dest_dir.rename("sub", Some(&mut nested), "loop", &fs),
+        Err(vfs::Error::InvalidInput)
+    ));
+    let mut root = ROOT.clone();
+    assert!(matches!(
+        root.rename("rename dest", Some(&mut nested), "loop", &fs),
+        Err(vfs::Error::InvalidInput)
+    ));
+    assert!(dest_dir.find("sub/nested", &fs).is_some());
+
+    // 移到根目录下，`..`指向根目录
+    dest_dir
+        .rename("sub", Some(&mut root), "rename sub", &fs)
+        .unwrap();
+    let sub = ROOT.find("rename sub", &fs).unwrap();
+    assert_eq!(parent_id(&sub, &fs), 0);
+
+    ROOT.find("rename sub", &fs)
+        .unwrap()
+        .rmdir("nested", &fs)
+        .unwrap();
+    root.rmdir("rename sub", &fs).unwrap();
+    src_dir.find("back", &fs).unwrap().clear(&fs);
+    src_dir.unlink("back", &fs).unwrap();
+    let mut full = dest_dir.find("full", &fs).unwrap();
+    full.unlink("child", &fs).unwrap();
+    dest_dir.rmdir("full", &fs).unwrap();
+    root.rmdir("rename src", &fs).unwrap();
+    root.rmdir("rename dest", &fs).unwrap();
+}
+
+/// 经由特型对象访问，同内核
+#[test]
+fn vfs_trait_objects() {
+    let _guard = volume();
+    let dev: Arc<dyn BlockDevice> = DISK.clone();
+    let fs = FatVfs::new(FatFileSystem::load(&dev).unwrap());
+    let root = fs.root();
+    assert_eq!(root.kind(), DirEntryType::Directory);
+
+    root.mkdir("vfs dir").unwrap();
+    let dir = root.lookup("vfs dir").unwrap();
+    let file = dir.create("file").unwrap();
+    assert!(matches!(dir.create("file"), Err(vfs::Error::AlreadyExists)));
+    assert_eq!(file.write_at(0, b"through the trait").unwrap(), 17);
+
+    let found = dir.lookup("file").unwrap();
+    assert_eq!(found.kind(), DirEntryType::Regular);
+    let mut buf = [0; 32];
+    assert_eq!(found.read_at(8, &mut buf).unwrap(), 9);
+    assert_eq!(&buf[..9], b"the trait");
+    assert!(matches!(
+        found.lookup("child"),
+        Err(vfs::Error::NotADirectory)
+    ));
+    assert!(matches!(
+        dir.read_at(0, &mut buf),
+        Err(vfs::Error::IsADirectory)
+    ));
+    assert!(matches!(
+        found.write_at(MAX_FILE_SIZE, b"x"),
+        Err(vfs::Error::FileTooLarge)
+    ));
+    found.truncate(7).unwrap();
+    assert_eq!(found.stat().size, 7);
+
+    // 读到一半可以从返回的游标续读
+    dir.create("second").unwrap();
+    let mut names = Vec::new();
+    let next = dir
+        .read_dir(0, &mut |dirent| {
+            names.push(dirent.name.clone());
+            names.last().unwrap() != "file"
+        })
+        .unwrap();
+    names.pop();
+    dir.read_dir(next, &mut |dirent| {
+        names.push(dirent.name.clone());
+        true
+    })
+    .unwrap();
+    assert_eq!(names.iter().filter(|name| *name == "file").count(), 1);
+    assert_eq!(names[names.len() - 2..], ["file", "second"]);
+
+    // 跨目录移动，目标须是目录
+    dir.rename("file", &*root, "vfs moved").unwrap();
+    assert!(matches!(dir.lookup("file"), Err(vfs::Error::NotFound)));
+    assert_eq!(root.lookup("vfs moved").unwrap().stat().size, 7);
+    assert!(matches!(
+        dir.rename("second", &*found, "x"),
+        Err(vfs::Error::NotADirectory)
+    ));
+    dir.rename("second", &*dir, "renamed").unwrap();
+    assert!(dir.lookup("renamed").is_ok());
+
+    root.unlink("vfs moved").unwrap();
+    dir.unlink("renamed").unwrap();
+    assert!(matches!(
+        root.unlink("vfs dir"),
+        Err(vfs::Error::IsADirectory)
+    ));
+    root.rmdir("vfs dir").unwrap();
+    assert!(matches!(root.lookup("vfs dir"), Err(vfs::Error::NotFound)));
+}
+
+/// 供[`peek`]查找的根目录，与修改所经由的不是同一个索引节点
+static PEEK_ROOT: Mutex<Option<Arc<dyn VfsInode>>> = Mutex::new(None);
+static PEEKS: AtomicUsize = AtomicUsize::new(0);
+
+/// 作为钩子在修改结束前调用，修改仍持有卷的修改锁
+fn peek() {
+    if let Some(root) = &*PEEK_ROOT.lock().unwrap() {
+        let peeked = root.lookup("peeked").unwrap();
+        let mut buf = [0; 6];
+        assert_eq!(peeked.read_at(0, &mut buf).unwrap(), 6);
+        PEEKS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// 查找与读取不等待进行中的修改
+#[test]
+fn lookup_during_modification() {
+    let _guard = volume();
+    let dev: Arc<dyn BlockDevice> = DISK.clone();
+    let mut sb = FatFileSystem::load(&dev).unwrap();
+    sb.set_flush_hook(peek);
+    let fs = FatVfs::new(sb);
+    let root = fs.root();
+    root.create("peeked")
+        .unwrap()
+        .write_at(0, b"peeked")
+        .unwrap();
+
+    *PEEK_ROOT.lock().unwrap() = Some(fs.root());
+    let file = root.create("writer").unwrap();
+    file.write_at(0, &[7; 5000]).unwrap();
+    file.truncate(10).unwrap();
+    root.rename("writer", &*root, "renamed").unwrap();
+    root.unlink("renamed").unwrap();
+    PEEK_ROOT.lock().unwrap().take();
+    assert_eq!(PEEKS.load(Ordering::Relaxed), 5);
+
+    root.unlink("peeked").unwrap();
+}
+
+#[test]
+fn read_dir_plus_matches_stat() {
+    let _guard = volume();
+    let dev: Arc<dyn BlockDevice> = DISK.clone();
+    let fs = FatVfs::new(FatFileSystem::load(&dev).unwrap());
+    let root = fs.root();
+    root.mkdir("plus dir").unwrap();
+    let dir = root.lookup("plus dir").unwrap();
+    dir.mkdir("sub").unwrap();
+    dir.create("empty").unwrap();
+    let file = dir.create("file").unwrap();
+    file.write_at(0, &[1; 5000]).unwrap();
+    file.set_times(Some(1_000_000_000), Some(1_200_000_000))
+        .unwrap();
+
+    let mut entries = Vec::new();
+    dir.read_dir_plus(0, &mut |dirent, stat| {
+        entries.push((dirent.name.clone(), dirent.ty, *stat));
+        true
+    })
+    .unwrap();
+    assert_eq!(entries.len(), 3);
+    for (name, ty, stat) in &entries {
+        assert_eq!(*ty, stat.mode);
+        assert_eq!(*stat, dir.lookup(name).unwrap().stat());
+    }
+    assert_eq!(entries[2].2.size, 5000);
+    assert_eq!(entries[2].2.mtime, 1_200_000_000);
+
+    // 游标与`read_dir`的相同
+    let next = dir.read_dir_plus(0, &mut |_, _| false).unwrap();
+    assert_eq!(next, dir.read_dir(0, &mut |_| false).unwrap());
+
+    dir.unlink("file").unwrap();
+    dir.unlink("empty").unwrap();
+    dir.rmdir("sub").unwrap();
+    root.rmdir("plus dir").unwrap();
+}
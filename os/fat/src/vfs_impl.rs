@@ -0,0 +1,257 @@
+//! 以[`vfs::FileSystem`]与[`vfs::VfsInode`]特型对象暴露FAT卷。
+//! 卷上不记录属主，文件都属于超级用户
+
+use alloc::sync::Arc;
+use core::any::Any;
+
+use spin::Mutex;
+use vfs::{DirEntry, DirEntryType, Stat, VfsInode};
+
+use crate::{sector, ClusterError, FatFileSystem, Inode, WriteError, MAX_FILE_SIZE, ROOT};
+
+/// 加载好的FAT卷。查找与读取随时可以进行，修改文件或目录的操作彼此串行
+pub struct FatVfs(Arc<Volume>);
+
+impl FatVfs {
+    pub fn new(sb: FatFileSystem) -> Self {
+        Self(Arc::new(Volume {
+            sb,
+            modify: Mutex::new(()),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct Volume {
+    sb: FatFileSystem,
+    /// 修改文件或目录时持有，查找与读取不必获取
+    modify: Mutex<()>,
+}
+
+impl vfs::FileSystem for FatVfs {
+    fn root(&self) -> Arc<dyn VfsInode> {
+        Arc::new(FatInode::new(&self.0, ROOT.clone()))
+    }
+
+    fn unmount(&self) {
+        let _modify = self.0.modify.lock();
+        self.0.sb.unmount();
+    }
+}
+
+/// 须先锁索引节点，再取卷的修改锁
+#[derive(Debug)]
+struct FatInode {
+    vol: Arc<Volume>,
+    inode: Mutex<Inode>,
+}
+
+impl FatInode {
+    fn new(vol: &Arc<Volume>, inode: Inode) -> Self {
+        Self {
+            vol: vol.clone(),
+            inode: Mutex::new(inode),
+        }
+    }
+
+    /// 取回同一卷上的目录，另一文件系统的报[`vfs::Error::CrossesDevices`]
+    fn same_volume<'a>(&self, dir: &'a dyn VfsInode) -> Result<&'a Self, vfs::Error> {
+        let dir = dir
+            .as_any()
+            .downcast_ref::<Self>()
+            .filter(|dir| Arc::ptr_eq(&dir.vol, &self.vol))
+            .ok_or(vfs::Error::CrossesDevices)?;
+        if dir.kind() != DirEntryType::Directory {
+            return Err(vfs::Error::NotADirectory);
+        }
+        Ok(dir)
+    }
+
+    fn dir(&self) -> Result<spin::MutexGuard<'_, Inode>, vfs::Error> {
+        let inode = self.inode.lock();
+        match inode.kind() {
+            DirEntryType::Directory => Ok(inode),
+            _ => Err(vfs::Error::NotADirectory),
+        }
+    }
+
+    fn file(&self) -> Result<spin::MutexGuard<'_, Inode>, vfs::Error> {
+        let inode = self.inode.lock();
+        match inode.kind() {
+            DirEntryType::Directory => Err(vfs::Error::IsADirectory),
+            _ => Ok(inode),
+        }
+    }
+}
+
+impl VfsInode for FatInode {
+    /// 首簇编号，空文件没有簇，均为0
+    fn id(&self) -> u64 {
+        self.inode.lock().id()
+    }
+
+    fn kind(&self) -> DirEntryType {
+        self.inode.lock().kind()
+    }
+
+    fn stat(&self) -> Stat {
+        self.inode.lock().stat(&self.vol.sb)
+    }
+
+    fn size(&self) -> usize {
+        self.inode.lock().size()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, vfs::Error> {
+        let inode = self.file()?;
+        inode
+            .read_at(offset, buf, &self.vol.sb)
+            .map_err(cluster_error)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, vfs::Error> {
+        let mut inode = self.file()?;
+        let _modify = self.vol.modify.lock();
+        inode
+            .write_at(offset, buf, &self.vol.sb)
+            .map_err(write_error)
+    }
+
+    fn truncate(&self, size: usize) -> Result<(), vfs::Error> {
+        let mut inode = self.file()?;
+        let _modify = self.vol.modify.lock();
+        inode.truncate(size, &self.vol.sb).map_err(write_error)
+    }
+
+    fn max_size(&self) -> usize {
+        MAX_FILE_SIZE
+    }
+
+    /// 按整簇分配，FAT表不计
+    fn allocated(&self, size: usize) -> usize {
+        size.next_multiple_of(self.vol.sb.data().cluster_sectors() * sector::size())
+    }
+
+    fn set_times(&self, atime: Option<u64>, mtime: Option<u64>) -> Result<(), vfs::Error> {
+        let inode = self.inode.lock();
+        let _modify = self.vol.modify.lock();
+        inode.set_times(atime, mtime, &self.vol.sb);
+        Ok(())
+    }
+
+    fn prefetch(&self, offset: usize, len: usize) {
+        if let Ok(inode) = self.file() {
+            inode.prefetch(offset, len, &self.vol.sb);
+        }
+    }
+
+    fn release(&self, offset: usize, len: usize) {
+        if let Ok(inode) = self.file() {
+            inode.release(offset, len, &self.vol.sb);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn VfsInode>, vfs::Error> {
+        let dir = self.dir()?;
+        let inode = dir.find(name, &self.vol.sb).ok_or(vfs::Error::NotFound)?;
+        Ok(Arc::new(Self::new(&self.vol, inode)))
+    }
+
+    fn create(&self, name: &str) -> Result<Arc<dyn VfsInode>, vfs::Error> {
+        let dir = self.dir()?;
+        let _modify = self.vol.modify.lock();
+        let inode = dir.create_file(name, &self.vol.sb)?;
+        Ok(Arc::new(Self::new(&self.vol, inode)))
+    }
+
+    fn mkdir(&self, name: &str) -> Result<(), vfs::Error> {
+        let dir = self.dir()?;
+        let _modify = self.vol.modify.lock();
+        dir.mkdir(name, &self.vol.sb)?;
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), vfs::Error> {
+        let mut dir = self.dir()?;
+        let _modify = self.vol.modify.lock();
+        dir.unlink(name, &self.vol.sb)
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), vfs::Error> {
+        let mut dir = self.dir()?;
+        let _modify = self.vol.modify.lock();
+        dir.rmdir(name, &self.vol.sb)
+    }
+
+    fn rename(
+        &self,
+        old_name: &str,
+        new_dir: &dyn VfsInode,
+        new_name: &str,
+    ) -> Result<(), vfs::Error> {
+        let new_dir = self.same_volume(new_dir)?;
+        let mut dir = self.dir()?;
+        // 同一目录不能再锁一次
+        if core::ptr::eq(self, new_dir) || dir.id() == new_dir.id() {
+            let _modify = self.vol.modify.lock();
+            return dir.rename(old_name, None, new_name, &self.vol.sb);
+        }
+        let mut new_parent = new_dir.inode.lock();
+        let _modify = self.vol.modify.lock();
+        dir.rename(old_name, Some(&mut new_parent), new_name, &self.vol.sb)
+    }
+
+    fn read_dir(
+        &self,
+        offset: usize,
+        sink: &mut dyn FnMut(&DirEntry) -> bool,
+    ) -> Result<usize, vfs::Error> {
+        let dir = self.dir()?;
+        let sb = &self.vol.sb;
+        // 偏移量是目录项槽位的序号，增删其它目录项不影响后续读取
+        let mut dir_iter = dir.dir_iter(offset, sb);
+        let mut offset = dir_iter.offset();
+        while let Some(dirent) = dir_iter.next() {
+            if !sink(&dirent) {
+                break;
+            }
+            offset = dir_iter.offset();
+        }
+        Ok(offset)
+    }
+
+    /// 状态取自遍历到的短目录项，块数仍须沿簇链表统计
+    fn read_dir_plus(
+        &self,
+        offset: usize,
+        sink: &mut dyn FnMut(&DirEntry, &Stat) -> bool,
+    ) -> Result<usize, vfs::Error> {
+        let dir = self.dir()?;
+        let sb = &self.vol.sb;
+        let mut dir_iter = dir.dir_iter(offset, sb);
+        let mut offset = dir_iter.offset();
+        while let Some((dirent, stat)) = dir_iter.next_plus() {
+            if !sink(&dirent, &stat) {
+                break;
+            }
+            offset = dir_iter.offset();
+        }
+        Ok(offset)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+fn cluster_error(e: ClusterError) -> vfs::Error {
+    log::error!("broken cluster chain: {e}");
+    vfs::Error::Io
+}
+
+fn write_error(e: WriteError) -> vfs::Error {
+    match e {
+        WriteError::Cluster(e) => cluster_error(e),
+        WriteError::FileTooLarge => vfs::Error::FileTooLarge,
+    }
+}
@@ -6,7 +6,7 @@
 
 use alloc::string::String;
 use alloc::vec::Vec;
-use core::mem;
+use core::mem::{self, offset_of};
 
 use enumflags2::{bitflags, BitFlags};
 
@@ -53,7 +53,7 @@ impl DirEntry {
 }
 
 #[derive(Debug, Default, Clone, Copy)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct ShortDirEntry {
     name: [u8; 11],
 
@@ -64,26 +64,26 @@ pub struct ShortDirEntry {
 
     /// Count of tenths of a second.
     /// Range: [0, 199]
-    _crt_time_tenth: u8,
+    crt_time_tenth: u8,
 
     /// Creation time, granularity is 2 seconds
-    _crt_time: u16,
+    crt_time: u16,
 
     /// Creation date
-    _crt_date: u16,
+    crt_date: u16,
 
     /// Last access date
-    _lst_acc_date: u16,
+    lst_acc_date: u16,
 
     /// High word of first data cluster number
     /// for file/directory described by this entry
     fst_clus_hi: u16,
 
     /// Last modification time
-    _wrt_time: u16,
+    wrt_time: u16,
 
     /// Last modification date
-    _wrt_date: u16,
+    wrt_date: u16,
 
     /// Low word of first data cluster number
     /// for file/directory described by this entry
@@ -94,6 +94,18 @@ pub struct ShortDirEntry {
     file_size: u32,
 }
 
+const _: () = {
+    assert!(mem::size_of::<ShortDirEntry>() == 32);
+    assert!(offset_of!(ShortDirEntry, attr) == 11);
+    assert!(offset_of!(ShortDirEntry, crt_time_tenth) == 13);
+    assert!(offset_of!(ShortDirEntry, crt_time) == 14);
+    assert!(offset_of!(ShortDirEntry, lst_acc_date) == 18);
+    assert!(offset_of!(ShortDirEntry, fst_clus_hi) == 20);
+    assert!(offset_of!(ShortDirEntry, wrt_time) == 22);
+    assert!(offset_of!(ShortDirEntry, fst_clus_lo) == 26);
+    assert!(offset_of!(ShortDirEntry, file_size) == 28);
+};
+
 impl ShortDirEntry {
     pub fn as_cwd(&self) -> Self {
         let mut cwd = *self;
@@ -101,14 +113,10 @@ impl ShortDirEntry {
         cwd
     }
 
-    /// 创建一个簇编号为`pid`的父目录项(..)
-    pub fn new_parent(mut pid: ClusterId<u32>) -> Self {
+    /// 创建一个簇编号为`pid`的父目录项(..)，父目录为根时`pid`为[`ClusterId::FREE`]
+    pub fn new_parent(pid: ClusterId<u32>) -> Self {
         let mut dirent = Self::default();
 
-        // 若此父目录为根，则使用[`ClusterId::FREE`]
-        if pid == ClusterId::MIN {
-            pid = ClusterId::FREE;
-        }
         dirent.set_cluster_id(pid);
 
         dirent.attr |= AttrFlag::Directory;
@@ -116,18 +124,9 @@ impl ShortDirEntry {
         dirent
     }
 
+    /// 新创的空文件，以及指向根目录的相对目录项，其簇编号为[`ClusterId::FREE`]
     pub fn cluster_id(&self) -> ClusterId<u32> {
-        let id: ClusterId<u32> = (self.fst_clus_lo, self.fst_clus_hi).into();
-
-        if self.attr.contains(AttrFlag::Directory) {
-            // NOTE: 相对目录项指向根目录时，其簇编号为0。
-            //       但我们需要有效的索引，所以直接提到[`ClusterId::MIN`]，
-            //       即真正的根目录的地址（绝大部分情况）。
-            id.max(ClusterId::MIN)
-        } else {
-            /* 新创的空文件ID为[`ClusterId::FREE`]，直接返回无妨 */
-            id
-        }
+        (self.fst_clus_lo, self.fst_clus_hi).into()
     }
 
     pub fn set_cluster_id(&mut self, id: ClusterId<u32>) {
@@ -191,6 +190,36 @@ impl ShortDirEntry {
     pub fn is_relative(&self) -> bool {
         self.name == CWD_NAME || self.name == PARENT_NAME
     }
+
+    /// 创建时间，自UNIX纪元的秒数，未记录时为0
+    pub fn created(&self) -> u64 {
+        decode_time(self.crt_date, self.crt_time, self.crt_time_tenth)
+    }
+
+    /// 最后修改时间
+    pub fn modified(&self) -> u64 {
+        decode_time(self.wrt_date, self.wrt_time, 0)
+    }
+
+    /// 最后访问时间，只精确到日
+    pub fn accessed(&self) -> u64 {
+        decode_time(self.lst_acc_date, 0, 0)
+    }
+
+    /// 新建时三个时间相同
+    pub fn set_created(&mut self, now: u64) {
+        (self.crt_date, self.crt_time, self.crt_time_tenth) = encode_time(now);
+        self.set_modified(now);
+        self.set_accessed(now);
+    }
+
+    pub fn set_modified(&mut self, time: u64) {
+        (self.wrt_date, self.wrt_time, _) = encode_time(time);
+    }
+
+    pub fn set_accessed(&mut self, time: u64) {
+        (self.lst_acc_date, _, _) = encode_time(time);
+    }
 }
 
 impl ShortDirEntry {
@@ -207,7 +236,7 @@ impl ShortDirEntry {
 ///
 /// 目录项名称最长为255字节，所以最多用到10个长目录项。
 #[derive(Debug, Clone, Copy)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct LongDirEntry {
     /// 序号（1起）
     pub ord: u8,
@@ -225,6 +254,19 @@ pub struct LongDirEntry {
     name3: [u8; 4],
 }
 
+const _: () = {
+    assert!(mem::size_of::<LongDirEntry>() == 32);
+    assert!(offset_of!(LongDirEntry, name1) == 1);
+    assert!(offset_of!(LongDirEntry, _attr) == 11);
+    assert!(offset_of!(LongDirEntry, _type) == 12);
+    assert!(offset_of!(LongDirEntry, chksum) == 13);
+    assert!(offset_of!(LongDirEntry, name2) == 14);
+    assert!(offset_of!(LongDirEntry, _fst_clus_lo) == 26);
+    assert!(offset_of!(LongDirEntry, name3) == 28);
+    // 长短目录项共用一个槽位
+    assert!(mem::size_of::<DirEntry>() == 32);
+};
+
 impl Default for LongDirEntry {
     fn default() -> Self {
         Self {
@@ -371,6 +413,68 @@ pub fn rename_dirents(short: &ShortDirEntry, new_name: &str) -> (ShortDirEntry,
     (short, longs)
 }
 
+/// FAT的纪元1980-01-01距UNIX纪元的秒数
+const FAT_EPOCH: u64 = 315_532_800;
+/// 日期字段的年份只有7位，能表示的最后一刻是2107-12-31T23:59:58
+const FAT_TIME_MAX: u64 = 4_354_819_198;
+
+/// 自UNIX纪元的秒数换成目录项的`(日期, 时间, 十毫秒)`，超出FAT能表示的范围时取最近的一端。
+///
+/// - 日期：年（自1980起）占高7位，月占4位，日占低5位；
+/// - 时间：时占高5位，分占6位，秒的一半占低5位；
+/// - 十毫秒：补足时间字段舍去的奇数秒，0～199。
+fn encode_time(secs: u64) -> (u16, u16, u8) {
+    let secs = secs.clamp(FAT_EPOCH, FAT_TIME_MAX);
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs = secs % 86400;
+    let (hour, minute, second) = (secs / 3600, secs % 3600 / 60, secs % 60);
+
+    let date = ((year - 1980) << 9) | (month << 5) | day;
+    let time = (hour << 11) | (minute << 5) | (second / 2);
+    (date as u16, time as u16, (second % 2 * 100) as u8)
+}
+
+/// [`encode_time`]的逆运算，日期为0（未记录）或无效时得到0
+fn decode_time(date: u16, time: u16, tenth: u8) -> u64 {
+    let (date, time) = (u64::from(date), u64::from(time));
+    let (month, day) = ((date >> 5) & 0xF, date & 0x1F);
+    if !(1..=12).contains(&month) || day == 0 {
+        return 0;
+    }
+    let days = days_from_civil(1980 + (date >> 9), month, day);
+    days * 86400
+        + (time >> 11) * 3600
+        + ((time >> 5) & 0x3F) * 60
+        + (time & 0x1F) * 2
+        + u64::from(tenth) / 100
+}
+
+/// 自1970-01-01起的天数对应的公历日期`(年, 月, 日)`，算法出自Howard Hinnant
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // 以3月1日为一年之始，闰日便落在年末
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (era * 400 + year_of_era + u64::from(month <= 2), month, day)
+}
+
+/// [`civil_from_days`]的逆运算，月与日须有效
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let mp = (month + 9) % 12;
+    let day_of_year = (153 * mp + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
 pub fn sector_dirents() -> usize {
     sector::size() / mem::size_of::<ShortDirEntry>()
 }
@@ -1,28 +1,38 @@
+use core::cmp::Ordering;
 use core::mem;
 use core::ops::Range;
 
-use crate::volume::reserved::{self, Bpb, Media};
+use crate::volume::reserved::{self, Bpb, FatType, Media};
 use crate::{sector, SectorId};
 use crate::{ClusterError, ClusterId};
 
 /// File Allocation Table
+///
+/// 表项宽12、16或32位，读出时统一为FAT32的编号空间：
+/// 簇链表的末尾记为[`ClusterId::EOF`]，坏簇记为[`ClusterId::BAD`]，写入时再换回表项的宽度。
 #[derive(Debug)]
 pub struct Fat {
     range: Range<SectorId>,
     media: Media,
-    /// 一个扇区能容纳多少条簇编号
-    sector_cids: usize,
+    ty: FatType,
+    /// 数据区最后一个簇的下一个编号
+    end: ClusterId<u32>,
+    /// FSInfo所在扇区，仅FAT32有
+    fs_info: Option<SectorId>,
 }
 
 impl Fat {
     pub fn new(bpb: &Bpb) -> Self {
         let start = bpb.fat_area();
         let end = start + bpb.fat_sectors();
+        let ty = bpb.fat_type();
 
         Self {
             range: Range { start, end },
             media: bpb.media,
-            sector_cids: bpb.sector_bytes() / mem::size_of::<u32>(),
+            ty,
+            end: ClusterId::from(bpb.total_clusters() + usize::from(ClusterId::MIN)),
+            fs_info: (ty == FatType::T32).then(|| bpb.fs_info()),
         }
     }
 
@@ -30,13 +40,17 @@ impl Fat {
         self.range.clone()
     }
 
+    pub const fn fat_type(&self) -> FatType {
+        self.ty
+    }
+
     /// 获取下一个簇编号。
     /// 若`id`指向未分配簇，则报错。
     /// `Ok(None)`表示`id`为链表上最后一个簇。
     pub fn next(&self, id: ClusterId<u32>) -> Result<Option<ClusterId<u32>>, ClusterError> {
         let id = self.validate_id(id)?;
 
-        match self.id2pos(id).access(|next_id| next_id.validate()) {
+        match self.entry(id).validate() {
             Ok(cid) => Ok(Some(cid)),
             Err(ClusterError::Eof) => Ok(None),
             Err(e) => Err(e),
@@ -54,7 +68,7 @@ impl Fat {
         Ok(id)
     }
 
-    /// 分配根目录
+    /// 分配根目录，仅格式化FAT32卷时使用
     pub fn alloc_root(&mut self) {
         sector::get(self.range.start)
             .lock()
@@ -66,7 +80,7 @@ impl Fat {
                 cids[2] = ClusterId::EOF;
             });
 
-        reserved::record_alloc();
+        self.record_alloc(1);
     }
 
     /// 寻找未分配的簇，并将其设为`EOF`。
@@ -76,27 +90,40 @@ impl Fat {
     ///
     /// [`FatFileSystem::alloc_cluster`]: crate::FatFileSystem::alloc_cluster
     pub fn alloc(&mut self) -> Option<ClusterId<u32>> {
-        for (i, sid) in self.range.clone().enumerate() {
-            if let Some(cidx) =
-                sector::get(sid)
-                    .lock()
-                    .map_mut_slice(|clusters: &mut [ClusterId<u32>]| {
-                        clusters
-                            .iter_mut()
-                            .enumerate()
-                            .find(|(_, cid)| **cid == ClusterId::FREE)
-                            .map(|(cidx, cid)| {
-                                *cid = ClusterId::EOF;
-                                cidx
-                            })
-                    })
-            {
-                reserved::record_alloc();
-                return Some(ClusterId::from(i * self.sector_cids + cidx));
-            }
+        self.alloc_run(1)
+    }
+
+    /// 寻找`n`个编号连续的未分配簇，依次链接并将末簇设为`EOF`，返回首簇。
+    /// 没有足够长的连续空闲段时返回`None`。
+    ///
+    /// 与[`Fat::alloc`]一样不会初始化簇。
+    pub fn alloc_run(&mut self, n: usize) -> Option<ClusterId<u32>> {
+        let start = usize::from(self.find_free_run(n)?);
+        let last = start + n - 1;
+
+        for id in start..last {
+            self.set_entry(ClusterId::from(id), ClusterId::from(id + 1));
         }
+        self.set_entry(ClusterId::from(last), ClusterId::EOF);
+        self.record_alloc(n);
+
+        Some(ClusterId::from(start))
+    }
 
-        None
+    /// FAT[1]的干净关闭位，卷上次未正常卸载时为假。FAT12没有此位，总是为真
+    pub fn is_clean(&self) -> bool {
+        self.clean_bit()
+            .is_none_or(|bit| self.read_raw(Self::FLAGS_ID) & bit != 0)
+    }
+
+    /// 挂载后清除干净关闭位，卸载时再置位
+    pub fn set_clean(&mut self, clean: bool) {
+        let Some(bit) = self.clean_bit() else {
+            return;
+        };
+        let flags = self.read_raw(Self::FLAGS_ID);
+        let flags = if clean { flags | bit } else { flags & !bit };
+        self.write_raw(Self::FLAGS_ID, flags);
     }
 
     /// 以前后顺序链接两个簇，为扩展分配准备的。
@@ -105,42 +132,52 @@ impl Fat {
     ///
     /// 若`prev`不是尾簇，赋予其`next`的链接会导致链表的剩余部分丢失！
     pub unsafe fn couple(&mut self, prev: ClusterId<u32>, next: ClusterId<u32>) {
-        self.id2pos(prev).access_mut(|next_id| *next_id = next);
+        self.set_entry(prev, next);
     }
 
     /// 移除整个簇链表。
+    ///
+    /// 途经未分配、坏簇或保留的表项时报错，链表已释放的部分不会恢复。
     pub fn dealloc(&mut self, id: ClusterId<u32>) -> Result<(), ClusterError> {
-        let mut id = self.validate_id(id)?;
+        let mut id = Some(self.validate_id(id)?);
 
-        loop {
-            let is_eof = self.id2pos(id).access_mut(|next_id| {
-                id = *next_id;
-                *next_id = ClusterId::FREE;
-                id == ClusterId::EOF
-            });
-            reserved::record_free();
-            if is_eof {
-                break;
-            }
+        while let Some(cur) = id {
+            id = self.next(cur)?;
+            self.set_entry(cur, ClusterId::FREE);
+            self.record_free();
         }
 
         Ok(())
     }
+
+    /// 将未分配的簇标为坏簇，此后不会再被分配。
+    /// 簇编号无效或簇并非未分配时返回`false`。
+    pub fn mark_bad(&mut self, id: ClusterId<u32>) -> bool {
+        let Ok(id) = self.validate_id(id) else {
+            return false;
+        };
+
+        if self.entry(id) != ClusterId::FREE {
+            return false;
+        }
+        self.set_entry(id, ClusterId::BAD);
+        self.record_alloc(1);
+
+        true
+    }
 }
 
 impl Fat {
     const SET_CLN_SHUT: u32 = 0x08000000;
     const SET_HRD_ERR: u32 = 0x04000000;
-
-    /// 获取`id`所在扇区
-    fn get_sector(&self, id: ClusterId<u32>) -> SectorId {
-        let sector_index = usize::from(id) / self.sector_cids;
-        self.range.start + sector_index
-    }
+    /// FAT16的干净关闭位
+    const SET_CLN_SHUT16: u32 = 0x8000;
+    /// 标志位所在的表项
+    const FLAGS_ID: ClusterId<u32> = ClusterId::new(1);
 
     fn validate_id(&self, id: ClusterId<u32>) -> Result<ClusterId<u32>, ClusterError> {
         id.validate().and_then(|id| {
-            if self.range.contains(&self.get_sector(id)) {
+            if id < self.end {
                 Ok(id)
             } else {
                 Err(ClusterError::Reserved)
@@ -148,36 +185,183 @@ impl Fat {
         })
     }
 
-    fn id2pos(&self, id: ClusterId<u32>) -> ClusterIdPos {
-        ClusterIdPos {
-            sector: self.get_sector(id),
-            nth: u32::from(id) as usize % self.sector_cids,
+    fn clean_bit(&self) -> Option<u32> {
+        match self.ty {
+            FatType::T12 => None,
+            FatType::T16 => Some(Self::SET_CLN_SHUT16),
+            FatType::T32 => Some(Self::SET_CLN_SHUT),
         }
     }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct ClusterIdPos {
-    sector: SectorId,
-    nth: usize,
-}
+    fn record_alloc(&self, count: usize) {
+        if let Some(sid) = self.fs_info {
+            reserved::record_alloc(sid, count as u32);
+        }
+    }
 
-impl ClusterIdPos {
-    pub fn access<F, R>(&self, f: F) -> R
-    where
-        F: FnOnce(&ClusterId) -> R,
-    {
-        sector::get(self.sector)
-            .lock()
-            .map(self.nth * mem::size_of::<ClusterId>(), f)
+    fn record_free(&self) {
+        if let Some(sid) = self.fs_info {
+            reserved::record_free(sid);
+        }
     }
 
-    pub fn access_mut<F, R>(&self, f: F) -> R
-    where
-        F: FnOnce(&mut ClusterId) -> R,
-    {
-        sector::get(self.sector)
-            .lock()
-            .map_mut(self.nth * mem::size_of::<ClusterId>(), f)
+    /// 首个由`n`个编号连续的未分配簇组成的段的首簇
+    fn find_free_run(&self, n: usize) -> Option<ClusterId<u32>> {
+        debug_assert!(n > 0);
+        let min = usize::from(ClusterId::MIN);
+        let end = usize::from(self.end);
+
+        // 已数到的连续空闲簇个数，跨扇区延续
+        let mut run = 0;
+        let mut count = |id: usize, entry: ClusterId<u32>| {
+            if entry == ClusterId::FREE {
+                run += 1;
+                (run == n).then(|| ClusterId::from(id + 1 - n))
+            } else {
+                run = 0;
+                None
+            }
+        };
+
+        // FAT12的表项可能跨扇区，好在FAT12的表至多几个扇区
+        if self.ty == FatType::T12 {
+            return (min..end).find_map(|id| count(id, self.entry(ClusterId::from(id))));
+        }
+
+        // 其余的表项不跨扇区，逐扇区查找以免每个表项都访问一次扇区缓存
+        let width = self.entry_bytes();
+        let sector_ids = sector::size() / width;
+        self.range.clone().enumerate().find_map(|(i, sid)| {
+            let first = i * sector_ids;
+            sector::get(sid).lock().map_slice(|bytes: &[u8]| {
+                bytes
+                    .chunks_exact(width)
+                    .enumerate()
+                    .map(|(j, raw)| (first + j, raw))
+                    .filter(|(id, _)| (min..end).contains(id))
+                    .find_map(|(id, raw)| count(id, self.decode(le_u32(raw))))
+            })
+        })
+    }
+
+    /// 表项的字节数，FAT12按2字节读写
+    fn entry_bytes(&self) -> usize {
+        match self.ty {
+            FatType::T12 | FatType::T16 => mem::size_of::<u16>(),
+            FatType::T32 => mem::size_of::<u32>(),
+        }
+    }
+
+    /// 表项在FAT区中的字节偏移
+    fn offset(&self, id: ClusterId<u32>) -> usize {
+        let id = usize::from(id);
+        match self.ty {
+            FatType::T12 => id + id / 2,
+            FatType::T16 => id * 2,
+            FatType::T32 => id * 4,
+        }
+    }
+
+    fn entry(&self, id: ClusterId<u32>) -> ClusterId<u32> {
+        self.decode(self.read_raw(id))
+    }
+
+    fn set_entry(&mut self, id: ClusterId<u32>, next: ClusterId<u32>) {
+        let raw = match next {
+            ClusterId::EOF => self.eoc(),
+            ClusterId::BAD => self.bad(),
+            next => u32::from(next),
+        };
+        self.write_raw(id, raw);
+    }
+
+    /// 表项中链表末尾的标记，大于坏簇标记的值都表示末尾
+    fn eoc(&self) -> u32 {
+        match self.ty {
+            FatType::T12 => 0xFFF,
+            FatType::T16 => 0xFFFF,
+            FatType::T32 => 0x0FFF_FFFF,
+        }
+    }
+
+    fn bad(&self) -> u32 {
+        self.eoc() - 8
+    }
+
+    fn decode(&self, raw: u32) -> ClusterId<u32> {
+        let raw = raw & 0x0FFF_FFFF;
+        match raw.cmp(&self.bad()) {
+            Ordering::Greater => ClusterId::EOF,
+            Ordering::Equal => ClusterId::BAD,
+            Ordering::Less => ClusterId::new(raw),
+        }
+    }
+
+    /// 表项的原始值，FAT32的高4位保留
+    fn read_raw(&self, id: ClusterId<u32>) -> u32 {
+        let mut bytes = [0; mem::size_of::<u32>()];
+        self.read_bytes(self.offset(id), &mut bytes[..self.entry_bytes()]);
+        let raw = u32::from_le_bytes(bytes);
+
+        match self.ty {
+            FatType::T12 if u32::from(id) % 2 == 1 => raw >> 4,
+            FatType::T12 => raw & 0xFFF,
+            FatType::T16 => raw,
+            FatType::T32 => raw & 0x0FFF_FFFF,
+        }
+    }
+
+    /// 写入表项，保留同一字节中相邻FAT12表项的半字节与FAT32的高4位
+    fn write_raw(&mut self, id: ClusterId<u32>, raw: u32) {
+        let offset = self.offset(id);
+        let width = self.entry_bytes();
+        let mut bytes = [0; mem::size_of::<u32>()];
+        self.read_bytes(offset, &mut bytes[..width]);
+        let old = u32::from_le_bytes(bytes);
+
+        let new = match self.ty {
+            FatType::T12 if u32::from(id) % 2 == 1 => (old & 0x000F) | (raw << 4),
+            FatType::T12 => (old & 0xF000) | raw,
+            FatType::T16 => raw,
+            FatType::T32 => (old & 0xF000_0000) | raw,
+        };
+        self.write_bytes(offset, &new.to_le_bytes()[..width]);
     }
+
+    /// 自FAT区的字节偏移`offset`处读入`buf`，FAT12的表项可能跨扇区
+    fn read_bytes(&self, offset: usize, buf: &mut [u8]) {
+        let sector_size = sector::size();
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let len = (buf.len() - done).min(sector_size - pos % sector_size);
+            sector::get(self.range.start + pos / sector_size)
+                .lock()
+                .map_slice(|data: &[u8]| {
+                    buf[done..done + len].copy_from_slice(&data[pos % sector_size..][..len])
+                });
+            done += len;
+        }
+    }
+
+    fn write_bytes(&mut self, offset: usize, bytes: &[u8]) {
+        let sector_size = sector::size();
+        let mut done = 0;
+        while done < bytes.len() {
+            let pos = offset + done;
+            let len = (bytes.len() - done).min(sector_size - pos % sector_size);
+            sector::get(self.range.start + pos / sector_size)
+                .lock()
+                .map_mut_slice(|data: &mut [u8]| {
+                    data[pos % sector_size..][..len].copy_from_slice(&bytes[done..done + len])
+                });
+            done += len;
+        }
+    }
+}
+
+fn le_u32(raw: &[u8]) -> u32 {
+    let mut bytes = [0; mem::size_of::<u32>()];
+    bytes[..raw.len()].copy_from_slice(raw);
+    u32::from_le_bytes(bytes)
 }
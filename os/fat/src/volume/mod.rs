@@ -1,6 +1,8 @@
 //! 卷的布局
 //!
 //! 保留区 | FAT区 | 根目录(FAT12/16) | 数据区
+//!
+//! 各结构的磁盘布局由FAT规范规定，其定义处以编译期断言核对大小与字段偏移
 
 pub mod data;
 pub mod fat;
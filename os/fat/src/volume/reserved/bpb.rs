@@ -1,11 +1,13 @@
-use core::num::{NonZero, NonZeroU16, NonZeroU32, NonZeroU8};
+use core::mem::{self, offset_of};
+use core::num::{NonZero, NonZeroU16, NonZeroU8};
+use core::ops::Range;
 
-use crate::SectorId;
+use crate::{ClusterId, SectorId};
 
 /// BIOS Parameter Block BIOS参数块
 /// 位于保留区的第一扇区，该扇区又名启动扇区。
 #[derive(Debug, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Bpb {
     /// 跳转至启动代码的指令
     _bs_jmp_boot: [u8; 3],
@@ -25,17 +27,21 @@ pub struct Bpb {
     /// 此卷的文件分配表(FAT)数量，建议为2
     num_fats: NonZeroU8,
 
+    /// 根目录的目录项数
+    /// - FAT12/16: 常为512
     /// - FAT32: 0
-    _root_ent_cnt: u16,
+    root_ent_cnt: u16,
 
+    /// - FAT12/16: 扇区总数少于0x10000时为扇区总数，否则为0
     /// - FAT32: 0
-    _tot_sec16: u16,
+    tot_sec16: u16,
 
     /// 物理媒介的类型
     pub media: Media,
 
+    /// - FAT12/16: FAT占用扇区数
     /// - FAT32: 0
-    _fat_sz16: u16,
+    fat_sz16: u16,
 
     /// 中断0x13模式下，轨道的扇区数
     _sec_per_trk: u16,
@@ -46,14 +52,17 @@ pub struct Bpb {
     /// 中断0x13模式下使用
     _hidd_sec: u32,
 
+    /// - FAT12/16: `tot_sec16`为0时为扇区总数，否则为0
     /// - FAT32: 此卷的扇区总数
-    tot_sec32: NonZeroU32,
+    tot_sec32: u32,
 
     /*
      * Extended BPB fields for FAT32 volume
+     *
+     * FAT12/16的扩展字段布局不同，以下字段对其无意义，也不应读取
      */
     /// FAT占用扇区数
-    fat_sz32: NonZeroU32,
+    fat_sz32: u32,
 
     _ext_flags: ExtFlags,
 
@@ -62,7 +71,7 @@ pub struct Bpb {
 
     /// 根目录首个簇的编号，
     /// 应该为2，或首个可用的簇编号
-    _root_clus: u32,
+    root_clus: u32,
 
     /// FSINFO所在扇区号（此扇区位于保留区），通常为1
     fs_info: u16,
@@ -77,8 +86,8 @@ pub struct Bpb {
 
     _reserved1: [u8; 1],
 
-    /// 启用时，表示接下来的三个字段存在
-    _boot_sig: BootSignature,
+    /// 启用时([`BootSignature::Set`])，表示接下来的三个字段存在
+    _boot_sig: u8,
 
     /// 供移动介质使用
     _voll_d: u32,
@@ -100,6 +109,23 @@ pub struct Bpb {
 
 /* 扇区剩余部分皆填0x00 */
 
+const _: () = {
+    assert!(mem::size_of::<Bpb>() == 512);
+    assert!(offset_of!(Bpb, byts_per_sec) == 11);
+    assert!(offset_of!(Bpb, sec_per_clus) == 13);
+    assert!(offset_of!(Bpb, rsvd_sec_cnt) == 14);
+    assert!(offset_of!(Bpb, num_fats) == 16);
+    assert!(offset_of!(Bpb, media) == 21);
+    assert!(offset_of!(Bpb, tot_sec32) == 32);
+    assert!(offset_of!(Bpb, fat_sz32) == 36);
+    assert!(offset_of!(Bpb, root_clus) == 44);
+    assert!(offset_of!(Bpb, fs_info) == 48);
+    assert!(offset_of!(Bpb, bk_boot_sec) == 50);
+    assert!(offset_of!(Bpb, _boot_sig) == 66);
+    assert!(offset_of!(Bpb, _fil_sys_type) == 82);
+    assert!(offset_of!(Bpb, _signature_word) == 510);
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u16)]
 pub enum SectorBytes {
@@ -130,6 +156,7 @@ pub enum Media {
     Removable = 0xF0,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum BootSignature {
@@ -188,12 +215,13 @@ static DS2SPC: DiskSz2SecPerClus = DiskSz2SecPerClus {
 
 impl Bpb {
     pub fn new(disk_size: usize) -> Self {
-        let sec_per_clus = DS2SPC.get(disk_size);
-        let num_fats = unsafe { NonZero::new_unchecked(2) };
-
         let byts_per_sec = SectorBytes::B512;
         let tot_sec32 = disk_size / byts_per_sec as usize;
 
+        // NOTE: 规范中的查找表与FAT大小的算法都以扇区数计磁盘大小
+        let sec_per_clus = DS2SPC.get(tot_sec32);
+        let num_fats = unsafe { NonZero::new_unchecked(2) };
+
         let mut bpb = Self {
             _bs_jmp_boot: Default::default(),
             _bs_oem_name: *b"rCore   ",
@@ -201,24 +229,26 @@ impl Bpb {
             sec_per_clus,
             rsvd_sec_cnt: unsafe { NonZero::new_unchecked(8) },
             num_fats,
-            _root_ent_cnt: Default::default(),
-            _tot_sec16: Default::default(),
+            root_ent_cnt: Default::default(),
+            tot_sec16: Default::default(),
             media: Media::Fixed,
-            _fat_sz16: Default::default(),
+            fat_sz16: Default::default(),
             _sec_per_trk: Default::default(),
             _num_heads: Default::default(),
             _hidd_sec: Default::default(),
-            tot_sec32: NonZero::new(tot_sec32 as u32).expect("Disk size should be enough"),
-            fat_sz32: unsafe { NonZero::new_unchecked(1) }, // 仅仅是用来占位
+            tot_sec32: NonZero::new(tot_sec32 as u32)
+                .expect("Disk size should be enough")
+                .get(),
+            fat_sz32: 1, // 仅仅是用来占位
             _ext_flags: Default::default(),
             _fs_ver: 0x0,
-            _root_clus: 2,
+            root_clus: 2,
             fs_info: 1,
             bk_boot_sec: 6,
             _reserved: Default::default(),
             _drv_num: Default::default(),
             _reserved1: Default::default(),
-            _boot_sig: BootSignature::Unset,
+            _boot_sig: BootSignature::Unset as u8,
             _voll_d: Default::default(),
             _voll_lab: *b"NO NAME    ",
             _fil_sys_type: *b"FAT32   ",
@@ -226,7 +256,7 @@ impl Bpb {
             _signature_word: [0x55, 0xAA],
         };
 
-        bpb.set_fat_size(FatType::T32, disk_size);
+        bpb.set_fat_size(FatType::T32, tot_sec32);
 
         bpb
     }
@@ -263,37 +293,38 @@ impl Bpb {
 
     /// FAT占用的扇区数
     pub const fn fat_sectors(&self) -> usize {
-        if self._fat_sz16 > 0 {
-            self._fat_sz16 as usize
+        if self.fat_sz16 > 0 {
+            self.fat_sz16 as usize
         } else {
-            self.fat_sz32.get() as usize
+            self.fat_sz32 as usize
         }
     }
 
     pub const fn total_sectors(&self) -> usize {
-        if self._tot_sec16 > 0 {
-            self._tot_sec16 as usize
+        if self.tot_sec16 > 0 {
+            self.tot_sec16 as usize
         } else {
-            self.tot_sec32.get() as usize
+            self.tot_sec32 as usize
         }
     }
 
     pub fn total_clusters(&self) -> usize {
         (self.total_sectors() - usize::from(self.data_area())) / self.sec_per_clus as usize
     }
-}
 
-impl Bpb {
-    /// 计算根目录占用的扇区数
-    ///
-    /// - FAT32: 0
-    const fn root_dir_sectors(&self) -> usize {
-        ((self._root_ent_cnt * 32 + (self.byts_per_sec as u16 - 1)) / self.byts_per_sec as u16)
-            as usize
+    /// FAT12/16根目录所在的定长区域，紧随FAT区之后；FAT32为空
+    pub fn root_dir_area(&self) -> Range<SectorId> {
+        let start = self.fat_area() + self.num_fats.get() as usize * self.fat_sectors();
+        start..start + self.root_dir_sectors()
+    }
+
+    /// FAT32根目录的首簇
+    pub fn root_cluster(&self) -> ClusterId<u32> {
+        ClusterId::new(self.root_clus)
     }
 
-    #[allow(dead_code)]
-    fn fat_type(&self) -> FatType {
+    /// 按规范，仅由数据区的簇数判定FAT类型，而非`_fil_sys_type`
+    pub fn fat_type(&self) -> FatType {
         let clusters = self.total_clusters();
 
         if clusters <= 4084 {
@@ -304,8 +335,17 @@ impl Bpb {
             FatType::T32
         }
     }
+}
+
+impl Bpb {
+    /// 计算根目录占用的扇区数
+    ///
+    /// - FAT32: 0
+    const fn root_dir_sectors(&self) -> usize {
+        (self.root_ent_cnt as usize * 32).div_ceil(self.byts_per_sec as usize)
+    }
 
-    /// 计算FAT占用扇区数并设置
+    /// 计算FAT占用扇区数并设置，`disk_size`为磁盘的扇区数
     fn set_fat_size(&mut self, ty: FatType, disk_size: usize) {
         let tmp1 = disk_size - (self.rsvd_sec_cnt.get() as usize + self.root_dir_sectors());
         let mut tmp2 = 256 * self.sec_per_clus as usize + self.num_fats.get() as usize;
@@ -316,10 +356,10 @@ impl Bpb {
         let fat_size = (tmp1 + tmp2 - 1) / tmp2;
 
         if ty == FatType::T32 {
-            self._fat_sz16 = 0;
-            self.fat_sz32 = (fat_size as u32).try_into().unwrap();
+            self.fat_sz16 = 0;
+            self.fat_sz32 = fat_size as u32;
         } else {
-            self._fat_sz16 = fat_size as u16;
+            self.fat_sz16 = fat_size as u16;
         }
     }
 }
@@ -1,3 +1,5 @@
+use core::mem::{self, offset_of};
+
 use crate::volume::reserved::Bpb;
 use crate::{sector, SectorId};
 
@@ -7,7 +9,7 @@ use crate::{sector, SectorId};
 /// 位于#1扇区，备份于#7扇区，
 /// 保存着空闲簇的信息，需要持续维护。
 #[derive(Debug, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct FsInfo {
     /// 头签名 0x41615252
     lead_sig: u32,
@@ -31,6 +33,15 @@ pub struct FsInfo {
     trail_sig: u32,
 }
 
+const _: () = {
+    assert!(mem::size_of::<FsInfo>() == 512);
+    assert!(offset_of!(FsInfo, lead_sig) == 0);
+    assert!(offset_of!(FsInfo, struc_sig) == 484);
+    assert!(offset_of!(FsInfo, free_count) == 488);
+    assert!(offset_of!(FsInfo, _nxt_free) == 492);
+    assert!(offset_of!(FsInfo, trail_sig) == 508);
+};
+
 impl FsInfo {
     pub fn new(bpb: &Bpb) -> Self {
         Self {
@@ -56,18 +67,25 @@ pub fn free_count() {
         .map(0, |fs_info: &FsInfo| fs_info.free_count);
 }
 
-pub fn record_alloc() {
-    sector::get(SectorId::new(1))
+/// 将主FSInfo复制到#7扇区的备份
+pub fn write_backup() {
+    let fs_info = sector::get(SectorId::new(1))
+        .lock()
+        .map(0, |fs_info: &FsInfo| fs_info.clone());
+    sector::get(SectorId::new(7))
         .lock()
-        .map_mut(0, |fs_info: &mut FsInfo| {
-            fs_info.free_count = fs_info.free_count.saturating_sub(1);
-        });
+        .map_mut(0, |backup: &mut FsInfo| *backup = fs_info);
 }
 
-pub fn record_free() {
-    sector::get(SectorId::new(1))
-        .lock()
-        .map_mut(0, |fs_info: &mut FsInfo| {
-            fs_info.free_count += 1;
-        });
+/// `sid`为FSInfo所在扇区，`count`为新分配的簇数
+pub fn record_alloc(sid: SectorId, count: u32) {
+    sector::get(sid).lock().map_mut(0, |fs_info: &mut FsInfo| {
+        fs_info.free_count = fs_info.free_count.saturating_sub(count);
+    });
+}
+
+pub fn record_free(sid: SectorId) {
+    sector::get(sid).lock().map_mut(0, |fs_info: &mut FsInfo| {
+        fs_info.free_count += 1;
+    });
 }
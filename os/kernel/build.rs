@@ -1,6 +1,93 @@
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
 const TARGET_PATH: &str = "../../user/target/riscv64gc-unknown-none-elf/release/";
 
+/// 未指定`KERNEL_PROFILE`时使用的配置
+const DEFAULT_PROFILE: &str = "qemu-small";
+
+/// 每个配置文件都必须给出的项
+const REQUIRED: &[&str] = &[
+    "CLOCK_FREQ",
+    "MEMORY_END",
+    "USER_STACK_SIZE",
+    "KERNEL_STACK_SIZE",
+    "KERNEL_HEAP_SIZE",
+    "BLOCK_CACHE_CAPACITY",
+    "FILE_MAX",
+];
+
 fn main() {
     println!("cargo:rerun-if-changed=../../user/src/");
     println!("cargo:rerun-if-changed={TARGET_PATH}");
+
+    generate_config();
+}
+
+/// 读取`configs/<KERNEL_PROFILE>.cfg`，在`OUT_DIR`下生成`config.rs`
+fn generate_config() {
+    println!("cargo:rerun-if-env-changed=KERNEL_PROFILE");
+    println!("cargo:rerun-if-changed=configs/");
+
+    let profile = env::var("KERNEL_PROFILE").unwrap_or_else(|_| DEFAULT_PROFILE.to_owned());
+    let path = format!("configs/{profile}.cfg");
+    let source = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("failed to read kernel profile {path}: {e}"));
+
+    let mut generated = format!("/// 构建所用的配置\npub const PROFILE: &str = {profile:?};\n");
+    let mut defined = Vec::new();
+    let mut doc = Vec::new();
+
+    for (lineno, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            doc.clear();
+            continue;
+        }
+        if let Some(comment) = line.strip_prefix('#') {
+            doc.push(comment.trim().to_owned());
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once('=')
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .unwrap_or_else(|| panic!("{path}:{}: expected `NAME = value`", lineno + 1));
+        if !REQUIRED.contains(&name) {
+            panic!("{path}:{}: unknown config `{name}`", lineno + 1);
+        }
+        if defined.contains(&name) {
+            panic!("{path}:{}: `{name}` is defined twice", lineno + 1);
+        }
+        let value = parse_usize(value)
+            .unwrap_or_else(|| panic!("{path}:{}: invalid integer `{value}`", lineno + 1));
+
+        generated.push('\n');
+        for line in doc.drain(..) {
+            writeln!(generated, "/// {line}").unwrap();
+        }
+        writeln!(generated, "pub const {name}: usize = {value:#x};").unwrap();
+        defined.push(name);
+    }
+
+    let missing: Vec<_> = REQUIRED
+        .iter()
+        .filter(|name| !defined.contains(name))
+        .collect();
+    if !missing.is_empty() {
+        panic!("{path}: missing {missing:?}");
+    }
+
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("config.rs");
+    fs::write(out, generated).unwrap();
+}
+
+fn parse_usize(value: &str) -> Option<usize> {
+    let value = value.replace('_', "");
+    match value.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
 }
@@ -3,10 +3,8 @@
 pub use self::virt::*;
 pub use crate::drivers::{init_device, irq_handler};
 
-pub const CLOCK_FREQ: usize = 10_000_000; // Hz
-
-/// 物理地址起始于`0x8000_0000`，我们现在有100M内存
-pub const MEMORY_END: usize = 0x8100_0000;
+/// 板上CPU可能实现了向量扩展(QEMU需以`-cpu rv64,v=true`启动)，实际是否实现仍以探测为准
+pub const VECTOR_EXTENSION: bool = true;
 
 /// [virtio 常量](https://github.com/qemu/qemu/blob/master/include/hw/riscv/virt.h)
 #[allow(dead_code)]
@@ -65,7 +63,7 @@ mod virt {
     }
 
     /// 见头文件里的IRQ枚举
-    #[derive(Debug, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct IrqId(pub u32);
 
     impl IrqId {
@@ -75,13 +73,13 @@ mod virt {
         pub const BLOCK: IrqId = Self(8);
         pub const SERIAL: IrqId = Self(10);
 
+        /// virtio-mmio 槽位的中断号范围
+        pub const VIRTIO_FIRST: IrqId = Self(1);
+        pub const VIRTIO_LAST: IrqId = Self(8);
+
         pub const fn virtio_mmio_addr(&self) -> usize {
-            assert!(1 <= self.0 && self.0 <= 8);
+            assert!(Self::VIRTIO_FIRST.0 <= self.0 && self.0 <= Self::VIRTIO_LAST.0);
             MemMapEntity::VIRTIO.addr - 0x1000 + self.0 as usize * 0x1000
         }
     }
-
-    pub fn irq_ids() -> impl Iterator<Item = IrqId> {
-        [IrqId::KEYBOARD, IrqId::MOUSE, IrqId::BLOCK, IrqId::SERIAL].into_iter()
-    }
 }
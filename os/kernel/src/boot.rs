@@ -0,0 +1,158 @@
+//! 启动各阶段的计时
+//!
+//! `rust_main`依次计量各[`Phase`]，阶段内可再以[`span`]计量子阶段，如各设备的探测。
+//! 启动完成时输出一行报告，顶层阶段的耗时还可经`SYSINFO`查询，
+//! 便于发现新加入的子系统拖慢了启动。
+//!
+//! 计时始于分页开启之前，此时堆尚不可用，故记录存于定长数组。
+
+use core::fmt;
+
+use crate::sync::UpCell;
+use crate::timer;
+
+/// 至多记录的阶段数，超出的子阶段只计时不记录
+const MAX_SPANS: usize = 32;
+
+static TIMES: UpCell<BootTimes> = UpCell::new(BootTimes::new());
+
+/// 启动的顶层阶段，按执行顺序排列
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    /// 从设备树中取出命令行
+    Cmdline,
+    /// 初始化堆、帧分配器并开启分页
+    Paging,
+    /// 注册并探测设备
+    Drivers,
+    /// 挂载根文件系统
+    Mount,
+    /// 设置Trap入口、时钟与外部中断
+    Trap,
+    /// 加载始祖进程
+    Initproc,
+}
+
+impl Phase {
+    pub const COUNT: usize = 6;
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Cmdline => "cmdline",
+            Self::Paging => "paging",
+            Self::Drivers => "drivers",
+            Self::Mount => "mount",
+            Self::Trap => "trap",
+            Self::Initproc => "initproc",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    name: &'static str,
+    depth: usize,
+    us: usize,
+}
+
+#[derive(Debug)]
+struct BootTimes {
+    /// 按开始的先后排列，即阶段树的先序遍历
+    spans: [Span; MAX_SPANS],
+    len: usize,
+    /// 正在计时的阶段的嵌套深度
+    depth: usize,
+    phases: [usize; Phase::COUNT],
+}
+
+impl BootTimes {
+    const fn new() -> Self {
+        Self {
+            spans: [Span {
+                name: "",
+                depth: 0,
+                us: 0,
+            }; MAX_SPANS],
+            len: 0,
+            depth: 0,
+            phases: [0; Phase::COUNT],
+        }
+    }
+}
+
+/// 计量顶层阶段`phase`
+pub fn phase<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    let (ret, us) = measure(phase.name(), f);
+    TIMES.exclusive_access().phases[phase as usize] = us;
+    ret
+}
+
+/// 计量当前阶段中名为`name`的子阶段
+pub fn span<T>(name: &'static str, f: impl FnOnce() -> T) -> T {
+    measure(name, f).0
+}
+
+fn measure<T>(name: &'static str, f: impl FnOnce() -> T) -> (T, usize) {
+    // 先占槽位，保证父阶段排在子阶段之前
+    let slot = TIMES.exclusive_session(|times| {
+        let slot = (times.len < MAX_SPANS).then(|| {
+            times.spans[times.len] = Span {
+                name,
+                depth: times.depth,
+                us: 0,
+            };
+            times.len += 1;
+            times.len - 1
+        });
+        times.depth += 1;
+        slot
+    });
+
+    let start = timer::get_time_us();
+    let ret = f();
+    let us = timer::get_time_us() - start;
+
+    TIMES.exclusive_session(|times| {
+        times.depth -= 1;
+        if let Some(slot) = slot {
+            times.spans[slot].us = us;
+        }
+    });
+    (ret, us)
+}
+
+/// 顶层各阶段的耗时，以微秒计，按[`Phase`]的顺序排列
+pub fn phases_us() -> [usize; Phase::COUNT] {
+    TIMES.exclusive_access().phases
+}
+
+/// 输出启动报告，子阶段列于父阶段后的括号中
+pub fn report() {
+    log::info!("{}", *TIMES.exclusive_access());
+}
+
+impl fmt::Display for BootTimes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "boot {}us:", self.phases.iter().sum::<usize>())?;
+        let mut depth = 0;
+        for (i, span) in self.spans[..self.len].iter().enumerate() {
+            if span.depth > depth {
+                f.write_str(" (")?;
+            } else {
+                for _ in span.depth..depth {
+                    f.write_str(")")?;
+                }
+                if i > 0 {
+                    f.write_str(",")?;
+                }
+                f.write_str(" ")?;
+            }
+            depth = span.depth;
+            write!(f, "{} {}us", span.name, span.us)?;
+        }
+        for _ in 0..depth {
+            f.write_str(")")?;
+        }
+        Ok(())
+    }
+}
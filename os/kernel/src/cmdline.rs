@@ -0,0 +1,97 @@
+//! 内核命令行
+//!
+//! SBI 进入内核时，a1 中是设备树(FDT)的物理地址，命令行即其中`/chosen`节点的`bootargs`属性，
+//! 由 QEMU 的`-append`参数给出。命令行由空白分隔的`键=值`或`键`组成。
+//!
+//! 设备树所在的内存随后会交给帧分配器，须在初始化内存之前将命令行复制出来。
+
+use core::str;
+
+use spin::Once;
+
+/// 命令行的最大长度，超出部分被截断
+const CAP: usize = 512;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+static CMDLINE: Once<([u8; CAP], usize)> = Once::new();
+
+/// 从位于`dtb`的设备树中取出命令行，须在分页开启前调用
+pub fn init(dtb: usize) {
+    let mut buf = [0; CAP];
+    let len = unsafe { bootargs(dtb) }.map_or(0, |args| {
+        let len = args.len().min(CAP);
+        buf[..len].copy_from_slice(&args[..len]);
+        len
+    });
+    CMDLINE.call_once(|| (buf, len));
+}
+
+/// 整条命令行
+pub fn cmdline() -> &'static str {
+    CMDLINE
+        .get()
+        .and_then(|(buf, len)| str::from_utf8(&buf[..*len]).ok())
+        .unwrap_or("")
+}
+
+/// 键为`key`的参数的值，只有键时值为空串
+pub fn get(key: &str) -> Option<&'static str> {
+    cmdline().split_whitespace().find_map(|arg| {
+        let (k, v) = arg.split_once('=').unwrap_or((arg, ""));
+        (k == key).then_some(v)
+    })
+}
+
+/// 在设备树的结构块中找到`/chosen`节点的`bootargs`属性，不含结尾的\0
+unsafe fn bootargs(dtb: usize) -> Option<&'static [u8]> {
+    let be32 = |addr: usize| u32::from_be(unsafe { (addr as *const u32).read_unaligned() });
+
+    if dtb == 0 || be32(dtb) != FDT_MAGIC {
+        return None;
+    }
+    let total = be32(dtb + 4) as usize;
+    let strings = dtb + be32(dtb + 12) as usize;
+    let mut p = dtb + be32(dtb + 8) as usize;
+
+    let cstr = |addr: usize| unsafe { core::ffi::CStr::from_ptr(addr as *const _) }.to_bytes();
+    // 根节点深度为1，`/chosen`为2
+    let mut depth = 0;
+    let mut in_chosen = false;
+
+    while p < dtb + total {
+        let token = be32(p);
+        p += 4;
+        match token {
+            FDT_BEGIN_NODE => {
+                let name = cstr(p);
+                p += (name.len() + 1).next_multiple_of(4);
+                depth += 1;
+                in_chosen = depth == 2 && name == b"chosen";
+            }
+            FDT_END_NODE => {
+                depth -= 1;
+                in_chosen = false;
+            }
+            FDT_PROP => {
+                let len = be32(p) as usize;
+                let name = cstr(strings + be32(p + 4) as usize);
+                p += 8;
+                if in_chosen && name == b"bootargs" {
+                    let value = unsafe { core::slice::from_raw_parts(p as *const u8, len) };
+                    return Some(value.strip_suffix(&[0]).unwrap_or(value));
+                }
+                p += len.next_multiple_of(4);
+            }
+            FDT_NOP => {}
+            // FDT_END或无法识别的标记
+            _ => break,
+        }
+    }
+
+    None
+}
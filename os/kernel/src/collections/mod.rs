@@ -12,7 +12,7 @@ impl<T> Default for SlotVec<T> {
 }
 
 impl<T> SlotVec<T> {
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Self(Vec::new())
     }
 
@@ -43,6 +43,15 @@ impl<T> SlotVec<T> {
         self.0[index].take()
     }
 
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.0.get_mut(index).and_then(Option::as_mut)
+    }
+
+    /// 遍历所有非空槽位
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.0.iter_mut().flatten()
+    }
+
     pub fn clear(&mut self) {
         self.0.clear()
     }
@@ -1,16 +1,28 @@
 //! Constants used in rCore
+//!
+//! 随板卡与内存规模变化的常量由`build.rs`依据`configs/<KERNEL_PROFILE>.cfg`生成，
+//! 其余与架构相关的常量定义于此。
 
-pub use crate::board::{CLOCK_FREQ, MEMORY_END};
-
-pub const USER_STACK_SIZE: usize = 4096;
-pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
-pub const KERNEL_HEAP_SIZE: usize = 0x300000;
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
 
 /// 物理页大小，十六进制表示方便地址转页号的计算
 pub const PAGE_SIZE: usize = 0x1000;
 /// 物理页内寻址的位数
 pub const PAGE_SIZE_BITS: usize = 12;
 
+/// 每个进程打开的文件描述符个数的默认上限
+pub const NOFILE: usize = 64;
+
+/// exec参数的个数上限
+pub const MAX_ARG_STRINGS: usize = 32;
+/// exec单个参数的字节数上限(含终止符)
+pub const MAX_ARG_STRLEN: usize = 256;
+/// exec参数连同其指针所占的总字节数上限，它们都要压入用户栈
+pub const ARG_MAX: usize = USER_STACK_SIZE / 2;
+
+/// 经`fcntl`可设置的管道容量上限，同Linux的`pipe-max-size`默认值
+pub const PIPE_MAX_SIZE: usize = 1 << 20;
+
 /// 跳板地址
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 /// Trap上下文地址的计算起点
@@ -21,7 +33,7 @@ pub const FRAMEBUFFER_VA: usize = 0x1000_0000;
 
 pub static IMG_MOUSE: &[u8] = include_bytes!("../assets/mouse.bmp");
 
-/*
-* /// mmap距离堆底的偏移量，8G
-* pub const MMAP_OFFSET_FROM: usize = 8 * 2usize.pow(30);
-*/
+/// 用户地址空间的上界(SV39的低半部分)，跳板与Trap上下文均在其上
+pub const USER_SPACE_END: usize = 1 << 38;
+/// mmap在此之上寻找空闲的虚拟地址，远离自ELF之后向上排布的用户栈
+pub const MMAP_BASE: usize = 1 << 37;
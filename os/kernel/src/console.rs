@@ -1,13 +1,30 @@
-use crate::sbi::console_putchar;
+//! # 控制台输出
+//!
+//! 格式化直接写往SBI控制台，不经缓冲区，任何路径都不分配内存。
+//!
+//! 普通输出持有控制台锁，使各条消息不相交错。
+//! 锁只有限地自旋：持锁者被中断而中断处理又要输出时，等不到锁就径直写出，宁可交错也不死锁。
+//! panic等紧急场合用[`emergency_print`]，完全不碰锁。
+
 use core::fmt;
 use core::fmt::Write;
+use core::hint;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::sbi::console_putchar;
+
+/// 等待控制台锁的最大自旋次数
+const SPIN_LIMIT: usize = 1 << 16;
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
 
 struct Stdout;
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        for c in s.chars() {
-            console_putchar(c as usize);
+        // SBI逐字节输出，多字节的UTF-8字符须拆开
+        for &byte in s.as_bytes() {
+            console_putchar(byte as usize);
         }
 
         Ok(())
@@ -15,7 +32,44 @@ impl Write for Stdout {
 }
 
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap()
+    let locked = lock();
+    // 格式化出错只会少输出一部分，不值得为此panic
+    let _ = Stdout.write_fmt(args);
+    unlock(locked);
+}
+
+/// 原样输出字节，不必是合法的UTF-8
+pub fn write_bytes(bytes: &[u8]) {
+    let locked = lock();
+    for &byte in bytes {
+        console_putchar(byte as usize);
+    }
+    unlock(locked);
+}
+
+/// 不取锁地输出，供panic等无法保证锁状态的场合使用
+pub fn emergency_print(args: fmt::Arguments) {
+    let _ = Stdout.write_fmt(args);
+}
+
+/// 尝试取得控制台锁，返回是否取得
+fn lock() -> bool {
+    for _ in 0..SPIN_LIMIT {
+        if LOCKED
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return true;
+        }
+        hint::spin_loop();
+    }
+    false
+}
+
+fn unlock(locked: bool) {
+    if locked {
+        LOCKED.store(false, Ordering::Release);
+    }
 }
 
 #[macro_export]
@@ -31,3 +85,11 @@ macro_rules! println {
         $crate::console::print(format_args_nl!($($arg)*))
     };
 }
+
+/// 不取控制台锁的[`println`]
+#[macro_export]
+macro_rules! emergency_println {
+    ($($arg:tt)*) => {
+        $crate::console::emergency_print(format_args_nl!($($arg)*))
+    };
+}
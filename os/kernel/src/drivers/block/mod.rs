@@ -1,10 +1,13 @@
 mod virtio_blk;
 
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
 use block_dev::BlockDevice;
 use spin::Lazy;
 
+use super::DeviceCell;
+use crate::board::IrqId;
 use crate::sync::UpCell;
 
 use self::virtio_blk::VirtIOBlock;
@@ -14,7 +17,43 @@ use self::virtio_blk::VirtIOBlock;
 /// 所以必须通过轮询加载始祖进程，尔后才能利用中断IO
 pub static DEV_IO_MODE: UpCell<IOMode> = UpCell::new(IOMode::Poll);
 
-pub static BLOCK_DEVICE: Lazy<Arc<dyn BlockDevice>> = Lazy::new(|| Arc::new(VirtIOBlock::new()));
+/// 根文件系统所在的块设备，即`/dev/block0`
+pub static BLOCK_DEVICE: DeviceCell<Arc<dyn BlockDevice>> = DeviceCell::new("block0", || {
+    let device: Arc<dyn BlockDevice> = Arc::new(VirtIOBlock::new(IrqId::BLOCK));
+    #[cfg(feature = "crypt")]
+    let device = encrypted(device);
+    device
+});
+
+/// 其余 virtio-mmio 槽位上的块设备，按槽位顺序编号为`/dev/block1`、`/dev/block2`……
+pub static SECONDARY_BLOCK_DEVICES: Lazy<Vec<(IrqId, Arc<dyn BlockDevice>)>> = Lazy::new(|| {
+    (IrqId::VIRTIO_FIRST.0..=IrqId::VIRTIO_LAST.0)
+        .map(IrqId)
+        .filter(|irq| *irq != IrqId::BLOCK && VirtIOBlock::probe(irq))
+        .map(|irq| (irq, Arc::new(VirtIOBlock::new(irq)) as Arc<dyn BlockDevice>))
+        .collect()
+});
+
+/// 按设备名查找块设备，即`block0`、`block1`……
+pub fn find_block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    match name.strip_prefix("block")?.parse::<usize>().ok()? {
+        0 => Some(BLOCK_DEVICE.clone()),
+        n => SECONDARY_BLOCK_DEVICES
+            .get(n - 1)
+            .map(|(_, device)| device.clone()),
+    }
+}
+
+/// 以命令行中`blkkey=`给出的十六进制密钥包装为加密块设备
+#[cfg(feature = "crypt")]
+fn encrypted(device: Arc<dyn BlockDevice>) -> Arc<dyn BlockDevice> {
+    use block_dev::crypt::{self, XtsBlockDevice};
+
+    let key = crate::cmdline::get("blkkey")
+        .expect("root block device is encrypted but no `blkkey=` on the command line");
+    let key = crypt::parse_key(key).expect("`blkkey=` must be 64 hex digits");
+    Arc::new(XtsBlockDevice::new(device, &key))
+}
 
 /// IO方式
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -1,24 +1,92 @@
+use alloc::boxed::Box;
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::hint;
 
-use block_dev::BlockDevice;
-use virtio_drivers::{BlkResp, RespStatus, VirtIOBlk, VirtIOHeader};
+use block_dev::{BlockDevice, BlockOp, BlockRequest, Completion};
+use virtio_drivers::{DeviceType, VirtIOHeader};
+use virtqueue::blk::{BlkReq, RespStatus};
+use virtqueue::{VirtQueue, PAGE_SIZE};
 
 use super::{IOMode, DEV_IO_MODE};
 use crate::board::IrqId;
 use crate::drivers::bus::VirtioHal;
 use crate::sync::{Condvar, UpCell};
-use crate::task::processor;
+use crate::task::processor::{self, HART_COUNT};
 
+/// virtio-mmio 寄存器中设备配置空间的偏移
+const CONFIG_SPACE_OFFSET: usize = 0x100;
+/// 配置空间中`num_queues`字段的偏移
+const NUM_QUEUES_OFFSET: usize = 34;
+/// 设备可提供多个请求队列，其数目见配置空间的`num_queues`
+const VIRTIO_BLK_F_MQ: u64 = 1 << 12;
+
+/// 请求队列的描述符数，一条描述符链至多覆盖其中除去头部与状态的块数
+const QUEUE_SIZE: u16 = 64;
+
+/// 多队列 virtio 块设备。
+///
+/// 每个处理器核向各自的提交队列发出请求，队列间互不争用。
+/// virtio-mmio 传输层的所有队列共用同一条中断线，
+/// 因此中断到来时需逐个检查各队列的完成情况。
 pub struct VirtIOBlock {
-    base: UpCell<VirtIOBlk<'static, VirtioHal>>,
+    header: Header,
+    queues: Vec<BlkQueue>,
+    /// 设备容量，以512字节的扇区计
+    capacity: usize,
+}
+
+/// 提交队列，各自持有独立的锁与等待请求完成的条件变量
+struct BlkQueue {
+    base: UpCell<Device>,
     condvars: BTreeMap<u16, Condvar>,
+    /// 经`submit`提交而尚未完成的异步请求，以描述符链的令牌为键
+    inflight: UpCell<BTreeMap<u16, InFlight>>,
+    /// 有请求完成时唤醒`wait_until`与`with_descriptors`的所有等待者
+    completed: Condvar,
+}
+
+/// 设备的寄存器，由各队列共用
+type Header = Arc<UpCell<&'static mut VirtIOHeader>>;
+
+/// 设备上的一个请求队列
+struct Device {
+    header: Header,
+    /// 队列在设备上的编号
+    index: u32,
+    queue: VirtQueue<VirtioHal>,
+}
+
+impl Device {
+    /// 告知设备队列中有新的请求
+    fn notify(&self) {
+        self.header.exclusive_access().notify(self.index);
+    }
+}
+
+/// 设备完成前，请求的缓冲区与头部、状态须保持有效，故由队列持有
+struct InFlight {
+    request: BlockRequest,
+    req: Box<BlkReq>,
+    done: Completion,
 }
 
 impl core::fmt::Debug for VirtIOBlock {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("VirtIOBlock")
+            .field("queues", &self.queues)
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl core::fmt::Debug for BlkQueue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("BlkQueue")
             .field("base", &"Virtio HAL")
             .field("condvars", &self.condvars)
+            .field("inflight", &self.inflight.exclusive_access().len())
             .finish()
     }
 }
@@ -34,67 +102,267 @@ impl core::fmt::Debug for VirtIOBlock {
 
 impl BlockDevice for VirtIOBlock {
     fn read_block(&self, block_id: usize, buf: &mut [u8]) {
-        match *DEV_IO_MODE.exclusive_access() {
+        self.queue().read_block(block_id, buf);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.queue().write_block(block_id, buf);
+    }
+
+    // 整批缓冲区串成一条描述符链，作为一个请求提交
+    fn read_blocks(&self, block_id: usize, bufs: &mut [&mut [u8]]) {
+        self.queue().read_blocks(block_id, bufs);
+    }
+
+    fn write_blocks(&self, block_id: usize, bufs: &[&[u8]]) {
+        self.queue().write_blocks(block_id, bufs);
+    }
+
+    fn submit(&self, request: BlockRequest, done: Completion) {
+        self.queue().submit(request, done);
+    }
+
+    // 请求提交到了当前处理器核的队列，也就在此等待
+    fn wait_until(&self, done: &dyn Fn() -> bool) {
+        self.queue().wait_until(done);
+    }
+
+    // 初始化时不协商`VIRTIO_BLK_F_FLUSH`，按规范设备此时须直写，
+    // 请求完成即已落盘，而同步的写请求都等到完成才返回，故无事可做
+    fn flush(&self) {}
+
+    fn handle_irq(&self) {
+        self.header.exclusive_access().ack_interrupt();
+        for queue in &self.queues {
+            queue.handle_irq();
+        }
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl VirtIOBlock {
+    /// 驱动`irq`所对应 virtio-mmio 槽位上的块设备
+    ///
+    /// 只协商`VIRTIO_BLK_F_MQ`特性。设备提供多个队列时每个处理器核一个，至多用到设备的上限；
+    /// 否则只有一个队列，由所有处理器核共享
+    pub fn new(irq: IrqId) -> Self {
+        let header = unsafe { &mut *(irq.virtio_mmio_addr() as *mut VirtIOHeader) };
+        let config = irq.virtio_mmio_addr() + CONFIG_SPACE_OFFSET;
+
+        let mut multi_queue = false;
+        header.begin_init(|features| {
+            multi_queue = features & VIRTIO_BLK_F_MQ != 0;
+            features & VIRTIO_BLK_F_MQ
+        });
+        let num_queues = if multi_queue {
+            unsafe { ((config + NUM_QUEUES_OFFSET) as *const u16).read_volatile() }
+        } else {
+            1
+        };
+        let num_queues = (num_queues as usize).clamp(1, HART_COUNT);
+
+        let mut queues = Vec::with_capacity(num_queues);
+        for index in 0..num_queues as u32 {
+            assert!(!header.queue_used(index));
+            assert!(header.max_queue_size() >= QUEUE_SIZE as u32);
+            let queue = VirtQueue::new(QUEUE_SIZE);
+            header.queue_set(index, QUEUE_SIZE as u32, PAGE_SIZE as u32, queue.pfn());
+            queues.push((index, queue));
+        }
+        header.finish_init();
+
+        let header = Arc::new(UpCell::new(header));
+        let queues = queues
+            .into_iter()
+            .map(|(index, queue)| BlkQueue::new(header.clone(), index, queue))
+            .collect();
+        // 直接读取配置空间开头的 capacity 字段
+        let capacity = unsafe { (config as *const u64).read_volatile() } as usize;
+
+        Self {
+            header,
+            queues,
+            capacity,
+        }
+    }
+
+    /// `irq`所对应的 virtio-mmio 槽位上是否挂着块设备
+    pub fn probe(irq: &IrqId) -> bool {
+        let header = unsafe { &*(irq.virtio_mmio_addr() as *const VirtIOHeader) };
+        header.verify() && header.device_type() == DeviceType::Block
+    }
+
+    /// 当前处理器核的提交队列
+    fn queue(&self) -> &BlkQueue {
+        &self.queues[processor::hart_id() % self.queues.len()]
+    }
+}
+
+impl BlkQueue {
+    /// `queue`已在设备上登记为第`index`个队列
+    fn new(header: Header, index: u32, queue: VirtQueue<VirtioHal>) -> Self {
+        let condvars = BTreeMap::from_iter((0..QUEUE_SIZE).map(|i| (i, Condvar::new())));
+
+        Self {
+            base: UpCell::new(Device {
+                header,
+                index,
+                queue,
+            }),
+            condvars,
+            inflight: UpCell::new(BTreeMap::new()),
+            completed: Condvar::new(),
+        }
+    }
+
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        self.read_blocks(block_id, &mut [buf]);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        self.write_blocks(block_id, &[buf]);
+    }
+
+    fn read_blocks(&self, mut block_id: usize, bufs: &mut [&mut [u8]]) {
+        // 超出一条链所能容纳的部分分作多个请求
+        for chunk in bufs.chunks_mut(QUEUE_SIZE as usize - 2) {
+            let len = chunk.len();
+            self.request(len + 2, |req, queue| unsafe {
+                req.add_read(queue, block_id, chunk)
+            });
+            block_id += len;
+        }
+    }
+
+    fn write_blocks(&self, mut block_id: usize, bufs: &[&[u8]]) {
+        for chunk in bufs.chunks(QUEUE_SIZE as usize - 2) {
+            self.request(chunk.len() + 2, |req, queue| unsafe {
+                req.add_write(queue, block_id, chunk)
+            });
+            block_id += chunk.len();
+        }
+    }
+
+    /// 以`add`放入占用`count`个描述符的请求，等到设备完成后返回
+    fn request(
+        &self,
+        count: usize,
+        add: impl FnOnce(&mut BlkReq, &mut VirtQueue<VirtioHal>) -> Option<u16>,
+    ) {
+        let mut req = BlkReq::default();
+        let mode = *DEV_IO_MODE.exclusive_access();
+        match mode {
             IOMode::Interrupt => {
-                let mut resp = BlkResp::default();
-                let task_ctx_ptr = self.base.exclusive_session(|blk| {
-                    let token = unsafe { blk.read_block_nb(block_id, buf, &mut resp).unwrap() };
+                let task_ctx_ptr = self.with_descriptors(count, |dev| {
+                    let token = add(&mut req, &mut dev.queue).unwrap();
+                    dev.notify();
                     self.condvars.get(&token).unwrap().wait()
                 });
                 processor::schedule(task_ctx_ptr);
-                assert_eq!(resp.status(), RespStatus::Ok);
             }
+            // 轮询模式下请求都同步完成，队列中不会有别的请求
             IOMode::Poll => {
-                self.base
-                    .exclusive_access()
-                    .read_block(block_id, buf)
-                    .unwrap();
+                let mut dev = self.base.exclusive_access();
+                let token = add(&mut req, &mut dev.queue).unwrap();
+                dev.notify();
+                while !dev.queue.can_pop() {
+                    hint::spin_loop();
+                }
+                assert_eq!(dev.queue.pop_used().unwrap().0, token);
             }
         }
+        assert_eq!(req.status(), RespStatus::Ok);
     }
 
-    fn write_block(&self, block_id: usize, buf: &[u8]) {
-        match *DEV_IO_MODE.exclusive_access() {
-            IOMode::Interrupt => {
-                let mut resp = BlkResp::default();
-                let task_ctx_ptr = self.base.exclusive_session(|blk| {
-                    let token = unsafe { blk.write_block_nb(block_id, buf, &mut resp).unwrap() };
-                    self.condvars.get(&token).unwrap().wait()
-                });
-                processor::schedule(task_ctx_ptr);
-                assert_eq!(resp.status(), RespStatus::Ok);
+    /// 队列中有`count`个空闲描述符时在会话中执行`f`，否则等待其它请求完成后重试
+    fn with_descriptors<V>(&self, count: usize, f: impl FnOnce(&mut Device) -> V) -> V {
+        let mut f = Some(f);
+        loop {
+            let result = self.base.exclusive_session(|dev| {
+                if dev.queue.num_free() >= count {
+                    Ok(f.take().unwrap()(dev))
+                } else {
+                    Err(self.completed.wait())
+                }
+            });
+            match result {
+                Ok(value) => return value,
+                Err(task_ctx_ptr) => processor::schedule(task_ctx_ptr),
             }
-            IOMode::Poll => {
-                self.base
-                    .exclusive_access()
-                    .write_block(block_id, buf)
-                    .unwrap();
+        }
+    }
+
+    /// 提交异步请求后立即返回，轮询模式下则同步完成
+    fn submit(&self, mut request: BlockRequest, done: Completion) {
+        if let IOMode::Poll = *DEV_IO_MODE.exclusive_access() {
+            match request.op {
+                BlockOp::Read => self.read_block(request.block_id, &mut request.buf),
+                BlockOp::Write => self.write_block(request.block_id, &request.buf),
             }
+            done(request);
+            return;
         }
+
+        let mut req = Box::new(BlkReq::default());
+        // 登记须在完成中断到来之前，而中断在会话期间被屏蔽
+        self.with_descriptors(3, |dev| {
+            let token = unsafe {
+                match request.op {
+                    BlockOp::Read => req.add_read(
+                        &mut dev.queue,
+                        request.block_id,
+                        &mut [&mut request.buf[..]],
+                    ),
+                    BlockOp::Write => {
+                        req.add_write(&mut dev.queue, request.block_id, &[&request.buf[..]])
+                    }
+                }
+            }
+            .unwrap();
+            dev.notify();
+            self.inflight
+                .exclusive_access()
+                .insert(token, InFlight { request, req, done });
+        });
     }
 
-    fn handle_irq(&self) {
-        let mut blk = self.base.exclusive_access();
-        while let Ok(token) = blk.pop_used() {
-            self.condvars.get(&token).unwrap().signal()
+    fn wait_until(&self, done: &dyn Fn() -> bool) {
+        loop {
+            // 检查与睡眠之间屏蔽中断，以免错过完成中断
+            let Some(task_ctx_ptr) = self
+                .base
+                .exclusive_session(|_| (!done()).then(|| self.completed.wait()))
+            else {
+                return;
+            };
+            processor::schedule(task_ctx_ptr);
         }
     }
-}
 
-impl VirtIOBlock {
-    pub fn new() -> Self {
-        let virtio_blk = unsafe {
-            VirtIOBlk::<VirtioHal>::new(
-                &mut *(IrqId::BLOCK.virtio_mmio_addr() as *mut VirtIOHeader),
-            )
-            .unwrap()
-        };
-        let condvars =
-            BTreeMap::from_iter((0..virtio_blk.virt_queue_size()).map(|i| (i, Condvar::new())));
+    fn handle_irq(&self) {
+        let mut popped = false;
+        let mut finished = Vec::new();
+        self.base.exclusive_session(|dev| {
+            while let Some((token, _)) = dev.queue.pop_used() {
+                popped = true;
+                match self.inflight.exclusive_access().remove(&token) {
+                    Some(inflight) => finished.push(inflight),
+                    None => self.condvars.get(&token).unwrap().signal(),
+                }
+            }
+        });
+        if !popped {
+            return;
+        }
 
-        Self {
-            base: UpCell::new(virtio_blk),
-            condvars,
+        for inflight in finished {
+            assert_eq!(inflight.req.status(), RespStatus::Ok);
+            (inflight.done)(inflight.request);
         }
+        // 等待描述符与等待同步请求完成的任务共用此条件变量，须全部唤醒各自重新检查
+        self.completed.notify_all();
     }
 }
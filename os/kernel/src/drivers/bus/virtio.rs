@@ -43,3 +43,22 @@ impl Hal for VirtioHal {
             .into()
     }
 }
+
+// 自行实现的块设备队列沿用同一套内存分配
+impl virtqueue::Hal for VirtioHal {
+    fn dma_alloc(pages: usize) -> usize {
+        <Self as Hal>::dma_alloc(pages)
+    }
+
+    fn dma_dealloc(paddr: usize, pages: usize) {
+        <Self as Hal>::dma_dealloc(paddr, pages);
+    }
+
+    fn phys_to_virt(paddr: usize) -> usize {
+        <Self as Hal>::phys_to_virt(paddr)
+    }
+
+    fn virt_to_phys(vaddr: usize) -> usize {
+        <Self as Hal>::virt_to_phys(vaddr)
+    }
+}
@@ -2,15 +2,16 @@
 mod ns16550a;
 
 use alloc::boxed::Box;
-use spin::Lazy;
 
 use self::ns16550a::NS16550a;
+use super::DeviceCell;
 use crate::board::MemMapEntity;
 
 const VIRT_UART0: usize = MemMapEntity::UART0.addr;
 type CharDeviceImpl = NS16550a<VIRT_UART0>;
 
-pub static SERIAL: Lazy<Box<dyn CharDevice>> = Lazy::new(|| Box::new(CharDeviceImpl::new()));
+pub static SERIAL: DeviceCell<Box<dyn CharDevice>> =
+    DeviceCell::new("serial", || Box::new(CharDeviceImpl::new()));
 
 pub trait CharDevice: Send + Sync {
     fn init(&self);
@@ -18,4 +19,10 @@ pub trait CharDevice: Send + Sync {
     fn write(&self, ch: u8);
     fn is_empty(&self) -> bool;
     fn handle_irq(&self);
+
+    /// 挂起前使设备静默
+    fn suspend(&self) {}
+
+    /// 唤醒后恢复设备
+    fn resume(&self) {}
 }
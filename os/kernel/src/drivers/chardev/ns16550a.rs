@@ -196,7 +196,7 @@ impl<const BASE_ADDR: usize> CharDevice for NS16550a<BASE_ADDR> {
         });
 
         if count > 0 {
-            self.condvar.signal();
+            self.condvar.notify_all();
         }
     }
 }
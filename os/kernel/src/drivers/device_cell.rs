@@ -0,0 +1,64 @@
+//! 设备的静态存储
+//!
+//! 设备只在注册表探测它时初始化，而非像[`Lazy`](spin::Lazy)那样在首次访问时初始化。
+//! 探测顺序由注册时声明的依赖决定，某设备若在探测期间访问了未声明为依赖的设备，
+//! 会立即panic并指出二者，而不是隐式地初始化后者，或在二者互相访问时死锁。
+
+use core::ops::Deref;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use spin::Once;
+
+pub struct DeviceCell<T> {
+    name: &'static str,
+    init: fn() -> T,
+    value: Once<T>,
+    /// 正在探测，用以发现探测期间访问自身的设备
+    probing: AtomicBool,
+}
+
+impl<T> DeviceCell<T> {
+    pub const fn new(name: &'static str, init: fn() -> T) -> Self {
+        Self {
+            name,
+            init,
+            value: Once::new(),
+            probing: AtomicBool::new(false),
+        }
+    }
+
+    /// 初始化设备，已初始化时什么也不做
+    ///
+    /// # Panics
+    ///
+    /// 在自身的初始化期间再次探测时panic。
+    pub fn force(&self) -> &T {
+        if let Some(value) = self.value.get() {
+            return value;
+        }
+        assert!(
+            !self.probing.swap(true, Ordering::Acquire),
+            "device `{}` is used during its own probe",
+            self.name
+        );
+        let value = self.value.call_once(self.init);
+        self.probing.store(false, Ordering::Release);
+        value
+    }
+}
+
+impl<T> Deref for DeviceCell<T> {
+    type Target = T;
+
+    /// # Panics
+    ///
+    /// 设备尚未被探测时panic，说明访问者漏声明了对它的依赖。
+    fn deref(&self) -> &Self::Target {
+        self.value.get().unwrap_or_else(|| {
+            panic!(
+                "device `{}` is used before being probed, declare it as a dependency",
+                self.name
+            )
+        })
+    }
+}
@@ -3,16 +3,17 @@ use alloc::vec::Vec;
 use core::slice;
 
 use embedded_graphics::pixelcolor::Rgb888;
-use spin::Lazy;
 use tinybmp::Bmp;
 use virtio_drivers::{VirtIOGpu, VirtIOHeader};
 
 use super::bus::VirtioHal;
+use super::DeviceCell;
 use crate::board::IrqId;
 use crate::config::IMG_MOUSE;
 use crate::sync::UpCell;
 
-pub static GPU_DEVICE: Lazy<Box<dyn GpuDevice>> = Lazy::new(|| Box::new(VirtIOGpuWrapper::new()));
+pub static GPU_DEVICE: DeviceCell<Box<dyn GpuDevice>> =
+    DeviceCell::new("gpu", || Box::new(VirtIOGpuWrapper::new()));
 
 pub trait GpuDevice: Send + Sync {
     #[allow(dead_code)]
@@ -22,6 +23,12 @@ pub trait GpuDevice: Send + Sync {
     fn framebuffer(&self) -> &mut [u8];
 
     fn flush(&self);
+
+    /// 挂起前使设备静默
+    fn suspend(&self) {}
+
+    /// 唤醒后恢复设备
+    fn resume(&self) {}
 }
 
 pub struct VirtIOGpuWrapper {
@@ -1,26 +1,33 @@
 use alloc::boxed::Box;
 use alloc::collections::VecDeque;
 
-use spin::Lazy;
-
 use virtio_drivers::VirtIOHeader;
 use virtio_drivers::VirtIOInput;
 
 use super::bus::VirtioHal;
+use super::DeviceCell;
 use crate::board::IrqId;
 use crate::sync::{Condvar, UpCell};
 use crate::task::processor;
 
-pub static KEYBOARD_DEVICE: Lazy<Box<dyn InputDevice>> =
-    Lazy::new(|| Box::new(VirtIOInputWrapper::new(IrqId::KEYBOARD.virtio_mmio_addr())));
+pub static KEYBOARD_DEVICE: DeviceCell<Box<dyn InputDevice>> = DeviceCell::new("keyboard", || {
+    Box::new(VirtIOInputWrapper::new(IrqId::KEYBOARD.virtio_mmio_addr()))
+});
 
-pub static MOUSE_DEVICE: Lazy<Box<dyn InputDevice>> =
-    Lazy::new(|| Box::new(VirtIOInputWrapper::new(IrqId::MOUSE.virtio_mmio_addr())));
+pub static MOUSE_DEVICE: DeviceCell<Box<dyn InputDevice>> = DeviceCell::new("mouse", || {
+    Box::new(VirtIOInputWrapper::new(IrqId::MOUSE.virtio_mmio_addr()))
+});
 
 pub trait InputDevice: Send + Sync {
     fn is_empty(&self) -> bool;
     fn read_event(&self) -> u64;
     fn handle_irq(&self);
+
+    /// 挂起前使设备静默
+    fn suspend(&self) {}
+
+    /// 唤醒后恢复设备
+    fn resume(&self) {}
 }
 
 struct VirtIOInputWrapper {
@@ -77,7 +84,7 @@ impl InputDevice for VirtIOInputWrapper {
             }
         });
         if count > 0 {
-            self.condvar.signal();
+            self.condvar.notify_all();
         }
     }
 }
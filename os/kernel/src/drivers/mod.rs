@@ -3,14 +3,21 @@
 mod block;
 mod bus;
 mod chardev;
+mod device_cell;
 mod gpu;
 mod input;
 mod plic;
+mod power;
+mod registry;
+pub mod rtc;
 
 pub use self::{
-    block::{IOMode, BLOCK_DEVICE, DEV_IO_MODE},
-    chardev::SERIAL,
-    gpu::GPU_DEVICE,
-    input::{KEYBOARD_DEVICE, MOUSE_DEVICE},
+    block::{find_block_device, IOMode, BLOCK_DEVICE, DEV_IO_MODE, SECONDARY_BLOCK_DEVICES},
+    chardev::{CharDevice, SERIAL},
+    device_cell::DeviceCell,
+    gpu::{GpuDevice, GPU_DEVICE},
+    input::{InputDevice, KEYBOARD_DEVICE, MOUSE_DEVICE},
     plic::{init_device, irq_handler},
+    power::{suspend_to_idle, WakeSource},
+    registry::{find_by_name, init, remove_all},
 };
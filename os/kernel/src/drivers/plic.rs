@@ -3,47 +3,75 @@
 //! Reference
 //! - [Spec](https://github.com/riscv/riscv-plic-spec/blob/master/riscv-plic.adoc)
 
+use riscv::register::scause::{Interrupt, Trap};
 use riscv::register::sie;
 
-use super::{BLOCK_DEVICE, KEYBOARD_DEVICE, MOUSE_DEVICE, SERIAL};
+use super::registry;
 use crate::board::{
-    irq_ids, IrqId, MemMapEntity, PLIC_CONTEXT_BASE, PLIC_CONTEXT_STRIDE, PLIC_ENABLE_BASE,
+    IrqId, MemMapEntity, PLIC_CONTEXT_BASE, PLIC_CONTEXT_STRIDE, PLIC_ENABLE_BASE,
     PLIC_ENABLE_STRIDE,
 };
+use crate::task::processor;
+use crate::trap;
 
 pub fn init_device() {
     let mut plic = PLIC::new(MemMapEntity::PLIC.addr);
-    let hart_id = 0;
+    let hart_id = processor::hart_id();
 
     plic.set_threshold(hart_id, InterruptTargetPriority::Supervisor, 0);
     plic.set_threshold(hart_id, InterruptTargetPriority::Machine, 1);
 
-    for source_id in irq_ids().map(|id| id.0 as usize) {
+    for source_id in irq_ids() {
         plic.enable(hart_id, InterruptTargetPriority::Supervisor, source_id);
         plic.set_priority(source_id, 1);
     }
 
+    let external = Trap::Interrupt(Interrupt::SupervisorExternal);
+    trap::register_user(external, |_| irq_handler());
+    trap::register_kernel(external, |_| irq_handler());
+
     unsafe {
         // 允许S级外部中断
         sie::set_sext();
     }
 }
 
+/// 屏蔽除`wake`以外的所有外部中断源
+pub fn mask_except(wake: &IrqId) {
+    let mut plic = PLIC::new(MemMapEntity::PLIC.addr);
+    for source_id in irq_ids().filter(|&id| id != wake.0 as usize) {
+        plic.set_priority(source_id, 0);
+    }
+}
+
+/// 恢复所有外部中断源
+pub fn unmask_all() {
+    let mut plic = PLIC::new(MemMapEntity::PLIC.addr);
+    for source_id in irq_ids() {
+        plic.set_priority(source_id, 1);
+    }
+}
+
 pub fn irq_handler() {
     let mut plic = PLIC::new(MemMapEntity::PLIC.addr);
-    let hart_id = 0;
+    let hart_id = processor::hart_id();
 
     let source_id = plic.claim(hart_id, InterruptTargetPriority::Supervisor);
-    match IrqId(source_id) {
-        IrqId::KEYBOARD => KEYBOARD_DEVICE.handle_irq(),
-        IrqId::MOUSE => MOUSE_DEVICE.handle_irq(),
-        IrqId::BLOCK => BLOCK_DEVICE.handle_irq(),
-        IrqId::SERIAL => SERIAL.handle_irq(),
-        _ => panic!("Unsupported IRQ {source_id}"),
+    match registry::find_by_irq(&IrqId(source_id)) {
+        Some(device) => device.driver.handle_irq(),
+        None => panic!("Unsupported IRQ {source_id}"),
     }
     plic.complete(hart_id, InterruptTargetPriority::Supervisor, source_id);
 }
 
+/// 已登记设备的中断源
+fn irq_ids() -> impl Iterator<Item = usize> {
+    registry::devices()
+        .into_iter()
+        .filter_map(|device| device.irq)
+        .map(|id| id.0 as usize)
+}
+
 #[allow(clippy::upper_case_acronyms)]
 struct PLIC {
     base_addr: usize,
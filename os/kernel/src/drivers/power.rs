@@ -0,0 +1,56 @@
+//! 挂起至空闲（suspend-to-idle）
+//!
+//! 令设备静默、屏蔽唤醒源以外的外部中断，
+//! 随后处理器停在`wfi`上，直到串口输入或定时到期才恢复运行。
+//! 挂起发生在系统调用内部，内核态不做任务切换，故期间不会调度任何任务。
+
+use riscv::asm::wfi;
+
+use super::{registry, SERIAL};
+use crate::board::IrqId;
+use crate::timer;
+
+/// 唤醒原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeSource {
+    Timer = 0,
+    Serial = 1,
+}
+
+/// 挂起至空闲，`timeout_ms`为0时只有串口输入能唤醒。
+///
+/// 串口缓冲区里尚有未读的输入时会立即唤醒。
+pub fn suspend_to_idle(timeout_ms: usize) -> WakeSource {
+    let deadline = (timeout_ms != 0).then(|| timer::get_time_ms() + timeout_ms);
+
+    log::info!("[kernel] suspend to idle, timeout={timeout_ms}ms");
+    quiesce();
+
+    let source = loop {
+        if !SERIAL.is_empty() {
+            break WakeSource::Serial;
+        }
+        if deadline.is_some_and(|deadline| timer::get_time_ms() >= deadline) {
+            break WakeSource::Timer;
+        }
+        // 时钟中断仍会周期性到来，醒来后再检查一遍唤醒条件
+        unsafe {
+            wfi();
+        }
+    };
+
+    revive();
+    log::info!("[kernel] resume from idle, woken by {source:?}");
+
+    source
+}
+
+fn quiesce() {
+    registry::suspend_all();
+    super::plic::mask_except(&IrqId::SERIAL);
+}
+
+fn revive() {
+    super::plic::unmask_all();
+    registry::resume_all();
+}
@@ -0,0 +1,259 @@
+//! # 设备驱动模型
+//!
+//! 所有设备以统一的[`Driver`]生命周期接入内核：
+//! 启动时登记进设备注册表并逐一探测，
+//! 外部中断按中断号分派给对应设备，
+//! 挂起、唤醒与关机时再由注册表统一通知各设备。
+//!
+//! 目前注册表由 [`init`] 按板级的固定布局填充，日后可改为解析设备树。
+//!
+//! 设备登记时声明所依赖的设备，注册表据此排定探测顺序：依赖先于依赖者探测，
+//! 挂起与卸下时则反过来。设备存于[`DeviceCell`]，探测前被访问即panic，
+//! 漏声明的依赖在启动时便会暴露。
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use block_dev::BlockDevice;
+
+use super::{
+    CharDevice, DeviceCell, GpuDevice, InputDevice, BLOCK_DEVICE, GPU_DEVICE, KEYBOARD_DEVICE,
+    MOUSE_DEVICE, SECONDARY_BLOCK_DEVICES, SERIAL,
+};
+use crate::board::IrqId;
+use crate::boot;
+use crate::sync::UpCell;
+
+static DEVICES: UpCell<Vec<Device>> = UpCell::new(Vec::new());
+
+/// 设备驱动的生命周期
+pub trait Driver: Send + Sync {
+    /// 探测并初始化设备
+    fn probe(&self);
+
+    /// 卸下设备，此后设备不再被使用
+    fn remove(&self) {}
+
+    /// 挂起前使设备静默
+    fn suspend(&self) {}
+
+    /// 唤醒后恢复设备
+    fn resume(&self) {}
+
+    /// 处理设备的外部中断
+    fn handle_irq(&self);
+}
+
+/// 注册表中的设备项
+#[derive(Clone, Copy)]
+pub struct Device {
+    pub name: &'static str,
+    /// 设备的外部中断号，`None`表示设备不产生中断
+    pub irq: Option<IrqId>,
+    /// 须先于本设备探测的设备名
+    pub deps: &'static [&'static str],
+    pub driver: &'static dyn Driver,
+}
+
+/// 登记板上的设备，按依赖排序后逐一探测
+pub fn init() {
+    register("serial", Some(IrqId::SERIAL), &[], &SERIAL);
+    register("gpu", None, &[], &GPU_DEVICE);
+    register("keyboard", Some(IrqId::KEYBOARD), &[], &KEYBOARD_DEVICE);
+    register("mouse", Some(IrqId::MOUSE), &[], &MOUSE_DEVICE);
+    register("block0", Some(IrqId::BLOCK), &[], &BLOCK_DEVICE);
+    for (n, (irq, device)) in SECONDARY_BLOCK_DEVICES.iter().enumerate() {
+        // 设备与注册表同寿，名称无需回收
+        let name = Box::leak(format!("block{}", n + 1).into_boxed_str());
+        register(name, Some(*irq), &[], device);
+    }
+
+    sort_by_dependencies();
+    for device in devices() {
+        log::info!("probe {}", device.name);
+        boot::span(device.name, || device.driver.probe());
+    }
+}
+
+pub fn register(
+    name: &'static str,
+    irq: Option<IrqId>,
+    deps: &'static [&'static str],
+    driver: &'static dyn Driver,
+) {
+    DEVICES.exclusive_access().push(Device {
+        name,
+        irq,
+        deps,
+        driver,
+    });
+}
+
+/// 将注册表重排为探测顺序，互不依赖的设备保持登记的先后
+///
+/// # Panics
+///
+/// 依赖的设备未登记或依赖成环时panic。
+fn sort_by_dependencies() {
+    let mut pending = DEVICES.exclusive_access().clone();
+    let mut sorted: Vec<Device> = Vec::with_capacity(pending.len());
+
+    for device in &pending {
+        for dep in device.deps {
+            assert!(
+                pending.iter().any(|other| other.name == *dep),
+                "device `{}` depends on unregistered device `{dep}`",
+                device.name
+            );
+        }
+    }
+
+    while !pending.is_empty() {
+        let Some(ready) = pending.iter().position(|device| {
+            device
+                .deps
+                .iter()
+                .all(|dep| sorted.iter().any(|done| done.name == *dep))
+        }) else {
+            let names: Vec<_> = pending.iter().map(|device| device.name).collect();
+            panic!("circular device dependencies among {names:?}");
+        };
+        sorted.push(pending.remove(ready));
+    }
+
+    *DEVICES.exclusive_access() = sorted;
+}
+
+/// 注册表的快照，遍历时不占用注册表
+pub fn devices() -> Vec<Device> {
+    DEVICES.exclusive_access().clone()
+}
+
+/// 按登记的名称查找设备
+pub fn find_by_name(name: &str) -> Option<Device> {
+    DEVICES
+        .exclusive_access()
+        .iter()
+        .find(|device| device.name == name)
+        .copied()
+}
+
+/// 按中断号查找设备
+pub fn find_by_irq(irq: &IrqId) -> Option<Device> {
+    DEVICES
+        .exclusive_access()
+        .iter()
+        .find(|device| device.irq.as_ref() == Some(irq))
+        .copied()
+}
+
+/// 按探测的逆序挂起全体设备
+pub fn suspend_all() {
+    for device in devices().iter().rev() {
+        device.driver.suspend();
+    }
+}
+
+/// 按探测的顺序唤醒全体设备
+pub fn resume_all() {
+    for device in devices() {
+        device.driver.resume();
+    }
+}
+
+/// 按探测的逆序卸下全体设备，关机前调用
+pub fn remove_all() {
+    for device in devices().iter().rev() {
+        log::info!("remove {}", device.name);
+        device.driver.remove();
+    }
+    DEVICES.exclusive_access().clear();
+}
+
+impl Driver for DeviceCell<Box<dyn CharDevice>> {
+    fn probe(&self) {
+        self.force().init();
+    }
+
+    fn suspend(&self) {
+        (***self).suspend();
+    }
+
+    fn resume(&self) {
+        (***self).resume();
+    }
+
+    fn handle_irq(&self) {
+        (***self).handle_irq();
+    }
+}
+
+impl Driver for DeviceCell<Box<dyn GpuDevice>> {
+    fn probe(&self) {
+        self.force();
+    }
+
+    fn suspend(&self) {
+        (***self).suspend();
+    }
+
+    fn resume(&self) {
+        (***self).resume();
+    }
+
+    fn handle_irq(&self) {}
+}
+
+impl Driver for DeviceCell<Box<dyn InputDevice>> {
+    fn probe(&self) {
+        self.force();
+    }
+
+    fn suspend(&self) {
+        (***self).suspend();
+    }
+
+    fn resume(&self) {
+        (***self).resume();
+    }
+
+    fn handle_irq(&self) {
+        (***self).handle_irq();
+    }
+}
+
+impl Driver for DeviceCell<Arc<dyn BlockDevice>> {
+    fn probe(&self) {
+        self.force();
+    }
+
+    fn suspend(&self) {
+        (***self).suspend();
+    }
+
+    fn resume(&self) {
+        (***self).resume();
+    }
+
+    fn handle_irq(&self) {
+        (***self).handle_irq();
+    }
+}
+
+impl Driver for Arc<dyn BlockDevice> {
+    fn probe(&self) {}
+
+    fn suspend(&self) {
+        (**self).suspend();
+    }
+
+    fn resume(&self) {
+        (**self).resume();
+    }
+
+    fn handle_irq(&self) {
+        (**self).handle_irq();
+    }
+}
@@ -0,0 +1,23 @@
+//! Goldfish实时时钟
+//!
+//! QEMU virt板载的RTC只提供自UNIX纪元以来的纳秒数，没有中断，也不需要初始化。
+//! 它只用来确定启动时刻，此后的墙上时间由`mtime`推算，见[`timer::realtime_us`](crate::timer::realtime_us)。
+
+use crate::board::MemMapEntity;
+use crate::ptr::volatile::ReadOnly;
+
+#[repr(C)]
+struct GoldfishRtc {
+    /// 读低32位时锁存高32位
+    time_low: ReadOnly<u32>,
+    time_high: ReadOnly<u32>,
+}
+
+/// 自UNIX纪元以来的纳秒数
+pub fn read_ns() -> u64 {
+    let rtc = unsafe { &*(MemMapEntity::RTC.addr as *const GoldfishRtc) };
+    // 必须先读低位
+    let low = rtc.time_low.vread() as u64;
+    let high = rtc.time_high.vread() as u64;
+    high << 32 | low
+}
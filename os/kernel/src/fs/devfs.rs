@@ -0,0 +1,193 @@
+//! # 设备文件系统
+//!
+//! 启动时挂载于`/dev`，提供常用的字符设备：
+//!
+//! ```text
+//! /dev
+//! ├── null       读到文件末尾，写入的都被丢弃
+//! ├── zero       读出无尽的0，写入的都被丢弃
+//! ├── urandom    读出伪随机字节，写入的都被丢弃
+//! └── tty        标准输入输出所在的终端
+//! ```
+
+use alloc::string::ToString;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use enumflags2::BitFlags;
+use vfs::{DirEntry, DirEntryType, Stat};
+
+use super::mount::FileSystem;
+use super::procfs::ProcDir;
+use super::stdio::{self, Stdout};
+use super::{File, FileKind, OpenFlag};
+use crate::memory::UserBuffer;
+use crate::sync::UpCell;
+use crate::timer;
+
+pub struct DevFs;
+
+impl FileSystem for DevFs {
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn open(
+        &self,
+        _path: &str,
+        relat_path: &str,
+        flags: BitFlags<OpenFlag>,
+    ) -> Result<Arc<dyn File + Send + Sync>, vfs::Error> {
+        if relat_path.is_empty() {
+            return Ok(Arc::new(ProcDir::new(root_entries())));
+        }
+        let device = Device::ALL
+            .into_iter()
+            .find(|device| device.name() == relat_path)
+            .ok_or(vfs::Error::NotFound)?;
+        // 设备没有内容可清空，`CREATE`与`TRUNC`都被忽略
+        let [readable, writable] = if flags.contains(OpenFlag::WRONLY) {
+            [false, true]
+        } else if flags.contains(OpenFlag::RDWR) {
+            [true, true]
+        } else {
+            [true, false]
+        };
+        Ok(Arc::new(DevFile {
+            device,
+            readable,
+            writable,
+        }))
+    }
+}
+
+fn root_entries() -> Vec<DirEntry> {
+    Device::ALL
+        .into_iter()
+        .enumerate()
+        .map(|(i, device)| DirEntry {
+            inode: i as u64 + 1,
+            ty: DirEntryType::Char,
+            name: device.name().to_string(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Null,
+    Zero,
+    Urandom,
+    Tty,
+}
+
+impl Device {
+    const ALL: [Self; 4] = [Self::Null, Self::Zero, Self::Urandom, Self::Tty];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Zero => "zero",
+            Self::Urandom => "urandom",
+            Self::Tty => "tty",
+        }
+    }
+}
+
+/// 打开的设备
+#[derive(Debug)]
+struct DevFile {
+    device: Device,
+    readable: bool,
+    writable: bool,
+}
+
+impl File for DevFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        match self.device {
+            Device::Null => 0,
+            Device::Zero => {
+                buf.iter_mut().for_each(|b| *b = 0);
+                buf.len()
+            }
+            Device::Urandom => {
+                let mut rng = RNG.exclusive_access();
+                buf.iter_mut().for_each(|b| *b = rng.next() as u8);
+                buf.len()
+            }
+            // 同标准输入，一次只读一个字符
+            Device::Tty => {
+                let Some(first) = buf.iter_mut().next() else {
+                    return 0;
+                };
+                match stdio::getchar() {
+                    Some(ch) => {
+                        *first = ch;
+                        1
+                    }
+                    None => 0,
+                }
+            }
+        }
+    }
+
+    fn write(&self, buf: UserBuffer) -> usize {
+        match self.device {
+            Device::Tty => Stdout.write(buf),
+            _ => buf.len(),
+        }
+    }
+
+    fn ioctl(&self, request: usize, arg: usize) -> Option<usize> {
+        match self.device {
+            Device::Tty => Stdout.ioctl(request, arg),
+            _ => None,
+        }
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: DirEntryType::Char,
+            block_size: 0,
+            blocks: 0,
+            size: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+
+    fn kind(&self) -> FileKind {
+        match self.device {
+            Device::Tty => FileKind::Tty,
+            _ => FileKind::CharDevice,
+        }
+    }
+}
+
+/// `/dev/urandom`的状态，首次读取时以时钟为种子
+static RNG: UpCell<XorShift> = UpCell::new(XorShift(0));
+
+/// xorshift64，不适用于密码学
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        // xorshift的状态不能为0
+        if self.0 == 0 {
+            self.0 = (timer::get_time() as u64).max(1);
+        }
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -5,7 +5,7 @@ use core::sync::atomic::AtomicU64;
 use enumflags2::bitflags;
 use enumflags2::BitFlags;
 
-use super::File;
+use super::{File, FileKind};
 use crate::memory::UserBuffer;
 use crate::sync::UpCell;
 use crate::task;
@@ -64,6 +64,10 @@ struct SemNonBlockEventFdContext {
 }
 
 impl File for EventFdContext {
+    fn kind(&self) -> FileKind {
+        FileKind::EventFd
+    }
+
     fn writable(&self) -> bool {
         true
     }
@@ -117,6 +121,10 @@ impl File for EventFdContext {
 }
 
 impl File for NonBlockEventFdContext {
+    fn kind(&self) -> FileKind {
+        FileKind::EventFd
+    }
+
     fn writable(&self) -> bool {
         true
     }
@@ -166,6 +174,10 @@ impl File for NonBlockEventFdContext {
 }
 
 impl File for SemEventFdContext {
+    fn kind(&self) -> FileKind {
+        FileKind::EventFd
+    }
+
     fn writable(&self) -> bool {
         true
     }
@@ -215,6 +227,10 @@ impl File for SemEventFdContext {
 }
 
 impl File for SemNonBlockEventFdContext {
+    fn kind(&self) -> FileKind {
+        FileKind::EventFd
+    }
+
     fn writable(&self) -> bool {
         true
     }
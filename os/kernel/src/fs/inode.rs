@@ -1,69 +1,316 @@
-use alloc::slice;
+//! 按挂载表将路径分派给各文件系统，并在文件系统的索引节点之上实现打开的文件
+
 use alloc::sync::Arc;
 use alloc::vec::Vec;
-use core::mem;
-use core::ptr;
 
 use enumflags2::bitflags;
 use enumflags2::BitFlags;
-use fat::FatFileSystem;
-use fat::Inode;
-use fat::ROOT;
-use spin::Lazy;
-use vfs::CDirEntry;
-use vfs::DirEntryType;
-use vfs::Stat;
-
-use super::File;
-use crate::drivers::BLOCK_DEVICE;
+use vfs::{DirEntryType, DiskQuota, Stat, VfsInode};
+
+use super::mount;
+use super::quota::Quota;
+use super::{Advice, DirentBuf, File, FileId, SeekError, Whence};
+use crate::memory::image;
 use crate::memory::UserBuffer;
-use crate::path::Path;
 use crate::sync::UpCell;
+use crate::task::processor;
+
+#[rustfmt::skip]
+#[allow(clippy::upper_case_acronyms)]
+#[bitflags]
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenFlag {
+    /// 只写
+    WRONLY = 0b0000_0000_0001,
+    /// 读写兼备
+    RDWR   = 0b0000_0000_0010,
+    /// 创建文件，若文件存在则清空
+    CREATE = 0b0010_0000_0000,
+    /// 先清空文件，再交给用户
+    TRUNC  = 0b0100_0000_0000,
+}
+
+impl OpenFlag {
+    // enumflags2拒绝值为0的标志
+    /// 只读
+    pub const RDONLY: u32 = 0b0000_0000_0000;
+
+    #[inline]
+    pub fn read_only() -> BitFlags<OpenFlag> {
+        BitFlags::from_bits_truncate(Self::RDONLY)
+    }
+}
+
+/// `path`为标准路径
+pub fn open_dir(path: &str) -> Result<Arc<dyn File + Send + Sync>, vfs::Error> {
+    let (fs, relat_path) = mount::resolve(path).ok_or(vfs::Error::NotFound)?;
+    let dir = fs.open(path, relat_path, OpenFlag::read_only())?;
+    if dir.stat().mode != DirEntryType::Directory {
+        return Err(vfs::Error::NotADirectory);
+    }
+    Ok(dir)
+}
+
+/// `path`为标准路径，交给其所在挂载点的文件系统打开
+pub fn open(path: &str, flags: BitFlags<OpenFlag>) -> Option<Arc<dyn File + Send + Sync>> {
+    let (fs, relat_path) = mount::resolve(path)?;
+    fs.open(path, relat_path, flags).ok()
+}
+
+/// `old_path`和`new_path`都是标准路径，须位于同一文件系统
+pub fn link(old_path: &str, new_path: &str) -> Result<(), vfs::Error> {
+    let (fs, old_relat) = mount::resolve(old_path).ok_or(vfs::Error::NotFound)?;
+    let (new_fs, new_relat) = mount::resolve(new_path).ok_or(vfs::Error::NotFound)?;
+    if !Arc::ptr_eq(&fs, &new_fs) {
+        return Err(vfs::Error::CrossesDevices);
+    }
+    fs.link(old_relat, new_relat)
+}
+
+/// `old_path`和`new_path`都是标准路径，须位于同一文件系统。
+/// `new_path`是已有的目录时移入其下，名称不变
+pub fn rename(old_path: &str, new_path: &str) -> Result<(), vfs::Error> {
+    let (fs, old_relat) = mount::resolve(old_path).ok_or(vfs::Error::NotFound)?;
+    let (new_fs, new_relat) = mount::resolve(new_path).ok_or(vfs::Error::NotFound)?;
+    if !Arc::ptr_eq(&fs, &new_fs) {
+        return Err(vfs::Error::CrossesDevices);
+    }
+    fs.rename(old_relat, new_relat)
+}
+
+/// 以[`vfs::FileSystem`]特型对象实现挂载表中的文件系统，
+/// 路径经[`VfsInode::lookup`]逐级解析，打开的文件都是[`OSInode`]
+pub struct DiskFs {
+    name: &'static str,
+    fs: Arc<dyn vfs::FileSystem>,
+    quota: Arc<Quota>,
+}
+
+impl DiskFs {
+    /// 同时读入`fs`根目录下的配额文件
+    pub fn new(name: &'static str, fs: Arc<dyn vfs::FileSystem>) -> Self {
+        let quota = Arc::new(Quota::load(fs.root()));
+        Self { name, fs, quota }
+    }
 
-static FS: Lazy<UpCell<FatFileSystem>> =
-    Lazy::new(|| UpCell::new(FatFileSystem::load(&BLOCK_DEVICE)));
+    /// `relat_path`为挂载点之下的相对路径，空串即根目录
+    fn lookup(&self, relat_path: &str) -> Result<Arc<dyn VfsInode>, vfs::Error> {
+        relat_path
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self.fs.root(), |dir, name| dir.lookup(name))
+    }
+
+    /// 文件系统的地址，与索引节点号一同标识文件
+    fn addr(&self) -> usize {
+        Arc::as_ptr(&self.fs).cast::<()>() as usize
+    }
+
+    /// 相对路径`relat_path`所在的目录及其文件名
+    fn lookup_parent<'a>(
+        &self,
+        relat_path: &'a str,
+    ) -> Result<(Arc<dyn VfsInode>, &'a str), vfs::Error> {
+        let (parent, name) = relat_path.rsplit_once('/').unwrap_or(("", relat_path));
+        if name.is_empty() {
+            return Err(vfs::Error::InvalidInput);
+        }
+        Ok((self.lookup(parent)?, name))
+    }
+}
+
+impl mount::FileSystem for DiskFs {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn open(
+        &self,
+        path: &str,
+        relat_path: &str,
+        flags: BitFlags<OpenFlag>,
+    ) -> Result<Arc<dyn File + Send + Sync>, vfs::Error> {
+        let [readable, writable] = if flags.is_empty() {
+            [true, false]
+        } else if flags.contains(OpenFlag::WRONLY) {
+            [false, true]
+        } else {
+            [true, true]
+        };
+        let create = flags.contains(OpenFlag::CREATE);
+
+        let inode = match self.lookup(relat_path) {
+            Ok(inode) => {
+                if (create || flags.contains(OpenFlag::TRUNC))
+                    && inode.kind() == DirEntryType::Regular
+                {
+                    let size = inode.size();
+                    image::invalidate((self.addr(), inode.id()));
+                    inode.truncate(0)?;
+                    self.quota.charge(
+                        inode.owner(),
+                        quota_blocks(&*inode, size),
+                        quota_blocks(&*inode, 0),
+                    );
+                }
+                inode
+            }
+            Err(vfs::Error::NotFound) if create => {
+                let (parent, name) = self.lookup_parent(relat_path)?;
+                let inode = parent.create(name)?;
+                let uid = processor::current_process().inner().exclusive_access().uid;
+                // 不记录属主的文件系统报Unsupported，文件仍属于超级用户
+                let _ = inode.set_owner(uid);
+                inode
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(Arc::new(OSInode::new(
+            path,
+            readable,
+            writable,
+            self.addr(),
+            inode,
+            self.quota.clone(),
+        )))
+    }
+
+    fn link(&self, old_relat: &str, new_relat: &str) -> Result<(), vfs::Error> {
+        let (old_dir, old_name) = self.lookup_parent(old_relat)?;
+        let (new_dir, new_name) = self.lookup_parent(new_relat)?;
+        old_dir.link(old_name, &*new_dir, new_name)
+    }
+
+    fn rename(&self, old_relat: &str, new_relat: &str) -> Result<(), vfs::Error> {
+        let (old_dir, old_name) = self.lookup_parent(old_relat)?;
+        let (new_dir, new_name) = match self.lookup(new_relat) {
+            Ok(dir) if dir.kind() == DirEntryType::Directory => (dir, old_name),
+            Ok(_) | Err(vfs::Error::NotFound) => self.lookup_parent(new_relat)?,
+            Err(e) => return Err(e),
+        };
+        // 被替换的文件不再占用空间
+        let replaced = new_dir
+            .lookup(new_name)
+            .ok()
+            .filter(|inode| inode.kind() == DirEntryType::Regular);
+        // 被替换的文件回收的索引节点号可能分给新文件
+        image::clear();
+        old_dir.rename(old_name, &*new_dir, new_name)?;
+        if let Some(inode) = replaced {
+            let blocks = quota_blocks(&*inode, inode.size());
+            self.quota.charge(inode.owner(), blocks, 0);
+        }
+        Ok(())
+    }
+
+    fn unmount(&self) {
+        if let Err(e) = self.quota.sync() {
+            log::error!("write back {}: {e:?}", vfs::quota::QUOTA_FILE);
+        }
+        self.fs.unmount();
+    }
+
+    fn quota(&self) -> Option<&Quota> {
+        Some(&self.quota)
+    }
+
+    /// 每个打开的文件都持有配额
+    fn busy(&self) -> bool {
+        Arc::strong_count(&self.quota) > 1
+    }
+}
 
 /// 表示进程打开的文件或目录
 #[derive(Debug)]
 pub struct OSInode {
+    /// 打开时的标准路径
+    path: Arc<str>,
     readable: bool,
     writable: bool,
+    /// 所在文件系统的地址
+    fs_addr: usize,
+    inode: Arc<dyn VfsInode>,
+    /// 所在文件系统的配额，增长与缩减都记在文件的属主名下
+    quota: Arc<Quota>,
     inner: UpCell<OSInodeInner>,
 }
 
 #[derive(Debug)]
 struct OSInodeInner {
-    /// **文件**内的偏移量
+    /// 文件内的字节偏移量，或目录的游标，见[`VfsInode::read_dir`]
     offset: usize,
-    inode: Inode,
+    advice: Advice,
+    /// 已预读至的字节偏移
+    readahead_end: usize,
 }
 
 impl OSInode {
     #[inline]
-    pub fn new(readable: bool, writable: bool, inode: Inode) -> Self {
+    pub fn new(
+        path: &str,
+        readable: bool,
+        writable: bool,
+        fs_addr: usize,
+        inode: Arc<dyn VfsInode>,
+        quota: Arc<Quota>,
+    ) -> Self {
         Self {
+            path: path.into(),
             readable,
             writable,
-            inner: UpCell::new(OSInodeInner { offset: 0, inode }),
+            fs_addr,
+            inode,
+            quota,
+            inner: UpCell::new(OSInodeInner {
+                offset: 0,
+                advice: Advice::default(),
+                readahead_end: 0,
+            }),
         }
     }
 
-    pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let mut buffer = [0u8; 512];
+    /// 文件内容已改变，失效其映像。FAT以首簇号为索引节点号，
+    /// 写入与截断可能改变之，改变前的`before`也须失效
+    fn invalidate_image(&self, before: u64) {
+        image::invalidate((self.fs_addr, before));
+        image::invalidate((self.fs_addr, self.inode.id()));
+    }
 
-        let mut bytes = Vec::new();
-        loop {
-            let len = inner
-                .inode
-                .read_at(inner.offset, &mut buffer, &FS.exclusive_access());
-            if len == 0 {
-                break;
-            }
-            inner.offset += len;
-            bytes.extend_from_slice(&buffer[..len]);
+    /// 从`size`字节写入或扩展至`end`字节是否超出属主的配额
+    fn exceeds_quota(&self, size: usize, end: usize) -> bool {
+        end > size
+            && self.quota.exceeds(
+                self.inode.owner(),
+                quota_blocks(&*self.inode, end) - quota_blocks(&*self.inode, size),
+            )
+    }
+
+    /// 文件从`before`字节变为当前的大小，增减的块记在属主名下
+    fn charge(&self, before: usize) {
+        self.quota.charge(
+            self.inode.owner(),
+            quota_blocks(&*self.inode, before),
+            quota_blocks(&*self.inode, self.inode.size()),
+        );
+    }
+
+    /// 读到上次预读窗口的后半段时，按`advice`预读下一个窗口
+    fn readahead(&self, inner: &mut OSInodeInner, advice: Advice) {
+        let window = advice.readahead_bytes();
+        if window == 0 || inner.offset + window / 2 < inner.readahead_end {
+            return;
+        }
+        let start = inner.readahead_end.max(inner.offset);
+        self.inode.prefetch(start, window);
+        inner.readahead_end = start + window;
+    }
+
+    /// DONTNEED时写回并丢弃刚访问过的`[offset, offset + len)`的缓存
+    fn drop_behind(&self, advice: Advice, offset: usize, len: usize) {
+        if advice == Advice::DontNeed {
+            self.inode.release(offset, len);
         }
-        bytes
     }
 }
 
@@ -81,271 +328,239 @@ impl File for OSInode {
     fn read(&self, mut buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
         let mut total_read_size = 0;
+        let start = inner.offset;
 
         for sub_buf in buf.as_mut() {
-            let read_size = inner
-                .inode
-                .read_at(inner.offset, sub_buf, &FS.exclusive_access());
-            if read_size == 0 {
-                break;
-            }
+            let advice = inner.advice;
+            self.readahead(&mut inner, advice);
+            let read_size = match self.inode.read_at(inner.offset, sub_buf) {
+                Ok(0) => break,
+                Ok(read_size) => read_size,
+                Err(e) => {
+                    log::error!("read inode {}: {e:?}", self.inode.id());
+                    break;
+                }
+            };
             inner.offset += read_size;
             total_read_size += read_size;
         }
 
+        self.drop_behind(inner.advice, start, total_read_size);
         total_read_size
     }
 
     fn write(&self, buf: UserBuffer) -> usize {
         let mut inner = self.inner.exclusive_access();
         let mut total_write_size = 0;
-        let offset = inner.offset;
+        let start = inner.offset;
+        let size = self.inode.size();
+        let id = self.inode.id();
+        // 系统调用已先行检查以报EDQUOT，在此分配前再把关
+        if self.exceeds_quota(size, start.saturating_add(buf.len())) {
+            return 0;
+        }
 
         for sub_buf in buf.as_ref() {
-            let write_size = inner
-                .inode
-                .write_at(offset, sub_buf, &mut FS.exclusive_access());
-            assert_eq!(write_size, sub_buf.len());
+            let write_size = match self.inode.write_at(inner.offset, sub_buf) {
+                Ok(write_size) => write_size,
+                Err(e) => {
+                    log::error!("write inode {}: {e:?}", self.inode.id());
+                    break;
+                }
+            };
             inner.offset += write_size;
             total_write_size += write_size;
+            // 越过了最大字节数
+            if write_size < sub_buf.len() {
+                break;
+            }
         }
 
+        self.drop_behind(inner.advice, start, total_write_size);
+        self.charge(size);
+        self.invalidate_image(id);
         total_write_size
     }
 
-    fn stat(&self) -> Stat {
-        self.inner
-            .exclusive_access()
-            .inode
-            .stat(&FS.exclusive_access())
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let read_size = self.inode.read_at(offset, buf).unwrap_or_else(|e| {
+            log::error!("read inode {}: {e:?}", self.inode.id());
+            0
+        });
+        let advice = self.inner.exclusive_access().advice;
+        self.drop_behind(advice, offset, read_size);
+        read_size
     }
 
-    fn getdents(&self, mut buf: UserBuffer, len: usize) -> usize {
-        let mut inner = self.inner.exclusive_access();
-        let dirents = inner.inode.ls_at(inner.offset, len, &FS.exclusive_access());
-        let read = dirents.len();
-        log::debug!("Read DirEntries: {read}");
-
-        let name_ptrs: Vec<_> = buf
-            .transmute_slice::<CDirEntry>()
-            .into_iter()
-            .take(read)
-            .map(|c_dirent| c_dirent.name)
-            .collect();
-
-        for (&name_ptr, dirent) in name_ptrs.iter().zip(&dirents) {
-            let mut name_buf = UserBuffer::new(buf.token(), name_ptr, CDirEntry::NAME_CAP);
-            for (cnb, &dnb) in name_buf.iter_mut().zip(dirent.name.as_bytes()) {
-                *cnb = dnb;
-            }
+    /// 写回映射时同样受配额限制，超出时一个字节也不写
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let size = self.inode.size();
+        let id = self.inode.id();
+        if self.exceeds_quota(size, offset.saturating_add(buf.len())) {
+            log::warn!("write inode {id}: disk quota exceeded");
+            return 0;
         }
+        let write_size = self.inode.write_at(offset, buf).unwrap_or_else(|e| {
+            log::error!("write inode {}: {e:?}", self.inode.id());
+            0
+        });
+        let advice = self.inner.exclusive_access().advice;
+        self.drop_behind(advice, offset, write_size);
+        self.charge(size);
+        self.invalidate_image(id);
+        write_size
+    }
 
-        let dirents: Vec<_> = dirents
-            .iter()
-            .zip(name_ptrs)
-            .map(|(dirent, name)| CDirEntry {
-                inode: dirent.inode,
-                ty: dirent.ty,
-                name,
-            })
-            .collect();
-
-        for (b, &db) in buf.iter_mut().zip(dirents.iter().flat_map(|dirent| unsafe {
-            slice::from_raw_parts(
-                ptr::from_ref(&dirent).cast::<u8>(),
-                mem::size_of::<CDirEntry>(),
-            )
-        })) {
-            *b = db;
-        }
+    fn read_all(&self) -> Vec<u8> {
+        let mut inner = self.inner.exclusive_access();
+        let mut buffer = [0u8; 512];
 
-        inner.offset += read;
-        read
+        let start = inner.offset;
+        let mut bytes = Vec::new();
+        loop {
+            // 加载程序总是顺序读完整个文件
+            self.readahead(&mut inner, Advice::Sequential);
+            let len = match self.inode.read_at(inner.offset, &mut buffer) {
+                Ok(0) => break,
+                Ok(len) => len,
+                Err(e) => {
+                    log::error!("read inode {}: {e:?}", self.inode.id());
+                    break;
+                }
+            };
+            inner.offset += len;
+            bytes.extend_from_slice(&buffer[..len]);
+        }
+        self.drop_behind(inner.advice, start, bytes.len());
+        bytes
     }
 
-    fn mkdir(&self, name: &str) -> Result<(), vfs::Error> {
-        let inner = self.inner.exclusive_access();
-        inner.inode.mkdir(name, &mut FS.exclusive_access())?;
-        Ok(())
+    fn fadvise(&self, advice: Advice) -> bool {
+        self.inner.exclusive_access().advice = advice;
+        // 同Linux，DONTNEED还会立即丢弃整个文件已缓存的数据
+        self.drop_behind(advice, 0, usize::MAX);
+        true
     }
 
-    fn unlink(&self, name: &str) -> Result<(), vfs::Error> {
+    fn lseek(&self, offset: isize, whence: Whence) -> Result<usize, SeekError> {
         let mut inner = self.inner.exclusive_access();
-        inner.inode.unlink(name, &mut FS.exclusive_access())
+        // 目录的偏移量是游标，没有与之对应的末尾
+        let size = match self.inode.kind() {
+            DirEntryType::Directory if whence == Whence::End => return Err(SeekError::Invalid),
+            DirEntryType::Directory => 0,
+            _ => self.inode.stat().size as usize,
+        };
+        inner.offset = whence.resolve(inner.offset, size, offset)?;
+        // 从新的位置重新预读
+        inner.readahead_end = inner.offset;
+        Ok(inner.offset)
     }
 
-    fn rmdir(&self, name: &str) -> Result<(), vfs::Error> {
-        let mut inner = self.inner.exclusive_access();
-        inner.inode.rmdir(name, &mut FS.exclusive_access())
+    fn truncate(&self, size: usize) -> bool {
+        if self.inode.kind() != DirEntryType::Regular {
+            return false;
+        }
+        let id = self.inode.id();
+        let before = self.inode.size();
+        if self.exceeds_quota(before, size) {
+            return false;
+        }
+        let truncated = self
+            .inode
+            .truncate(size)
+            .inspect_err(|e| log::error!("truncate inode {}: {e:?}", self.inode.id()))
+            .is_ok();
+        self.charge(before);
+        self.invalidate_image(id);
+        truncated
     }
 
-    fn rename(&self, old_name: &str, newpath: &str) -> Result<(), vfs::Error> {
-        let mut inner = self.inner.exclusive_access();
+    fn quota_exceeded(&self, end: usize) -> bool {
+        self.exceeds_quota(self.inode.size(), end)
+    }
 
-        let (mut new_parent, new_name) = match open_dir_inode(newpath) {
-            Ok(p) => {
-                log::info!("{old_name} -> {newpath}/");
-                (p, old_name)
-            }
-            Err(vfs::Error::NotADirectory | vfs::Error::NotFound) => {
-                let (parent, file) = newpath.parent_file().expect("path was verified as not `/`");
-                log::info!(
-                    "{old_name} -> {}/{file}",
-                    parent.root_relative().unwrap_or_default()
-                );
-                (open_dir_inode(parent)?, file)
-            }
-            Err(e) => return Err(e),
-        };
+    fn max_size(&self) -> usize {
+        self.inode.max_size()
+    }
 
-        if inner.inode.id() == new_parent.id() {
-            // 当前目录
-            log::info!("rename currently");
-            if old_name == new_name {
-                return Err(vfs::Error::AlreadyExists);
-            } else {
-                inner
-                    .inode
-                    .rename(old_name, None, new_name, &mut FS.exclusive_access())?;
-            }
-        } else {
-            // 跨目录
-            log::info!("rename cross directories");
-            inner.inode.rename(
-                old_name,
-                Some(&mut new_parent),
-                new_name,
-                &mut FS.exclusive_access(),
-            )?;
-        }
+    fn set_times(&self, atime: Option<u64>, mtime: Option<u64>) -> bool {
+        self.inode.set_times(atime, mtime).is_ok()
+    }
 
-        Ok(())
+    fn stat(&self) -> Stat {
+        self.inode.stat()
     }
-}
 
-#[rustfmt::skip]
-#[allow(clippy::upper_case_acronyms)]
-#[bitflags]
-#[repr(u32)]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum OpenFlag {
-    /// 只写
-    WRONLY = 0b0000_0000_0001,
-    /// 读写兼备
-    RDWR   = 0b0000_0000_0010,
-    /// 创建文件，若文件存在则清空
-    CREATE = 0b0010_0000_0000,
-    /// 先清空文件，再交给用户
-    TRUNC  = 0b0100_0000_0000,
-}
+    fn offset(&self) -> Option<usize> {
+        Some(self.inner.exclusive_access().offset)
+    }
 
-impl OpenFlag {
-    // enumflags2拒绝值为0的标志
-    /// 只读
-    pub const RDONLY: u32 = 0b0000_0000_0000;
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
 
-    #[inline]
-    pub fn read_only() -> BitFlags<OpenFlag> {
-        BitFlags::from_bits_truncate(Self::RDONLY)
+    fn file_id(&self) -> Option<FileId> {
+        Some((self.fs_addr, self.inode.id()))
     }
-}
 
-/// `path`为标准路径
-pub fn open_dir(path: &str) -> Result<Arc<OSInode>, vfs::Error> {
-    open_dir_inode(path).map(|inode| Arc::new(OSInode::new(true, true, inode)))
-}
+    fn getdents(&self, buf: UserBuffer) -> Option<usize> {
+        if self.inode.kind() != DirEntryType::Directory {
+            return Some(0);
+        }
+        let mut inner = self.inner.exclusive_access();
+        let mut records = DirentBuf::new(buf.len());
+        // 放不下的目录项留待下次读取
+        match self
+            .inode
+            .read_dir(inner.offset, &mut |dirent| records.push(dirent))
+        {
+            Ok(offset) => inner.offset = offset,
+            Err(e) => log::error!("read dir inode {}: {e:?}", self.inode.id()),
+        }
+        records.copy_out(buf)
+    }
 
-fn open_dir_inode(path: &str) -> Result<Inode, vfs::Error> {
-    if path == "/" {
-        Ok(ROOT.clone())
-    } else {
-        let inode = ROOT
-            .find(path.root_relative().unwrap(), &FS.exclusive_access())
-            .ok_or(vfs::Error::NotFound)?;
-        if inode.kind() != DirEntryType::Directory {
-            return Err(vfs::Error::NotADirectory);
+    fn getdents_plus(&self, buf: UserBuffer) -> Option<usize> {
+        if self.inode.kind() != DirEntryType::Directory {
+            return Some(0);
+        }
+        let mut inner = self.inner.exclusive_access();
+        let mut records = DirentBuf::new(buf.len());
+        match self.inode.read_dir_plus(inner.offset, &mut |dirent, stat| {
+            records.push_plus(dirent, stat)
+        }) {
+            Ok(offset) => inner.offset = offset,
+            Err(e) => log::error!("read dir inode {}: {e:?}", self.inode.id()),
         }
-        Ok(inode)
+        records.copy_out(buf)
     }
-}
 
-pub fn open(path: &str, flags: BitFlags<OpenFlag>) -> Option<Arc<OSInode>> {
-    let [readable, writable] = if flags.is_empty() {
-        [true, false]
-    } else if flags.contains(OpenFlag::WRONLY) {
-        [false, true]
-    } else {
-        [true, true]
-    };
-    let create = flags.contains(OpenFlag::CREATE);
-
-    let mut fs = FS.exclusive_access();
-    let Some(relat_path) = path.root_relative() else {
-        return Some(Arc::new(OSInode::new(readable, writable, ROOT.clone())));
-    };
-
-    ROOT.find(relat_path, &fs)
-        .map(|mut inode| {
-            if create || flags.contains(OpenFlag::TRUNC) {
-                inode.clear(&mut fs);
-            }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
-        .or_else(|| {
-            create
-                .then(|| {
-                    if let Some((parent, fname)) = relat_path.rsplit_once('/') {
-                        let parent = ROOT.find(parent, &fs)?;
-                        parent.create_file(fname, &mut fs)
-                    } else {
-                        ROOT.create_file(relat_path, &mut fs)
-                    }
-                    .ok()
-                    .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
-                })
-                .flatten()
-        })
-}
+    fn mkdir(&self, name: &str) -> Result<(), vfs::Error> {
+        self.inode.mkdir(name)
+    }
 
-#[allow(unused_variables)]
-#[inline]
-pub fn link(old_path: &str, new_path: &str) -> Option<()> {
-    None
+    fn unlink(&self, name: &str) -> Result<(), vfs::Error> {
+        let unlinked = self
+            .inode
+            .lookup(name)
+            .ok()
+            .filter(|inode| inode.kind() == DirEntryType::Regular);
+        // 回收的索引节点号可能分给新文件
+        image::clear();
+        self.inode.unlink(name)?;
+        if let Some(inode) = unlinked {
+            let blocks = quota_blocks(&*inode, inode.size());
+            self.quota.charge(inode.owner(), blocks, 0);
+        }
+        Ok(())
+    }
+
+    fn rmdir(&self, name: &str) -> Result<(), vfs::Error> {
+        self.inode.rmdir(name)
+    }
 }
 
-// /// # 参数
-// ///
-// /// `old_path`和`new_path`都是标准路径。
-// pub fn rename(old_path: &str, new_path: &str) -> Option<()> {
-//     // let fs = FS.exclusive_access();
-//     //
-//     // if new_path.starts_with(old_path) {
-//     //     // 倒反天罡: /foo/bar -> /foo/bar/zoo
-//     //     return None;
-//     // }
-//     //
-//     // let old_parent = old_path.parent()?;
-//     // let new_parent = new_path.parent();
-//     //
-//     // if old_parent == new_path {
-//     //     // 原地命名，跳过
-//     //     return None;
-//     // }
-//     //
-//     // if Some(old_parent) == new_parent {
-//     //     // 同一目录下的重命名
-//     //     let old_name = old_path
-//     //         .file_name()
-//     //         .expect("it has parent, should has file name");
-//     //     let new_name = new_path.file_name();
-//     //     let old_parent = ROOT.find(old_parent.root_relative()?, &fs)?;
-//     // }
-//     //
-//     // let old_parent = ROOT.find(old_parent.root_relative()?, &fs)?;
-//     // let new_parent = match new_path.parent().and_then(|p| p.root_relative()) {
-//     //     Some(parent) => &ROOT.find(parent, &fs)?,
-//     //     None => &ROOT,
-//     // };
-//
-//     todo!()
-// }
+/// `inode`长为`size`字节时计入配额的块数
+fn quota_blocks(inode: &dyn VfsInode, size: usize) -> u64 {
+    DiskQuota::blocks(inode.allocated(size) as u64)
+}
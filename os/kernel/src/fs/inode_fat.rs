@@ -0,0 +1,40 @@
+//! 挂载FAT卷，目前只能加载一个FAT卷
+
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use block_dev::BlockDevice;
+use fat::{FatFileSystem, FatVfs};
+
+use super::mount::FileSystem;
+use super::registry::FileSystemType;
+use super::{DiskFs, BLOCK_CACHE};
+use crate::timer;
+
+/// 文件系统类型在注册表中的名称
+pub const NAME: &str = "fat";
+
+/// 扇区缓存与根目录都是全局唯一的，加载过一次后不能再加载
+static LOADED: AtomicBool = AtomicBool::new(false);
+
+pub struct FatType;
+
+impl FileSystemType for FatType {
+    fn mount(&self, dev: Arc<dyn BlockDevice>) -> Option<Arc<dyn FileSystem>> {
+        if LOADED.load(Ordering::Relaxed) {
+            log::warn!("only one FAT volume can be mounted");
+            return None;
+        }
+        fat::set_block_cache(BLOCK_CACHE.clone());
+        let mut fs = FatFileSystem::load(&dev)?;
+        LOADED.store(true, Ordering::Relaxed);
+        if !fs.is_clean() {
+            log::warn!("FAT volume was not cleanly unmounted");
+        }
+        fs.mark_dirty();
+        fs.set_clock(|| (timer::realtime_us() / 1_000_000) as u64);
+        // 修改留在块缓存中，由时钟中断在稍后统一写回
+        fs.set_flush_hook(super::schedule_writeback);
+        Some(Arc::new(DiskFs::new(NAME, Arc::new(FatVfs::new(fs)))))
+    }
+}
@@ -0,0 +1,274 @@
+//! # 文件系统事件通知
+//!
+//! 仿Linux的inotify而从简：以标准路径登记监视，监视目录时还报告其下各项的事件。
+//! 修改文件系统的系统调用成功后调用[`notify`]或[`notify_rename`]投递事件，
+//! 读取监视实例的描述符得到[`CInotifyEvent`]记录。
+//!
+//! 监视跟随路径而非文件：路径本身被删除或移走时撤销监视。
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use vfs::inotify::{
+    IN_DELETE, IN_DELETE_SELF, IN_IGNORED, IN_ISDIR, IN_MODIFY, IN_MOVED_FROM, IN_MOVED_TO,
+    IN_MOVE_SELF, IN_Q_OVERFLOW,
+};
+use vfs::CInotifyEvent;
+
+use super::{File, FileKind};
+use crate::memory::UserBuffer;
+use crate::path::Path;
+use crate::sync::UpCell;
+use crate::task;
+
+/// 与Linux相同，读取监视实例队列中事件记录的总字节数
+pub const FIONREAD: usize = 0x541b;
+
+/// 每个监视实例至多排队的事件数，再多则丢弃并排入一个[`IN_Q_OVERFLOW`]
+const MAX_QUEUED_EVENTS: usize = 256;
+
+/// 所有监视实例，投递事件时逐一查看
+static INSTANCES: UpCell<Vec<Weak<Inotify>>> = UpCell::new(Vec::new());
+
+/// 下一次重命名的cookie，从1起
+static NEXT_COOKIE: AtomicU32 = AtomicU32::new(1);
+
+/// 监视实例，持有若干监视与待读的事件
+#[derive(Debug)]
+pub struct Inotify {
+    non_block: bool,
+    inner: UpCell<InotifyInner>,
+}
+
+#[derive(Debug, Default)]
+struct InotifyInner {
+    watches: Vec<Watch>,
+    /// 最近分配的监视描述符，从1起
+    last_wd: i32,
+    events: VecDeque<Event>,
+}
+
+#[derive(Debug)]
+struct Watch {
+    wd: i32,
+    /// 标准路径
+    path: String,
+    mask: u32,
+}
+
+#[derive(Debug)]
+struct Event {
+    header: CInotifyEvent,
+    name: String,
+}
+
+pub fn new(non_block: bool) -> Arc<Inotify> {
+    let inotify = Arc::new(Inotify {
+        non_block,
+        inner: UpCell::new(InotifyInner::default()),
+    });
+    INSTANCES.exclusive_session(|instances| {
+        instances.retain(|i| i.strong_count() > 0);
+        instances.push(Arc::downgrade(&inotify));
+    });
+    inotify
+}
+
+impl Inotify {
+    /// 监视标准路径`path`上的`mask`事件，返回监视描述符；已监视该路径时改用新的`mask`
+    pub fn add_watch(&self, path: &str, mask: u32) -> i32 {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(watch) = inner.watches.iter_mut().find(|w| w.path == path) {
+            watch.mask = mask;
+            return watch.wd;
+        }
+        inner.last_wd += 1;
+        let wd = inner.last_wd;
+        inner.watches.push(Watch {
+            wd,
+            path: String::from(path),
+            mask,
+        });
+        wd
+    }
+
+    /// 撤销监视`wd`并排入[`IN_IGNORED`]，`wd`不存在时返回`false`
+    pub fn rm_watch(&self, wd: i32) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let Some(i) = inner.watches.iter().position(|w| w.wd == wd) else {
+            return false;
+        };
+        inner.watches.remove(i);
+        inner.push(wd, IN_IGNORED, 0, "");
+        true
+    }
+
+    /// 标准路径`path`上发生了事件`mask`，按监视排入队列
+    fn deliver(&self, path: &str, mask: u32, cookie: u32) {
+        let event = mask & !IN_ISDIR;
+        let parent = path.parent_file();
+        let mut inner = self.inner.exclusive_access();
+
+        let mut i = 0;
+        while i < inner.watches.len() {
+            let watch = &inner.watches[i];
+            let wd = watch.wd;
+            if watch.path == path {
+                // 监视的路径本身
+                let self_event = match event {
+                    IN_MODIFY => IN_MODIFY,
+                    IN_DELETE => IN_DELETE_SELF,
+                    IN_MOVED_FROM => IN_MOVE_SELF,
+                    _ => 0,
+                };
+                if watch.mask & self_event != 0 {
+                    inner.push(wd, self_event, 0, "");
+                }
+                if self_event & (IN_DELETE_SELF | IN_MOVE_SELF) != 0 {
+                    inner.watches.remove(i);
+                    inner.push(wd, IN_IGNORED, 0, "");
+                    continue;
+                }
+            } else if let Some((_, name)) = parent.filter(|&(dir, _)| watch.path == dir) {
+                // 监视的目录下的一项
+                if watch.mask & event != 0 {
+                    inner.push(wd, mask, cookie, name);
+                }
+            }
+            i += 1;
+        }
+    }
+}
+
+impl InotifyInner {
+    fn push(&mut self, wd: i32, mask: u32, cookie: u32, name: &str) {
+        // 与队尾相同的事件合并，如连续的写入
+        if let Some(last) = self.events.back() {
+            let header = &last.header;
+            if (header.wd, header.mask, header.cookie) == (wd, mask, cookie) && last.name == name {
+                return;
+            }
+        }
+        let (wd, mask, cookie, name) = if self.events.len() < MAX_QUEUED_EVENTS {
+            (wd, mask, cookie, name)
+        } else if self.events.len() == MAX_QUEUED_EVENTS {
+            (-1, IN_Q_OVERFLOW, 0, "")
+        } else {
+            return;
+        };
+        self.events.push_back(Event {
+            header: CInotifyEvent {
+                wd,
+                mask,
+                cookie,
+                len: CInotifyEvent::name_space(name.len()) as u32,
+            },
+            name: String::from(name),
+        });
+    }
+
+    /// 队列中事件记录的总字节数
+    fn queued_bytes(&self) -> usize {
+        self.events.iter().map(|e| e.header.record_len()).sum()
+    }
+}
+
+impl File for Inotify {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    /// 尽可能多地读出完整的事件记录，返回读到的字节数；
+    /// `buf`连一条记录都放不下，或非阻塞而无事件时失败
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        loop {
+            let mut inner = self.inner.exclusive_access();
+            if inner.events.is_empty() {
+                if self.non_block {
+                    return usize::MAX;
+                }
+                if task::current_signal_pending() {
+                    task::interrupt_current_syscall();
+                    return 0;
+                }
+                drop(inner);
+                task::suspend_current_and_run_next();
+                continue;
+            }
+
+            let mut bytes = vec![0; buf.len()];
+            let mut len = 0;
+            while let Some(event) = inner.events.front() {
+                let Some(reclen) = event.header.encode(&event.name, &mut bytes[len..]) else {
+                    break;
+                };
+                len += reclen;
+                inner.events.pop_front();
+            }
+            if len == 0 {
+                return usize::MAX;
+            }
+            for (b, &eb) in buf.iter_mut().zip(&bytes[..len]) {
+                *b = eb;
+            }
+            return len;
+        }
+    }
+
+    /// 支持[`FIONREAD`]，返回值即可读的字节数
+    fn ioctl(&self, request: usize, _arg: usize) -> Option<usize> {
+        (request == FIONREAD).then(|| self.inner.exclusive_access().queued_bytes())
+    }
+
+    fn kind(&self) -> FileKind {
+        FileKind::Inotify
+    }
+
+    fn as_inotify(&self) -> Option<&Inotify> {
+        Some(self)
+    }
+}
+
+/// 是否有监视实例，没有时调用者可省去为投递事件所做的查询
+pub fn active() -> bool {
+    INSTANCES
+        .exclusive_access()
+        .iter()
+        .any(|i| i.strong_count() > 0)
+}
+
+/// 标准路径`path`上发生了事件`mask`，投递给监视它或其所在目录的实例
+pub fn notify(path: &str, mask: u32) {
+    deliver(path, mask, 0);
+}
+
+/// `old_path`重命名为`new_path`，两者都是标准路径，重命名已经完成
+pub fn notify_rename(old_path: &str, new_path: &str) {
+    if !active() {
+        return;
+    }
+    let is_dir = if super::open_dir(new_path).is_ok() {
+        IN_ISDIR
+    } else {
+        0
+    };
+    let cookie = NEXT_COOKIE.fetch_add(1, Ordering::Relaxed);
+    deliver(old_path, IN_MOVED_FROM | is_dir, cookie);
+    deliver(new_path, IN_MOVED_TO | is_dir, cookie);
+}
+
+fn deliver(path: &str, mask: u32, cookie: u32) {
+    // 不在持有实例表时借用各实例
+    let instances: Vec<_> = INSTANCES
+        .exclusive_access()
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect();
+    for inotify in instances {
+        inotify.deliver(path, mask, cookie);
+    }
+}
@@ -13,17 +13,100 @@
 //! 一个进程可以访问多个文件，并通过**文件描述符表**管理。
 //! 表中的描述符表示带有特定读写属性的I/O资源(文件/目录/socket等)。
 
+mod devfs;
 pub mod eventfd;
 mod inode;
+#[cfg(feature = "easy-fs")]
+mod inode_easy;
+#[cfg(feature = "fat")]
+mod inode_fat;
+pub mod inotify;
+pub mod mount;
 mod pipe;
+mod procfs;
+pub mod quota;
+pub mod registry;
+#[cfg(feature = "squashfs")]
+mod squash;
 pub mod stdio;
 
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::fmt::Debug;
 
-use vfs::{DirEntryType, Stat};
+use block_dev::{BlockCache, FifoBlockCache};
+use spin::Lazy;
+use vfs::{CDirEntry, CDirEntryPlus, DirEntryType, Stat};
 
+pub use self::registry::register;
 pub use self::{inode::*, pipe::*};
+use crate::cmdline;
+use crate::config::BLOCK_CACHE_CAPACITY;
+use crate::drivers::BLOCK_DEVICE;
 use crate::memory::UserBuffer;
+use crate::sync::UpCell;
+use crate::timer::TICKS_PRE_SEC;
+
+/// 所有文件系统共享的块缓存，在加载文件系统前注入
+pub static BLOCK_CACHE: Lazy<Arc<dyn BlockCache>> =
+    Lazy::new(|| Arc::new(FifoBlockCache::new(BLOCK_CACHE_CAPACITY)));
+
+/// 登记编入的文件系统，将根块设备上的文件系统挂载为根目录，
+/// 其类型由命令行的`rootfstype=`给出，默认为FAT，再挂载进程文件系统于`/proc`、设备文件系统于`/dev`
+pub fn init() {
+    registry::init();
+
+    let fstype = cmdline::get("rootfstype").unwrap_or("fat");
+    let root = registry::find(fstype)
+        .unwrap_or_else(|| panic!("root filesystem type `{fstype}` is not built in"))
+        .mount(BLOCK_DEVICE.clone())
+        .unwrap_or_else(|| panic!("no {fstype} filesystem on the root block device"));
+    mount::mount("/", root, Some(BLOCK_DEVICE.clone())).expect("root is mounted only once");
+    mount::mount("/proc", Arc::new(procfs::ProcFs), None).expect("/proc is mounted only once");
+    mount::mount("/dev", Arc::new(devfs::DevFs), None).expect("/dev is mounted only once");
+}
+
+/// 关机前卸载所有文件系统，再写回块缓存中余下的脏块
+pub fn unmount_all() {
+    mount::unmount_all();
+    sync();
+}
+
+/// 延迟写回的修改至多滞留的时钟中断数
+const WRITEBACK_DELAY: usize = 5 * TICKS_PRE_SEC;
+
+/// 距后台写回尚余的时钟中断数，`None`表示没有待写回的修改
+static WRITEBACK: UpCell<Option<usize>> = UpCell::new(None);
+
+/// 将块缓存中的脏块写回块设备
+pub fn sync() {
+    *WRITEBACK.exclusive_access() = None;
+    BLOCK_CACHE.sync_all();
+}
+
+/// 文件系统有了延迟写回的修改，安排在[`WRITEBACK_DELAY`]后写回；
+/// 已安排的不会因新的修改而推迟
+pub fn schedule_writeback() {
+    WRITEBACK.exclusive_session(|due| {
+        due.get_or_insert(WRITEBACK_DELAY);
+    });
+}
+
+/// 记一个时钟中断，返回是否该后台写回了
+pub fn writeback_due() -> bool {
+    WRITEBACK.exclusive_session(|due| {
+        let Some(ticks) = due else {
+            return false;
+        };
+        if *ticks > 0 {
+            *ticks -= 1;
+            return false;
+        }
+        *due = None;
+        true
+    })
+}
 
 /// 内存与存储设备之间的数据交换通道
 pub trait File: Debug + Send + Sync {
@@ -45,18 +128,46 @@ pub trait File: Debug + Send + Sync {
         0
     }
 
+    /// 从字节偏移`offset`处读入`buf`，返回读到的字节数，不改变当前偏移量。供文件映射使用
+    #[allow(unused_variables)]
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        0
+    }
+
+    /// 在字节偏移`offset`处写入`buf`，返回写入的字节数，不改变当前偏移量
+    #[allow(unused_variables)]
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        0
+    }
+
+    /// 从当前偏移量读至末尾，供内核加载程序
+    fn read_all(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
     fn stat(&self) -> Stat {
         Stat {
             mode: DirEntryType::Regular,
             block_size: 0,
             blocks: 0,
             size: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
         }
     }
 
+    /// 从当前偏移量起，将目录项编码为[`CDirEntry`]记录写入`buf`，返回写入的字节数；
+    /// `buf`连下一条记录都放不下时返回`None`
     #[allow(unused_variables)]
-    fn getdents(&self, buf: UserBuffer, len: usize) -> usize {
-        0
+    fn getdents(&self, buf: UserBuffer) -> Option<usize> {
+        Some(0)
+    }
+
+    /// 同[`getdents`](File::getdents)，记录为附带各项状态的[`CDirEntryPlus`]
+    #[allow(unused_variables)]
+    fn getdents_plus(&self, buf: UserBuffer) -> Option<usize> {
+        Some(0)
     }
 
     #[allow(unused_variables)]
@@ -74,8 +185,251 @@ pub trait File: Debug + Send + Sync {
         Err(vfs::Error::Unsupported)
     }
 
+    /// 设备相关的控制请求，不支持`request`时返回`None`
     #[allow(unused_variables)]
-    fn rename(&self, old_name: &str, newpath: &str) -> Result<(), vfs::Error> {
-        Err(vfs::Error::Unsupported)
+    fn ioctl(&self, request: usize, arg: usize) -> Option<usize> {
+        None
+    }
+
+    /// 文件是管道时返回之，供`fcntl`调整管道的容量
+    fn as_pipe(&self) -> Option<&Pipe> {
+        None
+    }
+
+    /// 文件是监视实例时返回之，供登记与撤销监视
+    fn as_inotify(&self) -> Option<&inotify::Inotify> {
+        None
+    }
+
+    /// 记下此后访问文件的方式，供预读与块缓存参考；不经块缓存的文件返回`false`
+    #[allow(unused_variables)]
+    fn fadvise(&self, advice: Advice) -> bool {
+        false
+    }
+
+    /// 以`whence`为基准将偏移量移动`offset`字节，返回新的偏移量，可以越过文件末尾
+    #[allow(unused_variables)]
+    fn lseek(&self, offset: isize, whence: Whence) -> Result<usize, SeekError> {
+        Err(SeekError::Unseekable)
+    }
+
+    /// 将文件截断或扩展至`size`字节，扩展出的部分读出为0；不支持截断的文件返回`false`
+    #[allow(unused_variables)]
+    fn truncate(&self, size: usize) -> bool {
+        false
+    }
+
+    /// 文件的最大字节数，写入或截断越过它时报`EFBIG`
+    fn max_size(&self) -> usize {
+        usize::MAX
+    }
+
+    /// 写入或扩展至字节偏移`end`是否超出文件属主的磁盘配额，超出时报`EDQUOT`；
+    /// 不计配额的文件总是`false`
+    #[allow(unused_variables)]
+    fn quota_exceeded(&self, end: usize) -> bool {
+        false
+    }
+
+    /// 设置最后访问与最后修改时间，自UNIX纪元的秒数，为`None`的保持不变；
+    /// 不支持记录时间的文件返回`false`
+    #[allow(unused_variables)]
+    fn set_times(&self, atime: Option<u64>, mtime: Option<u64>) -> bool {
+        false
+    }
+
+    /// 文件的种类，默认按[`stat`](File::stat)区分目录与普通文件
+    fn kind(&self) -> FileKind {
+        if self.stat().mode == DirEntryType::Directory {
+            FileKind::Directory
+        } else {
+            FileKind::Regular
+        }
+    }
+
+    /// 当前偏移量，没有偏移量的文件返回`None`
+    fn offset(&self) -> Option<usize> {
+        None
+    }
+
+    /// 打开时的标准路径，不经路径打开的文件返回`None`
+    fn path(&self) -> Option<&str> {
+        None
+    }
+
+    /// 文件在所有挂载的文件系统间的标识，同一文件的各个硬链接相同；
+    /// 不在文件系统中的文件返回`None`
+    fn file_id(&self) -> Option<FileId> {
+        None
+    }
+}
+
+/// 文件系统的地址与其中的索引节点号
+pub type FileId = (usize, u64);
+
+/// 打开的文件的种类，供`/proc/<pid>/fd`列出
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Regular,
+    Directory,
+    Pipe,
+    /// 标准输入输出所在的终端
+    Tty,
+    /// `/dev`下除终端外的字符设备
+    CharDevice,
+    EventFd,
+    /// 文件系统事件的监视实例
+    Inotify,
+}
+
+impl FileKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Regular => "file",
+            Self::Directory => "dir",
+            Self::Pipe => "pipe",
+            Self::Tty => "tty",
+            Self::CharDevice => "chr",
+            Self::EventFd => "eventfd",
+            Self::Inotify => "inotify",
+        }
+    }
+}
+
+/// 访问打开的文件的方式，记在打开的文件上，`dup`与`fork`得到的描述符共享
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Advice {
+    /// 适度预读
+    #[default]
+    Normal = 0,
+    /// 随机访问，不预读
+    Random = 1,
+    /// 顺序访问，加倍预读
+    Sequential = 2,
+    /// 访问过的数据不会再用，读写后立即写回并丢弃其缓存
+    DontNeed = 4,
+}
+
+impl Advice {
+    /// 同Linux的`POSIX_FADV_*`，不支持的取值返回`None`
+    pub fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Random),
+            2 => Some(Self::Sequential),
+            4 => Some(Self::DontNeed),
+            _ => None,
+        }
+    }
+
+    /// 每次预读的字节数
+    pub fn readahead_bytes(self) -> usize {
+        match self {
+            Self::Normal => READAHEAD_BYTES,
+            Self::Sequential => 2 * READAHEAD_BYTES,
+            Self::Random | Self::DontNeed => 0,
+        }
+    }
+}
+
+/// 默认的预读窗口
+const READAHEAD_BYTES: usize = 2048;
+
+/// `lseek`移动偏移量的基准
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whence {
+    /// 文件开头
+    Set = 0,
+    /// 当前偏移量
+    Cur = 1,
+    /// 文件末尾
+    End = 2,
+}
+
+impl Whence {
+    /// 同Linux的`SEEK_*`，不支持的取值返回`None`
+    pub fn from_raw(raw: usize) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Set),
+            1 => Some(Self::Cur),
+            2 => Some(Self::End),
+            _ => None,
+        }
+    }
+
+    /// 当前偏移量为`current`、文件大小为`size`时，移动`offset`字节后的偏移量
+    pub fn resolve(self, current: usize, size: usize, offset: isize) -> Result<usize, SeekError> {
+        let base = match self {
+            Self::Set => 0,
+            Self::Cur => current,
+            Self::End => size,
+        };
+        base.checked_add_signed(offset)
+            .filter(|&pos| isize::try_from(pos).is_ok())
+            .ok_or(SeekError::Invalid)
+    }
+}
+
+/// 无法移动偏移量的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekError {
+    /// 文件是管道等不可定位的对象
+    Unseekable,
+    /// 新的偏移量为负或溢出
+    Invalid,
+}
+
+/// 先在内核中将目录项编码为变长记录，再一次写入用户缓冲区
+struct DirentBuf {
+    bytes: Vec<u8>,
+    len: usize,
+    /// 曾有记录因放不下而被拒绝
+    full: bool,
+}
+
+impl DirentBuf {
+    /// `cap`为用户缓冲区的字节数
+    fn new(cap: usize) -> Self {
+        Self {
+            bytes: vec![0; cap],
+            len: 0,
+            full: false,
+        }
+    }
+
+    /// 追加一条记录，放不下时返回`false`
+    fn push(&mut self, dirent: &vfs::DirEntry) -> bool {
+        let reclen = CDirEntry::encode(dirent, &mut self.bytes[self.len..]);
+        self.advance(reclen)
+    }
+
+    /// 追加一条附带状态的记录，放不下时返回`false`
+    fn push_plus(&mut self, dirent: &vfs::DirEntry, stat: &Stat) -> bool {
+        let reclen = CDirEntryPlus::encode(dirent, stat, &mut self.bytes[self.len..]);
+        self.advance(reclen)
+    }
+
+    fn advance(&mut self, reclen: Option<usize>) -> bool {
+        match reclen {
+            Some(reclen) => {
+                self.len += reclen;
+                true
+            }
+            None => {
+                self.full = true;
+                false
+            }
+        }
+    }
+
+    /// 返回写入的字节数；缓冲区连一条记录都放不下时返回`None`
+    fn copy_out(self, mut buf: UserBuffer) -> Option<usize> {
+        if self.full && self.len == 0 {
+            return None;
+        }
+        for (b, &db) in buf.iter_mut().zip(&self.bytes[..self.len]) {
+            *b = db;
+        }
+        Some(self.len)
     }
 }
@@ -0,0 +1,171 @@
+//! # 挂载表
+//!
+//! 启动时根块设备上的文件系统挂载为根目录，其余文件系统挂载在其中已有的目录上，
+//! 挂载点之下的路径都交给挂载的文件系统解析。
+//!
+//! 同一块设备只能挂载一次。文件系统中还有打开的文件或进程的工作目录时不能卸载。
+
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use block_dev::BlockDevice;
+use enumflags2::BitFlags;
+
+use super::quota::Quota;
+use super::{File, OpenFlag};
+use crate::memory::image;
+use crate::sync::UpCell;
+use crate::task::manager;
+
+static MOUNTS: UpCell<Vec<Mount>> = UpCell::new(Vec::new());
+
+/// 可以挂载的文件系统
+pub trait FileSystem: Send + Sync {
+    /// 文件系统类型在注册表中的名称
+    fn name(&self) -> &'static str;
+
+    /// 打开标准路径`path`，`relat_path`为其在挂载点之下的部分，空串即挂载点本身
+    fn open(
+        &self,
+        path: &str,
+        relat_path: &str,
+        flags: BitFlags<OpenFlag>,
+    ) -> Result<Arc<dyn File + Send + Sync>, vfs::Error>;
+
+    /// 为`old_relat`建立硬链接`new_relat`，两者都是挂载点之下的相对路径；
+    /// 不支持硬链接的文件系统报[`vfs::Error::Unsupported`]
+    #[allow(unused_variables)]
+    fn link(&self, old_relat: &str, new_relat: &str) -> Result<(), vfs::Error> {
+        Err(vfs::Error::Unsupported)
+    }
+
+    /// 将`old_relat`移至`new_relat`，两者都是挂载点之下的相对路径；
+    /// `new_relat`是已有的目录时移入其下，名称不变
+    #[allow(unused_variables)]
+    fn rename(&self, old_relat: &str, new_relat: &str) -> Result<(), vfs::Error> {
+        Err(vfs::Error::Unsupported)
+    }
+
+    /// 关机前写回所有修改，并在卷上标记为正常卸载；只读的文件系统无事可做
+    fn unmount(&self) {}
+
+    /// 按用户统计占用的磁盘文件系统返回其配额
+    fn quota(&self) -> Option<&Quota> {
+        None
+    }
+
+    /// 是否还有打开的文件引用其中的索引节点
+    fn busy(&self) -> bool {
+        false
+    }
+}
+
+struct Mount {
+    /// 标准路径
+    target: String,
+    /// 文件系统所在的块设备，内存中的文件系统没有
+    dev: Option<Arc<dyn BlockDevice>>,
+    fs: Arc<dyn FileSystem>,
+}
+
+/// 将块设备`dev`上的`fs`挂载到标准路径`target`上，同一挂载点、同一块设备都只能挂载一次
+pub fn mount(
+    target: &str,
+    fs: Arc<dyn FileSystem>,
+    dev: Option<Arc<dyn BlockDevice>>,
+) -> Result<(), vfs::Error> {
+    let mut mounts = MOUNTS.exclusive_access();
+    let dev_mounted = dev
+        .as_ref()
+        .is_some_and(|dev| mounts.iter().any(|m| m.is_on(dev)));
+    if dev_mounted || mounts.iter().any(|m| m.target == target) {
+        return Err(vfs::Error::AlreadyExists);
+    }
+    mounts.push(Mount {
+        target: String::from(target),
+        dev,
+        fs,
+    });
+    Ok(())
+}
+
+/// 块设备`dev`上是否已挂载了文件系统
+pub fn is_mounted(dev: &Arc<dyn BlockDevice>) -> bool {
+    MOUNTS.exclusive_access().iter().any(|m| m.is_on(dev))
+}
+
+impl Mount {
+    fn is_on(&self, dev: &Arc<dyn BlockDevice>) -> bool {
+        self.dev
+            .as_ref()
+            .is_some_and(|d| Arc::as_ptr(d).cast::<()>() == Arc::as_ptr(dev).cast::<()>())
+    }
+}
+
+/// 标准路径`path`是否为`target`本身或在其之下
+fn is_within(path: &str, target: &str) -> bool {
+    path.strip_prefix(target)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// 无法卸载的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmountError {
+    /// 目标不是挂载点
+    NotMounted,
+    /// 目标是根目录，其下还有别的挂载点，或其中还有打开的文件或进程的工作目录
+    Busy,
+}
+
+/// 卸载挂载在标准路径`target`上的文件系统，写回其所有修改
+pub fn unmount(target: &str) -> Result<(), UnmountError> {
+    let cwd_inside = manager::processes()
+        .iter()
+        .any(|process| is_within(&process.inner().exclusive_access().cwd, target));
+
+    let fs = MOUNTS.exclusive_session(|mounts| {
+        let i = mounts
+            .iter()
+            .position(|m| m.target == target)
+            .ok_or(UnmountError::NotMounted)?;
+        let nested = mounts
+            .iter()
+            .any(|m| m.target != target && is_within(&m.target, target));
+        if target == "/" || nested || cwd_inside || mounts[i].fs.busy() {
+            return Err(UnmountError::Busy);
+        }
+        Ok(mounts.remove(i).fs)
+    })?;
+
+    log::info!("[kernel] Unmounting {} at {target}", fs.name());
+    fs.unmount();
+    super::sync();
+    // 文件系统的地址可能为此后挂载的复用
+    image::clear();
+    Ok(())
+}
+
+/// 标准路径`path`落在某个挂载点之下时，返回最深的挂载及其下的相对路径
+pub fn resolve(path: &str) -> Option<(Arc<dyn FileSystem>, &str)> {
+    MOUNTS
+        .exclusive_access()
+        .iter()
+        .filter_map(|m| {
+            // 根目录的挂载点去掉末尾的`/`后为空串，可匹配任意标准路径
+            let rest = path.strip_prefix(m.target.trim_end_matches('/'))?;
+            (rest.is_empty() || rest.starts_with('/')).then(|| (m, &rest[rest.len().min(1)..]))
+        })
+        .max_by_key(|(m, _)| m.target.len())
+        .map(|(m, rest)| (m.fs.clone(), rest))
+}
+
+/// 自最深的挂载点起依次卸载所有文件系统，仅在关机时调用
+pub fn unmount_all() {
+    let mut mounts = core::mem::take(&mut *MOUNTS.exclusive_access());
+    mounts.sort_unstable_by_key(|m| m.target.len());
+    for m in mounts.iter().rev() {
+        log::info!("[kernel] Unmounting {} at {}", m.fs.name(), m.target);
+        m.fs.unmount();
+    }
+}
@@ -1,6 +1,13 @@
+use alloc::boxed::Box;
 use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::slice;
 
-use super::File;
+use super::{File, FileKind};
+use crate::config::{PAGE_SIZE, PIPE_MAX_SIZE};
+use crate::memory::address::PhysAddr;
+use crate::memory::frame_allocator::{self, Frame};
 use crate::memory::UserBuffer;
 use crate::sync::UpCell;
 use crate::task;
@@ -12,15 +19,34 @@ pub struct Pipe {
     buffer: Arc<UpCell<PipeRingBuffer>>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct PipeRingBuffer {
-    base: [u8; PipeRingBuffer::CAP],
+    storage: Storage,
     head: usize,
     tail: usize,
     status: RingBufferStatus,
     write_end: Weak<Pipe>,
 }
 
+/// 环形缓冲区的存储。默认的小缓冲区取自内核堆，
+/// 超过一页的则取自连续的物理页帧，以免大管道挤占有限的内核堆
+#[derive(Debug)]
+enum Storage {
+    Heap(Box<[u8]>),
+    Frames(Vec<Frame>),
+}
+
+/// 无法调整管道容量的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeError {
+    /// 超过了[`PIPE_MAX_SIZE`]
+    TooLarge,
+    /// 缓冲区中的数据多于新的容量
+    Busy,
+    /// 物理页帧不足
+    NoMemory,
+}
+
 #[derive(Debug, Default, PartialEq, Eq)]
 enum RingBufferStatus {
     Full,
@@ -40,6 +66,14 @@ impl File for Pipe {
         self.writable
     }
 
+    fn as_pipe(&self) -> Option<&Pipe> {
+        Some(self)
+    }
+
+    fn kind(&self) -> FileKind {
+        FileKind::Pipe
+    }
+
     fn read(&self, mut buf: UserBuffer) -> usize {
         assert!(self.readable());
         let buf_len = buf.len();
@@ -54,6 +88,13 @@ impl File for Pipe {
                 if ring_buffer.write_end_closed() {
                     return read_len;
                 }
+                // 已读到数据则如数返回，否则打断系统调用
+                if task::current_signal_pending() {
+                    if read_len == 0 {
+                        task::interrupt_current_syscall();
+                    }
+                    return read_len;
+                }
                 drop(ring_buffer);
                 // 管道缓冲区的大小是有限的，
                 // 一次可能无法满足`Buffer`的需求量
@@ -86,6 +127,12 @@ impl File for Pipe {
             let writables = ring_buffer.hint_writables();
 
             if writables == 0 {
+                if task::current_signal_pending() {
+                    if written_len == 0 {
+                        task::interrupt_current_syscall();
+                    }
+                    return written_len;
+                }
                 drop(ring_buffer);
                 task::suspend_current_and_run_next();
                 continue;
@@ -125,11 +172,33 @@ impl Pipe {
             buffer,
         }
     }
+
+    /// 缓冲区的容量，以字节计
+    pub fn capacity(&self) -> usize {
+        self.buffer.exclusive_access().capacity()
+    }
+
+    /// 将缓冲区的容量调整为`size`字节，向上取整到页，返回调整后的容量。
+    /// 已缓冲的数据原样保留，两端共享同一缓冲区，故经任一端调整皆可
+    pub fn resize(&self, size: usize) -> Result<usize, ResizeError> {
+        if size > PIPE_MAX_SIZE {
+            return Err(ResizeError::TooLarge);
+        }
+        let cap = size.max(1).next_multiple_of(PAGE_SIZE);
+        self.buffer.exclusive_access().resize(cap)?;
+        Ok(cap)
+    }
 }
 
 impl PipeRingBuffer {
     pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
-        let buffer = Arc::new(UpCell::new(PipeRingBuffer::default()));
+        let buffer = Arc::new(UpCell::new(PipeRingBuffer {
+            storage: Storage::Heap(vec![0; Self::DEFAULT_CAP].into_boxed_slice()),
+            head: 0,
+            tail: 0,
+            status: RingBufferStatus::Empty,
+            write_end: Weak::new(),
+        }));
         let read_end = Arc::new(Pipe::read_end(buffer.clone()));
         let write_end = Arc::new(Pipe::write_end(buffer.clone()));
         buffer.exclusive_access().write_end = Arc::downgrade(&write_end);
@@ -139,7 +208,15 @@ impl PipeRingBuffer {
 }
 
 impl PipeRingBuffer {
-    const CAP: usize = 32;
+    /// 新建管道的容量
+    const DEFAULT_CAP: usize = 32;
+
+    fn capacity(&self) -> usize {
+        match &self.storage {
+            Storage::Heap(bytes) => bytes.len(),
+            Storage::Frames(frames) => frames.len() * PAGE_SIZE,
+        }
+    }
 
     fn hit_readables(&self) -> usize {
         if self.status == RingBufferStatus::Empty {
@@ -147,7 +224,7 @@ impl PipeRingBuffer {
         } else if self.tail > self.head {
             self.tail - self.head
         } else {
-            self.tail + Self::CAP - self.head
+            self.tail + self.capacity() - self.head
         }
     }
 
@@ -155,7 +232,7 @@ impl PipeRingBuffer {
         if self.status == RingBufferStatus::Full {
             0
         } else {
-            Self::CAP - self.hit_readables()
+            self.capacity() - self.hit_readables()
         }
     }
 
@@ -164,8 +241,8 @@ impl PipeRingBuffer {
     }
 
     fn pop(&mut self) -> u8 {
-        let byte = self.base[self.head];
-        self.head = (self.head + 1) % Self::CAP;
+        let byte = self.storage.bytes()[self.head];
+        self.head = (self.head + 1) % self.capacity();
 
         self.status = if self.head == self.tail {
             RingBufferStatus::Empty
@@ -177,8 +254,8 @@ impl PipeRingBuffer {
     }
 
     fn push(&mut self, byte: u8) {
-        self.base[self.head] = byte;
-        self.tail = (self.tail + 1) % Self::CAP;
+        self.storage.bytes()[self.tail] = byte;
+        self.tail = (self.tail + 1) % self.capacity();
 
         self.status = if self.tail == self.head {
             RingBufferStatus::Full
@@ -186,4 +263,48 @@ impl PipeRingBuffer {
             RingBufferStatus::Normal
         };
     }
+
+    /// 换用容量为`cap`的存储，已缓冲的数据移至其开头
+    fn resize(&mut self, cap: usize) -> Result<(), ResizeError> {
+        let len = self.hit_readables();
+        if len > cap {
+            return Err(ResizeError::Busy);
+        }
+        let mut storage = Storage::new(cap).ok_or(ResizeError::NoMemory)?;
+        for byte in &mut storage.bytes()[..len] {
+            *byte = self.pop();
+        }
+
+        self.storage = storage;
+        self.head = 0;
+        self.tail = len % cap;
+        self.status = if len == 0 {
+            RingBufferStatus::Empty
+        } else if len == cap {
+            RingBufferStatus::Full
+        } else {
+            RingBufferStatus::Normal
+        };
+        Ok(())
+    }
+}
+
+impl Storage {
+    fn new(cap: usize) -> Option<Self> {
+        if cap <= PAGE_SIZE {
+            return Some(Self::Heap(vec![0; cap].into_boxed_slice()));
+        }
+        frame_allocator::alloc_continuous(cap / PAGE_SIZE).map(Self::Frames)
+    }
+
+    fn bytes(&mut self) -> &mut [u8] {
+        match self {
+            Self::Heap(bytes) => bytes,
+            Self::Frames(frames) => {
+                // 页号降序排列，末尾的页帧即起点；物理内存是恒等映射的
+                let base = usize::from(PhysAddr::from(frames.last().unwrap().ppn));
+                unsafe { slice::from_raw_parts_mut(base as *mut u8, frames.len() * PAGE_SIZE) }
+            }
+        }
+    }
 }
@@ -0,0 +1,285 @@
+//! # 进程文件系统
+//!
+//! 启动时挂载于`/proc`，按进程列出其打开的文件，用以排查描述符泄漏：
+//!
+//! ```text
+//! /proc
+//! ├── <pid>
+//! │   └── fd
+//! │       └── <fd>    文件的种类、访问模式、偏移量及路径
+//! └── self            当前进程
+//! ```
+//!
+//! 内容在打开时依进程表生成，此后不再随之变化。
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use enumflags2::BitFlags;
+use vfs::{DirEntry, DirEntryType, Stat};
+
+use super::mount::FileSystem;
+use super::{DirentBuf, File, OpenFlag, SeekError, Whence};
+use crate::memory::UserBuffer;
+use crate::sync::UpCell;
+use crate::task::{manager, processor, ProcessControlBlock};
+
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn name(&self) -> &'static str {
+        "proc"
+    }
+
+    fn open(
+        &self,
+        _path: &str,
+        relat_path: &str,
+        flags: BitFlags<OpenFlag>,
+    ) -> Result<Arc<dyn File + Send + Sync>, vfs::Error> {
+        // 只读
+        if !flags.is_empty() {
+            return Err(vfs::Error::Unsupported);
+        }
+
+        let mut components = relat_path.split('/').filter(|c| !c.is_empty());
+        let Some(pid) = components.next() else {
+            return Ok(Arc::new(ProcDir::new(root_entries())));
+        };
+        let process = find_process(pid).ok_or(vfs::Error::NotFound)?;
+
+        let file: Arc<dyn File + Send + Sync> = match (components.next(), components.next()) {
+            (None, _) => Arc::new(ProcDir::new(vec![entry("fd", DirEntryType::Directory)])),
+            (Some("fd"), None) => Arc::new(ProcDir::new(fd_entries(&process))),
+            (Some("fd"), Some(fd)) => {
+                let fd = fd.parse().map_err(|_| vfs::Error::NotFound)?;
+                let file = process
+                    .inner()
+                    .exclusive_access()
+                    .fd_table
+                    .try_get(fd)
+                    .ok_or(vfs::Error::NotFound)?;
+                Arc::new(ProcText::new(describe(&*file)))
+            }
+            _ => return Err(vfs::Error::NotFound),
+        };
+        if components.next().is_some() {
+            return Err(vfs::Error::NotFound);
+        }
+        Ok(file)
+    }
+}
+
+fn find_process(name: &str) -> Option<Arc<ProcessControlBlock>> {
+    if name == "self" {
+        return Some(processor::current_process());
+    }
+    manager::get_process(name.parse().ok()?)
+}
+
+fn entry(name: &str, ty: DirEntryType) -> DirEntry {
+    DirEntry {
+        inode: 0,
+        ty,
+        name: name.to_string(),
+    }
+}
+
+fn root_entries() -> Vec<DirEntry> {
+    let mut entries: Vec<_> = manager::processes()
+        .iter()
+        .map(|process| DirEntry {
+            inode: process.pid() as u64,
+            ty: DirEntryType::Directory,
+            name: process.pid().to_string(),
+        })
+        .collect();
+    entries.push(entry("self", DirEntryType::Directory));
+    entries
+}
+
+fn fd_entries(process: &ProcessControlBlock) -> Vec<DirEntry> {
+    process
+        .inner()
+        .exclusive_access()
+        .fd_table
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| file.is_some())
+        .map(|(fd, _)| DirEntry {
+            inode: fd as u64,
+            ty: DirEntryType::Regular,
+            name: fd.to_string(),
+        })
+        .collect()
+}
+
+/// 每行一项，未知的偏移量与路径不列出：
+///
+/// ```text
+/// type:   file
+/// flags:  rw
+/// offset: 42
+/// path:   /foo
+/// ```
+fn describe(file: &dyn File) -> String {
+    let flags = match (file.readable(), file.writable()) {
+        (true, true) => "rw",
+        (true, false) => "r",
+        (false, true) => "w",
+        (false, false) => "-",
+    };
+    let mut text = format!("type:\t{}\nflags:\t{flags}\n", file.kind().name());
+    if let Some(offset) = file.offset() {
+        writeln!(text, "offset:\t{offset}").unwrap();
+    }
+    if let Some(path) = file.path() {
+        writeln!(text, "path:\t{path}").unwrap();
+    }
+    text
+}
+
+/// `/proc`下的目录，`/dev`也借用之
+#[derive(Debug)]
+pub(super) struct ProcDir {
+    entries: Vec<DirEntry>,
+    /// 下一个读取的目录项序号
+    offset: UpCell<usize>,
+}
+
+impl ProcDir {
+    pub(super) fn new(entries: Vec<DirEntry>) -> Self {
+        Self {
+            entries,
+            offset: UpCell::new(0),
+        }
+    }
+
+    /// 从当前序号起由`push`逐项编码，放不下的留待下次读取
+    fn fill(
+        &self,
+        buf: UserBuffer,
+        mut push: impl FnMut(&mut DirentBuf, &DirEntry) -> bool,
+    ) -> Option<usize> {
+        let mut offset = self.offset.exclusive_access();
+        let mut records = DirentBuf::new(buf.len());
+        for dirent in self.entries.iter().skip(*offset) {
+            if !push(&mut records, dirent) {
+                break;
+            }
+            *offset += 1;
+        }
+        records.copy_out(buf)
+    }
+}
+
+/// 内容在打开时才生成，大小与时间均记为0
+fn empty_stat(mode: DirEntryType) -> Stat {
+    Stat {
+        mode,
+        block_size: 0,
+        blocks: 0,
+        size: 0,
+        atime: 0,
+        mtime: 0,
+        ctime: 0,
+    }
+}
+
+impl File for ProcDir {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn stat(&self) -> Stat {
+        empty_stat(DirEntryType::Directory)
+    }
+
+    fn offset(&self) -> Option<usize> {
+        Some(*self.offset.exclusive_access())
+    }
+
+    fn getdents(&self, buf: UserBuffer) -> Option<usize> {
+        self.fill(buf, DirentBuf::push)
+    }
+
+    fn getdents_plus(&self, buf: UserBuffer) -> Option<usize> {
+        self.fill(buf, |records, dirent| {
+            records.push_plus(dirent, &empty_stat(dirent.ty))
+        })
+    }
+}
+
+/// `/proc`下的文本文件
+#[derive(Debug)]
+struct ProcText {
+    text: String,
+    offset: UpCell<usize>,
+}
+
+impl ProcText {
+    fn new(text: String) -> Self {
+        Self {
+            text,
+            offset: UpCell::new(0),
+        }
+    }
+}
+
+impl File for ProcText {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut offset = self.offset.exclusive_access();
+        let start = *offset;
+        for sub_buf in buf.as_mut() {
+            *offset += self.read_at(*offset, sub_buf);
+        }
+        *offset - start
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let bytes = self.text.as_bytes();
+        let Some(rest) = bytes.get(offset..) else {
+            return 0;
+        };
+        let len = rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&rest[..len]);
+        len
+    }
+
+    fn read_all(&self) -> Vec<u8> {
+        let mut offset = self.offset.exclusive_access();
+        let bytes = self.text.as_bytes()[(*offset).min(self.text.len())..].to_vec();
+        *offset += bytes.len();
+        bytes
+    }
+
+    fn stat(&self) -> Stat {
+        Stat {
+            mode: DirEntryType::Regular,
+            block_size: 0,
+            blocks: 0,
+            size: self.text.len() as u64,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+
+    fn offset(&self) -> Option<usize> {
+        Some(*self.offset.exclusive_access())
+    }
+
+    fn lseek(&self, offset: isize, whence: Whence) -> Result<usize, SeekError> {
+        let mut pos = self.offset.exclusive_access();
+        *pos = whence.resolve(*pos, self.text.len(), offset)?;
+        Ok(*pos)
+    }
+}
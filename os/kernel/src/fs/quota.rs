@@ -0,0 +1,100 @@
+//! 按用户统计每个磁盘文件系统上的占用，持久化于其根目录下的[`QUOTA_FILE`]。
+//!
+//! 文件所占的块都记在其属主名下，谁写入都一样；删除时退还给属主，
+//! 硬链接的文件因此可能被多退。
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec;
+
+use vfs::quota::QUOTA_FILE;
+use vfs::{DiskQuota, VfsInode};
+
+use crate::sync::UpCell;
+use crate::timer;
+
+/// 一个文件系统上所有用户的配额
+#[derive(Debug)]
+pub struct Quota {
+    root: Arc<dyn VfsInode>,
+    users: UpCell<BTreeMap<u32, DiskQuota>>,
+}
+
+impl Quota {
+    /// 读入`root`下的配额文件，没有时所有用户都不受限
+    pub fn load(root: Arc<dyn VfsInode>) -> Self {
+        let mut users = BTreeMap::new();
+        if let Ok(file) = root.lookup(QUOTA_FILE) {
+            let mut buf = vec![0; file.size()];
+            let len = file.read_at(0, &mut buf).unwrap_or(0);
+            for record in buf[..len].chunks(DiskQuota::RECORD_SIZE) {
+                if let Some((uid, quota)) = DiskQuota::decode(record) {
+                    users.insert(uid, quota);
+                }
+            }
+        }
+        Self {
+            root,
+            users: UpCell::new(users),
+        }
+    }
+
+    pub fn get(&self, uid: u32) -> DiskQuota {
+        self.users
+            .exclusive_access()
+            .get(&uid)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn set_limits(&self, uid: u32, hard_blocks: u64, soft_blocks: u64) {
+        self.users
+            .exclusive_access()
+            .entry(uid)
+            .or_default()
+            .set_limits(hard_blocks, soft_blocks, now());
+    }
+
+    /// `owner`名下再占用`blocks`块是否超出配额
+    pub fn exceeds(&self, owner: u32, blocks: u64) -> bool {
+        self.get(owner).exceeds(blocks, now())
+    }
+
+    /// `owner`名下的一个文件从占用`before`块改为`after`块
+    pub fn charge(&self, owner: u32, before: u64, after: u64) {
+        if before == after {
+            return;
+        }
+        let delta = after as i64 - before as i64;
+        self.users
+            .exclusive_access()
+            .entry(owner)
+            .or_default()
+            .charge(delta, now());
+    }
+
+    /// 重写配额文件。配额文件本身的大小不计入任何用户
+    pub fn sync(&self) -> Result<(), vfs::Error> {
+        let users = self.users.exclusive_access();
+        let records = users.iter().filter(|(_, quota)| !quota.is_empty());
+        let mut buf = vec![0; records.clone().count() * DiskQuota::RECORD_SIZE];
+        for ((uid, quota), record) in records.zip(buf.chunks_mut(DiskQuota::RECORD_SIZE)) {
+            quota.encode(*uid, record);
+        }
+        drop(users);
+
+        let file = match self.root.lookup(QUOTA_FILE) {
+            Ok(file) => file,
+            Err(vfs::Error::NotFound) => self.root.create(QUOTA_FILE)?,
+            Err(e) => return Err(e),
+        };
+        file.truncate(0)?;
+        file.write_at(0, &buf)?;
+        Ok(())
+    }
+}
+
+/// 自UNIX纪元的秒数
+fn now() -> u64 {
+    (timer::realtime_us() / 1_000_000) as u64
+}
@@ -0,0 +1,46 @@
+//! # 文件系统注册表
+//!
+//! 各文件系统以名称登记其类型，挂载时按名称选用。
+//! 编入哪些文件系统只取决于内核的特性，挂载的调用处无需改动。
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use block_dev::BlockDevice;
+
+use super::mount::FileSystem;
+use crate::sync::UpCell;
+
+static FILESYSTEMS: UpCell<Vec<(&'static str, &'static dyn FileSystemType)>> =
+    UpCell::new(Vec::new());
+
+/// 文件系统类型，负责从块设备上加载文件系统
+pub trait FileSystemType: Sync {
+    /// 设备上不是该类型的文件系统时返回`None`
+    fn mount(&self, dev: Arc<dyn BlockDevice>) -> Option<Arc<dyn FileSystem>>;
+}
+
+/// 登记编入内核的文件系统
+pub fn init() {
+    #[cfg(feature = "fat")]
+    register(super::inode_fat::NAME, &super::inode_fat::FatType);
+    #[cfg(feature = "squashfs")]
+    register(super::squash::NAME, &super::squash::SquashType);
+    #[cfg(feature = "easy-fs")]
+    register(super::inode_easy::NAME, &super::inode_easy::EasyType);
+}
+
+/// 以名称`name`登记文件系统类型，重名时后者覆盖前者
+pub fn register(name: &'static str, ops: &'static dyn FileSystemType) {
+    let mut filesystems = FILESYSTEMS.exclusive_access();
+    filesystems.retain(|&(n, _)| n != name);
+    filesystems.push((name, ops));
+}
+
+pub fn find(name: &str) -> Option<&'static dyn FileSystemType> {
+    FILESYSTEMS
+        .exclusive_access()
+        .iter()
+        .find(|&&(n, _)| n == name)
+        .map(|&(_, ops)| ops)
+}
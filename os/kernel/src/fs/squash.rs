@@ -0,0 +1,174 @@
+//! 挂载只读的squash-fs镜像
+
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use block_dev::BlockDevice;
+use enumflags2::BitFlags;
+use squash_fs::{Inode, SquashFileSystem};
+use vfs::Stat;
+
+use super::mount::FileSystem;
+use super::registry::FileSystemType;
+use super::{DirentBuf, File, FileId, FileKind, OpenFlag, SeekError, Whence};
+use crate::memory::UserBuffer;
+use crate::sync::UpCell;
+
+/// 文件系统类型在注册表中的名称
+pub const NAME: &str = "squashfs";
+
+pub struct SquashType;
+
+impl FileSystemType for SquashType {
+    fn mount(&self, dev: Arc<dyn BlockDevice>) -> Option<Arc<dyn FileSystem>> {
+        SquashFileSystem::load(dev)
+            .inspect_err(|e| log::warn!("not a squash-fs image: {e:?}"))
+            .ok()
+            .map(|fs| Arc::new(SquashFs(Arc::new(fs))) as _)
+    }
+}
+
+struct SquashFs(Arc<SquashFileSystem>);
+
+impl FileSystem for SquashFs {
+    fn name(&self) -> &'static str {
+        NAME
+    }
+
+    fn open(
+        &self,
+        path: &str,
+        relat_path: &str,
+        flags: BitFlags<OpenFlag>,
+    ) -> Result<Arc<dyn File + Send + Sync>, vfs::Error> {
+        // 只读
+        if !flags.is_empty() {
+            return Err(vfs::Error::Unsupported);
+        }
+        let inode = self
+            .0
+            .root()
+            .find(relat_path, &self.0)
+            .ok_or(vfs::Error::NotFound)?;
+
+        Ok(Arc::new(SquashFile {
+            path: path.into(),
+            fs: self.0.clone(),
+            inode,
+            offset: UpCell::new(0),
+        }))
+    }
+
+    /// 每个打开的文件都持有文件系统
+    fn busy(&self) -> bool {
+        Arc::strong_count(&self.0) > 1
+    }
+}
+
+/// squash-fs中打开的文件或目录
+#[derive(Debug)]
+struct SquashFile {
+    /// 打开时的标准路径
+    path: Arc<str>,
+    fs: Arc<SquashFileSystem>,
+    inode: Inode,
+    /// 文件内的字节偏移量，或目录内的目录项序号
+    offset: UpCell<usize>,
+}
+
+impl File for SquashFile {
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn read(&self, mut buf: UserBuffer) -> usize {
+        let mut offset = self.offset.exclusive_access();
+        let mut total_read_size = 0;
+
+        for sub_buf in buf.as_mut() {
+            let read_size = self.inode.read_at(*offset, sub_buf, &self.fs);
+            if read_size == 0 {
+                break;
+            }
+            *offset += read_size;
+            total_read_size += read_size;
+        }
+
+        total_read_size
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.inode.read_at(offset, buf, &self.fs)
+    }
+
+    fn read_all(&self) -> Vec<u8> {
+        let mut offset = self.offset.exclusive_access();
+        let mut bytes = vec![0; self.inode.size().saturating_sub(*offset)];
+        let len = self.inode.read_at(*offset, &mut bytes, &self.fs);
+        bytes.truncate(len);
+        *offset += len;
+        bytes
+    }
+
+    fn stat(&self) -> Stat {
+        self.inode.stat(&self.fs)
+    }
+
+    fn offset(&self) -> Option<usize> {
+        Some(*self.offset.exclusive_access())
+    }
+
+    fn lseek(&self, offset: isize, whence: Whence) -> Result<usize, SeekError> {
+        // 目录的偏移量是目录项序号，没有与之对应的末尾
+        if whence == Whence::End && self.kind() == FileKind::Directory {
+            return Err(SeekError::Invalid);
+        }
+        let mut pos = self.offset.exclusive_access();
+        *pos = whence.resolve(*pos, self.inode.size(), offset)?;
+        Ok(*pos)
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+
+    fn file_id(&self) -> Option<FileId> {
+        Some((Arc::as_ptr(&self.fs) as usize, self.inode.id()))
+    }
+
+    fn getdents(&self, buf: UserBuffer) -> Option<usize> {
+        self.fill(buf, DirentBuf::push)
+    }
+
+    /// squash-fs的目录项不含状态，只能逐项查找
+    fn getdents_plus(&self, buf: UserBuffer) -> Option<usize> {
+        self.fill(buf, |records, dirent| {
+            // 目录项指向的索引节点总在
+            let inode = self
+                .inode
+                .find(&dirent.name, &self.fs)
+                .expect("listed entry");
+            records.push_plus(dirent, &inode.stat(&self.fs))
+        })
+    }
+}
+
+impl SquashFile {
+    /// 从当前序号起由`push`逐项编码，放不下的留待下次读取
+    fn fill(
+        &self,
+        buf: UserBuffer,
+        mut push: impl FnMut(&mut DirentBuf, &vfs::DirEntry) -> bool,
+    ) -> Option<usize> {
+        let mut offset = self.offset.exclusive_access();
+        let mut records = DirentBuf::new(buf.len());
+        for dirent in self.inode.read_dir(&self.fs).iter().skip(*offset) {
+            if !push(&mut records, dirent) {
+                break;
+            }
+            *offset += 1;
+        }
+        records.copy_out(buf)
+    }
+}
@@ -1,7 +1,21 @@
-use super::File;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::{File, FileKind};
+use crate::console;
 use crate::memory::UserBuffer;
 use crate::sbi::console_getchar;
-use crate::task;
+use crate::sync::UpCell;
+use crate::task::{self, processor};
+
+/// 开关标准输出的行标记模式，`arg`非0时开启
+pub const TIOCSTAG: usize = 0x54f0;
+
+/// 行标记模式下各进程尚未输出的半行，`None`表示未开启
+///
+/// 多个进程同时输出时，串口上的行会互相穿插。开启后按进程缓冲到整行，
+/// 再冠以`[pid]`一并输出。
+static TAGGING: UpCell<Option<BTreeMap<usize, Vec<u8>>>> = UpCell::new(None);
 
 /// 标准输入
 #[derive(Debug)]
@@ -12,6 +26,10 @@ pub struct Stdin;
 pub struct Stdout;
 
 impl File for Stdin {
+    fn kind(&self) -> FileKind {
+        FileKind::Tty
+    }
+
     #[inline]
     fn readable(&self) -> bool {
         true
@@ -19,17 +37,9 @@ impl File for Stdin {
 
     fn read(&self, mut buf: UserBuffer) -> usize {
         assert_eq!(buf.len(), 1);
-        let mut c: usize;
-        loop {
-            c = console_getchar();
-            if c == 0 {
-                task::suspend_current_and_run_next();
-                continue;
-            } else {
-                break;
-            }
-        }
-        let ch = c as u8;
+        let Some(ch) = getchar() else {
+            return 0;
+        };
         unsafe {
             buf.as_mut()[0].as_mut_ptr().write_volatile(ch);
         }
@@ -37,16 +47,89 @@ impl File for Stdin {
     }
 }
 
+/// 读入一个字符，没有输入时让出处理器；等待时被信号打断则返回`None`
+pub(super) fn getchar() -> Option<u8> {
+    loop {
+        let c = console_getchar();
+        if c != 0 {
+            return Some(c as u8);
+        }
+        if task::current_signal_pending() {
+            task::interrupt_current_syscall();
+            return None;
+        }
+        task::suspend_current_and_run_next();
+    }
+}
+
 impl File for Stdout {
+    fn kind(&self) -> FileKind {
+        FileKind::Tty
+    }
+
     #[inline]
     fn writable(&self) -> bool {
         true
     }
 
     fn write(&self, buf: UserBuffer) -> usize {
-        for sub_buf in buf.as_ref() {
-            print!("{}", core::str::from_utf8(sub_buf).unwrap());
+        let pid = processor::current().process().pid();
+        let mut tagging = TAGGING.exclusive_access();
+        let Some(lines) = tagging.as_mut() else {
+            // 多字节字符可能跨页，原样输出
+            for sub_buf in buf.as_ref() {
+                console::write_bytes(sub_buf);
+            }
+            return buf.len();
+        };
+
+        let line = lines.entry(pid).or_default();
+        for &b in buf.iter() {
+            line.push(b);
+            if b == b'\n' {
+                print_tagged(pid, line);
+                line.clear();
+            }
         }
         buf.len()
     }
+
+    fn ioctl(&self, request: usize, arg: usize) -> Option<usize> {
+        if request != TIOCSTAG {
+            return None;
+        }
+
+        let mut tagging = TAGGING.exclusive_access();
+        if arg != 0 {
+            tagging.get_or_insert_with(BTreeMap::new);
+        } else if let Some(lines) = tagging.take() {
+            for (pid, line) in lines {
+                flush_line(pid, line);
+            }
+        }
+        Some(0)
+    }
+}
+
+/// 进程退出时输出其剩下的半行
+pub fn flush(pid: usize) {
+    let line = TAGGING
+        .exclusive_access()
+        .as_mut()
+        .and_then(|lines| lines.remove(&pid));
+    if let Some(line) = line {
+        flush_line(pid, line);
+    }
+}
+
+fn flush_line(pid: usize, mut line: Vec<u8>) {
+    if !line.is_empty() {
+        line.push(b'\n');
+        print_tagged(pid, &line);
+    }
+}
+
+fn print_tagged(pid: usize, line: &[u8]) {
+    print!("[{pid}] ");
+    console::write_bytes(line);
 }
@@ -0,0 +1,104 @@
+//! # 内核符号表
+//!
+//! 构建后由Makefile将内核的函数符号按地址升序写入预留的`.ksyms`段，每行一个，其后以0填充：
+//!
+//! ```text
+//! 0000000080200000 _start
+//! 0000000080201000 __alltraps
+//! ```
+//!
+//! 启动时解析为有序表，回溯、堆调试与剖析据此将地址换成`函数+偏移`。
+//! 未填入符号表时（如直接`cargo build`），查找一律失败，地址原样输出。
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::slice;
+use core::str;
+
+use spin::Once;
+
+/// 为符号表预留的字节数，Makefile以`.ksyms`段的大小为上限
+const CAPACITY: usize = 256 * 1024;
+
+/// 预留的空间。只经由链接脚本给出的`sksyms`读取，免得编译器把全0的初值折叠进代码
+#[used]
+#[link_section = ".ksyms"]
+static RESERVED: [u8; CAPACITY] = [0; CAPACITY];
+
+extern "C" {
+    fn stext();
+    fn etext();
+    fn sksyms();
+    fn eksyms();
+}
+
+/// 函数的起始地址及名称，按地址升序
+static SYMBOLS: Once<Vec<(usize, &'static str)>> = Once::new();
+
+/// 解析`.ksyms`段，须在堆初始化之后调用
+pub fn init() {
+    let table = unsafe {
+        slice::from_ptr_range(sksyms as usize as *const u8..eksyms as usize as *const u8)
+    };
+    let len = table.iter().position(|&b| b == 0).unwrap_or(table.len());
+    let Ok(text) = str::from_utf8(&table[..len]) else {
+        log::warn!("kernel symbol table is corrupted");
+        return;
+    };
+
+    let symbols: Vec<_> = text
+        .lines()
+        .filter_map(|line| {
+            let (addr, name) = line.split_once(' ')?;
+            Some((usize::from_str_radix(addr, 16).ok()?, name))
+        })
+        .collect();
+    if symbols.is_empty() {
+        log::warn!("kernel symbol table is empty, addresses will not be symbolized");
+    } else {
+        log::info!("{} kernel symbols", symbols.len());
+    }
+    SYMBOLS.call_once(|| symbols);
+}
+
+/// 包含`addr`的函数及`addr`在其中的偏移。
+/// `addr`不在内核代码段中，或符号表未填入时返回`None`
+pub fn lookup(addr: usize) -> Option<(&'static str, usize)> {
+    if !(stext as usize..etext as usize).contains(&addr) {
+        return None;
+    }
+    let symbols = SYMBOLS.get()?;
+    let i = symbols
+        .partition_point(|&(start, _)| start <= addr)
+        .checked_sub(1)?;
+    let (start, name) = symbols[i];
+    Some((name, addr - start))
+}
+
+/// 以`地址 <函数+偏移>`的形式输出，查不到时只输出地址
+#[derive(Debug, Clone, Copy)]
+pub struct Symbolized(pub usize);
+
+impl fmt::Display for Symbolized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018x}", self.0)?;
+        if let Some((name, offset)) = lookup(self.0) {
+            write!(f, " <{name}+{offset:#x}>")?;
+        }
+        Ok(())
+    }
+}
+
+/// 逐行输出[`stack_trace::capture`](crate::stack_trace::capture)得到的返回地址，
+/// 遇到0即止，以便直接输出定长的数组
+#[derive(Debug, Clone, Copy)]
+pub struct Backtrace<'a>(pub &'a [usize]);
+
+impl fmt::Display for Backtrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, &ra) in self.0.iter().take_while(|&&ra| ra != 0).enumerate() {
+            write!(f, "\n  #{i} {}", Symbolized(ra))?;
+        }
+        Ok(())
+    }
+}
@@ -1,24 +1,38 @@
 use core::panic::PanicInfo;
+use core::sync::atomic::{AtomicBool, Ordering};
 
 use crate::sbi::shutdown;
+use crate::stack_trace;
+use crate::task::processor;
+
+/// 已有panic正在处理
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
+    // 输出消息时再次panic，不再尝试格式化，免得无限递归
+    if PANICKING.swap(true, Ordering::Relaxed) {
+        emergency_println!("Panicked while panicking");
+        shutdown(true)
+    }
+
+    // 当前任务的状态可能正被借用，取不到时不提
+    if let Some((name, pid)) = processor::current_name() {
+        emergency_println!("Panicked in {name} (pid {pid})");
+    }
     let msg = info.message();
     if let Some(location) = info.location() {
-        println!(
+        emergency_println!(
             "Panicked at {}:{} {}",
             location.file(),
             location.line(),
             msg
         );
     } else {
-        println!("Panicked: {msg}");
+        emergency_println!("Panicked: {msg}");
     }
 
-    // unsafe {
-    //     print_stack_trace();
-    // }
+    stack_trace::print_stack_trace();
 
     shutdown(true)
 }
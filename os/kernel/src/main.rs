@@ -15,14 +15,18 @@ extern crate alloc;
 #[macro_use]
 mod console;
 
+mod boot;
+mod cmdline;
 mod collections;
 mod config;
 mod drivers;
 mod fs;
+mod ksyms;
 mod lang_items;
 mod logging;
 mod memory;
 mod path;
+mod power;
 mod ptr;
 mod sbi;
 mod stack_trace;
@@ -38,9 +42,8 @@ mod board;
 use core::arch::global_asm;
 use core::slice;
 
-use spin::Lazy;
-
-use crate::drivers::{IOMode, DEV_IO_MODE, GPU_DEVICE, KEYBOARD_DEVICE, MOUSE_DEVICE, SERIAL};
+use crate::boot::Phase;
+use crate::drivers::{IOMode, DEV_IO_MODE};
 
 global_asm!(include_str!("entry.S"));
 
@@ -56,29 +59,35 @@ fn clear_bss() {
 }
 
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(_hartid: usize, dtb: usize) -> ! {
     clear_bss();
     logging::init();
-    memory::init(); // 初始化分页
+    boot::phase(Phase::Cmdline, || cmdline::init(dtb)); // 设备树所在的内存随后归帧分配器所有
+    boot::phase(Phase::Paging, memory::init); // 初始化分页
+    ksyms::init();
+    memory::frame_allocator::register_compaction_hook(task::manager::compact_user_frames);
 
-    SERIAL.init();
+    log::info!("init drivers");
+    boot::phase(Phase::Drivers, drivers::init);
 
-    log::info!("init GPU");
-    Lazy::force(&GPU_DEVICE);
-    log::info!("init keyboard");
-    Lazy::force(&KEYBOARD_DEVICE);
-    log::info!("init mouse");
-    Lazy::force(&MOUSE_DEVICE);
+    log::info!("mount root filesystem");
+    boot::phase(Phase::Mount, fs::init);
 
     log::info!("init trap");
-    trap::init(); // 设置好 Trap 处理入口
-    trap::enable_timer_interrupt();
-    timer::set_next_trigger(); // 开始定时
-    board::init_device();
+    boot::phase(Phase::Trap, || {
+        trap::init(); // 设置好 Trap 处理入口
+        syscall::init();
+        trap::enable_timer_interrupt();
+        timer::set_next_trigger(); // 开始定时
+        board::init_device();
+    });
+
+    log::info!("frames: {}", memory::frame_allocator::report());
 
     log::info!("add initproc");
-    task::add_initproc(); // 启动始祖进程
+    boot::phase(Phase::Initproc, task::add_initproc); // 启动始祖进程
     *DEV_IO_MODE.exclusive_access() = IOMode::Interrupt;
+    boot::report();
 
     log::info!("run");
     task::run();
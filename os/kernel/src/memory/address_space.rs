@@ -74,6 +74,10 @@
 //! ```txt
 //!               256GiB
 //!         ┌───────────────┐
+//!         │  时钟共享页   │
+//!         ├───────────────┤ <- VDSO_VA
+//!         │   mmap区域    │
+//!         ├───────────────┤ <- MMAP_BASE
 //!         │     ......    │
 //!         ├───────────────┤
 //!         │ t2 user stack │
@@ -117,10 +121,12 @@
 //! ```
 
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::arch::riscv64;
+use core::mem;
 use core::ops::Range;
-use spin::Lazy;
+use spin::{Lazy, Once};
 
 use enumflags2::{bitflags, BitFlags};
 use goblin::elf::Elf;
@@ -129,16 +135,27 @@ use goblin::elf64::program_header::{PF_R, PF_W, PF_X};
 use riscv::register::satp;
 
 use super::address::*;
+use super::asid::Asid;
 use super::frame_allocator;
 use super::frame_allocator::Frame;
+use super::image::Image;
 use super::page_table;
 use super::page_table::PTEFlag;
 use super::page_table::{MappedVpn, UnmappedVpn};
+use super::rmap::{self, Mapping};
+use super::vdso::{self, VDSO_VA};
 use super::PageTable;
 use crate::board::mmio_segments;
-use crate::config::{MEMORY_END, PAGE_SIZE, TRAMPOLINE};
+use crate::config::{MEMORY_END, MMAP_BASE, PAGE_SIZE, TRAMPOLINE, USER_SPACE_END};
+use crate::fs::File;
 use crate::sync::UpCell;
 
+/// 已发现硬件不维护访问位与脏位
+static SOFTWARE_AD: Once = Once::new();
+
+/// 全局共享的零页。读取从未写过的匿名页时只读地映射它，首次写入时再换成私有页帧
+static ZERO_FRAME: Lazy<Arc<Frame>> = Lazy::new(|| Arc::new(frame_allocator::alloc().unwrap()));
+
 extern "C" {
     fn stext();
     fn etext();
@@ -155,19 +172,37 @@ extern "C" {
 pub static KERNEL_SPACE: Lazy<UpCell<AddressSpace>> =
     Lazy::new(|| UpCell::new(AddressSpace::new_kernel()));
 
+/// 逐页刷新快表的页数上限，超过时刷新整个地址空间
+const FLUSH_PAGES_MAX: usize = 32;
+
 /// 地址空间由一系列有关联但不一定连续的逻辑段组成
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct AddressSpace {
     page_table: PageTable,
     logic_segments: Vec<LogicSegment>,
+    /// 可常驻的物理页帧数上限，`None`表示不设限
+    frame_limit: Option<usize>,
+    /// 修改映射后只刷新该编号下的快表项
+    asid: Asid,
 }
 
 #[derive(Debug)]
 struct LogicSegment {
     vpn_range: Range<VirtPageNum>,
-    vpn2frame: BTreeMap<VirtPageNum, Frame>,
+    /// 页帧可能与其他地址空间共享，见[`AddressSpace::share_identical`]
+    vpn2frame: BTreeMap<VirtPageNum, Arc<Frame>>,
+    /// `vpn2frame`中除零页外的页帧数，映射与撤销映射时维护，免得每次缺页都要数一遍
+    resident: usize,
     map_type: MapType,
     permission: BitFlags<MapPermission>,
+    /// 物理页帧能否被压缩迁移，仅对[`MapType::Framed`]有意义
+    movable: bool,
+    /// 由mmap映射，可被munmap撤销或被mremap伸缩
+    mmapped: bool,
+    /// 页在首次访问时才映射，而非建立逻辑段时
+    lazy: bool,
+    /// 映射的文件，逻辑段的首页对应文件开头。页帧在映射时读入文件的内容
+    file: Option<Arc<dyn File + Send + Sync>>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -199,21 +234,23 @@ impl Clone for AddressSpace {
     /// 类型级别的复制无法包含页表，因此需要重新建立映射
     fn clone(&self) -> Self {
         // 用户地址空间的 fork
-        let mut addr_space = Self::default();
+        let mut addr_space = Self::new(Asid::alloc());
+        addr_space.set_frame_limit(self.frame_limit);
 
         addr_space.map_trampoline();
+        addr_space.map_vdso();
 
         // 复制所有段
         for seg in &self.logic_segments {
             // 页表创建新的映射
             addr_space.push(seg.clone()).unwrap();
-            // 取得物理页号，凭此复制该段的物理页
-            for vpn in seg.vpn_range.clone() {
-                let src_ppn = self.translate(vpn).unwrap().ppn();
+            // 复制该段常驻的物理页；未常驻的页与零页在子进程中为全零页
+            for (&vpn, frame) in seg.vpn2frame.iter().filter(|(_, frame)| !is_zero(frame)) {
+                addr_space.populate_page(vpn).unwrap();
                 let dest_ppn = addr_space.translate(vpn).unwrap().ppn();
                 dest_ppn
                     .page_bytes_mut()
-                    .copy_from_slice(src_ppn.page_bytes());
+                    .copy_from_slice(frame.ppn.page_bytes());
             }
         }
 
@@ -230,7 +267,7 @@ impl AddressSpace {
     /// 因此，2^64个虚拟地址中，只有最高256G（前导1）与最低256G（前导0）可用。
     pub fn new_kernel() -> Self {
         log::debug!("creating kernel address space");
-        let mut addr_space = Self::default();
+        let mut addr_space = Self::new(Asid::kernel());
 
         addr_space.map_trampoline();
 
@@ -312,12 +349,15 @@ impl AddressSpace {
     /// 创建用户的虚拟空间
     ///
     /// 返回：(地址空间, 用户栈顶地址, 程序入口地址)
-    pub fn new_user(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// 由程序映像创建用户地址空间，只读段共享映像中已载入的页帧
+    pub fn new_user(image: &Image) -> (Self, usize, usize) {
         log::debug!("creating user address space");
-        let mut addr_space = Self::default();
+        let mut addr_space = Self::new(Asid::alloc());
 
         addr_space.map_trampoline();
+        addr_space.map_vdso();
 
+        let elf_data = image.data();
         let elf = Elf::parse(elf_data).unwrap();
 
         // 魔数，ELF头的首串字节，用于核对文件是否为ELF
@@ -344,16 +384,16 @@ impl AddressSpace {
                 permission |= MapPermission::X;
             }
 
-            let seg = LogicSegment::new(start_va, end_va, MapType::Framed, permission);
+            let seg = LogicSegment::new(start_va, end_va, MapType::Framed, permission).movable();
 
             max_end_vpn = seg.vpn_range.end;
 
-            addr_space
-                .push_with_data(
-                    seg,
-                    &elf_data[ph.p_offset as usize..((ph.p_offset + ph.p_filesz) as usize)],
-                )
-                .unwrap();
+            let data = &elf_data[ph.p_offset as usize..((ph.p_offset + ph.p_filesz) as usize)];
+            if permission.contains(MapPermission::W) {
+                addr_space.push_with_data(seg, data).unwrap();
+            } else {
+                addr_space.push_shared(seg, data, image).unwrap();
+            }
         }
 
         let max_end_vpn: usize = VirtAddr::from(max_end_vpn).into();
@@ -368,7 +408,7 @@ impl AddressSpace {
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: BitFlags<MapPermission>,
-    ) -> Result<(), MappedVpn> {
+    ) -> Result<(), MapError> {
         self.push(LogicSegment::new(
             start_va,
             end_va,
@@ -377,13 +417,23 @@ impl AddressSpace {
         ))
     }
 
+    /// 插入可被压缩迁移的逻辑段，例如用户栈
+    pub fn insert_framed_movable(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: BitFlags<MapPermission>,
+    ) -> Result<(), MapError> {
+        self.push(LogicSegment::new(start_va, end_va, MapType::Framed, permission).movable())
+    }
+
     pub fn insert_linear(
         &mut self,
         start_va: VirtAddr,
         len: usize,
         pn_offset: isize,
         permission: BitFlags<MapPermission>,
-    ) -> Result<(), MappedVpn> {
+    ) -> Result<(), MapError> {
         self.push(LogicSegment::new(
             start_va,
             start_va + len,
@@ -392,63 +442,37 @@ impl AddressSpace {
         ))
     }
 
-    /*
-     * /// 映射一块内存
-     * ///
-     * /// suggested_start 建议的地址：
-     * /// - 若用户尚未给出建议，则该地址为默认的**mmap起始地址**；
-     * /// - 若用户给出建议地址，本函数的调用者应该确认其不低于mmap起始地址，否则用默认的代替。
-     * ///
-     * /// 总之，建议地址一定大于等于mmap起始地址。
-     * pub fn insert_mmap(
-     *     &mut self,
-     *     suggested_start: VirtAddr,
-     *     len: usize,
-     *     permission: BitFlags<MapPermission>,
-     * ) -> Result<VirtAddr, MappedVpn> {
-     *     let actual_start = self
-     *         .logic_segments
-     *         .iter()
-     *         .filter(|&seg| seg.map_type == MapType::Mmap)
-     *         .map(|seg| &seg.vpn_range)
-     *         .fold(
-     *             suggested_start,
-     *             |actual_start: VirtAddr, &Range { start, end }| {
-     *                 let actual_start_vpn = actual_start.page_number();
-     *
-     *                 if (actual_start_vpn < start
-     *                     && (actual_start + len + PAGE_SIZE).page_number() >= start)
-     *                     || (start <= actual_start_vpn && actual_start_vpn <= end)
-     *                 {
-     *                     // 不合格的情况：
-     *                     // - 将要映射的逻辑段(前)(计入保护页)与已映射段(后)交叠；
-     *                     // - 将要映射的逻辑段开始地址落在已映射段内(包含结束地址)；
-     *                     //
-     *                     // 取末页加上保护页作为新的起始地址
-     *                     VirtAddr::from(end + 1)
-     *                 } else {
-     *                     // 合格
-     *                     actual_start
-     *                 }
-     *             },
-     *         );
-     *
-     *     debug!(
-     *         "area of mmap: [{:#x}, {:#x}) ",
-     *         usize::from(actual_start),
-     *         usize::from(actual_start + len)
-     *     );
-     *
-     *     self.push(LogicSegment::new(
-     *         actual_start,
-     *         actual_start + len,
-     *         MapType::Mmap,
-     *         permission,
-     *     ))?;
-     *
-     *     Ok(actual_start)
-     * }
-     */
+    /// 为mmap映射`pages`页，优先使用建议的起始页`hint`，否则取[`MMAP_BASE`]之上首个足够大的空闲区间。
+    /// 给出`file`时映射该文件的开头，否则为全零的匿名内存，其页在首次访问时才映射。返回实际的起始页。
+    pub fn insert_mmap(
+        &mut self,
+        hint: Option<VirtPageNum>,
+        pages: usize,
+        permission: BitFlags<MapPermission>,
+        file: Option<Arc<dyn File + Send + Sync>>,
+    ) -> Result<VirtPageNum, MapError> {
+        let start = hint
+            .filter(|&start| self.is_free(start..start + pages))
+            .or_else(|| self.find_free(pages))
+            .ok_or(MapError {
+                vpn: hint.unwrap_or_default(),
+                kind: MapErrorKind::NoSpace,
+            })?;
+
+        let mut seg = LogicSegment::new(
+            VirtAddr::from(start),
+            VirtAddr::from(start + pages),
+            MapType::Framed,
+            permission,
+        )
+        .movable();
+        seg.mmapped = true;
+        seg.lazy = file.is_none();
+        seg.file = file;
+        self.push(seg)?;
+
+        Ok(start)
+    }
 
     pub fn remove(&mut self, start: VirtPageNum) -> Result<(), MapError> {
         let Some(index) = self
@@ -464,39 +488,485 @@ impl AddressSpace {
 
         let mut seg = self.logic_segments.remove(index);
         seg.unmap(&mut self.page_table)?;
+        self.flush_range(seg.vpn_range.clone());
 
         Ok(())
     }
 
-    /*
-     * pub fn remove_mmap(&mut self, start: VirtPageNum) -> Result<(), MapError> {
-     *      let Some(index) = self
-     *          .logic_segments
-     *          .iter_mut()
-     *          .position(|seg| seg.vpn_range.start == start)
-     *      else {
-     *          return Err(MapError {
-     *              vpn: start,
-     *              kind: MapErrorKind::NoSegement,
-     *          });
-     *      };
-     *
-     *      if self.logic_segments[index].map_type != MapType::Mmap {
-     *          return Err(MapError {
-     *              vpn: start,
-     *              kind: MapErrorKind::TypeMissed,
-     *          });
-     *      }
-     *
-     *      let mut seg = self.logic_segments.remove(index);
-     *      seg.unmap(&mut self.page_table)?;
-     *
-     *      Ok(())
-     *  }
-     */
+    /// 将`vpns`内文件映射中被写过的页写回文件并清除其脏位，返回写回的页数。
+    ///
+    /// 只有经页表的写入才会置脏位，内核按物理地址写入的页不在其列。
+    /// 文件末尾之后的部分不会写回，映射不改变文件的大小。
+    pub fn write_back(&mut self, vpns: Range<VirtPageNum>) -> usize {
+        let mut written = 0;
+        for seg in &self.logic_segments {
+            let Some(file) = &seg.file else {
+                continue;
+            };
+            let size = file.stat().size as usize;
+            for (&vpn, frame) in seg.vpn2frame.range(vpns.clone()) {
+                if !self.page_table.take_dirty(vpn) {
+                    continue;
+                }
+                let offset = seg.file_offset(vpn);
+                if offset < size {
+                    let len = PAGE_SIZE.min(size - offset);
+                    file.write_at(offset, &frame.ppn.page_bytes()[..len]);
+                    written += 1;
+                }
+            }
+        }
+
+        // 清除了脏位的页表项须重新从内存中读取
+        self.flush_range(vpns);
+
+        written
+    }
+
+    /// 撤销mmap映射的整个区间`vpns`，不支持只撤销其中一部分
+    pub fn remove_mmap(&mut self, vpns: Range<VirtPageNum>) -> Result<(), MapError> {
+        let index = self.mmap_index(&vpns)?;
+        self.write_back(vpns.clone());
+        let mut seg = self.logic_segments.remove(index);
+        seg.unmap(&mut self.page_table)?;
+        self.flush_range(vpns);
+
+        Ok(())
+    }
+
+    /// 将mmap映射的整个区间`vpns`伸缩至`pages`页，返回新的起始页。
+    ///
+    /// 缩小或其后有足够的空闲区间时原地伸缩，否则移到别处：
+    /// 页帧原样改映射到新的虚拟页上，不复制内容。
+    pub fn remap(
+        &mut self,
+        vpns: Range<VirtPageNum>,
+        pages: usize,
+    ) -> Result<VirtPageNum, MapError> {
+        let index = self.mmap_index(&vpns)?;
+        let old_pages = vpns.clone().count();
+        let mut start = vpns.start;
+
+        if pages < old_pages {
+            self.write_back(start + pages..vpns.end);
+            let seg = &mut self.logic_segments[index];
+            for vpn in start + pages..vpns.end {
+                seg.unmap_one(&mut self.page_table, vpn)?;
+            }
+            seg.vpn_range.end = start + pages;
+        } else if pages > old_pages {
+            if !self.fits(pages - old_pages) {
+                return Err(MapError {
+                    vpn: vpns.end,
+                    kind: MapErrorKind::ExceedLimit,
+                });
+            }
+            if !self.is_free(vpns.end..start + pages) {
+                start = self.find_free(pages).ok_or(MapError {
+                    vpn: vpns.end,
+                    kind: MapErrorKind::NoSpace,
+                })?;
+                self.logic_segments[index].relocate(&mut self.page_table, start)?;
+            }
+
+            let seg = &mut self.logic_segments[index];
+            let old_end = seg.vpn_range.end;
+            seg.vpn_range.end = start + pages;
+            if !seg.lazy {
+                for vpn in old_end..start + pages {
+                    seg.map_one(&mut self.page_table, vpn)?;
+                }
+            }
+        }
+
+        // 原区间与伸缩后的区间
+        self.flush_range(vpns);
+        self.flush_range(start..start + pages);
+        Ok(start)
+    }
+
+    /// 将可迁移逻辑段中落在`window`内的物理页搬到别处，返回迁移的页数
+    pub fn migrate(&mut self, window: &Range<PhysPageNum>) -> usize {
+        let migrated: usize = self
+            .logic_segments
+            .iter_mut()
+            .filter(|seg| seg.movable)
+            .map(|seg| seg.migrate(&mut self.page_table, window))
+            .sum();
+
+        if migrated > 0 {
+            // 页表项指向了新的物理页，旧的翻译不能再用
+            self.asid.flush();
+        }
+
+        migrated
+    }
+
+    /// 让用户页改用`canonical`给出的同内容页帧，返回改换的页数。
+    ///
+    /// 可写的匿名页也参与合并，合并者须经[`Self::write_protect`]收回写权限，
+    /// 写入时由[`Self::break_cow`]复制。除映射外还被别处持有的可写页帧不参与合并，
+    /// 例如系统调用正在读写的缓冲区；可写的文件映射须写回原页帧，亦不参与。
+    pub fn share_identical(
+        &mut self,
+        mut canonical: impl FnMut(&Arc<Frame>) -> Option<Arc<Frame>>,
+    ) -> usize {
+        let mut shared = 0;
+
+        for seg in self.logic_segments.iter_mut().filter(|seg| {
+            seg.movable && !(seg.permission.contains(MapPermission::W) && seg.file.is_some())
+        }) {
+            let writable = seg.permission.contains(MapPermission::W);
+            for (&vpn, frame) in seg.vpn2frame.iter_mut().filter(|(_, frame)| {
+                !is_zero(frame)
+                    && (!writable || Arc::strong_count(&**frame) == rmap::mappers(frame.ppn).len())
+            }) {
+                if let Some(new_frame) = canonical(frame) {
+                    self.page_table.remap(vpn, new_frame.ppn).unwrap();
+                    let mapping = Mapping {
+                        token: self.page_table.token(),
+                        vpn,
+                    };
+                    rmap::remove(frame.ppn, mapping);
+                    rmap::add(new_frame.ppn, mapping);
+                    *frame = new_frame;
+                    shared += 1;
+                }
+            }
+        }
+
+        if shared > 0 {
+            self.asid.flush();
+        }
+
+        shared
+    }
+
+    /// 收回用户对`vpn`这一页的写权限，下次写入时缺页
+    pub fn write_protect(&mut self, vpn: VirtPageNum) {
+        self.page_table.write_protect(vpn);
+        self.flush_page(vpn);
+    }
+
+    /// 释放`vpns`内由分配器分配的常驻页，返回释放的页数。
+    /// 所在逻辑段保留，此后访问这些页时按需映射全零或读入文件内容的页帧，见[`Self::populate_page`]。
+    ///
+    /// 与`vpns`相交的逻辑段须都是用户可访问的匿名内存或mmap映射，否则什么也不做并返回
+    /// [`MapErrorKind::TypeMissed`]：陷入上下文等内核使用的页不能由用户释放，
+    /// 只读的代码段与数据段与其它进程共享页帧，释放后读到全零亦无意义。
+    pub fn discard(&mut self, vpns: Range<VirtPageNum>) -> Result<usize, MapError> {
+        if let Some(seg) = self.logic_segments.iter().find(|seg| {
+            seg.vpn_range.start < vpns.end
+                && vpns.start < seg.vpn_range.end
+                && !(seg.map_type == MapType::Framed
+                    && seg.permission.contains(MapPermission::U)
+                    && (seg.mmapped || seg.permission.contains(MapPermission::W)))
+        }) {
+            return Err(MapError {
+                vpn: seg.vpn_range.start.max(vpns.start),
+                kind: MapErrorKind::TypeMissed,
+            });
+        }
+
+        // 文件映射的修改先写回，再次访问时从文件读入
+        self.write_back(vpns.clone());
+        let mut discarded = 0;
+        for seg in self
+            .logic_segments
+            .iter_mut()
+            .filter(|seg| seg.map_type == MapType::Framed)
+        {
+            let resident: Vec<_> = seg
+                .vpn2frame
+                .range(vpns.clone())
+                .map(|(&vpn, _)| vpn)
+                .collect();
+            for vpn in resident {
+                seg.unmap_one(&mut self.page_table, vpn)?;
+                discarded += 1;
+            }
+        }
+
+        if discarded > 0 {
+            self.flush_range(vpns);
+        }
+
+        Ok(discarded)
+    }
+
+    /// 为`vpns`内未常驻或映射着零页的页映射私有的页帧，返回新映射的页数，不在逻辑段内的页被略过。
+    ///
+    /// 内核按物理地址访问用户内存，不会触发缺页，也就无从写时复制，故须先经此映射。
+    pub fn populate(&mut self, vpns: Range<VirtPageNum>) -> Result<usize, MapError> {
+        // 按逻辑段求交集，免得逐页查找落在逻辑段外的大片区间
+        let missing: Vec<_> = self
+            .logic_segments
+            .iter()
+            .filter(|seg| seg.map_type == MapType::Framed)
+            .flat_map(|seg| {
+                let start = seg.vpn_range.start.max(vpns.start);
+                let end = seg.vpn_range.end.min(vpns.end);
+                (start..end).filter(|vpn| seg.vpn2frame.get(vpn).is_none_or(is_zero))
+            })
+            .collect();
+
+        let mut populated = 0;
+        for vpn in missing {
+            populated += self.populate_page(vpn)? as usize;
+        }
+        Ok(populated)
+    }
+
+    /// 若`vpn`落在由分配器分配的逻辑段内却未常驻，为其映射全零的页帧；
+    /// 若映射着零页，则换成私有的页帧，逻辑段可写时一并恢复写权限。返回是否新映射了该页。
+    ///
+    /// 页被[`Self::discard`]撤销映射后，由缺页异常经此重新映射；
+    /// 写入零页时亦由缺页异常经此写时复制。
+    pub fn populate_page(&mut self, vpn: VirtPageNum) -> Result<bool, MapError> {
+        let fits = self.fits(1);
+        let Some(seg) = self
+            .logic_segments
+            .iter_mut()
+            .find(|seg| seg.vpn_range.contains(&vpn))
+            .filter(|seg| {
+                seg.map_type == MapType::Framed && seg.vpn2frame.get(&vpn).is_none_or(is_zero)
+            })
+        else {
+            return Ok(false);
+        };
+        if !fits {
+            return Err(MapError {
+                vpn,
+                kind: MapErrorKind::ExceedLimit,
+            });
+        }
+
+        if seg.vpn2frame.contains_key(&vpn) {
+            seg.unmap_one(&mut self.page_table, vpn)?;
+        }
+        seg.map_one(&mut self.page_table, vpn)?;
+        // 无效的页表项也可能被快表缓存
+        self.flush_page(vpn);
+
+        Ok(true)
+    }
+
+    /// 若`vpn`是未常驻的匿名页，只读地映射零页，返回是否映射了该页。
+    ///
+    /// 读取从未写过的页因此不占用页帧，首次写入时由[`Self::populate_page`]换成私有的页帧。
+    pub fn map_zero_page(&mut self, vpn: VirtPageNum) -> Result<bool, MapError> {
+        let Some(seg) = self
+            .logic_segments
+            .iter_mut()
+            .find(|seg| seg.vpn_range.contains(&vpn))
+            .filter(|seg| {
+                seg.map_type == MapType::Framed
+                    && seg.file.is_none()
+                    && !seg.vpn2frame.contains_key(&vpn)
+            })
+        else {
+            return Ok(false);
+        };
+
+        seg.map_zero(&mut self.page_table, vpn)?;
+        self.flush_page(vpn);
+
+        Ok(true)
+    }
+
+    /// 处理用户以`access`访问`vpn`时的缺页，返回能否重新执行该访问。
+    ///
+    /// 先代硬件置上访问位与脏位，再为未常驻的页映射页帧，超出页帧上限时先回收文件映射的页。
+    /// 读取未常驻的匿名页时映射零页，不分配页帧。
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, access: MapPermission) -> bool {
+        let write = matches!(access, MapPermission::W);
+        let access = BitFlags::from_bits_truncate(access as u8);
+        if self.page_table.emulate_access(vpn, access) {
+            SOFTWARE_AD.call_once(|| {
+                log::info!(
+                    "[kernel] hardware does not update A/D bits, emulate them on page faults"
+                );
+            });
+            self.flush_page(vpn);
+            return true;
+        }
+        if !write && matches!(self.map_zero_page(vpn), Ok(true)) {
+            return true;
+        }
+        // 只读的逻辑段中的零页被写入，是真正的访存异常
+        if write
+            && self
+                .logic_segments
+                .iter()
+                .find(|seg| seg.vpn_range.contains(&vpn))
+                .is_some_and(|seg| !seg.permission.contains(MapPermission::W))
+        {
+            return false;
+        }
+        if write && self.break_cow(vpn) {
+            return true;
+        }
+
+        match self.populate_page(vpn) {
+            Ok(populated) => populated,
+            Err(MapError {
+                kind: MapErrorKind::ExceedLimit,
+                ..
+            }) => self.reclaim(1) > 0 && matches!(self.populate_page(vpn), Ok(true)),
+            Err(_) => false,
+        }
+    }
+
+    /// 回收至多`target`个文件映射的常驻页，返回回收的页数。
+    ///
+    /// 按访问位老化：首轮只回收访问位为0的页，并清除其余页的访问位；
+    /// 不够时次轮回收首轮放过的页。被回收的页先写回修改，再次访问时从文件读入。
+    /// 匿名内存无处换出，不会被回收。
+    pub fn reclaim(&mut self, target: usize) -> usize {
+        let mut victims = Vec::new();
+        'scan: for _ in 0..2 {
+            for seg in self.logic_segments.iter().filter(|seg| seg.file.is_some()) {
+                for &vpn in seg.vpn2frame.keys() {
+                    if victims.len() == target {
+                        break 'scan;
+                    }
+                    if !self.page_table.take_accessed(vpn) && !victims.contains(&vpn) {
+                        victims.push(vpn);
+                    }
+                }
+            }
+        }
+        // 清除了访问位的页表项须重新从内存中读取
+        self.asid.flush();
+
+        for &vpn in &victims {
+            self.write_back(vpn..vpn + 1);
+            let seg = self
+                .logic_segments
+                .iter_mut()
+                .find(|seg| seg.vpn_range.contains(&vpn))
+                .unwrap();
+            seg.unmap_one(&mut self.page_table, vpn).unwrap();
+            self.flush_page(vpn);
+        }
+
+        victims.len()
+    }
 
-    /// 删除所有段，主要目的是归还物理页帧
+    /// `vpns`内的页是否都落在逻辑段内
+    pub fn covers(&self, vpns: Range<VirtPageNum>) -> bool {
+        let mut segs: Vec<_> = self
+            .logic_segments
+            .iter()
+            .map(|seg| seg.vpn_range.clone())
+            .collect();
+        segs.sort_unstable_by_key(|range| range.start);
+
+        let mut next = vpns.start;
+        for range in segs {
+            if next >= vpns.end || range.start > next {
+                break;
+            }
+            next = next.max(range.end);
+        }
+        next >= vpns.end
+    }
+
+    /// 调试器访问用户内存：`va`须落在用户可访问的已映射页内，返回其物理地址。
+    ///
+    /// 写访问时先让该页独占页帧，免得改动波及与之共享页帧的其它地址空间，
+    /// 页表的权限位不受影响，因而可以改写代码段。
+    pub fn debug_access(&mut self, va: VirtAddr, write: bool) -> Option<PhysAddr> {
+        let vpn = va.page_number();
+        let entry = self.translate(vpn)?;
+        if !entry.is_valid() || !entry.flags().contains(PTEFlag::U) {
+            return None;
+        }
+
+        if write {
+            // 零页须换成私有的页帧，否则写入会波及所有映射它的页
+            self.populate_page(vpn).ok()?;
+            let seg = self
+                .logic_segments
+                .iter_mut()
+                .find(|seg| seg.vpn_range.contains(&vpn))?;
+            if seg.unshare(&mut self.page_table, vpn)? {
+                self.flush_page(vpn);
+            }
+        }
+
+        let ppn = self.translate(vpn)?.ppn();
+        Some(PhysAddr::from(ppn) + va.page_offset())
+    }
+
+    /// 内核代用户访问`vpn`这一页：按需映射该页后返回其物理页号。
+    /// 该页不是用户可访问的已映射页，或是`write`而用户不可写时，返回`None`。
+    ///
+    /// 内核按物理地址访问用户内存，既不触发缺页，也不受页表的权限约束，
+    /// 故写入前须先让零页与合并的页换成私有的页帧，并代硬件置上脏位，以免文件映射的修改不被写回。
+    pub fn user_page(&mut self, vpn: VirtPageNum, write: bool) -> Option<PhysPageNum> {
+        if write {
+            self.populate_page(vpn).ok()?;
+            self.break_cow(vpn);
+        } else if !self.translate(vpn).is_some_and(|entry| entry.is_valid()) {
+            // 读取未常驻的匿名页映射零页即可，文件映射的页则须读入
+            if !self.map_zero_page(vpn).ok()? {
+                self.populate_page(vpn).ok()?;
+            }
+        }
+
+        let mut access = BitFlags::from(PTEFlag::R);
+        if write {
+            access |= PTEFlag::W;
+        }
+        let entry = self.translate(vpn)?;
+        if !entry.is_valid() || !entry.flags().contains(access | PTEFlag::U) {
+            return None;
+        }
+        let ppn = entry.ppn();
+
+        if self.page_table.emulate_access(vpn, access) {
+            self.flush_page(vpn);
+        }
+        Some(ppn)
+    }
+
+    /// `vpn`所映射的页帧，不由分配器映射时返回`None`。
+    /// 持有者在页被撤销映射、迁移或合并后仍可安全地访问原页帧，见[`UserBuffer`](super::UserBuffer)
+    pub fn frame(&self, vpn: VirtPageNum) -> Option<Arc<Frame>> {
+        self.logic_segments
+            .iter()
+            .find(|seg| seg.vpn_range.contains(&vpn))?
+            .vpn2frame
+            .get(&vpn)
+            .cloned()
+    }
+
+    /// 常驻的物理页帧数，只计由分配器分配的页帧，不计零页
+    pub fn resident_frames(&self) -> usize {
+        self.logic_segments.iter().map(|seg| seg.resident).sum()
+    }
+
+    pub fn frame_limit(&self) -> Option<usize> {
+        self.frame_limit
+    }
+
+    /// 设置可常驻的物理页帧数上限。
+    /// 上限可低于当前的常驻页帧数，此后的分配都会失败。
+    pub fn set_frame_limit(&mut self, limit: Option<usize>) {
+        self.frame_limit = limit;
+    }
+
+    /// 能否再分配`frames`个物理页帧而不超出上限
+    pub fn fits(&self, frames: usize) -> bool {
+        self.frame_limit
+            .is_none_or(|limit| self.resident_frames() + frames <= limit)
+    }
+
+    /// 删除所有段，主要目的是归还物理页帧，文件映射的修改先行写回
     pub fn clear(&mut self) {
+        self.write_back(VirtPageNum::from(0)..VirtAddr::from(USER_SPACE_END).floor());
+        self.forget_mappings();
         self.logic_segments.clear();
     }
 
@@ -511,10 +981,8 @@ impl AddressSpace {
     pub fn activate(&self) {
         let satp = self.page_table.token();
         satp::write(satp);
-        // 快表 TLB 会缓存虚拟页号到页表项的映射，
-        // 但它与地址空间耦合，
-        // 改动 satp 的值即切换地址空间会导致
-        // 快表缓存失效，故而需要清零
+        // 只在启动时激活内核地址空间，此前的快表内容一概作废。
+        // 此后的切换由satp中的ASID区分，见[`asid`](super::asid)
         unsafe { riscv64::sfence_vma_all() };
     }
 
@@ -552,6 +1020,31 @@ impl AddressSpace {
 }
 
 impl AddressSpace {
+    fn new(asid: Asid) -> Self {
+        Self {
+            page_table: PageTable::new(asid.get()),
+            logic_segments: Vec::new(),
+            frame_limit: None,
+            asid,
+        }
+    }
+
+    /// 刷新`vpn`这一页的快表项
+    fn flush_page(&self, vpn: VirtPageNum) {
+        self.asid.flush_page(VirtAddr::from(vpn).into());
+    }
+
+    /// 刷新`vpns`的快表项，页数较多时刷新整个地址空间
+    fn flush_range(&self, vpns: Range<VirtPageNum>) {
+        if vpns.clone().count() > FLUSH_PAGES_MAX {
+            self.asid.flush();
+        } else {
+            for vpn in vpns {
+                self.flush_page(vpn);
+            }
+        }
+    }
+
     /// 在内核地址空间高256G部分的顶层分配跳板
     // NOTE: 实际上是将虚拟地址 TRAMPOLINE 映射到 .text.strampoline
     fn map_trampoline(&mut self) {
@@ -559,13 +1052,113 @@ impl AddressSpace {
             .map(
                 VirtAddr::from(TRAMPOLINE),
                 PhysAddr::from(strampoline as usize),
-                PTEFlag::R | PTEFlag::X,
+                PTEFlag::R | PTEFlag::X | PTEFlag::A,
             )
             .unwrap();
     }
 
-    fn push(&mut self, mut seg: LogicSegment) -> Result<(), MappedVpn> {
+    /// 在用户地址空间的最后一页只读地映射时钟共享页，它不属于任何逻辑段。
+    /// 页表项没有W位，系统调用不会代用户写入该页，见[`Self::user_page`]
+    fn map_vdso(&mut self) {
+        self.page_table
+            .map(
+                VirtAddr::from(VDSO_VA),
+                vdso::ppn(),
+                PTEFlag::R | PTEFlag::U | PTEFlag::A,
+            )
+            .unwrap();
+    }
+
+    /// 写入可写逻辑段中被收回写权限的页时，让其独占页帧并恢复写权限，返回是否如此处理了。
+    /// 页帧已无他人共享时不必复制。
+    fn break_cow(&mut self, vpn: VirtPageNum) -> bool {
+        if self
+            .translate(vpn)
+            .is_none_or(|entry| !entry.is_valid() || entry.flags().contains(PTEFlag::W))
+        {
+            return false;
+        }
+        let Some(seg) = self.logic_segments.iter_mut().find(|seg| {
+            seg.vpn_range.contains(&vpn)
+                && seg.permission.contains(MapPermission::W)
+                && seg.vpn2frame.get(&vpn).is_some_and(|frame| !is_zero(frame))
+        }) else {
+            return false;
+        };
+        if seg.unshare(&mut self.page_table, vpn).is_none() {
+            return false;
+        }
+
+        self.page_table.allow_write(vpn);
+        self.flush_page(vpn);
+        true
+    }
+
+    /// 从反向映射中除去本地址空间的所有映射
+    fn forget_mappings(&self) {
+        let token = self.page_table.token();
+        for (&vpn, frame) in self.logic_segments.iter().flat_map(|seg| &seg.vpn2frame) {
+            rmap::remove(frame.ppn, Mapping { token, vpn });
+        }
+    }
+
+    /// 恰为`vpns`的mmap逻辑段的下标
+    fn mmap_index(&self, vpns: &Range<VirtPageNum>) -> Result<usize, MapError> {
+        let index = self
+            .logic_segments
+            .iter()
+            .position(|seg| seg.vpn_range == *vpns)
+            .ok_or(MapError {
+                vpn: vpns.start,
+                kind: MapErrorKind::NoSegement,
+            })?;
+        if !self.logic_segments[index].mmapped {
+            return Err(MapError {
+                vpn: vpns.start,
+                kind: MapErrorKind::TypeMissed,
+            });
+        }
+        Ok(index)
+    }
+
+    /// `vpns`是否位于用户地址空间内，且不与任何逻辑段及时钟共享页重叠
+    fn is_free(&self, vpns: Range<VirtPageNum>) -> bool {
+        vpns.end <= VirtAddr::from(VDSO_VA).floor()
+            && self
+                .logic_segments
+                .iter()
+                .all(|seg| seg.vpn_range.end <= vpns.start || vpns.end <= seg.vpn_range.start)
+    }
+
+    /// [`MMAP_BASE`]之上首个能容纳`pages`页的空闲区间的起始页
+    fn find_free(&self, pages: usize) -> Option<VirtPageNum> {
+        let mut ranges: Vec<_> = self
+            .logic_segments
+            .iter()
+            .map(|seg| seg.vpn_range.clone())
+            .collect();
+        ranges.sort_unstable_by_key(|range| range.start);
+
+        let mut start = VirtAddr::from(MMAP_BASE).floor();
+        for range in ranges {
+            if range.start >= start + pages {
+                break;
+            }
+            start = start.max(range.end);
+        }
+        self.is_free(start..start + pages).then_some(start)
+    }
+
+    fn push(&mut self, mut seg: LogicSegment) -> Result<(), MapError> {
+        if seg.map_type == MapType::Framed && !self.fits(seg.vpn_range.clone().count()) {
+            return Err(MapError {
+                vpn: seg.vpn_range.start,
+                kind: MapErrorKind::ExceedLimit,
+            });
+        }
+
         seg.map(&mut self.page_table)?;
+        self.flush_range(seg.vpn_range.clone());
         self.logic_segments.push(seg);
         Ok(())
     }
@@ -576,6 +1169,50 @@ impl AddressSpace {
         self.logic_segments.push(seg);
         Ok(())
     }
+
+    /// 映射程序映像的只读段：映像中已有其各页的页帧时直接共享，
+    /// 否则同[`Self::push_with_data`]，并将新载入的页帧记入映像
+    fn push_shared(
+        &mut self,
+        mut seg: LogicSegment,
+        data: &[u8],
+        image: &Image,
+    ) -> Result<(), MappedVpn> {
+        let mut frames = image.frames().exclusive_access();
+        let cached: Option<Vec<_>> = seg
+            .vpn_range
+            .clone()
+            .map(|vpn| frames.get(&vpn).cloned())
+            .collect();
+
+        let Some(cached) = cached else {
+            self.push_with_data(seg, data)?;
+            let seg = self.logic_segments.last().unwrap();
+            frames.extend(
+                seg.vpn2frame
+                    .iter()
+                    .map(|(&vpn, frame)| (vpn, frame.clone())),
+            );
+            return Ok(());
+        };
+
+        for (vpn, frame) in seg.vpn_range.clone().zip(cached) {
+            seg.map_frame(&mut self.page_table, vpn, frame)?;
+        }
+        self.logic_segments.push(seg);
+        Ok(())
+    }
+}
+
+/// `frame`是否为零页
+fn is_zero(frame: &Arc<Frame>) -> bool {
+    Arc::ptr_eq(frame, &ZERO_FRAME)
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        self.forget_mappings();
+    }
 }
 
 impl Clone for LogicSegment {
@@ -584,8 +1221,13 @@ impl Clone for LogicSegment {
         Self {
             vpn_range: self.vpn_range.clone(),
             vpn2frame: BTreeMap::new(),
+            resident: 0,
             map_type: self.map_type,
             permission: self.permission,
+            movable: self.movable,
+            mmapped: self.mmapped,
+            lazy: self.lazy,
+            file: self.file.clone(),
         }
     }
 }
@@ -602,13 +1244,27 @@ impl LogicSegment {
         Self {
             vpn_range: Range { start, end },
             vpn2frame: BTreeMap::new(),
+            resident: 0,
             map_type,
             permission,
+            movable: false,
+            mmapped: false,
+            lazy: false,
+            file: None,
         }
     }
 
-    /// 将该逻辑段映射到物理内存
+    /// 标记为可迁移
+    fn movable(mut self) -> Self {
+        self.movable = self.map_type == MapType::Framed;
+        self
+    }
+
+    /// 将该逻辑段映射到物理内存，按需映射的逻辑段留待缺页时
     fn map(&mut self, page_table: &mut PageTable) -> Result<(), MappedVpn> {
+        if self.lazy {
+            return Ok(());
+        }
         for vpn in self.vpn_range.clone() {
             // 若VPN已被映射，则回收该逻辑段已分配的内存
             if let Err(e) = self.map_one(page_table, vpn) {
@@ -637,7 +1293,12 @@ impl LogicSegment {
             MapType::Framed => {
                 let frame = frame_allocator::alloc().unwrap();
                 ppn = frame.ppn;
-                self.vpn2frame.insert(vpn, frame);
+                if let Some(file) = &self.file {
+                    file.read_at(self.file_offset(vpn), ppn.page_bytes_mut());
+                }
+                self.insert_frame(vpn, Arc::new(frame));
+                let token = page_table.token();
+                rmap::add(ppn, Mapping { token, vpn });
             }
             MapType::Linear(pn_offset) => {
                 let vpn: usize = vpn.into();
@@ -646,8 +1307,38 @@ impl LogicSegment {
             }
         }
 
-        let pte_flags = BitFlags::from_bits_truncate(self.permission.bits());
-        page_table.map(vpn, ppn, pte_flags)
+        page_table.map(vpn, ppn, self.pte_flags())
+    }
+
+    /// 映射已有的页帧`frame`，与其它地址空间共享
+    fn map_frame(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+        frame: Arc<Frame>,
+    ) -> Result<(), MappedVpn> {
+        assert_eq!(self.map_type, MapType::Framed);
+        let ppn = frame.ppn;
+        self.insert_frame(vpn, frame);
+        let token = page_table.token();
+        rmap::add(ppn, Mapping { token, vpn });
+        page_table.map(vpn, ppn, self.pte_flags())
+    }
+
+    /// 只读地映射零页，零页不记入反向映射
+    fn map_zero(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Result<(), MappedVpn> {
+        assert_eq!(self.map_type, MapType::Framed);
+        self.insert_frame(vpn, ZERO_FRAME.clone());
+        page_table.map(vpn, ZERO_FRAME.ppn, self.pte_flags() & !PTEFlag::W)
+    }
+
+    fn pte_flags(&self) -> BitFlags<PTEFlag> {
+        let mut pte_flags = BitFlags::from_bits_truncate(self.permission.bits());
+        // 内核访问的页预先置上访问位与脏位，以免在不维护这两位的硬件上缺页
+        if !self.permission.contains(MapPermission::U) {
+            pte_flags |= PTEFlag::A | PTEFlag::D;
+        }
+        pte_flags
     }
 
     fn unmap_one(
@@ -656,12 +1347,134 @@ impl LogicSegment {
         vpn: VirtPageNum,
     ) -> Result<(), UnmappedVpn> {
         if self.map_type == MapType::Framed {
-            self.vpn2frame.remove(&vpn);
+            // 该页已被单独撤销过映射
+            let Some(frame) = self.remove_frame(vpn) else {
+                return Ok(());
+            };
+            let token = page_table.token();
+            rmap::remove(frame.ppn, Mapping { token, vpn });
         }
 
         page_table.unmap(vpn)
     }
 
+    /// 记下`vpn`所映射的页帧，并维护常驻页帧数
+    fn insert_frame(&mut self, vpn: VirtPageNum, frame: Arc<Frame>) {
+        if !is_zero(&frame) {
+            self.resident += 1;
+        }
+        if let Some(old) = self.vpn2frame.insert(vpn, frame) {
+            if !is_zero(&old) {
+                self.resident -= 1;
+            }
+        }
+    }
+
+    /// 去掉`vpn`所映射的页帧，并维护常驻页帧数
+    fn remove_frame(&mut self, vpn: VirtPageNum) -> Option<Arc<Frame>> {
+        let frame = self.vpn2frame.remove(&vpn)?;
+        if !is_zero(&frame) {
+            self.resident -= 1;
+        }
+        Some(frame)
+    }
+
+    /// `vpn`的页在所映射文件中的字节偏移
+    fn file_offset(&self, vpn: VirtPageNum) -> usize {
+        (usize::from(vpn) - usize::from(self.vpn_range.start)) * PAGE_SIZE
+    }
+
+    /// 将逻辑段整体移到以`start`起始的虚拟页上，常驻的页帧随之改映射
+    fn relocate(&mut self, page_table: &mut PageTable, start: VirtPageNum) -> Result<(), MapError> {
+        let token = page_table.token();
+        let old_start = self.vpn_range.start;
+        let mut vpn2frame = BTreeMap::new();
+
+        for (vpn, frame) in mem::take(&mut self.vpn2frame) {
+            let new_vpn = start + (usize::from(vpn) - usize::from(old_start));
+            // 连同脏位一起搬走
+            let flags = page_table.translate(vpn).unwrap().flags();
+            page_table.unmap(vpn)?;
+            page_table.map(new_vpn, frame.ppn, flags)?;
+            if !is_zero(&frame) {
+                rmap::remove(frame.ppn, Mapping { token, vpn });
+                rmap::add(
+                    frame.ppn,
+                    Mapping {
+                        token,
+                        vpn: new_vpn,
+                    },
+                );
+            }
+            vpn2frame.insert(new_vpn, frame);
+        }
+
+        self.vpn2frame = vpn2frame;
+        self.vpn_range = start..start + self.vpn_range.clone().count();
+        Ok(())
+    }
+
+    /// 将落在`window`内的物理页复制到新页帧上，并让页表项指向新页帧
+    fn migrate(&mut self, page_table: &mut PageTable, window: &Range<PhysPageNum>) -> usize {
+        let mut migrated = 0;
+
+        // 共享的页帧迁走也无法释放，被系统调用的缓冲区持有的页帧内核还要读写，故都略过
+        for (&vpn, frame) in self
+            .vpn2frame
+            .iter_mut()
+            .filter(|(_, frame)| window.contains(&frame.ppn) && Arc::strong_count(&**frame) == 1)
+        {
+            let Some(new_frame) = frame_allocator::alloc() else {
+                break;
+            };
+            new_frame
+                .ppn
+                .page_bytes_mut()
+                .copy_from_slice(frame.ppn.page_bytes());
+            page_table.remap(vpn, new_frame.ppn).unwrap();
+            let mapping = Mapping {
+                token: page_table.token(),
+                vpn,
+            };
+            rmap::remove(frame.ppn, mapping);
+            rmap::add(new_frame.ppn, mapping);
+            // 旧页帧随之释放
+            *frame = Arc::new(new_frame);
+            migrated += 1;
+        }
+
+        migrated
+    }
+
+    /// 让`vpn`独占其页帧，返回是否换了页帧。`vpn`不由分配器映射时返回`None`。
+    fn unshare(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Option<bool> {
+        let frame = self.vpn2frame.get_mut(&vpn)?;
+        if Arc::strong_count(frame) == 1 {
+            return Some(false);
+        }
+        let was_zero = is_zero(frame);
+
+        let new_frame = frame_allocator::alloc()?;
+        new_frame
+            .ppn
+            .page_bytes_mut()
+            .copy_from_slice(frame.ppn.page_bytes());
+        page_table.remap(vpn, new_frame.ppn).unwrap();
+        let mapping = Mapping {
+            token: page_table.token(),
+            vpn,
+        };
+        rmap::remove(frame.ppn, mapping);
+        rmap::add(new_frame.ppn, mapping);
+        *frame = Arc::new(new_frame);
+        // 零页换成了私有的页帧
+        if was_zero {
+            self.resident += 1;
+        }
+
+        Some(true)
+    }
+
     /// 将数据写到逻辑段所映射的物理页内
     fn write_data(&mut self, page_table: &PageTable, data: &[u8]) {
         assert_eq!(self.map_type, MapType::Framed);
@@ -726,7 +1539,12 @@ mod error {
         NoSegement,
         MappedVpn,
         UnmappedVpn,
-        // TypeMissed,
+        /// 超出地址空间的物理页帧上限
+        ExceedLimit,
+        /// 没有足够大的空闲虚拟地址区间
+        NoSpace,
+        /// 逻辑段不是所需的类型
+        TypeMissed,
     }
 
     impl From<MappedVpn> for MapError {
@@ -0,0 +1,108 @@
+//! # 地址空间标识符(ASID)
+//!
+//! satp的ASID字段标明快表项属于哪个地址空间。各地址空间的编号互不相同时，
+//! 切换地址空间不必清空快表，修改映射后也只需按页刷新该编号下的快表项。
+//!
+//! - 内核地址空间固定为[`KERNEL`]；
+//! - 用户地址空间创建时分配编号，销毁时先清除该编号的快表项再回收；
+//! - 硬件不支持ASID或编号用尽时得到[`SHARED`]，`__alltraps`与`__restore`
+//!   见到它便在切换satp后清空快表，与不用ASID时一样。
+
+use alloc::vec::Vec;
+use core::arch::riscv64;
+
+use riscv::register::satp;
+
+use crate::sync::UpCell;
+
+/// satp中ASID字段的起始位
+pub const SATP_SHIFT: usize = 44;
+/// satp中ASID字段的掩码（移位前），SV39下至多16位
+const MASK: usize = 0xFFFF;
+
+/// 无专属编号的用户地址空间共用，切换时须清空快表
+pub const SHARED: usize = 0;
+/// 内核地址空间
+pub const KERNEL: usize = 1;
+
+static POOL: UpCell<Pool> = UpCell::new(Pool {
+    next: KERNEL + 1,
+    end: KERNEL + 1,
+    recycled: Vec::new(),
+});
+
+/// 可分配给用户地址空间的编号
+struct Pool {
+    next: usize,
+    /// 硬件支持的最大编号加一，探测前不分配
+    end: usize,
+    recycled: Vec<usize>,
+}
+
+/// 探测硬件支持的ASID宽度，须在内核地址空间激活后调用
+pub fn init() {
+    let kernel_satp = satp::read().bits();
+    // ASID字段是WARL的，写入全1后读回的即是支持的位
+    satp::write(kernel_satp | (MASK << SATP_SHIFT));
+    let max = of(satp::read().bits());
+    satp::write(kernel_satp);
+    unsafe { riscv64::sfence_vma_all() };
+
+    let end = (max + 1).max(KERNEL + 1);
+    POOL.exclusive_access().end = end;
+    log::info!(
+        "[kernel] {} ASIDs for user address spaces",
+        end - (KERNEL + 1)
+    );
+}
+
+/// satp中的ASID
+pub const fn of(satp: usize) -> usize {
+    (satp >> SATP_SHIFT) & MASK
+}
+
+/// 地址空间的编号，用户地址空间的编号在销毁时回收
+#[derive(Debug)]
+pub struct Asid(usize);
+
+impl Asid {
+    /// 分配专属的编号，用尽时得到[`SHARED`]
+    pub fn alloc() -> Self {
+        let mut pool = POOL.exclusive_access();
+        let id = pool.recycled.pop().or_else(|| {
+            (pool.next < pool.end).then(|| {
+                pool.next += 1;
+                pool.next - 1
+            })
+        });
+        Self(id.unwrap_or(SHARED))
+    }
+
+    pub const fn kernel() -> Self {
+        Self(KERNEL)
+    }
+
+    pub const fn get(&self) -> usize {
+        self.0
+    }
+
+    /// 清除该编号下的所有快表项
+    pub fn flush(&self) {
+        unsafe { riscv64::sfence_vma_asid(self.0) };
+    }
+
+    /// 清除该编号下`va`所在页的快表项
+    pub fn flush_page(&self, va: usize) {
+        unsafe { riscv64::sfence_vma(va, self.0) };
+    }
+}
+
+impl Drop for Asid {
+    fn drop(&mut self) {
+        if self.0 > KERNEL {
+            // 下一个使用者不能看到本地址空间的翻译
+            self.flush();
+            POOL.exclusive_access().recycled.push(self.0);
+        }
+    }
+}
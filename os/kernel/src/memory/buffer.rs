@@ -1,31 +1,56 @@
 use core::mem::{self, MaybeUninit};
 use core::{ptr, slice};
 
+use alloc::string::String;
+use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
 
-use super::address::VirtAddr;
-use super::PageTable;
+use super::address::{PhysPageNum, VirtAddr, VirtPageNum};
+use super::frame_allocator::Frame;
+use crate::config::{ARG_MAX, MAX_ARG_STRINGS, MAX_ARG_STRLEN, USER_SPACE_END};
+use crate::task::processor;
+
+use vfs::PATH_MAX;
 
 /// 来自用户空间的缓冲区
 #[derive(Default)]
 pub struct UserBuffer {
-    token: usize,
     bufs: Vec<&'static mut [u8]>,
+    /// `bufs`所在的页帧。系统调用可能阻塞，期间页帧被迁移、合并或撤销映射时，
+    /// 持有引用使其不被释放，迁移与合并也会略过被持有的页帧
+    _frames: Vec<Arc<Frame>>,
 }
 
 impl UserBuffer {
-    /// 翻译虚拟内存的指针，集合来自不同物理页的字节流以组成连续的字节流(mut)
-    pub fn new(token: usize, ptr: *mut u8, len: usize) -> Self {
-        let page_table = PageTable::from_token(token);
-        let mut start = ptr as usize;
-        let end = start + len;
+    /// 翻译当前进程虚拟内存的指针，集合来自不同物理页的字节流以组成连续的字节流，内核从中读取
+    ///
+    /// 区间内的每一页都须已映射且用户可访问，否则返回`None`，不会只翻译前面的几页
+    pub fn new(ptr: *const u8, len: usize) -> Option<Self> {
+        Self::translate(ptr as usize, len, false)
+    }
+
+    /// 同[`Self::new`]，但内核要写入其中，每一页还须用户可写。
+    ///
+    /// 只读的代码段与数据段可能与运行同一程序的其它进程共享页帧，时钟共享页更是全局唯一，
+    /// 内核按物理地址写入时不受页表保护，故须在此拒绝
+    pub fn new_mut(ptr: *mut u8, len: usize) -> Option<Self> {
+        Self::translate(ptr as usize, len, true)
+    }
+
+    fn translate(mut start: usize, len: usize, write: bool) -> Option<Self> {
+        let end = start.checked_add(len)?;
+        if end > USER_SPACE_END {
+            return None;
+        }
         let mut bytes = vec![];
+        let mut frames = vec![];
 
         while start < end {
             let start_va = VirtAddr::from(start);
             let vpn = start_va.page_number();
-            let ppn = page_table.translate(vpn).unwrap().ppn();
+            let (ppn, frame) = user_page(vpn, write)?;
+            frames.extend(frame);
             let end_va = VirtAddr::from(end).min(VirtAddr::from(vpn + 1));
 
             if end_va.page_offset() == 0 {
@@ -39,11 +64,10 @@ impl UserBuffer {
             start = end_va.into();
         }
 
-        Self { token, bufs: bytes }
-    }
-
-    pub const fn token(&self) -> usize {
-        self.token
+        Some(Self {
+            bufs: bytes,
+            _frames: frames,
+        })
     }
 
     #[inline]
@@ -99,11 +123,104 @@ impl AsMut<[&'static mut [u8]]> for UserBuffer {
     }
 }
 
-pub fn write_any<T: 'static>(token: usize, ptr: *mut T, value: T) {
-    let mut buffer = UserBuffer::new(token, ptr.cast(), mem::size_of::<T>());
+/// 当前进程的`vpn`这一页的物理页号及其页帧，见[`AddressSpace::user_page`](super::AddressSpace::user_page)
+fn user_page(vpn: VirtPageNum, write: bool) -> Option<(PhysPageNum, Option<Arc<Frame>>)> {
+    let current = processor::current();
+    let mut inner = current.process().inner().exclusive_access();
+    let ppn = inner.address_space.user_page(vpn, write)?;
+    Some((ppn, inner.address_space.frame(vpn)))
+}
+
+/// 读出当前进程用户空间`ptr`处的`T`，所在的页未映射或用户不可访问时返回`None`
+pub fn read_any<T: Copy>(ptr: *const T) -> Option<T> {
+    let buffer = UserBuffer::new(ptr.cast(), mem::size_of::<T>())?;
+    let mut value = MaybeUninit::<T>::uninit();
+    for (b, &ub) in value.as_bytes_mut().iter_mut().zip(buffer.iter()) {
+        b.write(ub);
+    }
+    Some(unsafe { value.assume_init() })
+}
+
+/// 将`value`写入当前进程用户空间的`ptr`处，所在的页未映射或用户不可写时返回`false`
+#[must_use]
+pub fn write_any<T: 'static>(ptr: *mut T, value: T) -> bool {
+    let Some(mut buffer) = UserBuffer::new_mut(ptr.cast(), mem::size_of::<T>()) else {
+        return false;
+    };
     let bytes =
         unsafe { slice::from_raw_parts(ptr::from_ref(&value).cast::<u8>(), mem::size_of::<T>()) };
     for (b, &vb) in buffer.iter_mut().zip(bytes) {
         *b = vb;
     }
+    true
+}
+
+/// 从用户空间读取字符串失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrError {
+    /// 某一页未映射或用户不可读
+    Fault,
+    /// 超出长度限制
+    TooLong,
+}
+
+/// 读取当前进程用户空间`src`处至多`max_len`字节(含终止符)的字符串。
+///
+/// 逐页检查权限，读到终止符即止，不会触及其后的页
+pub fn read_cstr(src: *const u8, max_len: usize) -> Result<String, StrError> {
+    let mut bytes = Vec::new();
+    let mut start = src as usize;
+
+    while bytes.len() < max_len {
+        if start >= USER_SPACE_END {
+            return Err(StrError::Fault);
+        }
+        let va = VirtAddr::from(start);
+        let (ppn, _frame) = user_page(va.page_number(), false).ok_or(StrError::Fault)?;
+        let page = &ppn.page_bytes()[va.page_offset()..];
+        let page = &page[..page.len().min(max_len - bytes.len())];
+
+        if let Some(nul) = page.iter().position(|&b| b == b'\0') {
+            bytes.extend_from_slice(&page[..nul]);
+            return Ok(bytes.iter().map(|&b| b as char).collect());
+        }
+        bytes.extend_from_slice(page);
+        start += page.len();
+    }
+
+    Err(StrError::TooLong)
+}
+
+/// 读取当前进程用户空间`src`处的路径，连同终止符至多[`PATH_MAX`]字节
+pub fn read_path(src: *const u8) -> Result<String, StrError> {
+    read_cstr(src, PATH_MAX)
+}
+
+/// 读取当前进程以空指针结尾的参数指针数组，以及各指针指向的字符串。
+///
+/// 参数个数、单个参数长度、总长度分别受[`MAX_ARG_STRINGS`]、[`MAX_ARG_STRLEN`]、
+/// [`ARG_MAX`]限制，超出任一者都返回[`StrError::TooLong`]
+pub fn read_argv(argv: *const usize) -> Result<Vec<String>, StrError> {
+    let mut args = Vec::new();
+    // 终止符所占的指针
+    let mut total = mem::size_of::<usize>();
+
+    for i in 0..=MAX_ARG_STRINGS {
+        let arg = read_any(argv.wrapping_add(i)).ok_or(StrError::Fault)? as *const u8;
+        if arg.is_null() {
+            return Ok(args);
+        }
+        if i == MAX_ARG_STRINGS {
+            break;
+        }
+
+        let arg = read_cstr(arg, MAX_ARG_STRLEN)?;
+        total += mem::size_of::<usize>() + arg.len() + 1;
+        if total > ARG_MAX {
+            return Err(StrError::TooLong);
+        }
+        args.push(arg);
+    }
+
+    Err(StrError::TooLong)
 }
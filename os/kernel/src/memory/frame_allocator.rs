@@ -1,5 +1,7 @@
 use alloc::collections::VecDeque;
 use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
 
 use super::address::{PhysAddr, PhysPageNum};
 use crate::config::MEMORY_END;
@@ -11,6 +13,11 @@ extern "C" {
 
 static FRAME_ALLOCATOR: UpCell<StackFrameAllocator> = UpCell::new(StackFrameAllocator::new());
 
+/// 压缩钩子：把落在窗口内的可迁移页面搬走，返回迁移的页数
+pub type CompactionHook = fn(&Range<PhysPageNum>) -> usize;
+
+static COMPACTION_HOOK: UpCell<Option<CompactionHook>> = UpCell::new(None);
+
 pub fn init() {
     FRAME_ALLOCATOR.exclusive_access().init(
         PhysAddr::from(ekernel as usize).ceil(),
@@ -18,14 +25,19 @@ pub fn init() {
     );
 }
 
+/// 注册压缩钩子，连续分配失败时会调用它腾出空间
+pub fn register_compaction_hook(hook: CompactionHook) {
+    *COMPACTION_HOOK.exclusive_access() = Some(hook);
+}
+
 pub fn alloc() -> Option<Frame> {
     FRAME_ALLOCATOR.exclusive_access().alloc().map(Frame::new)
 }
 
 pub fn alloc_continuous(len: usize) -> Option<Vec<Frame>> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc_continuous(len)
+    let pages = FRAME_ALLOCATOR.exclusive_access().alloc_continuous(len);
+    pages
+        .or_else(|| compact(len))
         .map(|pages| pages.into_iter().map(Frame::new).collect())
 }
 
@@ -33,6 +45,37 @@ pub fn dealloc(ppn: PhysPageNum) {
     FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
 }
 
+/// 当前物理页帧的碎片化情况
+pub fn report() -> FragmentationReport {
+    FRAME_ALLOCATOR.exclusive_access().report()
+}
+
+/// 压缩物理内存，腾出`len`个连续的页帧
+///
+/// 1. 挑选空闲页帧最多的窗口，先扣留其中的空闲页帧，免得迁移时又被分配出去；
+/// 2. 通过钩子将窗口内的可迁移页面搬到窗口外；
+/// 3. 再次扣留窗口内被释放的页帧，若整个窗口都空闲了，便分配之。
+fn compact(len: usize) -> Option<Vec<PhysPageNum>> {
+    let hook = (*COMPACTION_HOOK.exclusive_access())?;
+
+    let window = FRAME_ALLOCATOR.exclusive_access().densest_window(len)?;
+    FRAME_ALLOCATOR.exclusive_access().reserve(&window);
+
+    let window = PhysPageNum::from_raw(window.start)..PhysPageNum::from_raw(window.end);
+    let migrated = hook(&window);
+    log::debug!("compaction migrated {migrated} pages in [{window:?})");
+
+    let mut allocator = FRAME_ALLOCATOR.exclusive_access();
+    let window = usize::from(window.start)..usize::from(window.end);
+    if allocator.reserve(&window) == len {
+        Some(allocator.take_reserved())
+    } else {
+        allocator.release_reserved();
+        log::warn!("compaction failed: {}", allocator.report());
+        None
+    }
+}
+
 /// 物理页帧分配器
 ///
 /// 物理页帧的管理有多种策略，其中最简单的一种是栈式分配
@@ -49,10 +92,55 @@ trait FrameAllocator {
 /// 页号区间 [current, end) 的物理内存**从未**被分配
 #[derive(Default)]
 pub struct StackFrameAllocator {
+    /// 可分配页号区间的左端点
+    start: usize,
     current: usize,
     end: usize,
     /// 被回收的物理页号之栈，栈顶位于尾部
     recycled: VecDeque<usize>,
+    /// 压缩期间扣留的空闲页号，不参与分配
+    reserved: Vec<usize>,
+}
+
+/// 碎片化报告
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FragmentationReport {
+    /// 空闲页帧总数
+    pub free_frames: usize,
+    /// 从未分配过的页帧数
+    pub untouched_frames: usize,
+    /// 回收栈中的页帧数
+    pub recycled_frames: usize,
+    /// 空闲页帧组成的连续段数
+    pub free_runs: usize,
+    /// 最长连续空闲段的页帧数
+    pub largest_run: usize,
+}
+
+impl FragmentationReport {
+    /// 碎片率(百分比)：不属于最长连续段的空闲页帧占比
+    pub fn fragmentation(&self) -> usize {
+        if self.free_frames == 0 {
+            0
+        } else {
+            (self.free_frames - self.largest_run) * 100 / self.free_frames
+        }
+    }
+}
+
+impl fmt::Display for FragmentationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "free={} (untouched={}, recycled={}) runs={} largest_run={} fragmentation={}%",
+            self.free_frames,
+            self.untouched_frames,
+            self.recycled_frames,
+            self.free_runs,
+            self.largest_run,
+            self.fragmentation()
+        )
+    }
 }
 
 /// 实际上是一个独占指针
@@ -90,25 +178,36 @@ impl FrameAllocator for StackFrameAllocator {
         }
     }
 
-    /// 分配一段连续的页面
+    /// 分配一段连续的页面，页号降序排列
+    ///
+    /// 优先从未分配区间切出，其次在回收栈中寻找连续段
     fn alloc_continuous(&mut self, len: usize) -> Option<Vec<PhysPageNum>> {
         let new_current = self.current + len;
-        (new_current < self.end).then(|| {
+        if new_current < self.end {
             self.current = new_current;
-            (1..=len)
-                .map(|i| PhysPageNum::from(new_current - i))
-                .collect()
-        })
+            return Some(
+                (1..=len)
+                    .map(|i| PhysPageNum::from(new_current - i))
+                    .collect(),
+            );
+        }
+
+        let start = self.recycled_run(len)?;
+        self.reserve(&(start..start + len));
+        Some(self.take_reserved())
     }
 
     /// 回收页面
     ///
     /// 合法的被回收页面
     /// - 之前一定被分配出去过，因此其物理页号小于`current`
-    /// - 它不是回收状态，即`recycled`中不包含此物理页号
+    /// - 它不是回收状态，即`recycled`与`reserved`中不包含此物理页号
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn: usize = ppn.into();
-        if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
+        if ppn >= self.current
+            || self.recycled.iter().any(|&v| v == ppn)
+            || self.reserved.contains(&ppn)
+        {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
         self.recycled.push_back(ppn);
@@ -118,14 +217,111 @@ impl FrameAllocator for StackFrameAllocator {
 impl StackFrameAllocator {
     const fn new() -> Self {
         Self {
+            start: 0,
             current: 0,
             end: 0,
             recycled: VecDeque::new(),
+            reserved: Vec::new(),
         }
     }
 
     fn init(&mut self, left: PhysPageNum, right: PhysPageNum) {
+        self.start = left.into();
         self.current = left.into();
         self.end = right.into();
     }
+
+    /// 升序排列的回收页号
+    fn sorted_recycled(&self) -> Vec<usize> {
+        let mut ppns: Vec<usize> = self.recycled.iter().copied().collect();
+        ppns.sort_unstable();
+        ppns
+    }
+
+    fn report(&self) -> FragmentationReport {
+        let untouched_frames = self.end - self.current;
+        let mut report = FragmentationReport {
+            free_frames: untouched_frames + self.recycled.len(),
+            untouched_frames,
+            recycled_frames: self.recycled.len(),
+            ..Default::default()
+        };
+
+        // 末尾的未分配区间本身就是一个连续段，
+        // 与之相邻的回收页帧会并入其中
+        let mut run_end = self.current;
+        let mut run_len = untouched_frames;
+        for ppn in self.sorted_recycled().into_iter().rev() {
+            if ppn + 1 == run_end {
+                run_len += 1;
+            } else {
+                if run_len > 0 {
+                    report.free_runs += 1;
+                    report.largest_run = report.largest_run.max(run_len);
+                }
+                run_len = 1;
+            }
+            run_end = ppn;
+        }
+        if run_len > 0 {
+            report.free_runs += 1;
+            report.largest_run = report.largest_run.max(run_len);
+        }
+
+        report
+    }
+
+    /// 在回收栈中寻找长度为`len`的连续段，返回其起始页号
+    fn recycled_run(&self, len: usize) -> Option<usize> {
+        if len == 0 {
+            return None;
+        }
+
+        let ppns = self.sorted_recycled();
+        ppns.windows(len)
+            .find(|w| w[len - 1] - w[0] == len - 1)
+            .map(|w| w[0])
+    }
+
+    /// 在已分配过的区间 [start, current) 中，
+    /// 寻找空闲页帧最多的长度为`len`的窗口
+    fn densest_window(&self, len: usize) -> Option<Range<usize>> {
+        if len == 0 || self.current - self.start < len {
+            return None;
+        }
+
+        let ppns = self.sorted_recycled();
+        (0..ppns.len())
+            .map(|i| {
+                let start = ppns[i].min(self.current - len);
+                let free = ppns[i..].iter().take_while(|&&p| p < start + len).count();
+                (start, free)
+            })
+            .max_by_key(|&(_, free)| free)
+            .map(|(start, _)| start..start + len)
+    }
+
+    /// 将窗口内的回收页号移入扣留区，返回扣留区内的页帧总数
+    fn reserve(&mut self, window: &Range<usize>) -> usize {
+        let reserved = &mut self.reserved;
+        self.recycled.retain(|ppn| {
+            let inside = window.contains(ppn);
+            if inside {
+                reserved.push(*ppn);
+            }
+            !inside
+        });
+        self.reserved.len()
+    }
+
+    /// 取走扣留的页帧，页号降序排列
+    fn take_reserved(&mut self) -> Vec<PhysPageNum> {
+        self.reserved.sort_unstable_by(|a, b| b.cmp(a));
+        self.reserved.drain(..).map(PhysPageNum::from_raw).collect()
+    }
+
+    /// 归还扣留的页帧
+    fn release_reserved(&mut self) {
+        self.recycled.extend(self.reserved.drain(..));
+    }
 }
@@ -1,9 +1,19 @@
 use crate::config::KERNEL_HEAP_SIZE;
+#[cfg(not(debug_assertions))]
 use buddy_system_allocator::LockedHeap;
 
+#[cfg(debug_assertions)]
+use super::heap_debug::DebugHeap;
+
+#[cfg(not(debug_assertions))]
 #[global_allocator]
 static HEAP_ALLOCATOR: LockedHeap<32> = LockedHeap::empty();
 
+/// 调试构建时检查重复释放、越界与释放后写入
+#[cfg(debug_assertions)]
+#[global_allocator]
+static HEAP_ALLOCATOR: DebugHeap = DebugHeap::empty();
+
 static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
 
 /// 把data段的一部分空间切给堆分配器
@@ -0,0 +1,218 @@
+//! 调试构建所用的堆分配器，尽早暴露堆上的内存错误
+//!
+//! 每块分配在数据之前放置记录布局与调用栈的头部，之后放置一段红区：
+//!
+//! ```text
+//! ┌─────────┬────────┬──────────┬──────────┐
+//! │ padding │ header │   data   │ red zone │
+//! └─────────┴────────┴──────────┴──────────┘
+//! ```
+//!
+//! 释放时检查头部与红区，以[`POISON`]填充数据后放入隔离区，隔离区满时才真正归还最早的块，
+//! 归还前检查填充的内容是否完好。重复释放与释放后写入因此能在隔离期内被发现。
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem;
+use core::ops::Deref;
+use core::slice;
+
+use buddy_system_allocator::LockedHeap;
+
+use crate::ksyms::Backtrace;
+use crate::stack_trace;
+use crate::sync::UpCell;
+
+/// 已分配的块的头部标记
+const ALLOCATED: usize = 0xa110_ca7e_a110_ca7e;
+/// 已释放的块的头部标记
+const FREED: usize = 0xdead_beef_dead_beef;
+/// 填充已释放的数据
+const POISON: u8 = 0x6b;
+/// 填充数据之后的红区
+const RED_ZONE: u8 = 0xcc;
+const RED_ZONE_SIZE: usize = 16;
+/// 记录的调用栈深度
+const BACKTRACE_DEPTH: usize = 8;
+/// 隔离区最多容纳的块数
+const QUARANTINE_BLOCKS: usize = 64;
+/// 隔离区最多占用的数据字节数
+const QUARANTINE_BYTES: usize = 256 * 1024;
+
+#[repr(C)]
+struct Header {
+    magic: usize,
+    layout: Layout,
+    alloc_trace: [usize; BACKTRACE_DEPTH],
+    free_trace: [usize; BACKTRACE_DEPTH],
+}
+
+/// 已释放但尚未归还的块，按释放的先后排列
+struct Quarantine {
+    blocks: [usize; QUARANTINE_BLOCKS],
+    head: usize,
+    len: usize,
+    bytes: usize,
+}
+
+pub struct DebugHeap {
+    heap: LockedHeap<32>,
+    quarantine: UpCell<Quarantine>,
+}
+
+impl DebugHeap {
+    pub const fn empty() -> Self {
+        Self {
+            heap: LockedHeap::empty(),
+            quarantine: UpCell::new(Quarantine {
+                blocks: [0; QUARANTINE_BLOCKS],
+                head: 0,
+                len: 0,
+                bytes: 0,
+            }),
+        }
+    }
+
+    /// 将块真正归还给伙伴分配器
+    unsafe fn release(&self, data: *mut u8) {
+        let header = &*header_of(data);
+        let layout = header.layout;
+        let content = slice::from_raw_parts(data, layout.size());
+        if let Some(offset) = content.iter().position(|&byte| byte != POISON) {
+            panic!(
+                "heap use after free: {data:p}+{offset:#x} written after being freed ({layout:?})\n\
+                 allocated at:{}\n\
+                 freed at:{}",
+                Backtrace(&header.alloc_trace),
+                Backtrace(&header.free_trace)
+            );
+        }
+
+        self.heap
+            .dealloc(data.sub(data_offset(layout)), outer_layout(layout));
+    }
+}
+
+impl Deref for DebugHeap {
+    type Target = LockedHeap<32>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.heap
+    }
+}
+
+unsafe impl GlobalAlloc for DebugHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut base = self.heap.alloc(outer_layout(layout));
+        if base.is_null() {
+            // 隔离区占着的内存可能恰好够用
+            while let Some(data) = self.quarantine.exclusive_access().pop() {
+                self.release(data);
+            }
+            base = self.heap.alloc(outer_layout(layout));
+            if base.is_null() {
+                return base;
+            }
+        }
+
+        let data = base.add(data_offset(layout));
+        let mut alloc_trace = [0; BACKTRACE_DEPTH];
+        stack_trace::capture(&mut alloc_trace);
+        header_of(data).write(Header {
+            magic: ALLOCATED,
+            layout,
+            alloc_trace,
+            free_trace: [0; BACKTRACE_DEPTH],
+        });
+        data.add(layout.size()).write_bytes(RED_ZONE, RED_ZONE_SIZE);
+
+        data
+    }
+
+    unsafe fn dealloc(&self, data: *mut u8, layout: Layout) {
+        let header = &mut *header_of(data);
+        match header.magic {
+            ALLOCATED => {}
+            FREED => panic!(
+                "heap double free: {data:p} ({layout:?})\n\
+                 allocated at:{}\n\
+                 first freed at:{}",
+                Backtrace(&header.alloc_trace),
+                Backtrace(&header.free_trace)
+            ),
+            magic => {
+                panic!("heap free of an invalid or corrupted block: {data:p}, magic={magic:#x}")
+            }
+        }
+        if header.layout != layout {
+            panic!(
+                "heap free with a mismatched layout: {data:p}, allocated as {:?}, freed as {layout:?}\n\
+                 allocated at:{}",
+                header.layout,
+                Backtrace(&header.alloc_trace)
+            );
+        }
+
+        let red_zone = slice::from_raw_parts(data.add(layout.size()), RED_ZONE_SIZE);
+        if let Some(offset) = red_zone.iter().position(|&byte| byte != RED_ZONE) {
+            panic!(
+                "heap buffer overflow: {data:p}+{:#x} written past the end ({layout:?})\n\
+                 allocated at:{}",
+                layout.size() + offset,
+                Backtrace(&header.alloc_trace)
+            );
+        }
+
+        header.magic = FREED;
+        stack_trace::capture(&mut header.free_trace);
+        data.write_bytes(POISON, layout.size());
+
+        let mut quarantine = self.quarantine.exclusive_access();
+        quarantine.push(data, layout.size());
+        while quarantine.len == QUARANTINE_BLOCKS || quarantine.bytes > QUARANTINE_BYTES {
+            let Some(oldest) = quarantine.pop() else {
+                break;
+            };
+            self.release(oldest);
+        }
+    }
+}
+
+impl Quarantine {
+    fn push(&mut self, data: *mut u8, size: usize) {
+        assert!(self.len < QUARANTINE_BLOCKS);
+        self.blocks[(self.head + self.len) % QUARANTINE_BLOCKS] = data as usize;
+        self.len += 1;
+        self.bytes += size;
+    }
+
+    fn pop(&mut self) -> Option<*mut u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let data = self.blocks[self.head] as *mut u8;
+        self.head = (self.head + 1) % QUARANTINE_BLOCKS;
+        self.len -= 1;
+        self.bytes -= unsafe { (*header_of(data)).layout.size() };
+        Some(data)
+    }
+}
+
+/// 数据相对于整块起始的偏移，头部紧贴数据之前
+fn data_offset(layout: Layout) -> usize {
+    mem::size_of::<Header>().next_multiple_of(outer_align(layout))
+}
+
+fn outer_align(layout: Layout) -> usize {
+    layout.align().max(mem::align_of::<Header>())
+}
+
+/// 连同头部与红区的整块布局
+fn outer_layout(layout: Layout) -> Layout {
+    let size = data_offset(layout) + layout.size() + RED_ZONE_SIZE;
+    Layout::from_size_align(size, outer_align(layout)).unwrap()
+}
+
+fn header_of(data: *mut u8) -> *mut Header {
+    data.wrapping_sub(mem::size_of::<Header>()).cast()
+}
@@ -0,0 +1,88 @@
+//! # 程序映像缓存
+//!
+//! 按文件缓存可执行文件的内容，以及已载入的只读段的页帧，同一文件的各个硬链接共用映像。
+//! 再次exec或spawn同一程序（如shell反复执行同一命令）时不必从磁盘重读，
+//! 只读段直接映射缓存的页帧，只有可写的数据段与用户栈等才分配新页帧。
+//!
+//! - 写入或截断文件时失效其映像；
+//! - 删除与改名回收的索引节点号可能分给新文件，卸载的文件系统的地址可能被复用，失效全部映像。
+//!
+//! 缓存持有的页帧引用计数大于1，不会被压缩迁移，故只缓存最近用过的[`CAPACITY`]个映像。
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::address::VirtPageNum;
+use super::frame_allocator::Frame;
+use crate::fs::{File, FileId};
+use crate::sync::UpCell;
+
+/// 至多缓存的映像数
+const CAPACITY: usize = 8;
+
+/// 最近用过的映像排在末尾
+static IMAGES: UpCell<Vec<(FileId, Arc<Image>)>> = UpCell::new(Vec::new());
+
+/// 可执行文件的内容，及其只读段载入后的页帧
+#[derive(Debug)]
+pub struct Image {
+    data: Vec<u8>,
+    frames: UpCell<BTreeMap<VirtPageNum, Arc<Frame>>>,
+}
+
+impl Image {
+    /// 不进入缓存的映像
+    pub fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            frames: UpCell::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// 只读段各页的页帧，首次载入时填入
+    pub(super) fn frames(&self) -> &UpCell<BTreeMap<VirtPageNum, Arc<Frame>>> {
+        &self.frames
+    }
+}
+
+/// 程序`file`的映像，缓存中没有时从当前偏移量读至末尾。
+/// 不在文件系统中的文件不缓存
+pub fn load(file: &(dyn File + Send + Sync)) -> Arc<Image> {
+    let Some(id) = file.file_id() else {
+        return Arc::new(Image::new(file.read_all()));
+    };
+
+    {
+        let mut images = IMAGES.exclusive_access();
+        if let Some(i) = images.iter().position(|(k, _)| *k == id) {
+            let entry = images.remove(i);
+            let image = entry.1.clone();
+            images.push(entry);
+            return image;
+        }
+    }
+
+    // 读盘时可能切换任务，不能持有借用
+    let image = Arc::new(Image::new(file.read_all()));
+    let mut images = IMAGES.exclusive_access();
+    if images.len() >= CAPACITY {
+        images.remove(0);
+    }
+    images.push((id, image.clone()));
+    image
+}
+
+/// 文件`id`的内容已改变
+pub fn invalidate(id: FileId) {
+    IMAGES.exclusive_access().retain(|(k, _)| *k != id);
+}
+
+/// 索引节点号与文件的对应已改变
+pub fn clear() {
+    IMAGES.exclusive_access().clear();
+}
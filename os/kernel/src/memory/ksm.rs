@@ -0,0 +1,148 @@
+//! 相同页合并（Kernel Samepage Merging）
+//!
+//! 启用后，每隔[`SCAN_INTERVAL`]个时钟中断扫描一遍所有用户地址空间，
+//! 按内容散列用户页，让内容相同的页共用同一个物理页帧，多余的页帧随之释放。
+//!
+//! 可写的页合并后收回写权限，用户写入时缺页，内核代用户写入前亦先检查，
+//! 二者都让该页重新独占一个页帧，即写时复制。
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use super::frame_allocator::Frame;
+use super::rmap;
+use super::AddressSpace;
+use crate::sync::UpCell;
+
+/// 扫描间隔，以时钟中断计
+pub const SCAN_INTERVAL: usize = 500;
+
+static KSM: UpCell<Ksm> = UpCell::new(Ksm::new());
+
+struct Ksm {
+    enabled: bool,
+    /// 距上次扫描经过的时钟中断数
+    elapsed: usize,
+    stats: KsmStats,
+}
+
+/// 合并统计，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KsmStats {
+    /// 完成的扫描次数
+    pub full_scans: usize,
+    /// 被多个页共享的页帧数
+    pub pages_shared: usize,
+    /// 改用共享页帧的页数，即省下的页帧数
+    pub pages_sharing: usize,
+}
+
+impl Ksm {
+    const fn new() -> Self {
+        Self {
+            enabled: false,
+            elapsed: 0,
+            stats: KsmStats {
+                full_scans: 0,
+                pages_shared: 0,
+                pages_sharing: 0,
+            },
+        }
+    }
+}
+
+pub fn set_enabled(enabled: bool) {
+    KSM.exclusive_session(|ksm| {
+        ksm.enabled = enabled;
+        ksm.elapsed = 0;
+    });
+}
+
+pub fn stats() -> KsmStats {
+    KSM.exclusive_access().stats
+}
+
+/// 记一个时钟中断，返回是否该扫描了
+pub fn due() -> bool {
+    KSM.exclusive_session(|ksm| {
+        if !ksm.enabled {
+            return false;
+        }
+        ksm.elapsed += 1;
+        if ksm.elapsed < SCAN_INTERVAL {
+            return false;
+        }
+        ksm.elapsed = 0;
+        true
+    })
+}
+
+/// 扫描一遍地址空间，合并内容相同的页，返回本次合并的页数
+pub fn merge(spaces: &mut [&mut AddressSpace]) -> usize {
+    // 散列值 => 内容各不相同的页帧
+    let mut stable: BTreeMap<u64, Vec<Arc<Frame>>> = BTreeMap::new();
+
+    let merged: usize = spaces
+        .iter_mut()
+        .map(|space| {
+            space.share_identical(|frame| {
+                let bytes = frame.ppn.page_bytes();
+                let frames = stable.entry(hash(bytes)).or_default();
+                match frames
+                    .iter()
+                    .find(|f| f.ppn == frame.ppn || f.ppn.page_bytes() == bytes)
+                {
+                    Some(f) if f.ppn != frame.ppn => Some(f.clone()),
+                    Some(_) => None,
+                    None => {
+                        frames.push(frame.clone());
+                        None
+                    }
+                }
+            })
+        })
+        .sum();
+
+    // 共享的页帧不论原先是否可写，此后都不能再直接写入
+    for mapping in stable
+        .values()
+        .flatten()
+        .map(|frame| rmap::mappers(frame.ppn))
+        .filter(|mappings| mappings.len() > 1)
+        .flatten()
+    {
+        if let Some(space) = spaces
+            .iter_mut()
+            .find(|space| space.token() == mapping.token)
+        {
+            space.write_protect(mapping.vpn);
+        }
+    }
+
+    let mut stats = KsmStats {
+        full_scans: KSM.exclusive_access().stats.full_scans + 1,
+        ..Default::default()
+    };
+    // 扣除`stable`自身持有的引用
+    for users in stable
+        .values()
+        .flatten()
+        .map(|frame| Arc::strong_count(frame) - 1)
+        .filter(|&users| users > 1)
+    {
+        stats.pages_shared += 1;
+        stats.pages_sharing += users - 1;
+    }
+    KSM.exclusive_access().stats = stats;
+
+    merged
+}
+
+/// FNV-1a
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
@@ -1,20 +1,72 @@
 pub mod address;
 mod address_space;
+mod asid;
 mod buffer;
 pub mod frame_allocator;
 mod heap_allocator;
+#[cfg(debug_assertions)]
+mod heap_debug;
+pub mod image;
 mod kernel_stack;
+pub mod ksm;
 mod page_table;
+pub mod rmap;
+pub mod vdso;
 
 pub use self::{
-    address_space::{AddressSpace, MapPermission, KERNEL_SPACE},
-    buffer::{write_any, UserBuffer},
+    address_space::{AddressSpace, MapError, MapErrorKind, MapPermission, KERNEL_SPACE},
+    buffer::{read_any, read_argv, read_cstr, read_path, write_any, StrError, UserBuffer},
     kernel_stack::{alloc_kernel_stack, kernel_token, KernelStack},
-    page_table::{read_mut, read_ref, read_str, write_str, PageTable},
+    page_table::{read_mut, read_ref, write_str, PageTable},
 };
 
+use riscv::register::scause::{Exception, Trap};
+
+use self::address::VirtAddr;
+use crate::task::signal::SignalFlag;
+use crate::task::{self, processor};
+use crate::trap;
+
 pub fn init() {
     heap_allocator::init();
     frame_allocator::init();
     KERNEL_SPACE.exclusive_access().activate();
+    asid::init();
+    register_fault_handlers();
+}
+
+fn register_fault_handlers() {
+    trap::register_user(Trap::Exception(Exception::StorePageFault), |stval| {
+        page_fault(stval, MapPermission::W)
+    });
+    trap::register_user(Trap::Exception(Exception::LoadPageFault), |stval| {
+        page_fault(stval, MapPermission::R)
+    });
+    trap::register_user(Trap::Exception(Exception::InstructionPageFault), |stval| {
+        page_fault(stval, MapPermission::X)
+    });
+
+    // 某些异常会令内核给进程发送信号，
+    // 这就是异步信号的由来，即异步异常的传染
+    for fault in [
+        Exception::StoreFault,
+        Exception::LoadFault,
+        Exception::InstructionFault,
+    ] {
+        trap::register_user(Trap::Exception(fault), |_| {
+            task::send_signal_to_current(SignalFlag::SIGSEGV)
+        });
+    }
+}
+
+/// 被释放的页在首次访问时按需映射页帧，访问位与脏位可能需由软件维护，其余缺页同访存异常
+fn page_fault(stval: usize, access: MapPermission) {
+    let handled = processor::current_process()
+        .inner()
+        .exclusive_access()
+        .address_space
+        .handle_page_fault(VirtAddr::from(stval).page_number(), access);
+    if !handled {
+        task::send_signal_to_current(SignalFlag::SIGSEGV);
+    }
 }
@@ -1,4 +1,3 @@
-use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 
@@ -6,6 +5,7 @@ use super::address::PhysAddr;
 use super::address::PhysPageNum;
 use super::address::VirtAddr;
 use super::address::VirtPageNum;
+use super::asid;
 use super::frame_allocator;
 use super::frame_allocator::Frame;
 
@@ -16,6 +16,8 @@ use enumflags2::BitFlags;
 pub struct PageTable {
     /// 一级页表的物理地址，要交给satp
     root: PhysPageNum,
+    /// 地址空间标识符，与根页表一同写入satp
+    asid: usize,
     frames: Vec<Frame>,
 }
 
@@ -58,20 +60,15 @@ pub struct MappedVpn(pub VirtPageNum);
 #[derive(Debug)]
 pub struct UnmappedVpn(pub VirtPageNum);
 
-impl Default for PageTable {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl PageTable {
     /// 可容纳的页表项数量
     pub const CAPACITY: usize = 512;
 
-    pub fn new() -> Self {
+    pub fn new(asid: usize) -> Self {
         let frame = frame_allocator::alloc().unwrap();
         Self {
             root: frame.ppn,
+            asid,
             frames: vec![frame],
         }
     }
@@ -105,6 +102,78 @@ impl PageTable {
         Ok(())
     }
 
+    /// 将`vpn`改映射到`ppn`，保留原有的标志位
+    pub fn remap(&mut self, vpn: VirtPageNum, ppn: PhysPageNum) -> Result<(), UnmappedVpn> {
+        let Some(pte) = self.get_mut(vpn).filter(|pte| pte.is_valid()) else {
+            return Err(UnmappedVpn(vpn));
+        };
+        *pte = Entry::new(ppn, pte.flags());
+
+        Ok(())
+    }
+
+    /// `vpn`的页自上次清除访问位以来是否被访问过，同时清除访问位，此后须刷新快表
+    #[inline]
+    pub fn take_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        self.take_flag(vpn, PTEFlag::A)
+    }
+
+    /// `vpn`的页自上次清除脏位以来是否被写过，同时清除脏位，此后须刷新快表
+    #[inline]
+    pub fn take_dirty(&mut self, vpn: VirtPageNum) -> bool {
+        self.take_flag(vpn, PTEFlag::D)
+    }
+
+    /// 收回`vpn`的写权限，此后须刷新快表
+    #[inline]
+    pub fn write_protect(&mut self, vpn: VirtPageNum) {
+        self.take_flag(vpn, PTEFlag::W);
+    }
+
+    /// 恢复`vpn`的写权限，此后须刷新快表
+    pub fn allow_write(&mut self, vpn: VirtPageNum) {
+        if let Some(pte) = self.get_mut(vpn).filter(|pte| pte.is_valid()) {
+            *pte = Entry::new(pte.ppn(), pte.flags() | PTEFlag::W);
+        }
+    }
+
+    fn take_flag(&mut self, vpn: VirtPageNum, flag: PTEFlag) -> bool {
+        let Some(pte) = self.get_mut(vpn).filter(|pte| pte.is_valid()) else {
+            return false;
+        };
+        let mut flags = pte.flags();
+        let taken = flags.contains(flag);
+        flags.remove(flag);
+        *pte = Entry::new(pte.ppn(), flags);
+
+        taken
+    }
+
+    /// 硬件不维护访问位与脏位的平台上，访问位为0的页被访问、脏位为0的页被写入时会缺页，
+    /// 由软件代为置位。
+    ///
+    /// `access`为访问所需的权限，仅当页表项允许用户如此访问且确因缺少这两位而缺页时置位并返回`true`，
+    /// 此后须刷新快表。
+    pub fn emulate_access(&mut self, vpn: VirtPageNum, access: BitFlags<PTEFlag>) -> bool {
+        let Some(pte) = self.get_mut(vpn).filter(|pte| pte.is_valid()) else {
+            return false;
+        };
+        let flags = pte.flags();
+        if !flags.contains(access | PTEFlag::U) {
+            return false;
+        }
+        let mut missing = BitFlags::from(PTEFlag::A);
+        if access.contains(PTEFlag::W) {
+            missing |= PTEFlag::D;
+        }
+        if flags.contains(missing) {
+            return false;
+        }
+        *pte = Entry::new(pte.ppn(), flags | missing);
+
+        true
+    }
+
     /// 凭借虚拟页号访问页表项
     #[inline]
     pub fn translate(&self, vpn: VirtPageNum) -> Option<&Entry> {
@@ -116,14 +185,15 @@ impl PageTable {
             .map(|pte| PhysAddr::from(pte.ppn()) + va.page_offset())
     }
 
-    /// 将一级页表地址转化成 satp 使用的数据
+    /// 将一级页表地址与ASID转化成 satp 使用的数据
     pub fn token(&self) -> usize {
-        self.root.into_satp()
+        self.root.into_satp() | (self.asid << asid::SATP_SHIFT)
     }
 
     pub fn from_token(satp: usize) -> Self {
         Self {
             root: PhysPageNum::from(satp),
+            asid: asid::of(satp),
             frames: vec![],
         }
     }
@@ -213,25 +283,8 @@ impl Entry {
     }
 }
 
-pub fn read_str(token: usize, src: *const u8) -> String {
-    let page_table = PageTable::from_token(token);
-    let mut string = String::new();
-    let mut src = src as usize;
-
-    loop {
-        let ch: u8 = *(page_table.read_ref(src.into()));
-
-        if ch != b'\0' {
-            string.push(ch as char);
-            src += 1;
-        } else {
-            break;
-        }
-    }
-
-    string
-}
-
+/// 不检查页的权限，只用于内核自己建立的用户内存，如`exec`时压入用户栈的参数。
+/// 写入用户给出的地址须经[`write_any`](super::write_any)或[`UserBuffer::new_mut`](super::UserBuffer::new_mut)
 pub fn write_str(token: usize, src: &str, dest: *mut u8) {
     let mut page_table = PageTable::from_token(token);
     let mut dest = dest as usize;
@@ -247,6 +300,7 @@ pub fn read_ref<T>(token: usize, ptr: *const T) -> &'static T {
     PageTable::from_token(token).read_ref((ptr as usize).into())
 }
 
+/// 同[`write_str`]，不检查页的权限
 pub fn read_mut<T>(token: usize, ptr: *mut T) -> &'static mut T {
     PageTable::from_token(token).read_mut((ptr as usize).into())
 }
@@ -0,0 +1,50 @@
+//! 反向映射（rmap）
+//!
+//! 记录每个由分配器分配的物理页帧被哪些地址空间的哪些虚拟页映射，
+//! 供页面迁移、相同页合并等需要从页帧找回映射的功能使用。
+//! 表项由[`LogicSegment`]在建立与撤销映射时维护。
+//!
+//! [`LogicSegment`]: super::address_space
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::address::{PhysPageNum, VirtPageNum};
+use crate::sync::UpCell;
+
+static RMAP: UpCell<BTreeMap<PhysPageNum, Vec<Mapping>>> = UpCell::new(BTreeMap::new());
+
+/// 一处映射
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mapping {
+    /// 地址空间的`satp`
+    pub token: usize,
+    pub vpn: VirtPageNum,
+}
+
+pub(super) fn add(ppn: PhysPageNum, mapping: Mapping) {
+    RMAP.exclusive_access()
+        .entry(ppn)
+        .or_default()
+        .push(mapping);
+}
+
+pub(super) fn remove(ppn: PhysPageNum, mapping: Mapping) {
+    let mut rmap = RMAP.exclusive_access();
+    let Some(mappings) = rmap.get_mut(&ppn) else {
+        return;
+    };
+
+    mappings.retain(|m| *m != mapping);
+    if mappings.is_empty() {
+        rmap.remove(&ppn);
+    }
+}
+
+/// 映射了`ppn`的所有位置
+pub fn mappers(ppn: PhysPageNum) -> Vec<Mapping> {
+    RMAP.exclusive_access()
+        .get(&ppn)
+        .cloned()
+        .unwrap_or_default()
+}
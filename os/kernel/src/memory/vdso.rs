@@ -0,0 +1,69 @@
+//! 时钟共享页
+//!
+//! 内核在每个用户地址空间的[`VDSO_VA`]处只读地映射同一个物理页帧，
+//! 时钟中断时更新其中的时间，用户读取时钟无需陷入内核。
+//! 该页帧为所有进程共享，内核只在此更新，绝不代用户写入。
+
+use core::sync::atomic::{self, AtomicUsize, Ordering};
+
+use spin::Lazy;
+
+use super::address::{PhysAddr, PhysPageNum};
+use super::frame_allocator::{self, Frame};
+use crate::config::{CLOCK_FREQ, PAGE_SIZE, USER_SPACE_END};
+use crate::timer::{self, TICKS_PRE_SEC};
+
+/// 共享页在用户地址空间中的虚地址，位于用户地址空间的最后一页
+pub const VDSO_VA: usize = USER_SPACE_END - PAGE_SIZE;
+
+static PAGE: Lazy<Frame> = Lazy::new(|| {
+    let frame = frame_allocator::alloc().expect("no frame for the vDSO page");
+    let data = frame.ppn.as_mut::<VdsoData>();
+    data.clock_freq = CLOCK_FREQ;
+    data.ticks_per_sec = TICKS_PRE_SEC;
+    frame
+});
+
+/// 共享页的内容，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug)]
+pub struct VdsoData {
+    /// 顺序锁，更新前后各加一，为奇数时正在更新，读者须重读
+    seq: AtomicUsize,
+    /// 最近一次时钟中断时`mtime`的值
+    mtime: AtomicUsize,
+    /// 自启动以来经过的时钟中断间隔数
+    ticks: AtomicUsize,
+    /// `mtime`每秒的计数
+    clock_freq: usize,
+    /// 每秒的时钟中断次数
+    ticks_per_sec: usize,
+}
+
+/// 共享页的物理页号
+pub fn ppn() -> PhysPageNum {
+    PAGE.ppn
+}
+
+/// 由时钟中断调用，记下当前的`mtime`
+pub fn update() {
+    let data: &VdsoData = PhysAddr::from(PAGE.ppn).as_ref();
+    // 各处理器都有时钟中断，已有处理器在更新时放弃本次更新
+    let seq = data.seq.load(Ordering::Relaxed);
+    if seq % 2 != 0
+        || data
+            .seq
+            .compare_exchange(seq, seq + 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+    {
+        return;
+    }
+    // 读者看到新数据时必能看到奇数的序号
+    atomic::fence(Ordering::Release);
+
+    let mtime = timer::get_time();
+    data.mtime.store(mtime, Ordering::Relaxed);
+    data.ticks
+        .store(mtime / (CLOCK_FREQ / TICKS_PRE_SEC), Ordering::Relaxed);
+    data.seq.store(seq + 2, Ordering::Release);
+}
@@ -2,6 +2,19 @@ use alloc::borrow::ToOwned;
 use alloc::string::String;
 use alloc::vec::Vec;
 
+use vfs::{NAME_MAX, PATH_MAX};
+
+/// 路径无法读出或化为标准路径的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathError {
+    /// 路径所在的页未映射或用户不可读
+    Fault,
+    /// 含有空项，或`..`越过了根目录
+    Invalid,
+    /// 标准路径加上结尾的`\0`超过[`PATH_MAX`]，或某一项超过[`NAME_MAX`]
+    NameTooLong,
+}
+
 pub trait Path: ToOwned {
     /// Returns the Path without its final component, if there is one.
     ///
@@ -18,7 +31,7 @@ pub trait Path: ToOwned {
     ///
     /// `cwd`: 来自于[`ProcessControlBlockInner`]，为绝对路径，
     ///        且非根时不以`/`结束。
-    fn canonicalize(&self, cwd: &Self) -> Option<Self::Owned>;
+    fn canonicalize(&self, cwd: &Self) -> Result<Self::Owned, PathError>;
 
     /// 返回根目录下的路径，若为根目录则返回`None`。
     fn root_relative(&self) -> Option<&Self>;
@@ -51,9 +64,9 @@ impl Path for str {
         Some(parent)
     }
 
-    fn canonicalize(&self, cwd: &Self) -> Option<Self::Owned> {
+    fn canonicalize(&self, cwd: &Self) -> Result<Self::Owned, PathError> {
         if self == "/" {
-            return Some(String::from("/"));
+            return Ok(String::from("/"));
         }
 
         let mut cmps = Vec::new();
@@ -66,20 +79,24 @@ impl Path for str {
         for cmp in self.trim_matches('/').split('/') {
             match cmp {
                 ".." => {
-                    cmps.pop()?;
+                    cmps.pop().ok_or(PathError::Invalid)?;
                 }
                 "." => (),
-                "" => return None,
+                "" => return Err(PathError::Invalid),
+                s if s.len() > NAME_MAX => return Err(PathError::NameTooLong),
                 s => cmps.push(s),
             }
         }
 
         if cmps.is_empty() {
-            Some("/".into())
-        } else {
-            cmps.insert(0, ""); // 在接下来的拼接中代表根目录
-            Some(cmps.join("/"))
+            return Ok("/".into());
+        }
+        cmps.insert(0, ""); // 在接下来的拼接中代表根目录
+        let path = cmps.join("/");
+        if path.len() >= PATH_MAX {
+            return Err(PathError::NameTooLong);
         }
+        Ok(path)
     }
 
     fn root_relative(&self) -> Option<&Self> {
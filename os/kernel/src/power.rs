@@ -0,0 +1,54 @@
+//! # 有序关机
+//!
+//! 先令其余进程限时退出，再卸载文件系统并写回缓存，移除设备，最后经SBI关机或重启。
+//! 空闲进程与发起关机的进程不会被结束。
+
+use crate::task::signal::SignalFlag;
+use crate::task;
+use crate::{drivers, fs, sbi, timer};
+
+/// 发送SIGTERM后等待进程自行退出的时限
+const TERM_TIMEOUT_MS: usize = 3000;
+/// 发送SIGKILL后等待进程退出的时限
+const KILL_TIMEOUT_MS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    PowerOff,
+    Reboot,
+}
+
+/// 只能由进程在系统调用中发起，等待期间会让出处理器
+pub fn shutdown(action: Action) -> ! {
+    log::info!("[kernel] Shutting down for {action:?}");
+    if !terminate_others(SignalFlag::SIGTERM, TERM_TIMEOUT_MS) {
+        terminate_others(SignalFlag::SIGKILL, KILL_TIMEOUT_MS);
+    }
+
+    fs::unmount_all();
+    drivers::remove_all();
+    match action {
+        Action::PowerOff => sbi::shutdown(false),
+        Action::Reboot => sbi::reboot(),
+    }
+}
+
+/// 向其余进程发送`signal`，至多等待`timeout_ms`毫秒，全部退出时返回真
+fn terminate_others(signal: SignalFlag, timeout_ms: usize) -> bool {
+    let deadline = timer::get_time_ms() + timeout_ms;
+    for process in task::other_processes() {
+        task::send_signal(&process, signal.into());
+    }
+
+    loop {
+        let left = task::other_processes().len();
+        if left == 0 {
+            return true;
+        }
+        if timer::get_time_ms() >= deadline {
+            log::warn!("[kernel] {left} processes did not exit on {signal:?}");
+            return false;
+        }
+        task::suspend_current_and_run_next();
+    }
+}
@@ -1,4 +1,4 @@
-use sbi_rt::{NoReason, Shutdown, SystemFailure};
+use sbi_rt::{ColdReboot, NoReason, Shutdown, SystemFailure};
 
 pub fn console_getchar() -> usize {
     #[allow(deprecated)]
@@ -23,3 +23,9 @@ pub fn shutdown(failure: bool) -> ! {
 
     unreachable!()
 }
+
+pub fn reboot() -> ! {
+    sbi_rt::system_reset(ColdReboot, NoReason);
+
+    unreachable!()
+}
@@ -1,4 +1,11 @@
 use core::arch::asm;
+use core::mem;
+
+use crate::config::KERNEL_STACK_SIZE;
+use crate::ksyms::Backtrace;
+
+/// panic时输出的调用栈深度
+const PRINT_DEPTH: usize = 16;
 
 // Stack
 //                    .
@@ -34,21 +41,35 @@ use core::arch::asm;
 //           │ saved registers │
 //   $sp --> │ local variables │
 //           └─────────────────┘
-#[allow(dead_code)]
-pub unsafe fn print_stack_trace() {
-    let mut fp: *const usize;
-    asm!("mv {}, fp", out(reg) fp);
+/// 输出当前的调用栈，返回地址换成所在的函数。panic时使用，不借用任何状态
+pub fn print_stack_trace() {
+    let mut frames = [0; PRINT_DEPTH];
+    let depth = capture(&mut frames);
+    emergency_println!("Backtrace:{}", Backtrace(&frames[..depth]));
+}
 
-    println!("== Begin stack trace ==");
-    while !fp.is_null() {
-        // RISC-V 调用函数是通过 jalr 指令，
-        // ra 即 jalr 的下一条指令之地址
-        let saved_ra = *fp.sub(1); // 往下获取保存的 ra
-        let pre_fp = *fp.sub(2); // 往下获取上上次调用前最后一帧之地址
+/// 沿帧指针回溯，将各层的返回地址依次写入`frames`，返回写入的个数。
+///
+/// 帧指针为空、未对齐，或上一帧不在本帧之上的一个栈的范围内时停止，
+/// 以免将陷入前的用户态`fp`当作内核的帧指针。
+pub fn capture(frames: &mut [usize]) -> usize {
+    let mut fp: usize;
+    unsafe { asm!("mv {}, fp", out(reg) fp) };
 
-        println!("0x{:016x}, fp = 0x{:016x}", saved_ra, pre_fp);
+    let mut depth = 0;
+    while depth < frames.len() && fp != 0 && fp % mem::size_of::<usize>() == 0 {
+        let (ra, pre_fp) = unsafe {
+            let fp = fp as *const usize;
+            (*fp.sub(1), *fp.sub(2))
+        };
+        frames[depth] = ra;
+        depth += 1;
 
-        fp = pre_fp as *const usize;
+        if pre_fp <= fp || pre_fp - fp > KERNEL_STACK_SIZE {
+            break;
+        }
+        fp = pre_fp;
     }
-    println!("== End stack trace ==");
+
+    depth
 }
@@ -7,8 +7,8 @@ use crate::task::processor;
 use crate::task::TaskContext;
 use crate::task::TaskControlBlock;
 
-use super::Mutex;
 use super::UpCell;
+use super::{Mutex, Poisoned};
 
 #[derive(Debug)]
 pub struct Condvar {
@@ -22,19 +22,28 @@ impl Condvar {
         }
     }
 
+    /// 唤醒最早睡眠的一个任务
     pub fn signal(&self) {
+        if let Some(task) = self.wait_queue.exclusive_access().pop_front() {
+            manager::wakeup_task(task);
+        }
+    }
+
+    /// 唤醒所有睡眠的任务，用于等待者各自等待不同条件的场合
+    pub fn notify_all(&self) {
         while let Some(task) = self.wait_queue.exclusive_access().pop_front() {
             manager::wakeup_task(task);
         }
     }
 
-    pub fn wait_with_mutex(&self, mutex: Arc<dyn Mutex>) {
+    /// 醒来后重新上锁，锁在睡眠期间被毒化时返回`Err(Poisoned)`
+    pub fn wait_with_mutex(&self, mutex: Arc<dyn Mutex>) -> Result<(), Poisoned> {
         mutex.unlock();
         self.wait_queue
             .exclusive_access()
             .push_back(processor::current_task().unwrap());
         task::block_current_and_run_next();
-        mutex.lock();
+        mutex.lock()
     }
 
     /// 唤醒一个睡眠的任务，仅给内核使用
@@ -0,0 +1,77 @@
+//! 快速用户态互斥（futex）
+//!
+//! 用户态的同步原语在无竞争时只用原子操作，
+//! 发生竞争时才借此进入内核排队或唤醒等待者。
+//! 等待队列以进程号与用户字的虚拟地址为键，故同一进程的各线程共用一个队列。
+//! 物理地址不宜作键：零页、写时复制、内存规整与相同页合并都会在进程不知情时
+//! 改变虚拟地址背后的页帧，等待者与唤醒者便会落入不同的队列。
+
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+
+use super::UpCell;
+use crate::memory;
+use crate::task;
+use crate::task::manager;
+use crate::task::processor;
+use crate::task::TaskControlBlock;
+
+/// (进程号, 用户字的虚拟地址)
+type Key = (usize, usize);
+
+static QUEUES: UpCell<BTreeMap<Key, VecDeque<Arc<TaskControlBlock>>>> =
+    UpCell::new(BTreeMap::new());
+
+fn key(uaddr: *const u32) -> Key {
+    (processor::current_process().pid(), uaddr as usize)
+}
+
+/// 若当前进程`uaddr`处的字仍为`expected`，则令当前任务睡眠直到被唤醒，返回是否睡眠过。
+///
+/// 检查与入队之间屏蔽了中断，唤醒者不会插入其中
+pub fn wait(uaddr: *const u32, expected: u32) -> bool {
+    let mut queues = QUEUES.exclusive_access();
+    if memory::read_any(uaddr) != Some(expected) {
+        return false;
+    }
+
+    queues
+        .entry(key(uaddr))
+        .or_default()
+        .push_back(processor::current_task().unwrap());
+    drop(queues);
+    task::block_current_and_run_next();
+
+    true
+}
+
+/// 唤醒至多`count`个在当前进程`uaddr`处等待的任务，返回唤醒的数目
+pub fn wake(uaddr: *const u32, count: usize) -> usize {
+    let key = key(uaddr);
+    let mut queues = QUEUES.exclusive_access();
+    let Some(queue) = queues.get_mut(&key) else {
+        return 0;
+    };
+
+    let woken = count.min(queue.len());
+    let tasks: VecDeque<_> = queue.drain(..woken).collect();
+    if queue.is_empty() {
+        queues.remove(&key);
+    }
+    drop(queues);
+
+    for task in tasks {
+        manager::wakeup_task(task);
+    }
+
+    woken
+}
+
+/// 将任务移出所有等待队列
+pub fn remove(task: &Arc<TaskControlBlock>) {
+    let task = Arc::as_ptr(task);
+    QUEUES.exclusive_access().retain(|_, queue| {
+        queue.retain(|t| Arc::as_ptr(t) != task);
+        !queue.is_empty()
+    });
+}
@@ -1,11 +1,12 @@
 mod condvar;
+pub mod futex;
 mod mutex;
 mod semaphore;
 mod up;
 
 pub use self::{
     condvar::Condvar,
-    mutex::{BlockMutex, Mutex, SpinMutex},
+    mutex::{BlockMutex, Mutex, Poisoned, SpinMutex},
     semaphore::Semaphore,
-    up::UpCell,
+    up::{UpCell, UpRef},
 };
@@ -2,7 +2,7 @@ use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use core::fmt::Debug;
 use core::sync::atomic;
-use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicBool, AtomicUsize};
 
 use super::UpCell;
 use crate::task;
@@ -10,36 +10,66 @@ use crate::task::manager;
 use crate::task::processor;
 use crate::task::TaskControlBlock;
 
+/// 没有持有者
+const NO_OWNER: usize = usize::MAX;
+
+/// 前一个持有者未释放便退出了，受保护的数据可能处于不一致的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Poisoned;
+
 pub trait Mutex: Debug + Send + Sync {
-    fn lock(&self);
+    /// 上锁。锁被毒化时仍会上锁，但返回`Err(Poisoned)`
+    fn lock(&self) -> Result<(), Poisoned>;
     fn unlock(&self);
+
+    /// 线程`tid`退出时调用，它若持有锁，则毒化并解锁，免得等待者永远等下去
+    fn release_dead(&self, tid: usize);
 }
 
 #[derive(Debug)]
 pub struct SpinMutex {
     locked: AtomicBool,
+    /// 持有者的线程ID
+    owner: AtomicUsize,
+    poisoned: AtomicBool,
 }
 
 #[derive(Debug)]
 pub struct BlockMutex {
     locked: AtomicBool,
+    /// 持有者的线程ID，解锁时直接改为被唤醒的等待者
+    owner: AtomicUsize,
+    poisoned: AtomicBool,
     wait_queue: UpCell<VecDeque<Arc<TaskControlBlock>>>,
 }
 
 impl Mutex for SpinMutex {
-    fn lock(&self) {
+    fn lock(&self) -> Result<(), Poisoned> {
         while self.locked.swap(true, atomic::Ordering::Acquire) {
             task::suspend_current_and_run_next();
         }
+        self.owner
+            .store(processor::current_tid(), atomic::Ordering::Relaxed);
+        check(&self.poisoned)
     }
 
     fn unlock(&self) {
+        self.owner.store(NO_OWNER, atomic::Ordering::Relaxed);
         self.locked.store(false, atomic::Ordering::Release);
     }
+
+    fn release_dead(&self, tid: usize) {
+        if self.locked.load(atomic::Ordering::Acquire)
+            && self.owner.load(atomic::Ordering::Relaxed) == tid
+        {
+            self.poisoned.store(true, atomic::Ordering::Relaxed);
+            self.unlock();
+        }
+    }
 }
 
 impl Mutex for BlockMutex {
-    fn lock(&self) {
+    fn lock(&self) -> Result<(), Poisoned> {
         if self.locked.swap(true, atomic::Ordering::Acquire) {
             // 也许你觉得lock里可以随意访问独占引用很迷惑，但是目前
             //
@@ -53,7 +83,11 @@ impl Mutex for BlockMutex {
                 .exclusive_access()
                 .push_back(processor::current_task().unwrap());
             task::block_current_and_run_next();
+        } else {
+            self.owner
+                .store(processor::current_tid(), atomic::Ordering::Relaxed);
         }
+        check(&self.poisoned)
     }
 
     fn unlock(&self) {
@@ -61,18 +95,41 @@ impl Mutex for BlockMutex {
         assert!(self.locked.load(atomic::Ordering::Acquire));
         if let Some(waiting_task) = self.wait_queue.exclusive_access().pop_front() {
             // 存在等候者，唤醒之，锁转移到其手上
+            let tid = waiting_task.inner().exclusive_access().resource.tid;
+            self.owner.store(tid, atomic::Ordering::Relaxed);
             manager::wakeup_task(waiting_task);
         } else {
             // 没有等候者，直接解锁
+            self.owner.store(NO_OWNER, atomic::Ordering::Relaxed);
             self.locked.store(false, atomic::Ordering::Release);
         }
     }
+
+    fn release_dead(&self, tid: usize) {
+        if self.locked.load(atomic::Ordering::Acquire)
+            && self.owner.load(atomic::Ordering::Relaxed) == tid
+        {
+            self.poisoned.store(true, atomic::Ordering::Relaxed);
+            self.unlock();
+        }
+    }
+}
+
+/// 毒化是永久的，此后每次上锁都会报告
+fn check(poisoned: &AtomicBool) -> Result<(), Poisoned> {
+    if poisoned.load(atomic::Ordering::Relaxed) {
+        Err(Poisoned)
+    } else {
+        Ok(())
+    }
 }
 
 impl SpinMutex {
     pub fn new() -> Self {
         Self {
             locked: AtomicBool::new(false),
+            owner: AtomicUsize::new(NO_OWNER),
+            poisoned: AtomicBool::new(false),
         }
     }
 }
@@ -81,6 +138,8 @@ impl BlockMutex {
     pub fn new() -> Self {
         Self {
             locked: AtomicBool::new(false),
+            owner: AtomicUsize::new(NO_OWNER),
+            poisoned: AtomicBool::new(false),
             wait_queue: UpCell::new(VecDeque::new()),
         }
     }
@@ -1,9 +1,10 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::sync::atomic;
-use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicBool, AtomicUsize};
 
-use super::UpCell;
+use super::{Poisoned, UpCell};
 use crate::task;
 use crate::task::manager;
 use crate::task::processor;
@@ -13,6 +14,11 @@ use crate::task::TaskControlBlock;
 pub struct Semaphore {
     permits: AtomicUsize,
     wait_queue: UpCell<VecDeque<Arc<TaskControlBlock>>>,
+    /// 健壮的信号量记录各线程取走而未归还的资源数，`None`表示不记录。
+    ///
+    /// 信号量常用于线程间通知，取走资源的线程未必归还，故须在创建时选用
+    holders: Option<UpCell<Vec<(usize, usize)>>>,
+    poisoned: AtomicBool,
 }
 
 impl Semaphore {
@@ -20,28 +26,42 @@ impl Semaphore {
         Self {
             permits: AtomicUsize::new(permits),
             wait_queue: UpCell::new(VecDeque::new()),
+            holders: None,
+            poisoned: AtomicBool::new(false),
+        }
+    }
+
+    /// 健壮的信号量：线程退出时归还其未归还的资源，并毒化信号量
+    pub fn robust(permits: usize) -> Self {
+        Self {
+            holders: Some(UpCell::new(Vec::new())),
+            ..Self::new(permits)
         }
     }
 
     /// Verhogen 增加
     pub fn up(&self) {
-        if let Some(task) = self.wait_queue.exclusive_access().pop_front() {
-            // 转让当前任务的资源
-            manager::wakeup_task(task);
-        } else {
-            // 释放当前任务的资源
-            self.permits.fetch_add(1, atomic::Ordering::Release);
+        if let Some(holders) = &self.holders {
+            let tid = processor::current_tid();
+            let mut holders = holders.exclusive_access();
+            if let Some(index) = holders.iter().position(|&(holder, _)| holder == tid) {
+                holders[index].1 -= 1;
+                if holders[index].1 == 0 {
+                    holders.swap_remove(index);
+                }
+            }
         }
+        self.release();
     }
 
-    /// Proberen 尝试
-    pub fn down(&self) {
+    /// Proberen 尝试。信号量被毒化时仍会取得资源，但返回`Err(Poisoned)`
+    pub fn down(&self) -> Result<(), Poisoned> {
         let mut permits = self.permits.load(atomic::Ordering::Acquire);
 
         // 若资源派发完，则去排队
         if permits == 0 {
             self.wait_current();
-            return;
+            return self.acquired();
         }
 
         // 尝试获取到一个资源，直到成功为止。
@@ -58,6 +78,25 @@ impl Semaphore {
             }
             permits = current;
         }
+        self.acquired()
+    }
+
+    /// 线程`tid`退出时调用，归还它未归还的资源。仅对健壮的信号量有效
+    pub fn release_dead(&self, tid: usize) {
+        let Some(holders) = &self.holders else {
+            return;
+        };
+        let mut holders = holders.exclusive_access();
+        let Some(index) = holders.iter().position(|&(holder, _)| holder == tid) else {
+            return;
+        };
+        let (_, count) = holders.swap_remove(index);
+        drop(holders);
+
+        self.poisoned.store(true, atomic::Ordering::Relaxed);
+        for _ in 0..count {
+            self.release();
+        }
     }
 }
 
@@ -68,4 +107,33 @@ impl Semaphore {
             .push_back(processor::current_task().unwrap());
         task::block_current_and_run_next();
     }
+
+    /// 归还一份资源，有等待者时直接转让
+    fn release(&self) {
+        if let Some(task) = self.wait_queue.exclusive_access().pop_front() {
+            // 转让当前任务的资源
+            manager::wakeup_task(task);
+        } else {
+            // 释放当前任务的资源
+            self.permits.fetch_add(1, atomic::Ordering::Release);
+        }
+    }
+
+    /// 当前线程已取得一份资源
+    fn acquired(&self) -> Result<(), Poisoned> {
+        if let Some(holders) = &self.holders {
+            let tid = processor::current_tid();
+            let mut holders = holders.exclusive_access();
+            match holders.iter_mut().find(|(holder, _)| *holder == tid) {
+                Some((_, count)) => *count += 1,
+                None => holders.push((tid, 1)),
+            }
+        }
+
+        if self.poisoned.load(atomic::Ordering::Relaxed) {
+            Err(Poisoned)
+        } else {
+            Ok(())
+        }
+    }
 }
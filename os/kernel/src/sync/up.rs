@@ -1,5 +1,5 @@
 use core::cell::UnsafeCell;
-use core::cell::{RefCell, RefMut};
+use core::cell::{Ref, RefCell, RefMut};
 use core::ops::{Deref, DerefMut};
 
 use riscv::register::sstatus;
@@ -15,6 +15,9 @@ unsafe impl<T> Sync for UpCell<T> {}
 // `Option`是为了在释放时可以提前销毁`RefMut`，不受启用中断的影响
 pub struct UpRefMut<'a, T>(Option<RefMut<'a, T>>);
 
+/// 共享借用，同理[`UpRefMut`]
+pub struct UpRef<'a, T>(Option<Ref<'a, T>>);
+
 impl<T> UpCell<T> {
     pub const fn new(value: T) -> Self {
         Self {
@@ -28,6 +31,25 @@ impl<T> UpCell<T> {
         UpRefMut(Some(self.inner.borrow_mut()))
     }
 
+    /// 共享借用，可与其它共享借用并存，但与[`UpCell::exclusive_access`]互斥。
+    /// 借用期间同样屏蔽中断，只是允许嵌套的只读借用，持有者并不能因此并行。
+    pub fn shared_access(&self) -> UpRef<'_, T> {
+        INTERRUPT_GUARD.get_mut().enter();
+        UpRef(Some(self.inner.borrow()))
+    }
+
+    /// 不会panic的[`UpCell::shared_access`]，已被独占借用时返回`None`，供panic等诊断路径使用
+    pub fn try_shared_access(&self) -> Option<UpRef<'_, T>> {
+        INTERRUPT_GUARD.get_mut().enter();
+        match self.inner.try_borrow() {
+            Ok(inner) => Some(UpRef(Some(inner))),
+            Err(_) => {
+                INTERRUPT_GUARD.get_mut().exit();
+                None
+            }
+        }
+    }
+
     pub fn exclusive_session<F, V>(&self, f: F) -> V
     where
         F: FnOnce(&mut T) -> V,
@@ -44,6 +66,21 @@ impl<'a, T> Drop for UpRefMut<'a, T> {
     }
 }
 
+impl<'a, T> Drop for UpRef<'a, T> {
+    fn drop(&mut self) {
+        self.0 = None;
+        INTERRUPT_GUARD.get_mut().exit();
+    }
+}
+
+impl<'a, T> Deref for UpRef<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_deref().unwrap()
+    }
+}
+
 impl<'a, T> Deref for UpRefMut<'a, T> {
     type Target = T;
 
@@ -0,0 +1,205 @@
+//! 系统调用参数的提取与校验
+//!
+//! 分发时先将原始的`usize`参数转换为此处的类型，转换失败即以相应错误码返回，
+//! 系统调用本身无需再检查指针是否为空、是否对齐、是否落在用户地址空间内。
+
+use core::marker::PhantomData;
+use core::mem;
+
+use vfs::PATH_MAX;
+
+use super::errno::{EBADF, EFAULT};
+use crate::config::USER_SPACE_END;
+use crate::memory::address::VirtAddr;
+use crate::memory::{self, StrError};
+use crate::task::processor;
+
+/// 参数校验失败的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgError {
+    /// 非法的文件描述符
+    BadFd,
+    /// 非法的用户指针
+    Fault,
+}
+
+impl ArgError {
+    pub fn errno(self) -> isize {
+        match self {
+            Self::BadFd => EBADF,
+            Self::Fault => EFAULT,
+        }
+    }
+}
+
+/// 检查`[addr, addr + len)`是否为非空、按`align`对齐、位于用户地址空间内的区间，
+/// 其中每一页都须用户可访问，`write`时还须用户可写，见[`AddressSpace::user_page`](crate::memory::AddressSpace::user_page)
+fn check_user_range(addr: usize, len: usize, align: usize, write: bool) -> Result<(), ArgError> {
+    let end = addr.checked_add(len).ok_or(ArgError::Fault)?;
+    if addr == 0 || addr % align != 0 || end > USER_SPACE_END {
+        return Err(ArgError::Fault);
+    }
+
+    let process = processor::current_process();
+    let mut inner = process.inner().exclusive_access();
+    for vpn in VirtAddr::from(addr).floor()..VirtAddr::from(end).ceil() {
+        inner
+            .address_space
+            .user_page(vpn, write)
+            .ok_or(ArgError::Fault)?;
+    }
+    Ok(())
+}
+
+/// 指向用户空间内单个`T`的指针，内核只从中读取
+#[derive(Debug)]
+pub struct UserPtr<T> {
+    addr: usize,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> TryFrom<usize> for UserPtr<T> {
+    type Error = ArgError;
+
+    fn try_from(addr: usize) -> Result<Self, Self::Error> {
+        check_user_range(addr, mem::size_of::<T>(), mem::align_of::<T>(), false)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T> UserPtr<T> {
+    pub fn get(self) -> *const T {
+        self.addr as *const T
+    }
+}
+
+/// 指向用户空间内单个`T`的指针，内核要写入其中
+#[derive(Debug)]
+pub struct UserPtrMut<T> {
+    addr: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> TryFrom<usize> for UserPtrMut<T> {
+    type Error = ArgError;
+
+    fn try_from(addr: usize) -> Result<Self, Self::Error> {
+        check_user_range(addr, mem::size_of::<T>(), mem::align_of::<T>(), true)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T> UserPtrMut<T> {
+    pub fn get(self) -> *mut T {
+        self.addr as *mut T
+    }
+}
+
+/// 指向用户空间内连续`len`个`T`的指针，内核只从中读取
+#[derive(Debug)]
+pub struct UserSlice<T> {
+    addr: usize,
+    _marker: PhantomData<*const T>,
+}
+
+impl<T> TryFrom<(usize, usize)> for UserSlice<T> {
+    type Error = ArgError;
+
+    fn try_from((addr, len): (usize, usize)) -> Result<Self, Self::Error> {
+        check_slice::<T>(addr, len, false)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T> UserSlice<T> {
+    pub fn get(self) -> *const T {
+        self.addr as *const T
+    }
+}
+
+/// 指向用户空间内连续`len`个`T`的指针，内核要写入其中
+#[derive(Debug)]
+pub struct UserSliceMut<T> {
+    addr: usize,
+    _marker: PhantomData<*mut T>,
+}
+
+impl<T> TryFrom<(usize, usize)> for UserSliceMut<T> {
+    type Error = ArgError;
+
+    fn try_from((addr, len): (usize, usize)) -> Result<Self, Self::Error> {
+        check_slice::<T>(addr, len, true)?;
+        Ok(Self {
+            addr,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T> UserSliceMut<T> {
+    pub fn get(self) -> *mut T {
+        self.addr as *mut T
+    }
+}
+
+fn check_slice<T>(addr: usize, len: usize, write: bool) -> Result<(), ArgError> {
+    let size = len
+        .checked_mul(mem::size_of::<T>())
+        .ok_or(ArgError::Fault)?;
+    check_user_range(addr, size, mem::align_of::<T>(), write)
+}
+
+/// 指向用户空间内以`\0`结尾的字符串
+#[derive(Debug)]
+pub struct UserCStr(usize);
+
+impl TryFrom<usize> for UserCStr {
+    type Error = ArgError;
+
+    /// 逐页检查直到终止符所在的页，至多检查[`PATH_MAX`]字节，更长的字符串由读取者按各自的上限拒绝
+    fn try_from(addr: usize) -> Result<Self, Self::Error> {
+        if addr == 0 {
+            return Err(ArgError::Fault);
+        }
+        match memory::read_cstr(addr as *const u8, PATH_MAX) {
+            Ok(_) | Err(StrError::TooLong) => Ok(Self(addr)),
+            Err(StrError::Fault) => Err(ArgError::Fault),
+        }
+    }
+}
+
+impl UserCStr {
+    pub fn get(self) -> *const u8 {
+        self.0 as *const u8
+    }
+}
+
+/// 文件描述符，用户态的负数描述符会被拒绝
+#[derive(Debug, Clone, Copy)]
+pub struct Fd(usize);
+
+impl TryFrom<usize> for Fd {
+    type Error = ArgError;
+
+    fn try_from(fd: usize) -> Result<Self, Self::Error> {
+        if fd > i32::MAX as usize {
+            return Err(ArgError::BadFd);
+        }
+        Ok(Self(fd))
+    }
+}
+
+impl Fd {
+    pub fn get(self) -> usize {
+        self.0
+    }
+}
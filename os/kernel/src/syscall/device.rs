@@ -0,0 +1,21 @@
+use vfs::PATH_MAX;
+
+use super::errno::EFAULT;
+use crate::drivers;
+use crate::memory::{self, StrError};
+
+/// 查询名为`name`的设备是否已登记
+///
+/// 结果
+/// * 1 => 设备存在
+/// * 0 => 设备不存在
+/// * -EFAULT => `name`所在的页未映射或用户不可读
+pub fn sys_device_present(name: *const u8) -> isize {
+    let name = match memory::read_cstr(name, PATH_MAX) {
+        Ok(name) => name,
+        Err(StrError::Fault) => return -EFAULT,
+        Err(StrError::TooLong) => return 0,
+    };
+
+    drivers::find_by_name(&name).is_some() as isize
+}
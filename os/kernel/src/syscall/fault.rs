@@ -0,0 +1,110 @@
+//! 系统调用的故障注入，仅在启用`fault-inject`特性时编译
+//!
+//! 进程经[`sys_fault_inject`]为自己安排故障，此后它的系统调用在进入之前就可能失败，
+//! 返回的错误码与真实的失败相同，借此确定地检验用户程序的错误处理路径。
+//! 故障只作用于安排它的进程，不被子进程继承，进程退出后随之失效。
+
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+use super::errno::{EINVAL, EIO, ENOMEM};
+use super::{MMAP, MMAP_FILE, MREMAP, READ, WRITE};
+use crate::sync::UpCell;
+use crate::task::{processor, ProcessControlBlock};
+
+/// 撤销当前进程的所有故障
+const FAULT_OFF: usize = 0;
+/// 此后第`n`次分配内存的系统调用失败，`n`从1起
+const FAULT_ALLOC: usize = 1;
+/// 此后读写的系统调用各以`n`%的概率失败，`seed`决定伪随机序列
+const FAULT_IO: usize = 2;
+
+/// 各进程安排的故障，以弱引用标识进程，进程号可能被复用而它不会
+static PLANS: UpCell<Vec<(Weak<ProcessControlBlock>, Plan)>> = UpCell::new(Vec::new());
+
+#[derive(Debug, Default)]
+struct Plan {
+    /// 距下一次失败的分配还有几次，0表示不注入
+    alloc_countdown: usize,
+    /// 读写失败的百分比
+    io_percent: usize,
+    /// xorshift64的状态
+    rng: u64,
+}
+
+impl Plan {
+    fn roll(&mut self) -> usize {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng % 100) as usize
+    }
+}
+
+/// 为当前进程安排故障，同类的故障取代先前安排的
+///
+/// # 结果
+///
+/// - `-EINVAL`：未知的故障，`FAULT_ALLOC`的`n`为0，或`FAULT_IO`的`n`大于100
+pub fn sys_fault_inject(kind: usize, n: usize, seed: usize) -> isize {
+    let process = processor::current_process();
+    let mut plans = PLANS.exclusive_access();
+    plans.retain(|(owner, _)| owner.strong_count() > 0);
+    let current = plans
+        .iter()
+        .position(|(owner, _)| is_current(owner, &process));
+
+    match kind {
+        FAULT_OFF => {
+            if let Some(index) = current {
+                plans.swap_remove(index);
+            }
+            return 0;
+        }
+        FAULT_ALLOC if n != 0 => {}
+        FAULT_IO if n <= 100 => {}
+        _ => return -EINVAL,
+    }
+
+    let index = current.unwrap_or_else(|| {
+        plans.push((Arc::downgrade(&process), Plan::default()));
+        plans.len() - 1
+    });
+    let plan = &mut plans[index].1;
+    if kind == FAULT_ALLOC {
+        plan.alloc_countdown = n;
+    } else {
+        plan.io_percent = n;
+        // xorshift的状态不能为0
+        plan.rng = (seed as u64).max(1);
+    }
+    0
+}
+
+/// 按当前进程安排的故障，`id`号系统调用应失败时返回错误码
+pub fn inject(id: usize, args: [usize; 3]) -> Option<isize> {
+    let alloc = matches!(id, MMAP | MMAP_FILE) || id == MREMAP && args[2] > args[1];
+    let io = matches!(id, READ | WRITE);
+    if !alloc && !io {
+        return None;
+    }
+
+    let process = processor::current_process();
+    let mut plans = PLANS.exclusive_access();
+    let (_, plan) = plans
+        .iter_mut()
+        .find(|(owner, _)| is_current(owner, &process))?;
+
+    if alloc && plan.alloc_countdown != 0 {
+        plan.alloc_countdown -= 1;
+        return (plan.alloc_countdown == 0).then_some(ENOMEM);
+    }
+    if io && plan.io_percent != 0 && plan.roll() < plan.io_percent {
+        return Some(EIO);
+    }
+    None
+}
+
+fn is_current(owner: &Weak<ProcessControlBlock>, process: &Arc<ProcessControlBlock>) -> bool {
+    Weak::as_ptr(owner) == Arc::as_ptr(process)
+}
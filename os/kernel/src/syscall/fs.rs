@@ -1,23 +1,39 @@
 //! File and filesystem-related syscalls
 
-use core::mem;
+use alloc::string::String;
 
 use enumflags2::BitFlags;
-use vfs::{CDirEntry, Stat};
-
+use vfs::inotify::{IN_ALL_EVENTS, IN_CREATE, IN_DELETE, IN_ISDIR, IN_MODIFY, IN_NONBLOCK};
+use vfs::quota::{Q_GETQUOTA, Q_SETQUOTA, Q_SYNC};
+use vfs::{DirEntryType, DiskQuota, Stat, PATH_MAX};
+
+use super::errno::{
+    EBADF, EBUSY, EDQUOT, EEXIST, EFAULT, EFBIG, EINVAL, EIO, EISDIR, EMFILE, ENAMETOOLONG, ENFILE,
+    ENODEV, ENOENT, ENOMEM, ENOSPC, ENOTDIR, ENOTEMPTY, ENOTTY, EOPNOTSUPP, EPERM, EROFS, ESPIPE,
+    ESRCH, EXDEV,
+};
+use super::time::TimeSpec;
+use crate::drivers;
 use crate::fs;
-use crate::fs::File;
-use crate::fs::PipeRingBuffer;
+use crate::fs::{inotify, mount};
+use crate::fs::{Advice, File, OpenFlag, SeekError, Whence};
+use crate::fs::{PipeRingBuffer, ResizeError};
 use crate::memory;
-use crate::memory::UserBuffer;
-use crate::path::Path;
-use crate::task::processor;
+use crate::memory::{StrError, UserBuffer};
+use crate::path::{Path, PathError};
+use crate::task::{processor, FdError, ROOT_UID};
+use crate::timer;
 
 /// try to write `buf` with length `len` to the file with `fd`
+///
+/// 结果
+/// * 写入的字节数
+/// * -EFBIG => 偏移量已在文件的最大字节数处
+/// * -EDQUOT => 写入将超出当前用户的磁盘配额，此时什么也不写
+/// * -EFAULT => `buf`中有未映射的页，此时什么也不写
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let process = processor::current_process();
     let process = process.inner().exclusive_access();
-    let token = process.user_token();
 
     if fd >= process.fd_table.len() {
         return -1;
@@ -34,14 +50,45 @@ pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     let file = file.clone();
     drop(process);
 
-    file.write(UserBuffer::new(token, buf as *mut u8, len)) as isize
+    // 同Linux，只写到文件的最大字节数为止，已在上限处时报错
+    let len = match file.offset() {
+        Some(offset) if len > 0 && offset >= file.max_size() => return -EFBIG,
+        Some(offset) => len.min(file.max_size().saturating_sub(offset)),
+        None => len,
+    };
+    if len > 0
+        && file
+            .offset()
+            .is_some_and(|offset| file.quota_exceeded(offset + len))
+    {
+        return -EDQUOT;
+    }
+
+    let Some(buf) = UserBuffer::new(buf, len) else {
+        return -EFAULT;
+    };
+    let written = file.write(buf);
+    if written > 0 {
+        notify_modified(&*file);
+    }
+    written as isize
+}
+
+/// 投递经路径打开的文件被修改的事件
+fn notify_modified(file: &dyn File) {
+    if let Some(path) = file.path() {
+        inotify::notify(path, IN_MODIFY);
+    }
 }
 
 /// try to read bytes with length `len` from the file with `fd` to `buf`
+///
+/// 结果
+/// * 读到的字节数
+/// * -EFAULT => `buf`中有未映射或用户不可写的页，此时什么也不读
 pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
     let process = processor::current_process();
     let process = process.inner().exclusive_access();
-    let token = process.user_token();
 
     if fd >= process.fd_table.len() {
         return -1;
@@ -58,24 +105,90 @@ pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
     let file = file.clone();
     drop(process);
 
-    file.read(UserBuffer::new(token, buf, len)) as isize
+    match UserBuffer::new_mut(buf, len) {
+        Some(buf) => file.read(buf) as isize,
+        None => -EFAULT,
+    }
+}
+
+/// 描述符用尽时的错误码
+fn fd_errno(e: FdError) -> isize {
+    match e {
+        FdError::ProcessLimit => -EMFILE,
+        FdError::SystemLimit => -ENFILE,
+    }
+}
+
+/// 读出用户给出的路径，按`cwd`化为标准路径。
+///
+/// 读取时要借用当前进程，调用者不可持有之
+fn read_path(path: *const u8, cwd: &str) -> Result<String, PathError> {
+    match memory::read_path(path) {
+        Ok(path) => path.canonicalize(cwd),
+        Err(StrError::Fault) => Err(PathError::Fault),
+        Err(StrError::TooLong) => Err(PathError::NameTooLong),
+    }
+}
+
+/// 路径无效时的错误码
+fn path_errno(e: PathError) -> isize {
+    match e {
+        PathError::Invalid => -ENOENT,
+        PathError::NameTooLong => -ENAMETOOLONG,
+        PathError::Fault => -EFAULT,
+    }
+}
+
+/// 文件系统操作失败时的错误码
+fn vfs_errno(e: vfs::Error) -> isize {
+    match e {
+        vfs::Error::AlreadyExists => -EEXIST,
+        vfs::Error::NotFound => -ENOENT,
+        vfs::Error::IsADirectory => -EISDIR,
+        vfs::Error::NotADirectory => -ENOTDIR,
+        vfs::Error::DirectoryNotEmpty => -ENOTEMPTY,
+        vfs::Error::Unsupported => -EOPNOTSUPP,
+        vfs::Error::InvalidInput => -EINVAL,
+        vfs::Error::NoSpace => -ENOSPC,
+        vfs::Error::NameTooLong => -ENAMETOOLONG,
+        vfs::Error::CrossesDevices => -EXDEV,
+        vfs::Error::Io => -EIO,
+        vfs::Error::FileTooLarge => -EFBIG,
+    }
 }
 
+/// 结果
+/// * -ENAMETOOLONG => 路径或其中某一项过长，见[`PathError::NameTooLong`]
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
 pub fn sys_open(path: *const u8, flags: u32) -> isize {
     let process = processor::current_process();
-    let (cwd, token) = process
+    let cwd = process
         .inner()
-        .exclusive_session(|process| (process.cwd.clone(), process.user_token()));
+        .exclusive_session(|process| process.cwd.clone());
 
-    let Some(path) = memory::read_str(token, path).canonicalize(&cwd) else {
-        return -1;
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
-    let Some(inode) = fs::open(&path, BitFlags::from_bits(flags).unwrap()) else {
+    let flags: BitFlags<OpenFlag> = BitFlags::from_bits(flags).unwrap();
+    // 有监视实例时才查询文件原先是否存在
+    let existed = (inotify::active() && flags.intersects(OpenFlag::CREATE | OpenFlag::TRUNC))
+        .then(|| fs::open(&path, OpenFlag::read_only()).is_some());
+    let Some(inode) = fs::open(&path, flags) else {
         return -1;
     };
+    match existed {
+        Some(false) => inotify::notify(&path, IN_CREATE),
+        Some(true) => inotify::notify(&path, IN_MODIFY),
+        None => {}
+    }
 
     let mut process = process.inner().exclusive_access();
-    process.fd_table.insert(inode) as isize
+    match process.fd_table.insert(inode) {
+        Ok(fd) => fd as isize,
+        Err(e) => fd_errno(e),
+    }
 }
 
 pub fn sys_close(fd: usize) -> isize {
@@ -92,50 +205,285 @@ pub fn sys_close(fd: usize) -> isize {
     }
 }
 
+/// 对打开的文件发出设备相关的控制请求
+///
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -ENOTTY => 文件不支持`request`
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    let process = processor::current_process();
+    let inner = process.inner().exclusive_access();
+
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else {
+        return -EBADF;
+    };
+    drop(inner);
+
+    match file.ioctl(request, arg) {
+        Some(ret) => ret as isize,
+        None => -ENOTTY,
+    }
+}
+
+/// 就此后如何访问打开的文件`fd`向内核提出建议，作用于整个文件。
+/// `advice`取值同Linux的`POSIX_FADV_*`，支持NORMAL、RANDOM、SEQUENTIAL与DONTNEED。
+///
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => 不支持`advice`
+/// * -ESPIPE => 文件是管道等不经块缓存的对象
+pub fn sys_fadvise(fd: usize, advice: usize) -> isize {
+    let Some(advice) = Advice::from_raw(advice) else {
+        return -EINVAL;
+    };
+    let process = processor::current_process();
+    let inner = process.inner().exclusive_access();
+
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else {
+        return -EBADF;
+    };
+    drop(inner);
+
+    if file.fadvise(advice) {
+        0
+    } else {
+        -ESPIPE
+    }
+}
+
+/// 将打开的文件`fd`截断或扩展至`length`字节，扩展出的部分读出为0，文件偏移量不变
+///
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => `length`为负，`fd`不可写，或文件不支持截断
+/// * -EFBIG => `length`超过文件的最大字节数
+/// * -EDQUOT => 扩展将超出当前用户的磁盘配额
+pub fn sys_ftruncate(fd: usize, length: isize) -> isize {
+    let process = processor::current_process();
+    let inner = process.inner().exclusive_access();
+
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else {
+        return -EBADF;
+    };
+    drop(inner);
+
+    let Ok(length) = usize::try_from(length) else {
+        return -EINVAL;
+    };
+    if length > file.max_size() {
+        return -EFBIG;
+    }
+    if !file.writable() {
+        return -EINVAL;
+    }
+    if file.quota_exceeded(length) {
+        return -EDQUOT;
+    }
+    if !file.truncate(length) {
+        return -EINVAL;
+    }
+    notify_modified(&*file);
+    0
+}
+
+/// [`TimeSpec::nsec`]取此值时，设为当前时间
+const UTIME_NOW: usize = (1 << 30) - 1;
+/// [`TimeSpec::nsec`]取此值时，保持不变
+const UTIME_OMIT: usize = (1 << 30) - 2;
+
+/// 设置`path`的最后访问与最后修改时间，`times`依次为两者，为空时都设为当前时间。
+/// 文件系统只记录到秒，纳秒部分被舍去
+///
+/// 结果
+/// * -ENOENT => `path`不存在
+/// * -ENAMETOOLONG => 路径或其中某一项过长
+/// * -EINVAL => 纳秒部分不在`[0, 1e9)`内，且不是[`UTIME_NOW`]或[`UTIME_OMIT`]
+/// * -EROFS => 文件所在的文件系统不记录时间
+pub fn sys_utimensat(path: *const u8, times: Option<*const [TimeSpec; 2]>) -> isize {
+    let now = (timer::realtime_us() / 1_000_000) as u64;
+    let [atime, mtime] = match times {
+        None => [Some(now); 2],
+        Some(times) => {
+            let Some(times) = memory::read_any(times) else {
+                return -EFAULT;
+            };
+            let mut resolved = [None; 2];
+            for (time, ts) in resolved.iter_mut().zip(times) {
+                *time = match ts.nsec {
+                    UTIME_NOW => Some(now),
+                    UTIME_OMIT => None,
+                    0..1_000_000_000 => Some(ts.sec as u64),
+                    _ => return -EINVAL,
+                };
+            }
+            resolved
+        }
+    };
+
+    let process = processor::current_process();
+    let cwd = process
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
+    let Some(file) = fs::open(&path, OpenFlag::read_only()) else {
+        return -ENOENT;
+    };
+
+    if !file.set_times(atime, mtime) {
+        return -EROFS;
+    }
+    0
+}
+
+/// 按`whence`移动打开的文件`fd`的偏移量，可越过文件末尾，此后写入时中间的空洞读出为0
+///
+/// 结果
+/// * 新的偏移量
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => `whence`无效，或新的偏移量为负
+/// * -ESPIPE => `fd`是管道等不可移动偏移量的文件
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    let process = processor::current_process();
+    let inner = process.inner().exclusive_access();
+
+    if fd >= inner.fd_table.len() {
+        return -EBADF;
+    }
+    let Some(file) = inner.fd_table[fd].clone() else {
+        return -EBADF;
+    };
+    drop(inner);
+
+    let Some(whence) = Whence::from_raw(whence) else {
+        return -EINVAL;
+    };
+    match file.lseek(offset, whence) {
+        Ok(pos) => pos as isize,
+        Err(SeekError::Invalid) => -EINVAL,
+        Err(SeekError::Unseekable) => -ESPIPE,
+    }
+}
+
+/// `fcntl`命令：设置管道的容量
+pub const F_SETPIPE_SZ: usize = 1031;
+/// `fcntl`命令：取得管道的容量
+pub const F_GETPIPE_SZ: usize = 1032;
+
+/// 将管道`fd`的容量调整为至少`size`字节，向上取整到页
+///
+/// 结果
+/// * 调整后的容量
+/// * -EBADF => `fd`未打开或不是管道
+/// * -EPERM => `size`超过了管道容量的上限
+/// * -EBUSY => 管道中已缓冲的数据多于新的容量
+/// * -ENOMEM => 没有足够的连续物理页帧
+pub fn sys_fcntl_setpipe_sz(fd: usize, size: usize) -> isize {
+    let process = processor::current_process();
+    let file = process.inner().exclusive_access().fd_table.try_get(fd);
+    let Some(pipe) = file.as_deref().and_then(|file| file.as_pipe()) else {
+        return -EBADF;
+    };
+
+    match pipe.resize(size) {
+        Ok(cap) => cap as isize,
+        Err(ResizeError::TooLarge) => -EPERM,
+        Err(ResizeError::Busy) => -EBUSY,
+        Err(ResizeError::NoMemory) => -ENOMEM,
+    }
+}
+
+/// 结果
+/// * 管道`fd`的容量
+/// * -EBADF => `fd`未打开或不是管道
+pub fn sys_fcntl_getpipe_sz(fd: usize) -> isize {
+    let process = processor::current_process();
+    let file = process.inner().exclusive_access().fd_table.try_get(fd);
+    match file.as_deref().and_then(|file| file.as_pipe()) {
+        Some(pipe) => pipe.capacity() as isize,
+        None => -EBADF,
+    }
+}
+
+/// 结果
+/// * -EOPNOTSUPP => 文件系统不支持硬链接，如FAT
+/// * -EXDEV => 两路径不在同一文件系统
+/// * -EEXIST => `newpath`已存在
+/// * -ENOENT => `oldpath`不存在
 pub fn sys_link(oldpath: *const u8, newpath: *const u8) -> isize {
-    let token = processor::current_user_token();
-    let oldpath = memory::read_str(token, oldpath);
-    let newpath = memory::read_str(token, newpath);
+    let cwd = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
+
+    let oldpath = match read_path(oldpath, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
+    let newpath = match read_path(newpath, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
 
     match fs::link(&oldpath, &newpath) {
-        Some(_) => 0,
-        None => -1,
+        Ok(_) => {
+            inotify::notify(&newpath, IN_CREATE);
+            0
+        }
+        Err(e) => vfs_errno(e),
     }
 }
 
+/// 结果
+/// * -ENOENT => 文件不存在
+/// * -EISDIR => 路径是目录，应改用`rmdir`
+/// * -EOPNOTSUPP => 文件系统不支持删除
 pub fn sys_unlink(path: *const u8) -> isize {
-    let process = processor::current_process();
-    let process = process.inner().exclusive_access();
+    let cwd = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
 
-    let path = memory::read_str(process.user_token(), path);
-    let Some(path) = path.canonicalize(&process.cwd) else {
-        return -1;
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
-    drop(process);
 
     let Some((parent, name)) = path.parent_file() else {
-        return -1;
+        return -EISDIR;
     };
-    let Ok(dir) = fs::open_dir(parent) else {
-        return -1;
+    let dir = match fs::open_dir(parent) {
+        Ok(dir) => dir,
+        Err(e) => return vfs_errno(e),
     };
 
     match dir.unlink(name) {
-        Ok(_) => 0,
-        Err(_) => -1,
+        Ok(_) => {
+            inotify::notify(&path, IN_DELETE);
+            0
+        }
+        Err(e) => vfs_errno(e),
     }
 }
 
 pub fn sys_mkdir(path: *const u8) -> isize {
-    let process = processor::current_process();
-    let process = process.inner().exclusive_access();
+    let cwd = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
 
-    let token = process.user_token();
-    let path = memory::read_str(token, path);
-    let Some(path) = path.canonicalize(&process.cwd) else {
-        return -1;
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
-    drop(process);
 
     let Some((parent, name)) = path.parent_file() else {
         return -1;
@@ -146,19 +494,20 @@ pub fn sys_mkdir(path: *const u8) -> isize {
     if dir.mkdir(name).is_err() {
         return -1;
     }
+    inotify::notify(&path, IN_CREATE | IN_ISDIR);
 
     0
 }
 
 pub fn sys_rmdir(path: *const u8) -> isize {
-    let process = processor::current_process();
-    let process = process.inner().exclusive_access();
+    let cwd = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
 
-    let path = memory::read_str(process.user_token(), path);
-    let Some(path) = path.canonicalize(&process.cwd) else {
-        return -1;
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
-    drop(process);
 
     let Some((parent, name)) = path.parent_file() else {
         return -1;
@@ -168,21 +517,22 @@ pub fn sys_rmdir(path: *const u8) -> isize {
     };
 
     match dir.rmdir(name) {
-        Ok(_) => 0,
+        Ok(_) => {
+            inotify::notify(&path, IN_DELETE | IN_ISDIR);
+            0
+        }
         Err(_) => -1,
     }
 }
 
 pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
-    let (file, token) = processor::current_process()
+    let file = processor::current_process()
         .inner()
-        .exclusive_session(|inner| (inner.fd_table.try_get(fd), inner.user_token()));
+        .exclusive_session(|inner| inner.fd_table.try_get(fd));
 
     match file.map(|file| file.stat()) {
-        Some(stat) => {
-            memory::write_any(token, st, stat);
-            0
-        }
+        Some(stat) if memory::write_any(st, stat) => 0,
+        Some(_) => -EFAULT,
         None => {
             log::error!("invalid fd={fd}");
             -1
@@ -191,53 +541,94 @@ pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
 }
 
 pub fn sys_rename(oldpath: *const u8, newpath: *const u8) -> isize {
-    let process = processor::current_process();
-    let process = process.inner().exclusive_access();
-    let token = process.user_token();
+    let cwd = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
 
-    let Some(oldpath) = memory::read_str(token, oldpath).canonicalize(&process.cwd) else {
-        return -1;
+    let oldpath = match read_path(oldpath, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
-    let Some(newpath) = memory::read_str(token, newpath).canonicalize(&process.cwd) else {
-        return -1;
+    let newpath = match read_path(newpath, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
     log::debug!("{oldpath} -> {newpath}");
-    drop(process);
     if newpath.starts_with(&oldpath) {
         // 不可以将父目录移到下属的子目录；或两路径不能相同
         return -1;
     }
-    let Some((old_parent, old_name)) = oldpath.parent_file() else {
-        return -1;
-    };
-    let Ok(dir) = fs::open_dir(old_parent) else {
-        return -1;
-    };
-    match dir.rename(old_name, &newpath) {
-        Ok(_) => 0,
+    match fs::rename(&oldpath, &newpath) {
+        Ok(_) => {
+            inotify::notify_rename(&oldpath, &newpath);
+            0
+        }
         Err(_) => -1,
     }
 }
 
+/// 结果
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
+/// * -EFAULT => `pipe`不可写，此时不打开任何描述符
 pub fn sys_pipe(pipe: *mut usize) -> isize {
     let process = processor::current_process();
-    let mut process = process.inner().exclusive_access();
-    let token = process.user_token();
+    let mut inner = process.inner().exclusive_access();
 
     let (pipe_read, pipe_write) = PipeRingBuffer::make_pipe();
-    let read_fd = process.fd_table.insert(pipe_read);
-    let write_fd = process.fd_table.insert(pipe_write);
-    *memory::read_mut(token, pipe) = read_fd;
-    *memory::read_mut(token, unsafe { pipe.add(1) }) = write_fd;
+    let read_fd = match inner.fd_table.insert(pipe_read) {
+        Ok(fd) => fd,
+        Err(e) => return fd_errno(e),
+    };
+    let write_fd = match inner.fd_table.insert(pipe_write) {
+        Ok(fd) => fd,
+        Err(e) => {
+            // 两端要么都打开，要么都不打开
+            inner.fd_table.remove(read_fd);
+            return fd_errno(e);
+        }
+    };
+    drop(inner);
+
+    if !memory::write_any(pipe.cast::<[usize; 2]>(), [read_fd, write_fd]) {
+        let mut inner = process.inner().exclusive_access();
+        inner.fd_table.remove(read_fd);
+        inner.fd_table.remove(write_fd);
+        return -EFAULT;
+    }
 
     0
 }
 
-// 若读取的对象不是目录，则会产生未定义行为
-pub fn sys_getdents(fd: usize, dents: *mut CDirEntry, len: usize) -> isize {
+/// 将目录项编码为变长的[`CDirEntry`]记录写入`dirp`处的`len`个字节
+///
+/// 结果
+/// * 写入的字节数，0表示已读完
+/// * -ENOTDIR => `fd`不是目录
+/// * -EINVAL => 缓冲区连下一条记录都放不下
+/// * -EFAULT => 缓冲区中有未映射的页
+///
+/// [`CDirEntry`]: vfs::CDirEntry
+pub fn sys_getdents(fd: usize, dirp: *mut u8, len: usize) -> isize {
+    getdents(fd, dirp, len, |dir, buf| dir.getdents(buf))
+}
+
+/// 同[`sys_getdents`]，记录为附带各项状态的[`CDirEntryPlus`]，省去逐项`fstat`
+///
+/// [`CDirEntryPlus`]: vfs::CDirEntryPlus
+pub fn sys_getdents_plus(fd: usize, dirp: *mut u8, len: usize) -> isize {
+    getdents(fd, dirp, len, |dir, buf| dir.getdents_plus(buf))
+}
+
+/// 由`read`将目录`fd`的记录写入`dirp`处的`len`个字节
+fn getdents(
+    fd: usize,
+    dirp: *mut u8,
+    len: usize,
+    read: impl FnOnce(&dyn File, UserBuffer) -> Option<usize>,
+) -> isize {
     let process = processor::current_process();
     let process = process.inner().exclusive_access();
-    let token = process.user_token();
 
     if fd >= process.fd_table.len() {
         return -1;
@@ -254,12 +645,21 @@ pub fn sys_getdents(fd: usize, dents: *mut CDirEntry, len: usize) -> isize {
     let dir = dir.clone();
     drop(process);
 
-    dir.getdents(
-        UserBuffer::new(token, dents.cast(), len * mem::size_of::<CDirEntry>()),
-        len,
-    ) as isize
+    if dir.stat().mode != DirEntryType::Directory {
+        return -ENOTDIR;
+    }
+    let Some(buf) = UserBuffer::new_mut(dirp, len) else {
+        return -EFAULT;
+    };
+    match read(&*dir, buf) {
+        Some(written) => written as isize,
+        None => -EINVAL,
+    }
 }
 
+/// 结果
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
 pub fn sys_dup(fd: usize) -> isize {
     let process = processor::current_process();
     let mut inner = process.inner().exclusive_access();
@@ -272,30 +672,112 @@ pub fn sys_dup(fd: usize) -> isize {
         return -1;
     };
 
-    inner.fd_table.insert(inode) as isize
+    match inner.fd_table.insert(inode) {
+        Ok(fd) => fd as isize,
+        Err(e) => fd_errno(e),
+    }
 }
 
 pub fn sys_eventfd(initval: u64, flags: u32) -> isize {
     let event_fd = fs::eventfd::new(initval, BitFlags::from_bits_truncate(flags));
     let process = processor::current_process();
     let mut process = process.inner().exclusive_access();
-    process.fd_table.insert(event_fd) as isize
+    match process.fd_table.insert(event_fd) {
+        Ok(fd) => fd as isize,
+        Err(e) => fd_errno(e),
+    }
 }
 
-pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+/// 新建文件系统事件的监视实例，`flags`只支持[`IN_NONBLOCK`]
+///
+/// 结果
+/// * 监视实例的描述符，读出[`CInotifyEvent`](vfs::CInotifyEvent)记录
+/// * -EINVAL => `flags`含有不支持的位
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
+pub fn sys_inotify_init1(flags: usize) -> isize {
+    if flags & !IN_NONBLOCK != 0 {
+        return -EINVAL;
+    }
+    let inotify = inotify::new(flags & IN_NONBLOCK != 0);
     let process = processor::current_process();
-    let process = process.inner().exclusive_access();
+    let mut process = process.inner().exclusive_access();
+    match process.fd_table.insert(inotify) {
+        Ok(fd) => fd as isize,
+        Err(e) => fd_errno(e),
+    }
+}
 
-    let token = process.user_token();
-    let mut path = UserBuffer::new(token, buf, len);
+/// 在监视实例`fd`上监视路径`path`的`mask`事件，同一路径再次登记时改用新的`mask`。
+/// 监视目录时还报告其下各项的事件
+///
+/// 结果
+/// * 监视描述符
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => `fd`不是监视实例，或`mask`不含可监视的事件
+/// * -ENOENT => `path`不存在
+/// * -ENAMETOOLONG => 路径或其中某一项过长
+pub fn sys_inotify_add_watch(fd: usize, path: *const u8, mask: u32) -> isize {
+    let process = processor::current_process();
+    let (file, cwd) = process
+        .inner()
+        .exclusive_session(|process| (process.fd_table.try_get(fd), process.cwd.clone()));
 
-    let cwd_len = process.cwd.len();
+    let Some(file) = file else {
+        return -EBADF;
+    };
+    let Some(inotify) = file.as_inotify() else {
+        return -EINVAL;
+    };
+    if mask & IN_ALL_EVENTS == 0 {
+        return -EINVAL;
+    }
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
+    if fs::open(&path, OpenFlag::read_only()).is_none() {
+        return -ENOENT;
+    }
+
+    inotify.add_watch(&path, mask & IN_ALL_EVENTS) as isize
+}
+
+/// 撤销监视实例`fd`上的监视`wd`，实例随后读出[`IN_IGNORED`](vfs::inotify::IN_IGNORED)
+///
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => `fd`不是监视实例，或`wd`不是其上的监视
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    let file = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.fd_table.try_get(fd));
+
+    let Some(file) = file else {
+        return -EBADF;
+    };
+    match file.as_inotify() {
+        Some(inotify) if inotify.rm_watch(wd) => 0,
+        _ => -EINVAL,
+    }
+}
+
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+    let cwd = processor::current_process()
+        .inner()
+        .exclusive_session(|process| process.cwd.clone());
+
+    let Some(mut path) = UserBuffer::new_mut(buf, len) else {
+        return -EFAULT;
+    };
+
+    let cwd_len = cwd.len();
 
     if len < cwd_len {
         return -(cwd_len as isize);
     }
 
-    for (b, &cb) in path.iter_mut().zip(process.cwd.as_bytes()) {
+    for (b, &cb) in path.iter_mut().zip(cwd.as_bytes()) {
         *b = cb;
     }
 
@@ -304,12 +786,13 @@ pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
 
 pub fn sys_chdir(path: *const u8) -> isize {
     let process = processor::current_process();
-    let (cwd, token) = process
+    let cwd = process
         .inner()
-        .exclusive_session(|process| (process.cwd.clone(), process.user_token()));
+        .exclusive_session(|process| process.cwd.clone());
 
-    let Some(path) = memory::read_str(token, path).canonicalize(&cwd) else {
-        return -1;
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
     };
     if path == cwd.as_ref() {
         return 0;
@@ -322,3 +805,163 @@ pub fn sys_chdir(path: *const u8) -> isize {
 
     0
 }
+
+/// 将块设备`source`上类型为`fstype`的文件系统挂载到目录`target`，仅限超级用户
+///
+/// 结果
+/// * 0 => 成功
+/// * -EPERM => 调用者不是超级用户
+/// * -EFAULT => 某个字符串所在的页未映射或用户不可读
+/// * -ENOENT => 设备或挂载点不存在
+/// * -ENAMETOOLONG => 挂载点的路径过长
+/// * -ENOTDIR => 挂载点不是目录
+/// * -ENODEV => 不支持该文件系统类型，可用的有`fat`、`squashfs`与`easy-fs`，取决于内核的特性
+/// * -EINVAL => 设备上不是该类型的文件系统
+/// * -EBUSY => 挂载点上已有文件系统，或设备已被挂载
+pub fn sys_mount(source: *const u8, target: *const u8, fstype: *const u8) -> isize {
+    let process = processor::current_process();
+    let (cwd, uid) = process
+        .inner()
+        .exclusive_session(|process| (process.cwd.clone(), process.uid));
+    if uid != ROOT_UID {
+        return -EPERM;
+    }
+
+    let target = match read_path(target, &cwd) {
+        Ok(target) => target,
+        Err(e) => return path_errno(e),
+    };
+    match fs::open_dir(&target) {
+        Ok(_) => {}
+        Err(vfs::Error::NotADirectory) => return -ENOTDIR,
+        Err(_) => return -ENOENT,
+    }
+    let dev = match memory::read_cstr(source, PATH_MAX) {
+        Ok(source) => drivers::find_block_device(&source),
+        Err(StrError::Fault) => return -EFAULT,
+        Err(StrError::TooLong) => None,
+    };
+    let Some(dev) = dev else {
+        return -ENOENT;
+    };
+    if mount::is_mounted(&dev) {
+        return -EBUSY;
+    }
+
+    let ops = match memory::read_cstr(fstype, PATH_MAX) {
+        Ok(fstype) => fs::registry::find(&fstype),
+        Err(StrError::Fault) => return -EFAULT,
+        Err(StrError::TooLong) => None,
+    };
+    let Some(ops) = ops else {
+        return -ENODEV;
+    };
+    let Some(fs) = ops.mount(dev.clone()) else {
+        return -EINVAL;
+    };
+
+    match mount::mount(&target, fs, Some(dev)) {
+        Ok(()) => 0,
+        Err(_) => -EBUSY,
+    }
+}
+
+/// 卸载挂载在目录`target`上的文件系统，`flags`须为0，仅限超级用户
+///
+/// 结果
+/// * 0 => 成功，挂载点下的路径重又指向原先的文件系统
+/// * -EPERM => 调用者不是超级用户
+/// * -ENOENT => 路径不存在
+/// * -ENAMETOOLONG => 路径过长
+/// * -EINVAL => `target`不是挂载点，或`flags`不为0
+/// * -EBUSY => `target`是根目录，其下还有别的挂载点，或其中还有打开的文件或进程的工作目录
+pub fn sys_umount(target: *const u8, flags: usize) -> isize {
+    if flags != 0 {
+        return -EINVAL;
+    }
+    let process = processor::current_process();
+    let (cwd, uid) = process
+        .inner()
+        .exclusive_session(|process| (process.cwd.clone(), process.uid));
+    if uid != ROOT_UID {
+        return -EPERM;
+    }
+
+    let target = match read_path(target, &cwd) {
+        Ok(target) => target,
+        Err(e) => return path_errno(e),
+    };
+    match mount::unmount(&target) {
+        Ok(()) => 0,
+        Err(mount::UnmountError::NotMounted) if fs::open_dir(&target).is_err() => -ENOENT,
+        Err(mount::UnmountError::NotMounted) => -EINVAL,
+        Err(mount::UnmountError::Busy) => -EBUSY,
+    }
+}
+
+/// 查询或设置`path`所在文件系统上的磁盘配额，`cmd`与用户ID`id`由[`vfs::quota::qcmd`]合成
+///
+/// * [`Q_GETQUOTA`]：将用户`id`的配额写入`addr`，非超级用户只能查询自己
+/// * [`Q_SETQUOTA`]：按`addr`中的上限设置用户`id`的配额并写回，仅限超级用户，其余字段被忽略
+/// * [`Q_SYNC`]：写回配额文件，`id`与`addr`被忽略
+///
+/// 结果
+/// * 0 => 成功
+/// * -ENOENT => 路径不存在
+/// * -ENAMETOOLONG => 路径过长
+/// * -ESRCH => 所在文件系统不计配额，如procfs
+/// * -EPERM => 无权查询或设置
+/// * -EFAULT => `addr`为空
+/// * -EINVAL => 未知的命令
+/// * -EIO => 写回配额文件失败
+pub fn sys_quotactl(cmd: usize, id: u32, path: *const u8, addr: Option<*const DiskQuota>) -> isize {
+    let process = processor::current_process();
+    let (cwd, uid) = process
+        .inner()
+        .exclusive_session(|process| (process.cwd.clone(), process.uid));
+
+    let path = match read_path(path, &cwd) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
+    if fs::open(&path, OpenFlag::read_only()).is_none() {
+        return -ENOENT;
+    }
+    let Some((fs, _)) = mount::resolve(&path) else {
+        return -ENOENT;
+    };
+    let Some(quota) = fs.quota() else {
+        return -ESRCH;
+    };
+
+    match cmd {
+        Q_GETQUOTA => {
+            if uid != ROOT_UID && uid != id {
+                return -EPERM;
+            }
+            let Some(addr) = addr else {
+                return -EFAULT;
+            };
+            if memory::write_any(addr.cast_mut(), quota.get(id)) {
+                0
+            } else {
+                -EFAULT
+            }
+        }
+        Q_SETQUOTA => {
+            if uid != ROOT_UID {
+                return -EPERM;
+            }
+            let Some(addr) = addr else {
+                return -EFAULT;
+            };
+            let Some(limits) = memory::read_any(addr) else {
+                return -EFAULT;
+            };
+            quota.set_limits(id, limits.hard_blocks, limits.soft_blocks);
+            quota.sync().map_or(-EIO, |()| 0)
+        }
+        Q_SYNC => quota.sync().map_or(-EIO, |()| 0),
+        _ => -EINVAL,
+    }
+}
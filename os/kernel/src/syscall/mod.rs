@@ -1,24 +1,57 @@
+mod args;
+mod device;
+mod errno;
+#[cfg(feature = "fault-inject")]
+mod fault;
 mod fs;
 mod graph;
 mod input;
+mod perf;
+mod power;
 mod process;
+#[cfg(feature = "syscall-profile")]
+mod profile;
+mod ptrace;
+mod sched;
 mod sync;
 mod thread;
 mod time;
 
-use self::{fs::*, graph::*, input::*, process::*, sync::*, thread::*, time::*};
+use self::args::{ArgError, Fd, UserCStr, UserPtr, UserPtrMut, UserSlice, UserSliceMut};
+use self::errno::{EINTR, EINVAL, ENOSYS, ERESTARTSYS};
+#[cfg(feature = "fault-inject")]
+use self::fault::*;
+#[cfg(feature = "syscall-profile")]
+use self::profile::*;
+use self::{
+    device::*, fs::*, graph::*, input::*, perf::*, power::*, process::*, ptrace::*, sched::*,
+    sync::*, thread::*, time::*,
+};
+use riscv::register::scause::{Exception, Trap};
+use riscv::register::sstatus;
+
+use crate::task::{self, processor};
+#[cfg(feature = "syscall-profile")]
+use crate::timer;
+use crate::trap;
 
 const READ: usize = 0;
 const WRITE: usize = 1;
 const OPEN: usize = 2;
 const CLOSE: usize = 3;
 const FSTAT: usize = 5;
+const LSEEK: usize = 8;
+const IOCTL: usize = 16;
 const PIPE: usize = 22;
+const MADVISE: usize = 28;
 const DUP: usize = 32;
+const NANOSLEEP: usize = 35;
 const GETPID: usize = 39;
 const FORK: usize = 57;
 const EXIT: usize = 60;
 const KILL: usize = 62;
+const FCNTL: usize = 72;
+const FTRUNCATE: usize = 77;
 const GETDENTS: usize = 78;
 const GETCWD: usize = 79;
 const CHDIR: usize = 80;
@@ -27,22 +60,45 @@ const MKDIR: usize = 83;
 const RMDIR: usize = 84;
 const LINK: usize = 86;
 const UNLINK: usize = 87;
+const GETTIMEOFDAY: usize = 96;
+const GETRLIMIT: usize = 97;
+const SYSINFO: usize = 99;
 const SLEEP: usize = 101;
 const YIELD: usize = 124;
+const SIGTIMEDWAIT: usize = 128;
 const SIGACTION: usize = 134;
 const SIGPROCMASK: usize = 135;
 const SIGRETURN: usize = 139;
+const REBOOT: usize = 142;
+const PRCTL: usize = 157;
+const SETRLIMIT: usize = 160;
+const MOUNT: usize = 165;
+const UMOUNT2: usize = 166;
 const GET_TIME: usize = 169;
+const QUOTACTL: usize = 179;
 const GETTID: usize = 186;
+const FUTEX: usize = 202;
+const SCHED_SETAFFINITY: usize = 203;
+const SCHED_GETAFFINITY: usize = 204;
 const SBRK: usize = 214;
 const MUNMAP: usize = 215;
+const MREMAP: usize = 216;
 const EXEC: usize = 221;
 const MMAP: usize = 222;
+const FADVISE: usize = 223;
+const MSYNC: usize = 227;
+const CLOCK_GETRES: usize = 229;
+const INOTIFY_ADD_WATCH: usize = 254;
+const INOTIFY_RM_WATCH: usize = 255;
 const WAITPID: usize = 260;
+const UTIMENSAT: usize = 280;
 const EVENTFD: usize = 290;
+const INOTIFY_INIT1: usize = 294;
 const SPAWN: usize = 400;
 const SPAWN_THREAD: usize = 1000;
 const WAITTID: usize = 1002;
+const THREAD_DETACH: usize = 1003;
+const THREAD_COUNTS: usize = 1004;
 const MUTEX_CREATE: usize = 1010;
 const MUTEX_LOCK: usize = 1011;
 const MUTEX_UNLOCK: usize = 1012;
@@ -56,48 +112,162 @@ const FRAMEBUFFER: usize = 2000;
 const FRAMEBUFFER_FLUSH: usize = 2001;
 const GET_EVENT: usize = 3000;
 const KEY_PRESSED: usize = 3001;
+const SUSPEND: usize = 4000;
+const SCHED_GROUP_CREATE: usize = 5000;
+const SCHED_GROUP_ASSIGN: usize = 5001;
+const KSM_CTL: usize = 6000;
+const KSM_STAT: usize = 6001;
+const MMAP_FILE: usize = 6002;
+const DEVICE_PRESENT: usize = 7000;
+const PTRACE: usize = 8000;
+#[cfg(feature = "syscall-profile")]
+const SYSCALL_PROFILE: usize = 9000;
+const PERF_CTL: usize = 9001;
+const PERF_READ: usize = 9002;
+const PROCESS_LIST: usize = 9003;
+#[cfg(feature = "fault-inject")]
+const FAULT_INJECT: usize = 9004;
+const KSYM: usize = 9005;
+const GETDENTS_PLUS: usize = 9006;
+
+/// 登记ecall的处理函数
+pub fn init() {
+    trap::register_user(Trap::Exception(Exception::UserEnvCall), handle_ecall);
+}
+
+fn handle_ecall(_stval: usize) {
+    // Trap上下文不在内核地址空间内，要间接获取
+    let ctx = processor::current_trap_ctx();
+    // ecall 指令触发的异常，
+    // 希望 sepc 可以指向 ecall 的下一条指令
+    // (RISC-V 64 指令长度不超过 32 位)
+    ctx.sepc += 4;
+
+    unsafe {
+        sstatus::set_sie();
+    }
+
+    let result = syscall(ctx.arg(7), [ctx.arg(0), ctx.arg(1), ctx.arg(2)]);
+
+    // 原来的Trap上下文在 sys_exec 时被回收，需获取新的Trap上下文
+    let ctx = processor::current_trap_ctx();
+    ctx.set_syscall_result(result as usize);
+}
 
-pub fn syscall(id: usize, args: [usize; 3]) -> isize {
-    match id {
-        READ => sys_read(args[0], args[1] as _, args[2]),
-        WRITE => sys_write(args[0], args[1] as _, args[2]),
-        OPEN => sys_open(args[0] as _, args[1] as u32),
-        CLOSE => sys_close(args[0]),
-        FSTAT => sys_fstat(args[0], args[1] as _),
-        PIPE => sys_pipe(args[0] as _),
-        DUP => sys_dup(args[0]),
+/// 被信号打断的系统调用，视信号的处置重新执行或返回`-EINTR`
+fn syscall(id: usize, args: [usize; 3]) -> isize {
+    #[cfg(feature = "fault-inject")]
+    if let Some(errno) = fault::inject(id, args) {
+        return -errno;
+    }
+    #[cfg(feature = "syscall-profile")]
+    let start = timer::get_time();
+    let ret = dispatch(id, args).unwrap_or_else(|e| -e.errno());
+    #[cfg(feature = "syscall-profile")]
+    profile::record(id, timer::get_time() - start);
+
+    if ret != -ERESTARTSYS && !task::take_current_syscall_interrupted() {
+        return ret;
+    }
+
+    if !task::restart_current_syscall() {
+        return -EINTR;
+    }
+    // 返回后重新执行ecall，a0作为返回值写回，须保持为第一个参数
+    let ctx = processor::current_trap_ctx();
+    ctx.sepc -= 4;
+    ctx.arg(0) as isize
+}
+
+/// 参数先经`args`模块中的类型提取并校验，校验失败时不会进入系统调用
+fn dispatch(id: usize, args: [usize; 3]) -> Result<isize, ArgError> {
+    let ret = match id {
+        READ => sys_read(fd(args[0])?, slice_mut(args[1], args[2])?.get(), args[2]),
+        WRITE => sys_write(fd(args[0])?, slice(args[1], args[2])?.get(), args[2]),
+        OPEN => sys_open(cstr(args[0])?, args[1] as u32),
+        CLOSE => sys_close(fd(args[0])?),
+        FSTAT => sys_fstat(fd(args[0])?, ptr_mut(args[1])?.get()),
+        LSEEK => sys_lseek(fd(args[0])?, args[1] as isize, args[2]),
+        IOCTL => sys_ioctl(fd(args[0])?, args[1], args[2]),
+        PIPE => sys_pipe(slice_mut(args[0], 2)?.get()),
+        MADVISE => sys_madvise(args[0], args[1], args[2]),
+        DUP => sys_dup(fd(args[0])?),
+        NANOSLEEP => sys_nanosleep(
+            ptr(args[0])?.get(),
+            opt_ptr_mut(args[1])?.map(UserPtrMut::get),
+        ),
         GETPID => sys_getpid(),
         FORK => sys_fork(),
         EXIT => sys_exit(args[0] as i32),
         KILL => sys_kill(args[0], args[1] as u32),
-        GETDENTS => sys_getdents(args[0], args[1] as _, args[2]),
-        GETCWD => sys_getcwd(args[0] as _, args[1]),
-        CHDIR => sys_chdir(args[0] as _),
-        RENAME => sys_rename(args[0] as _, args[1] as _),
-        MKDIR => sys_mkdir(args[0] as _),
-        RMDIR => sys_rmdir(args[0] as _),
-        LINK => sys_link(args[0] as _, args[1] as _),
-        UNLINK => sys_unlink(args[0] as _),
+        FCNTL => match args[1] {
+            F_SETPIPE_SZ => sys_fcntl_setpipe_sz(fd(args[0])?, args[2]),
+            F_GETPIPE_SZ => sys_fcntl_getpipe_sz(fd(args[0])?),
+            _ => -EINVAL,
+        },
+        FTRUNCATE => sys_ftruncate(fd(args[0])?, args[1] as isize),
+        GETDENTS => sys_getdents(fd(args[0])?, slice_mut(args[1], args[2])?.get(), args[2]),
+        GETCWD => sys_getcwd(slice_mut(args[0], args[1])?.get(), args[1]),
+        CHDIR => sys_chdir(cstr(args[0])?),
+        RENAME => sys_rename(cstr(args[0])?, cstr(args[1])?),
+        MKDIR => sys_mkdir(cstr(args[0])?),
+        RMDIR => sys_rmdir(cstr(args[0])?),
+        LINK => sys_link(cstr(args[0])?, cstr(args[1])?),
+        UNLINK => sys_unlink(cstr(args[0])?),
+        GETTIMEOFDAY => sys_gettimeofday(ptr_mut(args[0])?.get()),
+        GETRLIMIT => sys_getrlimit(args[0], ptr_mut(args[1])?.get()),
+        SYSINFO => sys_sysinfo(ptr_mut(args[0])?.get()),
         SLEEP => sys_sleep(args[0]),
         YIELD => sys_yield(),
-        SIGACTION => sys_sigaction(args[0] as u32, args[1] as _, args[2] as _),
+        SIGTIMEDWAIT => sys_sigtimedwait(args[0] as u32, args[1] as isize),
+        SIGACTION => sys_sigaction(
+            args[0] as u32,
+            opt_ptr(args[1])?.map(UserPtr::get),
+            opt_ptr_mut(args[2])?.map(UserPtrMut::get),
+        ),
         SIGPROCMASK => sys_sigprocmask(args[0] as u32),
         SIGRETURN => sys_sigreturn(),
+        REBOOT => sys_reboot(args[0]),
+        PRCTL => match args[0] {
+            PR_SET_NAME => sys_prctl_set_name(cstr(args[1])?),
+            PR_GET_NAME => sys_prctl_get_name(ptr_mut(args[1])?.get()),
+            _ => -EINVAL,
+        },
+        SETRLIMIT => sys_setrlimit(args[0], args[1]),
+        MOUNT => sys_mount(cstr(args[0])?, cstr(args[1])?, cstr(args[2])?),
+        UMOUNT2 => sys_umount(cstr(args[0])?, args[1]),
         GET_TIME => sys_get_time(),
+        QUOTACTL => {
+            let (cmd, id) = vfs::quota::split_qcmd(args[0]);
+            sys_quotactl(cmd, id, cstr(args[1])?, opt_ptr(args[2])?.map(UserPtr::get))
+        }
         GETTID => sys_gettid(),
+        FUTEX => sys_futex(ptr(args[0])?.get(), args[1], args[2]),
+        SCHED_SETAFFINITY => sys_sched_setaffinity(args[0], args[1]),
+        SCHED_GETAFFINITY => sys_sched_getaffinity(args[0]),
         SBRK => sys_sbrk(args[0] as i32),
         MUNMAP => sys_munmap(args[0], args[1]),
-        EXEC => sys_exec(args[0] as _, args[1] as _),
+        MREMAP => sys_mremap(args[0], args[1], args[2]),
+        EXEC => sys_exec(cstr(args[0])?, ptr(args[1])?.get()),
         MMAP => sys_mmap(args[0], args[1], args[2] as u8),
-        WAITPID => sys_waitpid(args[0] as isize, args[1] as _),
-        SPAWN => sys_spawn(args[0] as _),
+        FADVISE => sys_fadvise(fd(args[0])?, args[1]),
+        MSYNC => sys_msync(args[0], args[1], args[2]),
+        CLOCK_GETRES => sys_clock_getres(args[0], opt_ptr_mut(args[1])?.map(UserPtrMut::get)),
+        INOTIFY_ADD_WATCH => sys_inotify_add_watch(fd(args[0])?, cstr(args[1])?, args[2] as u32),
+        INOTIFY_RM_WATCH => sys_inotify_rm_watch(fd(args[0])?, args[1] as i32),
+        WAITPID => sys_waitpid(args[0] as isize, ptr_mut(args[1])?.get()),
+        UTIMENSAT => sys_utimensat(cstr(args[0])?, opt_ptr(args[1])?.map(UserPtr::get)),
+        SPAWN => sys_spawn(cstr(args[0])?, slice(args[1], args[2])?.get(), args[2]),
         SPAWN_THREAD => sys_spawn_thread(args[0], args[1]),
         WAITTID => sys_waittid(args[0]),
+        THREAD_DETACH => sys_thread_detach(args[0]),
+        THREAD_COUNTS => sys_thread_counts(ptr_mut(args[0])?.get()),
         EVENTFD => sys_eventfd(args[0] as u64, args[1] as u32),
+        INOTIFY_INIT1 => sys_inotify_init1(args[0]),
         MUTEX_CREATE => sys_mutex_create(args[0] == 1),
         MUTEX_LOCK => sys_mutex_lock(args[0]),
         MUTEX_UNLOCK => sys_mutex_unlock(args[0]),
-        SEMAPHORE_CREATE => sys_semaphore_create(args[0]),
+        SEMAPHORE_CREATE => sys_semaphore_create(args[0], args[1] == 1),
         SEMAPHORE_UP => sys_semaphore_up(args[0]),
         SEMAPHORE_DOWN => sys_semaphore_down(args[0]),
         CONDVAR_CREATE => sys_condvar_create(),
@@ -107,6 +277,68 @@ pub fn syscall(id: usize, args: [usize; 3]) -> isize {
         FRAMEBUFFER_FLUSH => sys_framebuffer_flush(),
         GET_EVENT => sys_get_event(),
         KEY_PRESSED => sys_key_pressed(),
-        _ => panic!("Unsupported syscall ID: {id}"),
-    }
+        SUSPEND => sys_suspend(args[0]),
+        SCHED_GROUP_CREATE => sys_sched_group_create(args[0]),
+        SCHED_GROUP_ASSIGN => sys_sched_group_assign(args[0], args[1]),
+        KSM_CTL => sys_ksm_ctl(args[0]),
+        KSM_STAT => sys_ksm_stat(ptr_mut(args[0])?.get()),
+        MMAP_FILE => sys_mmap_file(fd(args[0])?, args[1], args[2] as u8),
+        DEVICE_PRESENT => sys_device_present(cstr(args[0])?),
+        PTRACE => sys_ptrace(args[0], args[1], args[2]),
+        #[cfg(feature = "syscall-profile")]
+        SYSCALL_PROFILE => {
+            sys_syscall_profile(slice_mut(args[0], args[1])?.get(), args[1], args[2])
+        }
+        PERF_CTL => sys_perf_ctl(args[0], args[1]),
+        PERF_READ => sys_perf_read(slice_mut(args[0], args[1])?.get(), args[1]),
+        PROCESS_LIST => sys_process_list(slice_mut(args[0], args[1])?.get(), args[1]),
+        #[cfg(feature = "fault-inject")]
+        FAULT_INJECT => sys_fault_inject(args[0], args[1], args[2]),
+        KSYM => sys_ksym(args[0], slice_mut(args[1], args[2])?.get(), args[2]),
+        GETDENTS_PLUS => {
+            sys_getdents_plus(fd(args[0])?, slice_mut(args[1], args[2])?.get(), args[2])
+        }
+        _ => {
+            log::warn!("[kernel] Unsupported syscall ID: {id}");
+            -ENOSYS
+        }
+    };
+
+    Ok(ret)
+}
+
+fn fd(raw: usize) -> Result<usize, ArgError> {
+    Fd::try_from(raw).map(Fd::get)
+}
+
+fn cstr(raw: usize) -> Result<*const u8, ArgError> {
+    UserCStr::try_from(raw).map(UserCStr::get)
+}
+
+fn ptr<T>(raw: usize) -> Result<UserPtr<T>, ArgError> {
+    UserPtr::try_from(raw)
+}
+
+/// 可以为空的指针
+fn opt_ptr<T>(raw: usize) -> Result<Option<UserPtr<T>>, ArgError> {
+    (raw != 0).then(|| UserPtr::try_from(raw)).transpose()
+}
+
+/// 内核要写入的指针，所指的页须用户可写
+fn ptr_mut<T>(raw: usize) -> Result<UserPtrMut<T>, ArgError> {
+    UserPtrMut::try_from(raw)
+}
+
+/// 可以为空的、内核要写入的指针
+fn opt_ptr_mut<T>(raw: usize) -> Result<Option<UserPtrMut<T>>, ArgError> {
+    (raw != 0).then(|| UserPtrMut::try_from(raw)).transpose()
+}
+
+fn slice<T>(raw: usize, len: usize) -> Result<UserSlice<T>, ArgError> {
+    UserSlice::try_from((raw, len))
+}
+
+/// 内核要写入的数组，所指的页须用户可写
+fn slice_mut<T>(raw: usize, len: usize) -> Result<UserSliceMut<T>, ArgError> {
+    UserSliceMut::try_from((raw, len))
 }
@@ -0,0 +1,48 @@
+use super::errno::{EFAULT, ENOENT, EPERM};
+use crate::ksyms;
+use crate::memory::{self, UserBuffer};
+use crate::task::perf::{self, Sample};
+use crate::task::{processor, ROOT_UID};
+
+/// 清空样本，每隔`period`个时钟中断采样一次进程`pid`被打断的用户PC，
+/// `pid`为`usize::MAX`时采样所有进程；`period`为0时停止采样，已有的样本留待读取
+pub fn sys_perf_ctl(period: usize, pid: usize) -> isize {
+    perf::start(period, (pid != usize::MAX).then_some(pid));
+    0
+}
+
+/// 按时间先后取出至多`len`个样本写入`buf`，返回取出的样本数
+pub fn sys_perf_read(buf: *mut Sample, len: usize) -> isize {
+    let samples = perf::drain(len);
+    for (i, &sample) in samples.iter().enumerate() {
+        if !memory::write_any(buf.wrapping_add(i), sample) {
+            return -EFAULT;
+        }
+    }
+    samples.len() as isize
+}
+
+/// 将内核地址`addr`换成所在函数的名称写入`buf`，放不下时截断，仅限超级用户调用
+///
+/// 结果
+/// * 名称的完整字节数
+/// * -EPERM => 调用者不是超级用户
+/// * -ENOENT => `addr`不在内核代码段中，或内核未填入符号表
+/// * -EFAULT => `buf`中有未映射或用户不可写的页
+pub fn sys_ksym(addr: usize, buf: *mut u8, len: usize) -> isize {
+    let uid = processor::current_process().inner().exclusive_access().uid;
+    if uid != ROOT_UID {
+        return -EPERM;
+    }
+
+    let Some((name, _)) = ksyms::lookup(addr) else {
+        return -ENOENT;
+    };
+    let Some(mut buf) = UserBuffer::new_mut(buf, len) else {
+        return -EFAULT;
+    };
+    for (b, &nb) in buf.iter_mut().zip(name.as_bytes()) {
+        *b = nb;
+    }
+    name.len() as isize
+}
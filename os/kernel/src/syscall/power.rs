@@ -0,0 +1,43 @@
+use super::errno::{EINVAL, EPERM};
+use crate::drivers;
+use crate::power::{self, Action};
+use crate::task::processor;
+use crate::task::ROOT_UID;
+
+/// 关机，同Linux的`LINUX_REBOOT_CMD_POWER_OFF`
+const REBOOT_CMD_POWER_OFF: usize = 0x4321_FEDC;
+/// 重启，同Linux的`LINUX_REBOOT_CMD_RESTART`
+const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+
+/// 挂起至空闲，直到串口输入或`timeout_ms`毫秒后唤醒，仅限超级用户调用
+pub fn sys_suspend(timeout_ms: usize) -> isize {
+    let uid = processor::current()
+        .process()
+        .inner()
+        .exclusive_access()
+        .uid;
+    if uid != ROOT_UID {
+        return -1;
+    }
+
+    drivers::suspend_to_idle(timeout_ms) as isize
+}
+
+/// 结束其余进程、卸载文件系统后关机或重启，仅限超级用户调用，成功时不返回
+pub fn sys_reboot(cmd: usize) -> isize {
+    let uid = processor::current()
+        .process()
+        .inner()
+        .exclusive_access()
+        .uid;
+    if uid != ROOT_UID {
+        return -EPERM;
+    }
+
+    let action = match cmd {
+        REBOOT_CMD_POWER_OFF => Action::PowerOff,
+        REBOOT_CMD_RESTART => Action::Reboot,
+        _ => return -EINVAL,
+    };
+    power::shutdown(action)
+}
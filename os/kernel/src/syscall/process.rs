@@ -1,18 +1,30 @@
 use alloc::sync::Arc;
-use alloc::vec::Vec;
 
 use enumflags2::BitFlags;
 
+use super::errno::{
+    E2BIG, EACCES, EAGAIN, EBADF, EFAULT, EINVAL, ENAMETOOLONG, ENOMEM, EPERM, ERESTARTSYS,
+};
+use crate::config::{FILE_MAX, PAGE_SIZE, USER_SPACE_END};
 use crate::fs;
+use crate::fs::File;
 use crate::fs::OpenFlag;
 use crate::memory;
-use crate::task::manager;
+use crate::memory::address::VirtAddr;
+use crate::memory::image;
+use crate::memory::ksm::{self, KsmStats};
+use crate::memory::{MapError, MapErrorKind, MapPermission, StrError};
+use crate::path::Path;
 use crate::task::processor;
-use crate::task::signal::SignalAction;
-use crate::task::ProcessControlBlock;
+use crate::task::ptrace;
+use crate::task::signal::{self, SignalAction, SignalFlag};
+use crate::task::{self, manager, ROOT_UID};
+use crate::task::{FdTable, ProcessControlBlock, TaskName};
+use crate::timer;
+use crate::timer::TimerCondVar;
 
 pub fn sys_getpid() -> isize {
-    processor::current_process().pid() as isize
+    processor::current().process().pid() as isize
 }
 
 pub fn sys_fork() -> isize {
@@ -35,63 +47,160 @@ pub fn sys_fork() -> isize {
     new_pid as isize
 }
 
-pub fn sys_exec(path: *const u8, mut args: *const usize) -> isize {
-    let token = processor::current_user_token();
-    let path = memory::read_str(token, path);
+/// 结果：
+/// * -1 => 程序不存在或无法加载
+/// * -E2BIG => 参数超出[`MAX_ARG_STRINGS`]、[`MAX_ARG_STRLEN`]或[`ARG_MAX`]的限制
+/// * -ENAMETOOLONG => 路径连同终止符超过[`PATH_MAX`]
+/// * -EFAULT => 路径、参数数组或某个参数所在的页未映射或用户不可读
+///
+/// [`MAX_ARG_STRINGS`]: crate::config::MAX_ARG_STRINGS
+/// [`MAX_ARG_STRLEN`]: crate::config::MAX_ARG_STRLEN
+/// [`ARG_MAX`]: crate::config::ARG_MAX
+/// [`PATH_MAX`]: vfs::PATH_MAX
+pub fn sys_exec(path: *const u8, args: *const usize) -> isize {
+    let path = match memory::read_path(path) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
     log::info!("Executing: {path}");
 
-    let mut arg_vec = Vec::new();
-    loop {
-        let arg = *memory::read_ref(token, args) as *const u8;
-        if arg.is_null() {
-            break;
-        }
-        log::debug!("token={token:#x} arg={arg:#p}");
-        arg_vec.push(memory::read_str(token, arg));
-        unsafe {
-            args = args.add(1);
-        }
-    }
+    // 须在替换地址空间之前读出参数
+    let arg_vec = match memory::read_argv(args) {
+        Ok(arg_vec) => arg_vec,
+        Err(StrError::Fault) => return -EFAULT,
+        Err(StrError::TooLong) => return -E2BIG,
+    };
 
     let Some(app) = fs::open(&path, OpenFlag::read_only()) else {
         return -1;
     };
 
-    let data = app.read_all();
+    // 再次执行同一程序时不必重读文件
+    let image = image::load(&*app);
     let process = processor::current_process();
     let argc = arg_vec.len();
-    process.exec(&data, arg_vec);
+    if process.exec(&image, arg_vec).is_none() {
+        return -1;
+    }
+    process.rename(TaskName::from_path(&path));
+    drop(process);
+    ptrace::on_exec();
 
     // 返回`argc`是因为exec里`ctx.x[10]`被设成该值，
     // 需在后续写入系统调用结果(同为`ctx.x[10]`)时与其保持一致
     argc as isize
 }
 
-pub fn sys_spawn(path: *const u8) -> isize {
-    let token = processor::current_user_token();
-    let path = memory::read_str(token, path);
+/// 读出路径失败时的错误码
+fn path_errno(e: StrError) -> isize {
+    match e {
+        StrError::Fault => -EFAULT,
+        StrError::TooLong => -ENAMETOOLONG,
+    }
+}
+
+/// 在新进程执行前对其文件描述符表施加的操作
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FileAction {
+    kind: usize,
+    /// 操作的目标描述符
+    fd: usize,
+    /// [`FileAction::OPEN`]：路径指针；[`FileAction::DUP2`]：源描述符
+    arg: usize,
+    /// [`FileAction::OPEN`]的打开标志
+    flags: usize,
+}
+
+impl FileAction {
+    /// 打开`arg`指向的路径，并置于`fd`
+    const OPEN: usize = 0;
+    /// 将描述符`arg`复制到`fd`
+    const DUP2: usize = 1;
+    /// 关闭`fd`
+    const CLOSE: usize = 2;
+
+    /// 按父进程的视角(地址空间与工作目录)将操作施加于`fd_table`，失败时返回错误码
+    fn apply(&self, fd_table: &mut FdTable, cwd: &str) -> Result<(), isize> {
+        match self.kind {
+            Self::OPEN => {
+                let path = memory::read_path(self.arg as *const u8)
+                    .map_err(path_errno)?
+                    .canonicalize(cwd)
+                    .map_err(|_| -1)?;
+                let flags = BitFlags::from_bits(self.flags as u32).map_err(|_| -1)?;
+                let file = fs::open(&path, flags).ok_or(-1)?;
+                fd_table.insert_kv(self.fd, file).map_err(|_| -1)?;
+            }
+            Self::DUP2 => {
+                let file = fd_table.try_get(self.arg).ok_or(-1)?;
+                fd_table.insert_kv(self.fd, file).map_err(|_| -1)?;
+            }
+            Self::CLOSE => {
+                if self.fd >= fd_table.len() {
+                    return Err(-1);
+                }
+                fd_table.remove(self.fd).ok_or(-1)?;
+            }
+            _ => return Err(-1),
+        }
+
+        Ok(())
+    }
+}
+
+/// 参数：
+/// * `actions`与`len`：依次施加于子进程文件描述符表的操作，可为空
+///
+/// 子进程继承父进程的文件描述符表，任一操作失败则不创建子进程。
+///
+/// 结果：
+/// * -1 => 程序不存在，或某一操作失败
+/// * -ENAMETOOLONG => 路径或某一操作打开的路径连同终止符超过[`PATH_MAX`](vfs::PATH_MAX)
+/// * -EFAULT => 路径、某一操作或其打开的路径所在的页未映射或用户不可读
+pub fn sys_spawn(path: *const u8, actions: *const FileAction, len: usize) -> isize {
+    let current_process = processor::current_process();
+    let (cwd, mut fd_table) = current_process
+        .inner()
+        .exclusive_session(|process| (process.cwd.clone(), process.fd_table.clone()));
+    let path = match memory::read_path(path) {
+        Ok(path) => path,
+        Err(e) => return path_errno(e),
+    };
+
+    for i in 0..len {
+        let Some(action) = memory::read_any(actions.wrapping_add(i)) else {
+            return -EFAULT;
+        };
+        if let Err(errno) = action.apply(&mut fd_table, &cwd) {
+            return errno;
+        }
+    }
 
     let Some(app) = fs::open(&path, BitFlags::from_bits_truncate(OpenFlag::RDONLY)) else {
         return -1;
     };
 
-    let sub_process = ProcessControlBlock::new(&app.read_all());
+    let sub_process = ProcessControlBlock::with_fd_table(&image::load(&*app), fd_table);
+    sub_process.rename(TaskName::from_path(&path));
     let sub_pid = sub_process.pid();
 
-    let current_process = processor::current_process();
-    current_process
-        .inner()
-        .exclusive_access()
-        .children
-        .push(sub_process.clone());
-    sub_process.inner().exclusive_access().parent = Some(Arc::downgrade(&current_process));
+    let (uid, sched_group) = current_process.inner().exclusive_session(|process| {
+        process.children.push(sub_process.clone());
+        (process.uid, process.sched_group)
+    });
+    sub_process.inner().exclusive_session(|sub_process| {
+        sub_process.parent = Some(Arc::downgrade(&current_process));
+        sub_process.uid = uid;
+        sub_process.sched_group = sched_group;
+    });
 
     sub_pid as isize
 }
 
 pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    let process = processor::current_process();
-    let mut process = process.inner().exclusive_access();
+    let current = processor::current_process();
+    let process = current.inner().exclusive_access();
 
     let child_idx = if pid == -1 {
         let children = &process.children;
@@ -123,13 +232,17 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
 
     match child_idx {
         Some(index) => {
-            let child = process.children.remove(index);
+            // 将子进程的退出码传递给传入的 exit_code 指针，
+            // 写不进去时子进程仍是僵尸进程，留待下次回收
+            let exit_code = process.children[index].inner().exclusive_access().exit_code;
+            drop(process);
+            if !memory::write_any(exit_code_ptr, exit_code) {
+                return -EFAULT;
+            }
+
+            let child = current.inner().exclusive_access().children.remove(index);
             assert_eq!(Arc::strong_count(&child), 1);
 
-            // 将子进程的退出码传递给传入的 exit_code 指针
-            let exit_code = child.inner().exclusive_access().exit_code;
-            *memory::read_mut(process.user_token(), exit_code_ptr) = exit_code;
-
             // 传入的PID 或 僵尸进程的PID
             child.pid() as isize
 
@@ -139,13 +252,90 @@ pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
     }
 }
 
-#[allow(unused_variables)]
+/// 设置信号`signum`的处置，并将原处置写入`old_action`，二者均可为空。
+///
+/// 内核尚不会调用处理例程，目前只有[`SA_RESTART`](signal::SA_RESTART)生效。
 pub fn sys_sigaction(
     signum: u32,
-    action: *const SignalAction,
-    old_action: *mut SignalAction,
+    action: Option<*const SignalAction>,
+    old_action: Option<*mut SignalAction>,
 ) -> isize {
-    -1
+    let signum = signum as usize;
+    if signum == 0
+        || signum >= signal::COUNT
+        || signum == SignalFlag::SIGKILL.signum()
+        || signum == SignalFlag::SIGSTOP.signum()
+    {
+        return -EINVAL;
+    }
+
+    let new_action = match action {
+        Some(action) => match memory::read_any(action) {
+            Some(action) => Some(action),
+            None => return -EFAULT,
+        },
+        None => None,
+    };
+
+    let process = processor::current_process();
+    let old = process
+        .inner()
+        .exclusive_session(|inner| inner.sigactions[signum]);
+    if let Some(old_action) = old_action {
+        if !memory::write_any(old_action, old) {
+            return -EFAULT;
+        }
+    }
+    if let Some(action) = new_action {
+        process
+            .inner()
+            .exclusive_session(|inner| inner.sigactions[signum] = action);
+    }
+
+    0
+}
+
+/// 等待`mask`中的任一信号，取走其中编号最小的一个并返回其编号，不调用其处理例程。
+/// 子进程结束或被追踪而停下时，父进程收到`SIGCHLD`，
+/// 据此等待后再以`waitpid`回收，不必轮询。
+///
+/// `timeout_ms`为负时一直等待。被`mask`以外的信号打断时，
+/// 若重新执行，只等待剩余的时间。
+///
+/// 结果
+/// * 取走的信号的编号
+/// * -EINVAL => `mask`为空或含有不存在的信号
+/// * -EAGAIN => 超时
+pub fn sys_sigtimedwait(mask: u32, timeout_ms: isize) -> isize {
+    let set = match BitFlags::<SignalFlag>::from_bits(mask) {
+        Ok(set) if !set.is_empty() => set,
+        _ => return -EINVAL,
+    };
+    let expire_ms = usize::try_from(timeout_ms)
+        .map_or(usize::MAX, |ms| timer::get_time_ms().saturating_add(ms));
+    let task = processor::current_task().unwrap();
+
+    loop {
+        if let Some(signal) = task::take_current_signal(set) {
+            return signal.signum() as isize;
+        }
+        let now = timer::get_time_ms();
+        if task::current_signal_pending() {
+            if expire_ms != usize::MAX {
+                *processor::current_trap_ctx().arg_mut(1) = expire_ms.saturating_sub(now);
+            }
+            return -ERESTARTSYS;
+        }
+        if now >= expire_ms {
+            return -EAGAIN;
+        }
+
+        // 一直等待时也挂上计时器，信号经由移除计时器唤醒线程
+        task.inner().exclusive_access().sigwait = set;
+        timer::add_timer(TimerCondVar::new(expire_ms, task.clone()));
+        task::block_current_and_run_next();
+        task.inner().exclusive_access().sigwait = BitFlags::empty();
+    }
 }
 
 #[allow(unused_variables)]
@@ -167,11 +357,9 @@ pub fn sys_kill(pid: usize, signum: u32) -> isize {
         return -1;
     };
 
-    let mut inner = process.inner().exclusive_access();
-    if inner.signals.contains(signal) {
+    if !task::send_signal(&process, signal) {
         return -1;
     }
-    inner.signals.insert(signal);
 
     0
 }
@@ -182,12 +370,276 @@ pub fn sys_sbrk(size: i32) -> isize {
     -1
 }
 
-#[allow(unused_variables)]
+/// mmap的保护位，与[`MapPermission`]的位不同
+const PROT_READ: u8 = 0b001;
+const PROT_WRITE: u8 = 0b010;
+const PROT_EXEC: u8 = 0b100;
+
+/// 映射`len`字节的匿名内存，内容为全零，返回起始地址。
+/// `start`不为0时作为建议的起始地址，须按页对齐，该处已被占用时另择他处。
 pub fn sys_mmap(start: usize, len: usize, prot: u8) -> isize {
-    -1
+    if len == 0 || start % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    let Some(permission) = prot_to_permission(prot) else {
+        return -EINVAL;
+    };
+
+    let hint = (start != 0).then(|| VirtAddr::from(start).floor());
+    let pages = len.div_ceil(PAGE_SIZE);
+    let process = processor::current_process();
+    let mut inner = process.inner().exclusive_access();
+    match inner
+        .address_space
+        .insert_mmap(hint, pages, permission, None)
+    {
+        Ok(start) => usize::from(VirtAddr::from(start)) as isize,
+        Err(e) => map_errno(e.kind),
+    }
 }
 
-#[allow(unused_variables)]
+/// 共享地映射打开的文件`fd`自开头起的`len`字节，返回起始地址。
+///
+/// 页在首次访问时从文件读入，修改经[`sys_msync`]或撤销映射时写回文件；
+/// 超出文件末尾的部分读到全零，写入的内容不会写回。
+///
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EACCES => 文件不可读，或要求可写而文件不可写
+pub fn sys_mmap_file(fd: usize, len: usize, prot: u8) -> isize {
+    if len == 0 {
+        return -EINVAL;
+    }
+    let Some(permission) = prot_to_permission(prot) else {
+        return -EINVAL;
+    };
+
+    let process = processor::current_process();
+    let mut inner = process.inner().exclusive_access();
+    let Some(file) = inner.fd_table.try_get(fd) else {
+        return -EBADF;
+    };
+    if !file.readable() || (prot & PROT_WRITE != 0 && !file.writable()) {
+        return -EACCES;
+    }
+
+    let pages = len.div_ceil(PAGE_SIZE);
+    match inner
+        .address_space
+        .insert_mmap(None, pages, permission, Some(file))
+    {
+        Ok(start) => usize::from(VirtAddr::from(start)) as isize,
+        Err(e) => map_errno(e.kind),
+    }
+}
+
+/// 保护位转为用户可访问的映射权限，含未知的位时返回`None`
+fn prot_to_permission(prot: u8) -> Option<BitFlags<MapPermission>> {
+    if prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+        return None;
+    }
+
+    let mut permission = BitFlags::from(MapPermission::U);
+    for (bit, perm) in [
+        (PROT_READ, MapPermission::R),
+        (PROT_WRITE, MapPermission::W),
+        (PROT_EXEC, MapPermission::X),
+    ] {
+        if prot & bit != 0 {
+            permission |= perm;
+        }
+    }
+    Some(permission)
+}
+
+/// 撤销由mmap映射的整个区间
 pub fn sys_munmap(start: usize, len: usize) -> isize {
-    -1
+    if start % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+
+    let Some(end) = start.checked_add(len) else {
+        return -EINVAL;
+    };
+    let vpns = VirtAddr::from(start).floor()..VirtAddr::from(end).ceil();
+    let process = processor::current_process();
+    let mut inner = process.inner().exclusive_access();
+    match inner.address_space.remove_mmap(vpns) {
+        Ok(()) => 0,
+        Err(e) => map_errno(e.kind),
+    }
+}
+
+/// 将由mmap映射的整个区间`[start, start + old_len)`伸缩至`new_len`字节，返回新的起始地址。
+///
+/// 其后的虚拟地址已被占用时整体移到别处，如同Linux带上了`MREMAP_MAYMOVE`，
+/// 原有的内容随页帧一并移动，不做复制。
+pub fn sys_mremap(start: usize, old_len: usize, new_len: usize) -> isize {
+    if start % PAGE_SIZE != 0 || new_len == 0 {
+        return -EINVAL;
+    }
+
+    let Some(end) = start.checked_add(old_len) else {
+        return -EINVAL;
+    };
+    let vpns = VirtAddr::from(start).floor()..VirtAddr::from(end).ceil();
+    let process = processor::current_process();
+    let mut inner = process.inner().exclusive_access();
+    match inner.address_space.remap(vpns, new_len.div_ceil(PAGE_SIZE)) {
+        Ok(start) => usize::from(VirtAddr::from(start)) as isize,
+        Err(e) => map_errno(e.kind),
+    }
+}
+
+/// 写回后即返回，与同步写回无异
+const MS_ASYNC: usize = 0b001;
+/// 不缓存页，无需处理
+const MS_INVALIDATE: usize = 0b010;
+/// 写回后再将块缓存刷入设备
+const MS_SYNC: usize = 0b100;
+
+/// 将`[start, start + len)`内文件映射中被修改的页写回文件，`start`须按页对齐。
+/// 区间内有未映射的页时返回`-ENOMEM`。
+pub fn sys_msync(start: usize, len: usize, flags: usize) -> isize {
+    if start % PAGE_SIZE != 0
+        || flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+    {
+        return -EINVAL;
+    }
+    let Some(end) = start.checked_add(len) else {
+        return -EINVAL;
+    };
+    let vpns = VirtAddr::from(start).floor()..VirtAddr::from(end).ceil();
+
+    let process = processor::current_process();
+    let address_space = &mut process.inner().exclusive_access().address_space;
+    if !address_space.covers(vpns.clone()) {
+        return -ENOMEM;
+    }
+    address_space.write_back(vpns);
+
+    if flags & MS_SYNC != 0 {
+        fs::sync();
+    }
+    0
+}
+
+fn map_errno(kind: MapErrorKind) -> isize {
+    match kind {
+        MapErrorKind::ExceedLimit | MapErrorKind::NoSpace => -ENOMEM,
+        _ => -EINVAL,
+    }
+}
+
+/// 即将访问该区间，预先映射页帧
+const MADV_WILLNEED: usize = 3;
+/// 不再需要该区间的内容，释放其页帧，此后读到全零
+const MADV_DONTNEED: usize = 4;
+
+/// 就`[start, start + len)`的用法向内核提出建议，`start`须按页对齐。
+///
+/// 结果
+/// * -EINVAL => 区间超出用户地址空间，或DONTNEED涉及匿名内存与mmap映射以外的逻辑段
+/// * -ENOMEM => 区间内有未映射的页，或WILLNEED超出页帧上限
+pub fn sys_madvise(start: usize, len: usize, advice: usize) -> isize {
+    if start % PAGE_SIZE != 0 {
+        return -EINVAL;
+    }
+    let Some(end) = start.checked_add(len).filter(|&end| end <= USER_SPACE_END) else {
+        return -EINVAL;
+    };
+    let vpns = VirtAddr::from(start).floor()..VirtAddr::from(end).ceil();
+
+    let process = processor::current_process();
+    let address_space = &mut process.inner().exclusive_access().address_space;
+    if !address_space.covers(vpns.clone()) {
+        return -ENOMEM;
+    }
+    let result = match advice {
+        MADV_DONTNEED => address_space.discard(vpns),
+        MADV_WILLNEED => address_space.populate(vpns),
+        _ => return -EINVAL,
+    };
+
+    match result {
+        Ok(_) => 0,
+        Err(MapError {
+            kind: MapErrorKind::TypeMissed,
+            ..
+        }) => -EINVAL,
+        Err(_) => -ENOMEM,
+    }
+}
+
+/// 启用或停用相同页合并，仅限超级用户调用
+///
+/// 结果
+/// * -EPERM => 调用者不是超级用户
+pub fn sys_ksm_ctl(enable: usize) -> isize {
+    if processor::current_process().inner().exclusive_access().uid != ROOT_UID {
+        return -EPERM;
+    }
+    ksm::set_enabled(enable != 0);
+    0
+}
+
+/// 将相同页合并的统计写入`stats`
+pub fn sys_ksm_stat(stats: *mut KsmStats) -> isize {
+    if memory::write_any(stats, ksm::stats()) {
+        0
+    } else {
+        -EFAULT
+    }
+}
+
+/// 进程打开的文件描述符个数的资源限制
+const RLIMIT_NOFILE: usize = 7;
+/// 地址空间大小的资源限制，以字节计
+const RLIMIT_AS: usize = 9;
+const RLIM_INFINITY: usize = usize::MAX;
+
+/// 读取当前进程的资源限制，目前只支持[`RLIMIT_NOFILE`]与[`RLIMIT_AS`]
+pub fn sys_getrlimit(resource: usize, limit: *mut usize) -> isize {
+    let process = processor::current_process();
+    let process = process.inner().exclusive_access();
+    let value = match resource {
+        RLIMIT_NOFILE => process.fd_table.limit(),
+        RLIMIT_AS => process
+            .address_space
+            .frame_limit()
+            .map_or(RLIM_INFINITY, |frames| frames * PAGE_SIZE),
+        _ => return -1,
+    };
+    drop(process);
+
+    if memory::write_any(limit, value) {
+        0
+    } else {
+        -EFAULT
+    }
+}
+
+/// 设置当前进程的资源限制，目前只支持[`RLIMIT_NOFILE`]与[`RLIMIT_AS`]。
+///
+/// 地址空间的限制按页向上取整，超出限制的分配会失败。
+/// 描述符个数的限制不得超过全系统的上限，调低时已打开的描述符不受影响。
+pub fn sys_setrlimit(resource: usize, limit: usize) -> isize {
+    let process = processor::current_process();
+    let mut process = process.inner().exclusive_access();
+    match resource {
+        RLIMIT_NOFILE => {
+            if limit > FILE_MAX {
+                return -1;
+            }
+            process.fd_table.set_limit(limit);
+        }
+        RLIMIT_AS => {
+            let frame_limit = (limit != RLIM_INFINITY).then(|| limit.div_ceil(PAGE_SIZE));
+            process.address_space.set_frame_limit(frame_limit);
+        }
+        _ => return -1,
+    }
+
+    0
 }
@@ -0,0 +1,75 @@
+//! 系统调用延迟的直方图，仅在启用`syscall-profile`特性时编译
+//!
+//! 延迟以`mtime`的计数为单位，按2的幂分桶：第`i`个桶统计落在`[2^i, 2^(i+1))`内的次数，
+//! 第0个桶还包括延迟为0的调用。阻塞的系统调用，其延迟包括阻塞的时间。
+//! 目前只有一个CPU，直方图即该CPU的直方图。
+
+use alloc::collections::BTreeMap;
+
+use super::errno::EFAULT;
+use crate::memory;
+use crate::sync::UpCell;
+
+/// 直方图的桶数，足以容纳`usize`范围内的任何延迟
+pub const BUCKETS: usize = usize::BITS as usize;
+
+/// 取快照后清空直方图
+const RESET: usize = 1;
+
+/// 各系统调用的直方图，以系统调用号为键
+static HISTOGRAMS: UpCell<BTreeMap<usize, SyscallHistogram>> = UpCell::new(BTreeMap::new());
+
+/// 单个系统调用的延迟直方图，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallHistogram {
+    /// 系统调用号
+    pub id: usize,
+    /// 调用次数
+    pub count: usize,
+    /// 延迟之和
+    pub total: usize,
+    pub buckets: [usize; BUCKETS],
+}
+
+impl SyscallHistogram {
+    fn new(id: usize) -> Self {
+        Self {
+            id,
+            count: 0,
+            total: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+/// 记录一次耗时`ticks`的`id`号系统调用
+pub fn record(id: usize, ticks: usize) {
+    let mut histograms = HISTOGRAMS.exclusive_access();
+    let histogram = histograms
+        .entry(id)
+        .or_insert_with(|| SyscallHistogram::new(id));
+    histogram.count += 1;
+    histogram.total = histogram.total.saturating_add(ticks);
+    histogram.buckets[ticks.checked_ilog2().unwrap_or(0) as usize] += 1;
+}
+
+/// 将直方图按系统调用号升序写入`buf`，至多`len`个；
+/// `flags`含[`RESET`]且`buf`容纳得下全部直方图时随后清空。
+///
+/// 返回采集到的直方图总数，可能大于`len`。
+pub fn sys_syscall_profile(buf: *mut SyscallHistogram, len: usize, flags: usize) -> isize {
+    let mut histograms = HISTOGRAMS.exclusive_access();
+    let total = histograms.len();
+
+    for (i, &histogram) in histograms.values().take(len).enumerate() {
+        if !memory::write_any(buf.wrapping_add(i), histogram) {
+            return -EFAULT;
+        }
+    }
+    if flags & RESET != 0 && total <= len {
+        histograms.clear();
+    }
+
+    total as isize
+}
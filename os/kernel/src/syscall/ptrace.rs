@@ -0,0 +1,108 @@
+use alloc::sync::Arc;
+
+use super::args::{UserPtr, UserPtrMut};
+use super::errno::{EFAULT, EINVAL, EPERM, ESRCH};
+use crate::memory;
+use crate::task::ptrace::{self, UserRegs};
+use crate::task::{manager, processor, ProcessControlBlock};
+
+/// 请父进程追踪自己，首次`exec`完成时停下
+const TRACEME: usize = 0;
+/// 读取被追踪者的一个字
+const PEEKDATA: usize = 2;
+/// 改写被追踪者的一个字
+const POKEDATA: usize = 5;
+/// 放行被追踪者
+const CONT: usize = 7;
+/// 放行被追踪者，执行一条指令后停下
+const SINGLESTEP: usize = 9;
+/// 读取被追踪者的寄存器
+const GETREGS: usize = 12;
+/// 查询被追踪者是否停下，非Linux请求
+const STOPPED: usize = 0x4200;
+
+/// `PEEKDATA`与`POKEDATA`的参数，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PtraceWord {
+    pub addr: usize,
+    pub data: usize,
+}
+
+/// 除`TRACEME`与`STOPPED`外，各请求都要求`pid`是由当前进程追踪且已停下的进程
+///
+/// 结果
+/// * -ESRCH => `pid`不存在、已结束、不由当前进程追踪，或尚未停下
+/// * -EFAULT => `arg`或被追踪者的地址非法
+/// * -EINVAL => 未知的请求
+pub fn sys_ptrace(request: usize, pid: usize, arg: usize) -> isize {
+    if request == TRACEME {
+        return ptrace::trace_me().map_or(-EPERM, |_| 0);
+    }
+
+    let Some(tracee) = traced_by_current(pid) else {
+        return -ESRCH;
+    };
+    let stopped = tracee
+        .inner()
+        .exclusive_access()
+        .tracee
+        .as_ref()
+        .is_some_and(|tracee| tracee.stopped);
+
+    match request {
+        STOPPED => stopped as isize,
+        _ if !stopped => -ESRCH,
+        PEEKDATA | POKEDATA => {
+            // 读出的字写回同一处，故`PEEKDATA`要求其可写
+            let ptr = if request == PEEKDATA {
+                UserPtrMut::<PtraceWord>::try_from(arg).map(UserPtrMut::get)
+            } else {
+                UserPtr::<PtraceWord>::try_from(arg).map(|ptr| ptr.get().cast_mut())
+            };
+            let Some(mut word) = ptr.ok().and_then(memory::read_any) else {
+                return -EFAULT;
+            };
+            let mut inner = tracee.inner().exclusive_access();
+
+            if request == PEEKDATA {
+                let Some(data) = ptrace::peek(&mut inner.address_space, word.addr) else {
+                    return -EFAULT;
+                };
+                drop(inner);
+                word.data = data;
+                if !memory::write_any(arg as *mut PtraceWord, word) {
+                    return -EFAULT;
+                }
+                0
+            } else {
+                ptrace::poke(&mut inner.address_space, word.addr, word.data).map_or(-EFAULT, |_| 0)
+            }
+        }
+        GETREGS => {
+            let Ok(regs) = UserPtrMut::<UserRegs>::try_from(arg) else {
+                return -EFAULT;
+            };
+            let task = tracee.inner().exclusive_access().tasks.get(0);
+            let ctx = task.inner().exclusive_access().trap_ctx();
+            let regs_now = UserRegs {
+                x: *ctx.regs(),
+                pc: ctx.pc(),
+            };
+            if memory::write_any(regs.get(), regs_now) {
+                0
+            } else {
+                -EFAULT
+            }
+        }
+        CONT | SINGLESTEP => ptrace::resume(&tracee, request == SINGLESTEP).map_or(-EFAULT, |_| 0),
+        _ => -EINVAL,
+    }
+}
+
+/// 由当前进程追踪、尚未结束的进程
+fn traced_by_current(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    let process = manager::get_process(pid)?;
+    let tracer = process.inner().exclusive_access().tracee.as_ref()?.tracer;
+    (tracer == processor::current().process().pid()).then_some(process)
+}
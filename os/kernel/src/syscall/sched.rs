@@ -0,0 +1,153 @@
+use alloc::sync::{Arc, Weak};
+
+use super::errno::{EFAULT, EINVAL, EPERM, ESRCH};
+use crate::boot::{self, Phase};
+use crate::memory;
+use crate::task::manager::{self, FSHIFT};
+use crate::task::{self, group, processor, ROOT_UID, TASK_NAME_LEN};
+use crate::timer;
+
+/// [`SysInfo::loads`]中小数部分的位数，同Linux
+const SI_LOAD_SHIFT: usize = 16;
+
+/// 系统概况，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SysInfo {
+    /// 启动以来的秒数
+    pub uptime: usize,
+    /// 1、5、15分钟的平均负载，小数部分占[`SI_LOAD_SHIFT`]位
+    pub loads: [usize; 3],
+    /// 进程数
+    pub procs: usize,
+    /// 启动各阶段的耗时，以微秒计，按[`Phase`]的顺序排列
+    pub boot_us: [usize; Phase::COUNT],
+}
+
+/// 进程列表中的一项，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessInfo {
+    pub pid: usize,
+    /// 父进程号，没有父进程时为0
+    pub ppid: usize,
+    /// 线程数
+    pub threads: usize,
+    /// 以NUL结尾的进程名
+    pub name: [u8; TASK_NAME_LEN],
+}
+
+pub fn sys_sysinfo(info: *mut SysInfo) -> isize {
+    let info_now = SysInfo {
+        uptime: timer::get_time_ms() / 1000,
+        loads: manager::load_average().map(|load| load << (SI_LOAD_SHIFT - FSHIFT)),
+        procs: manager::process_count(),
+        boot_us: boot::phases_us(),
+    };
+    if memory::write_any(info, info_now) {
+        0
+    } else {
+        -EFAULT
+    }
+}
+
+/// 按进程号升序将至多`len`个进程的概况写入`buf`，返回写入的项数
+pub fn sys_process_list(buf: *mut ProcessInfo, len: usize) -> isize {
+    let mut written = 0;
+    for process in manager::processes().into_iter().take(len) {
+        let info = process.inner().exclusive_session(|inner| ProcessInfo {
+            pid: process.pid(),
+            ppid: inner
+                .parent
+                .as_ref()
+                .and_then(Weak::upgrade)
+                .map_or(0, |parent| parent.pid()),
+            threads: inner.thread_count(),
+            name: *inner.name.as_bytes(),
+        });
+        if !memory::write_any(buf.wrapping_add(written), info) {
+            return -EFAULT;
+        }
+        written += 1;
+    }
+    written as isize
+}
+
+/// 创建调度组，`share`为每周期可占用的CPU百分比
+pub fn sys_sched_group_create(share: usize) -> isize {
+    match group::create(share) {
+        Some(gid) => gid as isize,
+        None => -1,
+    }
+}
+
+/// 将进程`pid`划入调度组`gid`，`gid`为`usize::MAX`时移出调度组。
+/// 只有超级用户与目标进程的属主可以调整
+///
+/// 结果
+/// * -1 => 进程或调度组不存在
+/// * -EPERM => 调用者既不是超级用户，也不是目标进程的属主
+pub fn sys_sched_group_assign(pid: usize, gid: usize) -> isize {
+    let Some(process) = manager::get_process(pid) else {
+        return -1;
+    };
+    let uid = processor::current_process().inner().exclusive_access().uid;
+    if uid != ROOT_UID && uid != process.inner().exclusive_access().uid {
+        return -EPERM;
+    }
+
+    let sched_group = if gid == usize::MAX {
+        None
+    } else if group::exists(gid) {
+        Some(gid)
+    } else {
+        return -1;
+    };
+
+    process.inner().exclusive_access().sched_group = sched_group;
+    0
+}
+
+/// 将当前进程中线程`tid`的亲和性设为`mask`，第`i`位允许其在编号为`i`的处理器核上运行，
+/// 不存在的核被忽略。当前线程不再允许在所在的核上运行时立即让出。
+///
+/// 结果
+/// * -ESRCH => 线程不存在
+/// * -EINVAL => `mask`不含任何存在的处理器核
+pub fn sys_sched_setaffinity(tid: usize, mask: usize) -> isize {
+    let mask = mask & processor::ALL_HARTS;
+    if mask == 0 {
+        return -EINVAL;
+    }
+
+    let current = processor::current_task().unwrap();
+    let process = current.process.upgrade().unwrap();
+    let Some(task) = process.inner().exclusive_access().tasks.try_get(tid) else {
+        return -ESRCH;
+    };
+    task.inner().exclusive_access().affinity = mask;
+
+    if mask & (1 << processor::hart_id()) != 0 {
+        return 0;
+    }
+    if Arc::ptr_eq(&task, &current) {
+        drop((task, current, process));
+        task::suspend_current_and_run_next();
+    } else {
+        manager::migrate_task(&task);
+    }
+    0
+}
+
+/// 当前进程中线程`tid`的亲和性掩码
+///
+/// 结果
+/// * -ESRCH => 线程不存在
+pub fn sys_sched_getaffinity(tid: usize) -> isize {
+    let process = processor::current_process();
+    let Some(task) = process.inner().exclusive_access().tasks.try_get(tid) else {
+        return -ESRCH;
+    };
+    let affinity = task.inner().exclusive_access().affinity;
+    affinity as isize
+}
@@ -1,8 +1,17 @@
 use alloc::sync::Arc;
 
-use crate::sync::{BlockMutex, Condvar, Mutex, Semaphore, SpinMutex};
+use super::errno::EOWNERDEAD;
+use crate::sync::{futex, BlockMutex, Condvar, Mutex, Poisoned, Semaphore, SpinMutex};
 use crate::task::processor;
 
+/// 锁或信号量被毒化时，调用者仍取得了它，但须知悉受保护的数据可能不一致
+fn poison_status(result: Result<(), Poisoned>) -> isize {
+    match result {
+        Ok(()) => 0,
+        Err(Poisoned) => -EOWNERDEAD,
+    }
+}
+
 pub fn sys_mutex_create(block: bool) -> isize {
     let mutex: Arc<dyn Mutex> = if block {
         Arc::new(BlockMutex::new())
@@ -14,12 +23,13 @@ pub fn sys_mutex_create(block: bool) -> isize {
     process.mutex_list.insert(mutex) as isize
 }
 
+/// 结果
+/// * -EOWNERDEAD => 前一个持有者未解锁便退出了，锁已转交给调用者
 pub fn sys_mutex_lock(id: usize) -> isize {
     let process = processor::current_process();
     let mutex = process.inner().exclusive_access().mutex_list.get(id);
     drop(process);
-    mutex.lock();
-    0
+    poison_status(mutex.lock())
 }
 
 pub fn sys_mutex_unlock(id: usize) -> isize {
@@ -30,13 +40,19 @@ pub fn sys_mutex_unlock(id: usize) -> isize {
     0
 }
 
-pub fn sys_semaphore_create(permits: usize) -> isize {
+/// `robust`为真时创建健壮的信号量，见[`Semaphore::robust`]
+pub fn sys_semaphore_create(permits: usize, robust: bool) -> isize {
+    let semaphore = if robust {
+        Semaphore::robust(permits)
+    } else {
+        Semaphore::new(permits)
+    };
     let process = processor::current_process();
     let id = process
         .inner()
         .exclusive_access()
         .semaphore_list
-        .insert(Arc::new(Semaphore::new(permits)));
+        .insert(Arc::new(semaphore));
     id as isize
 }
 
@@ -48,12 +64,13 @@ pub fn sys_semaphore_up(id: usize) -> isize {
     0
 }
 
+/// 结果
+/// * -EOWNERDEAD => 健壮的信号量的某个持有者未归还资源便退出了，调用者仍取得了资源
 pub fn sys_semaphore_down(id: usize) -> isize {
     let process = processor::current_process();
     let semaphore = process.inner().exclusive_access().semaphore_list.get(id);
     drop(process);
-    semaphore.down();
-    0
+    poison_status(semaphore.down())
 }
 
 pub fn sys_condvar_create() -> isize {
@@ -74,11 +91,35 @@ pub fn sys_condvar_signal(id: usize) -> isize {
     0
 }
 
+/// 结果
+/// * -EOWNERDEAD => 睡眠期间锁的持有者未解锁便退出了，醒来后锁已转交给调用者
 pub fn sys_condvar_wait(id: usize, mutex_id: usize) -> isize {
     let process = processor::current_process();
     let condvar = process.inner().exclusive_access().condvar_list.get(id);
     let mutex = process.inner().exclusive_access().mutex_list.get(mutex_id);
     drop(process);
-    condvar.wait_with_mutex(mutex);
-    0
+    poison_status(condvar.wait_with_mutex(mutex))
+}
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// `uaddr`已由分发处校验为对齐且用户可读的地址
+///
+/// # 结果
+///
+/// * `FUTEX_WAIT` => 0 表示睡眠后被唤醒，-1 表示`uaddr`处的值已不是`val`
+/// * `FUTEX_WAKE` => 唤醒的任务数，至多为`val`
+pub fn sys_futex(uaddr: *const u32, op: usize, val: usize) -> isize {
+    match op {
+        FUTEX_WAIT => {
+            if futex::wait(uaddr, val as u32) {
+                0
+            } else {
+                -1
+            }
+        }
+        FUTEX_WAKE => futex::wake(uaddr, val) as isize,
+        _ => -1,
+    }
 }
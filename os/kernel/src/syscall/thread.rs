@@ -1,10 +1,16 @@
 use alloc::sync::Arc;
 
-use crate::memory;
+use vfs::PATH_MAX;
+
+use super::errno::{EFAULT, EINTR, EINVAL, ERESTARTSYS, ESRCH};
+use super::time::TimeSpec;
+use crate::memory::{self, StrError};
 use crate::task;
 use crate::task::manager;
 use crate::task::processor;
 use crate::task::TaskControlBlock;
+use crate::task::TaskUserResource;
+use crate::task::{TaskName, TASK_NAME_LEN};
 use crate::timer;
 use crate::timer::TimerCondVar;
 use crate::trap::trap_handler;
@@ -18,10 +24,63 @@ pub fn sys_yield() -> isize {
 
 pub fn sys_sleep(ms: usize) -> isize {
     let expire_ms = timer::get_time_ms() + ms;
+    let Some(remaining) = sleep_until(expire_ms) else {
+        return 0;
+    };
+
+    // 被信号提前唤醒，重新执行时只需睡完剩余的时间
+    *processor::current_trap_ctx().arg_mut(0) = remaining;
+    -ERESTARTSYS
+}
+
+/// 睡眠`req`长的时间，精度为毫秒，不足一毫秒的部分向上取整。
+///
+/// 结果
+/// * -EINVAL => 纳秒部分不小于10^9
+/// * -EINTR => 被信号提前唤醒，同Linux，不论信号的处置是否带有`SA_RESTART`都不重新执行；
+///   `rem`非空时写入未睡完的时间，供调用者接着睡
+pub fn sys_nanosleep(req: *const TimeSpec, rem: Option<*mut TimeSpec>) -> isize {
+    let Some(req) = memory::read_any(req) else {
+        return -EFAULT;
+    };
+    if req.nsec >= NSEC_PER_SEC {
+        return -EINVAL;
+    }
+
+    let ms = req
+        .sec
+        .saturating_mul(1000)
+        .saturating_add(req.nsec.div_ceil(NSEC_PER_MS));
+    let expire_ms = timer::get_time_ms().saturating_add(ms);
+    let Some(remaining) = sleep_until(expire_ms) else {
+        return 0;
+    };
+
+    if let Some(rem) = rem {
+        let rem_now = TimeSpec {
+            sec: remaining / 1000,
+            nsec: remaining % 1000 * NSEC_PER_MS,
+        };
+        if !memory::write_any(rem, rem_now) {
+            return -EFAULT;
+        }
+    }
+    // 打断睡眠的信号就此递送，否则下次睡眠也会被立即打断
+    task::restart_current_syscall();
+    -EINTR
+}
+
+const NSEC_PER_SEC: usize = 1_000_000_000;
+const NSEC_PER_MS: usize = 1_000_000;
+
+/// 睡眠至开机后`expire_ms`毫秒，被信号提前唤醒时返回剩余的毫秒数
+fn sleep_until(expire_ms: usize) -> Option<usize> {
     let task = processor::current_task().unwrap();
     timer::add_timer(TimerCondVar::new(expire_ms, task));
     task::block_current_and_run_next();
-    0
+
+    let now = timer::get_time_ms();
+    (now < expire_ms && task::current_signal_pending()).then(|| expire_ms - now)
 }
 
 pub fn sys_exit(exit_code: i32) -> ! {
@@ -32,11 +91,28 @@ pub fn sys_exit(exit_code: i32) -> ! {
 pub fn sys_spawn_thread(entry: usize, arg: usize) -> isize {
     let task = processor::current_task().unwrap();
     let process = task.process.upgrade().unwrap();
+    if !process
+        .inner()
+        .exclusive_access()
+        .address_space
+        .fits(TaskUserResource::FRAMES)
+    {
+        return -1;
+    }
+
     let new_task = Arc::new(TaskControlBlock::new(
         &process,
         task.inner().exclusive_access().resource.user_stack_base,
         false,
     ));
+    // 新线程继承创建者的亲和性与名称
+    let (affinity, name) = task
+        .inner()
+        .exclusive_session(|task| (task.affinity, task.name));
+    new_task.inner().exclusive_session(|new_task| {
+        new_task.affinity = affinity;
+        new_task.name = name;
+    });
 
     manager::add_task(new_task.clone());
     process
@@ -55,36 +131,145 @@ pub fn sys_spawn_thread(entry: usize, arg: usize) -> isize {
     );
 
     new_task_trap_ctx.set_syscall_result(arg);
+    // 主线程的`tp`为0，即其TID
+    new_task_trap_ctx.set_tp(new_task_inner.resource.tid);
     new_task_inner.resource.tid as isize
 }
 
 pub fn sys_gettid() -> isize {
-    processor::current_task()
-        .unwrap()
-        .inner()
-        .exclusive_access()
-        .resource
-        .tid as isize
+    processor::current_tid() as isize
 }
 
+/// 等待线程`tid`退出并回收之
+///
+/// 结果
+/// * -1 => 线程不存在、已分离或为调用者自身
+/// * -2 => 线程尚未退出
 pub fn sys_waittid(tid: usize) -> isize {
-    let task = processor::current_task().unwrap();
-    let process = task.process.upgrade().unwrap();
-    let task = task.inner().exclusive_access();
+    let process = processor::current_process();
     let mut process = process.inner().exclusive_access();
 
     // a thread cannot wait for itself
-    if task.resource.tid == tid {
+    if processor::current_tid() == tid {
         return -1;
     }
 
-    let Some(waited_task) = &process.tasks[tid] else {
+    let Some(waited_task) = process.tasks.try_get(tid) else {
         return -1;
     };
-    let Some(exit_code) = waited_task.inner().exclusive_access().exit_code else {
+    let (exit_code, detached) = waited_task
+        .inner()
+        .exclusive_session(|task| (task.exit_code, task.detached));
+    if detached {
+        return -1;
+    }
+    let Some(exit_code) = exit_code else {
         return -2;
     };
     // 资源已经在`task::exit_current_and_run_next`里面释放了
-    process.tasks.remove(tid);
+    process.reap_task(tid);
     exit_code as isize
 }
+
+/// 分离线程`tid`，它退出时即被回收，不可再等待。已退出的线程立即回收
+///
+/// 结果
+/// * -ESRCH => 线程不存在
+/// * -EINVAL => 线程已分离或为主线程
+pub fn sys_thread_detach(tid: usize) -> isize {
+    let process = processor::current_process();
+    let mut process = process.inner().exclusive_access();
+
+    let Some(task) = process.tasks.try_get(tid) else {
+        return -ESRCH;
+    };
+    let exited = task.inner().exclusive_session(|task| {
+        if tid == 0 || task.detached {
+            return None;
+        }
+        task.detached = true;
+        Some(task.exit_code.is_some())
+    });
+    match exited {
+        None => -EINVAL,
+        Some(true) => {
+            process.reap_task(tid);
+            0
+        }
+        Some(false) => 0,
+    }
+}
+
+/// 进程的线程数，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadCounts {
+    /// 仍在运行的线程
+    pub running: usize,
+    /// 已退出而未被等待回收的线程
+    pub exited: usize,
+}
+
+/// 将当前进程的线程数写入`counts`
+pub fn sys_thread_counts(counts: *mut ThreadCounts) -> isize {
+    let process = processor::current_process();
+    let counts_now = process.inner().exclusive_session(|process| {
+        let exited = process
+            .tasks
+            .iter()
+            .flatten()
+            .filter(|task| task.inner().exclusive_access().exit_code.is_some())
+            .count();
+        ThreadCounts {
+            running: process.thread_count() - exited,
+            exited,
+        }
+    });
+    if memory::write_any(counts, counts_now) {
+        0
+    } else {
+        -EFAULT
+    }
+}
+
+/// 设置当前线程的名称
+pub const PR_SET_NAME: usize = 15;
+/// 读取当前线程的名称
+pub const PR_GET_NAME: usize = 16;
+
+/// 将当前线程改名为`name`，超出[`TASK_NAME_LEN`]` - 1`字节的部分被截断。
+/// 主线程改名时进程名随之改变。
+///
+/// 结果
+/// * -EFAULT => `name`所在的页未映射或用户不可读
+/// * -EINVAL => `name`连同终止符超过[`PATH_MAX`]
+pub fn sys_prctl_set_name(name: *const u8) -> isize {
+    let name = match memory::read_cstr(name, PATH_MAX) {
+        Ok(name) => TaskName::new(name.as_bytes()),
+        Err(StrError::Fault) => return -EFAULT,
+        Err(StrError::TooLong) => return -EINVAL,
+    };
+    let task = processor::current_task().unwrap();
+    let tid = task.inner().exclusive_session(|task| {
+        task.name = name;
+        task.resource.tid
+    });
+    if tid == 0 {
+        processor::current()
+            .process()
+            .inner()
+            .exclusive_access()
+            .name = name;
+    }
+    0
+}
+
+/// 将当前线程以NUL结尾的名称写入`buf`
+pub fn sys_prctl_get_name(buf: *mut [u8; TASK_NAME_LEN]) -> isize {
+    let task = processor::current_task().unwrap();
+    let name = task.inner().exclusive_access().name;
+    if !memory::write_any(buf, *name.as_bytes()) {
+        return -EFAULT;
+    }
+    0
+}
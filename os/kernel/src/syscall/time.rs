@@ -1,5 +1,62 @@
+use super::errno::{EFAULT, EINVAL};
+use crate::memory;
 use crate::timer;
 
+/// 墙上时间，自UNIX纪元起算
+pub const CLOCK_REALTIME: usize = 0;
+/// 单调时间，自启动起算
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// 秒与微秒，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+/// 秒与纳秒，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
 pub fn sys_get_time() -> isize {
     timer::get_time_ms() as isize
 }
+
+/// 将自UNIX纪元以来的时间写入`tv`。内核不知道时区，没有`timezone`参数
+pub fn sys_gettimeofday(tv: *mut TimeVal) -> isize {
+    let us = timer::realtime_us();
+    let tv_now = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    if memory::write_any(tv, tv_now) {
+        0
+    } else {
+        -EFAULT
+    }
+}
+
+/// 将时钟`clock`的分辨率写入`res`，`res`为空时只检查时钟是否存在
+///
+/// # 结果
+///
+/// - `-EINVAL`：没有这个时钟
+pub fn sys_clock_getres(clock: usize, res: Option<*mut TimeSpec>) -> isize {
+    if !matches!(clock, CLOCK_REALTIME | CLOCK_MONOTONIC) {
+        return -EINVAL;
+    }
+    // 两个时钟都由`mtime`推算
+    let res_now = TimeSpec {
+        sec: 0,
+        nsec: timer::RESOLUTION_NS,
+    };
+    if res.is_some_and(|res| !memory::write_any(res, res_now)) {
+        return -EFAULT;
+    }
+    0
+}
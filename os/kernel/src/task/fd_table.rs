@@ -0,0 +1,130 @@
+//! 文件描述符表
+//!
+//! 每个进程的描述符不得超过其上限(`RLIMIT_NOFILE`)，
+//! 所有进程的描述符合计不得超过[`FILE_MAX`]，以免泄漏描述符的程序耗尽内核堆。
+
+use alloc::sync::Arc;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::collections::SlotVec;
+use crate::config::{FILE_MAX, NOFILE};
+use crate::fs::File;
+
+/// 所有进程打开的描述符总数
+static OPEN_FILES: AtomicUsize = AtomicUsize::new(0);
+
+type FileRef = Arc<dyn File + Send + Sync>;
+
+/// 无法再打开描述符的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdError {
+    /// 进程的描述符已达上限
+    ProcessLimit,
+    /// 全系统的描述符已达上限
+    SystemLimit,
+}
+
+#[derive(Debug)]
+pub struct FdTable {
+    slots: SlotVec<FileRef>,
+    /// 描述符须小于此值
+    limit: usize,
+}
+
+impl FdTable {
+    /// 以`files`依次占据描述符0、1、2……，上限取默认值
+    pub fn with_files(files: impl IntoIterator<Item = FileRef>) -> Self {
+        let slots = SlotVec::from_iter(files);
+        OPEN_FILES.fetch_add(slots.iter().flatten().count(), Ordering::Relaxed);
+        Self {
+            slots,
+            limit: NOFILE,
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// 调低上限不影响已打开的描述符
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// 将`file`置于最小的空闲描述符，返回该描述符
+    pub fn insert(&mut self, file: FileRef) -> Result<usize, FdError> {
+        let fd = self
+            .slots
+            .iter()
+            .position(Option::is_none)
+            .unwrap_or(self.slots.len());
+        if fd >= self.limit {
+            return Err(FdError::ProcessLimit);
+        }
+        reserve()?;
+        Ok(self.slots.insert(file))
+    }
+
+    /// 将`file`置于描述符`fd`，原有的文件被关闭
+    pub fn insert_kv(&mut self, fd: usize, file: FileRef) -> Result<(), FdError> {
+        if fd >= self.limit {
+            return Err(FdError::ProcessLimit);
+        }
+        if self.slots.try_get(fd).is_none() {
+            reserve()?;
+        }
+        self.slots.insert_kv(fd, file);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, fd: usize) -> Option<FileRef> {
+        let file = self.slots.remove(fd)?;
+        OPEN_FILES.fetch_sub(1, Ordering::Relaxed);
+        Some(file)
+    }
+
+    pub fn try_get(&self, fd: usize) -> Option<FileRef> {
+        self.slots.try_get(fd)
+    }
+
+    pub fn clear(&mut self) {
+        OPEN_FILES.fetch_sub(self.slots.iter().flatten().count(), Ordering::Relaxed);
+        self.slots.clear();
+    }
+}
+
+/// 为新的描述符计数，全系统已达上限时失败
+fn reserve() -> Result<(), FdError> {
+    OPEN_FILES
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+            (n < FILE_MAX).then_some(n + 1)
+        })
+        .map(|_| ())
+        .map_err(|_| FdError::SystemLimit)
+}
+
+impl Clone for FdTable {
+    /// fork复制的描述符照常计数，即使因此超出全系统的上限
+    fn clone(&self) -> Self {
+        OPEN_FILES.fetch_add(self.slots.iter().flatten().count(), Ordering::Relaxed);
+        Self {
+            slots: self.slots.clone(),
+            limit: self.limit,
+        }
+    }
+}
+
+impl Drop for FdTable {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl Deref for FdTable {
+    type Target = [Option<FileRef>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.slots
+    }
+}
@@ -0,0 +1,118 @@
+//! 调度组
+//!
+//! 进程可被划入调度组，同组进程共享一份CPU配额：
+//! 每个周期内，组内任务在用户态累计占用的时钟中断数超过配额后，
+//! 该组的任务在剩余周期里不再被调度，直到下一周期开始。
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use super::{manager, processor, TaskControlBlock};
+use crate::collections::SlotVec;
+use crate::sync::UpCell;
+
+/// 配额周期，以时钟中断计
+pub const PERIOD_TICKS: usize = 100;
+
+static GROUPS: UpCell<SlotVec<SchedGroup>> = UpCell::new(SlotVec::new());
+/// 当前周期已过去的时钟中断数
+static ELAPSED: UpCell<usize> = UpCell::new(0);
+
+#[derive(Debug, Default)]
+struct SchedGroup {
+    /// 每周期可占用的时钟中断数，即CPU份额的百分比
+    quota: usize,
+    /// 本周期已占用的时钟中断数
+    used: usize,
+    /// 超额而被暂停调度的任务
+    throttled: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl SchedGroup {
+    fn is_throttled(&self) -> bool {
+        self.used >= self.quota
+    }
+}
+
+/// 创建调度组，`share`为每周期可占用的CPU百分比，取值`1..=100`
+pub fn create(share: usize) -> Option<usize> {
+    (1..=100).contains(&share).then(|| {
+        GROUPS.exclusive_access().insert(SchedGroup {
+            quota: share * PERIOD_TICKS / 100,
+            ..Default::default()
+        })
+    })
+}
+
+/// 组是否存在
+pub fn exists(gid: usize) -> bool {
+    GROUPS.exclusive_access().get_mut(gid).is_some()
+}
+
+/// 为当前任务所在的组记一个时钟中断
+pub fn charge_current() {
+    let Some(gid) = processor::current_process()
+        .inner()
+        .exclusive_access()
+        .sched_group
+    else {
+        return;
+    };
+
+    if let Some(group) = GROUPS.exclusive_access().get_mut(gid) {
+        group.used += 1;
+    }
+}
+
+/// 推进周期，周期结束时清空用量并放回所有被暂停的任务
+pub fn tick() {
+    let mut elapsed = ELAPSED.exclusive_access();
+    *elapsed += 1;
+    if *elapsed < PERIOD_TICKS {
+        return;
+    }
+    *elapsed = 0;
+    drop(elapsed);
+
+    let mut released = VecDeque::new();
+    GROUPS.exclusive_session(|groups| {
+        for group in groups.iter_mut() {
+            group.used = 0;
+            released.append(&mut group.throttled);
+        }
+    });
+
+    for task in released {
+        manager::add_task(task);
+    }
+}
+
+/// 若任务所在的组已超额，则暂停调度该任务并返回`None`
+pub fn throttle(task: Arc<TaskControlBlock>) -> Option<Arc<TaskControlBlock>> {
+    let Some(gid) = task
+        .process
+        .upgrade()
+        .and_then(|process| process.inner().exclusive_access().sched_group)
+    else {
+        return Some(task);
+    };
+
+    let mut groups = GROUPS.exclusive_access();
+    match groups.get_mut(gid) {
+        Some(group) if group.is_throttled() => {
+            group.throttled.push_back(task);
+            None
+        }
+        _ => Some(task),
+    }
+}
+
+/// 将任务移出所有组的暂停队列
+pub fn remove(task: &Arc<TaskControlBlock>) {
+    let task = Arc::as_ptr(task);
+    GROUPS.exclusive_session(|groups| {
+        for group in groups.iter_mut() {
+            group.throttled.retain(|t| Arc::as_ptr(t) != task);
+        }
+    });
+}
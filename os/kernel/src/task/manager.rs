@@ -2,34 +2,124 @@
 
 use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
-
-use super::{ProcessControlBlock, TaskControlBlock, TaskStatus};
-use crate::sync::UpCell;
-use crate::timer;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use super::processor::{self, HART_COUNT};
+use super::{group, ProcessControlBlock, TaskControlBlock, TaskStatus};
+use crate::memory::address::PhysPageNum;
+use crate::memory::ksm;
+use crate::sync::{futex, UpCell};
+use crate::timer::{self, TICKS_PRE_SEC};
+
+/// 平均负载的定点数中小数部分的位数
+pub const FSHIFT: usize = 11;
+const FIXED_1: usize = 1 << FSHIFT;
+/// 更新平均负载的周期，以时钟中断计，同Linux为5秒
+const LOAD_FREQ: usize = 5 * TICKS_PRE_SEC;
+/// 每个周期1、5、15分钟平均负载的衰减系数，即`FIXED_1 / exp(5s / 1min)`等
+const EXP: [usize; 3] = [1884, 2014, 2037];
 
 static TASK_MANAGER: UpCell<TaskManager> = UpCell::new(TaskManager::new());
 static PID2TCB: UpCell<BTreeMap<usize, Arc<ProcessControlBlock>>> = UpCell::new(BTreeMap::new());
+static LOAD: UpCell<LoadAverage> = UpCell::new(LoadAverage {
+    ticks: 0,
+    runnable_sum: 0,
+    loads: [0; 3],
+});
+
+#[derive(Debug)]
+struct LoadAverage {
+    /// 本周期已过去的时钟中断数
+    ticks: usize,
+    /// 本周期每个时钟中断时可运行任务数之和
+    runnable_sum: usize,
+    /// 1、5、15分钟的平均负载
+    loads: [usize; 3],
+}
 
 pub fn add_task(task: Arc<TaskControlBlock>) {
-    TASK_MANAGER.exclusive_access().add(task);
+    let affinity = task.inner().exclusive_access().affinity;
+    TASK_MANAGER.exclusive_access().add(task, affinity);
 }
 
-#[inline]
+/// 为当前处理器核取出下一个可调度的任务，所在调度组已超额的任务会被暂停调度
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
-    TASK_MANAGER.exclusive_access().fetch()
+    loop {
+        let task = TASK_MANAGER
+            .exclusive_access()
+            .fetch(processor::hart_id())?;
+        if let Some(task) = group::throttle(task) {
+            break Some(task);
+        }
+    }
 }
 
 #[inline]
 pub fn remove_task(task: &Arc<TaskControlBlock>) {
     timer::remove_timer(task);
+    group::remove(task);
+    futex::remove(task);
     TASK_MANAGER.exclusive_access().remove(task);
 }
 
+/// 亲和性改变后，将就绪的任务移到允许的处理器核的队列中
+pub fn migrate_task(task: &Arc<TaskControlBlock>) {
+    let affinity = task.inner().exclusive_access().affinity;
+    let mut manager = TASK_MANAGER.exclusive_access();
+    if manager.remove(task) {
+        manager.add(task.clone(), affinity);
+    }
+}
+
 pub fn wakeup_task(task: Arc<TaskControlBlock>) {
     task.inner().exclusive_access().status = TaskStatus::Ready;
     add_task(task);
 }
 
+/// 每个时钟中断调用一次，累计就绪与正在运行的任务数，每个周期更新一次平均负载
+pub fn tick() {
+    let running = processor::current_task().is_some() as usize;
+    let runnable = TASK_MANAGER.exclusive_access().len() + running;
+
+    let mut load = LOAD.exclusive_access();
+    load.runnable_sum += runnable;
+    load.ticks += 1;
+    if load.ticks < LOAD_FREQ {
+        return;
+    }
+
+    // 本周期可运行任务数的均值，与平均负载同为定点数
+    let active = load.runnable_sum * FIXED_1 / LOAD_FREQ;
+    load.ticks = 0;
+    load.runnable_sum = 0;
+    for (avg, exp) in load.loads.iter_mut().zip(EXP) {
+        *avg = calc_load(*avg, exp, active);
+    }
+}
+
+/// 1、5、15分钟的平均负载，为小数部分占[`FSHIFT`]位的定点数
+pub fn load_average() -> [usize; 3] {
+    LOAD.exclusive_access().loads
+}
+
+/// 指数加权移动平均，负载上升时向上取整，使其能升至整数值
+fn calc_load(load: usize, exp: usize, active: usize) -> usize {
+    let mut new_load = load * exp + active * (FIXED_1 - exp);
+    if active >= load {
+        new_load += FIXED_1 - 1;
+    }
+    new_load / FIXED_1
+}
+
+pub fn process_count() -> usize {
+    PID2TCB.exclusive_access().len()
+}
+
+pub fn processes() -> Vec<Arc<ProcessControlBlock>> {
+    PID2TCB.exclusive_access().values().cloned().collect()
+}
+
 pub fn get_process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
     PID2TCB.exclusive_access().get(&pid).cloned()
 }
@@ -44,37 +134,97 @@ pub fn remove_process(pid: usize) {
     }
 }
 
-/// FIFO 预备进程调度器
-#[derive(Default)]
+/// 压缩钩子：迁移所有进程中落在`window`内的可迁移用户页
+pub fn compact_user_frames(window: &Range<PhysPageNum>) -> usize {
+    let processes: Vec<_> = PID2TCB.exclusive_access().values().cloned().collect();
+    processes
+        .iter()
+        .map(|process| {
+            process
+                .inner()
+                .exclusive_access()
+                .address_space
+                .migrate(window)
+        })
+        .sum()
+}
+
+/// 合并所有进程中内容相同的只读用户页
+pub fn merge_user_pages() -> usize {
+    let processes: Vec<_> = PID2TCB.exclusive_access().values().cloned().collect();
+    let mut inners: Vec<_> = processes
+        .iter()
+        .map(|process| process.inner().exclusive_access())
+        .collect();
+    let mut spaces: Vec<_> = inners
+        .iter_mut()
+        .map(|inner| &mut inner.address_space)
+        .collect();
+    ksm::merge(&mut spaces)
+}
+
+/// 每个处理器核一条FIFO就绪队列。
+///
+/// 任务优先放入当前核的队列，当前核不在其亲和性掩码内时放入允许的核中最空闲的一条；
+/// 本核队列为空时从最长的队列尾部窃取允许在本核运行的任务。
+/// 内核尚不支持多核，所有队列共用一把[`UpCell`]，多核时可换作每条队列各自的锁。
 struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    queues: [VecDeque<Arc<TaskControlBlock>>; HART_COUNT],
 }
 
 impl TaskManager {
     const fn new() -> Self {
         Self {
-            ready_queue: VecDeque::new(),
+            queues: [const { VecDeque::new() }; HART_COUNT],
         }
     }
 
-    fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
+    fn len(&self) -> usize {
+        self.queues.iter().map(VecDeque::len).sum()
     }
 
-    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
+    fn add(&mut self, task: Arc<TaskControlBlock>, affinity: usize) {
+        let current = processor::hart_id();
+        let hart = if affinity & (1 << current) != 0 {
+            current
+        } else {
+            (0..HART_COUNT)
+                .filter(|&hart| affinity & (1 << hart) != 0)
+                .min_by_key(|&hart| self.queues[hart].len())
+                .expect("task affinity allows no hart")
+        };
+        self.queues[hart].push_back(task);
     }
 
-    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
-        let task = Arc::as_ptr(task);
+    fn fetch(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>> {
+        if let Some(task) = self.queues[hart].pop_front() {
+            return Some(task);
+        }
+        self.steal(hart)
+    }
 
-        if let Some((id, _)) = self
-            .ready_queue
+    /// 从其它核最长的就绪队列尾部窃取一个允许在`hart`上运行的任务
+    fn steal(&mut self, hart: usize) -> Option<Arc<TaskControlBlock>> {
+        let victim = (0..HART_COUNT)
+            .filter(|&other| other != hart)
+            .max_by_key(|&other| self.queues[other].len())?;
+        let queue = &mut self.queues[victim];
+        let index = queue
             .iter()
-            .enumerate()
-            .find(|(_, t)| task == Arc::as_ptr(t))
-        {
-            self.ready_queue.remove(id);
+            .rposition(|task| task.inner().exclusive_access().affinity & (1 << hart) != 0)?;
+        queue.remove(index)
+    }
+
+    /// 返回任务是否在就绪队列中
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        let task = Arc::as_ptr(task);
+
+        for queue in &mut self.queues {
+            if let Some(index) = queue.iter().position(|t| task == Arc::as_ptr(t)) {
+                queue.remove(index);
+                return true;
+            }
         }
+        false
     }
 }
@@ -1,10 +1,15 @@
 //! 任务相关的结构体
 
 mod context;
+mod fd_table;
+pub mod group;
 mod id;
 pub mod manager;
+mod name;
+pub mod perf;
 mod process;
 pub mod processor;
+pub mod ptrace;
 pub mod signal;
 pub mod switch;
 #[allow(clippy::module_inception)]
@@ -12,35 +17,49 @@ mod task;
 
 pub use self::{
     context::TaskContext,
+    fd_table::{FdError, FdTable},
     id::RecycleAllocator,
-    process::ProcessControlBlock,
+    name::{TaskName, TASK_NAME_LEN},
+    process::{ProcessControlBlock, ROOT_UID},
     processor::run,
     switch::__switch,
-    task::{TaskControlBlock, TaskStatus},
+    task::{TaskControlBlock, TaskStatus, TaskUserResource},
 };
 
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
 use core::mem;
 
 use enumflags2::BitFlags;
 use spin::Lazy;
 
 use self::signal::SignalFlag;
-use crate::fs::open;
+use crate::drivers;
+use crate::fs::stdio;
+use crate::fs::File;
 use crate::fs::OpenFlag;
+use crate::fs::{self, open};
+use crate::memory::image::Image;
 use crate::sbi::shutdown;
+use crate::timer;
 
 const IDLE_PID: usize = 0;
+/// 栈溢出的线程的退出码，与SIGSEGV一致
+const STACK_OVERFLOW_EXIT_CODE: i32 = -11;
+
+const INITPROC_PATH: &str = "/usr/bin/initproc";
 
 static INITPROC: Lazy<Arc<ProcessControlBlock>> = Lazy::new(|| {
-    ProcessControlBlock::new(
-        &open(
-            "/usr/bin/initproc",
+    let initproc = ProcessControlBlock::new(&Image::new(
+        open(
+            INITPROC_PATH,
             BitFlags::from_bits_truncate(OpenFlag::RDONLY),
         )
         .unwrap()
         .read_all(),
-    )
+    ));
+    initproc.rename(TaskName::from_path(INITPROC_PATH));
+    initproc
 });
 
 pub fn add_initproc() {
@@ -73,20 +92,33 @@ pub fn block_current_and_run_next() {
 
 pub fn exit_current_and_run_next(exit_code: i32) {
     let task = processor::take_current_task().unwrap();
-    let tid = task.inner().exclusive_session(|inner| {
+    let process = task.process.upgrade().unwrap();
+    let (tid, exit_code, detached) = task.inner().exclusive_session(|inner| {
+        let tid = inner.resource.tid;
+        let exit_code = if inner.resource.stack_overflowed() {
+            log::error!(
+                "[kernel] Stack overflow in pid={} tid={tid}, stack canary corrupted",
+                process.pid()
+            );
+            STACK_OVERFLOW_EXIT_CODE
+        } else {
+            exit_code
+        };
         inner.exit_code = Some(exit_code);
         inner.resource.dealloc();
-        inner.resource.tid
+        (tid, exit_code, inner.detached)
     });
-    let process = task.process.upgrade().unwrap();
     drop(task);
 
     if tid == 0 {
         /* 退出主线程，即退出进程 */
         let pid = process.pid();
+        stdio::flush(pid);
         if pid == IDLE_PID {
             /* 如果是 idle 控制流退出，说明要关机了 */
             log::info!("[kernel] Idle process exit with exit_code={exit_code}");
+            fs::unmount_all();
+            drivers::remove_all();
             shutdown(exit_code != 0);
         }
 
@@ -95,16 +127,31 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         process_inner.is_zombie = true;
         process_inner.exit_code = exit_code;
 
-        INITPROC.inner().exclusive_session(|initproc| {
+        let orphaned_zombie = INITPROC.inner().exclusive_session(|initproc| {
+            let mut zombie = false;
             for child in &process_inner.children {
-                child.inner().exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+                ptrace::detach(child);
+                child.inner().exclusive_session(|child| {
+                    child.parent = Some(Arc::downgrade(&INITPROC));
+                    zombie |= child.is_zombie;
+                });
                 initproc.children.push(child.clone());
             }
+            zombie
         });
 
+        let parent = process_inner.parent.as_ref().and_then(Weak::upgrade);
         let tasks = mem::take(&mut process_inner.tasks);
         drop(process_inner);
 
+        // 通知父进程回收，托付给initproc的孤儿中已有结束的，也通知initproc
+        if let Some(parent) = parent {
+            send_signal(&parent, SignalFlag::SIGCHLD.into());
+        }
+        if orphaned_zombie {
+            send_signal(&INITPROC, SignalFlag::SIGCHLD.into());
+        }
+
         for task in tasks.iter().filter_map(Option::as_ref) {
             let task_inner = task.inner().exclusive_access();
             manager::remove_task(task);
@@ -115,6 +162,13 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         }
 
         process.inner().exclusive_access().die();
+    } else {
+        release_sync_objects(&process, tid);
+        if detached {
+            // 无人等待，立即回收，但内核栈仍在使用，须切换走后再释放
+            let task = process.inner().exclusive_access().reap_task(tid).unwrap();
+            processor::retire(task);
+        }
     }
 
     drop(process);
@@ -122,15 +176,125 @@ pub fn exit_current_and_run_next(exit_code: i32) {
     processor::schedule(&raw mut tmp_task_ctx);
 }
 
+/// 线程退出时释放其持有的锁与健壮的信号量，同进程的其它线程不会因此永远等待。
+/// 主线程退出时整个进程随之结束，毋须释放
+fn release_sync_objects(process: &ProcessControlBlock, tid: usize) {
+    // 释放时会唤醒等待者，先复制出来，不再借用进程
+    let (mutexes, semaphores) = process.inner().exclusive_session(|process| {
+        (
+            process
+                .mutex_list
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>(),
+            process
+                .semaphore_list
+                .iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>(),
+        )
+    });
+    for mutex in mutexes {
+        mutex.release_dead(tid);
+    }
+    for semaphore in semaphores {
+        semaphore.release_dead(tid);
+    }
+}
+
+/// 向进程发送信号，会打断阻塞的信号同时唤醒其睡眠中的线程，
+/// 在`sigtimedwait`中等待该信号的线程也被唤醒。
+/// 信号已在等待递送时返回假。
+pub fn send_signal(process: &ProcessControlBlock, signal: BitFlags<SignalFlag>) -> bool {
+    let mut inner = process.inner().exclusive_access();
+    if inner.signals.contains(signal) {
+        return false;
+    }
+    inner.signals.insert(signal);
+
+    // 唤醒睡眠中的线程，令其放弃等待
+    let interrupting = !signal::interrupting(signal).is_empty();
+    for task in inner.tasks.iter().flatten() {
+        let awaited = task.inner().exclusive_access().sigwait.intersects(signal);
+        if (interrupting || awaited) && timer::remove_timer(task) {
+            manager::wakeup_task(task.clone());
+        }
+    }
+    true
+}
+
+/// 空闲进程与当前进程以外的所有进程
+pub fn other_processes() -> Vec<Arc<ProcessControlBlock>> {
+    let current = processor::current().process().pid();
+    manager::processes()
+        .into_iter()
+        .filter(|process| ![IDLE_PID, current].contains(&process.pid()))
+        .collect()
+}
+
+/// 取走当前进程收到的、属于`set`的编号最小的信号，视为已递送
+pub fn take_current_signal(set: BitFlags<SignalFlag>) -> Option<SignalFlag> {
+    processor::current()
+        .process()
+        .inner()
+        .exclusive_session(|inner| {
+            let signal = (inner.signals & set).iter().next()?;
+            inner.signals.remove(signal);
+            Some(signal)
+        })
+}
+
 pub fn send_signal_to_current(signal: SignalFlag) {
-    processor::current_process()
+    processor::current()
+        .process()
         .inner()
         .exclusive_access()
         .signals |= signal;
 }
 
+/// 当前进程是否收到了会打断阻塞的系统调用的信号
+pub fn current_signal_pending() -> bool {
+    let signals = processor::current()
+        .process()
+        .inner()
+        .exclusive_access()
+        .signals;
+    !signal::interrupting(signals).is_empty()
+}
+
+/// 阻塞在系统调用中的当前任务因信号而放弃等待，系统调用返回时将重新执行或返回`-EINTR`
+pub fn interrupt_current_syscall() {
+    processor::current()
+        .task()
+        .inner()
+        .exclusive_access()
+        .syscall_interrupted = true;
+}
+
+/// 取出并清除当前任务的系统调用被打断的标记
+pub fn take_current_syscall_interrupted() -> bool {
+    mem::take(
+        &mut processor::current()
+            .task()
+            .inner()
+            .exclusive_access()
+            .syscall_interrupted,
+    )
+}
+
+/// 递送打断当前系统调用的信号，返回系统调用是否应重新执行
+pub fn restart_current_syscall() -> bool {
+    processor::current()
+        .process()
+        .inner()
+        .exclusive_session(|inner| signal::take_interrupting(&mut inner.signals, &inner.sigactions))
+}
+
 pub fn check_current_signal_error() -> Option<(i32, &'static str)> {
-    let signals = processor::current_process()
+    let signals = processor::current()
+        .process()
         .inner()
         .exclusive_access()
         .signals;
@@ -0,0 +1,57 @@
+//! 任务名，供诊断输出辨认进程与线程
+
+use core::fmt;
+use core::str;
+
+/// 任务名的最大字节数，同Linux含末尾的NUL
+pub const TASK_NAME_LEN: usize = 16;
+
+/// 定长的任务名，以NUL结尾，超长的名称被截断
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaskName([u8; TASK_NAME_LEN]);
+
+impl TaskName {
+    /// 取`name`中首个NUL之前、至多[`TASK_NAME_LEN`]` - 1`个字节
+    pub fn new(name: &[u8]) -> Self {
+        let len = name
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(name.len())
+            .min(TASK_NAME_LEN - 1);
+        let mut bytes = [0; TASK_NAME_LEN];
+        bytes[..len].copy_from_slice(&name[..len]);
+        Self(bytes)
+    }
+
+    /// 以程序路径的最后一段为名
+    pub fn from_path(path: &str) -> Self {
+        let basename = path.rsplit('/').next().unwrap_or(path);
+        Self::new(basename.as_bytes())
+    }
+
+    /// 含末尾NUL的原始字节
+    pub fn as_bytes(&self) -> &[u8; TASK_NAME_LEN] {
+        &self.0
+    }
+
+    /// 截断可能切开多字节字符，只取其中合法的UTF-8前缀
+    pub fn as_str(&self) -> &str {
+        let len = self.0.iter().position(|&b| b == 0).unwrap_or(TASK_NAME_LEN);
+        match str::from_utf8(&self.0[..len]) {
+            Ok(name) => name,
+            Err(e) => str::from_utf8(&self.0[..e.valid_up_to()]).unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for TaskName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for TaskName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
@@ -0,0 +1,109 @@
+//! # 采样剖析
+//!
+//! 开启后每隔`period`个时钟中断，记下被打断的PC及当前进程号，
+//! 存入环形缓冲区，满时丢弃最旧的样本。PC在系统调用中被打断时为内核地址。
+//! 内核不为样本解析符号：用户PC由用户对照程序的ELF符号表汇总，
+//! 内核PC可经[`ksyms`](crate::ksyms)查询。
+
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+
+use super::processor;
+use crate::sync::UpCell;
+
+/// 环形缓冲区可容纳的样本数
+const CAPACITY: usize = 4096;
+
+static SAMPLER: UpCell<Sampler> = UpCell::new(Sampler {
+    period: 0,
+    countdown: 0,
+    pid: None,
+    samples: VecDeque::new(),
+});
+
+/// 一次采样，与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub pid: usize,
+    /// 被打断的指令地址，用户地址或内核地址
+    pub pc: usize,
+}
+
+struct Sampler {
+    /// 采样间隔的时钟中断数，0表示未开启
+    period: usize,
+    /// 距下次采样的时钟中断数
+    countdown: usize,
+    /// 只采样该进程，`None`表示所有进程
+    pid: Option<usize>,
+    samples: VecDeque<Sample>,
+}
+
+impl Sampler {
+    /// 计一次时钟中断，返回是否该采样了
+    fn due(&mut self) -> bool {
+        if self.period == 0 {
+            return false;
+        }
+        self.countdown -= 1;
+        if self.countdown != 0 {
+            return false;
+        }
+        self.countdown = self.period;
+        true
+    }
+
+    fn push(&mut self, sample: Sample) {
+        if self.pid.is_some_and(|target| target != sample.pid) {
+            return;
+        }
+        if self.samples.len() == CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+}
+
+/// 清空缓冲区，每隔`period`个时钟中断采样一次；
+/// `period`为0时停止采样，已有的样本留待读取
+pub fn start(period: usize, pid: Option<usize>) {
+    let mut sampler = SAMPLER.exclusive_access();
+    sampler.period = period;
+    sampler.countdown = period;
+    sampler.pid = pid;
+    if period != 0 {
+        sampler.samples.clear();
+    }
+}
+
+/// 由来自用户态的时钟中断调用，须在切换任务之前
+pub fn tick() {
+    let mut sampler = SAMPLER.exclusive_access();
+    if !sampler.due() {
+        return;
+    }
+    let pid = processor::current().process().pid();
+    sampler.push(Sample {
+        pid,
+        pc: processor::current_trap_ctx().pc(),
+    });
+}
+
+/// 由来自内核态的时钟中断调用，`pc`为被打断的内核指令地址，idle时不采样
+pub fn tick_kernel(pc: usize) {
+    let mut sampler = SAMPLER.exclusive_access();
+    if !sampler.due() {
+        return;
+    }
+    if let Some(pid) = processor::current_pid() {
+        sampler.push(Sample { pid, pc });
+    }
+}
+
+/// 按时间先后取出至多`len`个样本
+pub fn drain(len: usize) -> Vec<Sample> {
+    let mut sampler = SAMPLER.exclusive_access();
+    let len = len.min(sampler.samples.len());
+    sampler.samples.drain(..len).collect()
+}
@@ -5,17 +5,25 @@ use core::mem;
 
 use enumflags2::BitFlags;
 
+use super::fd_table::FdTable;
 use super::manager;
-use super::signal::SignalFlag;
+use super::ptrace::Tracee;
+use super::signal::{self, SignalAction, SignalFlag};
 use super::RecycleAllocator;
 use super::TaskControlBlock;
+use super::TaskName;
+use super::TaskUserResource;
 use crate::collections::SlotVec;
 use crate::fs::stdio::{Stdin, Stdout};
 use crate::fs::File;
+use crate::memory::image::Image;
 use crate::memory::{self, AddressSpace, KERNEL_SPACE};
 use crate::sync::{Condvar, Mutex, Semaphore, UpCell};
 use crate::trap::{trap_handler, TrapContext};
 
+/// 超级用户的ID
+pub const ROOT_UID: u32 = 0;
+
 static PID_ALLOCATOR: UpCell<RecycleAllocator> = UpCell::new(RecycleAllocator::new());
 
 #[derive(Debug)]
@@ -36,16 +44,26 @@ pub struct ProcessControlBlockInner {
     /// 子进程，当前进程结束时，它们将被移交给 initproc
     pub children: Vec<Arc<ProcessControlBlock>>,
     pub exit_code: i32,
+    /// 用户ID，子进程继承父进程的用户ID
+    pub uid: u32,
+    /// 所属的调度组，子进程继承父进程的调度组
+    pub sched_group: Option<usize>,
     /// **文件描述符表**
     // Option 表示文件描述符是否指示着文件
-    pub fd_table: SlotVec<Arc<dyn File + Send + Sync>>,
+    pub fd_table: FdTable,
     pub signals: BitFlags<SignalFlag>,
+    /// 各信号的处置，子进程继承父进程的处置
+    pub sigactions: [SignalAction; signal::COUNT],
+    /// 被追踪时的状态，见[`ptrace`](super::ptrace)
+    pub tracee: Option<Tracee>,
     pub tasks: SlotVec<Arc<TaskControlBlock>>,
     task_resource_allocator: RecycleAllocator,
     pub mutex_list: SlotVec<Arc<dyn Mutex>>,
     pub semaphore_list: SlotVec<Arc<Semaphore>>,
     pub condvar_list: SlotVec<Arc<Condvar>>,
     pub cwd: Arc<str>,
+    /// 进程名，即主线程的名称，exec时取程序路径的最后一段
+    pub name: TaskName,
 }
 
 impl ProcessControlBlock {
@@ -57,11 +75,30 @@ impl ProcessControlBlock {
         self.pid.0
     }
 
-    pub fn new(elf_data: &[u8]) -> Arc<Self> {
-        let (address_space, ustack_base, entry_point) = AddressSpace::new_user(elf_data);
-        let pid_handle = alloc_pid();
+    /// 将进程及其所有线程改名为`name`
+    pub fn rename(&self, name: TaskName) {
+        let mut inner = self.inner.exclusive_access();
+        inner.name = name;
+        for task in inner.tasks.iter().flatten() {
+            task.inner().exclusive_access().name = name;
+        }
+    }
+
+    pub fn new(image: &Image) -> Arc<Self> {
+        Self::with_fd_table(image, Self::stdio_fd_table())
+    }
+
+    /// 仅含标准输入、标准输出和标准错误的文件描述符表
+    fn stdio_fd_table() -> FdTable {
         let fds: [Arc<dyn File + Send + Sync>; 3] =
             [Arc::new(Stdin), Arc::new(Stdout), Arc::new(Stdout)];
+        FdTable::with_files(fds)
+    }
+
+    /// 以给定的文件描述符表创建进程
+    pub fn with_fd_table(image: &Image, fd_table: FdTable) -> Arc<Self> {
+        let (address_space, ustack_base, entry_point) = AddressSpace::new_user(image);
+        let pid_handle = alloc_pid();
 
         let process = Arc::new(Self {
             pid: pid_handle,
@@ -72,14 +109,19 @@ impl ProcessControlBlock {
                     parent: None,
                     children: Vec::new(),
                     exit_code: 0,
-                    fd_table: SlotVec::from_iter(fds),
+                    uid: ROOT_UID,
+                    sched_group: None,
+                    fd_table,
                     signals: BitFlags::empty(),
+                    sigactions: Default::default(),
+                    tracee: None,
                     tasks: SlotVec::new(),
                     task_resource_allocator: RecycleAllocator::default(),
                     mutex_list: SlotVec::new(),
                     semaphore_list: SlotVec::new(),
                     condvar_list: SlotVec::new(),
                     cwd: Arc::from("/"),
+                    name: TaskName::default(),
                 })
             },
         });
@@ -109,7 +151,7 @@ impl ProcessControlBlock {
 
     pub fn fork(self: &Arc<Self>) -> Arc<Self> {
         let mut parent_inner = self.inner().exclusive_access();
-        assert_eq!(parent_inner.tasks.len(), 1);
+        assert_eq!(parent_inner.thread_count(), 1);
 
         let child = Arc::new(Self {
             pid: alloc_pid(),
@@ -120,14 +162,19 @@ impl ProcessControlBlock {
                     parent: Some(Arc::downgrade(self)),
                     children: Vec::new(),
                     exit_code: 0,
+                    uid: parent_inner.uid,
+                    sched_group: parent_inner.sched_group,
                     fd_table: parent_inner.fd_table.clone(),
                     signals: BitFlags::empty(),
+                    sigactions: parent_inner.sigactions.clone(),
+                    tracee: None,
                     tasks: SlotVec::new(),
                     task_resource_allocator: RecycleAllocator::default(),
                     mutex_list: SlotVec::new(),
                     semaphore_list: SlotVec::new(),
                     condvar_list: SlotVec::new(),
                     cwd: parent_inner.cwd.clone(),
+                    name: parent_inner.name,
                 })
             },
         });
@@ -145,10 +192,31 @@ impl ProcessControlBlock {
             true,
         ));
         child.inner.exclusive_access().tasks.push(task.clone());
-        task.inner()
-            .exclusive_access()
-            .trap_ctx()
-            .set_kernel_sp(task.kernel_stack.top());
+        let trap_ctx = task.inner().exclusive_access().trap_ctx();
+        trap_ctx.set_kernel_sp(task.kernel_stack.top());
+        trap_ctx.forget_fp();
+        // Trap上下文随地址空间复制，其VS字段已记录向量寄存器保存过，须一并复制
+        #[cfg(feature = "vector")]
+        {
+            task.inner().exclusive_access().vector = parent_inner
+                .tasks
+                .get(0)
+                .inner()
+                .exclusive_access()
+                .vector
+                .clone();
+        }
+
+        // 子进程的线程继承亲和性与名称
+        let (affinity, name) = parent_inner
+            .tasks
+            .get(0)
+            .inner()
+            .exclusive_session(|parent_task| (parent_task.affinity, parent_task.name));
+        task.inner().exclusive_session(|task| {
+            task.affinity = affinity;
+            task.name = name;
+        });
 
         manager::insert_process(child.pid(), child.clone());
         manager::add_task(task);
@@ -156,12 +224,20 @@ impl ProcessControlBlock {
         child
     }
 
-    pub fn exec(self: &Arc<Self>, elf_data: &[u8], args: Vec<String>) {
-        assert_eq!(self.inner.exclusive_access().tasks.len(), 1);
+    /// 以新程序替换当前进程的地址空间，新程序超出物理页帧上限时返回`None`
+    pub fn exec(self: &Arc<Self>, image: &Image, args: Vec<String>) -> Option<()> {
+        assert_eq!(self.inner.exclusive_access().thread_count(), 1);
+
+        let (mut addr_space, ustack_base, entry_point) = AddressSpace::new_user(image);
+        let frame_limit = self.inner.exclusive_access().address_space.frame_limit();
+        addr_space.set_frame_limit(frame_limit);
+        if !addr_space.fits(TaskUserResource::FRAMES) {
+            return None;
+        }
 
-        let (addr_space, ustack_base, entry_point) = AddressSpace::new_user(elf_data);
         let token = addr_space.token();
         let mut process = self.inner.exclusive_access();
+        process.address_space.clear();
         process.address_space = addr_space;
         let task = process.tasks.get(0);
         // 待会 TaskResource::alloc 要访问当前进程
@@ -235,6 +311,8 @@ impl ProcessControlBlock {
         *trap_ctx.arg_mut(0) = argc;
         *trap_ctx.arg_mut(1) = argv_base;
         *task_inner.trap_ctx() = trap_ctx;
+
+        Some(())
     }
 }
 
@@ -261,6 +339,20 @@ impl ProcessControlBlockInner {
         self.task_resource_allocator.dealloc(tid);
     }
 
+    /// 线程数，含已退出而未回收的线程
+    pub fn thread_count(&self) -> usize {
+        self.tasks.iter().flatten().count()
+    }
+
+    /// 回收已退出的线程`tid`：移出线程表并归还其线程ID，此后该ID可分配给新线程
+    pub fn reap_task(&mut self, tid: usize) -> Option<Arc<TaskControlBlock>> {
+        let task = self.tasks.try_get(tid)?;
+        assert!(task.inner().exclusive_access().exit_code.is_some());
+        self.tasks.remove(tid);
+        self.dealloc_tid(tid);
+        Some(task)
+    }
+
     pub fn insert_task(&mut self, task: Arc<TaskControlBlock>) {
         let tid = task.inner().exclusive_access().resource.tid;
         self.tasks.insert_kv(tid, task);
@@ -2,13 +2,14 @@
 
 use alloc::sync::Arc;
 
-use super::ProcessControlBlock;
 use super::__switch;
 use super::manager;
+use super::ProcessControlBlock;
 use super::TaskContext;
 use super::TaskControlBlock;
+use super::TaskName;
 use super::TaskStatus;
-use crate::sync::UpCell;
+use crate::sync::{UpCell, UpRef};
 use crate::trap::TrapContext;
 
 static PROCESSOR: UpCell<Processor> = UpCell::new(Processor::new());
@@ -16,6 +17,8 @@ static PROCESSOR: UpCell<Processor> = UpCell::new(Processor::new());
 #[derive(Default)]
 struct Processor {
     current: Option<Arc<TaskControlBlock>>,
+    /// 已被回收的任务，切换回idle控制流后再释放其内核栈
+    retired: Option<Arc<TaskControlBlock>>,
     idle_task_ctx: TaskContext,
 }
 
@@ -23,6 +26,7 @@ impl Processor {
     const fn new() -> Self {
         Self {
             current: None,
+            retired: None,
             idle_task_ctx: TaskContext::empty(),
         }
     }
@@ -36,12 +40,41 @@ impl Processor {
     }
 }
 
+/// 退出的任务仍运行在自己的内核栈上，交由idle控制流释放
+pub fn retire(task: Arc<TaskControlBlock>) {
+    PROCESSOR.exclusive_access().retired = Some(task);
+}
+
 pub fn current_process() -> Arc<ProcessControlBlock> {
     current_task().unwrap().process.upgrade().unwrap()
 }
 
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    PROCESSOR.shared_access().current()
+}
+
+/// 借用当前任务，不克隆[`Arc`]，idle时调用会panic。
+///
+/// 系统调用中只读的访问优先用此，免去引用计数的增减；
+/// 须持有当前任务或进程越过调度点时才用[`current_task`]与[`current_process`]
+pub fn current() -> Current<'static> {
+    Current(PROCESSOR.shared_access())
+}
+
+/// 共享借用处理器状态的守卫，存活期间屏蔽中断，也不能切换任务
+pub struct Current<'a>(UpRef<'a, Processor>);
+
+impl Current<'_> {
+    pub fn task(&self) -> &TaskControlBlock {
+        self.0.current.as_deref().unwrap()
+    }
+
+    pub fn process(&self) -> &ProcessControlBlock {
+        let process = self.task().process.as_ptr();
+        debug_assert!(self.task().process.strong_count() > 0);
+        // SAFETY: 守卫存活期间不能切换任务，当前进程无从退出，也就不会被回收
+        unsafe { &*process }
+    }
 }
 
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
@@ -55,26 +88,58 @@ pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
 ///
 /// [`ProcessControlBlock::user_token`]: crate::task::ProcessControlBlock::user_token
 pub fn current_user_token() -> usize {
-    current_process().inner().exclusive_access().user_token()
+    current().process().inner().exclusive_access().user_token()
 }
 
 pub fn current_trap_ctx() -> &'static mut TrapContext {
-    current_task()
-        .unwrap()
-        .inner()
-        .exclusive_access()
-        .trap_ctx()
+    current().task().inner().exclusive_access().trap_ctx()
 }
 
 pub fn current_trap_ctx_user_va() -> usize {
-    current_task()
-        .unwrap()
+    current()
+        .task()
         .inner()
         .exclusive_access()
         .resource
         .trap_ctx_user_va()
 }
 
+/// 当前线程在进程内的ID
+pub fn current_tid() -> usize {
+    current().task().inner().exclusive_access().resource.tid
+}
+
+/// 当前线程的名称及其进程号，供panic时输出。所需的状态正被独占借用时返回`None`
+pub fn current_name() -> Option<(TaskName, usize)> {
+    let processor = PROCESSOR.try_shared_access()?;
+    let task = processor.current.as_ref()?;
+    let name = task.inner().try_shared_access()?.name;
+    let pid = task.process.upgrade()?.pid();
+    Some((name, pid))
+}
+
+/// 当前进程的进程号，idle时或所需的状态正被独占借用时返回`None`
+pub fn current_pid() -> Option<usize> {
+    let processor = PROCESSOR.try_shared_access()?;
+    let pid = processor.current.as_ref()?.process.upgrade()?.pid();
+    Some(pid)
+}
+
+/// 处理器核的数量，亲和性掩码的第`i`位对应编号为`i`的核。
+///
+/// 内核尚不支持多核，恒为1
+pub const HART_COUNT: usize = 1;
+
+/// 所有处理器核的亲和性掩码，新任务的默认值
+pub const ALL_HARTS: usize = (1 << HART_COUNT) - 1;
+
+/// 当前处理器核的编号。
+///
+/// 内核尚不支持多核，恒为0
+pub const fn hart_id() -> usize {
+    0
+}
+
 /// 启动 idle 控制流
 pub fn run() {
     loop {
@@ -84,10 +149,11 @@ pub fn run() {
         if let Some(task) = manager::fetch_task() {
             let idle_task_ctx_ptr = &raw mut processor.idle_task_ctx;
 
-            let next_task_ctx_ptr = task.inner().exclusive_session(|task| {
+            let (next_task_ctx_ptr, name, tid) = task.inner().exclusive_session(|task| {
                 task.status = TaskStatus::Running;
-                &raw const task.ctx
+                (&raw const task.ctx, task.name, task.resource.tid)
             });
+            log::trace!("switch to {name} tid={tid}");
 
             processor.current = Some(task);
             drop(processor);
@@ -96,6 +162,8 @@ pub fn run() {
                 __switch(idle_task_ctx_ptr, next_task_ctx_ptr);
             }
             // 从 schedule 切换回来，继续循环
+            let retired = PROCESSOR.exclusive_access().retired.take();
+            drop(retired);
         }
     }
 }
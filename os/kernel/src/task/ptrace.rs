@@ -0,0 +1,298 @@
+//! 进程追踪(ptrace)的最小子集
+//!
+//! 子进程以`TRACEME`请父进程追踪自己，此后于以下时机停下，
+//! 等待追踪者以`CONT`或`SINGLESTEP`放行：
+//! - 首次`exec`完成时，停在新程序的入口；
+//! - 执行到`ebreak`时，`sepc`仍指向该指令；
+//! - 单步完成时，`sepc`指向下一条将执行的指令。
+//!
+//! S态没有可用的硬件单步，单步以软件断点实现：解码当前指令，
+//! 在其所有可能的后继处写入`c.ebreak`，停下时再恢复被覆盖的指令。
+//! 被追踪者须为单线程进程。
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem;
+
+use super::signal::SignalFlag;
+use super::{block_current_and_run_next, manager, processor, send_signal, ProcessControlBlock};
+use crate::memory::address::VirtAddr;
+use crate::memory::AddressSpace;
+
+/// `c.ebreak`的编码
+const C_EBREAK: u16 = 0x9002;
+
+/// 被追踪者的状态
+#[derive(Debug)]
+pub struct Tracee {
+    /// 追踪者的PID
+    pub tracer: usize,
+    /// 是否停着等待追踪者放行
+    pub stopped: bool,
+    /// 首次`exec`完成时停下
+    stop_on_exec: bool,
+    /// 单步时写入的断点及其覆盖的原指令
+    step_breakpoints: Vec<(usize, u16)>,
+}
+
+/// 与用户库的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UserRegs {
+    /// 通用寄存器，x0 ~ x31
+    pub x: [usize; 32],
+    pub pc: usize,
+}
+
+impl Tracee {
+    fn new(tracer: usize) -> Self {
+        Self {
+            tracer,
+            stopped: false,
+            stop_on_exec: true,
+            step_breakpoints: Vec::new(),
+        }
+    }
+
+    /// 恢复单步断点覆盖的指令
+    fn clear_step_breakpoints(&mut self, space: &mut AddressSpace) {
+        for (addr, inst) in self.step_breakpoints.drain(..) {
+            if let Some(pa) = space.debug_access(addr.into(), true) {
+                *pa.as_mut::<u16>() = inst;
+            }
+        }
+    }
+}
+
+/// 请父进程追踪当前进程。已被追踪或没有父进程时返回`None`。
+pub fn trace_me() -> Option<()> {
+    let process = processor::current_process();
+    let mut inner = process.inner().exclusive_access();
+    if inner.tracee.is_some() {
+        return None;
+    }
+
+    let tracer = inner.parent.as_ref()?.upgrade()?.pid();
+    inner.tracee = Some(Tracee::new(tracer));
+    Some(())
+}
+
+/// 当前进程是否正被追踪
+pub fn is_current_traced() -> bool {
+    processor::current()
+        .process()
+        .inner()
+        .exclusive_access()
+        .tracee
+        .is_some()
+}
+
+/// 停下当前进程并以`SIGCHLD`通知追踪者，直到追踪者放行
+pub fn stop_current() {
+    let tracer = processor::current_process()
+        .inner()
+        .exclusive_session(|inner| {
+            let tracee = inner.tracee.as_mut()?;
+            assert_eq!(inner.thread_count(), 1, "tracing a multi-threaded process");
+
+            tracee.clear_step_breakpoints(&mut inner.address_space);
+            tracee.stopped = true;
+            Some(tracee.tracer)
+        });
+
+    if let Some(tracer) = tracer {
+        if let Some(tracer) = manager::get_process(tracer) {
+            send_signal(&tracer, SignalFlag::SIGCHLD.into());
+        }
+        block_current_and_run_next();
+    }
+}
+
+/// 当前进程`exec`成功后调用：旧地址空间中的单步断点已随之消失；
+/// 若是`TRACEME`后的首次`exec`或正在单步，则停下。
+pub fn on_exec() {
+    let process = processor::current_process();
+    let should_stop = process
+        .inner()
+        .exclusive_session(|inner| match inner.tracee.as_mut() {
+            Some(tracee) => {
+                let stepping = !tracee.step_breakpoints.is_empty();
+                tracee.step_breakpoints.clear();
+                let first = tracee.stop_on_exec;
+                tracee.stop_on_exec = false;
+                stepping || first
+            }
+            None => false,
+        });
+    drop(process);
+
+    if should_stop {
+        stop_current();
+    }
+}
+
+/// 放行停下的被追踪者，`step`为真时只执行一条指令。
+/// 单步的后继指令不可访问时返回`None`，被追踪者保持停下。
+pub fn resume(process: &ProcessControlBlock, step: bool) -> Option<()> {
+    let mut inner = process.inner().exclusive_access();
+    let inner = &mut *inner;
+    let tracee = inner.tracee.as_mut()?;
+    let task = inner.tasks.get(0).clone();
+
+    if step {
+        let ctx = task.inner().exclusive_access().trap_ctx();
+        let (pc, regs) = (ctx.pc(), ctx.regs());
+        let space = &mut inner.address_space;
+
+        let low = read_u16(space, pc)?;
+        let inst = if low & 0b11 != 0b11 {
+            Inst::Compressed(low)
+        } else {
+            let high = read_u16(space, pc + 2)?;
+            Inst::Normal(u32::from(high) << 16 | u32::from(low))
+        };
+
+        for target in inst.successors(pc, regs).into_iter().flatten() {
+            if tracee
+                .step_breakpoints
+                .iter()
+                .any(|&(addr, _)| addr == target)
+            {
+                continue;
+            }
+            let Some(pa) = space.debug_access(target.into(), true) else {
+                tracee.clear_step_breakpoints(space);
+                return None;
+            };
+            let orig = mem::replace(pa.as_mut::<u16>(), C_EBREAK);
+            tracee.step_breakpoints.push((target, orig));
+        }
+    }
+
+    tracee.stopped = false;
+    manager::wakeup_task(task);
+    Some(())
+}
+
+/// 解除追踪，恢复单步断点，停着的进程随之继续运行。追踪者退出时对其子进程调用。
+pub fn detach(process: &Arc<ProcessControlBlock>) {
+    let mut inner = process.inner().exclusive_access();
+    let inner = &mut *inner;
+    let Some(mut tracee) = inner.tracee.take() else {
+        return;
+    };
+
+    tracee.clear_step_breakpoints(&mut inner.address_space);
+    if tracee.stopped {
+        manager::wakeup_task(inner.tasks.get(0).clone());
+    }
+}
+
+/// 读取被追踪者`addr`处的一个字，`addr`须按字对齐
+pub fn peek(space: &mut AddressSpace, addr: usize) -> Option<usize> {
+    let va = aligned_word(addr)?;
+    Some(*space.debug_access(va, false)?.as_ref::<usize>())
+}
+
+/// 改写被追踪者`addr`处的一个字，`addr`须按字对齐
+pub fn poke(space: &mut AddressSpace, addr: usize, data: usize) -> Option<()> {
+    let va = aligned_word(addr)?;
+    *space.debug_access(va, true)?.as_mut::<usize>() = data;
+    Some(())
+}
+
+/// 对齐的字不会跨页
+fn aligned_word(addr: usize) -> Option<VirtAddr> {
+    addr.is_multiple_of(mem::size_of::<usize>())
+        .then(|| VirtAddr::from(addr))
+}
+
+fn read_u16(space: &mut AddressSpace, addr: usize) -> Option<u16> {
+    Some(*space.debug_access(addr.into(), false)?.as_ref::<u16>())
+}
+
+/// 单步所需的指令解码，只区分会改变控制流的指令
+enum Inst {
+    Compressed(u16),
+    Normal(u32),
+}
+
+impl Inst {
+    /// 指令执行后可能到达的地址，条件分支有两个
+    fn successors(&self, pc: usize, x: &[usize; 32]) -> [Option<usize>; 2] {
+        match *self {
+            Self::Compressed(c) => {
+                let c = u32::from(c);
+                let (op, funct3) = (c & 0b11, c >> 13 & 0b111);
+                match (op, funct3) {
+                    // c.j
+                    (0b01, 0b101) => {
+                        let offset = bit(c, 12, 11)
+                            | bit(c, 11, 4)
+                            | bits(c, 9, 2, 8)
+                            | bit(c, 8, 10)
+                            | bit(c, 7, 6)
+                            | bit(c, 6, 7)
+                            | bits(c, 3, 3, 1)
+                            | bit(c, 2, 5);
+                        [Some(pc.wrapping_add_signed(sext(offset, 12))), None]
+                    }
+                    // c.beqz, c.bnez
+                    (0b01, 0b110 | 0b111) => {
+                        let offset = bit(c, 12, 8)
+                            | bits(c, 10, 2, 3)
+                            | bits(c, 5, 2, 6)
+                            | bits(c, 3, 2, 1)
+                            | bit(c, 2, 5);
+                        [Some(pc + 2), Some(pc.wrapping_add_signed(sext(offset, 9)))]
+                    }
+                    // c.jr, c.jalr
+                    (0b10, 0b100) if c >> 2 & 0x1f == 0 && c >> 7 & 0x1f != 0 => {
+                        [Some(x[(c >> 7 & 0x1f) as usize]), None]
+                    }
+                    _ => [Some(pc + 2), None],
+                }
+            }
+            Self::Normal(inst) => match inst & 0x7f {
+                // jal
+                0b110_1111 => {
+                    let offset = bit(inst, 31, 20)
+                        | bits(inst, 21, 10, 1)
+                        | bit(inst, 20, 11)
+                        | bits(inst, 12, 8, 12);
+                    [Some(pc.wrapping_add_signed(sext(offset, 21))), None]
+                }
+                // jalr
+                0b110_0111 => {
+                    let rs1 = (inst >> 15 & 0x1f) as usize;
+                    let offset = (inst as i32 >> 20) as isize;
+                    [Some(x[rs1].wrapping_add_signed(offset) & !1), None]
+                }
+                // beq, bne, blt, bge, bltu, bgeu
+                0b110_0011 => {
+                    let offset = bit(inst, 31, 12)
+                        | bits(inst, 25, 6, 5)
+                        | bits(inst, 8, 4, 1)
+                        | bit(inst, 7, 11);
+                    [Some(pc + 4), Some(pc.wrapping_add_signed(sext(offset, 13)))]
+                }
+                _ => [Some(pc + 4), None],
+            },
+        }
+    }
+}
+
+/// 取`value`的第`from`位，置于第`to`位
+fn bit(value: u32, from: u32, to: u32) -> u32 {
+    bits(value, from, 1, to)
+}
+
+/// 取`value`自第`from`位起的`len`位，置于第`to`位起
+fn bits(value: u32, from: u32, len: u32, to: u32) -> u32 {
+    (value >> from & ((1 << len) - 1)) << to
+}
+
+/// 将`width`位的立即数符号扩展
+fn sext(imm: u32, width: u32) -> isize {
+    ((imm << (32 - width)) as i32 >> (32 - width)) as isize
+}
@@ -1,15 +1,19 @@
 use enumflags2::{bitflags, BitFlags};
 
-/* pub const COUNT: usize = 32; */
+pub const COUNT: usize = 32;
+
+/// 被该信号打断的系统调用自动重新执行，而不是返回`-EINTR`
+pub const SA_RESTART: u32 = 0x1000_0000;
 
 #[repr(C, align(16))]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct SignalAction {
     pub(super) handler: usize,
     /// 例程执行期间屏蔽的信号，
     /// 若收到则记录在TCB中，例程运行结束后再行处理
     pub(super) mask: BitFlags<SignalFlag>,
     // 目前内核不支持嵌套信号处理，所以屏蔽与否效果都一样，哈哈哈
+    pub(super) flags: u32,
 }
 
 #[rustfmt::skip]
@@ -26,6 +30,7 @@ pub enum SignalFlag {
     SIGQUIT   = 1 << 3,
     /// 非法指令
     SIGILL    = 1 << 4,
+    /// 断点
     SIGTRAP   = 1 << 5,
     SIGABRT   = 1 << 6,
     SIGBUS    = 1 << 7,
@@ -66,10 +71,52 @@ impl Default for SignalAction {
         Self {
             handler: 0,
             mask: SignalFlag::SIGQUIT | SignalFlag::SIGTRAP,
+            flags: 0,
         }
     }
 }
 
+impl SignalFlag {
+    /// 信号的编号
+    pub fn signum(self) -> usize {
+        (self as u32).trailing_zeros() as usize
+    }
+}
+
+/// 默认忽略的信号，不会打断系统调用
+fn ignored() -> BitFlags<SignalFlag> {
+    SignalFlag::SIGCHLD | SignalFlag::SIGURG | SignalFlag::SIGWINCH | SignalFlag::SIGCONT
+}
+
+/// 令进程结束的信号，与[`check_error`]一致
+fn fatal() -> BitFlags<SignalFlag> {
+    SignalFlag::SIGINT
+        | SignalFlag::SIGILL
+        | SignalFlag::SIGTRAP
+        | SignalFlag::SIGABRT
+        | SignalFlag::SIGFPE
+        | SignalFlag::SIGKILL
+        | SignalFlag::SIGSEGV
+}
+
+/// 信号中会打断阻塞的系统调用的部分
+pub fn interrupting(signals: BitFlags<SignalFlag>) -> BitFlags<SignalFlag> {
+    signals & !ignored()
+}
+
+/// 取走打断系统调用的非致命信号，视为已递送。
+/// 它们的处置都带有[`SA_RESTART`]时返回真，系统调用应重新执行。
+pub(super) fn take_interrupting(
+    signals: &mut BitFlags<SignalFlag>,
+    actions: &[SignalAction; COUNT],
+) -> bool {
+    let taken = interrupting(*signals) & !fatal();
+    signals.remove(taken);
+    taken
+        .iter()
+        .all(|signal| actions[signal.signum()].flags & SA_RESTART != 0)
+}
+
 /// 检查因信号引发的进程错误，返回错误码及消息
 pub(super) fn check_error(signal: BitFlags<SignalFlag>) -> Option<(i32, &'static str)> {
     signal
@@ -80,6 +127,11 @@ pub(super) fn check_error(signal: BitFlags<SignalFlag>) -> Option<(i32, &'static
                 .contains(SignalFlag::SIGILL)
                 .then_some((-4, "Illegal Instruction, SIGILL=4"))
         })
+        .or_else(|| {
+            signal
+                .contains(SignalFlag::SIGTRAP)
+                .then_some((-5, "Trace/Breakpoint Trap, SIGTRAP=5"))
+        })
         .or_else(|| {
             signal
                 .contains(SignalFlag::SIGABRT)
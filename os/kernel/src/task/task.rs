@@ -1,9 +1,15 @@
 use alloc::sync::Arc;
 use alloc::sync::Weak;
 
+use enumflags2::BitFlags;
+
+use super::processor;
+use super::signal::SignalFlag;
 use super::ProcessControlBlock;
 use super::TaskContext;
+use super::TaskName;
 use crate::config::{PAGE_SIZE, TRAP_CONTEXT_BASE, USER_STACK_SIZE};
+use crate::memory;
 use crate::memory::address::PhysPageNum;
 use crate::memory::address::VirtAddr;
 use crate::memory::alloc_kernel_stack;
@@ -11,6 +17,8 @@ use crate::memory::KernelStack;
 use crate::memory::MapPermission;
 use crate::sync::UpCell;
 use crate::trap::TrapContext;
+#[cfg(feature = "vector")]
+use crate::trap::VectorState;
 
 #[derive(Debug)]
 pub struct TaskControlBlock {
@@ -28,6 +36,19 @@ pub struct TaskControlBlockInner {
     pub(super) ctx: TaskContext,
     pub(super) status: TaskStatus,
     pub exit_code: Option<i32>,
+    /// 分离的线程无须等待，退出时即被回收
+    pub detached: bool,
+    /// 正在执行的系统调用因信号而放弃了等待
+    pub syscall_interrupted: bool,
+    /// 在`sigtimedwait`中等待的信号，收到其一即被唤醒
+    pub sigwait: BitFlags<SignalFlag>,
+    /// 允许运行的处理器核的掩码，见[`processor::HART_COUNT`]
+    pub affinity: usize,
+    /// 线程名，新线程沿用进程名
+    pub name: TaskName,
+    /// 向量寄存器，首次保存时才分配
+    #[cfg(feature = "vector")]
+    pub vector: Option<VectorState>,
 }
 
 /// 线程资源：线程ID 与 用户栈
@@ -52,8 +73,11 @@ impl TaskControlBlock {
         user_stack_base: usize,
         is_forking: bool,
     ) -> Self {
+        let (tid, name) = process
+            .inner()
+            .exclusive_session(|process| (process.alloc_tid(), process.name));
         let resource = TaskUserResource {
-            tid: process.inner().exclusive_access().alloc_tid(),
+            tid,
             user_stack_base,
             process: Arc::downgrade(process),
         };
@@ -77,6 +101,13 @@ impl TaskControlBlock {
                     ctx: TaskContext::new(kstack_top),
                     status: TaskStatus::Ready,
                     exit_code: None,
+                    detached: false,
+                    syscall_interrupted: false,
+                    sigwait: BitFlags::empty(),
+                    affinity: processor::ALL_HARTS,
+                    name,
+                    #[cfg(feature = "vector")]
+                    vector: None,
                 })
             },
         }
@@ -93,7 +124,14 @@ impl TaskControlBlockInner {
     }
 }
 
+/// 写在用户栈底的金丝雀值，被改写即说明栈已溢出
+const STACK_CANARY: usize = 0x57ac_c0de_57ac_c0de;
+const CANARY_WORDS: usize = 4;
+
 impl TaskUserResource {
+    /// 每个任务占用的物理页帧数：用户栈与Trap上下文
+    pub const FRAMES: usize = USER_STACK_SIZE / PAGE_SIZE + 1;
+
     pub fn alloc(&self) {
         let process = self.process.upgrade().unwrap();
         let mut inner = process.inner().exclusive_access();
@@ -101,7 +139,7 @@ impl TaskUserResource {
         let (ustack_bottom, ustack_top) = user_stack_range(self.user_stack_base, self.tid);
         inner
             .address_space
-            .insert_framed(
+            .insert_framed_movable(
                 ustack_bottom.into(),
                 ustack_top.into(),
                 MapPermission::R | MapPermission::W | MapPermission::U,
@@ -117,14 +155,29 @@ impl TaskUserResource {
                 MapPermission::R | MapPermission::W,
             )
             .unwrap();
+
+        *memory::read_mut(inner.user_token(), self.canary_ptr()) = [STACK_CANARY; CANARY_WORDS];
     }
 
+    /// 用户栈底的金丝雀是否被改写，须在释放用户栈前调用
+    pub fn stack_overflowed(&self) -> bool {
+        let process = self.process.upgrade().unwrap();
+        let token = process.inner().exclusive_access().user_token();
+        memory::read_ref(token, self.canary_ptr())
+            .iter()
+            .any(|&word| word != STACK_CANARY)
+    }
+
+    fn canary_ptr(&self) -> *mut [usize; CANARY_WORDS] {
+        user_stack_range(self.user_stack_base, self.tid).0 as *mut _
+    }
+
+    /// 释放用户栈与Trap上下文。线程ID要等线程被回收时才归还，
+    /// 以免被新线程占去，令等待者取错退出码
     pub fn dealloc(&self) {
         let process = self.process.upgrade().unwrap();
         let mut process = process.inner().exclusive_access();
 
-        process.dealloc_tid(self.tid);
-
         let ustack_bottom: VirtAddr = user_stack_range(self.user_stack_base, self.tid).0.into();
         process.address_space.remove(ustack_bottom.into()).unwrap();
 
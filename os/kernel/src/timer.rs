@@ -10,15 +10,25 @@ use alloc::sync::Arc;
 use core::cmp::{Ordering, Reverse};
 
 use riscv::register::time;
+use spin::Lazy;
 
 use crate::config::CLOCK_FREQ;
+use crate::drivers::rtc;
 use crate::sbi::set_timer;
 use crate::sync::UpCell;
 use crate::task::{manager, TaskControlBlock};
 
-const TICKS_PRE_SEC: usize = 100;
+pub const TICKS_PRE_SEC: usize = 100;
 const MILLISECONDS: usize = 1000;
-/* const MICROSECONDS: usize = 1_000_000; */
+const MICROSECONDS: usize = 1_000_000;
+const NANOSECONDS: usize = 1_000_000_000;
+
+/// `mtime`每计一次经过的纳秒数，即各时钟的分辨率
+pub const RESOLUTION_NS: usize = NANOSECONDS / CLOCK_FREQ;
+
+/// 启动时刻距UNIX纪元的微秒数，首次读取墙上时间时由RTC确定
+static BOOT_EPOCH_US: Lazy<usize> =
+    Lazy::new(|| (rtc::read_ns() / 1000) as usize - get_time_us());
 
 static TIMERS: UpCell<BinaryHeap<TimerCondVar>> = UpCell::new(BinaryHeap::new());
 
@@ -32,6 +42,18 @@ pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MILLISECONDS)
 }
 
+/// get current time in microseconds
+pub fn get_time_us() -> usize {
+    time::read() / (CLOCK_FREQ / MICROSECONDS)
+}
+
+/// 自UNIX纪元以来的微秒数
+///
+/// RTC只读一次，此后由单调的`mtime`推算，墙上时间因此不会回退，也不受RTC的读取开销影响。
+pub fn realtime_us() -> usize {
+    *BOOT_EPOCH_US + get_time_us()
+}
+
 /// set `mtimecmp`, the next timer interrupt
 pub fn set_next_trigger() {
     set_timer(get_time() + CLOCK_FREQ / TICKS_PRE_SEC);
@@ -52,12 +74,13 @@ pub fn add_timer(timer: TimerCondVar) {
     TIMERS.exclusive_access().push(timer);
 }
 
-/// 移除传入任务的所有计时器
-pub fn remove_timer(task: &Arc<TaskControlBlock>) {
+/// 移除传入任务的所有计时器，返回是否移除了计时器
+pub fn remove_timer(task: &Arc<TaskControlBlock>) -> bool {
     let task = Arc::as_ptr(task);
-    TIMERS
-        .exclusive_access()
-        .retain(|t| Arc::as_ptr(&t.task) != task);
+    let mut timers = TIMERS.exclusive_access();
+    let len = timers.len();
+    timers.retain(|t| Arc::as_ptr(&t.task) != task);
+    timers.len() != len
 }
 
 pub fn wakeup_timeout_tasks() {
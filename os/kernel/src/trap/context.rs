@@ -5,9 +5,16 @@
 //! 并通 sret 让应用程序继续执行。
 
 use riscv::register::sstatus;
-use riscv::register::sstatus::Sstatus;
-use riscv::register::sstatus::SPP;
+use riscv::register::sstatus::{FS, SPP};
 
+use super::fp::{self, FpState};
+#[cfg(feature = "vector")]
+use super::vector::{VectorStatus, VS_SHIFT};
+
+/// sstatus中FS字段的位置
+const FS_SHIFT: usize = 13;
+
+// |   fp state    |
 // |  trap_handler |
 // |   kernel_sp   |
 // |   kernel_satp |
@@ -24,8 +31,8 @@ use riscv::register::sstatus::SPP;
 pub struct TrapContext {
     /// 所有通用寄存器，x0 ~ x31
     x: [usize; 32],
-    /// 中断使能 及 各种杂七杂八的状态
-    sstatus: Sstatus,
+    /// 中断使能 及 各种杂七杂八的状态，保存原始的位以便改写FS字段
+    sstatus: usize,
     /// Supervisor Exception PC, 指向出现异常的指令
     pub(super) sepc: usize,
     /* 以下都是地址，因为太多了 sscratch 放不下，就放上下文里了 */
@@ -35,6 +42,8 @@ pub struct TrapContext {
     kernel_sp: usize,
     /// Trap处理函数
     trap_handler: usize,
+    /// 浮点寄存器，由[`fp`]惰性地保存与恢复，不经过`__alltraps`与`__restore`
+    fp: FpState,
 }
 
 impl TrapContext {
@@ -57,7 +66,9 @@ impl TrapContext {
         unsafe {
             sstatus::set_spp(SPP::User);
         }
-        let sstatus = sstatus::read();
+        let sstatus = sstatus::read().bits();
+        // 上下文所在的页帧可能刚被释放的上下文用过
+        fp::forget();
         let mut ctx = Self {
             x: [0; 32],
             sstatus,
@@ -65,16 +76,73 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            fp: FpState::new(),
         };
 
         ctx.set_sp(sp);
+        // 用户可以使用浮点指令，浮点寄存器初始全为零
+        ctx.set_fs(FS::Initial);
+        // 首次使用向量指令时再开启
+        #[cfg(feature = "vector")]
+        ctx.set_vs(VectorStatus::Off);
         ctx
     }
 
+    /// 设置线程指针`tp`，用户库以之定位线程局部存储
+    pub fn set_tp(&mut self, tp: usize) {
+        self.x[4] = tp;
+    }
+
     pub fn set_kernel_sp(&mut self, kernel_sp: usize) {
         self.kernel_sp = kernel_sp;
     }
 
+    fn fs(&self) -> FS {
+        match self.sstatus >> FS_SHIFT & 0b11 {
+            0 => FS::Off,
+            1 => FS::Initial,
+            2 => FS::Clean,
+            _ => FS::Dirty,
+        }
+    }
+
+    fn set_fs(&mut self, fs: FS) {
+        self.sstatus = self.sstatus & !(0b11 << FS_SHIFT) | (fs as usize) << FS_SHIFT;
+    }
+
+    /// 上下文被复制到新的页帧后调用，如fork，见[`fp::forget`]
+    pub fn forget_fp(&self) {
+        fp::forget();
+    }
+
+    /// 陷入后调用：用户改动过浮点寄存器则保存之
+    pub fn save_fp(&mut self) {
+        if self.fs() == FS::Dirty {
+            let owner = self as *const Self as usize;
+            self.fp.save(owner);
+            self.set_fs(FS::Clean);
+        }
+    }
+
+    /// 返回用户态前调用：浮点寄存器中不是本上下文的状态则恢复之
+    pub fn restore_fp(&self) {
+        match self.fs() {
+            FS::Off => {}
+            FS::Initial => fp::clear(),
+            FS::Clean | FS::Dirty => self.fp.restore(self as *const Self as usize),
+        }
+    }
+
+    /// 全部通用寄存器，x0 ~ x31
+    pub fn regs(&self) -> &[usize; 32] {
+        &self.x
+    }
+
+    /// 返回用户态后将执行的指令地址
+    pub fn pc(&self) -> usize {
+        self.sepc
+    }
+
     /// 凭借ABI索引访问参数寄存器
     #[inline]
     pub fn arg(&self, n: usize) -> usize {
@@ -91,3 +159,19 @@ impl TrapContext {
         self.x[10] = res;
     }
 }
+
+#[cfg(feature = "vector")]
+impl TrapContext {
+    pub fn vs(&self) -> VectorStatus {
+        match self.sstatus >> VS_SHIFT & 0b11 {
+            0 => VectorStatus::Off,
+            1 => VectorStatus::Initial,
+            2 => VectorStatus::Clean,
+            _ => VectorStatus::Dirty,
+        }
+    }
+
+    pub fn set_vs(&mut self, vs: VectorStatus) {
+        self.sstatus = self.sstatus & !(0b11 << VS_SHIFT) | (vs as usize) << VS_SHIFT;
+    }
+}
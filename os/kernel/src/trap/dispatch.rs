@@ -0,0 +1,52 @@
+//! Trap分派表
+//!
+//! 各子系统为自己关心的异常与中断登记处理函数：缺页由内存管理处理，
+//! 系统调用由系统调用模块处理，外部中断由PLIC分派给设备……
+//! 来自用户态与内核态的Trap各有一张表，未登记的Trap视为内核错误。
+
+use alloc::vec::Vec;
+
+use riscv::register::scause::Trap;
+
+use crate::sync::UpCell;
+
+/// Trap处理函数，参数为`stval`
+pub type Handler = fn(usize);
+
+static USER_HANDLERS: UpCell<Vec<(Trap, Handler)>> = UpCell::new(Vec::new());
+static KERNEL_HANDLERS: UpCell<Vec<(Trap, Handler)>> = UpCell::new(Vec::new());
+
+/// 登记用户态Trap的处理函数，取代先前登记的
+pub fn register_user(trap: Trap, handler: Handler) {
+    insert(&USER_HANDLERS, trap, handler);
+}
+
+/// 登记内核态Trap的处理函数，取代先前登记的
+pub fn register_kernel(trap: Trap, handler: Handler) {
+    insert(&KERNEL_HANDLERS, trap, handler);
+}
+
+fn insert(table: &UpCell<Vec<(Trap, Handler)>>, trap: Trap, handler: Handler) {
+    let mut table = table.exclusive_access();
+    match table.iter_mut().find(|(registered, _)| *registered == trap) {
+        Some(entry) => entry.1 = handler,
+        None => table.push((trap, handler)),
+    }
+}
+
+pub(super) fn user_handler(trap: Trap) -> Option<Handler> {
+    find(&USER_HANDLERS, trap)
+}
+
+pub(super) fn kernel_handler(trap: Trap) -> Option<Handler> {
+    find(&KERNEL_HANDLERS, trap)
+}
+
+/// 复制出处理函数，处理期间不占用分派表
+fn find(table: &UpCell<Vec<(Trap, Handler)>>, trap: Trap) -> Option<Handler> {
+    table
+        .exclusive_access()
+        .iter()
+        .find(|(registered, _)| *registered == trap)
+        .map(|&(_, handler)| handler)
+}
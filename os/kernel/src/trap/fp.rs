@@ -0,0 +1,86 @@
+//! 用户浮点寄存器(F/D扩展)的惰性保存与恢复
+//!
+//! 用户改动浮点寄存器时，硬件将sstatus.FS置为Dirty。
+//! 陷入时只保存FS为Dirty的状态，随后将保存的FS改为Clean；
+//! 返回用户态时，只在浮点寄存器中不是该上下文的状态时才恢复。
+//! 任务切换总是经由陷入，故`__switch`无需处理浮点寄存器。
+
+use core::arch::global_asm;
+
+use riscv::register::sstatus::{self, FS};
+
+use crate::sync::UpCell;
+
+global_asm!(include_str!("fp.S"));
+
+extern "C" {
+    fn __save_fp(state: *mut FpState);
+    fn __restore_fp(state: *const FpState);
+    fn __clear_fp();
+}
+
+/// 浮点寄存器中现存的是哪个Trap上下文的状态，以上下文的地址标识。
+///
+/// 上下文所在的页帧释放后会被新的上下文复用，故每建立一个上下文都须经[`forget`]重置，
+/// 否则新上下文会误认旧上下文留下的浮点寄存器为己有
+static OWNER: UpCell<usize> = UpCell::new(UNKNOWN);
+
+/// 浮点寄存器的内容不属于任何上下文
+const UNKNOWN: usize = usize::MAX;
+/// 浮点寄存器全为零，即FS为Initial的上下文应有的状态
+const ZEROED: usize = 0;
+
+/// 浮点寄存器 f0 ~ f31 与 fcsr
+#[repr(C)]
+#[derive(Debug, Clone)]
+pub struct FpState {
+    f: [u64; 32],
+    fcsr: usize,
+}
+
+impl FpState {
+    pub const fn new() -> Self {
+        Self {
+            f: [0; 32],
+            fcsr: 0,
+        }
+    }
+
+    /// 保存浮点寄存器，并记为其主人
+    pub fn save(&mut self, owner: usize) {
+        unsafe {
+            sstatus::set_fs(FS::Clean);
+            __save_fp(self);
+        }
+        *OWNER.exclusive_access() = owner;
+    }
+
+    /// 若浮点寄存器的主人不是`owner`，则恢复之
+    pub fn restore(&self, owner: usize) {
+        let mut current = OWNER.exclusive_access();
+        if *current != owner {
+            unsafe {
+                sstatus::set_fs(FS::Clean);
+                __restore_fp(self);
+            }
+            *current = owner;
+        }
+    }
+}
+
+/// 浮点寄存器的内容不再归属任何上下文，下次返回用户态时必定恢复
+pub fn forget() {
+    *OWNER.exclusive_access() = UNKNOWN;
+}
+
+/// 将浮点寄存器清零，已是零则略过
+pub fn clear() {
+    let mut current = OWNER.exclusive_access();
+    if *current != ZEROED {
+        unsafe {
+            sstatus::set_fs(FS::Clean);
+            __clear_fp();
+        }
+        *current = ZEROED;
+    }
+}
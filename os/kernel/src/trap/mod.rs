@@ -11,10 +11,19 @@
 //! 故而不会再次触发中断导致嵌套中断了。
 //!
 //! NOTE: stvec(Supervisor Trap Vector)：当异常发生时，PC应该跳转的地址
+//!
+//! Trap按[`dispatch`]中登记的处理函数分派，本模块只登记时钟中断等与任务调度相关的处理。
 
 mod context;
+mod dispatch;
+mod fp;
+#[cfg(feature = "vector")]
+mod vector;
 
 pub use self::context::TrapContext;
+pub use self::dispatch::{register_kernel, register_user};
+#[cfg(feature = "vector")]
+pub use self::vector::VectorState;
 
 use core::arch::asm;
 use core::arch::global_asm;
@@ -24,15 +33,16 @@ use riscv::register::scause;
 use riscv::register::scause::Exception;
 use riscv::register::scause::Interrupt;
 use riscv::register::scause::Trap;
+use riscv::register::sepc;
 use riscv::register::sie;
 use riscv::register::sscratch;
 use riscv::register::sstatus;
 use riscv::register::stval;
 use riscv::register::stvec;
 
-use crate::board;
 use crate::config::TRAMPOLINE;
-use crate::syscall::syscall;
+use crate::fs;
+use crate::memory;
 use crate::task;
 use crate::task::processor;
 use crate::task::signal::SignalFlag;
@@ -48,6 +58,21 @@ extern "C" {
 
 pub fn init() {
     set_kernel_trap_entry();
+    register_user(
+        Trap::Interrupt(Interrupt::SupervisorTimer),
+        user_timer_interrupt,
+    );
+    register_kernel(
+        Trap::Interrupt(Interrupt::SupervisorTimer),
+        kernel_timer_interrupt,
+    );
+    register_user(Trap::Exception(Exception::Breakpoint), breakpoint);
+    register_user(
+        Trap::Exception(Exception::IllegalInstruction),
+        illegal_instruction,
+    );
+    #[cfg(feature = "vector")]
+    vector::init();
 }
 
 fn set_kernel_trap_entry() {
@@ -84,6 +109,9 @@ pub fn enable_timer_interrupt() {
 #[no_mangle]
 pub fn trap_handler() -> ! {
     set_kernel_trap_entry();
+    processor::current_trap_ctx().save_fp();
+    #[cfg(feature = "vector")]
+    vector::save_current();
     // Supervisor Exception Casue
     // 记录发生的异常
     let scause = scause::read();
@@ -95,50 +123,9 @@ pub fn trap_handler() -> ! {
     // | _ => 0
     let stval = stval::read();
 
-    match cause {
-        Trap::Exception(Exception::UserEnvCall) => {
-            // Trap上下文不在内核地址空间内，要间接获取
-            let ctx = processor::current_trap_ctx();
-            // ecall 指令触发的异常，
-            // 希望 sepc 可以指向 ecall 的下一条指令
-            // (RISC-V 64 指令长度不超过 32 位)
-            ctx.sepc += 4;
-
-            unsafe {
-                sstatus::set_sie();
-            }
-
-            let result = syscall(ctx.arg(7), [ctx.arg(0), ctx.arg(1), ctx.arg(2)]);
-
-            // 原来的Trap上下文在 sys_exec 时被回收，需获取新的Trap上下文
-            let ctx = processor::current_trap_ctx();
-            ctx.set_syscall_result(result as usize);
-        }
-
-        // 某些异常会令内核给进程发送信号，
-        // 这就是异步信号的由来，即异步异常的传染
-        Trap::Exception(
-            Exception::StoreFault
-            | Exception::StorePageFault
-            | Exception::LoadFault
-            | Exception::LoadPageFault
-            | Exception::InstructionFault
-            | Exception::InstructionPageFault,
-        ) => task::send_signal_to_current(SignalFlag::SIGSEGV),
-
-        Trap::Exception(Exception::IllegalInstruction) => {
-            task::send_signal_to_current(SignalFlag::SIGILL);
-        }
-
-        Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            timer::set_next_trigger();
-            timer::wakeup_timeout_tasks();
-            task::suspend_current_and_run_next();
-        }
-
-        Trap::Interrupt(Interrupt::SupervisorExternal) => board::irq_handler(),
-
-        _ => panic!("Unsupported trap {cause:?}, stval = {stval:#x}!"),
+    match dispatch::user_handler(cause) {
+        Some(handler) => handler(stval),
+        None => panic!("Unsupported trap {cause:?}, stval = {stval:#x}!"),
     }
 
     /* task::handle_signals(); */
@@ -157,6 +144,9 @@ pub fn trap_return() -> ! {
     unsafe {
         sstatus::clear_sie();
     }
+    processor::current_trap_ctx().restore_fp();
+    #[cfg(feature = "vector")]
+    vector::restore_current();
     set_user_trap_entry();
 
     // TRAMPOLINE 运行时地址
@@ -191,13 +181,57 @@ fn trap_from_kernel() {
     let stval = stval::read();
     let casue = scause.cause();
 
-    match casue {
-        Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            timer::set_next_trigger();
-            timer::wakeup_timeout_tasks();
-            // 内核不做时间片轮换
+    match dispatch::kernel_handler(casue) {
+        Some(handler) => handler(stval),
+        None => panic!("Unsupported trap from kernel: {casue:?}, stval = {stval:#x}"),
+    }
+}
+
+fn user_timer_interrupt(_stval: usize) {
+    timer::set_next_trigger();
+    memory::vdso::update();
+    timer::wakeup_timeout_tasks();
+    task::group::charge_current();
+    task::group::tick();
+    task::manager::tick();
+    task::perf::tick();
+    // 扫描要借用所有进程，只能在未持有任何进程时进行
+    if memory::ksm::due() {
+        task::manager::merge_user_pages();
+    }
+    if fs::writeback_due() {
+        // 同系统调用一样开中断，写块时才能等待设备的完成中断
+        unsafe {
+            sstatus::set_sie();
         }
-        Trap::Interrupt(Interrupt::SupervisorExternal) => board::irq_handler(),
-        _ => panic!("Unsupported trap from kernel: {casue:?}, stval = {stval:#x}"),
+        fs::sync();
     }
+    task::suspend_current_and_run_next();
+}
+
+fn kernel_timer_interrupt(_stval: usize) {
+    timer::set_next_trigger();
+    memory::vdso::update();
+    timer::wakeup_timeout_tasks();
+    task::group::tick();
+    task::manager::tick();
+    task::perf::tick_kernel(sepc::read());
+    // 内核不做时间片轮换
+}
+
+/// 被追踪的进程停下，交由追踪者处理；否则以SIGTRAP结束进程
+fn breakpoint(_stval: usize) {
+    if task::ptrace::is_current_traced() {
+        task::ptrace::stop_current();
+    } else {
+        log::warn!(
+            "[kernel] Breakpoint at {:#x}",
+            processor::current_trap_ctx().pc()
+        );
+        task::send_signal_to_current(SignalFlag::SIGTRAP);
+    }
+}
+
+fn illegal_instruction(_stval: usize) {
+    task::send_signal_to_current(SignalFlag::SIGILL);
 }
@@ -0,0 +1,264 @@
+//! 用户向量寄存器(V扩展)的惰性启用、保存与恢复
+//!
+//! S态读不到misa，无从直接得知CPU是否实现了V扩展；
+//! 好在sstatus.VS是WARL字段，未实现V扩展时恒为零，写入后读回即可探测。
+//!
+//! 用户任务起初VS为Off，首次执行向量指令会触发非法指令异常，
+//! 此时为其开启VS并重新执行该指令。此后与浮点寄存器一样：
+//! 陷入时只保存VS为Dirty的状态，返回用户态时只在向量寄存器中不是该任务的状态时才恢复。
+//! 向量寄存器的大小随实现而定，可能超出Trap上下文所在的页，故保存在任务控制块中。
+
+use alloc::boxed::Box;
+use alloc::vec;
+use core::arch::asm;
+
+use riscv::register::scause::{Exception, Trap};
+use spin::Lazy;
+
+use super::TrapContext;
+use crate::board::VECTOR_EXTENSION;
+use crate::sync::UpCell;
+use crate::task;
+use crate::task::processor;
+use crate::task::signal::SignalFlag;
+
+/// 是否可以使用向量扩展
+static SUPPORTED: Lazy<bool> = Lazy::new(|| VECTOR_EXTENSION && probe());
+
+/// 向量寄存器中现存的是哪个状态，以其地址标识
+static OWNER: UpCell<usize> = UpCell::new(UNKNOWN);
+
+/// 向量寄存器的内容不属于任何状态
+const UNKNOWN: usize = usize::MAX;
+/// 向量寄存器全为零，即VS为Initial的任务应有的状态
+const ZEROED: usize = 0;
+
+/// sstatus中VS字段的位置
+pub(super) const VS_SHIFT: usize = 9;
+
+/// 向量寄存器 v0 ~ v31 与向量CSR
+#[derive(Debug, Clone)]
+pub struct VectorState {
+    /// 32个向量寄存器，每个`vlenb`字节
+    regs: Box<[u8]>,
+    vstart: usize,
+    vl: usize,
+    vtype: usize,
+    vcsr: usize,
+}
+
+pub fn init() {
+    if *SUPPORTED {
+        log::info!("[kernel] Vector extension enabled, vlenb={}", vlenb());
+        super::register_user(
+            Trap::Exception(Exception::IllegalInstruction),
+            illegal_instruction,
+        );
+    }
+}
+
+/// 首次使用向量指令时开启向量扩展，重新执行该指令
+fn illegal_instruction(_stval: usize) {
+    if !enable_on_first_use(processor::current_trap_ctx()) {
+        task::send_signal_to_current(SignalFlag::SIGILL);
+    }
+}
+
+/// 写入VS后读回，不为零说明实现了V扩展
+fn probe() -> bool {
+    let sstatus: usize;
+    unsafe {
+        asm!(
+            "csrs sstatus, {vs}",
+            "csrr {sstatus}, sstatus",
+            "csrc sstatus, {mask}",
+            vs = in(reg) 1 << VS_SHIFT,
+            mask = in(reg) 0b11 << VS_SHIFT,
+            sstatus = out(reg) sstatus,
+        );
+    }
+    sstatus >> VS_SHIFT & 0b11 != 0
+}
+
+/// 开启内核自身的VS，以便存取向量寄存器。返回用户态时sstatus会被上下文中的值覆盖。
+fn enable() {
+    unsafe { asm!("csrs sstatus, {}", in(reg) 0b11 << VS_SHIFT) };
+}
+
+/// 每个向量寄存器的字节数
+fn vlenb() -> usize {
+    enable();
+    let vlenb;
+    unsafe { asm!("csrr {}, 0xc22", out(reg) vlenb) };
+    vlenb
+}
+
+impl VectorState {
+    fn new() -> Self {
+        Self {
+            regs: vec![0; 32 * vlenb()].into_boxed_slice(),
+            vstart: 0,
+            vl: 0,
+            vtype: 0,
+            vcsr: 0,
+        }
+    }
+
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    fn save(&mut self) {
+        enable();
+        let group = self.regs.len() / 4;
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +v",
+                "csrr {vstart}, 0x008",
+                "csrr {vcsr}, 0x00f",
+                "csrr {vl}, 0xc20",
+                "csrr {vtype}, 0xc21",
+                "vs8r.v v0, ({p})",
+                "add {p}, {p}, {group}",
+                "vs8r.v v8, ({p})",
+                "add {p}, {p}, {group}",
+                "vs8r.v v16, ({p})",
+                "add {p}, {p}, {group}",
+                "vs8r.v v24, ({p})",
+                ".option pop",
+                p = inout(reg) self.regs.as_mut_ptr() => _,
+                group = in(reg) group,
+                vstart = out(reg) self.vstart,
+                vcsr = out(reg) self.vcsr,
+                vl = out(reg) self.vl,
+                vtype = out(reg) self.vtype,
+            );
+        }
+        *OWNER.exclusive_access() = self.id();
+    }
+
+    fn restore(&self) {
+        let mut owner = OWNER.exclusive_access();
+        if *owner == self.id() {
+            return;
+        }
+
+        enable();
+        let group = self.regs.len() / 4;
+        unsafe {
+            asm!(
+                ".option push",
+                ".option arch, +v",
+                "vl8re8.v v0, ({p})",
+                "add {p}, {p}, {group}",
+                "vl8re8.v v8, ({p})",
+                "add {p}, {p}, {group}",
+                "vl8re8.v v16, ({p})",
+                "add {p}, {p}, {group}",
+                "vl8re8.v v24, ({p})",
+                // vsetvl会清零vstart，须在其后恢复
+                "vsetvl x0, {vl}, {vtype}",
+                "csrw 0x008, {vstart}",
+                "csrw 0x00f, {vcsr}",
+                ".option pop",
+                p = inout(reg) self.regs.as_ptr() => _,
+                group = in(reg) group,
+                vl = in(reg) self.vl,
+                vtype = in(reg) self.vtype,
+                vstart = in(reg) self.vstart,
+                vcsr = in(reg) self.vcsr,
+            );
+        }
+        *owner = self.id();
+    }
+}
+
+impl Drop for VectorState {
+    fn drop(&mut self) {
+        // 地址可能被新的状态复用，不能再认作寄存器的主人
+        let mut owner = OWNER.exclusive_access();
+        if *owner == self.id() {
+            *owner = UNKNOWN;
+        }
+    }
+}
+
+/// 将向量寄存器清零，已是零则略过
+fn clear() {
+    let mut owner = OWNER.exclusive_access();
+    if *owner == ZEROED {
+        return;
+    }
+
+    enable();
+    unsafe {
+        asm!(
+            ".option push",
+            ".option arch, +v",
+            "vsetvli {tmp}, x0, e8, m8, ta, ma",
+            "vmv.v.i v0, 0",
+            "vmv.v.i v8, 0",
+            "vmv.v.i v16, 0",
+            "vmv.v.i v24, 0",
+            "csrw 0x00f, x0",
+            ".option pop",
+            tmp = out(reg) _,
+        );
+    }
+    *owner = ZEROED;
+}
+
+/// 非法指令异常时调用：若是任务首次使用向量指令，开启VS并返回真，应重新执行该指令
+fn enable_on_first_use(ctx: &mut TrapContext) -> bool {
+    if !*SUPPORTED || ctx.vs() != VectorStatus::Off {
+        return false;
+    }
+
+    ctx.set_vs(VectorStatus::Initial);
+    true
+}
+
+/// 陷入后调用：当前任务改动过向量寄存器则保存之
+pub fn save_current() {
+    let ctx = processor::current_trap_ctx();
+    if ctx.vs() != VectorStatus::Dirty {
+        return;
+    }
+
+    let task = processor::current_task().unwrap();
+    task.inner()
+        .exclusive_access()
+        .vector
+        .get_or_insert_with(VectorState::new)
+        .save();
+    ctx.set_vs(VectorStatus::Clean);
+}
+
+/// 返回用户态前调用：向量寄存器中不是当前任务的状态则恢复之
+pub fn restore_current() {
+    let ctx = processor::current_trap_ctx();
+    match ctx.vs() {
+        VectorStatus::Off => {}
+        VectorStatus::Initial => clear(),
+        VectorStatus::Clean | VectorStatus::Dirty => {
+            let task = processor::current_task().unwrap();
+            // Clean意味着曾经保存过
+            task.inner()
+                .exclusive_access()
+                .vector
+                .as_ref()
+                .unwrap()
+                .restore();
+        }
+    }
+}
+
+/// sstatus中VS字段的取值，与FS相同
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorStatus {
+    Off = 0,
+    Initial = 1,
+    Clean = 2,
+    Dirty = 3,
+}
@@ -0,0 +1,149 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use vfs::DirEntryType;
+
+use crate::layout::{ChunkEntry, DiskInode, SuperBlock};
+use crate::{BLOCK_SIZE, CHUNK_SIZE, NAME_MAX_LEN, ROOT_ID};
+
+/// 在内存中搭建目录树，再一次性生成镜像
+#[derive(Debug)]
+pub struct Builder {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    File(Vec<u8>),
+    Dir(Vec<(String, u32)>),
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    /// 只有根目录的空镜像
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::Dir(Vec::new())],
+        }
+    }
+
+    #[inline]
+    pub fn root(&self) -> u32 {
+        ROOT_ID
+    }
+
+    /// 在目录`parent`下创建文件，返回其inode ID
+    ///
+    /// # Panics
+    ///
+    /// `parent`不是目录，名字为空、含有`/`、过长或已存在
+    pub fn add_file(&mut self, parent: u32, name: &str, data: Vec<u8>) -> u32 {
+        self.add(parent, name, Node::File(data))
+    }
+
+    /// 在目录`parent`下创建子目录，返回其inode ID，限制同[`Self::add_file`]
+    pub fn add_dir(&mut self, parent: u32, name: &str) -> u32 {
+        self.add(parent, name, Node::Dir(Vec::new()))
+    }
+
+    fn add(&mut self, parent: u32, name: &str, node: Node) -> u32 {
+        assert!(
+            !name.is_empty() && !name.contains('/') && name.len() <= NAME_MAX_LEN,
+            "invalid file name {name:?}"
+        );
+        let id = self.nodes.len() as u32;
+        let Node::Dir(entries) = &mut self.nodes[parent as usize] else {
+            panic!("inode {parent} is not a directory");
+        };
+        assert!(
+            entries.iter().all(|(n, _)| n != name),
+            "{name:?} already exists"
+        );
+        entries.push((String::from(name), id));
+        self.nodes.push(node);
+        id
+    }
+
+    /// 生成镜像，长度为块大小的整数倍
+    pub fn build(&self) -> Vec<u8> {
+        let mut inodes = Vec::with_capacity(self.nodes.len());
+        // (原样存放与否, 存放的字节)
+        let mut chunks: Vec<(bool, Vec<u8>)> = Vec::new();
+
+        for node in &self.nodes {
+            let (kind, content) = match node {
+                Node::File(data) => (DirEntryType::Regular, data.clone()),
+                Node::Dir(entries) => (DirEntryType::Directory, self.dir_content(entries)),
+            };
+            inodes.push(DiskInode {
+                kind,
+                size: content.len() as u32,
+                first_chunk: chunks.len() as u32,
+            });
+            chunks.extend(content.chunks(CHUNK_SIZE).map(|chunk| {
+                let compressed = lz4_flex::block::compress(chunk);
+                if compressed.len() < chunk.len() {
+                    (false, compressed)
+                } else {
+                    (true, chunk.to_vec())
+                }
+            }));
+        }
+
+        let chunk_index = 1;
+        let inode_table = chunk_index + (chunks.len() * ChunkEntry::SIZE).div_ceil(BLOCK_SIZE);
+        let data_start = inode_table + (inodes.len() * DiskInode::SIZE).div_ceil(BLOCK_SIZE);
+
+        let mut index = Vec::with_capacity(chunks.len() * ChunkEntry::SIZE);
+        let mut data = Vec::new();
+        for (raw, stored) in &chunks {
+            let entry = ChunkEntry {
+                block: (data_start + data.len() / BLOCK_SIZE) as u32,
+                len: stored.len() as u32,
+                raw: *raw,
+            };
+            index.extend_from_slice(&entry.encode());
+            data.extend_from_slice(stored);
+            data.resize(data.len().next_multiple_of(BLOCK_SIZE), 0);
+        }
+        let table: Vec<u8> = inodes.iter().flat_map(DiskInode::encode).collect();
+
+        let total_blocks = data_start + data.len() / BLOCK_SIZE;
+        let sb = SuperBlock {
+            total_blocks: total_blocks as u32,
+            chunk_index: chunk_index as u32,
+            chunk_count: chunks.len() as u32,
+            inode_table: inode_table as u32,
+            inode_count: inodes.len() as u32,
+        };
+
+        let mut image = vec![0; total_blocks * BLOCK_SIZE];
+        image[..SuperBlock::SIZE].copy_from_slice(&sb.encode());
+        let at = |block: usize| block * BLOCK_SIZE;
+        image[at(chunk_index)..at(chunk_index) + index.len()].copy_from_slice(&index);
+        image[at(inode_table)..at(inode_table) + table.len()].copy_from_slice(&table);
+        image[at(data_start)..].copy_from_slice(&data);
+        image
+    }
+
+    fn dir_content(&self, entries: &[(String, u32)]) -> Vec<u8> {
+        let mut content = Vec::new();
+        for (name, id) in entries {
+            let kind = match self.nodes[*id as usize] {
+                Node::File(_) => DirEntryType::Regular,
+                Node::Dir(_) => DirEntryType::Directory,
+            };
+            content.extend_from_slice(&id.to_le_bytes());
+            content.push(kind as u8);
+            content.push(name.len() as u8);
+            content.extend_from_slice(name.as_bytes());
+        }
+        content
+    }
+}
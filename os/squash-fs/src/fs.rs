@@ -0,0 +1,108 @@
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use block_dev::BlockDevice;
+use spin::Mutex;
+
+use crate::inode::Inode;
+use crate::layout::{ChunkEntry, DiskInode, SuperBlock};
+use crate::{Error, BLOCK_SIZE, CHUNK_SIZE, ROOT_ID};
+
+/// 已加载的镜像
+#[derive(Debug)]
+pub struct SquashFileSystem {
+    dev: Arc<dyn BlockDevice>,
+    sb: SuperBlock,
+    /// 最近解压的数据块，顺序读取时免于重复解压
+    last_chunk: Mutex<Option<(u32, Vec<u8>)>>,
+}
+
+impl SquashFileSystem {
+    pub fn load(dev: Arc<dyn BlockDevice>) -> Result<Self, Error> {
+        let mut block = [0; BLOCK_SIZE];
+        dev.read_block(0, &mut block);
+        let sb = SuperBlock::decode(&block)?;
+
+        Ok(Self {
+            dev,
+            sb,
+            last_chunk: Mutex::new(None),
+        })
+    }
+
+    #[inline]
+    pub fn super_block(&self) -> &SuperBlock {
+        &self.sb
+    }
+
+    pub fn root(&self) -> Inode {
+        self.inode(ROOT_ID)
+            .expect("root inode should be a directory")
+    }
+
+    /// `id`越界或inode损坏时返回`None`
+    pub fn inode(&self, id: u32) -> Option<Inode> {
+        if id >= self.sb.inode_count {
+            return None;
+        }
+        let mut bytes = [0; DiskInode::SIZE];
+        self.read_bytes(
+            self.sb.inode_table as usize * BLOCK_SIZE + id as usize * DiskInode::SIZE,
+            &mut bytes,
+        );
+        let disk_inode = DiskInode::decode(&bytes)?;
+        (disk_inode.first_chunk as usize + disk_inode.chunks() <= self.sb.chunk_count as usize)
+            .then(|| Inode::new(id, disk_inode))
+    }
+
+    pub(crate) fn chunk_entry(&self, index: u32) -> ChunkEntry {
+        let mut bytes = [0; ChunkEntry::SIZE];
+        self.read_bytes(
+            self.sb.chunk_index as usize * BLOCK_SIZE + index as usize * ChunkEntry::SIZE,
+            &mut bytes,
+        );
+        ChunkEntry::decode(&bytes)
+    }
+
+    /// 解压第`index`个数据块后交给`f`，数据块损坏时返回`None`
+    pub(crate) fn with_chunk<R>(&self, index: u32, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let mut last_chunk = self.last_chunk.lock();
+        if let Some((_, data)) = last_chunk.as_ref().filter(|(cached, _)| *cached == index) {
+            return Some(f(data));
+        }
+
+        let entry = self.chunk_entry(index);
+        let mut stored = vec![0; entry.blocks() * BLOCK_SIZE];
+        let mut bufs: Vec<_> = stored.chunks_mut(BLOCK_SIZE).collect();
+        self.dev.read_blocks(entry.block as usize, &mut bufs);
+        stored.truncate(entry.len as usize);
+
+        let data = if entry.raw {
+            stored
+        } else {
+            let mut data = vec![0; CHUNK_SIZE];
+            let len = lz4_flex::block::decompress_into(&stored, &mut data).ok()?;
+            data.truncate(len);
+            data
+        };
+
+        let ret = f(&data);
+        *last_chunk = Some((index, data));
+        Some(ret)
+    }
+
+    /// 读取从字节偏移量`offset`起的`buf.len()`个字节
+    fn read_bytes(&self, offset: usize, buf: &mut [u8]) {
+        let mut block = [0; BLOCK_SIZE];
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            self.dev.read_block(pos / BLOCK_SIZE, &mut block);
+            let start = pos % BLOCK_SIZE;
+            let len = (BLOCK_SIZE - start).min(buf.len() - done);
+            buf[done..done + len].copy_from_slice(&block[start..start + len]);
+            done += len;
+        }
+    }
+}
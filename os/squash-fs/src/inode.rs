@@ -0,0 +1,126 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use vfs::{DirEntry, DirEntryType, Stat};
+
+use crate::fs::SquashFileSystem;
+use crate::layout::{DiskInode, DIRENT_HEADER_SIZE};
+use crate::{BLOCK_SIZE, CHUNK_SIZE};
+
+/// 文件或目录，只读
+#[derive(Debug, Clone)]
+pub struct Inode {
+    id: u32,
+    disk_inode: DiskInode,
+}
+
+impl Inode {
+    pub(crate) fn new(id: u32, disk_inode: DiskInode) -> Self {
+        Self { id, disk_inode }
+    }
+
+    #[inline]
+    pub fn id(&self) -> u64 {
+        self.id as u64
+    }
+
+    #[inline]
+    pub fn kind(&self) -> DirEntryType {
+        self.disk_inode.kind
+    }
+
+    /// 解压后的字节数
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.disk_inode.size as usize
+    }
+
+    /// 按`/`分隔的相对路径查找，空串即自身
+    pub fn find(&self, relat_path: &str, fs: &SquashFileSystem) -> Option<Self> {
+        relat_path
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self.clone(), |dir, name| {
+                let dirent = dir.read_dir(fs).into_iter().find(|d| d.name == name)?;
+                fs.inode(dirent.inode as u32)
+            })
+    }
+
+    /// 从`offset`起读至`buf`，返回读到的字节数；读到损坏的数据块时提前结束
+    pub fn read_at(&self, offset: usize, buf: &mut [u8], fs: &SquashFileSystem) -> usize {
+        let end = self.size().min(offset + buf.len());
+        let mut pos = offset;
+
+        while pos < end {
+            let chunk = self.disk_inode.first_chunk + (pos / CHUNK_SIZE) as u32;
+            let start = pos % CHUNK_SIZE;
+            let Some(len) = fs.with_chunk(chunk, |data| {
+                let len = (end - pos).min(data.len().saturating_sub(start));
+                buf[pos - offset..pos - offset + len].copy_from_slice(&data[start..start + len]);
+                len
+            }) else {
+                break;
+            };
+            if len == 0 {
+                break;
+            }
+            pos += len;
+        }
+
+        pos.saturating_sub(offset)
+    }
+
+    /// 目录下的全部目录项，非目录时为空
+    pub fn read_dir(&self, fs: &SquashFileSystem) -> Vec<DirEntry> {
+        if self.kind() != DirEntryType::Directory {
+            return Vec::new();
+        }
+
+        let mut data = vec![0; self.size()];
+        let len = self.read_at(0, &mut data, fs);
+        data.truncate(len);
+
+        let mut dirents = Vec::new();
+        let mut rest = &data[..];
+        while rest.len() >= DIRENT_HEADER_SIZE {
+            let inode = u32::from_le_bytes(rest[..4].try_into().unwrap());
+            let kind = rest[4];
+            let name_len = rest[5] as usize;
+            let Some(name) = rest.get(DIRENT_HEADER_SIZE..DIRENT_HEADER_SIZE + name_len) else {
+                break;
+            };
+            rest = &rest[DIRENT_HEADER_SIZE + name_len..];
+
+            dirents.push(DirEntry {
+                inode: inode as u64,
+                ty: if kind == DirEntryType::Directory as u8 {
+                    DirEntryType::Directory
+                } else {
+                    DirEntryType::Regular
+                },
+                name: String::from_utf8_lossy(name).into_owned(),
+            });
+        }
+
+        dirents
+    }
+
+    /// 所占的块数按压缩后计
+    pub fn stat(&self, fs: &SquashFileSystem) -> Stat {
+        let first = self.disk_inode.first_chunk;
+        let blocks = (first..first + self.disk_inode.chunks() as u32)
+            .map(|chunk| fs.chunk_entry(chunk).blocks())
+            .sum::<usize>();
+
+        Stat {
+            mode: self.kind(),
+            block_size: BLOCK_SIZE as u64,
+            blocks: blocks as u64,
+            size: self.size() as u64,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! 磁盘上的结构，均以小端序逐字段编码
+
+use vfs::DirEntryType;
+
+use crate::{Error, BLOCK_SIZE, CHUNK_SIZE, VERSION};
+
+const MAGIC: u32 = u32::from_le_bytes(*b"sqfs");
+
+/// 超级块，位于0号块
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SuperBlock {
+    /// 镜像的总块数
+    pub total_blocks: u32,
+    /// 块索引的起始块号
+    pub chunk_index: u32,
+    /// 数据块的数量
+    pub chunk_count: u32,
+    /// inode表的起始块号
+    pub inode_table: u32,
+    /// inode的数量
+    pub inode_count: u32,
+}
+
+impl SuperBlock {
+    pub const SIZE: usize = 32;
+
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0; Self::SIZE];
+        let fields = [
+            MAGIC,
+            VERSION,
+            CHUNK_SIZE as u32,
+            self.total_blocks,
+            self.chunk_index,
+            self.chunk_count,
+            self.inode_table,
+            self.inode_count,
+        ];
+        for (dst, field) in buf.chunks_mut(4).zip(fields) {
+            dst.copy_from_slice(&field.to_le_bytes());
+        }
+        buf
+    }
+
+    /// 校验魔数、版本以及各区域的位置
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let field = |i: usize| le32(&bytes[i * 4..]);
+
+        if field(0) != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        if field(1) > VERSION {
+            return Err(Error::UnsupportedVersion(field(1)));
+        }
+        if field(2) as usize != CHUNK_SIZE {
+            return Err(Error::Corrupt);
+        }
+
+        let sb = Self {
+            total_blocks: field(3),
+            chunk_index: field(4),
+            chunk_count: field(5),
+            inode_table: field(6),
+            inode_count: field(7),
+        };
+        let index_end =
+            sb.chunk_index as usize * BLOCK_SIZE + sb.chunk_count as usize * ChunkEntry::SIZE;
+        let table_end =
+            sb.inode_table as usize * BLOCK_SIZE + sb.inode_count as usize * DiskInode::SIZE;
+        if sb.inode_count == 0
+            || index_end > sb.inode_table as usize * BLOCK_SIZE
+            || table_end > sb.total_blocks as usize * BLOCK_SIZE
+        {
+            return Err(Error::Corrupt);
+        }
+
+        Ok(sb)
+    }
+}
+
+/// 块索引中的一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkEntry {
+    /// 起始块号
+    pub block: u32,
+    /// 存放的字节数
+    pub len: u32,
+    /// 未经压缩，原样存放
+    pub raw: bool,
+}
+
+impl ChunkEntry {
+    pub const SIZE: usize = 8;
+    const RAW: u32 = 1 << 31;
+
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let len = if self.raw {
+            self.len | Self::RAW
+        } else {
+            self.len
+        };
+        let mut buf = [0; Self::SIZE];
+        buf[..4].copy_from_slice(&self.block.to_le_bytes());
+        buf[4..].copy_from_slice(&len.to_le_bytes());
+        buf
+    }
+
+    pub fn decode(bytes: &[u8]) -> Self {
+        let len = le32(&bytes[4..]);
+        Self {
+            block: le32(bytes),
+            len: len & !Self::RAW,
+            raw: len & Self::RAW != 0,
+        }
+    }
+
+    /// 占据的块数
+    pub fn blocks(&self) -> usize {
+        (self.len as usize).div_ceil(BLOCK_SIZE)
+    }
+}
+
+/// inode表中的一项
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskInode {
+    pub kind: DirEntryType,
+    /// 解压后的字节数
+    pub size: u32,
+    /// 首个数据块在块索引中的序号，其余数据块紧随其后
+    pub first_chunk: u32,
+}
+
+impl DiskInode {
+    pub const SIZE: usize = 12;
+
+    pub fn encode(&self) -> [u8; Self::SIZE] {
+        let mut buf = [0; Self::SIZE];
+        buf[0] = self.kind as u8;
+        buf[4..8].copy_from_slice(&self.size.to_le_bytes());
+        buf[8..].copy_from_slice(&self.first_chunk.to_le_bytes());
+        buf
+    }
+
+    /// 只有目录与普通文件两种类型
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let kind = match bytes[0] {
+            k if k == DirEntryType::Directory as u8 => DirEntryType::Directory,
+            k if k == DirEntryType::Regular as u8 => DirEntryType::Regular,
+            _ => return None,
+        };
+
+        Some(Self {
+            kind,
+            size: le32(&bytes[4..]),
+            first_chunk: le32(&bytes[8..]),
+        })
+    }
+
+    /// 内容所占的数据块数
+    pub fn chunks(&self) -> usize {
+        (self.size as usize).div_ceil(CHUNK_SIZE)
+    }
+}
+
+/// 目录项的头部，其后紧跟名字
+pub const DIRENT_HEADER_SIZE: usize = 6;
+
+fn le32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[..4].try_into().unwrap())
+}
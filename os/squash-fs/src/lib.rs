@@ -0,0 +1,55 @@
+//! # squash-fs
+//!
+//! 只读的压缩文件系统，用来存放应用镜像。
+//!
+//! 文件与目录的内容按[`CHUNK_SIZE`]切分成数据块，各数据块经LZ4压缩后从块的边界开始存放，
+//! 由块索引记录其位置与压缩后的长度；压缩无益的数据块原样存放。
+//!
+//! ## 布局
+//!
+//! 以[`BLOCK_SIZE`]字节的块为单位：
+//!
+//! | 超级块 | 块索引 | inode表 | 数据块…… |
+//!
+//! - 超级块：见[`SuperBlock`]
+//! - 块索引：依次为各数据块的位置与长度
+//! - inode表：依次为各inode，0号为根目录
+//! - 目录的内容：依次为`inode ID(u32) | 类型(u8) | 名字长度(u8) | 名字`
+//!
+//! 所有整数均为小端序。镜像由[`Builder`]一次性生成，此后不可修改。
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod builder;
+mod fs;
+mod inode;
+mod layout;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::{builder::Builder, fs::SquashFileSystem, inode::Inode, layout::SuperBlock};
+
+/// 块大小
+pub const BLOCK_SIZE: usize = 512;
+/// 数据块解压后的大小，文件的最后一个数据块可能更小
+pub const CHUNK_SIZE: usize = 4096;
+/// 文件名的最大长度
+pub const NAME_MAX_LEN: usize = u8::MAX as usize;
+/// 镜像格式的版本
+pub const VERSION: u32 = 1;
+/// 根目录的inode ID
+pub const ROOT_ID: u32 = 0;
+
+/// 加载镜像时的错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// 不是squash-fs镜像
+    BadMagic,
+    /// 由更新版本的工具生成
+    UnsupportedVersion(u32),
+    /// 超级块中的字段相互矛盾
+    Corrupt,
+}
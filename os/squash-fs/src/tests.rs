@@ -0,0 +1,81 @@
+//! 由[`Builder`]生成镜像，挂在内存中的块设备上读回
+
+use std::sync::Arc;
+
+use block_dev::mem::MemDisk;
+use vfs::DirEntryType;
+
+use crate::{Builder, Error, SquashFileSystem, BLOCK_SIZE, CHUNK_SIZE};
+
+/// 不可压缩的伪随机字节
+fn noise(len: usize) -> Vec<u8> {
+    let mut x = 0x2545_f491_4f6c_dd1du64;
+    (0..len)
+        .map(|_| {
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            x as u8
+        })
+        .collect()
+}
+
+fn load(image: Vec<u8>) -> SquashFileSystem {
+    SquashFileSystem::load(Arc::new(MemDisk::from_image(image))).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let text: Vec<u8> = b"hello squash-fs\n".repeat(1000);
+    let random = noise(CHUNK_SIZE + 100);
+
+    let mut builder = Builder::new();
+    let bin = builder.add_dir(builder.root(), "bin");
+    builder.add_file(bin, "text", text.clone());
+    builder.add_file(bin, "random", random.clone());
+    builder.add_file(builder.root(), "empty", Vec::new());
+    let image = builder.build();
+    assert_eq!(image.len() % BLOCK_SIZE, 0);
+    assert!(image.len() < text.len() + random.len());
+
+    let fs = load(image);
+    let root = fs.root();
+    let names: Vec<_> = root.read_dir(&fs).into_iter().map(|d| d.name).collect();
+    assert_eq!(names, ["bin", "empty"]);
+
+    let file = root.find("bin/text", &fs).unwrap();
+    assert_eq!(file.kind(), DirEntryType::Regular);
+    assert_eq!(file.size(), text.len());
+    // 跨越数据块边界读取
+    let mut buf = vec![0; 300];
+    assert_eq!(file.read_at(CHUNK_SIZE - 100, &mut buf, &fs), 300);
+    assert_eq!(buf, text[CHUNK_SIZE - 100..CHUNK_SIZE + 200]);
+    // 读至文件末尾
+    assert_eq!(file.read_at(text.len() - 10, &mut buf, &fs), 10);
+    assert_eq!(file.read_at(text.len(), &mut buf, &fs), 0);
+    assert!(file.stat(&fs).blocks < text.len().div_ceil(BLOCK_SIZE) as u64);
+
+    let file = root.find("/bin//random", &fs).unwrap();
+    let mut buf = vec![0; random.len()];
+    assert_eq!(file.read_at(0, &mut buf, &fs), random.len());
+    assert_eq!(buf, random);
+
+    let empty = root.find("empty", &fs).unwrap();
+    assert_eq!(empty.read_at(0, &mut buf, &fs), 0);
+    assert!(root.find("bin/missing", &fs).is_none());
+    assert!(root.find("empty/text", &fs).is_none());
+}
+
+#[test]
+fn rejects_foreign_images() {
+    let dev = Arc::new(MemDisk::new(1));
+    assert_eq!(SquashFileSystem::load(dev).unwrap_err(), Error::BadMagic);
+
+    let mut image = Builder::new().build();
+    image[4] = 2;
+    let dev = Arc::new(MemDisk::from_image(image));
+    assert_eq!(
+        SquashFileSystem::load(dev).unwrap_err(),
+        Error::UnsupportedVersion(2)
+    );
+}
@@ -1,5 +1,7 @@
 use alloc::string::String;
 
+use crate::Stat;
+
 #[derive(Debug)]
 pub struct DirEntry {
     /// Inode number
@@ -8,21 +10,156 @@ pub struct DirEntry {
     pub name: String,
 }
 
-/// 系统调用所交换的目录项
-#[derive(Debug)]
-#[repr(C)]
+/// 系统调用所交换的变长目录项记录的头部，仿Linux的`linux_dirent64`
+///
+/// | 偏移 | 字段 |
+/// | ---- | ---- |
+/// | 0 | `inode: u64` |
+/// | 8 | `reclen: u16` |
+/// | 10 | `ty: u8` |
+/// | 11 | NUL结尾的名字 |
+///
+/// 整数均为小端序，记录长度向上对齐到8字节。
+/// 读者须按`reclen`跳到下一条记录，日后记录末尾可追加字段而不破坏旧程序。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CDirEntry {
     /// Inode number
     pub inode: u64,
+    /// 整条记录的字节数
+    pub reclen: u16,
     pub ty: DirEntryType,
-    /// NULL结尾字符串，
-    /// 最长为[`CDirEntry::NAME_CAP`]，
-    /// 分配容量为最大长度+1
-    pub name: *mut u8,
 }
 
 impl CDirEntry {
-    pub const NAME_CAP: usize = 255;
+    /// 名字的最大字节数，不含NUL
+    pub const NAME_MAX: usize = 255;
+    /// 名字在记录中的偏移量
+    pub const NAME_OFFSET: usize = 11;
+    const ALIGN: usize = 8;
+
+    /// 名字长`name_len`字节的记录所占的字节数
+    pub const fn reclen(name_len: usize) -> usize {
+        (Self::NAME_OFFSET + name_len + 1).next_multiple_of(Self::ALIGN)
+    }
+
+    /// 将`dirent`编码为一条记录写入`buf`开头，返回记录长度；`buf`放不下时返回`None`
+    pub fn encode(dirent: &DirEntry, buf: &mut [u8]) -> Option<usize> {
+        let name = dirent.name.as_bytes();
+        let name = &name[..name.len().min(Self::NAME_MAX)];
+        let reclen = Self::reclen(name.len());
+        let record = buf.get_mut(..reclen)?;
+
+        record.fill(0);
+        record[0..8].copy_from_slice(&dirent.inode.to_le_bytes());
+        record[8..10].copy_from_slice(&(reclen as u16).to_le_bytes());
+        record[10] = dirent.ty as u8;
+        record[Self::NAME_OFFSET..Self::NAME_OFFSET + name.len()].copy_from_slice(name);
+        Some(reclen)
+    }
+
+    /// 解析`buf`开头的一条记录，返回其头部与名字；记录残缺时返回`None`
+    pub fn decode(buf: &[u8]) -> Option<(Self, &str)> {
+        let header = buf.get(..Self::NAME_OFFSET)?;
+        let reclen = u16::from_le_bytes([header[8], header[9]]);
+        let record = buf.get(..reclen as usize)?;
+        let name = record.get(Self::NAME_OFFSET..)?;
+        let name = &name[..name.iter().position(|&b| b == 0)?];
+
+        let entry = Self {
+            inode: u64::from_le_bytes(header[0..8].try_into().unwrap()),
+            reclen,
+            ty: DirEntryType::try_from(header[10]).ok()?,
+        };
+        Some((entry, core::str::from_utf8(name).ok()?))
+    }
+}
+
+/// 附带文件状态的目录项记录的头部，省去逐项`fstat`
+///
+/// | 偏移 | 字段 |
+/// | ---- | ---- |
+/// | 0 | `inode: u64` |
+/// | 8 | `reclen: u16` |
+/// | 10 | `mode: u8` |
+/// | 16 | `block_size: u64` |
+/// | 24 | `blocks: u64` |
+/// | 32 | `size: u64` |
+/// | 40 | `atime: u64` |
+/// | 48 | `mtime: u64` |
+/// | 56 | `ctime: u64` |
+/// | 64 | NUL结尾的名字 |
+///
+/// 前11字节同[`CDirEntry`]，其余约定亦同。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CDirEntryPlus {
+    /// Inode number
+    pub inode: u64,
+    /// 整条记录的字节数
+    pub reclen: u16,
+    pub stat: Stat,
+}
+
+impl CDirEntryPlus {
+    /// 名字在记录中的偏移量
+    pub const NAME_OFFSET: usize = 64;
+
+    /// 名字长`name_len`字节的记录所占的字节数
+    pub const fn reclen(name_len: usize) -> usize {
+        (Self::NAME_OFFSET + name_len + 1).next_multiple_of(CDirEntry::ALIGN)
+    }
+
+    /// 将`dirent`及其状态`stat`编码为一条记录写入`buf`开头，返回记录长度；
+    /// `buf`放不下时返回`None`
+    pub fn encode(dirent: &DirEntry, stat: &Stat, buf: &mut [u8]) -> Option<usize> {
+        let name = dirent.name.as_bytes();
+        let name = &name[..name.len().min(CDirEntry::NAME_MAX)];
+        let reclen = Self::reclen(name.len());
+        let record = buf.get_mut(..reclen)?;
+
+        record.fill(0);
+        record[0..8].copy_from_slice(&dirent.inode.to_le_bytes());
+        record[8..10].copy_from_slice(&(reclen as u16).to_le_bytes());
+        record[10] = stat.mode as u8;
+        let fields = [
+            stat.block_size,
+            stat.blocks,
+            stat.size,
+            stat.atime,
+            stat.mtime,
+            stat.ctime,
+        ];
+        for (i, field) in fields.into_iter().enumerate() {
+            record[16 + i * 8..24 + i * 8].copy_from_slice(&field.to_le_bytes());
+        }
+        record[Self::NAME_OFFSET..Self::NAME_OFFSET + name.len()].copy_from_slice(name);
+        Some(reclen)
+    }
+
+    /// 解析`buf`开头的一条记录，返回其头部与名字；记录残缺时返回`None`
+    pub fn decode(buf: &[u8]) -> Option<(Self, &str)> {
+        let header = buf.get(..Self::NAME_OFFSET)?;
+        let reclen = u16::from_le_bytes([header[8], header[9]]);
+        let record = buf.get(..reclen as usize)?;
+        let name = record.get(Self::NAME_OFFSET..)?;
+        let name = &name[..name.iter().position(|&b| b == 0)?];
+
+        let field =
+            |i: usize| u64::from_le_bytes(header[16 + i * 8..24 + i * 8].try_into().unwrap());
+        let entry = Self {
+            inode: u64::from_le_bytes(header[0..8].try_into().unwrap()),
+            reclen,
+            stat: Stat {
+                mode: DirEntryType::try_from(header[10]).ok()?,
+                block_size: field(0),
+                blocks: field(1),
+                size: field(2),
+                atime: field(3),
+                mtime: field(4),
+                ctime: field(5),
+            },
+        };
+        Some((entry, core::str::from_utf8(name).ok()?))
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
@@ -36,3 +173,19 @@ pub enum DirEntryType {
     #[default]
     Regular,
 }
+
+impl TryFrom<u8> for DirEntryType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Block,
+            1 => Self::Char,
+            2 => Self::Directory,
+            3 => Self::Fifo,
+            4 => Self::SymLink,
+            5 => Self::Regular,
+            _ => return Err(value),
+        })
+    }
+}
@@ -6,4 +6,16 @@ pub enum Error {
     NotADirectory,
     DirectoryNotEmpty,
     Unsupported,
+    /// 参数不合法，如将目录移到其自身之下
+    InvalidInput,
+    /// 卷或定长的目录已满
+    NoSpace,
+    /// 名称超过[`NAME_MAX`](crate::NAME_MAX)
+    NameTooLong,
+    /// 跨越了文件系统，如硬链接到另一挂载点
+    CrossesDevices,
+    /// 存储设备上的数据损坏，如簇链表断裂
+    Io,
+    /// 文件将超过文件系统允许的最大字节数
+    FileTooLarge,
 }
@@ -0,0 +1,125 @@
+//! 文件系统及其索引节点的特型，内核经由特型对象访问各文件系统，无需知道其具体类型
+
+use alloc::sync::Arc;
+use core::any::Any;
+use core::fmt::Debug;
+
+use crate::{DirEntry, DirEntryType, Error, Stat};
+
+/// 可挂载的文件系统
+pub trait FileSystem: Send + Sync {
+    fn root(&self) -> Arc<dyn VfsInode>;
+
+    /// 写回所有修改，并在卷上标记为正常卸载
+    fn unmount(&self) {}
+}
+
+/// 文件系统中的文件或目录
+///
+/// 标明“文件”或“目录”的方法只适用于其一：
+/// 对文件调用目录的方法报[`Error::NotADirectory`]，反之报[`Error::IsADirectory`]。
+/// 有默认实现的是文件系统可以不支持的操作，默认报[`Error::Unsupported`]。
+#[allow(unused_variables)]
+pub trait VfsInode: Debug + Send + Sync {
+    /// 索引节点号，在文件系统内唯一
+    fn id(&self) -> u64;
+
+    fn kind(&self) -> DirEntryType;
+
+    fn stat(&self) -> Stat;
+
+    /// 文件的字节数，不必像[`stat`](VfsInode::stat)那样统计所占的块
+    fn size(&self) -> usize {
+        self.stat().size as usize
+    }
+
+    /// 文件：从字节偏移`offset`处读入`buf`，返回读到的字节数，越过末尾时为0
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> Result<usize, Error>;
+
+    /// 文件：在字节偏移`offset`处写入`buf`，必要时扩展文件，返回写入的字节数
+    fn write_at(&self, offset: usize, buf: &[u8]) -> Result<usize, Error>;
+
+    /// 文件：截断或扩展至`size`字节，扩展出的部分读出为0
+    fn truncate(&self, size: usize) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 文件的最大字节数
+    fn max_size(&self) -> usize {
+        usize::MAX
+    }
+
+    /// 文件：长为`size`字节时在卷上所占的字节数，按整块计，含文件系统为其分配的索引块
+    fn allocated(&self, size: usize) -> usize {
+        size
+    }
+
+    /// 属主的用户ID，不记录属主的文件系统中都属于超级用户
+    fn owner(&self) -> u32 {
+        0
+    }
+
+    /// 设置属主的用户ID
+    fn set_owner(&self, uid: u32) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 设置最后访问与最后修改时间，自UNIX纪元的秒数，为`None`的保持不变
+    fn set_times(&self, atime: Option<u64>, mtime: Option<u64>) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 文件：预读`[offset, offset + len)`，只是提示
+    fn prefetch(&self, offset: usize, len: usize) {}
+
+    /// 文件：写回并丢弃`[offset, offset + len)`的缓存，只是提示
+    fn release(&self, offset: usize, len: usize) {}
+
+    /// 目录：名为`name`的项
+    fn lookup(&self, name: &str) -> Result<Arc<dyn VfsInode>, Error>;
+
+    /// 目录：新建名为`name`的空文件，已存在时报[`Error::AlreadyExists`]
+    fn create(&self, name: &str) -> Result<Arc<dyn VfsInode>, Error>;
+
+    /// 目录：新建名为`name`的空目录
+    fn mkdir(&self, name: &str) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 目录：删除文件`name`，`name`是目录时报[`Error::IsADirectory`]
+    fn unlink(&self, name: &str) -> Result<(), Error>;
+
+    /// 目录：删除空目录`name`
+    fn rmdir(&self, name: &str) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 目录：为其下的`name`在目录`new_dir`下建立硬链接`new_name`
+    fn link(&self, name: &str, new_dir: &dyn VfsInode, new_name: &str) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 目录：将其下的`old_name`移至目录`new_dir`下的`new_name`，`new_name`已存在时替换之
+    fn rename(&self, old_name: &str, new_dir: &dyn VfsInode, new_name: &str) -> Result<(), Error> {
+        Err(Error::Unsupported)
+    }
+
+    /// 目录：从游标`offset`起依次将目录项交给`sink`，直到其返回`false`或读完，
+    /// 返回首个未被接受的目录项的游标。游标的含义由文件系统决定，0总是开头
+    fn read_dir(
+        &self,
+        offset: usize,
+        sink: &mut dyn FnMut(&DirEntry) -> bool,
+    ) -> Result<usize, Error>;
+
+    /// 目录：同[`read_dir`](VfsInode::read_dir)，并附上各项的状态，
+    /// 应在遍历目录时一并得出，而非逐项查找
+    fn read_dir_plus(
+        &self,
+        offset: usize,
+        sink: &mut dyn FnMut(&DirEntry, &Stat) -> bool,
+    ) -> Result<usize, Error>;
+
+    /// 供文件系统从`new_dir`等参数取回自己的具体类型，另一文件系统的索引节点取不回
+    fn as_any(&self) -> &dyn Any;
+}
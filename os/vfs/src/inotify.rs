@@ -0,0 +1,108 @@
+//! 文件系统事件的记录格式与事件位，仿Linux的`inotify_event`
+
+/// 文件被写入或截断
+pub const IN_MODIFY: u32 = 0x0000_0002;
+/// 重命名时移出了监视的目录
+pub const IN_MOVED_FROM: u32 = 0x0000_0040;
+/// 重命名时移入了监视的目录
+pub const IN_MOVED_TO: u32 = 0x0000_0080;
+/// 监视的目录下新建了文件或目录
+pub const IN_CREATE: u32 = 0x0000_0100;
+/// 监视的目录下删除了文件或目录
+pub const IN_DELETE: u32 = 0x0000_0200;
+/// 监视的路径本身被删除，随后的[`IN_IGNORED`]表示监视已撤销
+pub const IN_DELETE_SELF: u32 = 0x0000_0400;
+/// 监视的路径本身被移走，随后的[`IN_IGNORED`]表示监视已撤销
+pub const IN_MOVE_SELF: u32 = 0x0000_0800;
+/// 事件队列已满，此后的事件被丢弃
+pub const IN_Q_OVERFLOW: u32 = 0x0000_4000;
+/// 监视已撤销
+pub const IN_IGNORED: u32 = 0x0000_8000;
+/// 事件关于目录
+pub const IN_ISDIR: u32 = 0x4000_0000;
+
+/// 可以登记监视的全部事件
+pub const IN_ALL_EVENTS: u32 =
+    IN_MODIFY | IN_MOVED_FROM | IN_MOVED_TO | IN_CREATE | IN_DELETE | IN_DELETE_SELF | IN_MOVE_SELF;
+
+/// 新建监视实例的标志：无事件可读时读取立即失败，而不是阻塞
+pub const IN_NONBLOCK: usize = 0o4000;
+
+/// 读取监视实例所得的变长事件记录的头部
+///
+/// | 偏移 | 字段 |
+/// | ---- | ---- |
+/// | 0 | `wd: i32` |
+/// | 4 | `mask: u32` |
+/// | 8 | `cookie: u32` |
+/// | 12 | `len: u32` |
+/// | 16 | NUL结尾的名字，补齐到4字节 |
+///
+/// 整数均为小端序。事件关于监视的路径本身时没有名字，`len`为0。
+/// 同一次重命名的[`IN_MOVED_FROM`]与[`IN_MOVED_TO`]有相同的非零`cookie`。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CInotifyEvent {
+    /// 登记监视时得到的描述符
+    pub wd: i32,
+    pub mask: u32,
+    pub cookie: u32,
+    /// 名字连同补齐所占的字节数
+    pub len: u32,
+}
+
+impl CInotifyEvent {
+    /// 名字在记录中的偏移量
+    pub const NAME_OFFSET: usize = 16;
+    const ALIGN: usize = 4;
+
+    /// 名字长`name_len`字节的记录中，名字连同补齐所占的字节数
+    pub const fn name_space(name_len: usize) -> usize {
+        if name_len == 0 {
+            0
+        } else {
+            (name_len + 1).next_multiple_of(Self::ALIGN)
+        }
+    }
+
+    /// 名字长`name_len`字节的记录所占的字节数
+    pub const fn reclen(name_len: usize) -> usize {
+        Self::NAME_OFFSET + Self::name_space(name_len)
+    }
+
+    /// 将事件编码为一条记录写入`buf`开头，`len`按`name`重新计算，返回记录长度；
+    /// `buf`放不下时返回`None`
+    pub fn encode(&self, name: &str, buf: &mut [u8]) -> Option<usize> {
+        let name = name.as_bytes();
+        let reclen = Self::reclen(name.len());
+        let record = buf.get_mut(..reclen)?;
+
+        record.fill(0);
+        record[0..4].copy_from_slice(&self.wd.to_le_bytes());
+        record[4..8].copy_from_slice(&self.mask.to_le_bytes());
+        record[8..12].copy_from_slice(&self.cookie.to_le_bytes());
+        record[12..16].copy_from_slice(&(Self::name_space(name.len()) as u32).to_le_bytes());
+        record[Self::NAME_OFFSET..Self::NAME_OFFSET + name.len()].copy_from_slice(name);
+        Some(reclen)
+    }
+
+    /// 解析`buf`开头的一条记录，返回其头部与名字；记录残缺时返回`None`
+    pub fn decode(buf: &[u8]) -> Option<(Self, &str)> {
+        let header = buf.get(..Self::NAME_OFFSET)?;
+        let field = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap());
+        let event = Self {
+            wd: field(0) as i32,
+            mask: field(4),
+            cookie: field(8),
+            len: field(12),
+        };
+
+        let name = buf.get(Self::NAME_OFFSET..Self::NAME_OFFSET + event.len as usize)?;
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        Some((event, core::str::from_utf8(name).ok()?))
+    }
+
+    /// 整条记录的字节数
+    pub fn record_len(&self) -> usize {
+        Self::NAME_OFFSET + self.len as usize
+    }
+}
@@ -1,13 +1,23 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 extern crate alloc;
 
 mod dirent;
 mod error;
+mod inode;
+pub mod inotify;
+mod limits;
+pub mod quota;
 mod stat;
+#[cfg(test)]
+mod tests;
 
 pub use self::{
-    dirent::{CDirEntry, DirEntry, DirEntryType},
+    dirent::{CDirEntry, CDirEntryPlus, DirEntry, DirEntryType},
     error::Error,
+    inode::{FileSystem, VfsInode},
+    inotify::CInotifyEvent,
+    limits::{NAME_MAX, PATH_MAX},
+    quota::DiskQuota,
     stat::Stat,
 };
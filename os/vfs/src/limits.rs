@@ -0,0 +1,7 @@
+//! 内核与用户程序共用的路径长度限制
+
+/// 路径的最大字节数，含结尾的`\0`
+pub const PATH_MAX: usize = 4096;
+
+/// 路径中一项（文件或目录名）的最大字节数
+pub const NAME_MAX: usize = 255;
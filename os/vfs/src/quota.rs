@@ -0,0 +1,132 @@
+//! 磁盘配额：按用户统计其名下普通文件占用的块数，限制其增长。
+//! 命令与记录仿Linux的`quotactl`与`if_dqblk`而从简，只有用户配额
+
+/// 写回配额文件
+pub const Q_SYNC: usize = 0x80_0001;
+/// 读取一个用户的配额
+pub const Q_GETQUOTA: usize = 0x80_0007;
+/// 设置一个用户的上限，已用空间与宽限期由内核维护
+pub const Q_SETQUOTA: usize = 0x80_0008;
+
+/// 系统调用只有3个参数，同Linux的`QCMD`将用户ID并入命令的高32位
+pub const fn qcmd(cmd: usize, id: u32) -> usize {
+    cmd | (id as usize) << 32
+}
+
+/// [`qcmd`]的逆运算，返回命令与用户ID
+pub const fn split_qcmd(raw: usize) -> (usize, u32) {
+    (raw & 0xFFFF_FFFF, (raw >> 32) as u32)
+}
+
+/// 上限的单位，同Linux为1KiB
+pub const QUOTA_BLOCK: u64 = 1024;
+/// 超过软上限后的宽限期，同Linux的默认值为7天
+pub const GRACE_SECS: u64 = 7 * 24 * 60 * 60;
+/// 配额文件在文件系统根目录下的名称
+pub const QUOTA_FILE: &str = "aquota.user";
+
+/// 一个用户在一个文件系统上的配额
+///
+/// | 偏移 | 字段 |
+/// | ---- | ---- |
+/// | 0 | `hard_blocks: u64` |
+/// | 8 | `soft_blocks: u64` |
+/// | 16 | `used_blocks: u64` |
+/// | 24 | `grace_until: u64` |
+///
+/// 上限为0表示不限。配额文件中每条记录前另有4字节的小端序用户ID与4字节的补齐。
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiskQuota {
+    /// 硬上限，以[`QUOTA_BLOCK`]为单位，任何时候都不能超过
+    pub hard_blocks: u64,
+    /// 软上限，以[`QUOTA_BLOCK`]为单位，超过后宽限期内仍可增长
+    pub soft_blocks: u64,
+    /// 已用的块数，以[`QUOTA_BLOCK`]为单位，每个文件所占的空间不足一块的按一块计
+    pub used_blocks: u64,
+    /// 超过软上限时，宽限期结束的时刻，自UNIX纪元的秒数；未超过时为0
+    pub grace_until: u64,
+}
+
+impl DiskQuota {
+    /// 配额文件中一条记录的字节数
+    pub const RECORD_SIZE: usize = 40;
+
+    /// 占用`bytes`字节的文件所计的块数
+    pub const fn blocks(bytes: u64) -> u64 {
+        bytes.div_ceil(QUOTA_BLOCK)
+    }
+
+    /// 在时刻`now`再占用`blocks`块是否超出配额。
+    /// 不超过硬上限时，越过软上限总是允许的，宽限期过后才不允许继续增长
+    pub fn exceeds(&self, blocks: u64, now: u64) -> bool {
+        if blocks == 0 {
+            return false;
+        }
+        let blocks = self.used_blocks.saturating_add(blocks);
+        let over_hard = self.hard_blocks != 0 && blocks > self.hard_blocks;
+        let grace_over = self.grace_until != 0 && now >= self.grace_until;
+        let over_soft = self.soft_blocks != 0 && blocks > self.soft_blocks && grace_over;
+        over_hard || over_soft
+    }
+
+    /// 在时刻`now`占用改变了`delta`块，不会减到0以下
+    pub fn charge(&mut self, delta: i64, now: u64) {
+        self.used_blocks = self.used_blocks.saturating_add_signed(delta);
+        self.update_grace(now);
+    }
+
+    /// 在时刻`now`设置上限
+    pub fn set_limits(&mut self, hard_blocks: u64, soft_blocks: u64, now: u64) {
+        self.hard_blocks = hard_blocks;
+        self.soft_blocks = soft_blocks;
+        self.update_grace(now);
+    }
+
+    /// 越过软上限时开始计算宽限期，回落后清除
+    fn update_grace(&mut self, now: u64) {
+        let over_soft = self.soft_blocks != 0 && self.used_blocks > self.soft_blocks;
+        if !over_soft {
+            self.grace_until = 0;
+        } else if self.grace_until == 0 {
+            self.grace_until = now + GRACE_SECS;
+        }
+    }
+
+    /// 没有上限也没有占用，不必记录
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// 将`uid`的配额编码为一条记录写入`buf`开头，`buf`放不下时返回`None`
+    pub fn encode(&self, uid: u32, buf: &mut [u8]) -> Option<()> {
+        let record = buf.get_mut(..Self::RECORD_SIZE)?;
+        record[0..4].copy_from_slice(&uid.to_le_bytes());
+        record[4..8].fill(0);
+        let fields = [
+            self.hard_blocks,
+            self.soft_blocks,
+            self.used_blocks,
+            self.grace_until,
+        ];
+        for (i, field) in fields.into_iter().enumerate() {
+            record[8 + i * 8..16 + i * 8].copy_from_slice(&field.to_le_bytes());
+        }
+        Some(())
+    }
+
+    /// 解析`buf`开头的一条记录，返回用户ID与其配额；记录残缺时返回`None`
+    pub fn decode(buf: &[u8]) -> Option<(u32, Self)> {
+        let record = buf.get(..Self::RECORD_SIZE)?;
+        let uid = u32::from_le_bytes(record[0..4].try_into().unwrap());
+        let field =
+            |i: usize| u64::from_le_bytes(record[8 + i * 8..16 + i * 8].try_into().unwrap());
+        let quota = Self {
+            hard_blocks: field(0),
+            soft_blocks: field(1),
+            used_blocks: field(2),
+            grace_until: field(3),
+        };
+        Some((uid, quota))
+    }
+}
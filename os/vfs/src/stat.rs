@@ -1,6 +1,6 @@
 use crate::DirEntryType;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(C)]
 pub struct Stat {
     pub mode: DirEntryType,
@@ -10,4 +10,10 @@ pub struct Stat {
     pub blocks: u64,
     /// File size
     pub size: u64,
+    /// Last access time, in seconds since the UNIX epoch; 0 if unknown
+    pub atime: u64,
+    /// Last modification time
+    pub mtime: u64,
+    /// Last status change time
+    pub ctime: u64,
 }
@@ -0,0 +1,174 @@
+use alloc::string::String;
+
+use crate::inotify::{IN_CREATE, IN_DELETE_SELF};
+use crate::quota::{qcmd, split_qcmd, GRACE_SECS, QUOTA_BLOCK, Q_GETQUOTA};
+use crate::{CDirEntry, CDirEntryPlus, CInotifyEvent, DirEntry, DirEntryType, DiskQuota, Stat};
+
+#[test]
+fn dirent_records() {
+    let dirents = [
+        DirEntry {
+            inode: 2,
+            ty: DirEntryType::Directory,
+            name: String::from("bin"),
+        },
+        DirEntry {
+            inode: u64::MAX,
+            ty: DirEntryType::Regular,
+            name: String::from("hello_world"),
+        },
+    ];
+
+    let mut buf = [0xff; 48];
+    let mut len = 0;
+    for dirent in &dirents {
+        len += CDirEntry::encode(dirent, &mut buf[len..]).unwrap();
+    }
+    assert_eq!(len, CDirEntry::reclen(3) + CDirEntry::reclen(11));
+    assert_eq!(len % 8, 0);
+    // 放不下的记录不写入
+    assert_eq!(CDirEntry::encode(&dirents[1], &mut buf[len..]), None);
+
+    let mut rest = &buf[..len];
+    for dirent in &dirents {
+        let (entry, name) = CDirEntry::decode(rest).unwrap();
+        assert_eq!(entry.inode, dirent.inode);
+        assert_eq!(entry.ty, dirent.ty);
+        assert_eq!(name, dirent.name);
+        rest = &rest[entry.reclen as usize..];
+    }
+    assert!(rest.is_empty());
+    assert_eq!(CDirEntry::decode(&buf[..5]), None);
+}
+
+#[test]
+fn dirent_plus_records() {
+    let dirent = DirEntry {
+        inode: 7,
+        ty: DirEntryType::Regular,
+        name: String::from("hello_world"),
+    };
+    let stat = Stat {
+        mode: DirEntryType::Regular,
+        block_size: 512,
+        blocks: 3,
+        size: 1025,
+        atime: 1,
+        mtime: 2,
+        ctime: u64::MAX,
+    };
+
+    let mut buf = [0xff; 96];
+    let len = CDirEntryPlus::encode(&dirent, &stat, &mut buf).unwrap();
+    assert_eq!(len, CDirEntryPlus::reclen(11));
+    assert_eq!(len % 8, 0);
+    assert_eq!(CDirEntryPlus::encode(&dirent, &stat, &mut buf[len..]), None);
+    // 头部与普通记录相同
+    let (entry, name) = CDirEntry::decode(&buf[..len]).unwrap();
+    assert_eq!(
+        (entry.inode, entry.ty, name),
+        (7, DirEntryType::Regular, "")
+    );
+
+    let (entry, name) = CDirEntryPlus::decode(&buf).unwrap();
+    assert_eq!(entry.reclen as usize, len);
+    assert_eq!(entry.inode, dirent.inode);
+    assert_eq!(entry.stat, stat);
+    assert_eq!(name, dirent.name);
+    assert_eq!(
+        CDirEntryPlus::decode(&buf[..CDirEntryPlus::NAME_OFFSET]),
+        None
+    );
+}
+
+#[test]
+fn inotify_records() {
+    let events = [
+        (
+            CInotifyEvent {
+                wd: 1,
+                mask: IN_CREATE,
+                cookie: 0,
+                len: 0,
+            },
+            "abc",
+        ),
+        (
+            CInotifyEvent {
+                wd: -1,
+                mask: IN_DELETE_SELF,
+                cookie: 7,
+                len: 0,
+            },
+            "",
+        ),
+    ];
+
+    let mut buf = [0xff; 36];
+    let mut len = 0;
+    for (event, name) in &events {
+        len += event.encode(name, &mut buf[len..]).unwrap();
+    }
+    // 名字连同NUL补齐到4字节，没有名字时不占空间
+    assert_eq!(len, CInotifyEvent::reclen(3) + CInotifyEvent::reclen(0));
+    assert_eq!(CInotifyEvent::reclen(3), 20);
+    assert_eq!(CInotifyEvent::reclen(4), 24);
+    assert_eq!(events[0].0.encode("abcd", &mut buf[len..]), None);
+
+    let mut rest = &buf[..len];
+    for (event, name) in &events {
+        let (decoded, decoded_name) = CInotifyEvent::decode(rest).unwrap();
+        assert_eq!(decoded.wd, event.wd);
+        assert_eq!(decoded.mask, event.mask);
+        assert_eq!(decoded.cookie, event.cookie);
+        assert_eq!(decoded_name, *name);
+        rest = &rest[decoded.record_len()..];
+    }
+    assert!(rest.is_empty());
+    assert_eq!(CInotifyEvent::decode(&buf[..12]), None);
+}
+
+#[test]
+fn quota_limits_and_records() {
+    let mut quota = DiskQuota::default();
+    quota.set_limits(4, 2, 100);
+    assert!(!quota.exceeds(4, 100));
+    assert!(quota.exceeds(5, 100));
+    // 不增长时总是允许的
+    assert!(!quota.exceeds(0, 100));
+    assert_eq!(DiskQuota::blocks(QUOTA_BLOCK + 1), 2);
+
+    // 越过软上限后开始宽限期，期满后不能再增长，回落后清除
+    quota.charge(3, 100);
+    assert_eq!(quota.used_blocks, 3);
+    assert_eq!(quota.grace_until, 100 + GRACE_SECS);
+    assert!(!quota.exceeds(1, 100 + GRACE_SECS - 1));
+    assert!(quota.exceeds(1, 100 + GRACE_SECS));
+    quota.charge(-2, 200);
+    assert_eq!(quota.grace_until, 0);
+    quota.charge(-5, 200);
+    assert_eq!(quota.used_blocks, 0);
+    assert!(!quota.is_empty());
+
+    let records = [(0, quota), (1000, DiskQuota::default())];
+    let mut buf = [0xff; 2 * DiskQuota::RECORD_SIZE];
+    for (i, (uid, quota)) in records.iter().enumerate() {
+        quota
+            .encode(*uid, &mut buf[i * DiskQuota::RECORD_SIZE..])
+            .unwrap();
+    }
+    assert_eq!(buf[4..8], [0; 4]);
+    assert_eq!(
+        quota.encode(0, &mut buf[DiskQuota::RECORD_SIZE + 1..]),
+        None
+    );
+    for (i, record) in buf.chunks(DiskQuota::RECORD_SIZE).enumerate() {
+        assert_eq!(DiskQuota::decode(record), Some(records[i]));
+    }
+    assert_eq!(DiskQuota::decode(&buf[1..DiskQuota::RECORD_SIZE]), None);
+
+    assert_eq!(
+        split_qcmd(qcmd(Q_GETQUOTA, u32::MAX)),
+        (Q_GETQUOTA, u32::MAX)
+    );
+}
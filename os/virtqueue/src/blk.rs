@@ -0,0 +1,110 @@
+//! virtio块设备的请求，见virtio规范1.1的5.2.6节
+//!
+//! 一个请求是一条描述符链：设备读取的头部，依次对应连续扇区的若干数据缓冲区，
+//! 以及设备写入的状态，各占一个描述符。
+
+use alloc::vec::Vec;
+use core::ptr;
+
+use crate::queue::bytes_of;
+use crate::{Hal, VirtQueue};
+
+/// 扇区的字节数，请求以扇区寻址
+pub const SECTOR_SIZE: usize = 512;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+
+/// 设备尚未写入状态
+const STATUS_NOT_READY: u8 = u8::MAX;
+
+#[repr(C)]
+#[derive(Debug, Default)]
+struct Header {
+    ty: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// 请求的头部与状态，设备完成请求前须保持有效
+#[derive(Debug)]
+pub struct BlkReq {
+    header: Header,
+    status: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RespStatus {
+    Ok,
+    IoErr,
+    Unsupported,
+    NotReady,
+}
+
+impl Default for BlkReq {
+    fn default() -> Self {
+        Self {
+            header: Header::default(),
+            status: STATUS_NOT_READY,
+        }
+    }
+}
+
+impl BlkReq {
+    /// 设备写入的状态
+    pub fn status(&self) -> RespStatus {
+        match unsafe { ptr::from_ref(&self.status).read_volatile() } {
+            0 => RespStatus::Ok,
+            1 => RespStatus::IoErr,
+            2 => RespStatus::Unsupported,
+            _ => RespStatus::NotReady,
+        }
+    }
+
+    /// 以一条链读取从`sector`起的连续扇区，依次填入`bufs`，返回令牌；描述符不足时返回`None`
+    ///
+    /// # Safety
+    ///
+    /// `self`与`bufs`须保持有效，直到以令牌从[`VirtQueue::pop_used`]取回
+    pub unsafe fn add_read<H: Hal>(
+        &mut self,
+        queue: &mut VirtQueue<H>,
+        sector: usize,
+        bufs: &mut [&mut [u8]],
+    ) -> Option<u16> {
+        self.prepare(VIRTIO_BLK_T_IN, sector);
+        let mut outputs: Vec<*mut [u8]> = bufs.iter_mut().map(|buf| ptr::from_mut(*buf)).collect();
+        outputs.push(ptr::slice_from_raw_parts_mut(&mut self.status, 1));
+        queue.add(&[bytes_of(&self.header)], &outputs)
+    }
+
+    /// 以一条链将`bufs`依次写入从`sector`起的连续扇区，返回令牌；描述符不足时返回`None`
+    ///
+    /// # Safety
+    ///
+    /// 同[`add_read`](Self::add_read)
+    pub unsafe fn add_write<H: Hal>(
+        &mut self,
+        queue: &mut VirtQueue<H>,
+        sector: usize,
+        bufs: &[&[u8]],
+    ) -> Option<u16> {
+        self.prepare(VIRTIO_BLK_T_OUT, sector);
+        let mut inputs = Vec::with_capacity(bufs.len() + 1);
+        inputs.push(bytes_of(&self.header));
+        inputs.extend(bufs.iter().map(|buf| ptr::from_ref(*buf)));
+        queue.add(
+            &inputs,
+            &[ptr::slice_from_raw_parts_mut(&mut self.status, 1)],
+        )
+    }
+
+    fn prepare(&mut self, ty: u32, sector: usize) {
+        self.header = Header {
+            ty,
+            reserved: 0,
+            sector: sector as u64,
+        };
+        self.status = STATUS_NOT_READY;
+    }
+}
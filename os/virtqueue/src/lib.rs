@@ -0,0 +1,19 @@
+//! # virtio的分离式虚拟队列
+//!
+//! virtio-drivers不开放其虚拟队列，无法构造含多个数据描述符的链，故在此自行实现。
+//! 只支持virtio-mmio旧版(legacy)接口的布局：描述符表、可用环与按页对齐的已用环
+//! 位于一段物理上连续的内存中，以页帧号告知设备。
+//!
+//! [`blk`]在队列之上构造块设备请求，一条描述符链即可覆盖内存中不连续的多个缓冲区。
+
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod blk;
+mod queue;
+
+#[cfg(test)]
+mod tests;
+
+pub use self::queue::{Hal, VirtQueue, PAGE_SIZE};
@@ -0,0 +1,218 @@
+//! 分离式虚拟队列，见virtio规范1.1的2.6节
+
+use core::marker::PhantomData;
+use core::mem::size_of;
+use core::ptr::{self, NonNull};
+use core::sync::atomic::{fence, Ordering};
+
+pub const PAGE_SIZE: usize = 4096;
+
+/// 描述符还链向下一个
+const DESC_F_NEXT: u16 = 1;
+/// 缓冲区供设备写入
+const DESC_F_WRITE: u16 = 2;
+
+/// 队列内存的分配与地址转换，设备只认物理地址
+pub trait Hal {
+    /// 分配`pages`个物理上连续的页，返回其物理地址
+    fn dma_alloc(pages: usize) -> usize;
+    fn dma_dealloc(paddr: usize, pages: usize);
+    fn phys_to_virt(paddr: usize) -> usize;
+    fn virt_to_phys(vaddr: usize) -> usize;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// 已用环中的项：链首描述符的序号，及设备写入的字节数
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct UsedElem {
+    id: u32,
+    len: u32,
+}
+
+/// 容纳`size`项的队列中已用环的偏移，及队列所占的页数
+pub(crate) fn layout(size: usize) -> (usize, usize) {
+    // 可用环与已用环都以flags与idx开头，以used_event或avail_event结尾
+    let driver = size_of::<Descriptor>() * size + size_of::<u16>() * (3 + size);
+    let used_offset = driver.next_multiple_of(PAGE_SIZE);
+    let device = size_of::<u16>() * 3 + size_of::<UsedElem>() * size;
+    (used_offset, (used_offset + device).div_ceil(PAGE_SIZE))
+}
+
+/// 驱动一侧的虚拟队列。未用的描述符经`next`串成空闲链表
+#[derive(Debug)]
+pub struct VirtQueue<H: Hal> {
+    paddr: usize,
+    pages: usize,
+    size: u16,
+    desc: NonNull<Descriptor>,
+    /// 可用环：flags、idx、ring
+    avail: NonNull<u16>,
+    /// 已用环：flags、idx，其后是[`UsedElem`]
+    used: NonNull<u16>,
+    free_head: u16,
+    num_free: u16,
+    /// 已放入可用环的链数，回绕计数
+    avail_idx: u16,
+    /// 已从已用环取回的链数，回绕计数
+    last_used_idx: u16,
+    _hal: PhantomData<H>,
+}
+
+unsafe impl<H: Hal> Send for VirtQueue<H> {}
+
+impl<H: Hal> VirtQueue<H> {
+    /// 分配容纳`size`个描述符的队列，`size`须是2的幂
+    pub fn new(size: u16) -> Self {
+        assert!(size.is_power_of_two(), "queue size must be a power of 2");
+        let (used_offset, pages) = layout(size as usize);
+        let paddr = H::dma_alloc(pages);
+        let vaddr = H::phys_to_virt(paddr) as *mut u8;
+        unsafe { vaddr.write_bytes(0, pages * PAGE_SIZE) };
+
+        let desc = NonNull::new(vaddr.cast::<Descriptor>()).unwrap();
+        for i in 0..size {
+            unsafe { (*desc.as_ptr().add(i as usize)).next = i + 1 };
+        }
+        let avail = unsafe { desc.add(size as usize).cast() };
+        let used = NonNull::new(unsafe { vaddr.add(used_offset) }.cast()).unwrap();
+
+        Self {
+            paddr,
+            pages,
+            size,
+            desc,
+            avail,
+            used,
+            free_head: 0,
+            num_free: size,
+            avail_idx: 0,
+            last_used_idx: 0,
+            _hal: PhantomData,
+        }
+    }
+
+    /// 队列内存的物理页帧号，以之设置设备的QueuePFN
+    pub fn pfn(&self) -> u32 {
+        (self.paddr() / PAGE_SIZE) as u32
+    }
+
+    /// 队列内存的物理地址
+    pub(crate) fn paddr(&self) -> usize {
+        self.paddr
+    }
+
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    /// 尚未使用的描述符数，一条链至多用去这么多
+    pub fn num_free(&self) -> usize {
+        self.num_free as usize
+    }
+
+    /// 将设备读取的`inputs`与其后设备写入的`outputs`串成一条描述符链放入可用环，
+    /// 返回令牌，即链首描述符的序号；描述符不足时返回`None`。放入后须通知设备
+    ///
+    /// # Safety
+    ///
+    /// 缓冲区须保持有效，直到以同一令牌从[`pop_used`](Self::pop_used)取回
+    pub unsafe fn add(&mut self, inputs: &[*const [u8]], outputs: &[*mut [u8]]) -> Option<u16> {
+        let count = inputs.len() + outputs.len();
+        if count == 0 || count > self.num_free() {
+            return None;
+        }
+
+        let bufs = inputs
+            .iter()
+            .map(|buf| (buf.cast::<u8>() as usize, buf.len(), 0))
+            .chain(
+                outputs
+                    .iter()
+                    .map(|buf| (buf.cast::<u8>() as usize, buf.len(), DESC_F_WRITE)),
+            );
+        let head = self.free_head;
+        let mut last = head;
+        for (vaddr, len, flags) in bufs {
+            last = self.free_head;
+            let desc = self.desc(last);
+            desc.addr = H::virt_to_phys(vaddr) as u64;
+            desc.len = len as u32;
+            desc.flags = flags | DESC_F_NEXT;
+            let next = desc.next;
+            self.free_head = next;
+        }
+        // 链尾的`next`仍指向空闲链表，回收时接回
+        self.desc(last).flags &= !DESC_F_NEXT;
+        self.num_free -= count as u16;
+
+        let slot = (self.avail_idx & (self.size - 1)) as usize;
+        self.avail.add(2 + slot).write_volatile(head);
+        // 描述符与环中的项须先于索引对设备可见
+        fence(Ordering::SeqCst);
+        self.avail_idx = self.avail_idx.wrapping_add(1);
+        self.avail.add(1).write_volatile(self.avail_idx);
+        Some(head)
+    }
+
+    /// 设备是否用完了尚未取回的链
+    pub fn can_pop(&self) -> bool {
+        fence(Ordering::SeqCst);
+        self.last_used_idx != unsafe { self.used.add(1).read_volatile() }
+    }
+
+    /// 取回设备用完的一条链，回收其描述符，返回其令牌与设备写入的字节数
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        if !self.can_pop() {
+            return None;
+        }
+        let slot = (self.last_used_idx & (self.size - 1)) as usize;
+        let elem = unsafe {
+            self.used
+                .add(2)
+                .cast::<UsedElem>()
+                .add(slot)
+                .read_volatile()
+        };
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+
+        let head = elem.id as u16;
+        let free_head = self.free_head;
+        let mut id = head;
+        loop {
+            self.num_free += 1;
+            let desc = self.desc(id);
+            if desc.flags & DESC_F_NEXT == 0 {
+                desc.next = free_head;
+                break;
+            }
+            id = desc.next;
+        }
+        self.free_head = head;
+        Some((head, elem.len))
+    }
+
+    fn desc(&mut self, id: u16) -> &mut Descriptor {
+        assert!(id < self.size);
+        unsafe { &mut *self.desc.as_ptr().add(id as usize) }
+    }
+}
+
+impl<H: Hal> Drop for VirtQueue<H> {
+    fn drop(&mut self) {
+        H::dma_dealloc(self.paddr, self.pages);
+    }
+}
+
+/// `value`所在的字节，作为描述符的缓冲区
+pub(crate) fn bytes_of<T>(value: *const T) -> *const [u8] {
+    ptr::slice_from_raw_parts(value.cast(), size_of::<T>())
+}
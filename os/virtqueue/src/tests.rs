@@ -0,0 +1,261 @@
+//! 以内存中的模拟设备处理队列中的请求，设备只按规范从队列的地址与长度找到各个环
+
+use alloc::vec;
+use alloc::vec::Vec;
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::ptr;
+
+use crate::blk::{BlkReq, RespStatus, SECTOR_SIZE};
+use crate::queue::layout;
+use crate::{Hal, VirtQueue, PAGE_SIZE};
+
+/// 物理地址即虚拟地址
+struct TestHal;
+
+impl Hal for TestHal {
+    fn dma_alloc(pages: usize) -> usize {
+        unsafe { alloc_zeroed(page_layout(pages)) as usize }
+    }
+
+    fn dma_dealloc(paddr: usize, pages: usize) {
+        unsafe { dealloc(paddr as *mut u8, page_layout(pages)) }
+    }
+
+    fn phys_to_virt(paddr: usize) -> usize {
+        paddr
+    }
+
+    fn virt_to_phys(vaddr: usize) -> usize {
+        vaddr
+    }
+}
+
+fn page_layout(pages: usize) -> Layout {
+    Layout::from_size_align(pages * PAGE_SIZE, PAGE_SIZE).unwrap()
+}
+
+/// 模拟的块设备，收到通知时处理可用环中的新请求
+struct MockDisk {
+    sectors: Vec<[u8; SECTOR_SIZE]>,
+    base: usize,
+    size: u16,
+    last_avail: u16,
+    used_idx: u16,
+    /// 处理过的请求数，即描述符链数
+    requests: usize,
+    /// 各请求的描述符数
+    chain_lens: Vec<usize>,
+}
+
+impl MockDisk {
+    fn new(queue: &VirtQueue<TestHal>, sectors: usize) -> Self {
+        Self {
+            sectors: (0..sectors).map(|i| [i as u8; SECTOR_SIZE]).collect(),
+            // 测试进程的地址超出旧版接口32位的页帧号
+            base: queue.paddr(),
+            size: queue.size(),
+            last_avail: 0,
+            used_idx: 0,
+            requests: 0,
+            chain_lens: Vec::new(),
+        }
+    }
+
+    unsafe fn read<T>(&self, offset: usize) -> T {
+        ptr::read_volatile((self.base + offset) as *const T)
+    }
+
+    unsafe fn write<T>(&self, offset: usize, value: T) {
+        ptr::write_volatile((self.base + offset) as *mut T, value)
+    }
+
+    /// 第`id`个描述符的地址、长度、标志与下一个
+    unsafe fn desc(&self, id: u16) -> (usize, usize, u16, u16) {
+        let offset = 16 * id as usize;
+        (
+            self.read::<u64>(offset) as usize,
+            self.read::<u32>(offset + 8) as usize,
+            self.read(offset + 12),
+            self.read(offset + 14),
+        )
+    }
+
+    fn notify(&mut self) {
+        let size = self.size as usize;
+        let avail = 16 * size;
+        let (used, _) = layout(size);
+        unsafe {
+            while self.last_avail != self.read::<u16>(avail + 2) {
+                let slot = (self.last_avail % self.size) as usize;
+                let head: u16 = self.read(avail + 4 + 2 * slot);
+                self.last_avail = self.last_avail.wrapping_add(1);
+
+                let mut chain = vec![self.desc(head)];
+                while chain.last().unwrap().2 & 1 != 0 {
+                    let next = chain.last().unwrap().3;
+                    chain.push(self.desc(next));
+                }
+                let written = self.process(&chain);
+
+                let slot = (self.used_idx % self.size) as usize;
+                self.write(used + 4 + 8 * slot, head as u32);
+                self.write(used + 8 + 8 * slot, written as u32);
+                self.used_idx = self.used_idx.wrapping_add(1);
+                self.write(used + 2, self.used_idx);
+                self.requests += 1;
+                self.chain_lens.push(chain.len());
+            }
+        }
+    }
+
+    /// 执行一条请求链，返回写入驱动内存的字节数
+    unsafe fn process(&mut self, chain: &[(usize, usize, u16, u16)]) -> usize {
+        let [header, data @ .., status] = chain else {
+            panic!("request chain too short");
+        };
+        assert_eq!(header.1, 16);
+        assert_eq!(header.2 & 2, 0, "header must be device-readable");
+        assert_eq!(status.1, 1);
+        assert_ne!(status.2 & 2, 0, "status must be device-writable");
+        let ty = ptr::read(header.0 as *const u32);
+        let mut sector = ptr::read((header.0 + 8) as *const u64) as usize;
+
+        let mut written = 1;
+        for &(addr, len, flags, _) in data {
+            assert_eq!(len % SECTOR_SIZE, 0);
+            for chunk in (addr..addr + len).step_by(SECTOR_SIZE) {
+                let buf = chunk as *mut [u8; SECTOR_SIZE];
+                match ty {
+                    0 => {
+                        assert_ne!(flags & 2, 0);
+                        *buf = self.sectors[sector];
+                        written += SECTOR_SIZE;
+                    }
+                    1 => {
+                        assert_eq!(flags & 2, 0);
+                        self.sectors[sector] = *buf;
+                    }
+                    _ => panic!("unexpected request type {ty}"),
+                }
+                sector += 1;
+            }
+        }
+        *(status.0 as *mut u8) = 0;
+        written
+    }
+}
+
+#[test]
+fn layout_aligns_used_ring() {
+    assert_eq!(layout(16), (PAGE_SIZE, 2));
+    assert_eq!(layout(256), (2 * PAGE_SIZE, 3));
+}
+
+#[test]
+fn scattered_read_is_one_request() {
+    let mut queue = VirtQueue::<TestHal>::new(16);
+    let mut disk = MockDisk::new(&queue, 16);
+
+    // 缓冲区在内存中各不相邻
+    let mut bufs: Vec<Vec<u8>> = (0..4).map(|_| vec![0xEE; 3 * SECTOR_SIZE]).collect();
+    let mut slices: Vec<&mut [u8]> = bufs
+        .iter_mut()
+        .map(|buf| &mut buf[SECTOR_SIZE..2 * SECTOR_SIZE])
+        .collect();
+    let mut req = BlkReq::default();
+    let token = unsafe { req.add_read(&mut queue, 5, &mut slices).unwrap() };
+    assert_eq!(queue.num_free(), 16 - 6);
+    assert_eq!(req.status(), RespStatus::NotReady);
+
+    disk.notify();
+    assert_eq!(disk.requests, 1);
+    assert_eq!(disk.chain_lens, [6]);
+    assert_eq!(queue.pop_used(), Some((token, 1 + 4 * SECTOR_SIZE as u32)));
+    assert_eq!(req.status(), RespStatus::Ok);
+    assert_eq!(queue.num_free(), 16);
+    for (i, buf) in bufs.iter().enumerate() {
+        assert!(buf[SECTOR_SIZE..2 * SECTOR_SIZE]
+            .iter()
+            .all(|&b| b == 5 + i as u8));
+        // 缓冲区之外不受影响
+        assert!(buf[..SECTOR_SIZE].iter().all(|&b| b == 0xEE));
+        assert!(buf[2 * SECTOR_SIZE..].iter().all(|&b| b == 0xEE));
+    }
+}
+
+#[test]
+fn gathered_write_is_one_request() {
+    let mut queue = VirtQueue::<TestHal>::new(8);
+    let mut disk = MockDisk::new(&queue, 8);
+
+    let bufs: Vec<[u8; SECTOR_SIZE]> = (0..3).map(|i| [0xA0 + i; SECTOR_SIZE]).collect();
+    let slices: Vec<&[u8]> = bufs.iter().map(|buf| &buf[..]).collect();
+    let mut req = BlkReq::default();
+    let token = unsafe { req.add_write(&mut queue, 2, &slices).unwrap() };
+    disk.notify();
+
+    assert_eq!(disk.requests, 1);
+    assert_eq!(disk.chain_lens, [5]);
+    assert_eq!(queue.pop_used(), Some((token, 1)));
+    assert_eq!(req.status(), RespStatus::Ok);
+    assert_eq!(disk.sectors[1], [1; SECTOR_SIZE]);
+    for (i, buf) in bufs.iter().enumerate() {
+        assert_eq!(disk.sectors[2 + i], *buf);
+    }
+    assert_eq!(disk.sectors[5], [5; SECTOR_SIZE]);
+}
+
+#[test]
+fn chains_need_enough_descriptors() {
+    let mut queue = VirtQueue::<TestHal>::new(4);
+    let mut disk = MockDisk::new(&queue, 8);
+    let mut bufs = [[0; SECTOR_SIZE]; 3];
+
+    // 头部与状态另占两个描述符
+    let mut req = BlkReq::default();
+    let mut slices: Vec<&mut [u8]> = bufs.iter_mut().map(|buf| &mut buf[..]).collect();
+    assert!(unsafe { req.add_read(&mut queue, 0, &mut slices) }.is_none());
+    assert_eq!(queue.num_free(), 4);
+
+    let token = unsafe { req.add_read(&mut queue, 0, &mut slices[..2]).unwrap() };
+    let mut other = BlkReq::default();
+    assert!(unsafe { other.add_read(&mut queue, 4, &mut slices[2..]) }.is_none());
+    disk.notify();
+    assert_eq!(queue.pop_used().map(|(token, _)| token), Some(token));
+    assert!(queue.pop_used().is_none());
+    assert!(unsafe { other.add_read(&mut queue, 4, &mut slices[2..]) }.is_some());
+}
+
+/// 描述符与两个环反复回绕后仍正确回收
+#[test]
+fn descriptors_are_recycled() {
+    let mut queue = VirtQueue::<TestHal>::new(8);
+    let mut disk = MockDisk::new(&queue, 64);
+
+    for round in 0..40 {
+        // 长短不一的链，令空闲链表的顺序不断变化
+        let mut bufs = vec![[0; SECTOR_SIZE]; round % 3 + 1];
+        let mut slices: Vec<&mut [u8]> = bufs.iter_mut().map(|buf| &mut buf[..]).collect();
+        let mut single = [0; SECTOR_SIZE];
+        let (mut req, mut other) = (BlkReq::default(), BlkReq::default());
+        let tokens = unsafe {
+            [
+                req.add_read(&mut queue, round, &mut slices).unwrap(),
+                other
+                    .add_read(&mut queue, 63 - round, &mut [&mut single[..]])
+                    .unwrap(),
+            ]
+        };
+
+        disk.notify();
+        let popped: Vec<u16> = core::iter::from_fn(|| queue.pop_used())
+            .map(|(token, _)| token)
+            .collect();
+        assert_eq!(popped, tokens);
+        assert_eq!(queue.num_free(), 8);
+        for (i, buf) in bufs.iter().enumerate() {
+            assert_eq!(*buf, [(round + i) as u8; SECTOR_SIZE], "round {round}");
+        }
+        assert_eq!(single, [(63 - round) as u8; SECTOR_SIZE]);
+    }
+}
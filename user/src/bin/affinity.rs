@@ -0,0 +1,40 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EINVAL, ESRCH};
+use user::println;
+use user::thread::{affinity, current_tid, exit, set_affinity, spawn, waittid};
+
+/// 编号为0的处理器核总是存在
+const HART0: usize = 0b1;
+
+fn pinned() -> ! {
+    assert_eq!(affinity(current_tid()), Some(HART0));
+    exit(0)
+}
+
+/// 钉在0号核上的线程照常运行，新线程继承创建者的亲和性
+#[no_mangle]
+fn main() -> i32 {
+    let tid = current_tid();
+    assert!(affinity(tid).is_some_and(|mask| mask & HART0 != 0));
+
+    assert!(set_affinity(tid, 0).is_none());
+    assert_eq!(errno(), EINVAL);
+    assert!(set_affinity(usize::MAX >> 1, HART0).is_none());
+    assert_eq!(errno(), ESRCH);
+
+    set_affinity(tid, HART0).unwrap();
+    let threads = [
+        spawn(pinned as usize, 0),
+        spawn(pinned as usize, 0),
+        spawn(pinned as usize, 0),
+    ];
+    for tid in threads {
+        assert_eq!(waittid(tid), Some(0));
+    }
+
+    println!("affinity passed!");
+    0
+}
@@ -7,65 +7,23 @@ extern crate user;
 extern crate alloc;
 
 use alloc::vec::Vec;
-use core::cell::UnsafeCell;
-use spin::Lazy;
-use user::sync::{
-    condvar_create, condvar_signal, condvar_wait, mutex_lock, mutex_unlock, spin_mutex,
-};
+use user::sync::Barrier;
 use user::thread::{exit, waittid};
 
 const THREAD_NUM: usize = 3;
 
-/// 同步屏障
-///
-/// 只有全部线程都完成上一阶段之后，它们才能够进入下一阶段
-struct Barrier {
-    mutex_id: usize,
-    condvar_id: usize,
-    count: UnsafeCell<usize>,
-}
-
-impl Barrier {
-    pub fn new() -> Self {
-        Self {
-            mutex_id: spin_mutex(),
-            condvar_id: condvar_create(),
-            count: UnsafeCell::new(0),
-        }
-    }
-
-    pub fn block(&self) {
-        mutex_lock(self.mutex_id);
-        let count = self.count.get();
-        // SAFETY: Here, the accesses of the count is in the
-        // critical section protected by the mutex.
-        unsafe {
-            *count += 1;
-        }
-        if unsafe { *count } == THREAD_NUM {
-            condvar_signal(self.condvar_id);
-        } else {
-            condvar_wait(self.condvar_id, self.mutex_id);
-            condvar_signal(self.condvar_id);
-        }
-        mutex_unlock(self.mutex_id);
-    }
-}
-
-unsafe impl Sync for Barrier {}
-
-static BARRIER_AB: Lazy<Barrier> = Lazy::new(Barrier::new);
-static BARRIER_BC: Lazy<Barrier> = Lazy::new(Barrier::new);
+static BARRIER_AB: Barrier = Barrier::new(THREAD_NUM);
+static BARRIER_BC: Barrier = Barrier::new(THREAD_NUM);
 
 fn thread_fn() {
     for _ in 0..300 {
         print!("a");
     }
-    BARRIER_AB.block();
+    BARRIER_AB.wait();
     for _ in 0..300 {
         print!("b");
     }
-    BARRIER_BC.block();
+    BARRIER_BC.wait();
     for _ in 0..300 {
         print!("c");
     }
@@ -0,0 +1,18 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::println;
+use user::process::{sysinfo, BOOT_PHASES};
+
+/// 列出内核启动各阶段的耗时
+#[no_mangle]
+fn main() -> i32 {
+    let info = sysinfo();
+    for (phase, us) in BOOT_PHASES.iter().zip(info.boot_us) {
+        println!("{phase:>10} {:>6}.{:03}ms", us / 1000, us % 1000);
+    }
+    let total: usize = info.boot_us.iter().sum();
+    println!("{:>10} {:>6}.{:03}ms", "total", total / 1000, total % 1000);
+    0
+}
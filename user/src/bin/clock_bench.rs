@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::slice;
+
+use user::errno::{errno, EFAULT};
+use user::fs::{close, pipe};
+use user::io::{read, write};
+use user::println;
+use user::thread::sleep;
+use user::time::{get_time, now, ticks, ticks_per_sec};
+
+const ROUNDS: usize = 100_000;
+/// 时钟共享页，位于用户地址空间的最后一页
+const VDSO_VA: usize = (1 << 38) - 0x1000;
+
+/// 比较经系统调用与读共享页计时的开销，并核对两者读数一致；
+/// 共享页只读，不能作为系统调用写入的目标
+#[no_mangle]
+fn main() -> i32 {
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    assert_eq!(write(fds[1], &[0xff; 64]), Some(64));
+    let vdso = unsafe { slice::from_raw_parts_mut(VDSO_VA as *mut u8, 64) };
+    assert!(read(fds[0], vdso).is_none());
+    assert_eq!(errno(), EFAULT);
+    close(fds[0]);
+    close(fds[1]);
+
+    // 两种读数至多相差一个时钟中断间隔
+    let interval = (1000 / ticks_per_sec()) as isize;
+    assert!((get_time() - now()).abs() <= interval);
+
+    let start = now();
+    for _ in 0..ROUNDS {
+        get_time();
+    }
+    let syscall_ms = now() - start;
+
+    let start = now();
+    for _ in 0..ROUNDS {
+        now();
+    }
+    let vdso_ms = now() - start;
+    println!("{ROUNDS} reads: syscall {syscall_ms}ms, shared page {vdso_ms}ms");
+
+    let before = ticks();
+    sleep(100);
+    assert!(ticks() > before);
+    println!("clock_bench passed!");
+    0
+}
@@ -7,45 +7,40 @@ extern crate user;
 
 extern crate alloc;
 
-use user::sync::{
-    block_mutex, condvar_create, condvar_signal, condvar_wait, mutex_lock, mutex_unlock,
-};
+use user::sync::{Condvar, Mutex};
 use user::thread::{exit, sleep, waittid};
 
 static mut A: usize = 0;
 
-const CONDVAR_ID: usize = 0;
-const MUTEX_ID: usize = 0;
+static CONDVAR: Condvar = Condvar::new();
+static MUTEX: Mutex = Mutex::new();
 
 unsafe fn first() -> ! {
     sleep(10);
     println!("First work, Change A --> 1 and wakeup Second");
-    mutex_lock(MUTEX_ID);
+    MUTEX.lock();
     A = 1;
-    condvar_signal(CONDVAR_ID);
-    mutex_unlock(MUTEX_ID);
+    CONDVAR.notify_one();
+    MUTEX.unlock();
     exit(0)
 }
 
 unsafe fn second() -> ! {
     println!("Second want to continue,but need to wait A=1");
-    mutex_lock(MUTEX_ID);
+    MUTEX.lock();
     // Mesa语义的实现，中间可能存在其它竞争线程，
     // 因此即使等待完成，条件也不一定满足
     while A == 0 {
         println!("Second: A is {}", A);
-        condvar_wait(CONDVAR_ID, MUTEX_ID);
+        CONDVAR.wait(&MUTEX);
     }
     println!("A is {}, Second can work now", A);
-    mutex_unlock(MUTEX_ID);
+    MUTEX.unlock();
     exit(0)
 }
 
 #[no_mangle]
 fn main() -> i32 {
-    // create condvar & mutex
-    assert_eq!(condvar_create(), CONDVAR_ID);
-    assert_eq!(block_mutex(), MUTEX_ID);
     // create threads
     let threads = [
         user::thread::spawn(first as usize, 0),
@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+#[macro_use]
+extern crate user;
+
+use core::ptr;
+
+use user::ucontext::{makecontext, swapcontext, UContext};
+
+const STACK_SIZE: usize = 4096;
+const ROUNDS: usize = 5;
+/// 每轮忙等的迭代次数，使协程的运行跨越时钟中断
+const SPIN: usize = 100_000;
+
+static mut MAIN_CTX: UContext = unsafe { core::mem::zeroed() };
+static mut PING_CTX: UContext = unsafe { core::mem::zeroed() };
+static mut PONG_CTX: UContext = unsafe { core::mem::zeroed() };
+static mut PING_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+static mut PONG_STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+
+fn spin() {
+    for i in 0..SPIN {
+        unsafe { ptr::read_volatile(&i) };
+    }
+}
+
+extern "C" fn ping(_: usize) {
+    for round in 0..ROUNDS {
+        spin();
+        println!("ping {round}");
+        unsafe { swapcontext(&mut *ptr::addr_of_mut!(PING_CTX), &*ptr::addr_of!(PONG_CTX)) };
+    }
+}
+
+extern "C" fn pong(_: usize) {
+    for round in 0..ROUNDS {
+        spin();
+        println!("pong {round}");
+        unsafe { swapcontext(&mut *ptr::addr_of_mut!(PONG_CTX), &*ptr::addr_of!(PING_CTX)) };
+    }
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    unsafe {
+        makecontext(
+            &mut *ptr::addr_of_mut!(PING_CTX),
+            &mut *ptr::addr_of_mut!(PING_STACK),
+            ping,
+            0,
+            Some(&*ptr::addr_of!(MAIN_CTX)),
+        );
+        makecontext(
+            &mut *ptr::addr_of_mut!(PONG_CTX),
+            &mut *ptr::addr_of_mut!(PONG_STACK),
+            pong,
+            0,
+            Some(&*ptr::addr_of!(MAIN_CTX)),
+        );
+        swapcontext(&mut *ptr::addr_of_mut!(MAIN_CTX), &*ptr::addr_of!(PING_CTX));
+    }
+
+    println!("coroutine passed!");
+    0
+}
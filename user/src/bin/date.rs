@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EINVAL};
+use user::println;
+use user::time::{clock_getres, gettimeofday, DateTime, CLOCK_MONOTONIC, CLOCK_REALTIME};
+
+#[no_mangle]
+fn main() -> i32 {
+    let before = gettimeofday();
+    let after = gettimeofday();
+    assert!(before <= after, "realtime went backwards");
+
+    let res = clock_getres(CLOCK_REALTIME).unwrap();
+    assert_eq!(clock_getres(CLOCK_MONOTONIC), Some(res));
+    assert!(clock_getres(2).is_none() && errno() == EINVAL);
+
+    println!("{}", DateTime::from(after));
+    println!("resolution {}ns", res.nsec);
+    0
+}
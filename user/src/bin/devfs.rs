@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use user::fs::{close, fstat, open, DirEntryType, OpenFlag, ReadDir};
+use user::io::{read, write};
+use user::println;
+
+/// `/dev`下的设备经普通的打开与读写访问
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open("/dev", OpenFlag::read_only()).unwrap();
+    let names: Vec<String> = ReadDir::new(fd).map(|entry| entry.name).collect();
+    assert_eq!(names, ["null", "zero", "urandom", "tty"]);
+    close(fd);
+    assert!(open("/dev/none", OpenFlag::read_only()).is_none());
+
+    // 写入的都被丢弃，读到的总是文件末尾
+    let null = open("/dev/null", OpenFlag::RDWR.into()).unwrap();
+    assert_eq!(fstat(null).unwrap().mode, DirEntryType::Char);
+    assert_eq!(write(null, b"discarded"), Some(9));
+    let mut buf = [0xFF; 64];
+    assert_eq!(read(null, &mut buf), Some(0));
+    close(null);
+
+    let zero = open("/dev/zero", OpenFlag::read_only()).unwrap();
+    assert_eq!(read(zero, &mut buf), Some(buf.len()));
+    assert!(buf.iter().all(|&b| b == 0));
+    // 只读打开的不能写
+    assert!(write(zero, b"x").is_none());
+    close(zero);
+
+    let urandom = open("/dev/urandom", OpenFlag::read_only()).unwrap();
+    let mut other = [0; 64];
+    assert_eq!(read(urandom, &mut buf), Some(buf.len()));
+    assert_eq!(read(urandom, &mut other), Some(other.len()));
+    assert_ne!(buf, other);
+    assert!(buf.iter().any(|&b| b != buf[0]));
+    close(urandom);
+
+    let tty = open("/dev/tty", OpenFlag::WRONLY.into()).unwrap();
+    let message = b"devfs: hello from /dev/tty\n";
+    assert_eq!(write(tty, message), Some(message.len()));
+    close(tty);
+
+    println!("devfs passed!");
+    0
+}
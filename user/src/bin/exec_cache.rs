@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use user::fs::{close, open, unlink, OpenFlag};
+use user::io::{read, write};
+use user::println;
+use user::process::{spawn, waitpid};
+
+const PATH: &str = "/exec_cache";
+
+/// 读出程序`name`的全部内容
+fn read_program(name: &str) -> Vec<u8> {
+    let fd = open(&format!("/usr/bin/{name}"), OpenFlag::read_only()).unwrap();
+    let mut data = Vec::new();
+    let mut buf = [0; 4096];
+    while let Some(len @ 1..) = read(fd, &mut buf) {
+        data.extend_from_slice(&buf[..len]);
+    }
+    close(fd);
+    data
+}
+
+fn install(data: &[u8]) {
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::WRONLY).unwrap();
+    write(fd, data).unwrap();
+    close(fd);
+}
+
+/// 同时运行的多个进程共享程序映像；改写程序后再运行的是新内容
+#[no_mangle]
+fn main() -> i32 {
+    install(&read_program("hello_world"));
+
+    let pids: Vec<_> = (0..4).map(|_| spawn(PATH).unwrap()).collect();
+    for pid in pids {
+        let mut exit_code = -1;
+        assert_eq!(waitpid(pid, &mut exit_code), Some(pid));
+        assert_eq!(exit_code, 0);
+    }
+
+    install(&read_program("stack_overflow"));
+    let pid = spawn(PATH).unwrap();
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Some(pid));
+    assert_eq!(exit_code, -2);
+    unlink(PATH).unwrap();
+
+    println!("exec_cache passed!");
+    0
+}
@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::ptr::null;
+
+use user::errno::{errno, EIO, ENOMEM, ENOSYS};
+use user::fault;
+use user::fs::{close, pipe};
+use user::io::{read, write};
+use user::mem::{mmap, munmap, ProtectFlag};
+use user::println;
+
+const PAGE_SIZE: usize = 4096;
+
+/// 注入的故障应按安排确定地发生，撤销后一切如常
+#[no_mangle]
+fn main() -> i32 {
+    if fault::clear().is_none() {
+        if errno() == ENOSYS {
+            println!("fault injection is disabled, rebuild the kernel with FAULT_INJECT=on");
+        }
+        return 1;
+    }
+
+    // 只有第2次分配失败
+    fault::fail_nth_alloc(2).unwrap();
+    let results: Vec<_> = (0..3)
+        .map(|_| mmap(null(), PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).ok_or(errno()))
+        .collect();
+    assert_eq!(results[1], Err(ENOMEM));
+    for area in results.into_iter().flatten() {
+        munmap(area).unwrap();
+    }
+
+    // 相同的种子得出相同的失败序列，期间不能打印
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    // 管道容量为32字节，两轮写入不能超过
+    let mut runs = [[false; 16]; 2];
+    for run in &mut runs {
+        fault::fail_io(50, 42).unwrap();
+        for failed in run.iter_mut() {
+            *failed = write(fds[1], b"x").is_none();
+            assert!(!*failed || errno() == EIO);
+        }
+        fault::clear().unwrap();
+    }
+    assert_eq!(runs[0], runs[1]);
+    let failures = runs[0].iter().filter(|&&failed| failed).count();
+    assert!(0 < failures && failures < runs[0].len());
+
+    // 撤销后写入的都能读出
+    close(fds[1]).unwrap();
+    let mut buf = [0; 64];
+    let written = 2 * (runs[0].len() - failures);
+    assert_eq!(read(fds[0], &mut buf), Some(written));
+    println!(
+        "fault_inject passed: {failures}/{} writes failed",
+        runs[0].len()
+    );
+    0
+}
@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use user::errno::{errno, EMFILE};
+use user::fs::{close, dup, file_limit, pipe, set_file_limit};
+use user::println;
+
+const LIMIT: usize = 8;
+
+/// 描述符达到进程上限后，再打开会以`EMFILE`失败，关闭后又可打开
+#[no_mangle]
+fn main() -> i32 {
+    let default = file_limit();
+    set_file_limit(LIMIT).unwrap();
+    assert_eq!(file_limit(), LIMIT);
+
+    // 0、1、2已被标准输入输出占据
+    let fds: Vec<usize> = (3..LIMIT).map(|_| dup(1).unwrap()).collect();
+    assert_eq!(fds.last(), Some(&(LIMIT - 1)));
+    assert!(dup(1).is_none());
+    assert_eq!(errno(), EMFILE);
+
+    // 管道的两端要么都打开，要么都不打开
+    close(fds[0]).unwrap();
+    let mut pipe_fd = [0; 2];
+    assert!(pipe(&mut pipe_fd).is_none());
+    assert_eq!(errno(), EMFILE);
+    assert_eq!(dup(1), Some(fds[0]));
+
+    for fd in fds {
+        close(fd).unwrap();
+    }
+    set_file_limit(default).unwrap();
+    println!("fd_limit passed!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+#[macro_use]
+extern crate user;
+
+use user::process::{fork, wait};
+use user::thread::{exit, yield_};
+
+const MAX_CHILD: usize = 8;
+const ROUNDS: usize = 1000;
+
+/// 每轮都让出CPU，浮点值跨越让出时留在被调用者保存的浮点寄存器中，
+/// 任务切换时若未保存恢复浮点寄存器，各进程的结果就会互相污染
+fn compute(seed: usize, yielding: bool) -> f64 {
+    let mut x = seed as f64;
+    for i in 0..ROUNDS {
+        x = x * 1.000_001 + 0.5 / (i + 1) as f64;
+        if yielding {
+            yield_();
+        }
+    }
+    x
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    for i in 0..MAX_CHILD {
+        let expected = compute(i, false);
+        if fork() == 0 {
+            let got = compute(i, true);
+            if got.to_bits() != expected.to_bits() {
+                println!("child {i}: expected {expected}, got {got}");
+                exit(-1);
+            }
+            exit(0);
+        }
+    }
+
+    let mut exit_code = 0;
+    for _ in 0..MAX_CHILD {
+        wait(&mut exit_code).unwrap();
+        assert_eq!(exit_code, 0, "FP registers corrupted across task switches");
+    }
+
+    println!("fpu_switch passed!");
+
+    0
+}
@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EBADF, EINVAL};
+use user::fs::{close, fstat, ftruncate, open, pipe, unlink, OpenFlag};
+use user::io::{read, write};
+use user::println;
+
+const PATH: &str = "ftruncate.txt";
+
+/// 截断释放文件末尾的数据，再扩展时读出为0
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::RDWR).unwrap();
+    write(fd, &[b'a'; 1000]).unwrap();
+
+    ftruncate(fd, 100).unwrap();
+    assert_eq!(fstat(fd).unwrap().size, 100);
+    ftruncate(fd, 300).unwrap();
+    assert_eq!(fstat(fd).unwrap().size, 300);
+    close(fd);
+
+    let fd = open(PATH, OpenFlag::read_only()).unwrap();
+    let mut buf = [0xFF; 1024];
+    assert_eq!(read(fd, &mut buf), Some(300));
+    assert!(buf[..100].iter().all(|&b| b == b'a'));
+    assert!(buf[100..300].iter().all(|&b| b == 0));
+
+    // 只读打开的文件不能截断
+    assert!(ftruncate(fd, 0).is_none());
+    assert_eq!(errno(), EINVAL);
+    close(fd);
+    unlink(PATH).unwrap();
+
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    assert!(ftruncate(fds[1], 0).is_none());
+    assert_eq!(errno(), EINVAL);
+    close(fds[0]);
+    close(fds[1]);
+    assert!(ftruncate(fds[1], 0).is_none());
+    assert_eq!(errno(), EBADF);
+
+    println!("ftruncate passed!");
+    0
+}
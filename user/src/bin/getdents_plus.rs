@@ -0,0 +1,67 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::vec::Vec;
+
+use user::fs::{
+    close, fstat, mkdir, open, rmdir, unlink, DirEntryType, OpenFlag, ReadDir, ReadDirPlus,
+};
+use user::io::write;
+use user::println;
+
+const DIR: &str = "getdents_plus";
+
+/// 随目录项一并返回的状态与逐个打开后[`fstat`]所得的一致
+#[no_mangle]
+fn main() -> i32 {
+    mkdir(DIR).unwrap();
+    mkdir(&format!("{DIR}/sub")).unwrap();
+    let fd = open(&format!("{DIR}/data"), OpenFlag::CREATE | OpenFlag::WRONLY).unwrap();
+    assert_eq!(write(fd, &[b'g'; 1000]), Some(1000));
+    close(fd);
+
+    let dir = open(DIR, OpenFlag::read_only()).unwrap();
+    let entries: Vec<_> = ReadDirPlus::new(dir)
+        .filter(|(dirent, _)| dirent.name != "." && dirent.name != "..")
+        .collect();
+    close(dir);
+    assert_eq!(entries.len(), 2);
+    for (dirent, stat) in &entries {
+        assert_eq!(dirent.ty, stat.mode);
+        let fd = open(&format!("{DIR}/{}", dirent.name), OpenFlag::read_only()).unwrap();
+        assert_eq!(fstat(fd).unwrap(), *stat);
+        close(fd);
+        match dirent.name.as_str() {
+            "data" => {
+                assert_eq!(stat.mode, DirEntryType::Regular);
+                assert_eq!(stat.size, 1000);
+            }
+            "sub" => assert_eq!(stat.mode, DirEntryType::Directory),
+            name => panic!("unexpected entry {name}"),
+        }
+    }
+
+    // 内存中的文件系统同样给出状态，名字与普通的目录项一致
+    for path in ["/proc", "/dev"] {
+        let fd = open(path, OpenFlag::read_only()).unwrap();
+        let names: Vec<_> = ReadDir::new(fd).map(|dirent| dirent.name).collect();
+        close(fd);
+        let fd = open(path, OpenFlag::read_only()).unwrap();
+        let plus: Vec<_> = ReadDirPlus::new(fd)
+            .inspect(|(dirent, stat)| assert_eq!(dirent.ty, stat.mode))
+            .map(|(dirent, _)| dirent.name)
+            .collect();
+        close(fd);
+        assert_eq!(names, plus);
+    }
+
+    unlink(&format!("{DIR}/data")).unwrap();
+    rmdir(&format!("{DIR}/sub")).unwrap();
+    rmdir(DIR).unwrap();
+    println!("getdents_plus passed!");
+    0
+}
@@ -0,0 +1,102 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, PrimitiveStyle};
+use user::graph::{Canvas, RESOLUTION_X, RESOLUTION_Y};
+use user::println;
+use user::thread::sleep;
+use user::time::now;
+
+/// 每帧的毫秒数，约30帧每秒
+const FRAME_MS: isize = 33;
+const FRAMES: usize = 300;
+const DIAMETER: u32 = 40;
+const BACKGROUND: Rgb888 = Rgb888::BLACK;
+
+struct Ball {
+    pos: Point,
+    velocity: Point,
+    color: Rgb888,
+}
+
+impl Ball {
+    fn shape(&self) -> Circle {
+        Circle::new(self.pos, DIAMETER)
+    }
+
+    /// 碰到屏幕边缘时反弹
+    fn step(&mut self) {
+        let max = Point::new(
+            (RESOLUTION_X - DIAMETER) as i32,
+            (RESOLUTION_Y - DIAMETER) as i32,
+        );
+        self.pos += self.velocity;
+        if !(0..=max.x).contains(&self.pos.x) {
+            self.velocity.x = -self.velocity.x;
+            self.pos.x = self.pos.x.clamp(0, max.x);
+        }
+        if !(0..=max.y).contains(&self.pos.y) {
+            self.velocity.y = -self.velocity.y;
+            self.pos.y = self.pos.y.clamp(0, max.y);
+        }
+    }
+}
+
+/// 小球在屏幕中弹跳，按时钟逐帧推进，结束时报告实际的帧间隔
+#[no_mangle]
+fn main() -> i32 {
+    let mut canvas = Canvas::new();
+    canvas.clear(BACKGROUND).unwrap();
+
+    let mut balls = [
+        Ball {
+            pos: Point::new(100, 100),
+            velocity: Point::new(7, 5),
+            color: Rgb888::RED,
+        },
+        Ball {
+            pos: Point::new(600, 300),
+            velocity: Point::new(-4, 9),
+            color: Rgb888::GREEN,
+        },
+        Ball {
+            pos: Point::new(900, 600),
+            velocity: Point::new(11, -6),
+            color: Rgb888::CYAN,
+        },
+    ];
+
+    let start = now();
+    let mut deadline = start;
+    for _ in 0..FRAMES {
+        for ball in &mut balls {
+            ball.shape()
+                .into_styled(PrimitiveStyle::with_fill(BACKGROUND))
+                .draw(&mut canvas)
+                .unwrap();
+            ball.step();
+            ball.shape()
+                .into_styled(PrimitiveStyle::with_fill(ball.color))
+                .draw(&mut canvas)
+                .unwrap();
+        }
+        canvas.present();
+
+        // 按截止时间而非固定时长休眠，绘制的耗时不会累积成漂移
+        deadline += FRAME_MS;
+        let rest = deadline - now();
+        if rest > 0 {
+            sleep(rest as usize);
+        }
+    }
+
+    let elapsed = now() - start;
+    println!(
+        "{FRAMES} frames in {elapsed}ms, {}ms per frame",
+        elapsed / FRAMES as isize
+    );
+    0
+}
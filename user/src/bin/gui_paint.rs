@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, PrimitiveStyle};
+use user::console::getchar;
+use user::graph::{get_event, key_pressed, Canvas, InputEvent, RESOLUTION_X, RESOLUTION_Y};
+use user::thread::sleep;
+use virtio_input_decoder::{DecodeType, Key, KeyType, Mouse};
+
+const BRUSH: u32 = 8;
+const BACKGROUND: Rgb888 = Rgb888::WHITE;
+const INK: Rgb888 = Rgb888::BLUE;
+/// 无事件时的轮询间隔
+const POLL_MS: usize = 10;
+
+/// 画板：按住鼠标左键拖动作画，右键清屏，在串口上按Q退出
+#[no_mangle]
+fn main() -> i32 {
+    let mut canvas = Canvas::new();
+    canvas.clear(BACKGROUND).unwrap();
+    canvas.present();
+
+    let mut cursor = Point::new(RESOLUTION_X as i32 / 2, RESOLUTION_Y as i32 / 2);
+    let mut drawing = false;
+    loop {
+        if key_pressed() && getchar() == b'q' {
+            return 0;
+        }
+
+        let Some(event) = get_event() else {
+            sleep(POLL_MS);
+            continue;
+        };
+        match InputEvent::from(event).decode() {
+            Some(DecodeType::Mouse(Mouse::X(dx))) => cursor.x += dx as i32,
+            Some(DecodeType::Mouse(Mouse::Y(dy))) => cursor.y += dy as i32,
+            Some(DecodeType::Key(Key::MouseLeft, kind)) => drawing = kind == KeyType::Press,
+            Some(DecodeType::Key(Key::MouseRight, KeyType::Press)) => {
+                canvas.clear(BACKGROUND).unwrap();
+                canvas.present();
+                continue;
+            }
+            _ => continue,
+        }
+        cursor.x = cursor.x.clamp(0, RESOLUTION_X as i32 - 1);
+        cursor.y = cursor.y.clamp(0, RESOLUTION_Y as i32 - 1);
+
+        if drawing {
+            Circle::with_center(cursor, BRUSH)
+                .into_styled(PrimitiveStyle::with_fill(INK))
+                .draw(&mut canvas)
+                .unwrap();
+            canvas.present();
+        }
+    }
+}
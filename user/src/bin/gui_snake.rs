@@ -0,0 +1,108 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::Rectangle;
+use user::console::getchar;
+use user::graph::{key_pressed, Canvas, RESOLUTION_X, RESOLUTION_Y};
+use user::println;
+use user::thread::sleep;
+use user::time::now;
+
+/// 格子的边长，以像素计
+const CELL: u32 = 40;
+const COLS: i32 = (RESOLUTION_X / CELL) as i32;
+const ROWS: i32 = (RESOLUTION_Y / CELL) as i32;
+/// 每步的毫秒数
+const STEP_MS: isize = 150;
+
+const BACKGROUND: Rgb888 = Rgb888::BLACK;
+const SNAKE: Rgb888 = Rgb888::GREEN;
+const FOOD: Rgb888 = Rgb888::RED;
+
+/// 线性同余发生器，以时间为种子放置食物
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self, bound: i32) -> i32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as i32
+    }
+}
+
+fn fill(canvas: &mut Canvas, cell: Point, color: Rgb888) {
+    let top_left = cell * CELL as i32;
+    canvas
+        .fill_solid(&Rectangle::new(top_left, Size::new(CELL, CELL)), color)
+        .unwrap();
+}
+
+/// 贪吃蛇，在串口上以WASD转向、Q退出，撞到墙或自身即结束
+#[no_mangle]
+fn main() -> i32 {
+    let mut canvas = Canvas::new();
+    canvas.clear(BACKGROUND).unwrap();
+    let mut rng = Rng(now() as u64);
+
+    let mut snake = VecDeque::from([Point::new(COLS / 2, ROWS / 2)]);
+    let mut direction = Point::new(1, 0);
+    let mut food = Point::new(rng.next(COLS), rng.next(ROWS));
+    fill(&mut canvas, snake[0], SNAKE);
+    fill(&mut canvas, food, FOOD);
+    canvas.present();
+
+    let mut deadline = now();
+    loop {
+        while key_pressed() {
+            let turn = match getchar() {
+                b'w' => Point::new(0, -1),
+                b's' => Point::new(0, 1),
+                b'a' => Point::new(-1, 0),
+                b'd' => Point::new(1, 0),
+                b'q' => {
+                    println!("quit with length {}", snake.len());
+                    return 0;
+                }
+                _ => continue,
+            };
+            // 不能掉头
+            if turn + direction != Point::zero() {
+                direction = turn;
+            }
+        }
+
+        let head = snake[0] + direction;
+        if !(0..COLS).contains(&head.x) || !(0..ROWS).contains(&head.y) || snake.contains(&head) {
+            println!("game over with length {}", snake.len());
+            return 0;
+        }
+        snake.push_front(head);
+        fill(&mut canvas, head, SNAKE);
+
+        if head == food {
+            while snake.contains(&food) {
+                food = Point::new(rng.next(COLS), rng.next(ROWS));
+            }
+            fill(&mut canvas, food, FOOD);
+        } else {
+            let tail = snake.pop_back().unwrap();
+            fill(&mut canvas, tail, BACKGROUND);
+        }
+        canvas.present();
+
+        deadline += STEP_MS;
+        let rest = deadline - now();
+        if rest > 0 {
+            sleep(rest as usize);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use user::mem::stats;
+use user::println;
+
+/// 分配后全部释放，堆的占用回到原处，峰值记下了最大的占用
+#[no_mangle]
+fn main() -> i32 {
+    let before = stats();
+
+    let boxes: Vec<Box<[u8; 100]>> = (0..10).map(|_| Box::new([0; 100])).collect();
+    let during = stats();
+    assert_eq!(during.live(), before.live() + 11);
+    assert!(during.in_use >= before.in_use + 1000);
+    drop(boxes);
+
+    let after = stats();
+    assert_eq!(after.live(), before.live());
+    assert_eq!(after.in_use, before.in_use);
+    assert!(after.peak >= during.in_use);
+    println!("heap_stats passed!");
+    0
+}
@@ -2,31 +2,215 @@
 #![no_main]
 #![feature(format_args_nl)]
 
-use user::println;
-use user::process::{exec, fork, wait};
-use user::thread::yield_;
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use user::fs::{close, open, OpenFlag};
+use user::io::read;
+use user::process::{spawn, try_wait};
+use user::signal::{sigtimedwait, SignalFlag};
+use user::time::get_time;
+use user::{device, println};
+
+/// 服务配置，格式见其中的注释
+const INITTAB: &str = "/etc/inittab";
+/// 读不到[`INITTAB`]时，只在控制台上启动shell
+const DEFAULT_INITTAB: &str = "shell:always:respawn:user_shell";
+
+/// 首次重启前的等待时间(ms)
+const BACKOFF_MIN: isize = 100;
+/// 重启等待时间的上限(ms)
+const BACKOFF_MAX: isize = 10_000;
+/// 运行超过此时长(ms)视为稳定，再结束时等待时间复位
+const STABLE_TIME: isize = 5_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    /// 结束后重启
+    Respawn,
+    /// 只运行一次
+    Once,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    /// 到达该时刻后启动
+    Pending(isize),
+    Running {
+        pid: usize,
+        since: isize,
+    },
+    /// 不再启动
+    Done,
+}
+
+#[derive(Debug)]
+struct Service {
+    name: String,
+    path: String,
+    action: Action,
+    /// 下次重启前的等待时间
+    backoff: isize,
+    state: State,
+}
+
+impl Service {
+    /// 解析`名称:条件:动作:程序`，条件不满足时返回`Ok(None)`
+    fn parse(line: &str) -> Result<Option<Self>, &'static str> {
+        let mut fields = line.split(':').map(str::trim);
+        let (Some(name), Some(cond), Some(action), Some(program), None) = (
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+            fields.next(),
+        ) else {
+            return Err("expected `name:condition:action:program`");
+        };
+
+        let action = match action {
+            "respawn" => Action::Respawn,
+            "once" => Action::Once,
+            _ => return Err("unknown action"),
+        };
+        if name.is_empty() || program.is_empty() {
+            return Err("empty name or program");
+        }
+        if cond != "always" && !device::present(cond) {
+            return Ok(None);
+        }
+
+        let path = if program.starts_with('/') {
+            String::from(program)
+        } else {
+            format!("/usr/bin/{program}")
+        };
+
+        Ok(Some(Self {
+            name: String::from(name),
+            path,
+            action,
+            backoff: BACKOFF_MIN,
+            state: State::Pending(0),
+        }))
+    }
+
+    fn start(&mut self, now: isize) {
+        match spawn(&self.path) {
+            Some(pid) => {
+                println!("[initproc] Started {}, pid={pid}", self.name);
+                self.state = State::Running { pid, since: now };
+            }
+            None => {
+                println!(
+                    "[initproc] Failed to start {} from {}",
+                    self.name, self.path
+                );
+                self.stopped(now, now);
+            }
+        }
+    }
+
+    /// 服务自`since`起运行，于`now`结束
+    fn stopped(&mut self, since: isize, now: isize) {
+        if self.action == Action::Once {
+            self.state = State::Done;
+            return;
+        }
+
+        if now - since >= STABLE_TIME {
+            self.backoff = BACKOFF_MIN;
+        }
+        println!("[initproc] Restarting {} in {}ms", self.name, self.backoff);
+        self.state = State::Pending(now + self.backoff);
+        self.backoff = (self.backoff * 2).min(BACKOFF_MAX);
+    }
+}
+
+fn read_file(path: &str) -> Option<String> {
+    let fd = open(path, OpenFlag::read_only())?;
+    let mut content = Vec::new();
+    let mut buf = [0u8; 256];
+    while let Some(size @ 1..) = read(fd, &mut buf) {
+        content.extend_from_slice(&buf[..size]);
+    }
+    close(fd);
+
+    String::from_utf8(content).ok()
+}
+
+fn load_services() -> Vec<Service> {
+    let inittab = read_file(INITTAB).unwrap_or_else(|| {
+        println!("[initproc] Cannot read {INITTAB}, falling back to the shell");
+        String::from(DEFAULT_INITTAB)
+    });
+
+    inittab
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|(lineno, line)| {
+            Service::parse(line)
+                .inspect_err(|e| println!("[initproc] {INITTAB}:{lineno}: {e}"))
+                .ok()
+                .flatten()
+        })
+        .collect()
+}
 
 #[no_mangle]
 fn main() -> i32 {
-    if fork() == 0 {
-        // 启动shell
-        exec::<&str, _>("user_shell", []);
-    } else {
-        loop {
-            let mut exit_code = 0;
-
-            match wait(&mut exit_code) {
-                None => {
-                    yield_();
+    let mut services = load_services();
+
+    loop {
+        let now = get_time();
+
+        for service in &mut services {
+            if let State::Pending(at) = service.state {
+                if at <= now {
+                    service.start(now);
                 }
-                Some(pid) => {
+            }
+        }
+
+        // 回收结束的服务，以及托付给initproc的孤儿进程
+        let mut exit_code = 0;
+        while let Some(Some(pid)) = try_wait(&mut exit_code) {
+            let service = services
+                .iter_mut()
+                .find(|service| matches!(service.state, State::Running { pid: p, .. } if p == pid));
+            match service {
+                Some(service) => {
+                    let State::Running { since, .. } = service.state else {
+                        unreachable!()
+                    };
+                    println!(
+                        "[initproc] {} exited, pid={pid}, exit_code={exit_code}",
+                        service.name
+                    );
+                    service.stopped(since, now);
+                }
+                None => {
                     println!(
                         "[initproc] Released a zombie process, pid={pid}, exit_code={exit_code}",
                     );
                 }
             }
         }
-    }
 
-    0
+        // 睡眠至有子进程结束，或下一个服务该启动时
+        let next = services
+            .iter()
+            .filter_map(|service| match service.state {
+                State::Pending(at) => Some(at),
+                _ => None,
+            })
+            .min();
+        let timeout = next.map(|at| (at - get_time()).max(0) as usize);
+        sigtimedwait(SignalFlag::SIGCHLD.into(), timeout);
+    }
 }
@@ -0,0 +1,119 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+#[macro_use]
+extern crate user;
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use user::fs::inotify::{
+    IN_ALL_EVENTS, IN_CREATE, IN_DELETE, IN_DELETE_SELF, IN_IGNORED, IN_ISDIR, IN_MODIFY,
+    IN_MOVED_FROM, IN_MOVED_TO,
+};
+use user::fs::{
+    close, inotify_add_watch, inotify_init, inotify_read, inotify_rm_watch, mkdir, open, rename,
+    rmdir, unlink, OpenFlag,
+};
+use user::io::write;
+use user::process::{fork, waitpid};
+use user::thread::{exit, sleep};
+
+const DIR: &str = "inotify.dir";
+
+/// 读出的事件是否依次为`expected`中的`(wd, mask, name)`
+fn read_events(fd: usize, expected: &[(i32, u32, &str)]) -> bool {
+    let events = inotify_read(fd).unwrap();
+    events.len() == expected.len()
+        && events
+            .iter()
+            .zip(expected)
+            .all(|((event, name), &(wd, mask, expected_name))| {
+                (event.wd, event.mask, name.as_str()) == (wd, mask, expected_name)
+            })
+}
+
+/// 监视目录得到其下各项的事件，监视文件得到其自身的事件；
+/// 阻塞的实例在有事件时被唤醒，省去轮询
+#[no_mangle]
+fn main() -> i32 {
+    mkdir(DIR).unwrap();
+    let fd = inotify_init(true).unwrap();
+    let wd = inotify_add_watch(fd, DIR, IN_ALL_EVENTS).unwrap();
+    assert!(inotify_read(fd).is_none());
+
+    let file = open("inotify.dir/a", OpenFlag::CREATE | OpenFlag::WRONLY).unwrap();
+    // 连续的写入合并为一个事件
+    write(file, b"x").unwrap();
+    write(file, b"y").unwrap();
+    close(file);
+    rename("inotify.dir/a", "inotify.dir/b").unwrap();
+    mkdir("inotify.dir/sub").unwrap();
+    rmdir("inotify.dir/sub").unwrap();
+
+    let events = inotify_read(fd).unwrap();
+    let got: Vec<_> = events
+        .iter()
+        .map(|(event, name)| (event.wd, event.mask, name.as_str()))
+        .collect();
+    assert_eq!(
+        got,
+        [
+            (wd, IN_CREATE, "a"),
+            (wd, IN_MODIFY, "a"),
+            (wd, IN_MOVED_FROM, "a"),
+            (wd, IN_MOVED_TO, "b"),
+            (wd, IN_CREATE | IN_ISDIR, "sub"),
+            (wd, IN_DELETE | IN_ISDIR, "sub"),
+        ]
+    );
+    // 同一次重命名的两个事件以cookie相关联
+    assert_ne!(events[2].0.cookie, 0);
+    assert_eq!(events[2].0.cookie, events[3].0.cookie);
+
+    // 监视文件本身，删除后监视随之撤销
+    let file_wd = inotify_add_watch(fd, "inotify.dir/b", IN_DELETE_SELF).unwrap();
+    unlink("inotify.dir/b").unwrap();
+    assert!(read_events(
+        fd,
+        &[
+            (wd, IN_DELETE, "b"),
+            (file_wd, IN_DELETE_SELF, ""),
+            (file_wd, IN_IGNORED, ""),
+        ]
+    ));
+    assert!(inotify_rm_watch(fd, file_wd).is_none());
+
+    // 阻塞的读取等到了另一进程造成的事件
+    let blocking = inotify_init(false).unwrap();
+    let blocking_wd = inotify_add_watch(blocking, DIR, IN_CREATE).unwrap();
+    let pid = fork();
+    if pid == 0 {
+        let ok = read_events(blocking, &[(blocking_wd, IN_CREATE, "c")]);
+        exit(if ok { 0 } else { 1 });
+    }
+    sleep(100);
+    let file = open("inotify.dir/c", OpenFlag::CREATE | OpenFlag::WRONLY).unwrap();
+    close(file);
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code);
+    assert_eq!(exit_code, 0, "blocked reader missed the event");
+    close(blocking);
+
+    unlink("inotify.dir/c").unwrap();
+    inotify_rm_watch(fd, wd).unwrap();
+    assert!(read_events(
+        fd,
+        &[
+            (wd, IN_CREATE, "c"),
+            (wd, IN_DELETE, "c"),
+            (wd, IN_IGNORED, ""),
+        ]
+    ));
+    close(fd);
+    rmdir(DIR).unwrap();
+
+    println!("inotify passed!");
+    0
+}
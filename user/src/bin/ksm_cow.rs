@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::ptr::null;
+
+use user::fs::{close, pipe};
+use user::io::{read, write};
+use user::mem::{ksm_stats, mmap, munmap, set_ksm, ProtectFlag};
+use user::println;
+use user::time::get_time;
+
+const PAGE_SIZE: usize = 4096;
+const FILL: u8 = 0x5a;
+
+/// 内容相同的可写页被合并后写时复制：用户写入与内核代写都只改动自己的那一页
+#[no_mangle]
+fn main() -> i32 {
+    let area = mmap(null(), 3 * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    area.fill(FILL);
+
+    set_ksm(true).unwrap();
+    wait_scans(2);
+    assert!(ksm_stats().pages_sharing >= 2);
+
+    let (first, rest) = area.split_at_mut(PAGE_SIZE);
+    let (second, third) = rest.split_at_mut(PAGE_SIZE);
+
+    first[0] = 1;
+    assert!(first[1..].iter().all(|&b| b == FILL));
+    assert!(second.iter().all(|&b| b == FILL));
+    assert!(third.iter().all(|&b| b == FILL));
+
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    assert_eq!(write(fds[1], &[2; 8]), Some(8));
+    assert_eq!(read(fds[0], &mut second[..8]), Some(8));
+    assert!(second[..8].iter().all(|&b| b == 2));
+    assert!(third.iter().all(|&b| b == FILL));
+    close(fds[0]);
+    close(fds[1]);
+
+    set_ksm(false).unwrap();
+    munmap(area).unwrap();
+    println!("ksm_cow passed!");
+    0
+}
+
+/// 在用户态忙等，直到内核再完成`scans`次扫描
+fn wait_scans(scans: usize) {
+    let target = ksm_stats().full_scans + scans;
+    let deadline = get_time() + 30_000;
+    while ksm_stats().full_scans < target {
+        assert!(get_time() < deadline, "ksm never scanned");
+    }
+}
@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EISDIR, ENOENT, EOPNOTSUPP, EXDEV};
+use user::fs::{close, link, mkdir, open, rmdir, unlink, OpenFlag};
+use user::println;
+
+const PATH: &str = "link_errno.txt";
+
+/// 根目录所在的FAT不支持硬链接，报EOPNOTSUPP而非笼统的失败，以与ENOENT等区分
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::WRONLY).unwrap();
+    close(fd);
+
+    assert!(link(PATH, "link_errno.alias").is_none());
+    assert_eq!(errno(), EOPNOTSUPP);
+    assert!(open("link_errno.alias", OpenFlag::read_only()).is_none());
+
+    // 硬链接不能跨越挂载点
+    assert!(link(PATH, "/proc/link_errno").is_none());
+    assert_eq!(errno(), EXDEV);
+
+    assert!(unlink("link_errno.missing").is_none());
+    assert_eq!(errno(), ENOENT);
+    mkdir("link_errno.dir").unwrap();
+    assert!(unlink("link_errno.dir").is_none());
+    assert_eq!(errno(), EISDIR);
+    rmdir("link_errno.dir").unwrap();
+
+    unlink(PATH).unwrap();
+    println!("link_errno passed!");
+    0
+}
@@ -1,11 +1,18 @@
 #![no_std]
 #![no_main]
+#![feature(format_args_nl)]
 
+use user::errno::errno;
 use user::fs::link;
+use user::println;
 
 #[no_mangle]
 fn main(argc: usize, argv: &[&str]) -> i32 {
     assert_eq!(argc, 3);
-    link(argv[1], argv[2]).expect("The linked file not found");
+    if link(argv[1], argv[2]).is_none() {
+        let (old, new) = (argv[1], argv[2]);
+        println!("ln: cannot link {new} to {old}, errno {}", errno());
+        return -1;
+    }
     0
 }
@@ -4,46 +4,28 @@
 
 extern crate alloc;
 
-use alloc::borrow::ToOwned;
 use alloc::string::String;
-use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::Write;
 
-use user::fs::{close, getdents, open, OpenFlag};
+use user::fs::{close, open, DirEntryType, OpenFlag, ReadDir, ReadDirPlus};
 use user::println;
-use vfs::{CDirEntry, DirEntryType};
 
 #[no_mangle]
 fn main(_: usize, argv: &[&str]) -> i32 {
-    let path = argv.get(1).copied().unwrap_or(".");
+    let long = argv.get(1) == Some(&"-l");
+    let path = argv.get(1 + long as usize).copied().unwrap_or(".");
 
-    let mut names = Vec::new();
     let fd = open(path, OpenFlag::read_only()).expect("Not found");
-
-    loop {
-        let mut raw_names = vec![[0u8; 256]; 8];
-        let mut c_dirents: Vec<_> = raw_names
-            .iter_mut()
-            .map(|name| CDirEntry {
-                inode: 0,
-                ty: DirEntryType::Regular,
-                name: name.as_mut_ptr(),
-            })
-            .collect();
-
-        let n = getdents(fd, &mut c_dirents).unwrap();
-
-        if n == 0 {
-            break;
+    if long {
+        // 一行一项：类型、字节数、名字
+        for (dirent, stat) in ReadDirPlus::new(fd) {
+            println!("{} {:>10} {}", type_char(stat.mode), stat.size, dirent.name);
         }
-
-        names.extend(raw_names.iter().take(n).map(|name| {
-            let end = name.iter().position(|&b| b == b'\0').unwrap();
-            core::str::from_utf8(&name[..end]).unwrap().to_owned()
-        }))
+        close(fd).unwrap();
+        return 0;
     }
-
+    let names: Vec<_> = ReadDir::new(fd).map(|dirent| dirent.name).collect();
     close(fd).unwrap();
 
     let Some(max_len) = names.iter().map(String::len).max() else {
@@ -59,3 +41,14 @@ fn main(_: usize, argv: &[&str]) -> i32 {
 
     0
 }
+
+fn type_char(ty: DirEntryType) -> char {
+    match ty {
+        DirEntryType::Block => 'b',
+        DirEntryType::Char => 'c',
+        DirEntryType::Directory => 'd',
+        DirEntryType::Fifo => 'p',
+        DirEntryType::SymLink => 'l',
+        DirEntryType::Regular => '-',
+    }
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EINVAL, ESPIPE};
+use user::fs::{close, fstat, lseek, open, pipe, unlink, OpenFlag, Whence};
+use user::io::{read, write};
+use user::println;
+
+const PATH: &str = "lseek.txt";
+
+/// 移动偏移量后读写，越过末尾写入留下的空洞读出为0
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::RDWR).unwrap();
+    write(fd, b"hello, world").unwrap();
+
+    assert_eq!(lseek(fd, 7, Whence::Set), Some(7));
+    let mut buf = [0; 5];
+    assert_eq!(read(fd, &mut buf), Some(5));
+    assert_eq!(&buf, b"world");
+
+    assert_eq!(lseek(fd, -12, Whence::Cur), Some(0));
+    assert_eq!(read(fd, &mut buf), Some(5));
+    assert_eq!(&buf, b"hello");
+
+    assert_eq!(lseek(fd, 0, Whence::End), Some(12));
+    assert_eq!(read(fd, &mut buf), Some(0));
+
+    // 越过末尾写入
+    assert_eq!(lseek(fd, 1000, Whence::End), Some(1012));
+    write(fd, b"!").unwrap();
+    assert_eq!(fstat(fd).unwrap().size, 1013);
+    assert_eq!(lseek(fd, 12, Whence::Set), Some(12));
+    let mut hole = [0xFF; 1001];
+    assert_eq!(read(fd, &mut hole), Some(1001));
+    assert!(hole[..1000].iter().all(|&b| b == 0));
+    assert_eq!(hole[1000], b'!');
+
+    // 偏移量不能为负，失败时保持不变
+    assert!(lseek(fd, -1, Whence::Set).is_none());
+    assert_eq!(errno(), EINVAL);
+    assert!(lseek(fd, -2000, Whence::Cur).is_none());
+    assert_eq!(errno(), EINVAL);
+    assert_eq!(lseek(fd, 0, Whence::Cur), Some(1013));
+    close(fd);
+    unlink(PATH).unwrap();
+
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    assert!(lseek(fds[0], 0, Whence::Set).is_none());
+    assert_eq!(errno(), ESPIPE);
+    close(fds[0]);
+    close(fds[1]);
+
+    println!("lseek passed!");
+    0
+}
@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::slice;
+
+use user::errno::{errno, EINVAL};
+use user::mem::{madvise, Advice};
+use user::println;
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 16;
+/// 主线程陷入上下文所在的页，内核使用，用户不可访问
+const TRAP_CONTEXT: usize = usize::MAX - 2 * PAGE_SIZE + 1;
+
+#[repr(align(4096))]
+struct Pages([u8; PAGES * PAGE_SIZE]);
+
+static mut PAGES_BUF: Pages = Pages([0; PAGES * PAGE_SIZE]);
+
+/// 释放的页再读到全零，预先映射后照常读写；内核使用的页与只读的代码段不能释放
+#[no_mangle]
+fn main() -> i32 {
+    let buf = unsafe { &mut (*&raw mut PAGES_BUF).0 };
+
+    buf.fill(0xa5);
+    madvise(&mut buf[PAGE_SIZE..3 * PAGE_SIZE], Advice::DontNeed).unwrap();
+    assert!(buf[..PAGE_SIZE].iter().all(|&b| b == 0xa5));
+    assert!(buf[PAGE_SIZE..3 * PAGE_SIZE].iter().all(|&b| b == 0));
+    assert!(buf[3 * PAGE_SIZE..].iter().all(|&b| b == 0xa5));
+
+    madvise(buf, Advice::DontNeed).unwrap();
+    madvise(buf, Advice::WillNeed).unwrap();
+    assert!(buf.iter().all(|&b| b == 0));
+    buf.fill(0x5a);
+    assert!(buf.iter().all(|&b| b == 0x5a));
+
+    // 高位被截去后与完整的地址都不能落到陷入上下文上
+    for addr in [TRAP_CONTEXT, TRAP_CONTEXT & ((1 << 39) - 1)] {
+        let page = unsafe { slice::from_raw_parts_mut(addr as *mut u8, PAGE_SIZE) };
+        assert!(madvise(page, Advice::DontNeed).is_none());
+        assert_eq!(errno(), EINVAL);
+    }
+    let text = (main as usize) & !(PAGE_SIZE - 1);
+    let text = unsafe { slice::from_raw_parts_mut(text as *mut u8, PAGE_SIZE) };
+    assert!(madvise(text, Advice::DontNeed).is_none());
+    assert_eq!(errno(), EINVAL);
+
+    println!("madvise passed!");
+    0
+}
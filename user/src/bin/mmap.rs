@@ -3,14 +3,38 @@
 #![feature(format_args_nl)]
 
 use core::ptr::null;
-use user::mem::{mmap, munmap, ProtectFlag};
+use user::mem::{mmap, mremap, munmap, ProtectFlag};
 use user::println;
 
+const PAGE_SIZE: usize = 4096;
+
+/// 映射、原地伸缩、被迫移动后内容都应保持不变
 #[no_mangle]
 fn main() -> i32 {
     let data = mmap(null(), 114514, ProtectFlag::R | ProtectFlag::W).unwrap();
     println!("{:#x}", data.as_ptr() as usize);
     println!("{}", data.len());
+    assert!(data.iter().all(|&b| b == 0));
+    data.fill(0xa5);
+
+    // 缩小后再增长，增长的部分为全零
+    let data = mremap(data, PAGE_SIZE).unwrap();
+    let data = mremap(data, 4 * PAGE_SIZE).unwrap();
+    assert!(data[..PAGE_SIZE].iter().all(|&b| b == 0xa5));
+    assert!(data[PAGE_SIZE..].iter().all(|&b| b == 0));
+
+    // 紧随其后的地址被占用，增长时只能移走
+    let end = data.as_ptr_range().end;
+    let blocker = mmap(end, PAGE_SIZE, ProtectFlag::R).unwrap();
+    assert_eq!(blocker.as_ptr(), end);
+    let start = data.as_ptr();
+    let data = mremap(data, 8 * PAGE_SIZE).unwrap();
+    assert_ne!(data.as_ptr(), start);
+    assert!(data[..PAGE_SIZE].iter().all(|&b| b == 0xa5));
+    assert!(data[PAGE_SIZE..].iter().all(|&b| b == 0));
+
+    munmap(blocker).unwrap();
     munmap(data).unwrap();
+    println!("mmap passed!");
     0
 }
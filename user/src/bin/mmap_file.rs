@@ -0,0 +1,45 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::fs::{close, open, unlink, OpenFlag};
+use user::io::{read, write};
+use user::mem::{mmap_file, msync, munmap, ProtectFlag, SyncMode};
+use user::println;
+
+const PATH: &str = "mmap_file.txt";
+const PAGE_SIZE: usize = 4096;
+
+/// 经映射修改的内容写回文件，文件末尾之后的部分不写回
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::RDWR).unwrap();
+    let content = [b'a'; PAGE_SIZE + 100];
+    write(fd, &content).unwrap();
+
+    let data = mmap_file(fd, 2 * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    assert!(data[..content.len()].iter().all(|&b| b == b'a'));
+    assert!(data[content.len()..].iter().all(|&b| b == 0));
+    data[0] = b'x';
+    data[PAGE_SIZE + 99] = b'y';
+    data[PAGE_SIZE + 100] = b'z';
+    msync(data, SyncMode::Sync).unwrap();
+    data[1] = b'w';
+    munmap(data).unwrap();
+    close(fd);
+
+    let fd = open(PATH, OpenFlag::read_only()).unwrap();
+    let mut buf = [0; PAGE_SIZE + 200];
+    let mut len = 0;
+    while let Some(n) = read(fd, &mut buf[len..]).filter(|&n| n > 0) {
+        len += n;
+    }
+    close(fd);
+    unlink(PATH).unwrap();
+    assert_eq!(len, content.len());
+    assert_eq!(&buf[..3], b"xwa");
+    assert_eq!(buf[PAGE_SIZE + 99], b'y');
+
+    println!("mmap_file passed!");
+    0
+}
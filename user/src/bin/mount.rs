@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EBUSY, EINVAL, ENODEV, ENOENT, ENOTDIR, EPERM};
+use user::fs::mount;
+use user::println;
+
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc != 4 {
+        println!("usage: mount <device> <dir> <fstype>");
+        return 1;
+    }
+
+    let (device, dir, fstype) = (argv[1], argv[2], argv[3]);
+    if mount(device, dir, fstype).is_some() {
+        return 0;
+    }
+
+    let reason = match errno() {
+        ENOENT => "no such device or directory",
+        ENOTDIR => "not a directory",
+        ENODEV => "unknown filesystem type",
+        EINVAL => "wrong filesystem type",
+        EBUSY => "already mounted",
+        EPERM => "must be superuser",
+        _ => "unknown error",
+    };
+    println!("mount: cannot mount {device} on {dir}: {reason}");
+    1
+}
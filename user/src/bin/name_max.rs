@@ -0,0 +1,38 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+
+use user::errno::{errno, ENAMETOOLONG};
+use user::fs::{close, mkdir, open, unlink, OpenFlag, NAME_MAX, PATH_MAX};
+use user::println;
+
+/// 分量长至[`NAME_MAX`]的文件名可用，再长则报告ENAMETOOLONG；路径总长亦受[`PATH_MAX`]限制
+#[no_mangle]
+fn main() -> i32 {
+    let longest = "n".repeat(NAME_MAX);
+    let fd = open(&longest, OpenFlag::CREATE | OpenFlag::WRONLY).unwrap();
+    close(fd);
+    unlink(&longest).unwrap();
+
+    let too_long = "n".repeat(NAME_MAX + 1);
+    assert!(open(&too_long, OpenFlag::CREATE | OpenFlag::WRONLY).is_none());
+    assert_eq!(errno(), ENAMETOOLONG);
+    assert!(mkdir(&too_long).is_none());
+    assert_eq!(errno(), ENAMETOOLONG);
+
+    // 各分量都不长，但总长连同终止符超过PATH_MAX
+    let mut path = String::new();
+    while path.len() < PATH_MAX {
+        path += &format!("/{}", "d".repeat(NAME_MAX - 1));
+    }
+    assert!(open(&path, OpenFlag::read_only()).is_none());
+    assert_eq!(errno(), ENAMETOOLONG);
+
+    println!("name_max passed!");
+    0
+}
@@ -0,0 +1,60 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+#[macro_use]
+extern crate user;
+
+use user::errno::{errno, EINTR, EINVAL};
+use user::process::{fork, waitpid};
+use user::signal::{kill, SIGUSR1};
+use user::thread::{exit, nanosleep, sleep};
+use user::time::{now, TimeSpec};
+
+const MS: usize = 1_000_000;
+
+/// 被信号打断的睡眠返回EINTR与剩余时间，凭剩余时间接着睡，总时长不少于请求的时长
+#[no_mangle]
+fn main() -> i32 {
+    let bad = TimeSpec {
+        sec: 0,
+        nsec: 1_000_000_000,
+    };
+    assert!(nanosleep(&bad, None).is_none());
+    assert_eq!(errno(), EINVAL);
+
+    let short = TimeSpec {
+        sec: 0,
+        nsec: 50 * MS,
+    };
+    let start = now();
+    nanosleep(&short, None).unwrap();
+    assert!(now() - start >= 50);
+
+    let pid = fork();
+    if pid == 0 {
+        let start = now();
+        let mut req = TimeSpec { sec: 1, nsec: 0 };
+        let mut rem = TimeSpec::default();
+        let mut interrupted = 0;
+        while nanosleep(&req, Some(&mut rem)).is_none() {
+            if errno() != EINTR || rem >= req {
+                exit(1);
+            }
+            interrupted += 1;
+            req = rem;
+        }
+        let ok = interrupted == 1 && now() - start >= 1000;
+        exit(if ok { 0 } else { 2 });
+    }
+
+    // 等子进程睡下
+    sleep(200);
+    kill(pid, SIGUSR1).unwrap();
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code);
+    assert_eq!(exit_code, 0, "sleep was not resumed accurately");
+
+    println!("nanosleep passed!");
+    0
+}
@@ -0,0 +1,92 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use user::perf::{self, Sample, Symbols};
+use user::println;
+use user::process::{exec, fork, waitpid};
+
+/// 默认每个时钟中断都采样
+const DEFAULT_PERIOD: usize = 1;
+/// 只列出样本最多的函数
+const TOP: usize = 20;
+
+/// 运行程序并采样其PC，按函数汇总，系统调用中的样本归到内核函数上
+///
+/// 用法：`perf [-p <period>] <program> [args...]`
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    let (period, args) = if argc >= 3 && argv[1] == "-p" {
+        (argv[2].parse().ok(), &argv[3..])
+    } else {
+        (Some(DEFAULT_PERIOD), &argv[1..])
+    };
+    let (Some(period), Some(program)) = (period.filter(|&p| p > 0), args.first()) else {
+        println!("usage: perf [-p <period>] <program> [args...]");
+        return 1;
+    };
+
+    // 与exec相同的路径规则
+    let path = if program.starts_with('/') {
+        String::from(*program)
+    } else {
+        format!("/usr/bin/{program}")
+    };
+    let Some(symbols) = Symbols::load(&path) else {
+        println!("perf: cannot read the symbol table of {path}");
+        return 1;
+    };
+
+    let pid = fork();
+    if pid == 0 {
+        exec(&path, args);
+        println!("perf: cannot execute {path}");
+        return 1;
+    }
+    perf::start(period, Some(pid));
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code);
+    perf::stop();
+
+    let mut buf = vec![Sample::default(); 256];
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    // 同一内核地址只查询一次
+    let mut kernel_names: BTreeMap<usize, String> = BTreeMap::new();
+    let mut total = 0;
+    loop {
+        let len = perf::read_samples(&mut buf);
+        if len == 0 {
+            break;
+        }
+        for sample in &buf[..len] {
+            let name = match symbols.lookup(sample.pc) {
+                Some(name) => String::from(name),
+                None => kernel_names
+                    .entry(sample.pc)
+                    .or_insert_with(|| match perf::kernel_symbol(sample.pc) {
+                        Some(name) => format!("[kernel] {name}"),
+                        None => String::from("[unknown]"),
+                    })
+                    .clone(),
+            };
+            *counts.entry(name).or_default() += 1;
+        }
+        total += len;
+    }
+
+    println!("{program} exited with {exit_code}, {total} samples every {period} ticks");
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by(|a, b| b.1.cmp(&a.1));
+    for (name, count) in counts.into_iter().take(TOP) {
+        println!("{:>3}% {count:>6}  {name}", count * 100 / total);
+    }
+    0
+}
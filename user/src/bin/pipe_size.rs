@@ -0,0 +1,101 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::vec;
+
+use user::errno::{errno, EBADF, EBUSY, EPERM};
+use user::fs::{close, pipe, pipe_size, set_pipe_size};
+use user::io::{read, write};
+use user::println;
+use user::process::{fork, waitpid};
+use user::thread::exit;
+use user::time::get_time;
+
+const PAGE_SIZE: usize = 4096;
+/// 每轮传输的字节数
+const TOTAL: usize = 1 << 20;
+
+/// 管道容量可经`fcntl`调整，调大后生产者与消费者切换得更少
+#[no_mangle]
+fn main() -> i32 {
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+
+    // 容量向上取整到页，两端看到的相同
+    assert_eq!(set_pipe_size(fds[1], 1), Some(PAGE_SIZE));
+    assert_eq!(pipe_size(fds[0]), Some(PAGE_SIZE));
+    assert_eq!(
+        set_pipe_size(fds[0], 3 * PAGE_SIZE + 1),
+        Some(4 * PAGE_SIZE)
+    );
+    assert_eq!(set_pipe_size(fds[1], usize::MAX), None);
+    assert_eq!(errno(), EPERM);
+    assert_eq!(pipe_size(0), None);
+    assert_eq!(errno(), EBADF);
+
+    // 已缓冲的数据在调整后保留，装不下时拒绝缩小
+    // 用户栈只有一页，缓冲区都放在堆上
+    let data = vec![0x5a; 2 * PAGE_SIZE];
+    assert_eq!(write(fds[1], &data), Some(data.len()));
+    assert_eq!(set_pipe_size(fds[1], PAGE_SIZE), None);
+    assert_eq!(errno(), EBUSY);
+    assert_eq!(set_pipe_size(fds[1], 2 * PAGE_SIZE), Some(2 * PAGE_SIZE));
+    let mut buf = vec![0; 2 * PAGE_SIZE];
+    assert_eq!(read(fds[0], &mut buf), Some(buf.len()));
+    assert_eq!(buf, data);
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+
+    for size in [None, Some(PAGE_SIZE), Some(64 * 1024)] {
+        let ms = transfer(size);
+        println!(
+            "pipe of {} bytes: {}KiB in {}ms",
+            size.unwrap_or(32),
+            TOTAL / 1024,
+            ms
+        );
+    }
+    println!("pipe_size passed!");
+    0
+}
+
+/// 经容量为`size`的管道从子进程向父进程传输[`TOTAL`]字节，返回耗时的毫秒数
+fn transfer(size: Option<usize>) -> isize {
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    if let Some(size) = size {
+        set_pipe_size(fds[1], size).unwrap();
+    }
+    let start = get_time();
+
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]).unwrap();
+        let chunk = vec![1; PAGE_SIZE];
+        for _ in 0..TOTAL / chunk.len() {
+            assert_eq!(write(fds[1], &chunk), Some(chunk.len()));
+        }
+        exit(0);
+    }
+
+    close(fds[1]).unwrap();
+    let mut buf = vec![0; PAGE_SIZE];
+    let mut received = 0;
+    loop {
+        match read(fds[0], &mut buf) {
+            Some(0) => break,
+            Some(len) => received += len,
+            None => panic!("reading the pipe failed"),
+        }
+    }
+    close(fds[0]).unwrap();
+    assert_eq!(received, TOTAL);
+
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code).unwrap();
+    assert_eq!(exit_code, 0);
+    get_time() - start
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EPERM};
+use user::power::{poweroff, reboot};
+use user::println;
+
+/// 用法：`poweroff [-r]`，`-r`表示重启
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    let result = match argv.get(1) {
+        None => poweroff(),
+        Some(&"-r") if argc == 2 => reboot(),
+        Some(_) => {
+            println!("usage: poweroff [-r]");
+            return 1;
+        }
+    };
+    if result.is_some() {
+        return 0;
+    }
+
+    let reason = match errno() {
+        EPERM => "permission denied",
+        _ => "unknown error",
+    };
+    println!("poweroff: {reason}");
+    1
+}
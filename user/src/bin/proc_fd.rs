@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use user::fs::{close, eventfd, open, pipe, OpenFlag, ReadDir};
+use user::io::read;
+use user::println;
+use user::process::getpid;
+
+/// `/proc/<pid>/fd`列出各描述符的种类、访问模式、偏移量与路径
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(fds("/proc/self/fd"), [0, 1, 2]);
+    assert_eq!(cat("/proc/self/fd/0"), "type:\ttty\nflags:\tr\n");
+    assert_eq!(cat("/proc/self/fd/1"), "type:\ttty\nflags:\tw\n");
+
+    let mut pipe_fd = [0; 2];
+    pipe(&mut pipe_fd).unwrap();
+    let event_fd = eventfd(0, Default::default()).unwrap();
+    let file = open("/proc_fd", OpenFlag::read_only()).unwrap();
+    let mut buf = [0; 10];
+    assert_eq!(read(file, &mut buf), Some(buf.len()));
+
+    assert_eq!(fds("/proc/self/fd"), [0, 1, 2, 3, 4, 5, 6]);
+    assert_eq!(fds(&format!("/proc/{}/fd", getpid())), fds("/proc/self/fd"));
+    assert_eq!(
+        cat(&format!("/proc/self/fd/{}", pipe_fd[0])),
+        "type:\tpipe\nflags:\tr\n"
+    );
+    assert_eq!(
+        cat(&format!("/proc/self/fd/{}", pipe_fd[1])),
+        "type:\tpipe\nflags:\tw\n"
+    );
+    assert_eq!(
+        cat(&format!("/proc/self/fd/{event_fd}")),
+        "type:\teventfd\nflags:\trw\n"
+    );
+    assert_eq!(
+        cat(&format!("/proc/self/fd/{file}")),
+        "type:\tfile\nflags:\tr\noffset:\t10\npath:\t/proc_fd\n"
+    );
+
+    // 关闭的描述符随即消失
+    for fd in [pipe_fd[0], pipe_fd[1], event_fd, file] {
+        close(fd).unwrap();
+    }
+    assert_eq!(fds("/proc/self/fd"), [0, 1, 2]);
+    assert!(open("/proc/self/fd/3", OpenFlag::read_only()).is_none());
+
+    println!("proc_fd passed!");
+    0
+}
+
+/// 目录中的描述符，不含为读取目录而打开的那个
+fn fds(path: &str) -> Vec<usize> {
+    let dir = open(path, OpenFlag::read_only()).unwrap();
+    let fds = ReadDir::new(dir)
+        .map(|dirent| dirent.name.parse().unwrap())
+        .collect();
+    close(dir).unwrap();
+    fds
+}
+
+fn cat(path: &str) -> String {
+    let fd = open(path, OpenFlag::read_only()).unwrap();
+    let mut text = Vec::new();
+    let mut buf = [0; 64];
+    loop {
+        let len = read(fd, &mut buf).unwrap();
+        if len == 0 {
+            break;
+        }
+        text.extend_from_slice(&buf[..len]);
+    }
+    close(fd).unwrap();
+    String::from_utf8(text).unwrap()
+}
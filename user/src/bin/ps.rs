@@ -0,0 +1,22 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::println;
+use user::process::processes;
+
+/// 列出所有进程
+#[no_mangle]
+fn main() -> i32 {
+    println!("{:>5} {:>5} {:>4} NAME", "PID", "PPID", "THR");
+    for info in processes() {
+        println!(
+            "{:>5} {:>5} {:>4} {}",
+            info.pid,
+            info.ppid,
+            info.threads,
+            info.name()
+        );
+    }
+    0
+}
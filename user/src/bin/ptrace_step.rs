@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::process::{exec, fork, waitpid};
+use user::ptrace::{cont, get_regs, peek, single_step, trace_me, wait_stop};
+
+#[macro_use]
+extern crate user;
+
+/// 单步执行的指令数
+const STEPS: usize = 16;
+
+#[no_mangle]
+fn main() -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        trace_me().unwrap();
+        exec::<&str, _>("hello_world", []);
+        unreachable!();
+    }
+
+    wait_stop(pid).expect("the child exited before exec");
+    for _ in 0..STEPS {
+        let regs = get_regs(pid).unwrap();
+        let inst = peek(pid, regs.pc & !0b111).unwrap();
+        println!("pc={:#x} sp={:#x} word={inst:#018x}", regs.pc, regs.x[2]);
+
+        single_step(pid).unwrap();
+        if wait_stop(pid).is_none() {
+            break;
+        }
+    }
+
+    cont(pid);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid, &mut exit_code), Some(pid));
+    println!("ptrace_step passed, exit_code={exit_code}");
+
+    0
+}
@@ -0,0 +1,68 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EDQUOT, ENOENT, ESRCH};
+use user::fs::quota::QUOTA_BLOCK;
+use user::fs::{
+    close, fstat, ftruncate, open, quota_get, quota_set, quota_sync, unlink, DiskQuota, OpenFlag,
+};
+use user::io::write;
+use user::println;
+
+const PATH: &str = "quota.txt";
+/// 测试以超级用户运行，配额对其同样生效
+const UID: u32 = 0;
+
+/// 硬上限任何时候都不能超过，缩减与删除退还占用，越过软上限后开始宽限期
+#[no_mangle]
+fn main() -> i32 {
+    let buf = [b'q'; 4 * QUOTA_BLOCK as usize];
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::RDWR).unwrap();
+    write(fd, &buf[..2 * QUOTA_BLOCK as usize]).unwrap();
+    let before = quota_get(".", UID).unwrap();
+    let stat = fstat(fd).unwrap();
+    let allocated = stat.blocks * stat.block_size;
+    let file_blocks = DiskQuota::blocks(allocated);
+    assert!(before.used_blocks >= file_blocks && file_blocks >= 2);
+
+    // 按整块计：已分配的块可以写满，多出一个字节就要分配新块
+    quota_set(".", UID, before.used_blocks, 0).unwrap();
+    let room = (allocated - stat.size) as usize;
+    assert!(room < buf.len());
+    assert!(write(fd, &buf[..room + 1]).is_none());
+    assert_eq!(errno(), EDQUOT);
+    assert_eq!(write(fd, &buf[..room]), Some(room));
+    assert_eq!(quota_get(".", UID).unwrap().used_blocks, before.used_blocks);
+    let size = 2 * QUOTA_BLOCK as usize + room;
+    assert!(ftruncate(fd, size + 1).is_none());
+    assert_eq!(errno(), EDQUOT);
+
+    // 缩减总是允许的
+    ftruncate(fd, 2 * QUOTA_BLOCK as usize).unwrap();
+    assert_eq!(quota_get(".", UID).unwrap().used_blocks, before.used_blocks);
+
+    // 宽限期内仍可越过软上限
+    quota_set(".", UID, 0, 1).unwrap();
+    assert_ne!(quota_get(".", UID).unwrap().grace_until, 0);
+    assert_eq!(write(fd, b"q"), Some(1));
+    close(fd);
+
+    unlink(PATH).unwrap();
+    assert_eq!(
+        quota_get(".", UID).unwrap().used_blocks,
+        before.used_blocks - file_blocks
+    );
+
+    quota_set(".", UID, 0, 0).unwrap();
+    assert_eq!(quota_get(".", UID).unwrap().grace_until, 0);
+    quota_sync(".").unwrap();
+
+    assert!(quota_get("/proc", UID).is_none());
+    assert_eq!(errno(), ESRCH);
+    assert!(quota_get("quota.none", UID).is_none());
+    assert_eq!(errno(), ENOENT);
+
+    println!("quota passed!");
+    0
+}
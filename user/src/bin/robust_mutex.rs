@@ -0,0 +1,41 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EOWNERDEAD};
+use user::println;
+use user::sync::{
+    block_mutex, mutex_lock, mutex_unlock, robust_semaphore_create, semaphore_down, semaphore_up,
+};
+use user::thread::{exit, spawn, waittid};
+
+/// 取走锁和信号量后不归还便退出
+fn holder(ids: *const [usize; 2]) -> ! {
+    let [mutex, sem] = unsafe { *ids };
+    mutex_lock(mutex).unwrap();
+    semaphore_down(sem).unwrap();
+    exit(0)
+}
+
+/// 线程持有锁或健壮的信号量退出后，资源被归还，取用者得到`EOWNERDEAD`
+#[no_mangle]
+fn main() -> i32 {
+    let ids = [block_mutex(), robust_semaphore_create(1)];
+    let tid = spawn(holder as usize, &ids as *const _ as usize);
+    assert_eq!(waittid(tid), Some(0));
+
+    let [mutex, sem] = ids;
+    assert!(mutex_lock(mutex).is_none());
+    assert_eq!(errno(), EOWNERDEAD);
+    mutex_unlock(mutex).unwrap();
+    assert!(semaphore_down(sem).is_none());
+    assert_eq!(errno(), EOWNERDEAD);
+    semaphore_up(sem).unwrap();
+
+    // 毒化是永久的
+    assert!(mutex_lock(mutex).is_none());
+    assert_eq!(errno(), EOWNERDEAD);
+    mutex_unlock(mutex).unwrap();
+    println!("robust_mutex passed!");
+    0
+}
@@ -0,0 +1,64 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+#[macro_use]
+extern crate user;
+
+use user::errno::{errno, EINTR};
+use user::fs::{close, pipe};
+use user::io::{read, write};
+use user::process::{fork, waitpid};
+use user::signal::{kill, sigaction, SignalAction, SA_RESTART, SIGUSR1};
+use user::thread::{exit, sleep};
+
+/// 子进程阻塞在空管道上时被SIGUSR1打断，`restart`决定是否设置[`SA_RESTART`]
+fn interrupted_read(restart: bool) -> bool {
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd).unwrap();
+    let [read_end, write_end] = pipe_fd;
+
+    let pid = fork();
+    if pid == 0 {
+        close(write_end);
+        if restart {
+            let action = SignalAction {
+                flags: SA_RESTART,
+                ..Default::default()
+            };
+            sigaction(SIGUSR1, &action, &mut SignalAction::default()).unwrap();
+        }
+
+        let mut buf = [0u8; 1];
+        let ok = match read(read_end, &mut buf) {
+            // 重新执行的读取等到了父进程随后写入的数据
+            Some(1) => restart && buf[0] == b'x',
+            None => !restart && errno() == EINTR,
+            _ => false,
+        };
+        exit(if ok { 0 } else { 1 });
+    }
+
+    close(read_end);
+    // 等子进程阻塞
+    sleep(100);
+    kill(pid, SIGUSR1).unwrap();
+    sleep(100);
+    // 未设置SA_RESTART的子进程此时已经返回，写入的数据无人读取
+    write(write_end, b"x");
+    close(write_end);
+
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code);
+    exit_code == 0
+}
+
+#[no_mangle]
+fn main() -> i32 {
+    assert!(interrupted_read(false), "read did not fail with EINTR");
+    println!("sig_restart: EINTR ok");
+    assert!(interrupted_read(true), "read was not restarted");
+    println!("sig_restart: SA_RESTART ok");
+    println!("sig_restart passed!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EAGAIN, EINVAL};
+use user::println;
+use user::process::{fork, getpid, try_wait};
+use user::signal::{kill, sigtimedwait, SignalFlag, SIGCHLD, SIGUSR1};
+use user::thread::{exit, sleep};
+
+const CHILDREN: usize = 3;
+
+/// 子进程结束时父进程收到`SIGCHLD`，等到后一次回收所有结束的子进程
+#[no_mangle]
+fn main() -> i32 {
+    // 没有子进程时只会超时
+    assert_eq!(sigtimedwait(SignalFlag::SIGCHLD.into(), Some(10)), None);
+    assert_eq!(errno(), EAGAIN);
+    assert_eq!(sigtimedwait(Default::default(), Some(10)), None);
+    assert_eq!(errno(), EINVAL);
+
+    // 等待的信号已在等待递送时立即取走
+    kill(getpid(), SIGUSR1).unwrap();
+    assert_eq!(
+        sigtimedwait(SignalFlag::SIGUSR1.into(), None),
+        Some(SIGUSR1)
+    );
+
+    for i in 0..CHILDREN {
+        if fork() == 0 {
+            sleep(20 * (i + 1));
+            exit(i as i32 + 1);
+        }
+    }
+
+    // 多个子进程接连结束，`SIGCHLD`可能合并为一次
+    let mut reaped = 0;
+    let mut codes = 0;
+    while reaped < CHILDREN {
+        assert_eq!(
+            sigtimedwait(SignalFlag::SIGCHLD.into(), Some(5000)),
+            Some(SIGCHLD)
+        );
+        let mut exit_code = 0;
+        while let Some(Some(_)) = try_wait(&mut exit_code) {
+            reaped += 1;
+            codes |= 1 << exit_code;
+        }
+    }
+    assert_eq!(codes, 0b1110);
+    assert_eq!(try_wait(&mut 0), None);
+
+    println!("sigchld passed!");
+    0
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::fs::{close, open, OpenFlag};
+use user::io::read;
+use user::process::{spawn_with, waitpid, SpawnFileActions};
+
+#[macro_use]
+extern crate user;
+
+const OUTPUT: &str = "spawn_redirect.out";
+
+#[no_mangle]
+fn main() -> i32 {
+    let mut actions = SpawnFileActions::new();
+    actions.add_open(1, OUTPUT, OpenFlag::CREATE | OpenFlag::WRONLY);
+    let sub_pid = spawn_with("hello_world", &actions).unwrap();
+    let mut xstate = 0;
+    assert_eq!(waitpid(sub_pid, &mut xstate), Some(sub_pid));
+    assert_eq!(xstate, 0);
+
+    let fd = open(OUTPUT, OpenFlag::read_only()).unwrap();
+    let mut buf = [0u8; 128];
+    let len = read(fd, &mut buf).unwrap();
+    close(fd).unwrap();
+
+    let output = core::str::from_utf8(&buf[..len]).unwrap();
+    assert!(output.contains("Hello world"));
+    println!("spawn_redirect passed!");
+
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::power::suspend;
+use user::println;
+use user::time::get_time;
+
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    let timeout_ms = if argc > 1 {
+        let Ok(timeout_ms) = argv[1].parse() else {
+            println!("usage: suspend [TIMEOUT_MS]");
+            return -1;
+        };
+        timeout_ms
+    } else {
+        1000
+    };
+
+    let start = get_time();
+    let Some(source) = suspend(timeout_ms) else {
+        println!("suspend: permission denied");
+        return -1;
+    };
+    println!("woken by {:?} after {}ms", source, get_time() - start);
+    0
+}
@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::fs::fstat;
+use user::println;
+use user::process::getpid;
+use user::profile;
+use user::thread::gettid;
+use user::time::now;
+
+const ROUNDS: usize = 100_000;
+
+/// 测量只读取当前任务状态的系统调用的往返耗时，比较内核中访问当前任务的开销。
+///
+/// 内核启用`syscall-profile`特性时，另从延迟直方图读出每次调用在内核中的平均耗时，
+/// 以`mtime`的计数为单位，不含陷入与返回，更能反映访问当前任务的开销
+#[no_mangle]
+fn main() -> i32 {
+    let benches: [(&str, fn()); 3] = [
+        ("getpid", || {
+            getpid();
+        }),
+        ("gettid", || {
+            gettid();
+        }),
+        ("fstat", || {
+            fstat(1).unwrap();
+        }),
+    ];
+
+    let profiling = profile::snapshot(true).is_some();
+    for (name, bench) in benches {
+        let start = now();
+        for _ in 0..ROUNDS {
+            bench();
+        }
+        let ms = (now() - start) as usize;
+        println!(
+            "{name:>6}: {ROUNDS} calls in {ms}ms, {}ns each",
+            ms * 1_000_000 / ROUNDS
+        );
+        if profiling {
+            // 清空后只有被测的系统调用调用了这么多次
+            let histograms = profile::snapshot(true).unwrap();
+            let histogram = histograms.iter().max_by_key(|h| h.count).unwrap();
+            println!("{:>6}  {} ticks in kernel each", "", histogram.mean());
+        }
+    }
+    println!("syscall_bench passed!");
+    0
+}
@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+#[macro_use]
+extern crate user;
+
+use user::errno::{errno, ENOSYS};
+use user::process::getpid;
+use user::profile;
+use user::thread::yield_;
+use user::time::get_time;
+
+/// 打印各系统调用的延迟分布，只列出非空的桶
+#[no_mangle]
+fn main() -> i32 {
+    // 先清空，只统计本程序产生的调用
+    if profile::snapshot(true).is_none() {
+        if errno() == ENOSYS {
+            println!("syscall profiling is disabled, rebuild the kernel with SYSCALL_PROFILE=on");
+        }
+        return 1;
+    }
+
+    for _ in 0..100 {
+        getpid();
+        get_time();
+        yield_();
+    }
+
+    let histograms = profile::snapshot(false).unwrap();
+    for histogram in &histograms {
+        println!(
+            "syscall {:>4}: count={} mean={} ticks",
+            histogram.id,
+            histogram.count,
+            histogram.mean()
+        );
+        for (i, &count) in histogram.buckets.iter().enumerate() {
+            if count != 0 {
+                println!("    [2^{i:<2}, 2^{:<2}): {count}", i + 1);
+            }
+        }
+    }
+    0
+}
@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::println;
+use user::process::{getpid, processes};
+use user::thread::{name, set_name, TASK_NAME_LEN};
+
+/// exec时取程序名，改名后进程列表随之改变，超长的名称被截断
+#[no_mangle]
+fn main() -> i32 {
+    assert_eq!(name(), "task_name");
+
+    set_name("renamed");
+    assert_eq!(name(), "renamed");
+    let pid = getpid();
+    let me = processes()
+        .into_iter()
+        .find(|info| info.pid == pid)
+        .unwrap();
+    assert_eq!(me.name(), "renamed");
+
+    set_name("a-rather-long-thread-name");
+    assert_eq!(name().len(), TASK_NAME_LEN - 1);
+
+    println!("task_name passed!");
+    0
+}
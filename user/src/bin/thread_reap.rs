@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EINVAL};
+use user::println;
+use user::thread::{detach, exit, sleep, spawn, thread_counts, waittid, ThreadCounts};
+
+const ROUNDS: usize = 100;
+
+fn worker(code: usize) -> ! {
+    exit(code as i32)
+}
+
+/// 等待过或分离的线程都被回收，TID被沿用，线程数不会随创建次数增长
+#[no_mangle]
+fn main() -> i32 {
+    let only_main = ThreadCounts {
+        running: 1,
+        exited: 0,
+    };
+
+    for i in 0..ROUNDS {
+        let tid = spawn(worker as usize, i);
+        assert_eq!(tid, 1);
+        assert_eq!(waittid(tid), Some(i as i32));
+    }
+    assert_eq!(thread_counts(), only_main);
+
+    // 已退出而未等待的线程占着TID，退出码不会被新线程冲掉
+    let first = spawn(worker as usize, 7);
+    sleep(10);
+    assert_eq!(thread_counts().exited, 1);
+    let second = spawn(worker as usize, 8);
+    assert_ne!(first, second);
+    assert_eq!(waittid(first), Some(7));
+    assert_eq!(waittid(second), Some(8));
+
+    for i in 0..ROUNDS {
+        let tid = spawn(worker as usize, i);
+        detach(tid).unwrap();
+    }
+    sleep(10);
+    assert_eq!(thread_counts(), only_main);
+
+    // 主线程不可分离
+    assert!(detach(0).is_none());
+    assert_eq!(errno(), EINVAL);
+    println!("thread_reap passed!");
+    0
+}
@@ -0,0 +1,30 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EBUSY, EINVAL, ENOENT, EPERM};
+use user::fs::umount;
+use user::println;
+
+#[no_mangle]
+fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc != 2 {
+        println!("usage: umount <dir>");
+        return 1;
+    }
+
+    let dir = argv[1];
+    if umount(dir).is_some() {
+        return 0;
+    }
+
+    let reason = match errno() {
+        ENOENT => "no such directory",
+        EINVAL => "not mounted",
+        EBUSY => "target is busy",
+        EPERM => "must be superuser",
+        _ => "unknown error",
+    };
+    println!("umount: {dir}: {reason}");
+    1
+}
@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EBUSY, EINVAL, ENOENT};
+use user::fs::{close, mkdir, open, rmdir, umount, OpenFlag};
+use user::println;
+
+/// 只有挂载点能卸载，根目录不能卸载；失败的卸载不影响挂载表
+#[no_mangle]
+fn main() -> i32 {
+    assert!(umount("/").is_none());
+    assert_eq!(errno(), EBUSY);
+
+    mkdir("umount_errno.dir").unwrap();
+    assert!(umount("umount_errno.dir").is_none());
+    assert_eq!(errno(), EINVAL);
+    rmdir("umount_errno.dir").unwrap();
+
+    assert!(umount("umount_errno.missing").is_none());
+    assert_eq!(errno(), ENOENT);
+
+    // /proc仍挂载着
+    close(open("/proc/self/fd", OpenFlag::read_only()).unwrap());
+    println!("umount_errno passed!");
+    0
+}
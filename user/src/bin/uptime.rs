@@ -0,0 +1,17 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::println;
+use user::process::sysinfo;
+
+#[no_mangle]
+fn main() -> i32 {
+    let info = sysinfo();
+    let [(l1, f1), (l5, f5), (l15, f15)] = info.load_average();
+    println!(
+        "up {}s, {} processes, load average: {l1}.{f1:02}, {l5}.{f5:02}, {l15}.{f15:02}",
+        info.uptime, info.procs
+    );
+    0
+}
@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::ptr::null;
+use core::slice;
+
+use user::errno::{errno, EFAULT};
+use user::fs::{close, fstat, getdents, lseek, open, unlink, OpenFlag, Whence};
+use user::io::{read, write};
+use user::mem::{mmap, munmap, ProtectFlag};
+use user::println;
+
+const PAGE_SIZE: usize = 4096;
+const PATH: &str = "user_buffer.txt";
+
+/// 跨越多页的缓冲区逐页翻译，读写完整；缓冲区中有未映射的页时报EFAULT，而不是只读写前几页。
+/// 内核不会写入用户不可写的页，包括与其它进程共享页帧的代码段
+#[no_mangle]
+fn main() -> i32 {
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::RDWR).unwrap();
+
+    // 首尾都不按页对齐，横跨七页
+    let src = mmap(null(), 8 * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    for (i, b) in src.iter_mut().enumerate() {
+        *b = (i % 251) as u8;
+    }
+    let len = 6 * PAGE_SIZE + 1000;
+    assert_eq!(write(fd, &src[100..100 + len]), Some(len));
+
+    let dest = mmap(null(), 8 * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    assert_eq!(lseek(fd, 0, Whence::Set), Some(0));
+    assert_eq!(read(fd, &mut dest[3000..3000 + len]), Some(len));
+    assert_eq!(dest[3000..3000 + len], src[100..100 + len]);
+    assert!(dest[..3000].iter().all(|&b| b == 0));
+    assert!(dest[3000 + len..].iter().all(|&b| b == 0));
+
+    // 紧随其后的两页映射后又撤销，得到后半截未映射的缓冲区
+    let area = mmap(null(), 2 * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    area.fill(0xa5);
+    let end = area.as_ptr_range().end;
+    let tail = mmap(end, 2 * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    assert_eq!(tail.as_ptr(), end);
+    munmap(tail).unwrap();
+    let span =
+        unsafe { slice::from_raw_parts_mut(area.as_mut_ptr().add(PAGE_SIZE), 2 * PAGE_SIZE) };
+
+    assert_eq!(lseek(fd, 0, Whence::Set), Some(0));
+    assert!(read(fd, span).is_none());
+    assert_eq!(errno(), EFAULT);
+    assert!(area.iter().all(|&b| b == 0xa5));
+    assert_eq!(lseek(fd, 0, Whence::Cur), Some(0));
+
+    assert!(write(fd, span).is_none());
+    assert_eq!(errno(), EFAULT);
+    assert_eq!(fstat(fd).unwrap().size, len as u64);
+
+    let dir = open(".", OpenFlag::read_only()).unwrap();
+    assert!(getdents(dir, span).is_none());
+    assert_eq!(errno(), EFAULT);
+    close(dir);
+
+    let read_only = mmap(null(), PAGE_SIZE, ProtectFlag::R).unwrap();
+    assert_eq!(lseek(fd, 0, Whence::Set), Some(0));
+    assert!(read(fd, read_only).is_none());
+    assert_eq!(errno(), EFAULT);
+    assert!(read_only.iter().all(|&b| b == 0));
+    munmap(read_only).unwrap();
+
+    let text = unsafe { slice::from_raw_parts_mut(main as usize as *mut u8, 16) };
+    let code: [u8; 16] = text.try_into().unwrap();
+    assert!(read(fd, text).is_none());
+    assert_eq!(errno(), EFAULT);
+    assert_eq!(*text, code);
+
+    munmap(area).unwrap();
+    munmap(dest).unwrap();
+    munmap(src).unwrap();
+    close(fd);
+    unlink(PATH).unwrap();
+    println!("user_buffer passed!");
+    0
+}
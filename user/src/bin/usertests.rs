@@ -2,6 +2,7 @@
 #![no_main]
 #![feature(format_args_nl)]
 
+use user::io::set_stdout_tagging;
 use user::println;
 use user::process::{exec, fork, waitpid};
 
@@ -10,6 +11,7 @@ use user::process::{exec, fork, waitpid};
 
 // item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
+    ("affinity", "", "", "", 0),
     ("exit", "", "", "", 0),
     ("fantastic_text", "", "", "", 0),
     ("forktest_simple", "", "", "", 0),
@@ -17,10 +19,32 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktest2", "", "", "", 0),
     ("forktree", "", "", "", 0),
     ("hello_world", "", "", "", 0),
+    ("madvise", "", "", "", 0),
+    ("ksm_cow", "", "", "", 0),
     ("matrix", "", "", "", 0),
+    ("mmap", "", "", "", 0),
+    ("mmap_file", "", "", "", 0),
+    ("pipe_size", "", "", "", 0),
+    ("proc_fd", "", "", "", 0),
+    ("ftruncate", "", "", "", 0),
+    ("lseek", "", "", "", 0),
+    ("sigchld", "", "", "", 0),
+    ("exec_cache", "", "", "", 0),
+    ("utimensat", "", "", "", 0),
+    ("name_max", "", "", "", 0),
+    ("user_buffer", "", "", "", 0),
+    ("link_errno", "", "", "", 0),
+    ("umount_errno", "", "", "", 0),
+    ("inotify", "", "", "", 0),
+    ("quota", "", "", "", 0),
+    ("devfs", "", "", "", 0),
+    ("getdents_plus", "", "", "", 0),
     ("sleep_simple", "", "", "", 0),
     ("sleep", "", "", "", 0),
+    ("nanosleep", "", "", "", 0),
+    ("task_name", "", "", "", 0),
     ("yield", "", "", "", 0),
+    ("zero_page", "", "", "", 0),
 ];
 
 static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow", "", "", "", -2)];
@@ -77,8 +101,11 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
 
 #[no_mangle]
 fn main() -> i32 {
+    // 测试中的多个进程同时输出，按行标记进程号
+    set_stdout_tagging(true);
     let succ_num = run_tests(SUCC_TESTS);
     let err_num = run_tests(FAIL_TESTS);
+    set_stdout_tagging(false);
     if succ_num == SUCC_TESTS.len() as i32 && err_num == FAIL_TESTS.len() as i32 {
         println!(
             "{} of sueecssed apps, {} of failed apps run correctly. \nUsertests passed!",
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use user::errno::{errno, EINVAL, ENOENT, EROFS};
+use user::fs::{close, fstat, open, unlink, utimensat, OpenFlag, UTIME_NOW, UTIME_OMIT};
+use user::io::write;
+use user::println;
+use user::time::{gettimeofday, TimeSpec};
+
+const PATH: &str = "utimensat.txt";
+/// 2001-09-09T01:46:40Z
+const BILLENNIUM: usize = 1_000_000_000;
+const DAY: usize = 86400;
+
+fn at(sec: usize, nsec: usize) -> TimeSpec {
+    TimeSpec { sec, nsec }
+}
+
+/// 创建与写入时记录时间，也可显式设置；FAT的修改时间精确到2秒，访问时间只精确到日
+#[no_mangle]
+fn main() -> i32 {
+    let before = gettimeofday().sec as u64;
+    let fd = open(PATH, OpenFlag::CREATE | OpenFlag::RDWR).unwrap();
+    write(fd, b"timestamps").unwrap();
+    let stat = fstat(fd).unwrap();
+    let after = gettimeofday().sec as u64;
+    assert!((before - 1..=after).contains(&stat.mtime));
+    assert!((before - 1..=after).contains(&stat.ctime));
+    assert!(stat.atime <= after && after - stat.atime < DAY as u64);
+
+    utimensat(PATH, Some([at(BILLENNIUM, 0), at(BILLENNIUM + 1, 999)])).unwrap();
+    let stat = fstat(fd).unwrap();
+    assert_eq!(stat.mtime, BILLENNIUM as u64);
+    assert_eq!(stat.atime, (BILLENNIUM / DAY * DAY) as u64);
+
+    // 保持访问时间，修改时间设为当前
+    utimensat(PATH, Some([at(0, UTIME_OMIT), at(0, UTIME_NOW)])).unwrap();
+    let stat = fstat(fd).unwrap();
+    assert_eq!(stat.atime, (BILLENNIUM / DAY * DAY) as u64);
+    assert!(stat.mtime + 2 >= before);
+
+    // 创建时间不随之改变
+    utimensat(PATH, None).unwrap();
+    assert!(fstat(fd).unwrap().ctime <= after);
+    close(fd);
+
+    assert!(utimensat(PATH, Some([at(0, 1_000_000_000), at(0, 0)])).is_none());
+    assert_eq!(errno(), EINVAL);
+    unlink(PATH).unwrap();
+    assert!(utimensat(PATH, None).is_none());
+    assert_eq!(errno(), ENOENT);
+    assert!(utimensat("/proc/self/fd", None).is_none());
+    assert_eq!(errno(), EROFS);
+
+    println!("utimensat passed!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::ptr::null;
+
+use user::fs::{close, pipe};
+use user::io::{read, write};
+use user::mem::{mmap, munmap, ProtectFlag};
+use user::println;
+use user::process::{fork, waitpid};
+use user::thread::exit;
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 64;
+
+/// 读取从未写过的匿名页得到全零，写入某页不会波及同样映射着零页的其它页
+#[no_mangle]
+fn main() -> i32 {
+    let area = mmap(null(), PAGES * PAGE_SIZE, ProtectFlag::R | ProtectFlag::W).unwrap();
+    let other = mmap(null(), PAGES * PAGE_SIZE, ProtectFlag::R).unwrap();
+    assert!(area.iter().all(|&b| b == 0));
+    assert!(other.iter().all(|&b| b == 0));
+
+    // 写时复制只换掉被写的页
+    area[PAGE_SIZE..2 * PAGE_SIZE].fill(0xa5);
+    assert!(area[..PAGE_SIZE].iter().all(|&b| b == 0));
+    assert!(area[2 * PAGE_SIZE..].iter().all(|&b| b == 0));
+    assert!(other.iter().all(|&b| b == 0));
+
+    // 内核代为写入读过的页时同样不能写到零页上
+    let mut fds = [0; 2];
+    pipe(&mut fds).unwrap();
+    assert_eq!(write(fds[1], b"zero"), Some(4));
+    let page = &mut area[3 * PAGE_SIZE..4 * PAGE_SIZE];
+    assert_eq!(read(fds[0], &mut page[..4]), Some(4));
+    assert_eq!(&page[..4], b"zero");
+    assert!(other.iter().all(|&b| b == 0));
+    close(fds[0]).unwrap();
+    close(fds[1]).unwrap();
+
+    // 子进程看到父进程写过的页，各自的写入互不影响
+    let pid = fork();
+    if pid == 0 {
+        assert!(area[PAGE_SIZE..2 * PAGE_SIZE].iter().all(|&b| b == 0xa5));
+        assert!(area[4 * PAGE_SIZE..].iter().all(|&b| b == 0));
+        area[4 * PAGE_SIZE..].fill(0x5a);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    waitpid(pid, &mut exit_code).unwrap();
+    assert_eq!(exit_code, 0);
+    assert!(area[4 * PAGE_SIZE..].iter().all(|&b| b == 0));
+
+    munmap(other).unwrap();
+    munmap(area).unwrap();
+    println!("zero_page passed!");
+    0
+}
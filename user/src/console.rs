@@ -6,17 +6,53 @@ use crate::io::{read, write};
 const STDIN: usize = 0;
 const STDOUT: usize = 1;
 
-struct Stdout;
+/// 单次输出的缓冲区大小
+const BUFFER_SIZE: usize = 256;
+
+/// 先在栈上拼好再整体写出，使多线程的输出不在行内交错，且无需分配堆内存
+struct Stdout {
+    buf: [u8; BUFFER_SIZE],
+    len: usize,
+}
+
+impl Stdout {
+    const fn new() -> Self {
+        Self {
+            buf: [0; BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.len > 0 {
+            write(STDOUT, &self.buf[..self.len]).unwrap();
+            self.len = 0;
+        }
+    }
+}
 
 impl Write for Stdout {
     fn write_str(&mut self, s: &str) -> fmt::Result {
-        write(STDOUT, s.as_bytes()).unwrap();
+        let bytes = s.as_bytes();
+        if self.len + bytes.len() > BUFFER_SIZE {
+            self.flush();
+        }
+
+        if bytes.len() > BUFFER_SIZE {
+            write(STDOUT, bytes).unwrap();
+        } else {
+            self.buf[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+            self.len += bytes.len();
+        }
+
         Ok(())
     }
 }
 
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    let mut stdout = Stdout::new();
+    stdout.write_fmt(args).unwrap();
+    stdout.flush();
 }
 
 #[macro_export]
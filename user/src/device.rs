@@ -0,0 +1,8 @@
+use alloc::ffi::CString;
+
+use crate::syscall::sys_device_present;
+
+/// 名为`name`的设备是否已由内核登记，如`gpu`、`keyboard`、`block0`
+pub fn present(name: &str) -> bool {
+    CString::new(name).is_ok_and(|name| sys_device_present(&name) == 1)
+}
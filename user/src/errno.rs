@@ -0,0 +1,84 @@
+//! 线程局部的错误码
+//!
+//! 系统调用的封装失败时，将内核返回值的相反数记为当前线程的错误码，
+//! 成功时不改变错误码。TID不小于[`MAX_THREADS`]的线程没有槽位，不记录错误码，读出恒为0。
+
+use core::cell::Cell;
+
+use crate::thread::{ThreadLocal, MAX_THREADS};
+
+/// 操作不被允许
+pub const EPERM: isize = 1;
+/// 文件或目录不存在
+pub const ENOENT: isize = 2;
+/// 进程不存在
+pub const ESRCH: isize = 3;
+/// 被信号打断
+pub const EINTR: isize = 4;
+/// 输入输出错误
+pub const EIO: isize = 5;
+/// 参数列表过长
+pub const E2BIG: isize = 7;
+/// 非法的文件描述符
+pub const EBADF: isize = 9;
+/// 资源暂时不可用，如等待超时
+pub const EAGAIN: isize = 11;
+/// 内存不足，或地址区间未被映射
+pub const ENOMEM: isize = 12;
+/// 权限不足
+pub const EACCES: isize = 13;
+/// 非法的地址
+pub const EFAULT: isize = 14;
+/// 资源正被占用
+pub const EBUSY: isize = 16;
+/// 文件已存在
+pub const EEXIST: isize = 17;
+/// 跨越了文件系统
+pub const EXDEV: isize = 18;
+/// 设备不支持该操作
+pub const ENODEV: isize = 19;
+/// 不是目录
+pub const ENOTDIR: isize = 20;
+/// 是目录
+pub const EISDIR: isize = 21;
+/// 非法的参数
+pub const EINVAL: isize = 22;
+/// 全系统打开的文件描述符已达上限
+pub const ENFILE: isize = 23;
+/// 进程打开的文件描述符已达上限
+pub const EMFILE: isize = 24;
+/// 文件不支持该控制请求
+pub const ENOTTY: isize = 25;
+/// 文件超过了允许的最大字节数
+pub const EFBIG: isize = 27;
+/// 文件系统已满
+pub const ENOSPC: isize = 28;
+/// 文件是管道等不可定位的对象
+pub const ESPIPE: isize = 29;
+/// 文件所在的文件系统不支持修改
+pub const EROFS: isize = 30;
+/// 路径或其中某一分量过长
+pub const ENAMETOOLONG: isize = 36;
+/// 未实现的系统调用
+pub const ENOSYS: isize = 38;
+/// 目录非空
+pub const ENOTEMPTY: isize = 39;
+/// 文件系统不支持该操作，如FAT不支持硬链接
+pub const EOPNOTSUPP: isize = 95;
+/// 超出了磁盘配额
+pub const EDQUOT: isize = 122;
+/// 锁的前一个持有者未释放便退出了，锁已转交给调用者
+pub const EOWNERDEAD: isize = 130;
+
+static ERRNO: ThreadLocal<Cell<isize>> = ThreadLocal::new([const { Cell::new(0) }; MAX_THREADS]);
+
+/// 当前线程最近一次失败的系统调用的错误码
+pub fn errno() -> isize {
+    ERRNO.get().map_or(0, Cell::get)
+}
+
+pub(crate) fn set_errno(code: isize) {
+    if let Some(errno) = ERRNO.get() {
+        errno.set(code);
+    }
+}
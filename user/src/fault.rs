@@ -0,0 +1,26 @@
+//! 系统调用的故障注入，内核启用`fault-inject`特性时才可用
+//!
+//! 故障只作用于当前进程，被注入的系统调用不会执行，而是直接返回真实失败时的错误码。
+//! 失败原因见[`errno`](crate::errno::errno)，内核不支持时为`ENOSYS`。
+
+use crate::syscall::{sys_fault_inject, Status};
+
+const FAULT_OFF: usize = 0;
+const FAULT_ALLOC: usize = 1;
+const FAULT_IO: usize = 2;
+
+/// 此后第`n`次映射或扩大内存的系统调用以`ENOMEM`失败，`n`从1起
+pub fn fail_nth_alloc(n: usize) -> Option<()> {
+    sys_fault_inject(FAULT_ALLOC, n, 0).some()
+}
+
+/// 此后的`read`与`write`各以`percent`%的概率以`EIO`失败，
+/// 相同的`seed`得出相同的失败序列，`percent`为0时不再注入
+pub fn fail_io(percent: usize, seed: usize) -> Option<()> {
+    sys_fault_inject(FAULT_IO, percent, seed).some()
+}
+
+/// 撤销当前进程的所有故障
+pub fn clear() -> Option<()> {
+    sys_fault_inject(FAULT_OFF, 0, 0).some()
+}
@@ -1,14 +1,24 @@
 use alloc::ffi::CString;
 use alloc::string::String;
 use alloc::vec;
+use alloc::vec::Vec;
 use core::cmp::Ordering;
 use core::mem::MaybeUninit;
 
 use enumflags2::{bitflags, BitFlags};
-use vfs::{CDirEntry, Stat};
+use vfs::inotify::IN_NONBLOCK;
+use vfs::quota::{Q_GETQUOTA, Q_SETQUOTA, Q_SYNC};
+pub use vfs::{inotify, quota, CInotifyEvent, DirEntryType, DiskQuota, NAME_MAX, PATH_MAX};
+use vfs::{CDirEntry, CDirEntryPlus, DirEntry, Stat};
 
 use crate::io::{read, write};
 use crate::syscall::*;
+use crate::time::TimeSpec;
+
+/// [`utimensat`]的纳秒部分取此值时，设为当前时间
+pub const UTIME_NOW: usize = (1 << 30) - 1;
+/// [`utimensat`]的纳秒部分取此值时，保持不变
+pub const UTIME_OMIT: usize = (1 << 30) - 2;
 
 #[bitflags]
 #[repr(u32)]
@@ -57,10 +67,40 @@ pub fn pipe(pipe_fd: &mut [usize]) -> Option<()> {
     sys_pipe(pipe_fd).some()
 }
 
+const F_SETPIPE_SZ: usize = 1031;
+const F_GETPIPE_SZ: usize = 1032;
+
+/// 将管道`fd`的容量调整为至少`size`字节，返回实际的容量，它是页大小的整数倍。
+/// 失败原因见[`errno`](crate::errno::errno)
+pub fn set_pipe_size(fd: usize, size: usize) -> Option<usize> {
+    sys_fcntl(fd, F_SETPIPE_SZ, size).status()
+}
+
+/// 管道`fd`的容量
+pub fn pipe_size(fd: usize) -> Option<usize> {
+    sys_fcntl(fd, F_GETPIPE_SZ, 0).status()
+}
+
 pub fn dup(fd: usize) -> Option<usize> {
     sys_dup(fd).status()
 }
 
+/// 进程打开的文件描述符个数的资源限制
+const RLIMIT_NOFILE: usize = 7;
+
+/// 当前进程可打开的文件描述符个数上限
+pub fn file_limit() -> usize {
+    let mut limit = 0;
+    sys_getrlimit(RLIMIT_NOFILE, &mut limit);
+    limit
+}
+
+/// 设置当前进程可打开的文件描述符个数上限，不得超过全系统的上限。
+/// 调低时已打开的描述符不受影响。
+pub fn set_file_limit(limit: usize) -> Option<()> {
+    sys_setrlimit(RLIMIT_NOFILE, limit).some()
+}
+
 pub fn link(old_path: &str, new_path: &str) -> Option<()> {
     let old_path = CString::new(old_path).unwrap();
     let new_path = CString::new(new_path).unwrap();
@@ -72,6 +112,14 @@ pub fn unlink(path: &str) -> Option<()> {
     sys_unlink(&path).some()
 }
 
+/// 设置`path`的最后访问与最后修改时间，`times`依次为两者，为`None`时都设为当前时间。
+/// 纳秒部分可取[`UTIME_NOW`]或[`UTIME_OMIT`]，文件系统只记录到秒。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn utimensat(path: &str, times: Option<[TimeSpec; 2]>) -> Option<()> {
+    let path = CString::new(path).unwrap();
+    sys_utimensat(&path, times.as_ref()).some()
+}
+
 pub fn rmdir(path: &str) -> Option<()> {
     let path = CString::new(path).unwrap();
     sys_rmdir(&path).some()
@@ -105,6 +153,46 @@ pub fn mkdir(path: &str) -> Option<()> {
     sys_mkdir(&path).some()
 }
 
+/// 将块设备`source`上类型为`fstype`的文件系统挂载到目录`target`，失败原因见[`errno`](crate::errno::errno)
+pub fn mount(source: &str, target: &str, fstype: &str) -> Option<()> {
+    let source = CString::new(source).ok()?;
+    let target = CString::new(target).ok()?;
+    let fstype = CString::new(fstype).ok()?;
+    sys_mount(&source, &target, &fstype).some()
+}
+
+/// 卸载挂载在目录`target`上的文件系统，失败原因见[`errno`](crate::errno::errno)
+pub fn umount(target: &str) -> Option<()> {
+    let target = CString::new(target).ok()?;
+    sys_umount(&target, 0).some()
+}
+
+/// 查询`path`所在文件系统上用户`uid`的磁盘配额，失败原因见[`errno`](crate::errno::errno)
+pub fn quota_get(path: &str, uid: u32) -> Option<DiskQuota> {
+    let path = CString::new(path).ok()?;
+    let mut quota = DiskQuota::default();
+    sys_quotactl(Q_GETQUOTA, &path, uid, Some(&mut quota)).some()?;
+    Some(quota)
+}
+
+/// 设置`path`所在文件系统上用户`uid`的硬上限与软上限，以[`quota::QUOTA_BLOCK`]为单位，
+/// 0表示不限。仅限超级用户，失败原因见[`errno`](crate::errno::errno)
+pub fn quota_set(path: &str, uid: u32, hard_blocks: u64, soft_blocks: u64) -> Option<()> {
+    let path = CString::new(path).ok()?;
+    let mut quota = DiskQuota {
+        hard_blocks,
+        soft_blocks,
+        ..DiskQuota::default()
+    };
+    sys_quotactl(Q_SETQUOTA, &path, uid, Some(&mut quota)).some()
+}
+
+/// 将`path`所在文件系统的配额写回配额文件
+pub fn quota_sync(path: &str) -> Option<()> {
+    let path = CString::new(path).ok()?;
+    sys_quotactl(Q_SYNC, &path, 0, None).some()
+}
+
 pub fn fstat(fd: usize) -> Option<Stat> {
     let mut stat = MaybeUninit::zeroed();
     unsafe {
@@ -113,14 +201,150 @@ pub fn fstat(fd: usize) -> Option<Stat> {
     }
 }
 
+/// 将打开的文件截断或扩展至`length`字节，扩展出的部分读出为0。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn ftruncate(fd: usize, length: usize) -> Option<()> {
+    sys_ftruncate(fd, length as isize).some()
+}
+
+/// 移动偏移量的基准
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Whence {
+    /// 文件开头
+    Set = 0,
+    /// 当前偏移量
+    Cur = 1,
+    /// 文件末尾
+    End = 2,
+}
+
+/// 按`whence`移动打开的文件的偏移量，返回新的偏移量。
+/// 可越过文件末尾，此后写入时中间的空洞读出为0。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn lseek(fd: usize, offset: isize, whence: Whence) -> Option<usize> {
+    sys_lseek(fd, offset, whence as usize).status()
+}
+
+/// 此后访问打开的文件的方式，决定内核预读与缓存的策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// 适度预读
+    Normal = 0,
+    /// 随机访问，不预读
+    Random = 1,
+    /// 顺序访问，加倍预读
+    Sequential = 2,
+    /// 读写过的数据不会再用，不必留在缓存中，适合只追加的日志
+    DontNeed = 4,
+}
+
+/// 就此后如何访问打开的文件`fd`向内核提出建议，作用于整个文件，
+/// 由同一次打开得到的描述符共享。失败原因见[`errno`](crate::errno::errno)。
+pub fn fadvise(fd: usize, advice: Advice) -> Option<()> {
+    sys_fadvise(fd, advice as usize).some()
+}
+
 pub fn rename(old_path: &str, new_path: &str) -> Option<()> {
     let old_path = CString::new(old_path).ok()?;
     let new_path = CString::new(new_path).ok()?;
     sys_rename(&old_path, &new_path).some()
 }
 
-pub fn getdents(fd: usize, dents: &mut [CDirEntry]) -> Option<usize> {
-    sys_getdents(fd, dents).status()
+/// 将目录项记录读入`buf`，返回读取的字节数，0表示已读完；
+/// 记录可由[`CDirEntry::decode`]逐条解析，或直接使用[`ReadDir`]
+pub fn getdents(fd: usize, buf: &mut [u8]) -> Option<usize> {
+    sys_getdents(fd, buf).status()
+}
+
+/// 逐个产出已打开目录中的目录项
+pub struct ReadDir {
+    fd: usize,
+    buf: Vec<u8>,
+    /// `buf`中有效记录的范围
+    pos: usize,
+    len: usize,
+}
+
+impl ReadDir {
+    /// 缓冲区能容纳数条最长的记录
+    const BUF_SIZE: usize = 4 * CDirEntry::reclen(CDirEntry::NAME_MAX);
+
+    pub fn new(fd: usize) -> Self {
+        Self {
+            fd,
+            buf: vec![0; Self::BUF_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.len {
+            self.len = getdents(self.fd, &mut self.buf)?;
+            self.pos = 0;
+        }
+
+        let (entry, name) = CDirEntry::decode(&self.buf[self.pos..self.len])?;
+        self.pos += entry.reclen as usize;
+        Some(DirEntry {
+            inode: entry.inode,
+            ty: entry.ty,
+            name: String::from(name),
+        })
+    }
+}
+
+/// 同[`getdents`]，记录附带各项的状态，可由[`CDirEntryPlus::decode`]逐条解析，
+/// 或直接使用[`ReadDirPlus`]
+pub fn getdents_plus(fd: usize, buf: &mut [u8]) -> Option<usize> {
+    sys_getdents_plus(fd, buf).status()
+}
+
+/// 逐个产出已打开目录中的目录项及其状态，省去逐项[`fstat`]
+pub struct ReadDirPlus {
+    fd: usize,
+    buf: Vec<u8>,
+    /// `buf`中有效记录的范围
+    pos: usize,
+    len: usize,
+}
+
+impl ReadDirPlus {
+    /// 缓冲区能容纳数条最长的记录
+    const BUF_SIZE: usize = 4 * CDirEntryPlus::reclen(CDirEntry::NAME_MAX);
+
+    pub fn new(fd: usize) -> Self {
+        Self {
+            fd,
+            buf: vec![0; Self::BUF_SIZE],
+            pos: 0,
+            len: 0,
+        }
+    }
+}
+
+impl Iterator for ReadDirPlus {
+    type Item = (DirEntry, Stat);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos == self.len {
+            self.len = getdents_plus(self.fd, &mut self.buf)?;
+            self.pos = 0;
+        }
+
+        let (entry, name) = CDirEntryPlus::decode(&self.buf[self.pos..self.len])?;
+        self.pos += entry.reclen as usize;
+        let dirent = DirEntry {
+            inode: entry.inode,
+            ty: entry.stat.mode,
+            name: String::from(name),
+        };
+        Some((dirent, entry.stat))
+    }
 }
 
 pub fn eventfd(initval: u64, flags: BitFlags<EventFdFlag>) -> Option<usize> {
@@ -137,3 +361,37 @@ pub fn eventfd_write(fd: usize, num: u64) -> Option<()> {
     write(fd, &num.to_ne_bytes())?;
     Some(())
 }
+
+/// 新建文件系统事件的监视实例，`non_block`时无事件可读的读取立即失败而不阻塞
+pub fn inotify_init(non_block: bool) -> Option<usize> {
+    let flags = if non_block { IN_NONBLOCK } else { 0 };
+    sys_inotify_init1(flags).status()
+}
+
+/// 在监视实例`fd`上监视路径`path`的`mask`事件，事件位见[`inotify`]，
+/// 返回监视描述符。失败原因见[`errno`](crate::errno::errno)
+pub fn inotify_add_watch(fd: usize, path: &str, mask: u32) -> Option<i32> {
+    let path = CString::new(path).ok()?;
+    sys_inotify_add_watch(fd, &path, mask)
+        .status()
+        .map(|wd| wd as i32)
+}
+
+/// 撤销监视`wd`，失败原因见[`errno`](crate::errno::errno)
+pub fn inotify_rm_watch(fd: usize, wd: i32) -> Option<()> {
+    sys_inotify_rm_watch(fd, wd).some()
+}
+
+/// 读出监视实例`fd`中排队的事件及其名字，没有事件时阻塞，除非实例是非阻塞的
+pub fn inotify_read(fd: usize) -> Option<Vec<(CInotifyEvent, String)>> {
+    let mut buf = [0u8; 1024];
+    let len = read(fd, &mut buf)?;
+
+    let mut events = Vec::new();
+    let mut rest = &buf[..len];
+    while let Some((event, name)) = CInotifyEvent::decode(rest) {
+        events.push((event, String::from(name)));
+        rest = &rest[event.record_len()..];
+    }
+    Some(events)
+}
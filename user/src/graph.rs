@@ -4,9 +4,11 @@ use core::slice;
 use crate::syscall::{sys_framebuffer, sys_framebuffer_flush, sys_get_event, sys_key_pressed};
 use embedded_graphics::{
     draw_target::DrawTarget,
-    geometry::OriginDimensions,
+    geometry::{Dimensions, OriginDimensions},
     pixelcolor::{Rgb888, RgbColor},
-    prelude::Size,
+    prelude::{Point, Size},
+    primitives::Rectangle,
+    Pixel,
 };
 use virtio_input_decoder::{DecodeType, Decoder};
 
@@ -87,6 +89,75 @@ impl DrawTarget for Display {
     }
 }
 
+/// 整屏的画布。绘制只改动帧缓冲，调用[`present`](Canvas::present)时才刷新到屏幕，
+/// 一帧只刷新一次，适合逐帧绘制的动画
+pub struct Canvas {
+    framebuffer: &'static mut [u8],
+}
+
+impl Canvas {
+    pub fn new() -> Self {
+        Self {
+            framebuffer: framebuffer(),
+        }
+    }
+
+    /// 将这一帧刷新到屏幕
+    pub fn present(&mut self) {
+        flush_framebuffer();
+    }
+
+    fn put(&mut self, x: usize, y: usize, color: Rgb888) {
+        let i = (y * RESOLUTION_X as usize + x) * 4;
+        self.framebuffer[i] = color.b();
+        self.framebuffer[i + 1] = color.g();
+        self.framebuffer[i + 2] = color.r();
+    }
+}
+
+impl Default for Canvas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OriginDimensions for Canvas {
+    fn size(&self) -> Size {
+        Size::new(RESOLUTION_X, RESOLUTION_Y)
+    }
+}
+
+impl DrawTarget for Canvas {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    /// 超出屏幕的像素被裁去
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(Point { x, y }, color) in pixels {
+            if (0..RESOLUTION_X as i32).contains(&x) && (0..RESOLUTION_Y as i32).contains(&y) {
+                self.put(x as usize, y as usize, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn fill_solid(&mut self, area: &Rectangle, color: Self::Color) -> Result<(), Self::Error> {
+        let area = area.intersection(&self.bounding_box());
+        let Some(bottom_right) = area.bottom_right() else {
+            return Ok(());
+        };
+        for y in area.top_left.y..=bottom_right.y {
+            for x in area.top_left.x..=bottom_right.x {
+                self.put(x as usize, y as usize, color);
+            }
+        }
+        Ok(())
+    }
+}
+
 #[repr(C)]
 pub struct InputEvent {
     pub event_type: u16,
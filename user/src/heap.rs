@@ -0,0 +1,155 @@
+//! 用户堆
+//!
+//! 在伙伴分配器外包一层，统计分配次数与占用的字节数，供测试程序断言没有泄漏。
+//! 启用`heap-canary`特性时，每块分配的前后各放一个金丝雀值：
+//!
+//! ```text
+//! ┌─────────┬──────────────┬──────────┬────────┐
+//! │ padding │ size, canary │   data   │ canary │
+//! └─────────┴──────────────┴──────────┴────────┘
+//! ```
+//!
+//! 释放时检查二者，越界写入因此在释放时即被发现，而不是等到破坏了伙伴分配器的链表。
+
+use core::alloc::{GlobalAlloc, Layout};
+#[cfg(feature = "heap-canary")]
+use core::mem;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use buddy_system_allocator::LockedHeap;
+
+#[cfg(feature = "heap-canary")]
+const CANARY: usize = 0x5afe_c0de_5afe_c0de;
+/// 数据之前的头部：数据的字节数与金丝雀
+#[cfg(feature = "heap-canary")]
+const HEADER_SIZE: usize = 2 * mem::size_of::<usize>();
+
+/// 堆的统计，字节数均为请求的大小，不含金丝雀等额外开销
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    /// 累计分配的次数
+    pub allocs: usize,
+    /// 累计释放的次数
+    pub frees: usize,
+    /// 当前占用的字节数
+    pub in_use: usize,
+    /// 占用字节数的峰值
+    pub peak: usize,
+}
+
+impl HeapStats {
+    /// 尚未释放的块数
+    pub fn live(&self) -> usize {
+        self.allocs - self.frees
+    }
+}
+
+pub struct Heap {
+    heap: LockedHeap<32>,
+    allocs: AtomicUsize,
+    frees: AtomicUsize,
+    in_use: AtomicUsize,
+    peak: AtomicUsize,
+}
+
+impl Heap {
+    pub const fn empty() -> Self {
+        Self {
+            heap: LockedHeap::empty(),
+            allocs: AtomicUsize::new(0),
+            frees: AtomicUsize::new(0),
+            in_use: AtomicUsize::new(0),
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `[start, start + size)`须是可用且不作他用的内存，且只能初始化一次。
+    pub unsafe fn init(&self, start: usize, size: usize) {
+        self.heap.lock().init(start, size);
+    }
+
+    pub fn stats(&self) -> HeapStats {
+        HeapStats {
+            allocs: self.allocs.load(Ordering::Relaxed),
+            frees: self.frees.load(Ordering::Relaxed),
+            in_use: self.in_use.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+        }
+    }
+
+    fn record_alloc(&self, size: usize) {
+        self.allocs.fetch_add(1, Ordering::Relaxed);
+        let in_use = self.in_use.fetch_add(size, Ordering::Relaxed) + size;
+        self.peak.fetch_max(in_use, Ordering::Relaxed);
+    }
+
+    fn record_dealloc(&self, size: usize) {
+        self.frees.fetch_add(1, Ordering::Relaxed);
+        self.in_use.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(not(feature = "heap-canary"))]
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.heap.alloc(layout);
+        if !ptr.is_null() {
+            self.record_alloc(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.record_dealloc(layout.size());
+        self.heap.dealloc(ptr, layout);
+    }
+}
+
+#[cfg(feature = "heap-canary")]
+unsafe impl GlobalAlloc for Heap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (outer, offset) = guarded(layout);
+        let base = self.heap.alloc(outer);
+        if base.is_null() {
+            return base;
+        }
+
+        let data = base.add(offset);
+        let header = data.sub(HEADER_SIZE) as *mut usize;
+        header.write(layout.size());
+        header.add(1).write(CANARY);
+        (data.add(layout.size()) as *mut usize).write_unaligned(CANARY);
+
+        self.record_alloc(layout.size());
+        data
+    }
+
+    unsafe fn dealloc(&self, data: *mut u8, layout: Layout) {
+        let header = data.sub(HEADER_SIZE) as *mut usize;
+        if header.add(1).read() != CANARY || header.read() != layout.size() {
+            panic!("heap corruption: underrun before the block at {data:p}");
+        }
+        if (data.add(layout.size()) as *const usize).read_unaligned() != CANARY {
+            panic!(
+                "heap corruption: overrun past the {}-byte block at {data:p}",
+                layout.size()
+            );
+        }
+
+        self.record_dealloc(layout.size());
+        let (outer, offset) = guarded(layout);
+        self.heap.dealloc(data.sub(offset), outer);
+    }
+}
+
+/// 加上头部与尾部金丝雀的布局，及数据在其中的偏移
+#[cfg(feature = "heap-canary")]
+fn guarded(layout: Layout) -> (Layout, usize) {
+    let align = layout.align().max(mem::align_of::<usize>());
+    // 头部紧贴数据之前，数据须保持原有的对齐
+    let offset = HEADER_SIZE.next_multiple_of(align);
+    let size = offset + layout.size() + mem::size_of::<usize>();
+    (Layout::from_size_align(size, align).unwrap(), offset)
+}
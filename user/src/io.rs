@@ -7,3 +7,15 @@ pub fn read(fd: usize, buf: &mut [u8]) -> Option<usize> {
 pub fn write(fd: usize, buf: &[u8]) -> Option<usize> {
     sys_write(fd, buf).status()
 }
+
+/// 开关标准输出的行标记模式
+pub const TIOCSTAG: usize = 0x54f0;
+
+pub fn ioctl(fd: usize, request: usize, arg: usize) -> Option<usize> {
+    sys_ioctl(fd, request, arg).status()
+}
+
+/// 开启后各进程的输出按行缓冲，并冠以`[pid]`，多个进程同时输出时不再穿插
+pub fn set_stdout_tagging(enabled: bool) -> Option<()> {
+    ioctl(1, TIOCSTAG, enabled as usize).map(|_| ())
+}
@@ -6,23 +6,32 @@
 
 #[macro_use]
 pub mod console;
+pub mod device;
+pub mod errno;
+pub mod fault;
 pub mod fs;
 pub mod graph;
+mod heap;
 pub mod io;
 mod lang_items;
 pub mod mem;
+pub mod perf;
+pub mod power;
 pub mod process;
+pub mod profile;
+pub mod ptrace;
 pub mod signal;
 pub mod sync;
 mod syscall;
 pub mod thread;
 pub mod time;
+pub mod ucontext;
 
 extern crate alloc;
 
+use self::heap::Heap;
 use self::thread::exit;
 use alloc::vec::Vec;
-use buddy_system_allocator::LockedHeap;
 use core::slice;
 
 /// 分配的堆空间
@@ -31,14 +40,13 @@ const USER_HEAP_SIZE: usize = 2usize.pow(20);
 static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
 
 #[global_allocator]
-static HEAP: LockedHeap<32> = LockedHeap::empty();
+static HEAP: Heap = Heap::empty();
 
 #[no_mangle]
 #[link_section = ".text.entry"]
 pub extern "C" fn _start(argc: usize, argv: usize) -> ! {
     unsafe {
-        HEAP.lock()
-            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
+        HEAP.init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
     }
 
     let argv = argv as *const usize;
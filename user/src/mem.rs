@@ -1,6 +1,7 @@
 use core::ptr::NonNull;
 use core::slice;
 
+pub use crate::heap::HeapStats;
 use crate::syscall::*;
 
 use enumflags2::{bitflags, BitFlags};
@@ -14,31 +15,118 @@ pub enum ProtectFlag {
     X = 0b0000_0100,
 }
 
+/// 对内存区间用法的建议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Advice {
+    /// 即将访问，预先映射页帧
+    WillNeed = 3,
+    /// 不再需要其内容，释放页帧，此后读到全零
+    DontNeed = 4,
+}
+
+/// 就`area`的用法向内核提出建议，`area`须按页对齐。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn madvise(area: &mut [u8], advice: Advice) -> Option<()> {
+    sys_madvise(area.as_mut_ptr() as usize, area.len(), advice as usize).some()
+}
+
+/// 相同页合并的统计
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct KsmStats {
+    /// 完成的扫描次数
+    pub full_scans: usize,
+    /// 被多个页共享的页帧数
+    pub pages_shared: usize,
+    /// 改用共享页帧的页数，即省下的页帧数
+    pub pages_sharing: usize,
+}
+
+/// 启用或停用内核的相同页合并，仅限超级用户。可写的页合并后在写入时复制
+pub fn set_ksm(enabled: bool) -> Option<()> {
+    sys_ksm_ctl(enabled as usize).some()
+}
+
+pub fn ksm_stats() -> KsmStats {
+    let mut stats = KsmStats::default();
+    sys_ksm_stat(&mut stats);
+    stats
+}
+
+/// 用户堆的统计。启用`heap-canary`特性时，释放越界写过的块会panic
+pub fn stats() -> HeapStats {
+    crate::HEAP.stats()
+}
+
+/// 地址空间大小的资源限制
+const RLIMIT_AS: usize = 9;
+const RLIM_INFINITY: usize = usize::MAX;
+
+/// 当前进程可占用的内存上限，以字节计。`None`表示不设限
+pub fn memory_limit() -> Option<usize> {
+    let mut limit = RLIM_INFINITY;
+    sys_getrlimit(RLIMIT_AS, &mut limit);
+    (limit != RLIM_INFINITY).then_some(limit)
+}
+
+/// 设置当前进程可占用的内存上限，以字节计，按页向上取整。
+/// 超出上限的分配会失败。
+pub fn set_memory_limit(limit: Option<usize>) {
+    sys_setrlimit(RLIMIT_AS, limit.unwrap_or(RLIM_INFINITY));
+}
+
 pub fn sbrk(size: i32) -> Option<NonNull<u8>> {
     sys_sbrk(size)
         .status()
         .and_then(|old_brk| NonNull::new(old_brk as *mut u8))
 }
 
+/// 映射`len`字节的匿名内存，内容为全零，页在首次写入时才占用物理页帧。
+/// `start`非空时作为建议的起始地址，须按页对齐。
+/// 失败原因见[`errno`](crate::errno::errno)。
 pub fn mmap(
     start: *const u8,
     len: usize,
     prot: impl Into<BitFlags<ProtectFlag>>,
 ) -> Option<&'static mut [u8]> {
-    match sys_mmap(start as usize, len, prot.into().bits()) {
-        -1 => None,
-        mmap_start => unsafe {
-            Some(slice::from_raw_parts_mut(
-                mmap_start as usize as *mut u8,
-                len,
-            ))
-        },
-    }
+    let start = sys_mmap(start as usize, len, prot.into().bits()).status()?;
+    unsafe { Some(slice::from_raw_parts_mut(start as *mut u8, len)) }
+}
+
+/// 共享地映射打开的文件`fd`自开头起的`len`字节，页在首次访问时从文件读入。
+/// 修改经[`msync`]或撤销映射时写回文件，超出文件末尾的部分不会写回。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn mmap_file(
+    fd: usize,
+    len: usize,
+    prot: impl Into<BitFlags<ProtectFlag>>,
+) -> Option<&'static mut [u8]> {
+    let start = sys_mmap_file(fd, len, prot.into().bits()).status()?;
+    unsafe { Some(slice::from_raw_parts_mut(start as *mut u8, len)) }
+}
+
+/// 写回的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// 写回文件后即返回
+    Async = 0b001,
+    /// 写回文件并刷入设备
+    Sync = 0b100,
 }
 
+/// 将由[`mmap_file`]映射的`area`中被修改的页写回文件，`area`须按页对齐
+pub fn msync(area: &[u8], mode: SyncMode) -> Option<()> {
+    sys_msync(area.as_ptr() as usize, area.len(), mode as usize).some()
+}
+
+/// 撤销由[`mmap`]映射的整块内存，不能只撤销其中一部分
 pub fn munmap(area: &mut [u8]) -> Option<()> {
-    match sys_munmap(area.as_mut_ptr() as usize, area.len()) {
-        -1 => None,
-        _ => Some(()),
-    }
+    sys_munmap(area.as_mut_ptr() as usize, area.len()).some()
+}
+
+/// 将由[`mmap`]映射的整块内存伸缩至`new_len`字节，原有的内容保留。
+/// 其后的地址已被占用时整块移到别处，`area`随之失效，应改用返回值。
+pub fn mremap(area: &'static mut [u8], new_len: usize) -> Option<&'static mut [u8]> {
+    let start = sys_mremap(area.as_mut_ptr() as usize, area.len(), new_len).status()?;
+    unsafe { Some(slice::from_raw_parts_mut(start as *mut u8, new_len)) }
 }
@@ -0,0 +1,194 @@
+//! 采样剖析
+//!
+//! 内核每隔若干时钟中断记下被打断的PC，
+//! 用户PC由[`Symbols`]对照程序ELF的符号表归到函数上，内核PC由[`kernel_symbol`]向内核查询。
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::fs::{close, open, OpenFlag};
+use crate::io::read;
+use crate::syscall::{sys_ksym, sys_perf_ctl, sys_perf_read, Status};
+
+/// 一次采样
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sample {
+    pub pid: usize,
+    /// 被打断的指令地址，在系统调用中被打断时为内核地址
+    pub pc: usize,
+}
+
+/// 清空内核中的样本，每隔`period`个时钟中断采样一次进程`pid`，
+/// `pid`为`None`时采样所有进程
+pub fn start(period: usize, pid: Option<usize>) {
+    sys_perf_ctl(period, pid.unwrap_or(usize::MAX));
+}
+
+/// 停止采样，已有的样本仍可读取
+pub fn stop() {
+    sys_perf_ctl(0, usize::MAX);
+}
+
+/// 按时间先后取出样本填入`buf`，返回取出的样本数
+pub fn read_samples(buf: &mut [Sample]) -> usize {
+    sys_perf_read(buf) as usize
+}
+
+/// 包含内核地址`addr`的函数名，仅限超级用户。失败原因见[`errno`](crate::errno::errno)
+pub fn kernel_symbol(addr: usize) -> Option<String> {
+    let mut buf = vec![0; 128];
+    loop {
+        let len = sys_ksym(addr, &mut buf).status()?;
+        if len <= buf.len() {
+            buf.truncate(len);
+            return Some(String::from_utf8_lossy(&buf).into_owned());
+        }
+        buf.resize(len, 0);
+    }
+}
+
+/// ELF中的函数符号，按地址升序
+pub struct Symbols(Vec<Symbol>);
+
+struct Symbol {
+    addr: usize,
+    size: usize,
+    name: String,
+}
+
+const SHT_SYMTAB: u32 = 2;
+const STT_FUNC: u8 = 2;
+const SHDR_SIZE: usize = 64;
+const SYM_SIZE: usize = 24;
+
+impl Symbols {
+    /// 读取`path`处ELF64程序的符号表，程序被剥离了符号表时返回`None`
+    ///
+    /// 用户堆容不下带调试信息的整个程序，只读出所需的部分。
+    pub fn load(path: &str) -> Option<Self> {
+        let ehdr = read_range(path, 0, 64)?;
+        if ehdr[..4] != *b"\x7fELF" || ehdr[4] != 2 {
+            return None;
+        }
+        let shoff = le(&ehdr[0x28..0x30]);
+        let shnum = le(&ehdr[0x3c..0x3e]);
+
+        let shdrs = read_range(path, shoff, shnum * SHDR_SIZE)?;
+        let shdr = |i: usize| &shdrs[i * SHDR_SIZE..(i + 1) * SHDR_SIZE];
+        let symtab = (0..shnum)
+            .map(shdr)
+            .find(|sh| le(&sh[4..8]) as u32 == SHT_SYMTAB)?;
+        let strtab = shdr(le(&symtab[0x28..0x2c]));
+
+        let syms = read_range(path, le(&symtab[0x18..0x20]), le(&symtab[0x20..0x28]))?;
+        let strs = read_range(path, le(&strtab[0x18..0x20]), le(&strtab[0x20..0x28]))?;
+
+        let mut symbols: Vec<_> = syms
+            .chunks_exact(SYM_SIZE)
+            .filter(|sym| sym[4] & 0xf == STT_FUNC && le(&sym[8..16]) != 0)
+            .map(|sym| {
+                let name = &strs[le(&sym[0..4])..];
+                let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+                Symbol {
+                    addr: le(&sym[8..16]),
+                    size: le(&sym[16..24]),
+                    name: demangle(&String::from_utf8_lossy(&name[..len])),
+                }
+            })
+            .collect();
+        symbols.sort_unstable_by_key(|sym| sym.addr);
+        Some(Self(symbols))
+    }
+
+    /// 包含`pc`的函数名
+    pub fn lookup(&self, pc: usize) -> Option<&str> {
+        let i = self
+            .0
+            .partition_point(|sym| sym.addr <= pc)
+            .checked_sub(1)?;
+        let sym = &self.0[i];
+        (pc < sym.addr + sym.size.max(1)).then_some(sym.name.as_str())
+    }
+}
+
+/// 读出文件`path`中`[offset, offset + len)`的字节
+fn read_range(path: &str, offset: usize, len: usize) -> Option<Vec<u8>> {
+    let fd = open(path, OpenFlag::read_only())?;
+    let mut skip = vec![0; 512];
+    let mut pos = 0;
+    while pos < offset {
+        let n = (offset - pos).min(skip.len());
+        match read(fd, &mut skip[..n]) {
+            Some(0) | None => break,
+            Some(n) => pos += n,
+        }
+    }
+
+    let mut bytes = vec![0; len];
+    let mut filled = 0;
+    while pos == offset && filled < len {
+        match read(fd, &mut bytes[filled..]) {
+            Some(0) | None => break,
+            Some(n) => filled += n,
+        }
+    }
+    close(fd);
+    (filled == len).then_some(bytes)
+}
+
+/// 小端序的无符号整数
+fn le(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, &b| (acc << 8) | b as usize)
+}
+
+/// 还原旧式Rust修饰名`_ZN{len}{ident}...17h{hash}E`的路径，其余原样返回
+fn demangle(name: &str) -> String {
+    let Some(mut rest) = name.strip_prefix("_ZN") else {
+        return String::from(name);
+    };
+
+    let mut segments = Vec::new();
+    while let Some(digits) = rest.find(|c: char| !c.is_ascii_digit()).filter(|&n| n > 0) {
+        let Ok(len) = rest[..digits].parse::<usize>() else {
+            break;
+        };
+        let Some(segment) = rest.get(digits..digits + len) else {
+            break;
+        };
+        segments.push(segment);
+        rest = &rest[digits + len..];
+    }
+    if rest != "E" || segments.is_empty() {
+        return String::from(name);
+    }
+    // 去掉末尾的哈希
+    if segments
+        .last()
+        .is_some_and(|s| s.len() == 17 && s.starts_with('h'))
+    {
+        segments.pop();
+    }
+
+    segments
+        .iter()
+        .map(|s| {
+            s.replace("$LT$", "<")
+                .replace("$GT$", ">")
+                .replace("$u20$", " ")
+                .replace("$RF$", "&")
+                .replace("$C$", ",")
+                .replace("$BP$", "*")
+                .replace("$LP$", "(")
+                .replace("$RP$", ")")
+                .replace("$u7b$", "{")
+                .replace("$u7d$", "}")
+                .replace("..", "::")
+        })
+        .collect::<Vec<_>>()
+        .join("::")
+}
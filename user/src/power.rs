@@ -0,0 +1,35 @@
+use crate::syscall::{sys_reboot, sys_suspend, Status};
+
+/// 唤醒原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeSource {
+    Timer,
+    Serial,
+}
+
+/// 挂起至空闲，直到串口输入或`timeout_ms`毫秒后唤醒。
+/// `timeout_ms`为0表示只由串口输入唤醒。
+///
+/// 结果：
+/// None => 当前进程无权挂起系统
+pub fn suspend(timeout_ms: usize) -> Option<WakeSource> {
+    match sys_suspend(timeout_ms) {
+        0 => Some(WakeSource::Timer),
+        1 => Some(WakeSource::Serial),
+        _ => None,
+    }
+}
+
+const REBOOT_CMD_POWER_OFF: usize = 0x4321_FEDC;
+const REBOOT_CMD_RESTART: usize = 0x0123_4567;
+
+/// 结束其余进程、卸载文件系统后关机，成功时不返回。
+/// 失败原因见[`errno`](crate::errno::errno)，非超级用户调用时为[`EPERM`](crate::errno::EPERM)。
+pub fn poweroff() -> Option<()> {
+    sys_reboot(REBOOT_CMD_POWER_OFF).some()
+}
+
+/// 同[`poweroff`]，但最后重启
+pub fn reboot() -> Option<()> {
+    sys_reboot(REBOOT_CMD_RESTART).some()
+}
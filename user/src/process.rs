@@ -1,10 +1,16 @@
 use alloc::ffi::CString;
 use alloc::format;
+use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::ptr;
 
+use enumflags2::BitFlags;
+
+use crate::fs::OpenFlag;
+use crate::signal::{sigtimedwait, SignalFlag};
 use crate::syscall::*;
-use crate::thread::yield_;
+use crate::thread::{name_from_bytes, TASK_NAME_LEN};
 
 pub fn getpid() -> usize {
     sys_getpid() as usize
@@ -15,7 +21,9 @@ pub fn fork() -> usize {
 }
 
 /// 结果：
-/// None => 程序不存在
+/// None => 程序不存在，或参数过长(错误码为[`E2BIG`])
+///
+/// [`E2BIG`]: crate::errno::E2BIG
 pub fn exec<S, I>(path: &str, args: I) -> Option<!>
 where
     S: AsRef<str>,
@@ -35,24 +43,97 @@ where
         .unwrap();
     let mut args: Vec<_> = args.iter().map(|s| s.as_c_str().as_ptr()).collect();
     args.push(ptr::null());
-    match sys_exec(&path, &args) {
-        -1 => None,
-        _ => unreachable!(),
-    }
+    // 成功时不会返回
+    sys_exec(&path, &args).status().map(|_| unreachable!())
 }
 
 pub fn spawn(path: &str) -> Option<usize> {
     let path = CString::new(path).ok()?;
-    sys_spawn(&path).status()
+    sys_spawn(&path, &[]).status()
+}
+
+/// 以重定向后的文件描述符表创建子进程
+///
+/// 结果：
+/// None => 程序不存在 或 某一文件操作失败
+pub fn spawn_with(path: &str, actions: &SpawnFileActions) -> Option<usize> {
+    let path = CString::new(path).ok()?;
+    sys_spawn(&path, &actions.actions).status()
+}
+
+/// 子进程执行前，依次施加于其文件描述符表的操作。
+///
+/// 子进程继承父进程的文件描述符表，操作只影响子进程。
+#[derive(Debug, Default)]
+pub struct SpawnFileActions {
+    actions: Vec<FileAction>,
+    /// 保证[`FileAction`]中的路径指针在系统调用期间有效
+    paths: Vec<CString>,
+}
+
+/// 与内核约定的文件操作布局
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FileAction {
+    kind: usize,
+    fd: usize,
+    arg: usize,
+    flags: usize,
+}
+
+impl SpawnFileActions {
+    const OPEN: usize = 0;
+    const DUP2: usize = 1;
+    const CLOSE: usize = 2;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 以`flags`打开`path`，并置于描述符`fd`
+    pub fn add_open(&mut self, fd: usize, path: &str, flags: BitFlags<OpenFlag>) -> &mut Self {
+        let path = CString::new(path).unwrap();
+        self.actions.push(FileAction {
+            kind: Self::OPEN,
+            fd,
+            arg: path.as_ptr() as usize,
+            flags: flags.bits() as usize,
+        });
+        // CString的堆内存不随移动而改变
+        self.paths.push(path);
+        self
+    }
+
+    /// 将描述符`fd`复制到`new_fd`
+    pub fn add_dup2(&mut self, fd: usize, new_fd: usize) -> &mut Self {
+        self.actions.push(FileAction {
+            kind: Self::DUP2,
+            fd: new_fd,
+            arg: fd,
+            flags: 0,
+        });
+        self
+    }
+
+    /// 关闭描述符`fd`
+    pub fn add_close(&mut self, fd: usize) -> &mut Self {
+        self.actions.push(FileAction {
+            kind: Self::CLOSE,
+            fd,
+            arg: 0,
+            flags: 0,
+        });
+        self
+    }
 }
 
-/// 等待任意一个子进程结束
+/// 等待任意一个子进程结束，其间睡眠至收到`SIGCHLD`
 pub fn wait(exit_code: &mut i32) -> Option<usize> {
     loop {
         // -1 是约定参数
         match sys_waitpid(-1, exit_code) {
             -2 => {
-                yield_();
+                sigtimedwait(SignalFlag::SIGCHLD.into(), None);
             }
             -1 => return None,
             exit_pid => return Some(exit_pid as usize),
@@ -60,13 +141,26 @@ pub fn wait(exit_code: &mut i32) -> Option<usize> {
     }
 }
 
-/// 等待指定子进程结束
+/// 不阻塞地回收任意一个已结束的子进程
+///
+/// 结果：
+/// None => 没有子进程
+/// Some(None) => 子进程均未结束
+pub fn try_wait(exit_code: &mut i32) -> Option<Option<usize>> {
+    match sys_waitpid(-1, exit_code) {
+        -2 => Some(None),
+        -1 => None,
+        exit_pid => Some(Some(exit_pid as usize)),
+    }
+}
+
+/// 等待指定子进程结束，其间睡眠至收到`SIGCHLD`
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> Option<usize> {
     loop {
         // -1 是约定参数
         match sys_waitpid(pid as isize, exit_code) {
             -2 => {
-                yield_();
+                sigtimedwait(SignalFlag::SIGCHLD.into(), None);
             }
             // - 没有子进程
             // - 指定子进程存在但尚未结束
@@ -75,3 +169,79 @@ pub fn waitpid(pid: usize, exit_code: &mut i32) -> Option<usize> {
         }
     }
 }
+
+/// 创建调度组，同组进程每周期共享`share`%的CPU时间
+///
+/// 结果：
+/// None => `share`不在`1..=100`之内
+pub fn sched_group_create(share: usize) -> Option<usize> {
+    sys_sched_group_create(share).status()
+}
+
+/// 将进程划入调度组，`gid`为`None`时移出调度组
+pub fn sched_group_assign(pid: usize, gid: Option<usize>) -> Option<()> {
+    sys_sched_group_assign(pid, gid.unwrap_or(usize::MAX)).some()
+}
+
+/// [`SysInfo::loads`]中小数部分的位数
+pub const SI_LOAD_SHIFT: usize = 16;
+
+/// 内核启动的各阶段
+pub const BOOT_PHASES: [&str; 6] = ["cmdline", "paging", "drivers", "mount", "trap", "initproc"];
+
+/// 系统概况
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SysInfo {
+    /// 启动以来的秒数
+    pub uptime: usize,
+    /// 1、5、15分钟的平均负载，小数部分占[`SI_LOAD_SHIFT`]位
+    pub loads: [usize; 3],
+    /// 进程数
+    pub procs: usize,
+    /// 启动各阶段的耗时，以微秒计，依次为[`BOOT_PHASES`]
+    pub boot_us: [usize; BOOT_PHASES.len()],
+}
+
+impl SysInfo {
+    /// 平均负载四舍五入到两位小数，依次为整数部分与百分位
+    pub fn load_average(&self) -> [(usize, usize); 3] {
+        self.loads.map(|load| {
+            let hundredths = (load * 100 + (1 << (SI_LOAD_SHIFT - 1))) >> SI_LOAD_SHIFT;
+            (hundredths / 100, hundredths % 100)
+        })
+    }
+}
+
+pub fn sysinfo() -> SysInfo {
+    let mut info = SysInfo::default();
+    sys_sysinfo(&mut info);
+    info
+}
+
+/// 进程列表中的一项
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ProcessInfo {
+    pub pid: usize,
+    /// 父进程号，没有父进程时为0
+    pub ppid: usize,
+    /// 线程数
+    pub threads: usize,
+    name: [u8; TASK_NAME_LEN],
+}
+
+impl ProcessInfo {
+    pub fn name(&self) -> String {
+        name_from_bytes(&self.name)
+    }
+}
+
+/// 按进程号升序列出所有进程
+pub fn processes() -> Vec<ProcessInfo> {
+    // 列出期间可能有新进程产生，多留些余量
+    let mut buf = vec![ProcessInfo::default(); sysinfo().procs + 8];
+    let len = sys_process_list(&mut buf) as usize;
+    buf.truncate(len);
+    buf
+}
@@ -0,0 +1,59 @@
+//! 系统调用的延迟直方图，内核启用`syscall-profile`特性时才可用
+//!
+//! 延迟以`mtime`的计数为单位，第`i`个桶统计落在`[2^i, 2^(i+1))`内的次数。
+
+use alloc::vec::Vec;
+
+use crate::syscall::{sys_syscall_profile, Status};
+
+/// 直方图的桶数
+pub const BUCKETS: usize = usize::BITS as usize;
+
+/// 取快照后清空直方图
+const RESET: usize = 1;
+
+/// 单个系统调用的延迟直方图
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SyscallHistogram {
+    /// 系统调用号
+    pub id: usize,
+    /// 调用次数
+    pub count: usize,
+    /// 延迟之和
+    pub total: usize,
+    pub buckets: [usize; BUCKETS],
+}
+
+impl Default for SyscallHistogram {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            count: 0,
+            total: 0,
+            buckets: [0; BUCKETS],
+        }
+    }
+}
+
+impl SyscallHistogram {
+    /// 平均延迟
+    pub fn mean(&self) -> usize {
+        self.total.checked_div(self.count).unwrap_or(0)
+    }
+}
+
+/// 取得所有系统调用的直方图，按系统调用号升序；`reset`为真时随后清空
+pub fn snapshot(reset: bool) -> Option<Vec<SyscallHistogram>> {
+    let flags = if reset { RESET } else { 0 };
+    let mut histograms = Vec::new();
+    loop {
+        let total = sys_syscall_profile(&mut histograms, flags).status()?;
+        if total <= histograms.len() {
+            histograms.truncate(total);
+            return Some(histograms);
+        }
+        // 容量不足时内核不会清空，扩容后重取
+        histograms.resize(total, SyscallHistogram::default());
+    }
+}
@@ -0,0 +1,93 @@
+//! 进程追踪，足以写出简单的调试器
+//!
+//! 子进程在`fork`后调用[`trace_me`]，随后`exec`的程序停在入口处；
+//! 父进程以[`wait_stop`]等它停下，再读写其内存与寄存器，并以[`cont`]或[`single_step`]放行。
+//! 程序执行到`ebreak`时也会停下，此时`pc`指向该指令。
+
+use core::arch::asm;
+
+use crate::signal::{sigtimedwait, SignalFlag};
+use crate::syscall::{sys_ptrace, Status};
+
+const TRACEME: usize = 0;
+const PEEKDATA: usize = 2;
+const POKEDATA: usize = 5;
+const CONT: usize = 7;
+const SINGLESTEP: usize = 9;
+const GETREGS: usize = 12;
+const STOPPED: usize = 0x4200;
+
+/// 被追踪者停下时的寄存器
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UserRegs {
+    /// 通用寄存器，x0 ~ x31
+    pub x: [usize; 32],
+    pub pc: usize,
+}
+
+/// 与内核约定的字读写参数
+#[repr(C)]
+#[derive(Debug)]
+struct PtraceWord {
+    addr: usize,
+    data: usize,
+}
+
+/// 请父进程追踪当前进程
+///
+/// 结果：
+/// None => 当前进程已被追踪
+pub fn trace_me() -> Option<()> {
+    sys_ptrace(TRACEME, 0, 0).some()
+}
+
+/// 等待被追踪者停下
+///
+/// 结果：
+/// None => 被追踪者已结束，或不由当前进程追踪
+pub fn wait_stop(pid: usize) -> Option<()> {
+    loop {
+        match sys_ptrace(STOPPED, pid, 0).status()? {
+            0 => {
+                sigtimedwait(SignalFlag::SIGCHLD.into(), None);
+            }
+            _ => return Some(()),
+        }
+    }
+}
+
+/// 读取被追踪者`addr`处的一个字，`addr`须按字对齐
+pub fn peek(pid: usize, addr: usize) -> Option<usize> {
+    let mut word = PtraceWord { addr, data: 0 };
+    sys_ptrace(PEEKDATA, pid, &mut word as *mut PtraceWord as usize).some()?;
+    Some(word.data)
+}
+
+/// 改写被追踪者`addr`处的一个字，`addr`须按字对齐，代码段亦可改写
+pub fn poke(pid: usize, addr: usize, data: usize) -> Option<()> {
+    let mut word = PtraceWord { addr, data };
+    sys_ptrace(POKEDATA, pid, &mut word as *mut PtraceWord as usize).some()
+}
+
+pub fn get_regs(pid: usize) -> Option<UserRegs> {
+    let mut regs = UserRegs { x: [0; 32], pc: 0 };
+    sys_ptrace(GETREGS, pid, &mut regs as *mut UserRegs as usize).some()?;
+    Some(regs)
+}
+
+/// 放行停下的被追踪者
+pub fn cont(pid: usize) -> Option<()> {
+    sys_ptrace(CONT, pid, 0).some()
+}
+
+/// 放行停下的被追踪者，执行一条指令后再次停下
+pub fn single_step(pid: usize) -> Option<()> {
+    sys_ptrace(SINGLESTEP, pid, 0).some()
+}
+
+/// 执行`ebreak`：被追踪时停下交给追踪者，否则进程以SIGTRAP结束
+#[inline(always)]
+pub fn breakpoint() {
+    unsafe { asm!("ebreak") };
+}
@@ -14,12 +14,17 @@ pub const SIGBUS: u32 = 7;
 pub const SIGFPE: u32 = 8;
 pub const SIGKILL: u32 = 9;
 pub const SIGUSR1: u32 = 10;
+pub const SIGCHLD: u32 = 17;
+
+/// 被该信号打断的系统调用自动重新执行，而不是以[`EINTR`](crate::errno::EINTR)失败
+pub const SA_RESTART: u32 = 0x1000_0000;
 
 #[repr(C, align(16))]
 #[derive(Debug, Clone, Copy, Default)]
 pub struct SignalAction {
     pub handler: usize,
     pub mask: BitFlags<SignalFlag>,
+    pub flags: u32,
 }
 
 #[rustfmt::skip]
@@ -70,6 +75,18 @@ pub fn sigaction(signum: u32, action: &SignalAction, old_action: &mut SignalActi
     (sys_sigaction(signum, action, old_action) == 0).then_some(())
 }
 
+/// 等待`set`中的任一信号，取走其中编号最小的一个并返回其编号，不调用其处理例程。
+/// `timeout_ms`为`None`时一直等待。
+///
+/// 子进程结束时父进程收到[`SignalFlag::SIGCHLD`]，等到后再回收，不必轮询。
+/// 超时以[`EAGAIN`](crate::errno::EAGAIN)失败，其余失败原因见[`errno`](crate::errno::errno)。
+pub fn sigtimedwait(set: BitFlags<SignalFlag>, timeout_ms: Option<usize>) -> Option<u32> {
+    let timeout_ms = timeout_ms.map_or(-1, |ms| ms as isize);
+    sys_sigtimedwait(set.bits(), timeout_ms)
+        .status()
+        .map(|signum| signum as u32)
+}
+
 pub fn sigprocmask(mask: u32) -> Option<u32> {
     match sys_sigprocmask(mask) {
         -1 => None,
@@ -1,3 +1,5 @@
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
 use crate::syscall::*;
 
 pub fn spin_mutex() -> usize {
@@ -8,6 +10,8 @@ pub fn block_mutex() -> usize {
     sys_mutex_create(true) as usize
 }
 
+/// 前一个持有者未解锁便退出时，锁被毒化。此后上锁仍会成功，
+/// 但返回`None`且错误码为[`EOWNERDEAD`](crate::errno::EOWNERDEAD)
 pub fn mutex_lock(id: usize) -> Option<()> {
     sys_mutex_lock(id).some()
 }
@@ -17,7 +21,14 @@ pub fn mutex_unlock(id: usize) -> Option<()> {
 }
 
 pub fn semaphore_create(permits: usize) -> usize {
-    sys_semaphore_create(permits) as usize
+    sys_semaphore_create(permits, false) as usize
+}
+
+/// 健壮的信号量，适合当作锁使用：线程退出时归还它取走而未归还的资源，并毒化信号量。
+/// 此后`semaphore_down`仍会取得资源，但返回`None`且错误码为
+/// [`EOWNERDEAD`](crate::errno::EOWNERDEAD)
+pub fn robust_semaphore_create(permits: usize) -> usize {
+    sys_semaphore_create(permits, true) as usize
 }
 
 pub fn semaphore_up(id: usize) -> Option<()> {
@@ -39,3 +50,166 @@ pub fn condvar_signal(id: usize) -> Option<()> {
 pub fn condvar_wait(id: usize, mutex_id: usize) -> Option<()> {
     sys_condvar_wait(id, mutex_id).some()
 }
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// 若`word`仍为`expected`则睡眠，直到被唤醒
+fn futex_wait(word: &AtomicU32, expected: u32) {
+    sys_futex(word.as_ptr(), FUTEX_WAIT, expected as usize);
+}
+
+/// 唤醒至多`count`个在`word`上等待的线程
+fn futex_wake(word: &AtomicU32, count: usize) {
+    sys_futex(word.as_ptr(), FUTEX_WAKE, count);
+}
+
+/// 基于futex的互斥锁，无竞争时不陷入内核
+#[derive(Debug, Default)]
+pub struct Mutex {
+    /// 0 => 未上锁，1 => 上锁且无等待者，2 => 上锁且可能有等待者
+    state: AtomicU32,
+}
+
+impl Mutex {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(0),
+        }
+    }
+
+    pub fn lock(&self) {
+        if self
+            .state
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            return;
+        }
+
+        while self.state.swap(2, Ordering::Acquire) != 0 {
+            futex_wait(&self.state, 2);
+        }
+    }
+
+    pub fn unlock(&self) {
+        if self.state.swap(0, Ordering::Release) == 2 {
+            futex_wake(&self.state, 1);
+        }
+    }
+}
+
+/// 基于futex的条件变量，遵循Mesa语义，醒来后须重新检查条件
+#[derive(Debug, Default)]
+pub struct Condvar {
+    /// 每次通知都递增，等待者借此发觉错过的通知
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    pub const fn new() -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    /// 释放`mutex`并睡眠，被唤醒后重新获取`mutex`
+    pub fn wait(&self, mutex: &Mutex) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        mutex.unlock();
+        futex_wait(&self.seq, seq);
+        mutex.lock();
+    }
+
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, 1);
+    }
+
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq, usize::MAX);
+    }
+}
+
+/// 同步屏障
+///
+/// 只有全部线程都完成上一阶段之后，它们才能够进入下一阶段
+#[derive(Debug)]
+pub struct Barrier {
+    threads: usize,
+    arrived: AtomicUsize,
+    /// 每当全部线程到齐便递增，等待者以此判断能否离开
+    generation: AtomicU32,
+}
+
+impl Barrier {
+    pub const fn new(threads: usize) -> Self {
+        Self {
+            threads,
+            arrived: AtomicUsize::new(0),
+            generation: AtomicU32::new(0),
+        }
+    }
+
+    /// 等待全部线程到齐，最后到达的线程返回`true`
+    pub fn wait(&self) -> bool {
+        let generation = self.generation.load(Ordering::Acquire);
+
+        if self.arrived.fetch_add(1, Ordering::AcqRel) + 1 == self.threads {
+            self.arrived.store(0, Ordering::Relaxed);
+            self.generation.fetch_add(1, Ordering::Release);
+            futex_wake(&self.generation, usize::MAX);
+            return true;
+        }
+
+        while self.generation.load(Ordering::Acquire) == generation {
+            futex_wait(&self.generation, generation);
+        }
+        false
+    }
+}
+
+/// 一次性初始化
+#[derive(Debug, Default)]
+pub struct Once {
+    /// 0 => 未执行，1 => 执行中，2 => 已完成
+    state: AtomicU32,
+}
+
+impl Once {
+    const INCOMPLETE: u32 = 0;
+    const RUNNING: u32 = 1;
+    const COMPLETE: u32 = 2;
+
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(Self::INCOMPLETE),
+        }
+    }
+
+    /// 只有第一个调用者执行`f`，其余调用者等待其完成
+    pub fn call_once(&self, f: impl FnOnce()) {
+        match self.state.compare_exchange(
+            Self::INCOMPLETE,
+            Self::RUNNING,
+            Ordering::Acquire,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => {
+                f();
+                self.state.store(Self::COMPLETE, Ordering::Release);
+                futex_wake(&self.state, usize::MAX);
+            }
+            Err(_) => {
+                while self.state.load(Ordering::Acquire) == Self::RUNNING {
+                    futex_wait(&self.state, Self::RUNNING);
+                }
+            }
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == Self::COMPLETE
+    }
+}
@@ -1,21 +1,35 @@
 use core::arch::asm;
 use core::ffi::{c_char, CStr};
 
-use vfs::{CDirEntry, Stat};
-
+use vfs::quota::qcmd;
+use vfs::{DiskQuota, Stat};
+
+use crate::errno::set_errno;
+use crate::mem::KsmStats;
+use crate::perf::Sample;
+use crate::process::{FileAction, ProcessInfo, SysInfo};
+use crate::profile::SyscallHistogram;
 use crate::signal::SignalAction;
+use crate::thread::ThreadCounts;
+use crate::time::{TimeSpec, TimeVal};
 
 const READ: usize = 0;
 const WRITE: usize = 1;
 const OPEN: usize = 2;
 const CLOSE: usize = 3;
 const FSTAT: usize = 5;
+const LSEEK: usize = 8;
+const IOCTL: usize = 16;
 const PIPE: usize = 22;
+const MADVISE: usize = 28;
 const DUP: usize = 32;
+const NANOSLEEP: usize = 35;
 const GETPID: usize = 39;
 const FORK: usize = 57;
 const EXIT: usize = 60;
 const KILL: usize = 62;
+const FCNTL: usize = 72;
+const FTRUNCATE: usize = 77;
 const GETDENTS: usize = 78;
 const GETCWD: usize = 79;
 const CHDIR: usize = 80;
@@ -24,22 +38,45 @@ const MKDIR: usize = 83;
 const RMDIR: usize = 84;
 const LINK: usize = 86;
 const UNLINK: usize = 87;
+const GETTIMEOFDAY: usize = 96;
+const GETRLIMIT: usize = 97;
+const SYSINFO: usize = 99;
 const SLEEP: usize = 101;
 const YIELD: usize = 124;
+const SIGTIMEDWAIT: usize = 128;
 const SIGACTION: usize = 134;
 const SIGPROCMASK: usize = 135;
 const SIGRETURN: usize = 139;
+const REBOOT: usize = 142;
+const PRCTL: usize = 157;
+const SETRLIMIT: usize = 160;
+const MOUNT: usize = 165;
+const UMOUNT2: usize = 166;
 const GET_TIME: usize = 169;
+const QUOTACTL: usize = 179;
 const GETTID: usize = 186;
+const FUTEX: usize = 202;
+const SCHED_SETAFFINITY: usize = 203;
+const SCHED_GETAFFINITY: usize = 204;
 const SBRK: usize = 214;
 const MUNMAP: usize = 215;
+const MREMAP: usize = 216;
 const EXEC: usize = 221;
 const MMAP: usize = 222;
+const FADVISE: usize = 223;
+const MSYNC: usize = 227;
+const CLOCK_GETRES: usize = 229;
+const INOTIFY_ADD_WATCH: usize = 254;
+const INOTIFY_RM_WATCH: usize = 255;
 const WAITPID: usize = 260;
+const UTIMENSAT: usize = 280;
 const EVENTFD: usize = 290;
+const INOTIFY_INIT1: usize = 294;
 const SPAWN: usize = 400;
 const SPAWN_THREAD: usize = 1000;
 const WAITTID: usize = 1002;
+const THREAD_DETACH: usize = 1003;
+const THREAD_COUNTS: usize = 1004;
 const MUTEX_CREATE: usize = 1010;
 const MUTEX_LOCK: usize = 1011;
 const MUTEX_UNLOCK: usize = 1012;
@@ -53,19 +90,40 @@ const FRAMEBUFFER: usize = 2000;
 const FRAMEBUFFER_FLUSH: usize = 2001;
 const GET_EVENT: usize = 3000;
 const KEY_PRESSED: usize = 3001;
-
+const SUSPEND: usize = 4000;
+const SCHED_GROUP_CREATE: usize = 5000;
+const SCHED_GROUP_ASSIGN: usize = 5001;
+const KSM_CTL: usize = 6000;
+const KSM_STAT: usize = 6001;
+const MMAP_FILE: usize = 6002;
+const DEVICE_PRESENT: usize = 7000;
+const PTRACE: usize = 8000;
+const SYSCALL_PROFILE: usize = 9000;
+const PERF_CTL: usize = 9001;
+const PERF_READ: usize = 9002;
+const PROCESS_LIST: usize = 9003;
+const FAULT_INJECT: usize = 9004;
+const KSYM: usize = 9005;
+const GETDENTS_PLUS: usize = 9006;
+
+/// 系统调用的返回值，负值是错误码的相反数
 pub(crate) trait Status: Sized {
+    /// 失败时记下错误码并返回`None`，成功时返回非负的返回值
     fn status(self) -> Option<usize>;
+    /// 同[`Status::status`]，但不关心成功时的返回值
     fn some(self) -> Option<()>;
 }
 
 impl Status for isize {
     fn status(self) -> Option<usize> {
+        if self < 0 {
+            set_errno(-self);
+        }
         (self >= 0).then_some(self as usize)
     }
 
     fn some(self) -> Option<()> {
-        (self == 0).then_some(())
+        self.status().map(|_| ())
     }
 }
 
@@ -84,6 +142,9 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+/// 结果
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
 pub fn sys_open(path: &CStr, flags: u32) -> isize {
     syscall(OPEN, [path.as_ptr() as usize, flags as usize, 0])
 }
@@ -100,16 +161,19 @@ pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
 
-/// 将指定目录下的项填充进缓冲区`dents`
+/// 将目录下的项编码为变长的[`CDirEntry`](vfs::CDirEntry)记录填充进缓冲区`buf`
 ///
 /// 结果
-/// -1 => 读取的一定不是目录
-/// count => 读取到的文件项数目
-///
-/// UB
-/// 若读取的不是目录，则可能发生未定义行为
-pub fn sys_getdents(fd: usize, dents: &mut [CDirEntry]) -> isize {
-    syscall(GETDENTS, [fd, dents.as_mut_ptr() as usize, dents.len()])
+/// * 写入的字节数，0表示已读完
+/// * -ENOTDIR => `fd`不是目录
+/// * -EINVAL => 缓冲区连下一条记录都放不下
+pub fn sys_getdents(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(GETDENTS, [fd, buf.as_mut_ptr() as usize, buf.len()])
+}
+
+/// 同[`sys_getdents`]，记录为附带各项状态的[`CDirEntryPlus`](vfs::CDirEntryPlus)
+pub fn sys_getdents_plus(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(GETDENTS_PLUS, [fd, buf.as_mut_ptr() as usize, buf.len()])
 }
 
 pub fn sys_exit(exit_code: i32) -> ! {
@@ -121,6 +185,14 @@ pub fn sys_sleep(duration_ms: usize) -> isize {
     syscall(SLEEP, [duration_ms, 0, 0])
 }
 
+/// 结果
+/// * -EINVAL => 纳秒部分不小于10^9
+/// * -EINTR => 被信号提前唤醒，`rem`非空时写入未睡完的时间
+pub fn sys_nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> isize {
+    let rem = rem.map_or(0, |rem| rem as *mut TimeSpec as usize);
+    syscall(NANOSLEEP, [req as *const TimeSpec as usize, rem, 0])
+}
+
 pub fn sys_yield() -> isize {
     syscall(YIELD, [0, 0, 0])
 }
@@ -129,6 +201,16 @@ pub fn sys_get_time() -> isize {
     syscall(GET_TIME, [0, 0, 0])
 }
 
+pub fn sys_gettimeofday(tv: &mut TimeVal) -> isize {
+    syscall(GETTIMEOFDAY, [tv as *mut TimeVal as usize, 0, 0])
+}
+
+/// 结果
+/// * -EINVAL => 没有这个时钟
+pub fn sys_clock_getres(clock: usize, res: &mut TimeSpec) -> isize {
+    syscall(CLOCK_GETRES, [clock, res as *mut TimeSpec as usize, 0])
+}
+
 pub fn sys_sbrk(size: i32) -> isize {
     // 有符号数转无符号数，会直接写补码，
     // 因此再转回有符号数是无损的
@@ -139,10 +221,51 @@ pub fn sys_mmap(start: usize, len: usize, prot: u8) -> isize {
     syscall(MMAP, [start, len, prot as usize])
 }
 
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EACCES => 文件不可读，或要求可写而文件不可写
+pub fn sys_mmap_file(fd: usize, len: usize, prot: u8) -> isize {
+    syscall(MMAP_FILE, [fd, len, prot as usize])
+}
+
+pub fn sys_msync(start: usize, len: usize, flags: usize) -> isize {
+    syscall(MSYNC, [start, len, flags])
+}
+
+pub fn sys_madvise(start: usize, len: usize, advice: usize) -> isize {
+    syscall(MADVISE, [start, len, advice])
+}
+
 pub fn sys_munmap(start: usize, len: usize) -> isize {
     syscall(MUNMAP, [start, len, 0])
 }
 
+pub fn sys_mremap(start: usize, old_len: usize, new_len: usize) -> isize {
+    syscall(MREMAP, [start, old_len, new_len])
+}
+
+/// 读取资源限制至`limit`，目前只支持`RLIMIT_NOFILE`与`RLIMIT_AS`
+///
+/// 结果
+/// * -1 => 不支持的资源
+/// * 0 => 正常
+pub fn sys_getrlimit(resource: usize, limit: &mut usize) -> isize {
+    syscall(GETRLIMIT, [resource, limit as *mut usize as usize, 0])
+}
+
+/// 设置资源限制，目前只支持`RLIMIT_NOFILE`与`RLIMIT_AS`
+///
+/// 结果
+/// * -1 => 不支持的资源，或描述符个数的限制超出全系统的上限
+/// * 0 => 正常
+pub fn sys_setrlimit(resource: usize, limit: usize) -> isize {
+    syscall(SETRLIMIT, [resource, limit, 0])
+}
+
+pub fn sys_sysinfo(info: &mut SysInfo) -> isize {
+    syscall(SYSINFO, [info as *mut SysInfo as usize, 0, 0])
+}
+
 pub fn sys_getpid() -> isize {
     syscall(GETPID, [0, 0, 0])
 }
@@ -154,6 +277,8 @@ pub fn sys_fork() -> isize {
     syscall(FORK, [0, 0, 0])
 }
 
+/// 结果
+/// * -EFAULT => 参数数组或某个参数所在的页未映射或用户不可读
 pub fn sys_exec(path: &CStr, args: &[*const c_char]) -> isize {
     syscall(EXEC, [path.as_ptr() as usize, args.as_ptr() as usize, 0])
 }
@@ -170,12 +295,54 @@ pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
     syscall(WAITPID, [pid as usize, exit_code as usize, 0])
 }
 
+pub fn sys_utimensat(path: &CStr, times: Option<&[TimeSpec; 2]>) -> isize {
+    syscall(
+        UTIMENSAT,
+        [
+            path.as_ptr() as usize,
+            times.map_or(0, |times| times.as_ptr() as usize),
+            0,
+        ],
+    )
+}
+
 pub fn sys_eventfd(initval: u64, flags: u32) -> isize {
     syscall(EVENTFD, [initval as usize, flags as usize, 0])
 }
 
-pub fn sys_spawn(path: &CStr) -> isize {
-    syscall(SPAWN, [path.as_ptr() as usize, 0, 0])
+/// 结果
+/// * 监视实例的描述符
+/// * -EINVAL => `flags`含有[`IN_NONBLOCK`](vfs::inotify::IN_NONBLOCK)以外的位
+pub fn sys_inotify_init1(flags: usize) -> isize {
+    syscall(INOTIFY_INIT1, [flags, 0, 0])
+}
+
+/// 结果
+/// * 监视描述符
+/// * -EINVAL => `fd`不是监视实例，或`mask`不含可监视的事件
+/// * -ENOENT => `path`不存在
+pub fn sys_inotify_add_watch(fd: usize, path: &CStr, mask: u32) -> isize {
+    syscall(
+        INOTIFY_ADD_WATCH,
+        [fd, path.as_ptr() as usize, mask as usize],
+    )
+}
+
+/// 结果
+/// * -EINVAL => `fd`不是监视实例，或`wd`不是其上的监视
+pub fn sys_inotify_rm_watch(fd: usize, wd: i32) -> isize {
+    syscall(INOTIFY_RM_WATCH, [fd, wd as usize, 0])
+}
+
+pub fn sys_spawn(path: &CStr, actions: &[FileAction]) -> isize {
+    syscall(
+        SPAWN,
+        [
+            path.as_ptr() as usize,
+            actions.as_ptr() as usize,
+            actions.len(),
+        ],
+    )
 }
 
 pub fn sys_link(oldpath: &CStr, newpath: &CStr) -> isize {
@@ -201,6 +368,52 @@ pub fn sys_rmdir(path: &CStr) -> isize {
     syscall(RMDIR, [path.as_ptr() as usize, 0, 0])
 }
 
+/// 将块设备`source`(如`block1`)上类型为`fstype`的文件系统挂载到目录`target`
+///
+/// 结果
+/// * 0 => 成功
+/// * -ENOENT => 设备或挂载点不存在
+/// * -ENOTDIR => 挂载点不是目录
+/// * -ENODEV => 不支持该文件系统类型
+/// * -EINVAL => 设备上不是该类型的文件系统
+/// * -EBUSY => 挂载点上已有文件系统
+pub fn sys_mount(source: &CStr, target: &CStr, fstype: &CStr) -> isize {
+    syscall(
+        MOUNT,
+        [
+            source.as_ptr() as usize,
+            target.as_ptr() as usize,
+            fstype.as_ptr() as usize,
+        ],
+    )
+}
+
+/// 结果
+/// * 0 => 成功
+/// * -ENOENT => 路径不存在
+/// * -EINVAL => `target`不是挂载点，或`flags`不为0
+/// * -EBUSY => `target`是根目录，或其下还有别的挂载点
+pub fn sys_umount(target: &CStr, flags: usize) -> isize {
+    syscall(UMOUNT2, [target.as_ptr() as usize, flags, 0])
+}
+
+/// 查询或设置`path`所在文件系统上用户`id`的磁盘配额，命令见[`vfs::quota`]
+///
+/// 结果
+/// * 0 => 成功
+/// * -ENOENT => 路径不存在
+/// * -ESRCH => 所在文件系统不计配额
+/// * -EPERM => 无权查询或设置
+/// * -EFAULT => 查询或设置时`quota`为空
+/// * -EINVAL => 未知的命令
+pub fn sys_quotactl(cmd: usize, path: &CStr, id: u32, quota: Option<&mut DiskQuota>) -> isize {
+    let quota = quota.map_or(core::ptr::null_mut(), |quota| quota as *mut DiskQuota);
+    syscall(
+        QUOTACTL,
+        [qcmd(cmd, id), path.as_ptr() as usize, quota as usize],
+    )
+}
+
 /// 将当前进程所在目录的绝对路径写入缓冲区
 ///
 /// # 结果
@@ -212,6 +425,43 @@ pub fn sys_getcwd(buf: &mut [u8], len: usize) -> isize {
     syscall(GETCWD, [buf.as_mut_ptr() as usize, len, 0])
 }
 
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -ENOTTY => 文件不支持`request`
+pub fn sys_ioctl(fd: usize, request: usize, arg: usize) -> isize {
+    syscall(IOCTL, [fd, request, arg])
+}
+
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => 不支持`advice`
+/// * -ESPIPE => 文件是管道等不经块缓存的对象
+pub fn sys_fadvise(fd: usize, advice: usize) -> isize {
+    syscall(FADVISE, [fd, advice, 0])
+}
+
+/// 结果
+/// * 依`cmd`而定，设置与取得管道容量时为容量
+/// * -EBADF => `fd`未打开，或`cmd`要求管道而`fd`不是
+/// * -EINVAL => 不支持`cmd`
+/// * -EPERM => 管道容量超过上限
+/// * -EBUSY => 管道中已缓冲的数据多于新的容量
+/// * -ENOMEM => 没有足够的内存容纳管道
+pub fn sys_fcntl(fd: usize, cmd: usize, arg: usize) -> isize {
+    syscall(FCNTL, [fd, cmd, arg])
+}
+
+/// 结果
+/// * -EBADF => `fd`未打开
+/// * -EINVAL => `length`为负，`fd`不可写，或文件不支持截断
+pub fn sys_ftruncate(fd: usize, length: isize) -> isize {
+    syscall(FTRUNCATE, [fd, length as usize, 0])
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    syscall(LSEEK, [fd, offset as usize, whence])
+}
+
 pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
     syscall(FSTAT, [fd, st as usize, 0])
 }
@@ -232,6 +482,8 @@ pub fn sys_rename(oldpath: &CStr, newpath: &CStr) -> isize {
 /// # 结果
 ///
 /// * -1 => 出现错误，可能是`fd`无效
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
 /// * new_fd => 文件副本的描述符
 pub fn sys_dup(fd: usize) -> isize {
     syscall(DUP, [fd, 0, 0])
@@ -245,6 +497,8 @@ pub fn sys_dup(fd: usize) -> isize {
 ///
 /// 结果
 /// * -1 => 出现错误，可能是传入的地址不合法
+/// * -EMFILE => 进程打开的描述符已达上限
+/// * -ENFILE => 全系统打开的描述符已达上限
 /// * 0 => 正常
 pub fn sys_pipe(pipe: &mut [usize]) -> isize {
     syscall(PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
@@ -263,6 +517,11 @@ pub fn sys_kill(pid: usize, signal: u32) -> isize {
     syscall(KILL, [pid, signal as usize, 0])
 }
 
+/// 等待`mask`中的任一信号，取走并返回其编号，`timeout_ms`为负时一直等待
+pub fn sys_sigtimedwait(mask: u32, timeout_ms: isize) -> isize {
+    syscall(SIGTIMEDWAIT, [mask as usize, timeout_ms as usize, 0])
+}
+
 /// 结果
 /// -1 => `action`,`old_action`为空指针；信号类型不存在返回
 /// 0 => 正常
@@ -307,15 +566,64 @@ pub fn sys_gettid() -> isize {
 /// 结果
 /// * exit_code => 结束任务的退出码
 /// * -2 => 任务存在，但尚未退出
-/// * -1 => 发生错误，例如任务不存在
+/// * -1 => 发生错误，例如任务不存在、已分离或为调用者自身
 pub fn sys_waittid(tid: usize) -> isize {
     syscall(WAITTID, [tid, 0, 0])
 }
 
+/// 结果
+/// * -ESRCH => 线程不存在
+/// * -EINVAL => 线程已分离或为主线程
+pub fn sys_thread_detach(tid: usize) -> isize {
+    syscall(THREAD_DETACH, [tid, 0, 0])
+}
+
+pub fn sys_thread_counts(counts: &mut ThreadCounts) -> isize {
+    syscall(THREAD_COUNTS, [counts as *mut ThreadCounts as usize, 0, 0])
+}
+
+/// 参数
+/// * op: 0 => 等待，1 => 唤醒
+///
+/// 结果
+/// * 等待 => 0 表示被唤醒，-1 表示`uaddr`处的值已不是`val`
+/// * 唤醒 => 唤醒的线程数，至多为`val`
+/// * -EFAULT => `uaddr`未对齐，或所在的页未映射、用户不可读
+pub fn sys_futex(uaddr: *const u32, op: usize, val: usize) -> isize {
+    syscall(FUTEX, [uaddr as usize, op, val])
+}
+
+/// 将当前进程中线程`tid`的亲和性设为`mask`，第`i`位允许其在编号为`i`的处理器核上运行
+///
+/// 结果
+/// * -ESRCH => 线程不存在
+/// * -EINVAL => `mask`不含任何存在的处理器核
+pub fn sys_sched_setaffinity(tid: usize, mask: usize) -> isize {
+    syscall(SCHED_SETAFFINITY, [tid, mask, 0])
+}
+
+/// 结果
+/// * -ESRCH => 线程不存在
+/// * mask => 线程`tid`的亲和性掩码
+pub fn sys_sched_getaffinity(tid: usize) -> isize {
+    syscall(SCHED_GETAFFINITY, [tid, 0, 0])
+}
+
+/// 参数
+/// * option: 15 => 以`arg`处的字符串为当前线程的名称，16 => 将名称写入`arg`处的16字节
+///
+/// 结果
+/// * -EINVAL => 不支持的`option`
+pub fn sys_prctl(option: usize, arg: usize) -> isize {
+    syscall(PRCTL, [option, arg, 0])
+}
+
 pub fn sys_mutex_create(block: bool) -> isize {
     syscall(MUTEX_CREATE, [block as usize, 0, 0])
 }
 
+/// 结果
+/// * -EOWNERDEAD => 前一个持有者未解锁便退出了，锁已转交给调用者
 pub fn sys_mutex_lock(id: usize) -> isize {
     syscall(MUTEX_LOCK, [id, 0, 0])
 }
@@ -324,14 +632,17 @@ pub fn sys_mutex_unlock(id: usize) -> isize {
     syscall(MUTEX_UNLOCK, [id, 0, 0])
 }
 
-pub fn sys_semaphore_create(permits: usize) -> isize {
-    syscall(SEMAPHORE_CREATE, [permits, 0, 0])
+/// `robust`为真时，线程退出时归还其取走而未归还的资源，并毒化信号量
+pub fn sys_semaphore_create(permits: usize, robust: bool) -> isize {
+    syscall(SEMAPHORE_CREATE, [permits, robust as usize, 0])
 }
 
 pub fn sys_semaphore_up(id: usize) -> isize {
     syscall(SEMAPHORE_UP, [id, 0, 0])
 }
 
+/// 结果
+/// * -EOWNERDEAD => 健壮的信号量的某个持有者未归还资源便退出了，调用者仍取得了资源
 pub fn sys_semaphore_down(id: usize) -> isize {
     syscall(SEMAPHORE_DOWN, [id, 0, 0])
 }
@@ -344,6 +655,8 @@ pub fn sys_condvar_signal(id: usize) -> isize {
     syscall(CONDVAR_SIGNAL, [id, 0, 0])
 }
 
+/// 结果
+/// * -EOWNERDEAD => 睡眠期间锁的持有者未解锁便退出了，醒来后锁已转交给调用者
 pub fn sys_condvar_wait(id: usize, mutex_id: usize) -> isize {
     syscall(CONDVAR_WAIT, [id, mutex_id, 0])
 }
@@ -363,3 +676,105 @@ pub fn sys_get_event() -> isize {
 pub fn sys_key_pressed() -> isize {
     syscall(KEY_PRESSED, [0, 0, 0])
 }
+
+/// 挂起至空闲，直到串口输入或`timeout_ms`毫秒后唤醒。
+/// `timeout_ms`为0表示只由串口输入唤醒。
+///
+/// 结果
+/// * -1 => 当前进程无权挂起系统
+/// * 0 => 因定时到期而唤醒
+/// * 1 => 因串口输入而唤醒
+pub fn sys_reboot(cmd: usize) -> isize {
+    syscall(REBOOT, [cmd, 0, 0])
+}
+
+pub fn sys_suspend(timeout_ms: usize) -> isize {
+    syscall(SUSPEND, [timeout_ms, 0, 0])
+}
+
+/// 创建调度组，同组进程每周期共享`share`%的CPU时间，超额后暂停调度至下一周期
+///
+/// 结果
+/// * -1 => `share`不在`1..=100`之内
+/// * gid => 调度组ID
+pub fn sys_sched_group_create(share: usize) -> isize {
+    syscall(SCHED_GROUP_CREATE, [share, 0, 0])
+}
+
+/// 将进程`pid`划入调度组`gid`，`gid`为`usize::MAX`时移出调度组
+///
+/// 结果
+/// * -1 => 进程或调度组不存在
+/// * -EPERM => 调用者既不是超级用户，也不是目标进程的属主
+/// * 0 => 正常
+pub fn sys_sched_group_assign(pid: usize, gid: usize) -> isize {
+    syscall(SCHED_GROUP_ASSIGN, [pid, gid, 0])
+}
+
+/// 启用（非0）或停用（0）相同页合并
+///
+/// 结果
+/// * -EPERM => 调用者不是超级用户
+pub fn sys_ksm_ctl(enable: usize) -> isize {
+    syscall(KSM_CTL, [enable, 0, 0])
+}
+
+pub fn sys_ksm_stat(stats: &mut KsmStats) -> isize {
+    syscall(KSM_STAT, [stats as *mut KsmStats as usize, 0, 0])
+}
+
+/// 结果
+/// * 1 => 设备存在
+/// * 0 => 设备不存在
+pub fn sys_device_present(name: &CStr) -> isize {
+    syscall(DEVICE_PRESENT, [name.as_ptr() as usize, 0, 0])
+}
+
+/// 结果
+/// * -ESRCH => `pid`不存在、已结束、不由当前进程追踪，或尚未停下
+/// * -EFAULT => `arg`或被追踪者的地址非法
+/// * -EINVAL => 未知的请求
+pub fn sys_ptrace(request: usize, pid: usize, arg: usize) -> isize {
+    syscall(PTRACE, [request, pid, arg])
+}
+
+/// 结果
+/// * -ENOSYS => 内核未启用`syscall-profile`特性
+/// * 其它 => 采集到的直方图总数
+pub fn sys_syscall_profile(buf: &mut [SyscallHistogram], flags: usize) -> isize {
+    syscall(
+        SYSCALL_PROFILE,
+        [buf.as_mut_ptr() as usize, buf.len(), flags],
+    )
+}
+
+/// `period`为0时停止采样，否则清空样本并每隔`period`个时钟中断采样一次，
+/// `pid`为`usize::MAX`时采样所有进程
+pub fn sys_perf_ctl(period: usize, pid: usize) -> isize {
+    syscall(PERF_CTL, [period, pid, 0])
+}
+
+/// 结果：取出的样本数
+pub fn sys_perf_read(buf: &mut [Sample]) -> isize {
+    syscall(PERF_READ, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+/// 结果：写入的项数
+pub fn sys_process_list(buf: &mut [ProcessInfo]) -> isize {
+    syscall(PROCESS_LIST, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+/// 结果
+/// * -ENOSYS => 内核未启用`fault-inject`特性
+/// * -EINVAL => 未知的故障，或`n`超出范围
+pub fn sys_fault_inject(kind: usize, n: usize, seed: usize) -> isize {
+    syscall(FAULT_INJECT, [kind, n, seed])
+}
+
+/// 结果
+/// * 名称的完整字节数，超出`buf`的部分被截断
+/// * -EPERM => 调用者不是超级用户
+/// * -ENOENT => `addr`不在内核代码段中，或内核未填入符号表
+pub fn sys_ksym(addr: usize, buf: &mut [u8]) -> isize {
+    syscall(KSYM, [addr, buf.as_mut_ptr() as usize, buf.len()])
+}
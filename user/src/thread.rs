@@ -1,4 +1,29 @@
+use alloc::ffi::CString;
+use alloc::string::String;
+use core::arch::asm;
+
 use crate::syscall::*;
+use crate::time::TimeSpec;
+
+/// 线程局部存储的槽位数，TID须小于此值
+pub const MAX_THREADS: usize = 64;
+
+/// 以TID为索引的线程局部存储，每个线程只访问自己的槽位
+pub struct ThreadLocal<T>([T; MAX_THREADS]);
+
+// 槽位不在线程间共享，TID被回收后由新线程沿用
+unsafe impl<T: Send> Sync for ThreadLocal<T> {}
+
+impl<T> ThreadLocal<T> {
+    pub const fn new(slots: [T; MAX_THREADS]) -> Self {
+        Self(slots)
+    }
+
+    /// 当前线程的槽位，TID不小于[`MAX_THREADS`]的线程没有槽位，返回`None`
+    pub fn get(&self) -> Option<&T> {
+        self.0.get(current_tid())
+    }
+}
 
 pub fn yield_() -> isize {
     sys_yield()
@@ -13,6 +38,12 @@ pub fn sleep(duration_ms: usize) {
     sys_sleep(duration_ms);
 }
 
+/// 睡眠`req`长的时间，精度为毫秒。被信号提前唤醒时不会自动接着睡，
+/// `rem`非空时写入未睡完的时间，可凭之接着睡。失败原因见[`errno`](crate::errno::errno)
+pub fn nanosleep(req: &TimeSpec, rem: Option<&mut TimeSpec>) -> Option<()> {
+    sys_nanosleep(req, rem).some()
+}
+
 pub fn spawn(entry: usize, arg: usize) -> usize {
     sys_spawn_thread(entry, arg) as usize
 }
@@ -21,6 +52,53 @@ pub fn gettid() -> usize {
     sys_gettid() as usize
 }
 
+/// 只允许当前进程中的线程`tid`在掩码`mask`所含的处理器核上运行，第`i`位对应编号为`i`的核。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn set_affinity(tid: usize, mask: usize) -> Option<()> {
+    sys_sched_setaffinity(tid, mask).some()
+}
+
+/// 当前进程中线程`tid`的亲和性掩码
+pub fn affinity(tid: usize) -> Option<usize> {
+    sys_sched_getaffinity(tid).status()
+}
+
+/// 线程名的最大字节数，含末尾的NUL
+pub const TASK_NAME_LEN: usize = 16;
+
+const PR_SET_NAME: usize = 15;
+const PR_GET_NAME: usize = 16;
+
+/// 为当前线程命名，超出[`TASK_NAME_LEN`]` - 1`字节的部分被截断。主线程的名称即进程名
+pub fn set_name(name: &str) {
+    let name = CString::new(name).unwrap();
+    sys_prctl(PR_SET_NAME, name.as_ptr() as usize);
+}
+
+/// 当前线程的名称
+pub fn name() -> String {
+    let mut buf = [0u8; TASK_NAME_LEN];
+    sys_prctl(PR_GET_NAME, buf.as_mut_ptr() as usize);
+    name_from_bytes(&buf)
+}
+
+/// 截取以NUL结尾的名称
+pub(crate) fn name_from_bytes(bytes: &[u8]) -> String {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..len]).into_owned()
+}
+
+/// 从`tp`读取当前线程的TID，内核创建线程时将其写入，不必陷入内核
+#[inline]
+pub fn current_tid() -> usize {
+    let tid;
+    unsafe {
+        asm!("mv {}, tp", out(reg) tid);
+    }
+    tid
+}
+
+/// 等待线程`tid`退出并回收之，其TID此后可被新线程沿用
 pub fn waittid(tid: usize) -> Option<i32> {
     loop {
         match sys_waittid(tid) {
@@ -32,3 +110,25 @@ pub fn waittid(tid: usize) -> Option<i32> {
         }
     }
 }
+
+/// 分离线程`tid`，它退出时即被回收，不可再等待。
+/// 失败原因见[`errno`](crate::errno::errno)。
+pub fn detach(tid: usize) -> Option<()> {
+    sys_thread_detach(tid).some()
+}
+
+/// 进程的线程数，与内核的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ThreadCounts {
+    /// 仍在运行的线程
+    pub running: usize,
+    /// 已退出而未被等待回收的线程
+    pub exited: usize,
+}
+
+pub fn thread_counts() -> ThreadCounts {
+    let mut counts = ThreadCounts::default();
+    sys_thread_counts(&mut counts);
+    counts
+}
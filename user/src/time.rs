@@ -1,5 +1,178 @@
-use crate::syscall::sys_get_time;
+use core::fmt;
+use core::hint;
+use core::sync::atomic::{self, AtomicUsize, Ordering};
 
+use crate::syscall::*;
+
+/// 内核只读地映射进每个进程的时钟共享页，位于用户地址空间的最后一页
+const VDSO_VA: usize = (1 << 38) - 0x1000;
+
+/// 时钟共享页的内容，与内核的同名结构体布局一致
+#[repr(C)]
+struct VdsoData {
+    /// 顺序锁，为奇数时内核正在更新
+    seq: AtomicUsize,
+    /// 最近一次时钟中断时`mtime`的值
+    mtime: AtomicUsize,
+    /// 自启动以来经过的时钟中断间隔数
+    ticks: AtomicUsize,
+    /// `mtime`每秒的计数
+    clock_freq: usize,
+    /// 每秒的时钟中断次数
+    ticks_per_sec: usize,
+}
+
+fn vdso() -> &'static VdsoData {
+    unsafe { &*(VDSO_VA as *const VdsoData) }
+}
+
+/// 读出内核更新时一致的`(mtime, ticks)`
+fn snapshot() -> (usize, usize) {
+    let data = vdso();
+    loop {
+        let seq = data.seq.load(Ordering::Acquire);
+        if seq % 2 != 0 {
+            hint::spin_loop();
+            continue;
+        }
+        let mtime = data.mtime.load(Ordering::Relaxed);
+        let ticks = data.ticks.load(Ordering::Relaxed);
+        atomic::fence(Ordering::Acquire);
+        if data.seq.load(Ordering::Relaxed) == seq {
+            return (mtime, ticks);
+        }
+    }
+}
+
+/// 开机以来的毫秒数，需要陷入内核
 pub fn get_time() -> isize {
     sys_get_time()
 }
+
+/// 开机以来的毫秒数，读自时钟共享页而无需系统调用，
+/// 精度为一个时钟中断间隔，适合在基准测试中频繁计时
+pub fn now() -> isize {
+    let (mtime, _) = snapshot();
+    (mtime / (vdso().clock_freq / 1000)) as isize
+}
+
+/// 开机以来经过的时钟中断间隔数，每秒[`ticks_per_sec`]个
+pub fn ticks() -> usize {
+    snapshot().1
+}
+
+/// 每秒的时钟中断次数
+pub fn ticks_per_sec() -> usize {
+    vdso().ticks_per_sec
+}
+
+/// 墙上时间，自UNIX纪元起算
+pub const CLOCK_REALTIME: usize = 0;
+/// 单调时间，自启动起算
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// 秒与微秒，与内核的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeVal {
+    pub sec: usize,
+    pub usec: usize,
+}
+
+/// 秒与纳秒，与内核的同名结构体布局一致
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeSpec {
+    pub sec: usize,
+    pub nsec: usize,
+}
+
+/// 自UNIX纪元以来的时间
+pub fn gettimeofday() -> TimeVal {
+    let mut tv = TimeVal::default();
+    sys_gettimeofday(&mut tv);
+    tv
+}
+
+/// 时钟`clock`的分辨率。失败原因见[`errno`](crate::errno::errno)
+pub fn clock_getres(clock: usize) -> Option<TimeSpec> {
+    let mut res = TimeSpec::default();
+    sys_clock_getres(clock, &mut res).some()?;
+    Some(res)
+}
+
+/// UTC的日期与时间，按ISO-8601格式化，如`2024-02-29T13:05:09.042000Z`
+///
+/// 格式化时可用精度指定秒的小数位数，至多6位：`{:.3}`精确到毫秒，`{:.0}`不带小数。
+/// 全程只用整数运算，不依赖浮点。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: usize,
+    /// 1～12
+    pub month: usize,
+    /// 1～31
+    pub day: usize,
+    pub hour: usize,
+    pub minute: usize,
+    pub second: usize,
+    pub usec: usize,
+}
+
+impl DateTime {
+    /// 当前时间
+    pub fn now() -> Self {
+        Self::from(gettimeofday())
+    }
+}
+
+impl From<TimeVal> for DateTime {
+    fn from(tv: TimeVal) -> Self {
+        let (year, month, day) = civil_from_days(tv.sec / 86400);
+        let secs = tv.sec % 86400;
+        Self {
+            year,
+            month,
+            day,
+            hour: secs / 3600,
+            minute: secs % 3600 / 60,
+            second: secs % 60,
+            usec: tv.usec,
+        }
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )?;
+        let digits = f.precision().unwrap_or(6).min(6);
+        if digits > 0 {
+            let frac = self.usec / 10usize.pow(6 - digits as u32);
+            write!(f, ".{frac:0digits$}")?;
+        }
+        f.write_str("Z")
+    }
+}
+
+/// 自1970-01-01起的天数对应的公历日期`(年, 月, 日)`
+///
+/// 以3月1日为一年之始，闰日便落在年末；再以400年为一个周期，
+/// 每个周期恰有146097天。算法出自Howard Hinnant的`civil_from_days`。
+fn civil_from_days(days: usize) -> (usize, usize, usize) {
+    // 平移到0000-03-01起算
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // 自3月起算的月份，0～11
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = era * 400 + year_of_era + usize::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,188 @@
+//! 用户态上下文切换
+//!
+//! 只保存被调用者保存寄存器(`ra`、`sp`、`s0`~`s11`)，
+//! 足以在函数调用边界切换执行流，用以实现协程与绿色线程。
+//! 浮点寄存器不在保存之列。
+
+use core::arch::global_asm;
+use core::ptr;
+
+use crate::thread::exit;
+
+/// 用户态上下文
+#[repr(C)]
+#[derive(Debug, Default, Clone)]
+pub struct UContext {
+    ra: usize,
+    sp: usize,
+    s: [usize; 12],
+    /// 恢复上下文时，保存点(`getcontext`)的返回值
+    a0: usize,
+}
+
+global_asm!(
+    "
+    .section .text
+    .globl getcontext
+    .globl setjmp
+getcontext:
+setjmp:
+    sd ra, 0(a0)
+    sd sp, 8(a0)
+    sd s0, 16(a0)
+    sd s1, 24(a0)
+    sd s2, 32(a0)
+    sd s3, 40(a0)
+    sd s4, 48(a0)
+    sd s5, 56(a0)
+    sd s6, 64(a0)
+    sd s7, 72(a0)
+    sd s8, 80(a0)
+    sd s9, 88(a0)
+    sd s10, 96(a0)
+    sd s11, 104(a0)
+    li t0, 1
+    sd t0, 112(a0)
+    li a0, 0
+    ret
+
+    .globl __setcontext
+__setcontext:
+    ld ra, 0(a0)
+    ld sp, 8(a0)
+    ld s0, 16(a0)
+    ld s1, 24(a0)
+    ld s2, 32(a0)
+    ld s3, 40(a0)
+    ld s4, 48(a0)
+    ld s5, 56(a0)
+    ld s6, 64(a0)
+    ld s7, 72(a0)
+    ld s8, 80(a0)
+    ld s9, 88(a0)
+    ld s10, 96(a0)
+    ld s11, 104(a0)
+    ld a0, 112(a0)
+    ret
+
+    .globl __swapcontext
+__swapcontext:
+    sd ra, 0(a0)
+    sd sp, 8(a0)
+    sd s0, 16(a0)
+    sd s1, 24(a0)
+    sd s2, 32(a0)
+    sd s3, 40(a0)
+    sd s4, 48(a0)
+    sd s5, 56(a0)
+    sd s6, 64(a0)
+    sd s7, 72(a0)
+    sd s8, 80(a0)
+    sd s9, 88(a0)
+    sd s10, 96(a0)
+    sd s11, 104(a0)
+    sd zero, 112(a0)
+    mv a0, a1
+    j __setcontext
+
+    .globl __ucontext_trampoline
+__ucontext_trampoline:
+    mv a0, s0
+    mv a1, s1
+    mv a2, s2
+    j {start}
+    ",
+    start = sym ucontext_start,
+);
+
+extern "C" {
+    /// 将当前上下文保存至`ctx`。
+    ///
+    /// 结果：
+    /// * 0 => 直接返回
+    /// * 非0 => 经由[`setcontext`]恢复而返回
+    ///
+    /// # Safety
+    ///
+    /// 本函数会“返回两次”，恢复时调用者的栈帧必须仍然有效，
+    /// 且保存点之后修改过的局部变量的值是不确定的。
+    pub fn getcontext(ctx: *mut UContext) -> usize;
+
+    /// 设置非局部跳转的保存点，直接返回时结果为0，
+    /// 经由[`longjmp`]返回时结果为其传入的值。
+    ///
+    /// # Safety
+    ///
+    /// 同[`getcontext`]。
+    pub fn setjmp(buf: *mut JmpBuf) -> usize;
+
+    fn __setcontext(ctx: *const UContext) -> !;
+    fn __swapcontext(old: *mut UContext, new: *const UContext) -> usize;
+    fn __ucontext_trampoline();
+}
+
+/// 由[`makecontext`]构造的上下文首次恢复时的落脚点
+extern "C" fn ucontext_start(entry: extern "C" fn(usize), arg: usize, link: *const UContext) -> ! {
+    entry(arg);
+
+    if link.is_null() {
+        exit(0)
+    } else {
+        unsafe { __setcontext(link) }
+    }
+}
+
+/// 恢复`ctx`所保存的上下文
+///
+/// # Safety
+///
+/// `ctx`须由[`getcontext`]、[`swapcontext`]或[`makecontext`]初始化，
+/// 且其引用的栈仍然有效。
+pub unsafe fn setcontext(ctx: &UContext) -> ! {
+    __setcontext(ctx)
+}
+
+/// 将当前上下文保存至`old`，再恢复`new`。`old`被恢复时，本函数返回。
+///
+/// # Safety
+///
+/// 同[`setcontext`]。
+pub unsafe fn swapcontext(old: &mut UContext, new: &UContext) {
+    __swapcontext(old, new);
+}
+
+/// 构造一个在`stack`上执行`entry(arg)`的上下文。
+/// `entry`返回后恢复`link`，若其为`None`则退出进程。
+///
+/// # Safety
+///
+/// `stack`与`link`须在该上下文执行期间保持有效。
+pub unsafe fn makecontext(
+    ctx: &mut UContext,
+    stack: &mut [u8],
+    entry: extern "C" fn(usize),
+    arg: usize,
+    link: Option<&UContext>,
+) {
+    let stack_top = stack.as_mut_ptr_range().end as usize & !0xf;
+
+    *ctx = UContext::default();
+    ctx.ra = __ucontext_trampoline as usize;
+    ctx.sp = stack_top;
+    ctx.s[0] = entry as usize;
+    ctx.s[1] = arg;
+    ctx.s[2] = link.map_or(ptr::null(), ptr::from_ref) as usize;
+}
+
+/// 非局部跳转的保存点
+pub type JmpBuf = UContext;
+
+/// 跳转至`buf`处，令对应的[`setjmp`]返回`val`，`val`为0时视作1
+///
+/// # Safety
+///
+/// `buf`须由[`setjmp`]设置，且设置它的函数尚未返回。
+pub unsafe fn longjmp(buf: &mut JmpBuf, val: usize) -> ! {
+    buf.a0 = val.max(1);
+    __setcontext(buf)
+}